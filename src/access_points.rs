@@ -0,0 +1,94 @@
+use aws_sdk_s3::config::endpoint::{
+    DefaultResolver, Endpoint, EndpointFuture, Params, ResolveEndpoint,
+};
+use aws_sdk_s3::error::BoxError;
+use aws_sdk_sts as sts;
+use tokio::sync::OnceCell;
+
+/// Whether `bucket_name` is the alias of a Multi-Region Access Point, which the
+/// endpoint rules only accept as part of the access point's ARN.
+pub fn is_mrap_alias(bucket_name: &str) -> bool {
+    bucket_name.ends_with(".mrap") && !bucket_name.starts_with("arn:")
+}
+
+/// Resolves endpoints with the SDK's rules, after expanding Multi-Region Access
+/// Point aliases into their ARN. The ARN names the account owning the access
+/// point, taken to be the one of the credentials, which STS is asked for once.
+#[derive(Debug)]
+pub struct AccessPointResolver {
+    rules: DefaultResolver,
+    sts: sts::Client,
+    account_id: OnceCell<String>,
+}
+
+impl AccessPointResolver {
+    pub fn new(sts: sts::Client) -> Self {
+        Self {
+            rules: DefaultResolver::new(),
+            sts,
+            account_id: OnceCell::new(),
+        }
+    }
+
+    async fn account_id(&self) -> Result<&str, BoxError> {
+        let account_id = self
+            .account_id
+            .get_or_try_init(|| async {
+                let identity = self.sts.get_caller_identity().send().await?;
+                identity.account().map(str::to_string).ok_or_else(|| {
+                    BoxError::from("STS did not report the account of the credentials")
+                })
+            })
+            .await?;
+        Ok(account_id)
+    }
+
+    async fn resolve_alias(&self, params: &Params, alias: &str) -> Result<Endpoint, BoxError> {
+        let arn = format!(
+            "arn:{}:s3::{}:accesspoint/{}",
+            partition(params.region().unwrap_or_default()),
+            self.account_id().await?,
+            alias
+        );
+        let params = Params::builder()
+            .bucket(arn)
+            .set_region(params.region().map(str::to_string))
+            .set_use_fips(params.use_fips())
+            .set_use_dual_stack(params.use_dual_stack())
+            .set_endpoint(params.endpoint().map(str::to_string))
+            .set_force_path_style(params.force_path_style())
+            .set_accelerate(params.accelerate())
+            .set_use_global_endpoint(params.use_global_endpoint())
+            .set_use_object_lambda_endpoint(params.use_object_lambda_endpoint())
+            .set_key(params.key().map(str::to_string))
+            .set_prefix(params.prefix().map(str::to_string))
+            .set_copy_source(params.copy_source().map(str::to_string))
+            .set_disable_access_points(params.disable_access_points())
+            .set_disable_multi_region_access_points(params.disable_multi_region_access_points())
+            .set_use_arn_region(params.use_arn_region())
+            .set_use_s3_express_control_endpoint(params.use_s3_express_control_endpoint())
+            .set_disable_s3_express_session_auth(params.disable_s3_express_session_auth())
+            .build()?;
+        ResolveEndpoint::resolve_endpoint(&self.rules, &params).await
+    }
+}
+
+impl ResolveEndpoint for AccessPointResolver {
+    fn resolve_endpoint<'a>(&'a self, params: &'a Params) -> EndpointFuture<'a> {
+        match params.bucket().filter(|bucket| is_mrap_alias(bucket)) {
+            Some(alias) => EndpointFuture::new(self.resolve_alias(params, alias)),
+            None => ResolveEndpoint::resolve_endpoint(&self.rules, params),
+        }
+    }
+}
+
+/// The AWS partition of `region`, which an ARN names.
+fn partition(region: &str) -> &'static str {
+    if region.starts_with("cn-") {
+        "aws-cn"
+    } else if region.starts_with("us-gov-") {
+        "aws-us-gov"
+    } else {
+        "aws"
+    }
+}
@@ -0,0 +1,120 @@
+use std::cell::OnceCell;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::{Duration, Instant};
+
+use pyo3::prelude::*;
+use pyo3_async_runtimes::generic::{self, ContextExt, Runtime};
+use pyo3_async_runtimes::TaskLocals;
+use tokio::runtime::Handle;
+
+use crate::runtime::ProcessRuntime;
+
+/// Upper bound on how long interpreter shutdown waits for pending completions.
+const SHUTDOWN_GRACE_PERIOD: Duration = Duration::from_secs(2);
+
+/// Number of bridge tasks that may still touch the interpreter.
+static IN_FLIGHT: AtomicUsize = AtomicUsize::new(0);
+
+tokio::task_local! {
+    static TASK_LOCALS: OnceCell<TaskLocals>;
+}
+
+struct InFlightGuard;
+
+impl InFlightGuard {
+    fn new() -> Self {
+        IN_FLIGHT.fetch_add(1, Ordering::SeqCst);
+        Self
+    }
+}
+
+impl Drop for InFlightGuard {
+    fn drop(&mut self) {
+        IN_FLIGHT.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+/// Tokio runtime adapter that tracks every spawned bridge task.
+///
+/// pyo3-async-runtimes resolves the asyncio future from a Tokio worker while holding
+/// the GIL, and the interpreter may switch threads halfway through. If the main thread
+/// finalizes in that window the worker crashes the process, so shutdown waits for
+/// the tracked tasks to drain first.
+struct BridgeRuntime;
+
+impl Runtime for BridgeRuntime {
+    type JoinError = tokio::task::JoinError;
+    type JoinHandle = tokio::task::JoinHandle<()>;
+
+    fn spawn<F>(fut: F) -> Self::JoinHandle
+    where
+        F: Future<Output = ()> + Send + 'static,
+    {
+        let guard = InFlightGuard::new();
+        let fut = async move {
+            let _guard = guard;
+            fut.await;
+        };
+        // Tasks go to the runtime `future_into_py` entered, and the tasks they
+        // spawn to the runtime they run on.
+        match Handle::try_current() {
+            Ok(handle) => handle.spawn(fut),
+            Err(_) => pyo3_async_runtimes::tokio::get_runtime().spawn(fut),
+        }
+    }
+}
+
+impl ContextExt for BridgeRuntime {
+    fn scope<F, R>(locals: TaskLocals, fut: F) -> Pin<Box<dyn Future<Output = R> + Send>>
+    where
+        F: Future<Output = R> + Send + 'static,
+    {
+        let cell = OnceCell::new();
+        let _ = cell.set(locals);
+
+        Box::pin(TASK_LOCALS.scope(cell, fut))
+    }
+
+    fn get_task_locals() -> Option<TaskLocals> {
+        TASK_LOCALS
+            .try_with(|cell| {
+                cell.get()
+                    .map(|locals| Python::with_gil(|py| locals.clone_ref(py)))
+            })
+            .unwrap_or_default()
+    }
+}
+
+/// Converts a Rust future into a Python awaitable running on `runtime`, the
+/// runtime of the instance it was called on.
+///
+/// Fails like blocking calls do in a process forked after `runtime` was built,
+/// whose worker threads would never run the future; `reinitialize()` builds a
+/// runtime the awaitables of the child run on.
+pub fn future_into_py<'py, F, T>(
+    py: Python<'py>,
+    runtime: &ProcessRuntime,
+    fut: F,
+) -> PyResult<Bound<'py, PyAny>>
+where
+    F: Future<Output = PyResult<T>> + Send + 'static,
+    T: for<'a> IntoPyObject<'a>,
+{
+    runtime.check_process()?;
+    let _entered = runtime.enter();
+    generic::future_into_py::<BridgeRuntime, F, T>(py, fut)
+}
+
+/// Registered with `atexit`: waits for pending awaitable completions to finish
+/// touching the interpreter before it finalizes.
+#[pyfunction]
+pub fn drain_pending_tasks(py: Python<'_>) {
+    py.allow_threads(|| {
+        let started = Instant::now();
+        while IN_FLIGHT.load(Ordering::SeqCst) > 0 && started.elapsed() < SHUTDOWN_GRACE_PERIOD {
+            std::thread::sleep(Duration::from_millis(1));
+        }
+    });
+}
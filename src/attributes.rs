@@ -0,0 +1,373 @@
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::fs::Metadata;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use aws_sdk_s3::primitives::DateTime;
+use pyo3::exceptions::PyValueError;
+use pyo3::types::PyAnyMethods;
+use pyo3::{Bound, FromPyObject, PyAny, PyResult};
+use tokio::fs::OpenOptions;
+
+use crate::error::TransferError;
+use crate::settings::TransferSettings;
+use crate::upload_options::UploadOptions;
+
+/// User metadata key under which uploads with `preserve_mtime` record the
+/// modification time of their file, in seconds since the Unix epoch, as s3fs
+/// and other tools do.
+pub const MTIME_KEY: &str = "mtime";
+/// User metadata key under which uploads record the `st_mode` of their file,
+/// in decimal, as s3fs does: its permission bits with `preserve_permissions`,
+/// and the link type for symbolic links stored with `symlinks="preserve"`.
+pub const MODE_KEY: &str = "mode";
+
+const FILE_TYPE_BITS: u32 = 0o170000;
+const REGULAR_FILE: u32 = 0o100000;
+const SYMLINK: u32 = 0o120000;
+/// Mode bits recorded and restored: read, write and execute. As with tar
+/// without `-p`, setuid, setgid and sticky bits aren't taken from metadata
+/// anyone able to upload could have set.
+const PERMISSION_BITS: u32 = 0o777;
+
+/// What transfers do with symbolic links: an upload finding one at a local
+/// path, or a download getting an object stored from one.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum SymlinkPolicy {
+    /// Uploads send the file the link points to; downloads write the object
+    /// as a file, the target of a stored link as its contents.
+    #[default]
+    Follow,
+    /// Links are left out and reported as skipped.
+    Skip,
+    /// Uploads store the link as an object holding its target; downloads
+    /// recreate the link.
+    Preserve,
+}
+
+impl SymlinkPolicy {
+    /// The name the policy is selected by.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Follow => "follow",
+            Self::Skip => "skip",
+            Self::Preserve => "preserve",
+        }
+    }
+}
+
+impl<'py> FromPyObject<'py> for SymlinkPolicy {
+    fn extract_bound(value: &Bound<'py, PyAny>) -> PyResult<Self> {
+        match value.extract::<String>()?.as_str() {
+            "follow" => Ok(Self::Follow),
+            "skip" => Ok(Self::Skip),
+            "preserve" => Ok(Self::Preserve),
+            other => Err(PyValueError::new_err(format!(
+                "Invalid symlinks policy '{}', expected 'follow', 'skip' or 'preserve'",
+                other
+            ))),
+        }
+    }
+}
+
+/// What a downloaded object tells about the file it was uploaded from.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct FileAttributes {
+    /// The mtime its uploader recorded, or else its LastModified.
+    mtime: Option<SystemTime>,
+    mode: Option<u32>,
+}
+
+impl FileAttributes {
+    pub fn of(
+        metadata: Option<&HashMap<String, String>>,
+        last_modified: Option<&DateTime>,
+    ) -> Self {
+        let recorded = |key| metadata.and_then(|metadata| metadata.get(key));
+        Self {
+            mtime: recorded(MTIME_KEY)
+                .map(String::as_str)
+                .and_then(parse_seconds)
+                .or_else(|| {
+                    last_modified
+                        .and_then(|time| Duration::try_from_secs_f64(time.as_secs_f64()).ok())
+                })
+                .map(|since_epoch| UNIX_EPOCH + since_epoch),
+            mode: recorded(MODE_KEY).and_then(|mode| mode.parse().ok()),
+        }
+    }
+
+    fn is_symlink(&self) -> bool {
+        self.mode
+            .is_some_and(|mode| mode & FILE_TYPE_BITS == SYMLINK)
+    }
+
+    /// Gives the file just downloaded to `local_path` the attributes `settings`
+    /// preserve. An object stored from a link becomes that link again with
+    /// `symlinks="preserve"`, unless it would lead out of the destination of
+    /// the batch; with `"skip"` its file is removed and `false` returned.
+    pub async fn apply(
+        &self,
+        local_path: &str,
+        settings: &TransferSettings,
+    ) -> Result<bool, TransferError> {
+        if self.is_symlink() {
+            match settings.symlinks {
+                SymlinkPolicy::Follow => return Ok(true),
+                SymlinkPolicy::Skip => {
+                    let _ = tokio::fs::remove_file(local_path).await;
+                    return Ok(false);
+                }
+                SymlinkPolicy::Preserve => {
+                    restore_symlink(local_path, settings.destination.as_deref()).await?;
+                    return Ok(true);
+                }
+            }
+        }
+        if let Some(mtime) = self.mtime.filter(|_| settings.preserve_mtime) {
+            set_mtime(local_path, mtime).await?;
+        }
+        // Set last, as read-only permissions would keep the mtime from being set.
+        if let Some(mode) = self.mode.filter(|_| settings.preserve_permissions) {
+            set_permissions(local_path, mode & PERMISSION_BITS).await?;
+        }
+        Ok(true)
+    }
+}
+
+/// Parses seconds as `file_options` records them, to the nanosecond, which
+/// a float can't hold.
+fn parse_seconds(value: &str) -> Option<Duration> {
+    let (seconds, fraction) = value.split_once('.').unwrap_or((value, ""));
+    if !fraction.bytes().all(|byte| byte.is_ascii_digit()) {
+        return None;
+    }
+    let nanos = format!("{:0<9}", &fraction[..fraction.len().min(9)]);
+    Some(Duration::new(seconds.parse().ok()?, nanos.parse().ok()?))
+}
+
+/// Sets the modification time of the file at `local_path`.
+async fn set_mtime(local_path: &str, mtime: SystemTime) -> Result<(), TransferError> {
+    let failed = |e: std::io::Error| {
+        TransferError::new(format!(
+            "Failed to set the modification time of '{}': {}",
+            local_path, e
+        ))
+    };
+    let file = OpenOptions::new()
+        .write(true)
+        .open(local_path)
+        .await
+        .map_err(failed)?;
+    file.into_std().await.set_modified(mtime).map_err(failed)
+}
+
+#[cfg(unix)]
+async fn set_permissions(local_path: &str, bits: u32) -> Result<(), TransferError> {
+    use std::os::unix::fs::PermissionsExt;
+
+    tokio::fs::set_permissions(local_path, std::fs::Permissions::from_mode(bits))
+        .await
+        .map_err(|e| {
+            TransferError::new(format!(
+                "Failed to set the permissions of '{}': {}",
+                local_path, e
+            ))
+        })
+}
+
+// Other platforms have no mode bits to restore.
+#[cfg(not(unix))]
+async fn set_permissions(_local_path: &str, _bits: u32) -> Result<(), TransferError> {
+    Ok(())
+}
+
+/// Replaces the file at `local_path`, which holds the target of a link, with
+/// the link.
+///
+/// Targets are untrusted input: an absolute one, or one leading out of
+/// `destination` (out of the directory of the link when there's none), would
+/// let later downloads be written through the link to anywhere. Such links
+/// aren't created, and their file is removed.
+#[cfg(unix)]
+async fn restore_symlink(
+    local_path: &str,
+    destination: Option<&Path>,
+) -> Result<(), TransferError> {
+    use std::ffi::OsString;
+    use std::os::unix::ffi::OsStringExt;
+
+    let failed = |e: std::io::Error| {
+        TransferError::new(format!("Failed to restore symlink '{}': {}", local_path, e))
+    };
+    let target = PathBuf::from(OsString::from_vec(
+        tokio::fs::read(local_path).await.map_err(failed)?,
+    ));
+    let directory = Path::new(local_path).parent().unwrap_or(Path::new(""));
+    let destination = destination.unwrap_or(directory);
+    if !leads_inside(directory, &target, destination) {
+        let _ = tokio::fs::remove_file(local_path).await;
+        return Err(TransferError::new(format!(
+            "Refused to restore symlink '{}' to '{}', which leads out of '{}'",
+            local_path,
+            target.display(),
+            destination.display()
+        )));
+    }
+    tokio::fs::remove_file(local_path).await.map_err(failed)?;
+    tokio::fs::symlink(target, local_path).await.map_err(failed)
+}
+
+// Creating links needs privileges elsewhere; the target is kept as a file.
+#[cfg(not(unix))]
+async fn restore_symlink(
+    _local_path: &str,
+    _destination: Option<&Path>,
+) -> Result<(), TransferError> {
+    Ok(())
+}
+
+/// Whether `target`, read from a link in `directory`, names a path inside
+/// `destination`, going by its components alone. Links restored below
+/// `destination` only ever lead further down, so those among the components
+/// can't take it anywhere else.
+#[cfg(unix)]
+fn leads_inside(directory: &Path, target: &Path, destination: &Path) -> bool {
+    use std::path::Component;
+
+    let Ok(below) = directory.strip_prefix(destination) else {
+        return false;
+    };
+    let mut depth = below.components().count();
+    for component in target.components() {
+        match component {
+            Component::Normal(_) => depth += 1,
+            Component::CurDir => {}
+            Component::ParentDir if depth > 0 => depth -= 1,
+            Component::ParentDir | Component::RootDir | Component::Prefix(_) => return false,
+        }
+    }
+    true
+}
+
+/// Fails when a batch restoring links would download to `local_path` through
+/// one: a directory between the destination of the batch and the file that is
+/// a link, which may have come from an object and lead anywhere.
+pub async fn check_destination(
+    local_path: &str,
+    settings: &TransferSettings,
+) -> Result<(), TransferError> {
+    let Some(destination) = settings
+        .destination
+        .as_deref()
+        .filter(|_| settings.symlinks == SymlinkPolicy::Preserve)
+    else {
+        return Ok(());
+    };
+    let mut ancestors = Path::new(local_path).ancestors().skip(1);
+    while let Some(directory) = ancestors
+        .next()
+        .filter(|directory| directory.starts_with(destination) && *directory != destination)
+    {
+        let is_link = tokio::fs::symlink_metadata(directory)
+            .await
+            .is_ok_and(|metadata| metadata.is_symlink());
+        if is_link {
+            return Err(TransferError::new(format!(
+                "Refused to write '{}' through the symlink '{}'",
+                local_path,
+                directory.display()
+            )));
+        }
+    }
+    Ok(())
+}
+
+/// The target of the link at `local_path`, as the bytes an upload stores, or
+/// `None` when it isn't a link.
+pub async fn symlink_target(local_path: &str) -> Result<Option<Vec<u8>>, TransferError> {
+    let failed = |e: std::io::Error| {
+        TransferError::new(format!("Failed to read file '{}': {}", local_path, e))
+    };
+    let metadata = tokio::fs::symlink_metadata(local_path)
+        .await
+        .map_err(failed)?;
+    if !metadata.is_symlink() {
+        return Ok(None);
+    }
+    let target = tokio::fs::read_link(local_path).await.map_err(failed)?;
+    Ok(Some(target_bytes(target)))
+}
+
+#[cfg(unix)]
+fn target_bytes(target: PathBuf) -> Vec<u8> {
+    use std::os::unix::ffi::OsStringExt;
+
+    target.into_os_string().into_vec()
+}
+
+#[cfg(not(unix))]
+fn target_bytes(target: PathBuf) -> Vec<u8> {
+    target.to_string_lossy().into_owned().into_bytes()
+}
+
+/// `options` with the mode of a link added to their metadata.
+pub fn symlink_options(options: &UploadOptions) -> UploadOptions {
+    let mut metadata = options.metadata.clone().unwrap_or_default();
+    metadata.insert(MODE_KEY.to_string(), (SYMLINK | 0o777).to_string());
+    UploadOptions {
+        metadata: Some(metadata),
+        ..options.clone()
+    }
+}
+
+/// `options` with the attributes of the file at `local_path` that `settings`
+/// preserve added to their metadata.
+pub async fn file_options<'a>(
+    options: &'a UploadOptions,
+    local_path: &str,
+    settings: &TransferSettings,
+) -> Result<Cow<'a, UploadOptions>, TransferError> {
+    if !settings.preserve_mtime && !settings.preserve_permissions {
+        return Ok(Cow::Borrowed(options));
+    }
+    let file = tokio::fs::metadata(local_path)
+        .await
+        .map_err(|e| TransferError::new(format!("Failed to read file '{}': {}", local_path, e)))?;
+
+    let mut metadata = options.metadata.clone().unwrap_or_default();
+    if settings.preserve_mtime {
+        let modified = file.modified().map_err(|e| {
+            TransferError::new(format!("Failed to read file '{}': {}", local_path, e))
+        })?;
+        let since_epoch = modified.duration_since(UNIX_EPOCH).unwrap_or_default();
+        metadata.insert(
+            MTIME_KEY.to_string(),
+            format!(
+                "{}.{:09}",
+                since_epoch.as_secs(),
+                since_epoch.subsec_nanos()
+            ),
+        );
+    }
+    if let Some(bits) = permission_bits(&file).filter(|_| settings.preserve_permissions) {
+        metadata.insert(MODE_KEY.to_string(), (REGULAR_FILE | bits).to_string());
+    }
+    Ok(Cow::Owned(UploadOptions {
+        metadata: Some(metadata),
+        ..options.clone()
+    }))
+}
+
+#[cfg(unix)]
+fn permission_bits(file: &Metadata) -> Option<u32> {
+    use std::os::unix::fs::PermissionsExt;
+
+    Some(file.permissions().mode() & PERMISSION_BITS)
+}
+
+#[cfg(not(unix))]
+fn permission_bits(_file: &Metadata) -> Option<u32> {
+    None
+}
@@ -0,0 +1,223 @@
+use std::fs::{File, OpenOptions};
+use std::io::{BufRead, BufReader, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, Weak};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use aws_smithy_types::date_time::{DateTime, Format};
+use log::warn;
+use pyo3::exceptions::PyValueError;
+use pyo3::{pyfunction, PyResult};
+use ring::digest::{digest, SHA256};
+use serde::{Deserialize, Serialize};
+
+use crate::checksums::file_sha256;
+use crate::conditions::is_not_modified;
+use crate::error::TransferError;
+use crate::exceptions::RobinzhonError;
+use crate::integrity::hex;
+use crate::results::{Completed, TransferKind};
+
+/// `prev` of the first entry of a log, which follows no other.
+const FIRST_PREV: &str = "0000000000000000000000000000000000000000000000000000000000000000";
+
+/// Bytes read from the end of an existing log to find its last entry.
+const TAIL_SIZE: u64 = 1024 * 1024;
+
+/// Logs opened in this process, so that every client appending to a file
+/// shares one writer and its entries chain in order.
+static OPEN_LOGS: Mutex<Vec<Weak<AuditLog>>> = Mutex::new(Vec::new());
+
+/// One line of an audit log.
+#[derive(Debug, Serialize, Deserialize)]
+struct AuditEntry {
+    /// UTC time the transfer ended, as RFC 3339.
+    timestamp: String,
+    operation: TransferKind,
+    bucket: String,
+    key: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    version_id: Option<String>,
+    local_path: String,
+    /// `"success"`, `"skipped"` or `"failed"`.
+    result: String,
+    /// Size of the local file, for transferred items.
+    bytes: Option<u64>,
+    /// Hex SHA-256 of the local file, for transferred items.
+    sha256: Option<String>,
+    /// ETag S3 returned for an uploaded item.
+    etag: Option<String>,
+    error: Option<String>,
+    /// Hex SHA-256 of the previous line, chaining every entry to the ones
+    /// before it.
+    prev: String,
+}
+
+/// Where the last entry of a log stands.
+#[derive(Debug)]
+struct LogTail {
+    file: File,
+    /// Hex SHA-256 of the last line.
+    last_hash: String,
+}
+
+/// An append-only JSON Lines record of every file downloaded or uploaded.
+///
+/// Each line holds the SHA-256 of the line before it, so editing, inserting or
+/// removing an entry breaks the chain from there on; `verify_audit_log` finds
+/// where.
+#[derive(Debug)]
+pub struct AuditLog {
+    path: PathBuf,
+    tail: Mutex<LogTail>,
+}
+
+impl AuditLog {
+    /// The log at `path`, created if missing and appended to otherwise; the
+    /// same one for every client of the process naming that file.
+    pub fn open(path: &str) -> PyResult<Arc<Self>> {
+        let open_error = |e: std::io::Error| {
+            RobinzhonError::new_err(format!("Failed to open audit log '{}': {}", path, e))
+        };
+        let mut file = OpenOptions::new()
+            .read(true)
+            .append(true)
+            .create(true)
+            .open(path)
+            .map_err(open_error)?;
+        let path = std::fs::canonicalize(path).map_err(open_error)?;
+        let mut open_logs = OPEN_LOGS.lock().unwrap();
+        open_logs.retain(|log| log.strong_count() > 0);
+        if let Some(log) = open_logs
+            .iter()
+            .filter_map(Weak::upgrade)
+            .find(|log| log.path == path)
+        {
+            return Ok(log);
+        }
+
+        let last_hash = last_line(&mut file)
+            .map_err(open_error)?
+            .map_or_else(|| FIRST_PREV.to_string(), |line| line_hash(&line));
+        let log = Arc::new(Self {
+            path,
+            tail: Mutex::new(LogTail { file, last_hash }),
+        });
+        open_logs.push(Arc::downgrade(&log));
+        Ok(log)
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Appends how transferring `object_key` of `bucket_name` to or from
+    /// `local_path` ended. The file of a transferred item is read back for its
+    /// SHA-256. A log that can't be written is warned about; the transfer it
+    /// records has happened either way.
+    #[allow(clippy::too_many_arguments)]
+    pub async fn record(
+        &self,
+        operation: TransferKind,
+        bucket_name: &str,
+        object_key: &str,
+        version_id: Option<&str>,
+        local_path: &str,
+        outcome: &Result<Completed, TransferError>,
+    ) {
+        let (result, etag, error) = match outcome {
+            Ok(Completed::Skipped(_)) => ("skipped", None, None),
+            Ok(Completed::Uploaded { stored, .. }) => ("success", stored.etag.clone(), None),
+            Ok(_) => ("success", None, None),
+            Err(error) if is_not_modified(error) => ("skipped", None, None),
+            Err(error) => ("failed", None, Some(error.message.clone())),
+        };
+        let (bytes, sha256) = if result == "success" {
+            let bytes = tokio::fs::metadata(local_path)
+                .await
+                .ok()
+                .map(|metadata| metadata.len());
+            (bytes, file_sha256(Path::new(local_path)).await.ok())
+        } else {
+            (None, None)
+        };
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default();
+        let timestamp = DateTime::from_secs_f64(now.as_secs_f64())
+            .fmt(Format::DateTime)
+            .unwrap_or_default();
+
+        let mut tail = self.tail.lock().unwrap();
+        let entry = AuditEntry {
+            timestamp,
+            operation,
+            bucket: bucket_name.to_string(),
+            key: object_key.to_string(),
+            version_id: version_id.map(str::to_string),
+            local_path: local_path.to_string(),
+            result: result.to_string(),
+            bytes,
+            sha256,
+            etag,
+            error,
+            prev: tail.last_hash.clone(),
+        };
+        let line = serde_json::to_string(&entry).expect("audit entries serialize");
+        match writeln!(tail.file, "{}", line) {
+            Ok(()) => tail.last_hash = line_hash(&line),
+            Err(e) => warn!("Failed to write audit log '{}': {}", self.path.display(), e),
+        }
+    }
+}
+
+fn line_hash(line: &str) -> String {
+    hex(digest(&SHA256, line.as_bytes()).as_ref())
+}
+
+/// The last line of `file`, found in its last `TAIL_SIZE` bytes.
+fn last_line(file: &mut File) -> std::io::Result<Option<String>> {
+    let length = file.metadata()?.len();
+    file.seek(SeekFrom::Start(length.saturating_sub(TAIL_SIZE)))?;
+    // The tail may start inside a character, but never inside the last line.
+    let mut tail = Vec::new();
+    file.read_to_end(&mut tail)?;
+    Ok(tail
+        .split(|byte| *byte == b'\n')
+        .rev()
+        .find(|line| !line.trim_ascii().is_empty())
+        .map(|line| String::from_utf8_lossy(line).into_owned()))
+}
+
+/// Checks that no entry of the audit log at `path` was edited, inserted or
+/// removed, and returns how many it holds. Entries cut from the end of the
+/// log leave no trace in it, so keep the count, or the last line, elsewhere to
+/// tell.
+#[pyfunction]
+pub fn verify_audit_log(path: &str) -> PyResult<usize> {
+    let file = File::open(path).map_err(|e| {
+        RobinzhonError::new_err(format!("Failed to read audit log '{}': {}", path, e))
+    })?;
+    let mut prev = FIRST_PREV.to_string();
+    let mut entries = 0;
+    for (index, line) in BufReader::new(file).lines().enumerate() {
+        let line = line.map_err(|e| {
+            RobinzhonError::new_err(format!("Failed to read audit log '{}': {}", path, e))
+        })?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let chained =
+            serde_json::from_str::<AuditEntry>(&line).is_ok_and(|entry| entry.prev == prev);
+        if !chained {
+            return Err(PyValueError::new_err(format!(
+                "Audit log '{}' was altered at or before line {}",
+                path,
+                index + 1
+            )));
+        }
+        prev = line_hash(&line);
+        entries += 1;
+    }
+    Ok(entries)
+}
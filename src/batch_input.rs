@@ -0,0 +1,124 @@
+use std::marker::PhantomData;
+
+use futures::future;
+use futures::stream::{self, Stream, StreamExt};
+use pyo3::exceptions::PyTypeError;
+use pyo3::types::{PyAnyMethods, PyBytes, PyIterator, PyString, PyTypeMethods};
+use pyo3::{Bound, FromPyObject, Py, PyAny, PyErr, PyResult, Python};
+
+use crate::exceptions::RobinzhonError;
+
+/// Items pulled from Python per trip to the blocking pool.
+const CHUNK_LEN: usize = 1024;
+
+/// The items of a batch method: any Python iterable, generators included.
+///
+/// Items are pulled lazily, a chunk at a time, as the batch has room for more
+/// transfers, so a generator of millions of keys is never held in memory at once.
+pub struct BatchInput<T> {
+    items: Py<PyIterator>,
+    /// `len()` of the iterable, when it has one.
+    len: Option<usize>,
+    item: PhantomData<fn() -> T>,
+}
+
+impl<'py, T> FromPyObject<'py> for BatchInput<T> {
+    fn extract_bound(value: &Bound<'py, PyAny>) -> PyResult<Self> {
+        // Strings are iterable too, but a key passed where a list of keys was
+        // expected is a mistake, not a batch of one-character keys.
+        if value.is_instance_of::<PyString>() || value.is_instance_of::<PyBytes>() {
+            return Err(PyTypeError::new_err(format!(
+                "expected an iterable of items, not '{}'",
+                value.get_type().name()?
+            )));
+        }
+
+        Ok(Self {
+            items: value.try_iter()?.unbind(),
+            len: value.len().ok(),
+            item: PhantomData,
+        })
+    }
+}
+
+impl<T> BatchInput<T>
+where
+    T: for<'py> FromPyObject<'py> + Send + 'static,
+{
+    pub fn len(&self) -> Option<usize> {
+        self.len
+    }
+
+    /// Streams the items. An item that fails to convert, or an exception raised
+    /// by the iterable, ends the stream with that error.
+    pub fn into_stream(self) -> impl Stream<Item = PyResult<T>> + Send {
+        stream::unfold(Some(self.items), |items| async move {
+            let items = items?;
+            let pulled = tokio::task::spawn_blocking(move || {
+                Python::with_gil(|py| {
+                    let chunk = Self::pull_chunk(items.bind(py));
+                    (chunk, items)
+                })
+            })
+            .await;
+
+            match pulled {
+                Ok(((chunk, None), _)) if chunk.is_empty() => None,
+                Ok(((chunk, None), items)) => {
+                    let next = (chunk.len() == CHUNK_LEN).then_some(items);
+                    Some((chunk.into_iter().map(Ok).collect::<Vec<_>>(), next))
+                }
+                Ok(((chunk, Some(error)), _)) => {
+                    let items = chunk.into_iter().map(Ok).chain([Err(error)]);
+                    Some((items.collect(), None))
+                }
+                Err(error) => Some((
+                    vec![Err(RobinzhonError::new_err(format!(
+                        "Failed to read batch items: {}",
+                        error
+                    )))],
+                    None,
+                )),
+            }
+        })
+        .flat_map(stream::iter)
+    }
+
+    /// Pulls up to `CHUNK_LEN` items, along with the error that cut the chunk
+    /// short, if any.
+    fn pull_chunk(items: &Bound<'_, PyIterator>) -> (Vec<T>, Option<PyErr>) {
+        let mut chunk = Vec::with_capacity(CHUNK_LEN);
+        for item in items.clone().take(CHUNK_LEN) {
+            match item.and_then(|item| item.extract()) {
+                Ok(item) => chunk.push(item),
+                Err(error) => return (chunk, Some(error)),
+            }
+        }
+        (chunk, None)
+    }
+}
+
+/// Ends `items` at its first error, which is left in `error` for the caller to
+/// raise once the transfers already started are done.
+pub fn until_error<'a, T: Send + 'a>(
+    items: impl Stream<Item = PyResult<T>> + Send + 'a,
+    error: &'a mut Option<PyErr>,
+) -> impl Stream<Item = T> + Send + 'a {
+    items.scan(error, |error, item| {
+        future::ready(match item {
+            Ok(item) => Some(item),
+            Err(e) => {
+                **error = Some(e);
+                None
+            }
+        })
+    })
+}
+
+/// Streams items already in memory, for batches built on the Rust side.
+pub fn ready_items<T>(items: Vec<T>) -> impl Stream<Item = PyResult<T>> + Send
+where
+    T: Send,
+{
+    stream::iter(items.into_iter().map(Ok))
+}
@@ -0,0 +1,118 @@
+use std::future::Future;
+use std::sync::{Arc, Mutex};
+
+use pyo3::exceptions::PyTypeError;
+use pyo3::types::PyTuple;
+use pyo3::{pyclass, pymethods, Bound, PyRef, PyRefMut, PyResult, Python};
+use tokio::sync::mpsc;
+use tokio::task::JoinHandle;
+
+use crate::exceptions::RobinzhonError;
+use crate::results::{ResultItem, Results, RustOperationResult};
+use crate::runtime::{block_on_interruptible, ProcessRuntime};
+
+/// Iterator over a batch running in the background, yielding the `ResultItem`
+/// of every item as it finishes, in the order they finish.
+///
+/// Once exhausted, `results` holds the `Results` of the whole batch; an error
+/// stopping the batch is raised after the items that finished before it.
+#[pyclass(module = "robinzhon")]
+pub struct BatchIterator {
+    runtime: Arc<ProcessRuntime>,
+    items: Option<mpsc::UnboundedReceiver<ResultItem>>,
+    batch: Option<JoinHandle<PyResult<Results>>>,
+    /// Number of items, when the input of the batch has a `len()`.
+    total: Option<usize>,
+    /// `Results` of the batch, once the iterator is exhausted.
+    #[pyo3(get)]
+    results: Option<Results>,
+}
+
+impl BatchIterator {
+    /// Spawns the batch `run` builds on `runtime`, recording its items into
+    /// the result it is given.
+    pub fn spawn<F, Fut>(runtime: Arc<ProcessRuntime>, total: Option<usize>, run: F) -> Self
+    where
+        F: FnOnce(Arc<Mutex<RustOperationResult>>) -> Fut,
+        Fut: Future<Output = PyResult<Results>> + Send + 'static,
+    {
+        // Unbounded, as items are recorded under a lock that can't wait for
+        // the consumer; they are small next to the transfers they stand for.
+        let (sender, receiver) = mpsc::unbounded_channel();
+        let collected = Arc::new(Mutex::new(RustOperationResult::streaming(sender)));
+        let batch = runtime.spawn(run(collected));
+
+        Self {
+            runtime,
+            items: Some(receiver),
+            batch: Some(batch),
+            total,
+            results: None,
+        }
+    }
+}
+
+#[pymethods]
+impl BatchIterator {
+    fn __iter__(slf: PyRef<'_, Self>) -> PyRef<'_, Self> {
+        slf
+    }
+
+    fn __next__(&mut self, py: Python<'_>) -> PyResult<Option<ResultItem>> {
+        let Some(items) = self.items.as_mut() else {
+            return Ok(None);
+        };
+        if let Some(item) = block_on_interruptible(py, &self.runtime, items.recv())? {
+            return Ok(Some(item));
+        }
+
+        // Every item was recorded, so the batch is done or about to be.
+        self.items = None;
+        let Some(batch) = self.batch.take() else {
+            return Ok(None);
+        };
+        match block_on_interruptible(py, &self.runtime, batch)? {
+            Ok(Ok(results)) => {
+                self.results = Some(results);
+                Ok(None)
+            }
+            Ok(Err(error)) => Err(error),
+            Err(error) => Err(RobinzhonError::new_err(format!(
+                "Batch task failed: {}",
+                error
+            ))),
+        }
+    }
+
+    /// Number of items in the batch, for progress bars. Raises `TypeError` when
+    /// the input of the batch had no `len()`.
+    fn __len__(&self) -> PyResult<usize> {
+        self.total.ok_or_else(|| {
+            PyTypeError::new_err("the input of this batch has no len(), so neither has the batch")
+        })
+    }
+
+    /// Cancels the transfers still running; the iterator is exhausted afterwards.
+    fn close(&mut self) {
+        self.items = None;
+        if let Some(batch) = self.batch.take() {
+            batch.abort();
+        }
+    }
+
+    fn __enter__(slf: PyRefMut<'_, Self>) -> PyRefMut<'_, Self> {
+        slf
+    }
+
+    #[pyo3(signature = (*_args))]
+    fn __exit__(&mut self, _args: &Bound<'_, PyTuple>) -> bool {
+        self.close();
+        false
+    }
+}
+
+impl Drop for BatchIterator {
+    fn drop(&mut self) {
+        self.close();
+    }
+}
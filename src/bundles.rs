@@ -0,0 +1,215 @@
+use std::collections::BTreeMap;
+use std::fmt::Display;
+use std::fs::File;
+use std::io::{self, Cursor, Write};
+
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use pyo3::exceptions::PyValueError;
+use pyo3::{pyclass, pymethods, PyResult};
+use serde::{Deserialize, Serialize};
+use zip::write::SimpleFileOptions;
+use zip::{CompressionMethod, ZipWriter};
+
+use crate::error::TransferError;
+use crate::extract::ArchiveFormat;
+
+/// Files up to 1 MiB are packed by default.
+pub const DEFAULT_MAX_FILE_SIZE: u64 = 1024 * 1024;
+/// Bundles are closed once they hold 64 MiB of files by default.
+pub const DEFAULT_BUNDLE_SIZE: u64 = 64 * 1024 * 1024;
+/// Bundles are sent with a single PUT, which stores at most 5 GiB.
+const MAX_BUNDLE_SIZE: u64 = 5 * 1024 * 1024 * 1024;
+/// Name of the manifest below the prefix of the bundles.
+const MANIFEST_NAME: &str = "manifest.json";
+
+/// Packs the small files of an upload batch into archives, "bundles", stored
+/// below `prefix` next to a manifest naming the bundle of every key. Larger
+/// files are uploaded as objects of their own.
+///
+/// Bundles are built in memory, so `bundle_size` also bounds what every upload
+/// in flight holds.
+#[pyclass(frozen, module = "robinzhon")]
+#[derive(Debug, Clone)]
+pub struct ArchiveMode {
+    #[pyo3(get)]
+    pub prefix: String,
+    pub format: ArchiveFormat,
+    #[pyo3(get)]
+    pub max_file_size: u64,
+    #[pyo3(get)]
+    pub bundle_size: u64,
+}
+
+impl ArchiveMode {
+    /// Key of the `number`th bundle, counting from one.
+    pub fn bundle_key(&self, number: usize) -> String {
+        format!("{}bundle-{:05}.{}", self.prefix, number, self.format.name())
+    }
+
+    pub fn manifest_key(&self) -> String {
+        format!("{}{}", self.prefix, MANIFEST_NAME)
+    }
+
+    /// Splits `files`, as `(item, size)`, into bundles of up to `bundle_size`
+    /// bytes of files each, keeping their order.
+    pub fn group<T>(&self, files: Vec<(T, u64)>) -> Vec<Vec<T>> {
+        let mut bundles = Vec::new();
+        let mut bundle = Vec::new();
+        let mut size = 0;
+        for (item, file_size) in files {
+            if !bundle.is_empty() && size + file_size > self.bundle_size {
+                bundles.push(std::mem::take(&mut bundle));
+                size = 0;
+            }
+            size += file_size;
+            bundle.push(item);
+        }
+        if !bundle.is_empty() {
+            bundles.push(bundle);
+        }
+        bundles
+    }
+}
+
+#[pymethods]
+impl ArchiveMode {
+    #[new]
+    #[pyo3(signature = (prefix, format=ArchiveFormat::Tar, max_file_size=DEFAULT_MAX_FILE_SIZE, bundle_size=DEFAULT_BUNDLE_SIZE))]
+    fn new(
+        prefix: String,
+        format: ArchiveFormat,
+        max_file_size: u64,
+        bundle_size: u64,
+    ) -> PyResult<Self> {
+        if bundle_size == 0 || bundle_size > MAX_BUNDLE_SIZE {
+            return Err(PyValueError::new_err(
+                "bundle_size must be between 1 byte and 5 GiB",
+            ));
+        }
+        if max_file_size > bundle_size {
+            return Err(PyValueError::new_err(
+                "max_file_size must not be larger than bundle_size",
+            ));
+        }
+
+        Ok(Self {
+            prefix,
+            format,
+            max_file_size,
+            bundle_size,
+        })
+    }
+
+    #[getter]
+    fn format(&self) -> &'static str {
+        self.format.name()
+    }
+
+    /// Pickles the mode as its constructor arguments.
+    fn __getnewargs__(&self) -> (String, &'static str, u64, u64) {
+        (
+            self.prefix.clone(),
+            self.format.name(),
+            self.max_file_size,
+            self.bundle_size,
+        )
+    }
+
+    fn __repr__(&self) -> String {
+        format!(
+            "ArchiveMode(prefix={:?}, format={:?}, max_file_size={}, bundle_size={})",
+            self.prefix,
+            self.format.name(),
+            self.max_file_size,
+            self.bundle_size
+        )
+    }
+}
+
+/// The object uploaded next to the bundles of a batch: the format of the
+/// bundles and, by key, the bundle each file was packed into.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct Manifest {
+    pub format: String,
+    pub members: BTreeMap<String, String>,
+}
+
+impl Manifest {
+    /// The keys packed into every bundle, by bundle key.
+    pub fn bundles(&self) -> BTreeMap<&str, Vec<&str>> {
+        let mut bundles: BTreeMap<&str, Vec<&str>> = BTreeMap::new();
+        for (key, bundle_key) in &self.members {
+            bundles.entry(bundle_key).or_default().push(key);
+        }
+        bundles
+    }
+}
+
+/// An archive built by `pack`, and the members left out of it.
+pub struct Packed {
+    pub body: Vec<u8>,
+    /// Position in the members and error of every file that couldn't be opened.
+    pub unreadable: Vec<(usize, TransferError)>,
+}
+
+/// Packs `members`, as `(local_path, key)`, into the archive `bundle_key`, in
+/// which every file is named by its key. A file that can't be opened is left
+/// out; failing to read one once it's opened fails the whole archive.
+pub fn pack(
+    format: ArchiveFormat,
+    members: &[(&str, &str)],
+    bundle_key: &str,
+) -> Result<Packed, TransferError> {
+    let mut unreadable = Vec::new();
+    let files =
+        members
+            .iter()
+            .enumerate()
+            .filter_map(|(index, &(local_path, key))| match File::open(local_path) {
+                Ok(file) => Some((key, file)),
+                Err(e) => {
+                    let error =
+                        TransferError::new(format!("Failed to read file '{}': {}", local_path, e));
+                    unreadable.push((index, error));
+                    None
+                }
+            });
+    let failed = |error: &dyn Display| {
+        TransferError::new(format!("Failed to pack bundle '{}': {}", bundle_key, error))
+    };
+    let body = match format {
+        ArchiveFormat::Tar => pack_tar(Vec::new(), files).map_err(|e| failed(&e)),
+        ArchiveFormat::TarGz => pack_tar(GzEncoder::new(Vec::new(), Compression::default()), files)
+            .and_then(GzEncoder::finish)
+            .map_err(|e| failed(&e)),
+        ArchiveFormat::Zip => pack_zip(files).map_err(|e| failed(&e)),
+    }?;
+    Ok(Packed { body, unreadable })
+}
+
+fn pack_tar<'a, W: Write>(
+    writer: W,
+    files: impl Iterator<Item = (&'a str, File)>,
+) -> io::Result<W> {
+    let mut builder = tar::Builder::new(writer);
+    for (key, mut file) in files {
+        builder.append_file(key, &mut file)?;
+    }
+    builder.into_inner()
+}
+
+// Written to a seekable buffer, every member's sizes land in its local header,
+// which is all `download_and_extract` reads.
+fn pack_zip<'a>(files: impl Iterator<Item = (&'a str, File)>) -> zip::result::ZipResult<Vec<u8>> {
+    let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+    for (key, mut file) in files {
+        let size = file.metadata()?.len();
+        let options = SimpleFileOptions::default()
+            .compression_method(CompressionMethod::Deflated)
+            .large_file(size >= u64::from(u32::MAX));
+        writer.start_file(key, options)?;
+        io::copy(&mut file, &mut writer)?;
+    }
+    Ok(writer.finish()?.into_inner())
+}
@@ -0,0 +1,60 @@
+use pyo3::exceptions::PyValueError;
+use pyo3::types::PyAnyMethods;
+use pyo3::{Bound, FromPyObject, PyAny, PyResult};
+
+/// A slice of an object fetched with a ranged GET: `length` bytes from
+/// `offset`, up to the end of the object when `length` is unset. A negative
+/// `offset` stands for the last `-offset` bytes, wherever the object ends.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ByteRange {
+    offset: i64,
+    length: Option<u64>,
+}
+
+impl ByteRange {
+    /// Checks a range given from Python.
+    pub fn new(offset: i64, length: Option<u64>) -> PyResult<Self> {
+        if length == Some(0) {
+            return Err(PyValueError::new_err("length must be positive"));
+        }
+        if offset < 0 && length.is_some() {
+            return Err(PyValueError::new_err(
+                "A negative offset already selects the end of the object; length must be None",
+            ));
+        }
+        Ok(Self { offset, length })
+    }
+
+    /// The value of the `Range` header selecting the slice.
+    pub fn header(&self) -> String {
+        match self.length {
+            _ if self.offset < 0 => format!("bytes={}", self.offset),
+            None => format!("bytes={}-", self.offset),
+            Some(length) => format!("bytes={}-{}", self.offset, self.offset as u64 + length - 1),
+        }
+    }
+
+    /// Bytes the slice holds of an object of `size`.
+    pub fn length_in(&self, size: u64) -> u64 {
+        match self.length {
+            _ if self.offset < 0 => self.offset.unsigned_abs().min(size),
+            None => size.saturating_sub(self.offset as u64),
+            Some(length) => size.saturating_sub(self.offset as u64).min(length),
+        }
+    }
+}
+
+/// `(offset, length)`, with `length` possibly `None`.
+impl<'py> FromPyObject<'py> for ByteRange {
+    fn extract_bound(value: &Bound<'py, PyAny>) -> PyResult<Self> {
+        let (offset, length) = value.extract::<(i64, Option<u64>)>()?;
+        Self::new(offset, length)
+    }
+}
+
+/// Where the body of a ranged GET starts in the object, read from its
+/// `Content-Range: bytes start-end/size` header.
+pub fn content_range_start(content_range: &str) -> Option<u64> {
+    let (start, _) = content_range.strip_prefix("bytes ")?.split_once('-')?;
+    start.parse().ok()
+}
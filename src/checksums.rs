@@ -0,0 +1,211 @@
+use std::collections::{BTreeMap, HashMap};
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+
+use futures::stream::{self, StreamExt};
+use pyo3::exceptions::PyValueError;
+use pyo3::{PyErr, PyResult};
+use ring::digest::{Context, SHA256};
+use tokio::io::AsyncReadExt;
+
+use crate::error::TransferError;
+use crate::exceptions::RobinzhonError;
+use crate::integrity::hex;
+use crate::results::{FailedTransfer, ItemReport, ItemStats, Results, RustOperationResult};
+
+/// SHA-256 digests of the files a batch downloads, checked against the ones
+/// expected of their keys and collected for a `SHA256SUMS` manifest.
+#[derive(Debug, Default)]
+pub struct Sha256Sums {
+    /// Lowercase hex digests, by object key.
+    expected: HashMap<String, String>,
+    /// Hex digests of the files downloaded, by local path.
+    computed: Mutex<HashMap<String, String>>,
+}
+
+impl Sha256Sums {
+    /// The digests of a batch writing a manifest or expecting some of them, or
+    /// `None` when it does neither.
+    pub fn requested(
+        manifest: bool,
+        expected: Option<HashMap<String, String>>,
+    ) -> PyResult<Option<Arc<Self>>> {
+        if !manifest && expected.is_none() {
+            return Ok(None);
+        }
+        let expected = expected
+            .unwrap_or_default()
+            .into_iter()
+            .map(|(key, digest)| {
+                if is_sha256(&digest) {
+                    Ok((key, digest.to_ascii_lowercase()))
+                } else {
+                    Err(PyValueError::new_err(format!(
+                        "Invalid SHA-256 digest '{}' for '{}'",
+                        digest, key
+                    )))
+                }
+            })
+            .collect::<PyResult<_>>()?;
+        Ok(Some(Arc::new(Self {
+            expected,
+            computed: Mutex::default(),
+        })))
+    }
+
+    /// Records the digest of the file `object_key` was downloaded to, and fails
+    /// when it isn't the one expected of the key. The digest is `streamed` when
+    /// the whole body went through it on its way to the file; otherwise the
+    /// file is read back.
+    pub async fn check(
+        &self,
+        object_key: &str,
+        local_path: &str,
+        streamed: Option<Context>,
+    ) -> Result<(), TransferError> {
+        let actual = match streamed {
+            Some(context) => hex(context.finish().as_ref()),
+            None => file_sha256(Path::new(local_path)).await?,
+        };
+        if let Some(expected) = self.expected.get(object_key) {
+            if *expected != actual {
+                return Err(mismatch(object_key, expected, &actual));
+            }
+        }
+        self.computed
+            .lock()
+            .unwrap()
+            .insert(local_path.to_string(), actual);
+        Ok(())
+    }
+
+    /// Writes the digests of the files `results` downloaded to `manifest_path`,
+    /// one `<digest>  <path>` line per file sorted by path, as `sha256sum` does.
+    /// Files the batch left in place aren't read, so they aren't listed.
+    fn write_manifest(&self, manifest_path: &str, results: &Results) -> PyResult<()> {
+        let computed = self.computed.lock().unwrap();
+        let lines: BTreeMap<&str, &str> = results
+            .successful
+            .iter()
+            .filter_map(|path| Some((path.as_str(), computed.get(path)?.as_str())))
+            .collect();
+        let contents: String = lines
+            .into_iter()
+            .map(|(path, digest)| format!("{}  {}\n", digest, path))
+            .collect();
+        std::fs::write(manifest_path, contents).map_err(|e| {
+            RobinzhonError::new_err(format!(
+                "Failed to write checksum manifest '{}': {}",
+                manifest_path, e
+            ))
+        })
+    }
+}
+
+/// Writes the manifest of a batch run with `sha256` to `manifest_path`, when
+/// one was asked for.
+pub fn with_manifest(
+    results: Results,
+    sha256: Option<&Sha256Sums>,
+    manifest_path: Option<&str>,
+) -> PyResult<Results> {
+    if let (Some(sha256), Some(manifest_path)) = (sha256, manifest_path) {
+        sha256.write_manifest(manifest_path, &results)?;
+    }
+    Ok(results)
+}
+
+/// Checks every file a `SHA256SUMS` manifest lists against its digest, up to
+/// `concurrency` at a time. Each file is an item: successful when it matches,
+/// failed when it doesn't or can't be read. Relative paths are taken from the
+/// current directory, as `sha256sum -c` does.
+pub async fn verify_manifest(
+    manifest_path: &str,
+    concurrency: usize,
+) -> PyResult<RustOperationResult> {
+    let contents = tokio::fs::read_to_string(manifest_path)
+        .await
+        .map_err(|e| {
+            RobinzhonError::new_err(format!(
+                "Failed to read checksum manifest '{}': {}",
+                manifest_path, e
+            ))
+        })?;
+    let entries: Vec<(String, String)> = contents
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty())
+        .map(|(number, line)| {
+            parse_line(line).ok_or_else(|| invalid_line(manifest_path, number + 1))
+        })
+        .collect::<PyResult<_>>()?;
+
+    let mut result = RustOperationResult::default();
+    let mut checked = stream::iter(entries.into_iter().enumerate())
+        .map(|(index, (expected, path))| async move {
+            let actual = file_sha256(Path::new(&path)).await;
+            let outcome = match actual {
+                Ok(actual) if actual == expected => Ok(path.clone()),
+                Ok(actual) => Err(mismatch(&path, &expected, &actual)),
+                Err(error) => Err(error),
+            }
+            .map_err(|error| FailedTransfer::new(&path, (path.clone(), String::new()), error));
+            let report = ItemReport {
+                index,
+                source: path.clone(),
+                destination: path,
+                stats: ItemStats::default(),
+            };
+            (report, outcome)
+        })
+        .buffered(concurrency);
+    while let Some((report, outcome)) = checked.next().await {
+        result.record_transfer(report, outcome);
+    }
+    Ok(result)
+}
+
+/// Splits a manifest line into its lowercase digest and path. `sha256sum`
+/// marks files it read in binary mode with a `*` before the path.
+fn parse_line(line: &str) -> Option<(String, String)> {
+    let (digest, path) = line.split_once(' ')?;
+    let path = path.strip_prefix([' ', '*'])?;
+    (is_sha256(digest) && !path.is_empty()).then(|| (digest.to_ascii_lowercase(), path.into()))
+}
+
+fn invalid_line(manifest_path: &str, number: usize) -> PyErr {
+    PyValueError::new_err(format!(
+        "Invalid line {} of checksum manifest '{}': expected '<sha256>  <path>'",
+        number, manifest_path
+    ))
+}
+
+fn is_sha256(digest: &str) -> bool {
+    digest.len() == 64 && digest.bytes().all(|byte| byte.is_ascii_hexdigit())
+}
+
+// Not retryable: a body that doesn't match its digest is most likely another
+// object than the one expected, which fetching it again won't change.
+fn mismatch(name: &str, expected: &str, actual: &str) -> TransferError {
+    TransferError::new(format!(
+        "SHA-256 mismatch for '{}': expected {}, got {}",
+        name, expected, actual
+    ))
+}
+
+pub async fn file_sha256(path: &Path) -> Result<String, TransferError> {
+    let read_error = |e: std::io::Error| {
+        TransferError::new(format!("Failed to read file '{}': {}", path.display(), e))
+    };
+    let mut file = tokio::fs::File::open(path).await.map_err(read_error)?;
+    let mut context = Context::new(&SHA256);
+    let mut buffer = vec![0; 1024 * 1024];
+    loop {
+        let read = file.read(&mut buffer).await.map_err(read_error)?;
+        if read == 0 {
+            break;
+        }
+        context.update(&buffer[..read]);
+    }
+    Ok(hex(context.finish().as_ref()))
+}
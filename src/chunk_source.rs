@@ -0,0 +1,94 @@
+use std::sync::{Arc, Mutex};
+
+use bytes::{Bytes, BytesMut};
+use pyo3::exceptions::PyTypeError;
+use pyo3::pybacked::PyBackedBytes;
+use pyo3::types::{PyAnyMethods, PyIterator, PyTypeMethods};
+use pyo3::{Bound, Py, PyAny, PyResult, Python};
+
+use crate::exceptions::RobinzhonError;
+
+enum Source {
+    /// A binary file object, read with `read(n)` until it returns `b""`.
+    Reader(Py<PyAny>),
+    /// An iterator of bytes-like chunks of any size.
+    Chunks(Py<PyIterator>),
+}
+
+/// Upload body produced by Python code, consumed in fixed-size parts.
+///
+/// Reads call back into Python, so they run on Tokio's blocking pool with the GIL
+/// held there; parts already read keep uploading in the meantime.
+pub struct ChunkSource {
+    source: Source,
+    pending: Mutex<BytesMut>,
+}
+
+impl ChunkSource {
+    pub fn new(fileobj: &Bound<'_, PyAny>) -> PyResult<Arc<Self>> {
+        let source = if fileobj.hasattr("read")? {
+            Source::Reader(fileobj.clone().unbind())
+        } else {
+            let chunks = fileobj.try_iter().map_err(|_| {
+                PyTypeError::new_err(format!(
+                    "fileobj must have a read() method or be an iterable of bytes, not '{}'",
+                    type_name(fileobj)
+                ))
+            })?;
+            Source::Chunks(chunks.unbind())
+        };
+
+        Ok(Arc::new(Self {
+            source,
+            pending: Mutex::default(),
+        }))
+    }
+
+    /// Returns the next `part_size` bytes, fewer only for the last part, or `None`
+    /// once the source is exhausted.
+    pub async fn next_part(self: &Arc<Self>, part_size: usize) -> PyResult<Option<Bytes>> {
+        let source = Arc::clone(self);
+        tokio::task::spawn_blocking(move || Python::with_gil(|py| source.read_part(py, part_size)))
+            .await
+            .map_err(|e| RobinzhonError::new_err(format!("Failed to read upload body: {}", e)))?
+    }
+
+    fn read_part(&self, py: Python<'_>, part_size: usize) -> PyResult<Option<Bytes>> {
+        let mut pending = self.pending.lock().unwrap();
+        while pending.len() < part_size {
+            let chunk = match &self.source {
+                Source::Reader(reader) => reader
+                    .bind(py)
+                    .call_method1("read", (part_size - pending.len(),))?,
+                Source::Chunks(chunks) => match chunks.bind(py).clone().next() {
+                    Some(chunk) => chunk?,
+                    None => break,
+                },
+            };
+            let chunk = chunk.extract::<PyBackedBytes>().map_err(|_| {
+                PyTypeError::new_err(format!(
+                    "fileobj must produce bytes, not '{}'",
+                    type_name(&chunk)
+                ))
+            })?;
+            if chunk.is_empty() && matches!(self.source, Source::Reader(_)) {
+                break;
+            }
+            pending.extend_from_slice(&chunk);
+        }
+
+        if pending.is_empty() {
+            return Ok(None);
+        }
+        let len = pending.len().min(part_size);
+        Ok(Some(pending.split_to(len).freeze()))
+    }
+}
+
+fn type_name(object: &Bound<'_, PyAny>) -> String {
+    object
+        .get_type()
+        .name()
+        .map(|name| name.to_string())
+        .unwrap_or_default()
+}
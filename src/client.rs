@@ -0,0 +1,314 @@
+use futures::TryStreamExt;
+use pyo3::exceptions::PyAttributeError;
+use pyo3::types::{PyAnyMethods, PyDict, PyDictMethods};
+use pyo3::{pyclass, pymethods, Bound, Py, PyAny, PyErr, PyRef, PyResult, Python};
+
+use crate::batch_input::BatchInput;
+use crate::limiter::TransferLimiter;
+use crate::objects::{
+    copy_object, delete_object, delete_objects, presign, presign_expiry, PresignMethod,
+};
+use crate::plan::{Planned, TransferPlan};
+use crate::preflight::{preflight, PreflightReport};
+use crate::progress::TransferProgress;
+use crate::results::{Results, TransferKind};
+use crate::retry::RetryPolicy;
+use crate::runtime::block_on_interruptible;
+use crate::s3_config::S3Config;
+use crate::s3_downloader::S3Downloader;
+use crate::s3_uploader::S3Uploader;
+use crate::settings::{DEFAULT_PART_CONCURRENCY, DEFAULT_PART_SIZE};
+
+/// Methods both sides have, which the client defines itself rather than
+/// forwarding to the downloader.
+const NOT_FORWARDED: [&str; 2] = ["retry_failed", "reinitialize"];
+
+/// One client for both directions: an `S3Downloader` and an `S3Uploader` running
+/// on a single S3 client, runtime and set of rate limits, plus the object
+/// operations neither of them has.
+///
+/// Methods of the downloader and the uploader are called on the client
+/// directly, as in `client.download_file(...)` or `client.upload_file(...)`.
+#[pyclass(frozen, module = "robinzhon")]
+pub struct S3Client {
+    downloader: Py<S3Downloader>,
+    uploader: Py<S3Uploader>,
+}
+
+impl S3Client {
+    /// Points the uploader at the downloader's client, runtime and limits.
+    fn share_state(&self, py: Python<'_>) {
+        self.uploader
+            .get()
+            .share(&self.downloader.bind(py).get().state());
+    }
+}
+
+#[pymethods]
+impl S3Client {
+    #[new]
+    #[pyo3(signature = (region_name=None, endpoint_url=None, force_path_style=false, config=None, max_concurrent=5, runtime_threads=None, retry_policy=None, part_size=DEFAULT_PART_SIZE, part_concurrency=DEFAULT_PART_CONCURRENCY, max_bandwidth_bytes_per_sec=None, max_requests_per_sec=None, request_burst=None, limiter=None, audit_log=None, max_in_flight_bytes=None))]
+    #[allow(clippy::too_many_arguments)]
+    fn new(
+        py: Python<'_>,
+        region_name: Option<&str>,
+        endpoint_url: Option<String>,
+        force_path_style: bool,
+        config: Option<PyRef<'_, S3Config>>,
+        max_concurrent: usize,
+        runtime_threads: Option<usize>,
+        retry_policy: Option<RetryPolicy>,
+        part_size: u64,
+        part_concurrency: usize,
+        max_bandwidth_bytes_per_sec: Option<u64>,
+        max_requests_per_sec: Option<f64>,
+        request_burst: Option<u32>,
+        limiter: Option<TransferLimiter>,
+        audit_log: Option<String>,
+        max_in_flight_bytes: Option<u64>,
+    ) -> PyResult<Self> {
+        let s3_config = S3Config::from_args(
+            config.as_deref(),
+            region_name,
+            endpoint_url,
+            force_path_style,
+        )?;
+
+        let kwargs = PyDict::new(py);
+        kwargs.set_item("config", (*s3_config).clone())?;
+        kwargs.set_item("retry_policy", retry_policy)?;
+        kwargs.set_item("part_size", part_size)?;
+        kwargs.set_item("part_concurrency", part_concurrency)?;
+        // Both sides open the same log, so their entries share one chain.
+        kwargs.set_item("audit_log", audit_log)?;
+
+        let uploader_kwargs = kwargs.copy()?;
+        uploader_kwargs.set_item("max_concurrent_uploads", max_concurrent)?;
+        let uploader = py
+            .get_type::<S3Uploader>()
+            .call((), Some(&uploader_kwargs))?
+            .downcast_into::<S3Uploader>()?
+            .unbind();
+
+        kwargs.set_item("max_concurrent_downloads", max_concurrent)?;
+        kwargs.set_item("runtime_threads", runtime_threads)?;
+        kwargs.set_item("max_bandwidth_bytes_per_sec", max_bandwidth_bytes_per_sec)?;
+        kwargs.set_item("max_requests_per_sec", max_requests_per_sec)?;
+        kwargs.set_item("request_burst", request_burst)?;
+        kwargs.set_item("limiter", limiter)?;
+        kwargs.set_item("max_in_flight_bytes", max_in_flight_bytes)?;
+        let downloader = py
+            .get_type::<S3Downloader>()
+            .call((), Some(&kwargs))?
+            .downcast_into::<S3Downloader>()?
+            .unbind();
+
+        let client = Self {
+            downloader,
+            uploader,
+        };
+        client.share_state(py);
+        Ok(client)
+    }
+
+    /// Looks up methods the client doesn't define on the downloader, then on the
+    /// uploader.
+    fn __getattr__<'py>(&self, py: Python<'py>, name: &str) -> PyResult<Bound<'py, PyAny>> {
+        if !name.starts_with('_') && !NOT_FORWARDED.contains(&name) {
+            for side in [
+                self.downloader.bind(py).as_any(),
+                self.uploader.bind(py).as_any(),
+            ] {
+                if side.hasattr(name)? {
+                    return side.getattr(name);
+                }
+            }
+        }
+        Err(PyAttributeError::new_err(format!(
+            "'S3Client' object has no attribute '{}'",
+            name
+        )))
+    }
+
+    /// The downloader the client runs downloads, listings and HEAD requests with.
+    #[getter]
+    fn downloader(&self, py: Python<'_>) -> Py<S3Downloader> {
+        self.downloader.clone_ref(py)
+    }
+
+    /// The uploader the client runs uploads with.
+    #[getter]
+    fn uploader(&self, py: Python<'_>) -> Py<S3Uploader> {
+        self.uploader.clone_ref(py)
+    }
+
+    /// Transfers the failed items of `results` again, with the downloader or
+    /// the uploader depending on the batch they came from.
+    #[pyo3(signature = (results, progress=None, fail_fast=false, max_failures=None, raise_on_failure=false))]
+    fn retry_failed<'py>(
+        &self,
+        py: Python<'py>,
+        results: Bound<'py, Results>,
+        progress: Option<TransferProgress>,
+        fail_fast: bool,
+        max_failures: Option<usize>,
+        raise_on_failure: bool,
+    ) -> PyResult<Bound<'py, PyAny>> {
+        let kind = results.borrow().kind();
+        let side = match kind {
+            Some(TransferKind::Upload) => self.uploader.bind(py).as_any().clone(),
+            _ => self.downloader.bind(py).as_any().clone(),
+        };
+        side.call_method1(
+            "retry_failed",
+            (results, progress, fail_fast, max_failures, raise_on_failure),
+        )
+    }
+
+    /// Rebuilds the runtime and the S3 client shared by both directions, for a
+    /// process forked after the client was created.
+    fn reinitialize(&self, py: Python<'_>) -> PyResult<()> {
+        self.downloader.bind(py).call_method0("reinitialize")?;
+        self.share_state(py);
+        Ok(())
+    }
+
+    /// Checks that a batch on `bucket_name` can run before starting it:
+    /// credentials load, the bucket exists in the client's region, and the client
+    /// may list it and, with `check_write`, write to it.
+    #[pyo3(signature = (bucket_name, check_write=true))]
+    fn preflight(
+        &self,
+        py: Python<'_>,
+        bucket_name: &str,
+        check_write: bool,
+    ) -> PyResult<PreflightReport> {
+        let state = &self.downloader.get().state();
+        block_on_interruptible(
+            py,
+            &state.runtime,
+            preflight(&state.s3_config, bucket_name, check_write),
+        )
+    }
+
+    /// Deletes an object, or one version of it with `version_id`.
+    #[pyo3(signature = (bucket_name, object_key, version_id=None))]
+    fn delete_object(
+        &self,
+        py: Python<'_>,
+        bucket_name: &str,
+        object_key: &str,
+        version_id: Option<&str>,
+    ) -> PyResult<()> {
+        let state = &self.downloader.get().state();
+        block_on_interruptible(
+            py,
+            &state.runtime,
+            delete_object(
+                &state.s3_config,
+                bucket_name,
+                object_key,
+                version_id,
+                &state.settings.retry_policy,
+            ),
+        )?
+        .map_err(PyErr::from)
+    }
+
+    /// Deletes objects in batches of 1000, as the keys are read, and returns
+    /// the keys deleted; keys that couldn't be are logged and left out. With
+    /// `dry_run`, nothing is deleted and the plan of the deletions is returned.
+    #[pyo3(signature = (bucket_name, object_keys, dry_run=false))]
+    fn delete_objects(
+        &self,
+        py: Python<'_>,
+        bucket_name: &str,
+        object_keys: BatchInput<String>,
+        dry_run: bool,
+    ) -> PyResult<Planned<Vec<String>>> {
+        let state = &self.downloader.get().state();
+        if dry_run {
+            let object_keys: Vec<String> = block_on_interruptible(
+                py,
+                &state.runtime,
+                object_keys.into_stream().try_collect(),
+            )??;
+            return Ok(Planned::Plan(TransferPlan::delete(object_keys)));
+        }
+        block_on_interruptible(
+            py,
+            &state.runtime,
+            delete_objects(
+                &state.s3_config,
+                bucket_name,
+                object_keys.into_stream(),
+                &state.settings.retry_policy,
+            ),
+        )?
+        .map(Planned::Done)
+    }
+
+    /// Copies an object within S3, across buckets too, with its metadata.
+    /// Objects larger than 5 GiB can't be copied in one request.
+    #[pyo3(signature = (source_bucket, source_key, bucket_name, object_key))]
+    fn copy_object(
+        &self,
+        py: Python<'_>,
+        source_bucket: &str,
+        source_key: &str,
+        bucket_name: &str,
+        object_key: &str,
+    ) -> PyResult<()> {
+        let state = &self.downloader.get().state();
+        block_on_interruptible(
+            py,
+            &state.runtime,
+            copy_object(
+                &state.s3_config,
+                source_bucket,
+                source_key,
+                bucket_name,
+                object_key,
+                &state.settings.retry_policy,
+            ),
+        )?
+        .map_err(PyErr::from)
+    }
+
+    /// A URL to GET or PUT the object with, without credentials, for
+    /// `expires_in` seconds.
+    #[pyo3(signature = (bucket_name, object_key, method=PresignMethod::Get, expires_in=3600.0))]
+    fn presign(
+        &self,
+        py: Python<'_>,
+        bucket_name: &str,
+        object_key: &str,
+        method: PresignMethod,
+        expires_in: f64,
+    ) -> PyResult<String> {
+        let expires_in = presign_expiry(expires_in)?;
+        let state = &self.downloader.get().state();
+        block_on_interruptible(
+            py,
+            &state.runtime,
+            presign(
+                &state.s3_config,
+                bucket_name,
+                object_key,
+                method,
+                expires_in,
+            ),
+        )?
+        .map_err(PyErr::from)
+    }
+
+    fn __repr__(&self) -> String {
+        let state = self.downloader.get().state();
+        format!(
+            "S3Client(max_concurrent={}, part_size={}, part_concurrency={})",
+            state.settings.max_concurrent,
+            state.settings.part_size,
+            state.settings.part_concurrency
+        )
+    }
+}
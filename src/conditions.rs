@@ -0,0 +1,52 @@
+use aws_sdk_s3::operation::get_object::builders::GetObjectFluentBuilder;
+use aws_sdk_s3::primitives::DateTime;
+use pyo3::types::{PyAnyMethods, PyDateTime};
+use pyo3::{Bound, FromPyObject, PyAny, PyResult};
+
+use crate::error::TransferError;
+
+/// A point in time passed as a Python `datetime`. Naive datetimes are taken as
+/// local time, as `datetime.timestamp()` does.
+#[derive(Debug, Clone, Copy)]
+pub struct Timestamp(pub DateTime);
+
+impl<'py> FromPyObject<'py> for Timestamp {
+    fn extract_bound(value: &Bound<'py, PyAny>) -> PyResult<Self> {
+        let seconds = value
+            .downcast::<PyDateTime>()?
+            .call_method0("timestamp")?
+            .extract::<f64>()?;
+        Ok(Self(DateTime::from_secs_f64(seconds)))
+    }
+}
+
+/// Conditions under which a GET transfers the object. When they don't hold, S3
+/// answers "304 Not Modified" and the local copy is left as it is.
+#[derive(Debug, Clone, Default)]
+pub struct GetConditions {
+    /// ETag of the copy already held; the object is only sent if it differs.
+    pub if_none_match: Option<String>,
+    /// The object is only sent if it was modified after this time.
+    pub if_modified_since: Option<DateTime>,
+}
+
+impl GetConditions {
+    pub fn new(if_none_match: Option<String>, if_modified_since: Option<Timestamp>) -> Self {
+        Self {
+            if_none_match,
+            if_modified_since: if_modified_since.map(|Timestamp(time)| time),
+        }
+    }
+
+    pub fn apply(&self, request: GetObjectFluentBuilder) -> GetObjectFluentBuilder {
+        request
+            .set_if_none_match(self.if_none_match.clone())
+            .set_if_modified_since(self.if_modified_since)
+    }
+}
+
+/// Whether `error` is the "304 Not Modified" answer to a conditional GET, which
+/// means the local copy is current rather than that anything failed.
+pub fn is_not_modified(error: &TransferError) -> bool {
+    error.http_status == Some(304)
+}
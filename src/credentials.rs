@@ -0,0 +1,331 @@
+use std::error::Error;
+use std::fmt;
+use std::sync::Arc;
+use std::time::{Duration, Instant, SystemTime};
+
+use aws_config::sts::AssumeRoleProvider;
+use aws_config::SdkConfig;
+use aws_credential_types::provider::error::CredentialsError;
+use aws_credential_types::provider::{self, future, ProvideCredentials, SharedCredentialsProvider};
+use aws_credential_types::Credentials;
+use log::debug;
+use pyo3::exceptions::{PyTypeError, PyValueError};
+use pyo3::types::PyAnyMethods;
+use pyo3::{Bound, Py, PyAny, PyErr, PyResult, Python};
+use tokio::sync::Mutex;
+
+use crate::error::error_chain;
+
+/// How long before they expire credentials are replaced, so that requests
+/// signed with them still arrive in time.
+const REFRESH_MARGIN: Duration = Duration::from_secs(5 * 60);
+
+/// Credentials loaded this recently are kept when a request reports them
+/// expired, so that a burst of such failures loads new ones only once.
+const MIN_RELOAD_INTERVAL: Duration = Duration::from_secs(10);
+
+/// Shortest and longest sessions STS grants for an assumed role.
+const ROLE_DURATION: (u64, u64) = (15 * 60, 12 * 60 * 60);
+
+/// Where an `S3Config` gets its credentials, beyond the default provider chain.
+#[derive(Debug, Clone, Default)]
+pub struct CredentialSource {
+    /// Profile of the shared config files to read, instead of `AWS_PROFILE` or
+    /// `default`.
+    pub profile_name: Option<String>,
+    pub assume_role: Option<AssumeRole>,
+    /// Python callable asked for credentials instead of the provider chain.
+    pub callback: Option<PythonCredentials>,
+}
+
+/// A role the client assumes with STS, on top of the credentials it resolves.
+#[derive(Debug, Clone, Default)]
+pub struct AssumeRole {
+    pub role_arn: String,
+    /// Name of the sessions, shown in the role account's CloudTrail; one
+    /// naming the library and the time is used when unset.
+    pub session_name: Option<String>,
+    pub external_id: Option<String>,
+    /// Length of each session; STS picks one hour when unset.
+    pub duration: Option<Duration>,
+}
+
+impl AssumeRole {
+    /// Checks the role arguments given from Python; the others need `role_arn`.
+    pub fn from_args(
+        role_arn: Option<String>,
+        session_name: Option<String>,
+        external_id: Option<String>,
+        duration: Option<f64>,
+    ) -> PyResult<Option<Self>> {
+        let Some(role_arn) = role_arn else {
+            if session_name.is_some() || external_id.is_some() || duration.is_some() {
+                return Err(PyValueError::new_err(
+                    "role_session_name, external_id and role_duration need role_arn",
+                ));
+            }
+            return Ok(None);
+        };
+        let (shortest, longest) = ROLE_DURATION;
+        let duration = match duration {
+            None => None,
+            Some(seconds) if (shortest as f64..=longest as f64).contains(&seconds) => {
+                Some(Duration::from_secs_f64(seconds))
+            }
+            Some(_) => {
+                return Err(PyValueError::new_err(format!(
+                    "role_duration must be between {} and {} seconds",
+                    shortest, longest
+                )))
+            }
+        };
+        Ok(Some(Self {
+            role_arn,
+            session_name,
+            external_id,
+            duration,
+        }))
+    }
+
+    /// Provider of the role's credentials, assuming it with those of `config`
+    /// again whenever a session runs out.
+    pub async fn provider(&self, config: &SdkConfig) -> SharedCredentialsProvider {
+        let session_name = self.session_name.clone().unwrap_or_else(|| {
+            let now = SystemTime::now()
+                .duration_since(SystemTime::UNIX_EPOCH)
+                .unwrap_or_default();
+            format!("robinzhon-{}", now.as_secs())
+        });
+        let mut builder = AssumeRoleProvider::builder(&self.role_arn)
+            .configure(config)
+            .session_name(session_name);
+        if let Some(external_id) = &self.external_id {
+            builder = builder.external_id(external_id);
+        }
+        if let Some(duration) = self.duration {
+            builder = builder.session_length(duration);
+        }
+        SharedCredentialsProvider::new(builder.build().await)
+    }
+}
+
+/// Credentials cached until shortly before they expire, then loaded again from
+/// their source, so that batches outlive the sessions they started with.
+/// Unlike the SDK's own cache it can be told the credentials stopped working.
+#[derive(Debug, Clone)]
+pub struct RefreshingCredentials {
+    source: SharedCredentialsProvider,
+    /// Profile the credentials come from, to name in `aws sso login` hints.
+    profile_name: Option<String>,
+    cached: Arc<Mutex<Option<(Credentials, Instant)>>>,
+}
+
+impl RefreshingCredentials {
+    pub fn new(source: SharedCredentialsProvider, profile_name: Option<String>) -> Self {
+        Self {
+            source,
+            profile_name,
+            cached: Arc::default(),
+        }
+    }
+
+    /// Drops the cached credentials after S3 rejected them as expired, unless
+    /// they were only just loaded.
+    pub async fn invalidate(&self) {
+        let mut cached = self.cached.lock().await;
+        if cached
+            .as_ref()
+            .is_some_and(|(_, loaded)| loaded.elapsed() >= MIN_RELOAD_INTERVAL)
+        {
+            debug!("AWS credentials were rejected as expired; loading new ones");
+            *cached = None;
+        }
+    }
+
+    async fn credentials(&self) -> provider::Result {
+        let mut cached = self.cached.lock().await;
+        if let Some((credentials, _)) = cached.as_ref().filter(|(c, _)| !expiring(c)) {
+            return Ok(credentials.clone());
+        }
+        let credentials = self
+            .source
+            .provide_credentials()
+            .await
+            .map_err(|e| self.explain(e))?;
+        if let Some(expiry) = credentials.expiry() {
+            let remaining = expiry.duration_since(SystemTime::now()).unwrap_or_default();
+            debug!(
+                "Loaded AWS credentials valid for {} more seconds",
+                remaining.as_secs()
+            );
+        }
+        *cached = Some((credentials.clone(), Instant::now()));
+        Ok(credentials)
+    }
+
+    /// Turns a failure of the SSO provider, which comes down to a session that
+    /// expired or was never started, into a `SsoLoginRequired`.
+    fn explain(&self, error: CredentialsError) -> CredentialsError {
+        let chain = error_chain(&error);
+        if !chain.to_ascii_lowercase().contains("sso") {
+            return error;
+        }
+        let command = match &self.profile_name {
+            Some(profile_name) => format!("aws sso login --profile {}", profile_name),
+            None => "aws sso login".to_string(),
+        };
+        CredentialsError::provider_error(SsoLoginRequired { command, chain })
+    }
+}
+
+/// The AWS SSO session credentials come from has expired or was never
+/// started, and `command` starts a new one.
+#[derive(Debug)]
+pub struct SsoLoginRequired {
+    command: String,
+    /// What the SSO provider reported.
+    chain: String,
+}
+
+impl fmt::Display for SsoLoginRequired {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "The AWS SSO session has expired or was never started; run `{}` and try again ({})",
+            self.command, self.chain
+        )
+    }
+}
+
+impl Error for SsoLoginRequired {}
+
+impl ProvideCredentials for RefreshingCredentials {
+    fn provide_credentials<'a>(&'a self) -> future::ProvideCredentials<'a>
+    where
+        Self: 'a,
+    {
+        future::ProvideCredentials::new(self.credentials())
+    }
+}
+
+/// Credentials handed out by Python: by a callable returning `(access_key,
+/// secret_key, session_token, expiry)`, for brokers the SDK doesn't know about,
+/// or by botocore credentials, as boto3 sessions hold.
+#[derive(Debug, Clone)]
+pub struct PythonCredentials {
+    object: Arc<Py<PyAny>>,
+    /// Whether `object` is botocore credentials rather than a callable.
+    botocore: bool,
+}
+
+/// Access key, secret key, session token and expiry, as Python gave them.
+type CredentialParts<'py> = (String, String, Option<String>, Option<Bound<'py, PyAny>>);
+
+impl PythonCredentials {
+    pub fn new(object: Bound<'_, PyAny>) -> PyResult<Self> {
+        let botocore = object.hasattr("get_frozen_credentials")?
+            || (object.hasattr("access_key")? && object.hasattr("secret_key")?);
+        if !botocore && !object.is_callable() {
+            return Err(PyTypeError::new_err(
+                "credentials_provider must be callable or botocore credentials",
+            ));
+        }
+        Ok(Self {
+            object: Arc::new(object.unbind()),
+            botocore,
+        })
+    }
+
+    pub fn object(&self, py: Python<'_>) -> Py<PyAny> {
+        self.object.clone_ref(py)
+    }
+
+    /// Asks Python on a blocking thread, as a callable may wait on its broker
+    /// and botocore on a refresh.
+    async fn credentials(&self) -> provider::Result {
+        let (object, botocore) = (self.object.clone(), self.botocore);
+        tokio::task::spawn_blocking(move || {
+            Python::with_gil(|py| {
+                let object = object.bind(py);
+                let parts = if botocore {
+                    botocore_parts(object)
+                } else {
+                    object.call0().and_then(|parts| parts.extract())
+                };
+                into_credentials(parts)
+            })
+        })
+        .await
+        .map_err(CredentialsError::provider_error)?
+    }
+}
+
+impl ProvideCredentials for PythonCredentials {
+    fn provide_credentials<'a>(&'a self) -> future::ProvideCredentials<'a>
+    where
+        Self: 'a,
+    {
+        future::ProvideCredentials::new(self.credentials())
+    }
+}
+
+/// Reads botocore credentials, which refresh themselves when they are
+/// refreshable and about to expire.
+fn botocore_parts<'py>(credentials: &Bound<'py, PyAny>) -> PyResult<CredentialParts<'py>> {
+    let frozen = if credentials.hasattr("get_frozen_credentials")? {
+        credentials.call_method0("get_frozen_credentials")?
+    } else {
+        credentials.clone()
+    };
+    // Refreshable credentials keep their expiry in a private attribute only.
+    let expiry = credentials
+        .getattr("_expiry_time")
+        .ok()
+        .filter(|expiry| !expiry.is_none());
+    Ok((
+        frozen.getattr("access_key")?.extract()?,
+        frozen.getattr("secret_key")?.extract()?,
+        frozen.getattr("token")?.extract()?,
+        expiry,
+    ))
+}
+
+fn into_credentials(parts: PyResult<CredentialParts<'_>>) -> provider::Result {
+    let failed =
+        |e: PyErr| CredentialsError::provider_error(format!("credentials_provider failed: {}", e));
+    let (access_key, secret_key, session_token, expiry) = parts.map_err(failed)?;
+    let expiry = expiry
+        .map(|expiry| expiry_time(&expiry))
+        .transpose()
+        .map_err(failed)?;
+    Ok(Credentials::new(
+        access_key,
+        secret_key,
+        session_token,
+        expiry,
+        "robinzhon-credentials-provider",
+    ))
+}
+
+/// Reads an expiry given as an aware `datetime` or in seconds since the epoch.
+fn expiry_time(expiry: &Bound<'_, PyAny>) -> PyResult<SystemTime> {
+    let seconds: f64 = match expiry.extract() {
+        Ok(seconds) => seconds,
+        Err(_) => expiry.call_method0("timestamp")?.extract()?,
+    };
+    if !seconds.is_finite() || seconds < 0.0 {
+        return Err(PyValueError::new_err(format!(
+            "Invalid credentials expiry: {}",
+            seconds
+        )));
+    }
+    Ok(SystemTime::UNIX_EPOCH + Duration::from_secs_f64(seconds))
+}
+
+/// Whether `credentials` expire within the refresh margin.
+fn expiring(credentials: &Credentials) -> bool {
+    match credentials.expiry() {
+        Some(expiry) => expiry <= SystemTime::now() + REFRESH_MARGIN,
+        None => false,
+    }
+}
@@ -0,0 +1,62 @@
+use std::io::Write;
+
+use flate2::write::MultiGzDecoder;
+
+use crate::error::TransferError;
+
+/// Whether an object is stored decompressed when `auto_decompress` is on:
+/// it was uploaded with `Content-Encoding: gzip`, or its key ends in `.gz`.
+pub fn is_gzipped(object_key: &str, content_encoding: Option<&str>) -> bool {
+    content_encoding.is_some_and(|encoding| {
+        encoding.eq_ignore_ascii_case("gzip") || encoding.eq_ignore_ascii_case("x-gzip")
+    }) || object_key.ends_with(".gz")
+}
+
+/// Inflates a gzip body as it arrives, counting the bytes on either side.
+/// Bodies of several concatenated gzip members are inflated whole.
+pub struct Gunzip<'a> {
+    object_key: &'a str,
+    decoder: MultiGzDecoder<Vec<u8>>,
+    pub compressed_bytes: u64,
+    pub decompressed_bytes: u64,
+}
+
+impl<'a> Gunzip<'a> {
+    pub fn new(object_key: &'a str) -> Self {
+        Self {
+            object_key,
+            decoder: MultiGzDecoder::new(Vec::new()),
+            compressed_bytes: 0,
+            decompressed_bytes: 0,
+        }
+    }
+
+    /// Inflates the next chunk of the body, returning the output it completed.
+    pub fn inflate(&mut self, compressed: &[u8]) -> Result<Vec<u8>, TransferError> {
+        self.decoder
+            .write_all(compressed)
+            .map_err(|e| self.corrupt(e))?;
+        self.compressed_bytes += compressed.len() as u64;
+        Ok(self.take_output())
+    }
+
+    /// Checks that the body ended with a complete gzip member, returning the
+    /// last of the output.
+    pub fn finish(&mut self) -> Result<Vec<u8>, TransferError> {
+        self.decoder.try_finish().map_err(|e| self.corrupt(e))?;
+        Ok(self.take_output())
+    }
+
+    fn take_output(&mut self) -> Vec<u8> {
+        let output = std::mem::take(self.decoder.get_mut());
+        self.decompressed_bytes += output.len() as u64;
+        output
+    }
+
+    fn corrupt(&self, error: std::io::Error) -> TransferError {
+        TransferError::new(format!(
+            "Failed to decompress S3 object '{}': {}",
+            self.object_key, error
+        ))
+    }
+}
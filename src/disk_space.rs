@@ -0,0 +1,87 @@
+use std::path::{Path, PathBuf};
+
+use log::warn;
+use pyo3::types::PyAnyMethods;
+use pyo3::{PyResult, Python};
+
+use crate::exceptions::InsufficientSpaceError;
+
+/// Fails with `InsufficientSpaceError` when the filesystem of `directory` has
+/// less room than `downloads`, `(size, local_path)` pairs, would take. Files
+/// are written over in place, so a download only takes what its object
+/// outgrows the file it replaces by. Filesystems mounted below `directory`
+/// aren't told apart; where free space can't be read, nothing is checked.
+pub async fn ensure_space(directory: &Path, downloads: Vec<(u64, PathBuf)>) -> PyResult<()> {
+    let mut required = 0u64;
+    for (size, local_path) in downloads {
+        let replaced = tokio::fs::metadata(&local_path)
+            .await
+            .map_or(0, |metadata| metadata.len());
+        required = required.saturating_add(size.saturating_sub(replaced));
+    }
+
+    let free = match free_space(directory) {
+        Ok(free) => free,
+        Err(e) => {
+            warn!(
+                "Can't tell the free space of '{}', so it isn't checked: {}",
+                directory.display(),
+                e
+            );
+            return Ok(());
+        }
+    };
+    if required <= free {
+        return Ok(());
+    }
+    Err(Python::with_gil(|py| {
+        let error = InsufficientSpaceError::new_err(format!(
+            "Not enough space in '{}': the batch needs {} bytes, {} are free",
+            directory.display(),
+            required,
+            free
+        ));
+        let value = error.value(py);
+        let _ = value.setattr("path", directory.to_string_lossy().as_ref());
+        let _ = value.setattr("required_bytes", required);
+        let _ = value.setattr("available_bytes", free);
+        error
+    }))
+}
+
+/// Bytes unprivileged users may still write to the filesystem of `path`, or of
+/// its closest existing ancestor when `path` isn't created yet.
+fn free_space(path: &Path) -> std::io::Result<u64> {
+    let path = std::path::absolute(path)?;
+    let existing = path
+        .ancestors()
+        .find(|ancestor| ancestor.exists())
+        .unwrap_or(&path);
+    filesystem_free_space(existing)
+}
+
+#[cfg(unix)]
+fn filesystem_free_space(path: &Path) -> std::io::Result<u64> {
+    use std::ffi::CString;
+    use std::os::unix::ffi::OsStrExt;
+
+    let path = CString::new(path.as_os_str().as_bytes())?;
+    let mut stats = std::mem::MaybeUninit::<libc::statvfs>::uninit();
+    // SAFETY: `path` is NUL-terminated and `stats` is only read once
+    // `statvfs` reports it filled in.
+    if unsafe { libc::statvfs(path.as_ptr(), stats.as_mut_ptr()) } != 0 {
+        return Err(std::io::Error::last_os_error());
+    }
+    let stats = unsafe { stats.assume_init() };
+    // The widths of these fields vary between platforms.
+    #[allow(clippy::unnecessary_cast)]
+    Ok((stats.f_bavail as u64).saturating_mul(stats.f_frsize as u64))
+}
+
+#[cfg(not(unix))]
+fn filesystem_free_space(_path: &Path) -> std::io::Result<u64> {
+    Err(std::io::Error::new(
+        std::io::ErrorKind::Unsupported,
+        "only supported on POSIX systems",
+    ))
+}
@@ -0,0 +1,500 @@
+use std::collections::HashMap;
+use std::io;
+use std::pin::Pin;
+use std::task::{ready, Context, Poll};
+
+use aes::cipher::{BlockEncrypt, InnerIvInit, KeyInit, StreamCipher};
+use aes::Aes256;
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use bytes::Bytes;
+use ghash::universal_hash::UniversalHash;
+use ghash::GHash;
+use http_body::{Body, Frame, SizeHint};
+use pyo3::exceptions::PyValueError;
+use pyo3::{pyclass, pymethods, PyResult};
+use ring::rand::{SecureRandom, SystemRandom};
+use tokio::fs::File;
+use tokio::io::{AsyncRead, ReadBuf};
+
+use crate::error::TransferError;
+use crate::kms::KmsClient;
+use crate::s3_config::S3Config;
+
+// Object metadata of the S3 Encryption Client v2 format, which the AWS SDKs'
+// encryption clients read and write.
+const KEY: &str = "x-amz-key-v2";
+const LEGACY_KEY: &str = "x-amz-key";
+const IV: &str = "x-amz-iv";
+const CONTENT_ALGORITHM: &str = "x-amz-cek-alg";
+const WRAP_ALGORITHM: &str = "x-amz-wrap-alg";
+const MATERIAL_DESCRIPTION: &str = "x-amz-matdesc";
+const TAG_LENGTH: &str = "x-amz-tag-len";
+const UNENCRYPTED_LENGTH: &str = "x-amz-unencrypted-content-length";
+
+const AES_GCM: &str = "AES/GCM/NoPadding";
+const KMS_CONTEXT: &str = "kms+context";
+/// Encryption context entry binding a data key to the algorithm it encrypts with.
+const CONTEXT_ALGORITHM: &str = "aws:x-amz-cek-alg";
+
+const IV_LEN: usize = 12;
+/// The GCM tag, which ends the ciphertext.
+pub const TAG_LEN: usize = 16;
+/// Bytes of a file read and encrypted at a time.
+const READ_SIZE: usize = 64 * 1024;
+
+/// Client-side envelope encryption: every object is encrypted with AES-256-GCM
+/// under its own data key, which KMS wraps and which is stored, wrapped, in the
+/// object's metadata.
+///
+/// Objects are written in the S3 Encryption Client v2 format ("kms+context" key
+/// wrapping), so the AWS SDKs' encryption clients can read them and robinzhon can
+/// read theirs; this is not the message format of the AWS Encryption SDK. Bodies
+/// are encrypted and decrypted as they stream, so files aren't held in memory.
+/// Downloads of objects without encryption metadata are left as they are, unless
+/// `require_encryption` is set.
+#[pyclass(frozen, module = "robinzhon")]
+#[derive(Debug, Clone)]
+pub struct ClientEncryption {
+    /// KMS key the data keys of uploads are generated under. Downloads don't need
+    /// it: the wrapped key names its KMS key.
+    #[pyo3(get)]
+    pub kms_key_id: Option<String>,
+    /// Extra encryption context the data keys are bound to, stored in the clear
+    /// with the object.
+    #[pyo3(get)]
+    pub encryption_context: HashMap<String, String>,
+    /// KMS endpoint; the regional one of the client when `None`.
+    #[pyo3(get)]
+    pub kms_endpoint_url: Option<String>,
+    /// Whether downloads of objects without encryption metadata fail.
+    #[pyo3(get)]
+    pub require_encryption: bool,
+}
+
+impl ClientEncryption {
+    /// The envelope of a downloader or uploader with `s3_config`. Uploads need a
+    /// KMS key.
+    pub fn envelope(&self, s3_config: &S3Config, uploads: bool) -> PyResult<Envelope> {
+        if uploads && self.kms_key_id.is_none() {
+            return Err(PyValueError::new_err(
+                "client_encryption needs a kms_key_id to encrypt uploads",
+            ));
+        }
+        Ok(Envelope {
+            kms: KmsClient::new(s3_config, self.kms_endpoint_url.clone()),
+            key_id: self.kms_key_id.clone().unwrap_or_default(),
+            context: self.encryption_context.clone(),
+            require_encryption: self.require_encryption,
+        })
+    }
+}
+
+#[pymethods]
+impl ClientEncryption {
+    #[new]
+    #[pyo3(signature = (kms_key_id=None, encryption_context=None, kms_endpoint_url=None, require_encryption=false))]
+    fn new(
+        kms_key_id: Option<String>,
+        encryption_context: Option<HashMap<String, String>>,
+        kms_endpoint_url: Option<String>,
+        require_encryption: bool,
+    ) -> PyResult<Self> {
+        let encryption_context = encryption_context.unwrap_or_default();
+        if encryption_context.contains_key(CONTEXT_ALGORITHM) {
+            return Err(PyValueError::new_err(format!(
+                "'{}' is reserved in the encryption context",
+                CONTEXT_ALGORITHM
+            )));
+        }
+
+        Ok(Self {
+            kms_key_id,
+            encryption_context,
+            kms_endpoint_url,
+            require_encryption,
+        })
+    }
+
+    /// Pickles the settings as their constructor arguments.
+    fn __getnewargs__(
+        &self,
+    ) -> (
+        Option<String>,
+        HashMap<String, String>,
+        Option<String>,
+        bool,
+    ) {
+        (
+            self.kms_key_id.clone(),
+            self.encryption_context.clone(),
+            self.kms_endpoint_url.clone(),
+            self.require_encryption,
+        )
+    }
+
+    fn __repr__(&self) -> String {
+        let optional = |value: &Option<String>| {
+            value
+                .as_deref()
+                .map_or_else(|| "None".to_string(), |value| format!("{:?}", value))
+        };
+        format!(
+            "ClientEncryption(kms_key_id={}, encryption_context={:?}, kms_endpoint_url={}, require_encryption={})",
+            optional(&self.kms_key_id),
+            self.encryption_context,
+            optional(&self.kms_endpoint_url),
+            if self.require_encryption { "True" } else { "False" }
+        )
+    }
+}
+
+/// An encrypted object body, with the metadata to store it with.
+pub struct Sealed {
+    pub body: Vec<u8>,
+    pub metadata: HashMap<String, String>,
+}
+
+/// Encrypts and decrypts object bodies for a downloader or uploader.
+#[derive(Debug)]
+pub struct Envelope {
+    kms: KmsClient,
+    key_id: String,
+    context: HashMap<String, String>,
+    require_encryption: bool,
+}
+
+impl Envelope {
+    /// Starts encrypting a body under a new data key. The metadata returned is
+    /// `metadata` plus what decrypting the body takes, with its length when known.
+    pub async fn sealer(
+        &self,
+        metadata: Option<&HashMap<String, String>>,
+        unencrypted_length: Option<u64>,
+    ) -> Result<(Sealer, HashMap<String, String>), TransferError> {
+        let mut context = self.context.clone();
+        context.insert(CONTEXT_ALGORITHM.to_string(), AES_GCM.to_string());
+        let data_key = self.kms.generate_data_key(&self.key_id, &context).await?;
+
+        let mut iv = [0u8; IV_LEN];
+        SystemRandom::new()
+            .fill(&mut iv)
+            .map_err(|_| TransferError::new("Failed to generate an IV"))?;
+        let sealer = Sealer(Gcm::new(&data_key.plaintext, &iv)?);
+
+        let mut metadata = metadata.cloned().unwrap_or_default();
+        metadata.extend([
+            (KEY.to_string(), BASE64.encode(&data_key.ciphertext)),
+            (IV.to_string(), BASE64.encode(iv)),
+            (CONTENT_ALGORITHM.to_string(), AES_GCM.to_string()),
+            (WRAP_ALGORITHM.to_string(), KMS_CONTEXT.to_string()),
+            (
+                MATERIAL_DESCRIPTION.to_string(),
+                serde_json::to_string(&context).unwrap_or_default(),
+            ),
+            (TAG_LENGTH.to_string(), (TAG_LEN * 8).to_string()),
+        ]);
+        if let Some(unencrypted_length) = unencrypted_length {
+            metadata.insert(
+                UNENCRYPTED_LENGTH.to_string(),
+                unencrypted_length.to_string(),
+            );
+        }
+        Ok((sealer, metadata))
+    }
+
+    /// Encrypts `plaintext`, a body held in memory, under a new data key.
+    pub async fn seal(
+        &self,
+        mut plaintext: Vec<u8>,
+        metadata: Option<&HashMap<String, String>>,
+    ) -> Result<Sealed, TransferError> {
+        let (mut sealer, metadata) = self.sealer(metadata, Some(plaintext.len() as u64)).await?;
+        sealer.update(&mut plaintext);
+        plaintext.extend_from_slice(&sealer.finish());
+        Ok(Sealed {
+            body: plaintext,
+            metadata,
+        })
+    }
+
+    /// Starts decrypting the body of `object_key` with the data key in its
+    /// `metadata`. A body stored without one needs no decrypting, which is `None`,
+    /// unless encryption is required.
+    pub async fn opener(
+        &self,
+        object_key: &str,
+        metadata: Option<&HashMap<String, String>>,
+    ) -> Result<Option<Opener>, TransferError> {
+        let failed = |what: &str| decrypt_failed(object_key, what);
+        let metadata = match metadata {
+            Some(metadata) if metadata.contains_key(KEY) => metadata,
+            Some(metadata) if metadata.contains_key(LEGACY_KEY) => {
+                return Err(failed("encryption client v1 objects are not supported"))
+            }
+            _ if self.require_encryption => {
+                return Err(failed(
+                    "it has no encryption metadata and require_encryption is set",
+                ))
+            }
+            _ => return Ok(None),
+        };
+        let field = |name: &str| {
+            metadata
+                .get(name)
+                .map(String::as_str)
+                .ok_or_else(|| failed(&format!("no '{}' in its metadata", name)))
+        };
+
+        let algorithm = field(CONTENT_ALGORITHM)?;
+        if algorithm != AES_GCM {
+            return Err(failed(&format!(
+                "unsupported content algorithm '{}'",
+                algorithm
+            )));
+        }
+        let wrapping = field(WRAP_ALGORITHM)?;
+        if wrapping != KMS_CONTEXT {
+            return Err(failed(&format!("unsupported key wrapping '{}'", wrapping)));
+        }
+        let context: HashMap<String, String> =
+            serde_json::from_str(field(MATERIAL_DESCRIPTION)?)
+                .map_err(|e| failed(&format!("invalid '{}': {}", MATERIAL_DESCRIPTION, e)))?;
+        if context.get(CONTEXT_ALGORITHM).map(String::as_str) != Some(algorithm) {
+            return Err(failed(
+                "the encryption context doesn't match the content algorithm",
+            ));
+        }
+        let decode = |name: &str| {
+            BASE64
+                .decode(field(name)?)
+                .map_err(|e| failed(&format!("invalid '{}': {}", name, e)))
+        };
+        let iv: [u8; IV_LEN] = decode(IV)?
+            .try_into()
+            .map_err(|_| failed(&format!("invalid '{}'", IV)))?;
+
+        let data_key = self.kms.decrypt(&decode(KEY)?, &context).await?;
+        Ok(Some(Opener {
+            gcm: Gcm::new(&data_key, &iv)?,
+            held: Vec::with_capacity(TAG_LEN),
+            object_key: object_key.to_string(),
+        }))
+    }
+
+    /// Decrypts `body`, held in memory, with the data key in its `metadata`. A
+    /// body stored without one is returned as it is.
+    pub async fn open(
+        &self,
+        object_key: &str,
+        body: Vec<u8>,
+        metadata: Option<&HashMap<String, String>>,
+    ) -> Result<Vec<u8>, TransferError> {
+        match self.opener(object_key, metadata).await? {
+            Some(mut opener) => {
+                let plaintext = opener.update(&body);
+                opener.finish()?;
+                Ok(plaintext)
+            }
+            None => Ok(body),
+        }
+    }
+}
+
+/// Encrypts a body piece by piece; the tag `finish` returns ends the ciphertext.
+pub struct Sealer(Gcm);
+
+impl Sealer {
+    pub fn update(&mut self, plaintext: &mut [u8]) {
+        self.0.encrypt(plaintext);
+    }
+
+    pub fn finish(self) -> [u8; TAG_LEN] {
+        self.0.tag()
+    }
+}
+
+/// Decrypts a body piece by piece. The body ends with its tag, so the last
+/// `TAG_LEN` bytes received are held back until more arrive or it ends.
+pub struct Opener {
+    gcm: Gcm,
+    held: Vec<u8>,
+    object_key: String,
+}
+
+impl Opener {
+    /// Returns the plaintext of `ciphertext`, less what is held back. It is only
+    /// authentic once `finish` succeeds.
+    pub fn update(&mut self, ciphertext: &[u8]) -> Vec<u8> {
+        self.held.extend_from_slice(ciphertext);
+        let ready = self.held.len().saturating_sub(TAG_LEN);
+        let mut plaintext: Vec<u8> = self.held.drain(..ready).collect();
+        self.gcm.decrypt(&mut plaintext);
+        plaintext
+    }
+
+    /// Checks the tag that ended the body.
+    pub fn finish(self) -> Result<(), TransferError> {
+        if self.held.len() < TAG_LEN {
+            return Err(decrypt_failed(&self.object_key, "the body is too short"));
+        }
+        let tag = self.gcm.tag();
+        let difference = tag
+            .iter()
+            .zip(&self.held)
+            .fold(0, |difference, (a, b)| difference | (a ^ b));
+        if difference != 0 {
+            return Err(decrypt_failed(
+                &self.object_key,
+                "the body doesn't authenticate",
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// The body of a PUT that encrypts a file as it is read, ending with the tag.
+pub struct EncryptedFile {
+    file: File,
+    sealer: Option<Sealer>,
+    buffer: Box<[u8]>,
+    remaining: u64,
+}
+
+impl EncryptedFile {
+    /// Encrypts the `length` bytes of `file` with `sealer`.
+    pub fn new(file: File, length: u64, sealer: Sealer) -> Self {
+        Self {
+            file,
+            sealer: Some(sealer),
+            buffer: vec![0; READ_SIZE].into_boxed_slice(),
+            remaining: length,
+        }
+    }
+}
+
+impl Body for EncryptedFile {
+    type Data = Bytes;
+    type Error = io::Error;
+
+    fn poll_frame(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<Frame<Bytes>, io::Error>>> {
+        let this = self.get_mut();
+        if this.sealer.is_none() {
+            return Poll::Ready(None);
+        }
+        let mut read = ReadBuf::new(&mut this.buffer);
+        ready!(Pin::new(&mut this.file).poll_read(cx, &mut read))?;
+        let mut chunk = read.filled().to_vec();
+        // The length was sent ahead, so the file must not change size.
+        if chunk.len() as u64 > this.remaining || chunk.is_empty() && this.remaining > 0 {
+            return Poll::Ready(Some(Err(io::Error::other(
+                "the file changed size while it was uploaded",
+            ))));
+        }
+        if chunk.is_empty() {
+            let tag = this.sealer.take().map(Sealer::finish).unwrap_or_default();
+            return Poll::Ready(Some(Ok(Frame::data(Bytes::copy_from_slice(&tag)))));
+        }
+        this.remaining -= chunk.len() as u64;
+        if let Some(sealer) = &mut this.sealer {
+            sealer.update(&mut chunk);
+        }
+        Poll::Ready(Some(Ok(Frame::data(Bytes::from(chunk)))))
+    }
+
+    fn is_end_stream(&self) -> bool {
+        self.sealer.is_none()
+    }
+
+    fn size_hint(&self) -> SizeHint {
+        match self.sealer {
+            Some(_) => SizeHint::with_exact(self.remaining + TAG_LEN as u64),
+            None => SizeHint::with_exact(0),
+        }
+    }
+}
+
+/// AES-256-GCM over a body that comes in pieces, as the AWS encryption clients
+/// stream it: CTR encryption from the second counter block, authenticated by a
+/// GHASH of the ciphertext.
+struct Gcm {
+    keystream: ctr::Ctr32BE<Aes256>,
+    ghash: GHash,
+    /// Ciphertext short of a whole block, not hashed yet.
+    partial: Vec<u8>,
+    length: u64,
+    tag_mask: [u8; TAG_LEN],
+}
+
+impl Gcm {
+    fn new(data_key: &[u8], iv: &[u8; IV_LEN]) -> Result<Self, TransferError> {
+        let cipher = Aes256::new_from_slice(data_key)
+            .map_err(|_| TransferError::new("KMS returned a data key that isn't 256 bits"))?;
+        let mut hash_key = ghash::Block::default();
+        cipher.encrypt_block(&mut hash_key);
+        let mut counter = [0u8; 16];
+        counter[..IV_LEN].copy_from_slice(iv);
+        counter[15] = 1;
+        let mut tag_mask = counter.into();
+        cipher.encrypt_block(&mut tag_mask);
+        counter[15] = 2;
+        let keystream = ctr::CtrCore::inner_iv_init(cipher, &counter.into());
+        Ok(Self {
+            keystream: ctr::Ctr32BE::from_core(keystream),
+            ghash: GHash::new(&hash_key),
+            partial: Vec::with_capacity(16),
+            length: 0,
+            tag_mask: tag_mask.into(),
+        })
+    }
+
+    fn encrypt(&mut self, data: &mut [u8]) {
+        self.keystream.apply_keystream(data);
+        self.hash(data);
+    }
+
+    fn decrypt(&mut self, data: &mut [u8]) {
+        self.hash(data);
+        self.keystream.apply_keystream(data);
+    }
+
+    fn hash(&mut self, mut ciphertext: &[u8]) {
+        self.length += ciphertext.len() as u64;
+        if !self.partial.is_empty() {
+            let taken = (16 - self.partial.len()).min(ciphertext.len());
+            self.partial.extend_from_slice(&ciphertext[..taken]);
+            ciphertext = &ciphertext[taken..];
+            if self.partial.len() < 16 {
+                return;
+            }
+            self.ghash.update_padded(&self.partial);
+            self.partial.clear();
+        }
+        let whole = ciphertext.len() - ciphertext.len() % 16;
+        self.ghash.update_padded(&ciphertext[..whole]);
+        self.partial.extend_from_slice(&ciphertext[whole..]);
+    }
+
+    fn tag(mut self) -> [u8; TAG_LEN] {
+        self.ghash.update_padded(&self.partial);
+        // No additional data, then the bit length of the ciphertext.
+        let mut lengths = [0u8; 16];
+        lengths[8..].copy_from_slice(&(self.length * 8).to_be_bytes());
+        self.ghash.update(&[lengths.into()]);
+        let mut tag: [u8; TAG_LEN] = self.ghash.finalize().into();
+        for (byte, mask) in tag.iter_mut().zip(self.tag_mask) {
+            *byte ^= mask;
+        }
+        tag
+    }
+}
+
+fn decrypt_failed(object_key: &str, what: &str) -> TransferError {
+    TransferError::new(format!(
+        "Failed to decrypt S3 object '{}': {}",
+        object_key, what
+    ))
+}
@@ -0,0 +1,244 @@
+use std::error::Error;
+use std::fmt;
+
+use aws_sdk_s3::config::http::HttpResponse;
+use aws_sdk_s3::error::{ProvideErrorMetadata, SdkError};
+use pyo3::types::PyAnyMethods;
+use pyo3::{PyErr, Python};
+
+use crate::credentials::SsoLoginRequired;
+use crate::exceptions::{
+    AccessDeniedError, BucketNotFoundError, NoSuchKeyError, PreconditionFailedError,
+    RobinzhonError, SsoLoginRequiredError, TimeoutError,
+};
+
+/// Error codes S3 uses for conditions that usually clear up on their own.
+const RETRYABLE_CODES: &[&str] = &[
+    "InternalError",
+    "RequestTimeout",
+    "ServiceUnavailable",
+    "SlowDown",
+    "Throttling",
+    "ThrottlingException",
+    "RequestLimitExceeded",
+    // A conditional write raced another request on the same key.
+    "ConditionalRequestConflict",
+];
+
+/// HTTP statuses treated as transient server-side failures.
+const RETRYABLE_STATUSES: &[u16] = &[500, 502, 503, 504];
+
+/// Error codes S3 answers requests signed with expired credentials with.
+const EXPIRED_CREDENTIAL_CODES: &[&str] = &[
+    "ExpiredToken",
+    "ExpiredTokenException",
+    "TokenRefreshRequired",
+];
+
+/// Error codes S3 answers requests sent to the wrong region for their bucket with.
+const WRONG_REGION_CODES: &[&str] = &["PermanentRedirect", "AuthorizationHeaderMalformed"];
+
+/// A failed transfer, with enough detail to decide whether retrying may help.
+#[derive(Debug, Clone, Default)]
+pub struct TransferError {
+    pub message: String,
+    pub code: Option<String>,
+    pub http_status: Option<u16>,
+    pub retryable: bool,
+    pub timed_out: bool,
+    /// Whether S3 rejected the credentials the request was signed with as
+    /// expired.
+    pub expired_credentials: bool,
+    /// Whether credentials couldn't be loaded because the AWS SSO session they
+    /// come from has to be started again.
+    pub sso_login_required: bool,
+    /// Whether S3 answered that the bucket lives in another region.
+    pub wrong_region: bool,
+    /// Region of the bucket, when the response named it.
+    pub bucket_region: Option<String>,
+    /// `x-amz-request-id` of the response, which AWS support asks for. The IDs
+    /// are boxed so that results erring with this stay small.
+    pub request_id: Option<Box<str>>,
+    /// `x-amz-id-2` of the response, asked for along with the request ID.
+    pub extended_request_id: Option<Box<str>>,
+}
+
+impl TransferError {
+    /// A local failure (filesystem, invalid input...) that retrying won't fix.
+    pub fn new(message: impl Into<String>) -> Self {
+        Self {
+            message: message.into(),
+            ..Self::default()
+        }
+    }
+
+    /// A transient failure, such as a connection dropped while streaming a body.
+    pub fn retryable(message: impl Into<String>) -> Self {
+        Self {
+            message: message.into(),
+            retryable: true,
+            ..Self::default()
+        }
+    }
+
+    pub fn from_sdk<E>(context: impl fmt::Display, error: &SdkError<E, HttpResponse>) -> Self
+    where
+        E: ProvideErrorMetadata + Error + 'static,
+    {
+        let code = error.code().map(str::to_string);
+        let http_status = error.raw_response().map(|r| r.status().as_u16());
+        let timed_out = match error {
+            SdkError::TimeoutError(_) => true,
+            SdkError::DispatchFailure(failure) => failure.is_timeout(),
+            _ => code.as_deref() == Some("RequestTimeout"),
+        };
+        let retryable = match error {
+            SdkError::TimeoutError(_) | SdkError::ResponseError(_) => true,
+            SdkError::DispatchFailure(failure) => failure.is_io() || failure.is_timeout(),
+            SdkError::ServiceError(_) => {
+                http_status.is_some_and(|status| RETRYABLE_STATUSES.contains(&status))
+                    || code
+                        .as_deref()
+                        .is_some_and(|code| RETRYABLE_CODES.contains(&code))
+            }
+            _ => false,
+        };
+        let expired_credentials = code
+            .as_deref()
+            .is_some_and(|code| EXPIRED_CREDENTIAL_CODES.contains(&code));
+        let wrong_region = http_status == Some(301)
+            || code
+                .as_deref()
+                .is_some_and(|code| WRONG_REGION_CODES.contains(&code));
+        let header = |name: &str| {
+            error
+                .raw_response()
+                .and_then(|r| r.headers().get(name))
+                .map(Into::into)
+        };
+
+        Self {
+            message: format!("{}: {}", context, error_chain(error)),
+            code,
+            http_status,
+            retryable,
+            timed_out,
+            expired_credentials,
+            sso_login_required: caused_by::<SsoLoginRequired>(error),
+            wrong_region,
+            bucket_region: header("x-amz-bucket-region").map(String::from),
+            request_id: header("x-amz-request-id"),
+            extended_request_id: header("x-amz-id-2"),
+        }
+    }
+}
+
+impl TransferError {
+    /// A plain HTTP request that failed before a response came back, such as the
+    /// GET of a presigned URL.
+    pub fn from_http(context: impl fmt::Display, error: reqwest::Error) -> Self {
+        // The URL would leak the signature of presigned requests into messages.
+        let error = error.without_url();
+        Self {
+            message: format!("{}: {}", context, error_chain(&error)),
+            retryable: error.is_timeout()
+                || error.is_connect()
+                || error.is_request()
+                || error.is_body(),
+            timed_out: error.is_timeout(),
+            ..Self::default()
+        }
+    }
+
+    /// An error response to a plain HTTP request, described by its status and the
+    /// `<Code>` of the S3 error document in `body`, if any.
+    pub fn from_status(context: impl fmt::Display, status: u16, body: &str) -> Self {
+        let code = body
+            .split_once("<Code>")
+            .and_then(|(_, rest)| rest.split_once("</Code>"))
+            .map(|(code, _)| code.to_string());
+        let message = match &code {
+            Some(code) => format!("{}: HTTP {} ({})", context, status, code),
+            None => format!("{}: HTTP {}", context, status),
+        };
+        let retryable = RETRYABLE_STATUSES.contains(&status)
+            || code
+                .as_deref()
+                .is_some_and(|code| RETRYABLE_CODES.contains(&code));
+
+        Self {
+            message,
+            timed_out: code.as_deref() == Some("RequestTimeout"),
+            code,
+            http_status: Some(status),
+            retryable,
+            ..Self::default()
+        }
+    }
+}
+
+/// Renders an error and its sources as `outer: inner: root cause`.
+pub fn error_chain(error: &dyn Error) -> String {
+    let mut parts = vec![error.to_string()];
+    let mut source = error.source();
+    while let Some(cause) = source {
+        let part = cause.to_string();
+        // SDK wrappers often repeat their inner error's text verbatim.
+        if parts.last() != Some(&part) {
+            parts.push(part);
+        }
+        source = cause.source();
+    }
+    parts.join(": ")
+}
+
+/// Whether `error` or one of its causes is a `T`.
+fn caused_by<T: Error + 'static>(error: &(dyn Error + 'static)) -> bool {
+    let mut source = Some(error);
+    while let Some(cause) = source {
+        if cause.is::<T>() {
+            return true;
+        }
+        source = cause.source();
+    }
+    false
+}
+
+impl fmt::Display for TransferError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+impl From<TransferError> for PyErr {
+    fn from(error: TransferError) -> Self {
+        let ids = (error.request_id.clone(), error.extended_request_id.clone());
+        let code = error.code.as_deref();
+        // Error responses to HEAD requests carry no body, and so no code; the
+        // status is all there is to go on.
+        let err = if error.sso_login_required {
+            SsoLoginRequiredError::new_err(error.message)
+        } else if error.timed_out {
+            TimeoutError::new_err(error.message)
+        } else if code == Some("NoSuchBucket") {
+            BucketNotFoundError::new_err(error.message)
+        } else if matches!(code, Some("NoSuchKey") | Some("NotFound"))
+            || (code.is_none() && error.http_status == Some(404))
+        {
+            NoSuchKeyError::new_err(error.message)
+        } else if code == Some("AccessDenied") || error.http_status == Some(403) {
+            AccessDeniedError::new_err(error.message)
+        } else if code == Some("PreconditionFailed") || error.http_status == Some(412) {
+            PreconditionFailedError::new_err(error.message)
+        } else {
+            RobinzhonError::new_err(error.message)
+        };
+        // Exceptions carry the IDs of the response they came from, if any.
+        Python::with_gil(|py| {
+            let value = err.value(py);
+            let _ = value.setattr("request_id", ids.0.as_deref());
+            let _ = value.setattr("extended_request_id", ids.1.as_deref());
+        });
+        err
+    }
+}
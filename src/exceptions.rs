@@ -0,0 +1,95 @@
+use pyo3::create_exception;
+use pyo3::exceptions::PyRuntimeError;
+use pyo3::prelude::*;
+
+create_exception!(
+    robinzhon,
+    RobinzhonError,
+    PyRuntimeError,
+    "Base class for errors raised by robinzhon transfers."
+);
+create_exception!(
+    robinzhon,
+    NoSuchKeyError,
+    RobinzhonError,
+    "The requested S3 object does not exist."
+);
+create_exception!(
+    robinzhon,
+    AccessDeniedError,
+    RobinzhonError,
+    "The credentials in use are not allowed to perform the request."
+);
+create_exception!(
+    robinzhon,
+    BucketNotFoundError,
+    RobinzhonError,
+    "The requested bucket does not exist."
+);
+create_exception!(
+    robinzhon,
+    PreconditionFailedError,
+    RobinzhonError,
+    "The object did not meet a condition of the request, such as a key that already exists when uploading with `if_none_match`."
+);
+create_exception!(
+    robinzhon,
+    TimeoutError,
+    RobinzhonError,
+    "The request did not complete in time."
+);
+create_exception!(
+    robinzhon,
+    SsoLoginRequiredError,
+    RobinzhonError,
+    "The AWS SSO session credentials come from has expired or was never started; `aws sso login` starts a new one."
+);
+
+create_exception!(
+    robinzhon,
+    BatchAbortedError,
+    RobinzhonError,
+    "A batch stopped early because too many of its items failed, as `fail_fast` or `max_failures` asked."
+);
+
+create_exception!(
+    robinzhon,
+    PartialFailureError,
+    RobinzhonError,
+    "Some items of a batch failed and `raise_on_failure` was set; the batch's `Results` are attached as `results`."
+);
+
+create_exception!(
+    robinzhon,
+    InsufficientSpaceError,
+    RobinzhonError,
+    "A batch download would not fit in the free space of its destination; `required_bytes` and `available_bytes` tell by how much."
+);
+
+pub fn register(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    let py = m.py();
+    // Set on the instances raised for failed S3 requests; None for the others.
+    let base = py.get_type::<RobinzhonError>();
+    base.setattr("request_id", py.None())?;
+    base.setattr("extended_request_id", py.None())?;
+    m.add("RobinzhonError", py.get_type::<RobinzhonError>())?;
+    m.add("NoSuchKeyError", py.get_type::<NoSuchKeyError>())?;
+    m.add("AccessDeniedError", py.get_type::<AccessDeniedError>())?;
+    m.add("BucketNotFoundError", py.get_type::<BucketNotFoundError>())?;
+    m.add(
+        "PreconditionFailedError",
+        py.get_type::<PreconditionFailedError>(),
+    )?;
+    m.add("TimeoutError", py.get_type::<TimeoutError>())?;
+    m.add(
+        "SsoLoginRequiredError",
+        py.get_type::<SsoLoginRequiredError>(),
+    )?;
+    m.add("BatchAbortedError", py.get_type::<BatchAbortedError>())?;
+    m.add("PartialFailureError", py.get_type::<PartialFailureError>())?;
+    m.add(
+        "InsufficientSpaceError",
+        py.get_type::<InsufficientSpaceError>(),
+    )?;
+    Ok(())
+}
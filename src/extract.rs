@@ -0,0 +1,263 @@
+use std::fmt::Display;
+use std::fs::{self, File};
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+use std::time::Instant;
+
+use bytes::{Buf, Bytes};
+use flate2::read::MultiGzDecoder;
+use pyo3::exceptions::PyValueError;
+use pyo3::types::PyAnyMethods;
+use pyo3::{Bound, FromPyObject, PyAny, PyResult};
+use tokio::sync::mpsc;
+
+use crate::error::TransferError;
+use crate::results::{Completed, FailedTransfer, ItemReport, ItemStats, RustOperationResult};
+use crate::s3_downloader::contained_path;
+
+/// Chunks of the body queued for the thread unpacking it, which bounds the
+/// part of the archive held in memory.
+pub const QUEUED_CHUNKS: usize = 8;
+
+/// How an archive `download_and_extract` unpacks, or `archive_mode` packs, is
+/// packed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ArchiveFormat {
+    Tar,
+    TarGz,
+    Zip,
+}
+
+impl ArchiveFormat {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "tar" => Some(Self::Tar),
+            "tar.gz" | "tgz" => Some(Self::TarGz),
+            "zip" => Some(Self::Zip),
+            _ => None,
+        }
+    }
+
+    /// The name of the format, which is also the extension of its archives.
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Tar => "tar",
+            Self::TarGz => "tar.gz",
+            Self::Zip => "zip",
+        }
+    }
+}
+
+impl<'py> FromPyObject<'py> for ArchiveFormat {
+    fn extract_bound(value: &Bound<'py, PyAny>) -> PyResult<Self> {
+        let name = value.extract::<String>()?;
+        Self::from_name(&name).ok_or_else(|| {
+            PyValueError::new_err(format!(
+                "Invalid archive format '{}', expected 'tar', 'tar.gz' or 'zip'",
+                name
+            ))
+        })
+    }
+}
+
+/// The body of an object as the blocking reader of an extractor sees it,
+/// chunk by chunk as the runtime receives them. It ends early if the
+/// runtime stops sending, which the extractor takes for a truncated archive.
+pub struct BodyReader {
+    chunks: mpsc::Receiver<Bytes>,
+    current: Bytes,
+}
+
+impl BodyReader {
+    pub fn new(chunks: mpsc::Receiver<Bytes>) -> Self {
+        Self {
+            chunks,
+            current: Bytes::new(),
+        }
+    }
+}
+
+impl Read for BodyReader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        while self.current.is_empty() {
+            match self.chunks.blocking_recv() {
+                Some(chunk) => self.current = chunk,
+                None => return Ok(0),
+            }
+        }
+        let length = buf.len().min(self.current.len());
+        self.current.copy_to_slice(&mut buf[..length]);
+        Ok(length)
+    }
+}
+
+/// Unpacks the archive read from `body` below `dest_dir`, recording every file
+/// as an item: its name in the archive as the source, its path as the
+/// destination. Members outside `dest_dir` fail; links and special files are
+/// skipped, as following them could write anywhere.
+pub fn unpack(
+    format: ArchiveFormat,
+    body: impl Read,
+    dest_dir: &Path,
+    object_key: &str,
+) -> Result<RustOperationResult, TransferError> {
+    let mut unpacker = Unpacker {
+        dest_dir,
+        object_key,
+        result: RustOperationResult::default(),
+        index: 0,
+    };
+    match format {
+        ArchiveFormat::Tar => unpacker.tar(body)?,
+        ArchiveFormat::TarGz => unpacker.tar(MultiGzDecoder::new(body))?,
+        ArchiveFormat::Zip => unpacker.zip(body)?,
+    }
+    Ok(unpacker.result)
+}
+
+struct Unpacker<'a> {
+    dest_dir: &'a Path,
+    object_key: &'a str,
+    result: RustOperationResult,
+    index: usize,
+}
+
+impl Unpacker<'_> {
+    fn tar(&mut self, body: impl Read) -> Result<(), TransferError> {
+        let mut archive = tar::Archive::new(body);
+        for entry in archive.entries().map_err(|e| self.corrupt(e))? {
+            let mut entry = entry.map_err(|e| self.corrupt(e))?;
+            let name = entry
+                .path()
+                .map_err(|e| self.corrupt(e))?
+                .to_string_lossy()
+                .into_owned();
+            let entry_type = entry.header().entry_type();
+            if entry_type.is_dir() {
+                self.directory(&name)?;
+            } else if entry_type.is_file() || entry_type.is_contiguous() {
+                self.file(&name, &mut entry)?;
+            } else {
+                self.skip(&name);
+            }
+        }
+        Ok(())
+    }
+
+    // Members are read from their local headers, as the central directory
+    // only comes at the end of the archive.
+    fn zip(&mut self, mut body: impl Read) -> Result<(), TransferError> {
+        while let Some(mut member) =
+            zip::read::read_zipfile_from_stream(&mut body).map_err(|e| self.corrupt(e))?
+        {
+            let name = member.name().to_string();
+            if member.is_dir() {
+                self.directory(&name)?;
+            } else if member.is_file() {
+                self.file(&name, &mut member)?;
+            } else {
+                self.skip(&name);
+            }
+        }
+        Ok(())
+    }
+
+    fn directory(&mut self, name: &str) -> Result<(), TransferError> {
+        // Archives often list their root, as `./`, which `dest_dir` already is.
+        if name
+            .split('/')
+            .all(|segment| segment.is_empty() || segment == ".")
+        {
+            return Ok(());
+        }
+        let Some(path) = self.target(name) else {
+            return Ok(());
+        };
+        fs::create_dir_all(&path).map_err(|e| {
+            TransferError::new(format!(
+                "Failed to create directory '{}': {}",
+                path.display(),
+                e
+            ))
+        })
+    }
+
+    fn file(&mut self, name: &str, contents: &mut impl Read) -> Result<(), TransferError> {
+        let Some(path) = self.target(name) else {
+            return Ok(());
+        };
+        let started = Instant::now();
+        let written = path
+            .parent()
+            .map_or(Ok(()), fs::create_dir_all)
+            .and_then(|()| File::create(&path))
+            .and_then(|mut file| io::copy(contents, &mut file));
+        let bytes = match written {
+            Ok(bytes) => bytes,
+            Err(e) => {
+                let _ = fs::remove_file(&path);
+                return Err(TransferError::new(format!(
+                    "Failed to extract '{}' of '{}' to '{}': {}",
+                    name,
+                    self.object_key,
+                    path.display(),
+                    e
+                )));
+            }
+        };
+
+        let local_path = path.to_string_lossy().into_owned();
+        let stats = ItemStats::since(started, bytes, 1);
+        let report = self.report(name, &local_path, stats);
+        self.result
+            .record_transfer(report, Ok::<_, FailedTransfer>(local_path));
+        Ok(())
+    }
+
+    fn skip(&mut self, name: &str) {
+        if let Some(path) = self.target(name) {
+            let local_path = path.to_string_lossy().into_owned();
+            let report = self.report(name, &local_path, ItemStats::default());
+            self.result.record_transfer(
+                report,
+                Ok::<_, FailedTransfer>(Completed::Skipped(local_path)),
+            );
+        }
+    }
+
+    /// Where the member `name` goes, or `None` after recording its failure when
+    /// its path would leave `dest_dir`.
+    fn target(&mut self, name: &str) -> Option<PathBuf> {
+        let path = contained_path(self.dest_dir, name);
+        if path.is_none() {
+            let error = TransferError::new(format!(
+                "Archive member '{}' does not map to a file inside '{}'",
+                name,
+                self.dest_dir.display()
+            ));
+            let report = self.report(name, "", ItemStats::default());
+            let failure = FailedTransfer::new(name, (name.to_string(), String::new()), error);
+            self.result
+                .record_transfer(report, Err::<String, _>(failure));
+        }
+        path
+    }
+
+    /// The report of the next member, in the order the archive lists them.
+    fn report(&mut self, name: &str, local_path: &str, stats: ItemStats) -> ItemReport {
+        self.index += 1;
+        ItemReport {
+            index: self.index - 1,
+            source: name.to_string(),
+            destination: local_path.to_string(),
+            stats,
+        }
+    }
+
+    fn corrupt(&self, error: impl Display) -> TransferError {
+        TransferError::new(format!(
+            "Failed to extract S3 object '{}': {}",
+            self.object_key, error
+        ))
+    }
+}
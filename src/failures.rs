@@ -0,0 +1,125 @@
+use futures::stream::{Stream, StreamExt};
+use pyo3::exceptions::PyValueError;
+use pyo3::types::PyAnyMethods;
+use pyo3::{Bound, Py, PyErr, PyResult, Python};
+use tokio::sync::watch;
+
+use crate::error::TransferError;
+use crate::exceptions::{BatchAbortedError, PartialFailureError};
+use crate::results::{Results, RustOperationResult};
+
+/// Failed items after which a batch stops, from the `fail_fast` and
+/// `max_failures` arguments of batch methods; `None` runs every item.
+pub fn failure_limit(fail_fast: bool, max_failures: Option<usize>) -> PyResult<Option<usize>> {
+    match (fail_fast, max_failures) {
+        (_, Some(0)) => Err(PyValueError::new_err(
+            "max_failures must be greater than zero",
+        )),
+        (true, Some(max_failures)) if max_failures != 1 => Err(PyValueError::new_err(
+            "fail_fast stops at the first failure; pass either fail_fast or max_failures",
+        )),
+        (true, _) => Ok(Some(1)),
+        (false, max_failures) => Ok(max_failures),
+    }
+}
+
+/// Stops a batch from dispatching more items once `limit` of them failed. Items
+/// already in flight still finish, so a stopped batch may end with a few more
+/// failures than its limit.
+#[derive(Debug)]
+pub struct FailureLimit {
+    limit: Option<usize>,
+    reached: watch::Sender<bool>,
+}
+
+impl FailureLimit {
+    pub fn new(limit: Option<usize>) -> Self {
+        Self {
+            limit,
+            reached: watch::Sender::new(false),
+        }
+    }
+
+    /// Takes the failures of the batch so far into account.
+    pub fn update(&self, failures: usize) {
+        if self.limit.is_some_and(|limit| failures >= limit) {
+            self.reached.send_replace(true);
+        }
+    }
+
+    /// The error a batch stopped by the limit raises, if it was.
+    pub fn error(&self, result: &RustOperationResult) -> Option<PyErr> {
+        let first = result
+            .failed
+            .first()
+            .map(|failure| (failure.name.as_str(), &failure.error));
+        self.error_after(result.failed.len(), first)
+    }
+
+    /// `error`, for batches that only keep count of their failures and the
+    /// first of them.
+    pub fn error_after(
+        &self,
+        failed: usize,
+        first: Option<(&str, &TransferError)>,
+    ) -> Option<PyErr> {
+        if !*self.reached.borrow() {
+            return None;
+        }
+        let first = first
+            .map(|(name, error)| format!("; first: {}: {}", name, error))
+            .unwrap_or_default();
+        Some(BatchAbortedError::new_err(format!(
+            "Batch stopped after {} failed items{}",
+            failed, first
+        )))
+    }
+}
+
+/// Ends `stream` once `limit` is reached, without pulling another item from it.
+pub fn until_limit<'a, S>(
+    stream: S,
+    limit: &'a FailureLimit,
+) -> impl Stream<Item = S::Item> + Send + 'a
+where
+    S: Stream + Send + 'a,
+    S::Item: Send,
+{
+    let mut reached = limit.reached.subscribe();
+    stream.take_until(async move {
+        // The sender lives as long as the limit, so the channel never closes.
+        let _ = reached.wait_for(|reached| *reached).await;
+    })
+}
+
+/// `results`, or the `PartialFailureError` carrying them when `raise_on_failure`
+/// is set and some of their items failed.
+pub fn checked(raise_on_failure: bool, results: Results) -> PyResult<Results> {
+    if !raise_on_failure || results.failed.is_empty() {
+        return Ok(results);
+    }
+    Python::with_gil(|py| Err(partial_failure(Py::new(py, results)?.bind(py))))
+}
+
+/// The `PartialFailureError` a batch with failed items raises, with its
+/// `results` attached.
+pub fn partial_failure(results: &Bound<'_, Results>) -> PyErr {
+    let message = {
+        let results = results.borrow();
+        let total = results.successful.len() + results.failed.len() + results.skipped.len();
+        let first = results
+            .failed
+            .first()
+            .map(|item| format!("; first: {}: {}", item.key, item.error_message))
+            .unwrap_or_default();
+        format!(
+            "{} of {} items failed{}",
+            results.failed.len(),
+            total,
+            first
+        )
+    };
+    let error = PartialFailureError::new_err(message);
+    let _ = error.value(results.py()).setattr("results", results);
+    error
+}
@@ -0,0 +1,171 @@
+use globset::{Glob, GlobSet, GlobSetBuilder};
+use pyo3::exceptions::PyValueError;
+use pyo3::PyResult;
+use regex::Regex;
+
+use crate::conditions::Timestamp;
+use crate::listing::ObjectInfo;
+
+/// Include/exclude glob patterns selecting which paths of a bulk transfer to keep.
+///
+/// Paths are relative to the transfer root, with `/` separators. `*` matches
+/// across separators, so `*.parquet` selects files at any depth.
+#[derive(Debug, Clone, Default)]
+pub struct PathFilter {
+    include: Option<GlobSet>,
+    exclude: Option<GlobSet>,
+}
+
+impl PathFilter {
+    pub fn new(include: Option<Vec<String>>, exclude: Option<Vec<String>>) -> PyResult<Self> {
+        Ok(Self {
+            include: include.map(|patterns| build_set(&patterns)).transpose()?,
+            exclude: exclude.map(|patterns| build_set(&patterns)).transpose()?,
+        })
+    }
+
+    /// Whether `path` matches an include pattern (or none were given) and no
+    /// exclude pattern.
+    pub fn matches(&self, path: &str) -> bool {
+        self.include.as_ref().is_none_or(|set| set.is_match(path))
+            && !self.exclude.as_ref().is_some_and(|set| set.is_match(path))
+    }
+}
+
+fn build_set(patterns: &[String]) -> PyResult<GlobSet> {
+    let mut builder = GlobSetBuilder::new();
+    for pattern in patterns {
+        let glob = Glob::new(pattern).map_err(|e| {
+            PyValueError::new_err(format!("Invalid glob pattern '{}': {}", pattern, e))
+        })?;
+        builder.add(glob);
+    }
+    builder
+        .build()
+        .map_err(|e| PyValueError::new_err(format!("Invalid glob patterns: {}", e)))
+}
+
+/// Size, modification time and key conditions selecting which objects of a
+/// listing to keep, checked as each page arrives.
+///
+/// `key_pattern` is searched for anywhere in the key, as `re.search` does.
+/// When it's anchored with `^` and starts with literal text, only keys
+/// beginning with that text are listed at all.
+#[derive(Debug, Clone, Default)]
+pub struct ObjectFilter {
+    min_size: Option<u64>,
+    max_size: Option<u64>,
+    /// Seconds since the Unix epoch.
+    modified_after: Option<f64>,
+    /// Seconds since the Unix epoch.
+    modified_before: Option<f64>,
+    key_pattern: Option<Regex>,
+}
+
+impl ObjectFilter {
+    pub fn new(
+        min_size: Option<u64>,
+        max_size: Option<u64>,
+        modified_after: Option<Timestamp>,
+        modified_before: Option<Timestamp>,
+        key_pattern: Option<String>,
+    ) -> PyResult<Self> {
+        if let (Some(min_size), Some(max_size)) = (min_size, max_size) {
+            if min_size > max_size {
+                return Err(PyValueError::new_err(
+                    "min_size must not be larger than max_size",
+                ));
+            }
+        }
+        let modified_after = modified_after.map(|Timestamp(time)| time.as_secs_f64());
+        let modified_before = modified_before.map(|Timestamp(time)| time.as_secs_f64());
+        if let (Some(after), Some(before)) = (modified_after, modified_before) {
+            if after >= before {
+                return Err(PyValueError::new_err(
+                    "modified_after must be earlier than modified_before",
+                ));
+            }
+        }
+        let key_pattern = key_pattern
+            .map(|pattern| {
+                Regex::new(&pattern).map_err(|e| {
+                    PyValueError::new_err(format!("Invalid key pattern '{}': {}", pattern, e))
+                })
+            })
+            .transpose()?;
+
+        Ok(Self {
+            min_size,
+            max_size,
+            modified_after,
+            modified_before,
+            key_pattern,
+        })
+    }
+
+    /// Whether `object` passes every condition. Common prefixes have no size or
+    /// time of their own, so they are always kept.
+    pub fn matches(&self, object: &ObjectInfo) -> bool {
+        if object.is_prefix {
+            return true;
+        }
+        let modified = |check: fn(f64, f64) -> bool, bound: Option<f64>| {
+            bound.is_none_or(|bound| object.last_modified.is_some_and(|time| check(time, bound)))
+        };
+        self.min_size.is_none_or(|min_size| object.size >= min_size)
+            && self.max_size.is_none_or(|max_size| object.size <= max_size)
+            && modified(|time, after| time > after, self.modified_after)
+            && modified(|time, before| time < before, self.modified_before)
+            && self
+                .key_pattern
+                .as_ref()
+                .is_none_or(|pattern| pattern.is_match(&object.key))
+    }
+
+    /// The prefix to list for `prefix`: the literal start of an anchored key
+    /// pattern when it extends `prefix`, as no other key could match.
+    pub fn narrowed_prefix(&self, prefix: Option<&str>) -> Option<String> {
+        let prefix = prefix.unwrap_or_default();
+        match self
+            .key_pattern
+            .as_ref()
+            .map(|pattern| literal_prefix(pattern.as_str()))
+        {
+            Some(literal) if literal.len() > prefix.len() && literal.starts_with(prefix) => {
+                Some(literal)
+            }
+            _ => Some(prefix.to_string()).filter(|prefix| !prefix.is_empty()),
+        }
+    }
+}
+
+/// The text every match of `pattern` starts with when it's anchored with `^`,
+/// or an empty string. Patterns with alternations are left alone, as each
+/// branch may start differently.
+fn literal_prefix(pattern: &str) -> String {
+    let mut literal = String::new();
+    let Some(rest) = pattern.strip_prefix('^') else {
+        return literal;
+    };
+    if pattern.contains('|') {
+        return literal;
+    }
+    let mut chars = rest.chars().peekable();
+    while let Some(c) = chars.next() {
+        let next = match c {
+            '\\' => match chars.next() {
+                Some(escaped) if escaped.is_ascii_punctuation() => escaped,
+                _ => break,
+            },
+            '.' | '+' | '*' | '?' | '(' | ')' | '[' | ']' | '{' | '}' | '^' | '$' => break,
+            c => c,
+        };
+        // A quantifier allowing zero repetitions makes the last character
+        // optional.
+        if matches!(chars.peek(), Some('?' | '*' | '{')) {
+            break;
+        }
+        literal.push(next);
+    }
+    literal
+}
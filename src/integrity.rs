@@ -0,0 +1,160 @@
+use aws_sdk_s3::operation::complete_multipart_upload::CompleteMultipartUploadOutput;
+use aws_sdk_s3::operation::get_object::builders::GetObjectFluentBuilder;
+use aws_sdk_s3::operation::get_object::GetObjectOutput;
+use aws_sdk_s3::operation::put_object::PutObjectOutput;
+use aws_sdk_s3::types::{ChecksumAlgorithm, ChecksumMode, ServerSideEncryption};
+use md5::{Digest, Md5};
+
+use crate::error::TransferError;
+use crate::results::{StoredChecksum, StoredObject};
+use crate::sync::plain_md5;
+
+/// MD5 of a download computed as its bytes arrive, checked against the ETag of
+/// the object once it is complete.
+///
+/// Only ETags that are plain MD5s can be checked this way; multipart ETags and
+/// those of SSE-KMS or SSE-C objects are left to the checksums S3 stored with
+/// the object, which the SDK validates itself when `request_checksums` asks for
+/// them.
+pub struct BodyDigest {
+    expected: Option<String>,
+    hasher: Md5,
+}
+
+impl BodyDigest {
+    /// A digest checking against `etag` when `verify` is set, or a no-op.
+    pub fn new(etag: Option<&str>, verify: bool) -> Self {
+        Self {
+            expected: etag
+                .filter(|_| verify)
+                .and_then(plain_md5)
+                .map(str::to_ascii_lowercase),
+            hasher: Md5::new(),
+        }
+    }
+
+    pub fn update(&mut self, bytes: &[u8]) {
+        if self.expected.is_some() {
+            self.hasher.update(bytes);
+        }
+    }
+
+    /// Fails with a retryable error when the body doesn't match its ETag.
+    pub fn verify(self, object_key: &str) -> Result<(), TransferError> {
+        let Some(expected) = self.expected else {
+            return Ok(());
+        };
+        let actual = hex(&self.hasher.finalize());
+        check_md5(object_key, &expected, &actual)
+    }
+}
+
+/// The ETag of a GET response, unless the object is encrypted with SSE-KMS or
+/// SSE-C: S3 doesn't derive those ETags from the MD5 of the body.
+pub fn digest_etag(output: &GetObjectOutput) -> Option<&str> {
+    output.e_tag().filter(|_| {
+        !encrypted_etag(
+            output
+                .server_side_encryption()
+                .map(ServerSideEncryption::as_str),
+            output.sse_customer_algorithm(),
+        )
+    })
+}
+
+/// Whether an object's server-side encryption keeps its ETag from being an MD5.
+pub fn encrypted_etag(encryption: Option<&str>, customer_algorithm: Option<&str>) -> bool {
+    customer_algorithm.is_some() || matches!(encryption, Some("aws:kms" | "aws:kms:dsse"))
+}
+
+/// Compares the MD5 of a downloaded body with the one its ETag stands for.
+pub fn check_md5(object_key: &str, expected: &str, actual: &str) -> Result<(), TransferError> {
+    if expected.eq_ignore_ascii_case(actual) {
+        Ok(())
+    } else {
+        Err(TransferError::retryable(format!(
+            "Checksum mismatch for '{}': expected MD5 {}, got {}",
+            object_key, expected, actual
+        )))
+    }
+}
+
+/// Fails with a retryable error when the bytes received don't add up to the
+/// Content-Length of the response, as when the connection drops mid-body.
+pub fn check_length(
+    object_key: &str,
+    content_length: Option<u64>,
+    received: u64,
+) -> Result<(), TransferError> {
+    match content_length {
+        Some(expected) if expected != received => Err(TransferError::retryable(format!(
+            "Incomplete download of '{}': Content-Length is {} bytes, received {}",
+            object_key, expected, received
+        ))),
+        _ => Ok(()),
+    }
+}
+
+/// Asks S3 for the checksums stored with the object, which the SDK then
+/// validates as the body is read.
+pub fn request_checksums(request: GetObjectFluentBuilder, verify: bool) -> GetObjectFluentBuilder {
+    if verify {
+        request.checksum_mode(ChecksumMode::Enabled)
+    } else {
+        request
+    }
+}
+
+/// What S3 reported storing with a PUT object.
+pub fn put_stored(output: &PutObjectOutput) -> StoredObject {
+    StoredObject {
+        encryption: output
+            .server_side_encryption()
+            .map(ServerSideEncryption::as_str)
+            .map(str::to_string),
+        checksum: put_checksum(output),
+        etag: output.e_tag.clone(),
+        version_id: output.version_id.clone(),
+    }
+}
+
+/// What S3 reported storing with a completed multipart upload.
+pub fn multipart_stored(output: &CompleteMultipartUploadOutput) -> StoredObject {
+    StoredObject {
+        encryption: output
+            .server_side_encryption()
+            .map(ServerSideEncryption::as_str)
+            .map(str::to_string),
+        checksum: stored_checksum([
+            (ChecksumAlgorithm::Crc32, output.checksum_crc32()),
+            (ChecksumAlgorithm::Crc32C, output.checksum_crc32_c()),
+            (ChecksumAlgorithm::Crc64Nvme, output.checksum_crc64_nvme()),
+            (ChecksumAlgorithm::Sha1, output.checksum_sha1()),
+            (ChecksumAlgorithm::Sha256, output.checksum_sha256()),
+        ]),
+        etag: output.e_tag.clone(),
+        version_id: output.version_id.clone(),
+    }
+}
+
+/// The checksum S3 reported storing with a PUT object, if any.
+fn put_checksum(output: &PutObjectOutput) -> Option<StoredChecksum> {
+    stored_checksum([
+        (ChecksumAlgorithm::Crc32, output.checksum_crc32()),
+        (ChecksumAlgorithm::Crc32C, output.checksum_crc32_c()),
+        (ChecksumAlgorithm::Crc64Nvme, output.checksum_crc64_nvme()),
+        (ChecksumAlgorithm::Sha1, output.checksum_sha1()),
+        (ChecksumAlgorithm::Sha256, output.checksum_sha256()),
+    ])
+}
+
+/// The first of the checksums a response carried.
+fn stored_checksum(checksums: [(ChecksumAlgorithm, Option<&str>); 5]) -> Option<StoredChecksum> {
+    checksums.into_iter().find_map(|(algorithm, value)| {
+        value.map(|value| (algorithm.as_str().to_string(), value.to_string()))
+    })
+}
+
+pub fn hex(digest: &[u8]) -> String {
+    digest.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
@@ -0,0 +1,114 @@
+use std::collections::BTreeMap;
+use std::sync::Arc;
+
+use aws_sdk_s3::config::interceptors::{
+    BeforeDeserializationInterceptorContextRef, BeforeTransmitInterceptorContextMut,
+};
+use aws_sdk_s3::config::{ConfigBag, Intercept, RuntimeComponents};
+use aws_sdk_s3::error::BoxError;
+use hyper::header::{HeaderName, HeaderValue};
+use pyo3::exceptions::{PyTypeError, PyValueError};
+use pyo3::types::{PyAnyMethods, PyDict, PyDictMethods};
+use pyo3::{Bound, Py, PyAny, PyResult, Python};
+
+/// Headers an `S3Config`'s client adds to every request, and Python hooks that
+/// see each request before it is signed and each response as it arrives.
+#[derive(Debug, Clone, Default)]
+pub struct RequestHooks {
+    pub headers: BTreeMap<String, String>,
+    /// Called with `(method, url, headers)`; may return headers to add.
+    pub before_request: Option<Arc<Py<PyAny>>>,
+    /// Called with `(status, headers)` of every response.
+    pub after_response: Option<Arc<Py<PyAny>>>,
+}
+
+impl RequestHooks {
+    /// Checks the hook arguments given from Python.
+    pub fn from_args(
+        headers: Option<BTreeMap<String, String>>,
+        before_request: Option<Bound<'_, PyAny>>,
+        after_response: Option<Bound<'_, PyAny>>,
+    ) -> PyResult<Self> {
+        let headers = headers.unwrap_or_default();
+        for (name, value) in &headers {
+            if HeaderName::from_bytes(name.as_bytes()).is_err()
+                || HeaderValue::from_str(value).is_err()
+            {
+                return Err(PyValueError::new_err(format!(
+                    "Invalid header '{}: {}'",
+                    name, value
+                )));
+            }
+        }
+        let hook = |name: &str, hook: Option<Bound<'_, PyAny>>| match hook {
+            Some(hook) if !hook.is_callable() => {
+                Err(PyTypeError::new_err(format!("{} must be callable", name)))
+            }
+            hook => Ok(hook.map(|hook| Arc::new(hook.unbind()))),
+        };
+        Ok(Self {
+            headers,
+            before_request: hook("before_request", before_request)?,
+            after_response: hook("after_response", after_response)?,
+        })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.headers.is_empty() && self.before_request.is_none() && self.after_response.is_none()
+    }
+}
+
+impl Intercept for RequestHooks {
+    fn name(&self) -> &'static str {
+        "RequestHooks"
+    }
+
+    // Headers go in before signing, as S3 rejects unsigned `x-amz-*` ones.
+    fn modify_before_signing(
+        &self,
+        context: &mut BeforeTransmitInterceptorContextMut<'_>,
+        _runtime_components: &RuntimeComponents,
+        _cfg: &mut ConfigBag,
+    ) -> Result<(), BoxError> {
+        let request = context.request_mut();
+        for (name, value) in &self.headers {
+            request
+                .headers_mut()
+                .try_insert(name.clone(), value.clone())?;
+        }
+        if let Some(hook) = &self.before_request {
+            let added = Python::with_gil(|py| {
+                let headers = PyDict::new(py);
+                for (name, value) in request.headers().iter() {
+                    headers.set_item(name, value)?;
+                }
+                hook.call1(py, (request.method(), request.uri(), headers))?
+                    .extract::<Option<BTreeMap<String, String>>>(py)
+            })?;
+            for (name, value) in added.unwrap_or_default() {
+                request.headers_mut().try_insert(name, value)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn read_after_transmit(
+        &self,
+        context: &BeforeDeserializationInterceptorContextRef<'_>,
+        _runtime_components: &RuntimeComponents,
+        _cfg: &mut ConfigBag,
+    ) -> Result<(), BoxError> {
+        if let Some(hook) = &self.after_response {
+            let response = context.response();
+            Python::with_gil(|py| {
+                let headers = PyDict::new(py);
+                for (name, value) in response.headers().iter() {
+                    headers.set_item(name, value)?;
+                }
+                hook.call1(py, (response.status().as_u16(), headers))
+                    .map(drop)
+            })?;
+        }
+        Ok(())
+    }
+}
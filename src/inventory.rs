@@ -0,0 +1,232 @@
+use std::io::{BufRead, BufReader};
+
+use aws_smithy_types::date_time::{DateTime, Format};
+use bytes::Bytes;
+use flate2::read::MultiGzDecoder;
+use parquet::file::reader::{FileReader, SerializedFileReader};
+use parquet::record::Field;
+use serde::Deserialize;
+
+use crate::error::TransferError;
+use crate::listing::ObjectInfo;
+use crate::transfer_manifest::split_csv;
+
+/// The `manifest.json` S3 Inventory writes with every report, naming the data
+/// files the report is split into.
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct InventoryManifest {
+    /// Bucket the report lists.
+    pub source_bucket: String,
+    file_format: String,
+    /// Comma-separated column names of CSV reports; a Parquet schema otherwise.
+    file_schema: String,
+    files: Vec<InventoryFile>,
+}
+
+#[derive(Debug, Deserialize)]
+struct InventoryFile {
+    key: String,
+}
+
+impl InventoryManifest {
+    pub fn parse(body: &[u8], manifest_key: &str) -> Result<Self, TransferError> {
+        serde_json::from_slice(body).map_err(|e| {
+            TransferError::new(format!(
+                "Invalid inventory manifest '{}': {}",
+                manifest_key, e
+            ))
+        })
+    }
+
+    /// Keys of the data files of the report, stored next to the manifest.
+    pub fn data_keys(&self) -> Vec<String> {
+        self.files.iter().map(|file| file.key.clone()).collect()
+    }
+
+    /// The reader of the data files of the report.
+    pub fn reader(&self, manifest_key: &str) -> Result<InventoryReader, TransferError> {
+        match self.file_format.to_ascii_lowercase().as_str() {
+            "csv" => {
+                let columns: Vec<String> = self.file_schema.split(',').map(column_name).collect();
+                if !columns.iter().any(|column| column == "key") {
+                    return Err(TransferError::new(format!(
+                        "Inventory manifest '{}' has no Key column",
+                        manifest_key
+                    )));
+                }
+                Ok(InventoryReader::Csv { columns })
+            }
+            "parquet" => Ok(InventoryReader::Parquet),
+            _ => Err(TransferError::new(format!(
+                "Inventory manifest '{}' has unsupported format '{}'; only CSV and Parquet reports can be read",
+                manifest_key, self.file_format
+            ))),
+        }
+    }
+}
+
+/// Parses the data files of an inventory report into the objects they list.
+#[derive(Debug, Clone)]
+pub enum InventoryReader {
+    /// Gzipped CSV without a header, with the manifest's columns.
+    Csv {
+        columns: Vec<String>,
+    },
+    Parquet,
+}
+
+impl InventoryReader {
+    /// The current objects the data file `data_key` lists: delete markers and
+    /// noncurrent versions of versioned reports are left out. Decompresses and
+    /// parses the whole file, so it's meant for a blocking thread.
+    pub fn objects(&self, body: Vec<u8>, data_key: &str) -> Result<Vec<ObjectInfo>, TransferError> {
+        let invalid = |reason: String| {
+            TransferError::new(format!("Invalid inventory file '{}': {}", data_key, reason))
+        };
+        let mut objects = Vec::new();
+        match self {
+            Self::Csv { columns } => {
+                let lines = BufReader::new(MultiGzDecoder::new(body.as_slice())).lines();
+                for (index, line) in lines.enumerate() {
+                    let line = line.map_err(|e| invalid(e.to_string()))?;
+                    if line.is_empty() {
+                        continue;
+                    }
+                    let fields = split_csv(&line).ok_or_else(|| {
+                        invalid(format!("unbalanced quotes on line {}", index + 1))
+                    })?;
+                    let mut row = InventoryRow::default();
+                    for (column, value) in columns.iter().zip(&fields) {
+                        row.set_text(column, value);
+                    }
+                    objects.extend(row.into_object());
+                }
+            }
+            Self::Parquet => {
+                let reader = SerializedFileReader::new(Bytes::from(body))
+                    .map_err(|e| invalid(e.to_string()))?;
+                let rows = reader
+                    .get_row_iter(None)
+                    .map_err(|e| invalid(e.to_string()))?;
+                for record in rows {
+                    let record = record.map_err(|e| invalid(e.to_string()))?;
+                    let mut row = InventoryRow::default();
+                    for (column, value) in record.get_column_iter() {
+                        row.set_field(&column_name(column), value);
+                    }
+                    objects.extend(row.into_object());
+                }
+            }
+        }
+        Ok(objects)
+    }
+}
+
+/// A column name as CSV (`LastModifiedDate`) and Parquet (`last_modified_date`)
+/// reports both reduce to it.
+fn column_name(name: &str) -> String {
+    name.trim()
+        .chars()
+        .filter(|c| *c != '_')
+        .collect::<String>()
+        .to_ascii_lowercase()
+}
+
+/// The columns of an inventory row that matter for downloading.
+#[derive(Debug, Default)]
+struct InventoryRow {
+    key: Option<String>,
+    size: u64,
+    last_modified: Option<f64>,
+    etag: Option<String>,
+    storage_class: Option<String>,
+    is_latest: Option<bool>,
+    is_delete_marker: Option<bool>,
+}
+
+impl InventoryRow {
+    fn set_text(&mut self, column: &str, value: &str) {
+        if value.is_empty() {
+            return;
+        }
+        match column {
+            // CSV reports URL-encode keys.
+            "key" => self.key = Some(decode_key(value)),
+            "size" => self.size = value.parse().unwrap_or_default(),
+            "lastmodifieddate" => {
+                self.last_modified = DateTime::from_str(value, Format::DateTime)
+                    .ok()
+                    .map(|time| time.as_secs_f64())
+            }
+            "etag" => self.etag = Some(quoted(value)),
+            "storageclass" => self.storage_class = Some(value.to_string()),
+            "islatest" => self.is_latest = Some(value.eq_ignore_ascii_case("true")),
+            "isdeletemarker" => self.is_delete_marker = Some(value.eq_ignore_ascii_case("true")),
+            _ => {}
+        }
+    }
+
+    fn set_field(&mut self, column: &str, value: &Field) {
+        match (column, value) {
+            ("key", Field::Str(key)) => self.key = Some(key.clone()),
+            ("size", Field::Long(size)) => self.size = (*size).max(0) as u64,
+            ("lastmodifieddate", Field::TimestampMillis(millis)) => {
+                self.last_modified = Some(*millis as f64 / 1000.0)
+            }
+            ("etag", Field::Str(etag)) => self.etag = Some(quoted(etag)),
+            ("storageclass", Field::Str(class)) => self.storage_class = Some(class.clone()),
+            ("islatest", Field::Bool(latest)) => self.is_latest = Some(*latest),
+            ("isdeletemarker", Field::Bool(marker)) => self.is_delete_marker = Some(*marker),
+            _ => {}
+        }
+    }
+
+    fn into_object(self) -> Option<ObjectInfo> {
+        if self.is_latest == Some(false) || self.is_delete_marker == Some(true) {
+            return None;
+        }
+        Some(ObjectInfo {
+            key: self.key?,
+            size: self.size,
+            etag: self.etag,
+            last_modified: self.last_modified,
+            storage_class: self.storage_class,
+            is_prefix: false,
+        })
+    }
+}
+
+/// Inventory reports list ETags bare, where listings quote them.
+fn quoted(etag: &str) -> String {
+    if etag.starts_with('"') {
+        etag.to_string()
+    } else {
+        format!("\"{}\"", etag)
+    }
+}
+
+/// Decodes a form-encoded key: `%XX` escapes, and `+` for a space.
+fn decode_key(encoded: &str) -> String {
+    let bytes = encoded.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut index = 0;
+    while index < bytes.len() {
+        let escaped = bytes
+            .get(index + 1..index + 3)
+            .filter(|_| bytes[index] == b'%')
+            .and_then(|hex| std::str::from_utf8(hex).ok())
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        match (bytes[index], escaped) {
+            (_, Some(byte)) => {
+                decoded.push(byte);
+                index += 3;
+                continue;
+            }
+            (b'+', None) => decoded.push(b' '),
+            (byte, None) => decoded.push(byte),
+        }
+        index += 1;
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
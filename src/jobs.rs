@@ -0,0 +1,457 @@
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+use std::future::Future;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Condvar, Mutex};
+use std::time::{Duration, Instant};
+
+use pyo3::exceptions::{PyTimeoutError, PyValueError};
+use pyo3::types::PyAnyMethods;
+use pyo3::{pyclass, pymethods, Bound, FromPyObject, PyAny, PyRef, PyResult, Python};
+use tokio::sync::Notify;
+use tokio::task::AbortHandle;
+
+use crate::exceptions::RobinzhonError;
+use crate::pause::PauseGate;
+use crate::progress::TransferProgress;
+use crate::results::{Results, TransferRequest};
+use crate::runtime::{ProcessRuntime, SIGNAL_CHECK_INTERVAL};
+use crate::s3_downloader::S3Downloader;
+use crate::s3_uploader::S3Uploader;
+
+/// Where a job is in its life.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum JobStatus {
+    Queued,
+    Running,
+    /// Running, but dispatching nothing new until it is resumed.
+    Paused,
+    /// Ran to the end, whether or not all its items succeeded.
+    Done,
+    /// Stopped by an error of the batch as a whole.
+    Failed,
+    Cancelled,
+}
+
+impl JobStatus {
+    fn as_str(self) -> &'static str {
+        match self {
+            Self::Queued => "queued",
+            Self::Running => "running",
+            Self::Paused => "paused",
+            Self::Done => "done",
+            Self::Failed => "failed",
+            Self::Cancelled => "cancelled",
+        }
+    }
+
+    fn is_finished(self) -> bool {
+        matches!(self, Self::Done | Self::Failed | Self::Cancelled)
+    }
+}
+
+#[derive(Debug)]
+struct JobState {
+    status: JobStatus,
+    outcome: Option<PyResult<Results>>,
+}
+
+/// An item of a job: `(source, destination)`, or `(source, destination,
+/// priority)` to have it dispatched before the items of lower priority.
+#[derive(FromPyObject)]
+enum JobItem {
+    Prioritized(String, String, i32),
+    Plain(String, String),
+}
+
+impl JobItem {
+    fn priority(&self) -> i32 {
+        match self {
+            Self::Prioritized(_, _, priority) => *priority,
+            Self::Plain(..) => 0,
+        }
+    }
+
+    fn into_request(self) -> TransferRequest {
+        match self {
+            Self::Prioritized(source, destination, _) | Self::Plain(source, destination) => {
+                TransferRequest::from((source, destination))
+            }
+        }
+    }
+}
+
+/// A job waiting for a running slot. Higher priorities come first, then the
+/// earlier job.
+type QueuedJob = (i32, Reverse<u64>);
+
+#[derive(Debug, Default)]
+struct SlotState {
+    running: usize,
+    waiting: BinaryHeap<QueuedJob>,
+}
+
+/// The running slots of a manager, handed out by priority rather than in the
+/// order jobs asked for them.
+#[derive(Debug)]
+struct JobSlots {
+    max_running: usize,
+    state: Mutex<SlotState>,
+    changed: Notify,
+}
+
+impl JobSlots {
+    /// Waits until `job` is the most urgent job waiting and a slot is free.
+    async fn acquire(self: Arc<Self>, job: QueuedJob) -> RunningSlot {
+        self.state.lock().unwrap().waiting.push(job);
+        let mut waiting = WaitingJob {
+            slots: &self,
+            job,
+            queued: true,
+        };
+        loop {
+            let changed = self.changed.notified();
+            {
+                let mut state = self.state.lock().unwrap();
+                if state.running < self.max_running && state.waiting.peek() == Some(&job) {
+                    state.waiting.pop();
+                    state.running += 1;
+                    waiting.queued = false;
+                    // The next job in line may fit in another free slot.
+                    self.changed.notify_waiters();
+                    break;
+                }
+            }
+            changed.await;
+        }
+        drop(waiting);
+        RunningSlot { slots: self }
+    }
+
+    fn running(&self) -> usize {
+        self.state.lock().unwrap().running
+    }
+}
+
+/// Takes a job cancelled while waiting out of the line.
+struct WaitingJob<'a> {
+    slots: &'a JobSlots,
+    job: QueuedJob,
+    queued: bool,
+}
+
+impl Drop for WaitingJob<'_> {
+    fn drop(&mut self) {
+        if self.queued {
+            let mut state = self.slots.state.lock().unwrap();
+            state.waiting.retain(|job| *job != self.job);
+            self.slots.changed.notify_waiters();
+        }
+    }
+}
+
+/// A running slot, given back when the job finishes or is cancelled.
+struct RunningSlot {
+    slots: Arc<JobSlots>,
+}
+
+impl Drop for RunningSlot {
+    fn drop(&mut self) {
+        self.slots.state.lock().unwrap().running -= 1;
+        self.slots.changed.notify_waiters();
+    }
+}
+
+/// A batch submitted to a `TransferManager`, shared by its task and its handle.
+#[derive(Debug)]
+struct Job {
+    id: u64,
+    priority: i32,
+    state: Mutex<JobState>,
+    finished: Condvar,
+    progress: TransferProgress,
+    pause: Arc<PauseGate>,
+    task: Mutex<Option<AbortHandle>>,
+}
+
+impl Job {
+    /// Moves a queued job to running; `false` if it was cancelled meanwhile.
+    fn start(&self) -> bool {
+        let mut state = self.state.lock().unwrap();
+        if state.status != JobStatus::Queued {
+            return false;
+        }
+        state.status = JobStatus::Running;
+        true
+    }
+
+    /// What the job is doing, with a running job shown as paused while its gate
+    /// holds it back.
+    fn status(&self) -> JobStatus {
+        match self.state.lock().unwrap().status {
+            JobStatus::Running if self.pause.is_paused() => JobStatus::Paused,
+            status => status,
+        }
+    }
+
+    fn finish(&self, outcome: PyResult<Results>) {
+        let mut state = self.state.lock().unwrap();
+        if state.status.is_finished() {
+            return;
+        }
+        state.status = match outcome {
+            Ok(_) => JobStatus::Done,
+            Err(_) => JobStatus::Failed,
+        };
+        state.outcome = Some(outcome);
+        self.finished.notify_all();
+    }
+
+    fn cancel(&self) -> bool {
+        let mut state = self.state.lock().unwrap();
+        if state.status.is_finished() {
+            return false;
+        }
+        state.status = JobStatus::Cancelled;
+        state.outcome = Some(Err(RobinzhonError::new_err(format!(
+            "Job {} was cancelled",
+            self.id
+        ))));
+        // Dropping the batch cancels its transfers and removes partial files.
+        if let Some(task) = self.task.lock().unwrap().take() {
+            task.abort();
+        }
+        self.finished.notify_all();
+        true
+    }
+}
+
+/// Runs batches in the background on the shared async runtime, at most
+/// `max_running_jobs` at a time, by priority and then in the order they were
+/// submitted.
+#[pyclass(frozen, module = "robinzhon")]
+pub struct TransferManager {
+    slots: Arc<JobSlots>,
+    #[pyo3(get)]
+    max_running_jobs: usize,
+    next_id: AtomicU64,
+}
+
+impl TransferManager {
+    /// Queues `batch` to run on `runtime`, the runtime of the client it was
+    /// submitted with.
+    fn spawn(
+        &self,
+        runtime: &ProcessRuntime,
+        batch: impl Future<Output = PyResult<Results>> + Send + 'static,
+        progress: TransferProgress,
+        pause: Arc<PauseGate>,
+        priority: i32,
+    ) -> JobHandle {
+        let job = Arc::new(Job {
+            id: self.next_id.fetch_add(1, Ordering::Relaxed),
+            priority,
+            state: Mutex::new(JobState {
+                status: JobStatus::Queued,
+                outcome: None,
+            }),
+            finished: Condvar::new(),
+            progress,
+            pause,
+            task: Mutex::new(None),
+        });
+
+        let slots = Arc::clone(&self.slots);
+        let running = Arc::clone(&job);
+        let task = runtime.spawn(async move {
+            let _slot = slots.acquire((running.priority, Reverse(running.id))).await;
+            if running.start() {
+                running.finish(batch.await);
+            }
+        });
+        *job.task.lock().unwrap() = Some(task.abort_handle());
+        JobHandle { job }
+    }
+}
+
+#[pymethods]
+impl TransferManager {
+    #[new]
+    #[pyo3(signature = (max_running_jobs=1))]
+    fn new(max_running_jobs: usize) -> PyResult<Self> {
+        if max_running_jobs == 0 {
+            return Err(PyValueError::new_err(
+                "max_running_jobs must be greater than zero",
+            ));
+        }
+        Ok(Self {
+            slots: Arc::new(JobSlots {
+                max_running: max_running_jobs,
+                state: Mutex::default(),
+                changed: Notify::new(),
+            }),
+            max_running_jobs,
+            next_id: AtomicU64::new(1),
+        })
+    }
+
+    /// Queues a batch and returns at once. With an `S3Downloader`, `items` are
+    /// `(key, local_path)` downloads; with an `S3Uploader`, `(local_path, key)`
+    /// uploads. Jobs and items of higher `priority` go first.
+    #[pyo3(signature = (client, bucket_name, items, priority=0))]
+    fn submit(
+        &self,
+        client: &Bound<'_, PyAny>,
+        bucket_name: String,
+        mut items: Vec<JobItem>,
+        priority: i32,
+    ) -> PyResult<JobHandle> {
+        // Items are dispatched in order, so the most urgent are sorted first;
+        // the sort is stable, so items of equal priority keep their order.
+        items.sort_by_key(|item| Reverse(item.priority()));
+        let requests = items.into_iter().map(JobItem::into_request).collect();
+        let progress = TransferProgress::default();
+        let state = Arc::clone(&progress.state);
+        let pause = Arc::new(PauseGate::default());
+        if let Ok(downloader) = client.extract::<PyRef<'_, S3Downloader>>() {
+            let runtime = Arc::clone(&downloader.state().runtime);
+            runtime.check_process()?;
+            let batch = downloader.detached_batch(bucket_name, requests, state, Arc::clone(&pause));
+            Ok(self.spawn(&runtime, batch, progress, pause, priority))
+        } else if let Ok(uploader) = client.extract::<PyRef<'_, S3Uploader>>() {
+            let runtime = Arc::clone(&uploader.state().runtime);
+            runtime.check_process()?;
+            let batch = uploader.detached_batch(bucket_name, requests, state, Arc::clone(&pause));
+            Ok(self.spawn(&runtime, batch, progress, pause, priority))
+        } else {
+            Err(PyValueError::new_err(
+                "Jobs are submitted with an S3Downloader or an S3Uploader",
+            ))
+        }
+    }
+
+    fn __repr__(&self) -> String {
+        format!(
+            "TransferManager(max_running_jobs={}, running={})",
+            self.max_running_jobs,
+            self.slots.running()
+        )
+    }
+}
+
+/// A batch submitted to a `TransferManager`.
+#[pyclass(frozen, module = "robinzhon")]
+pub struct JobHandle {
+    job: Arc<Job>,
+}
+
+#[pymethods]
+impl JobHandle {
+    /// Number of the job within its manager, starting at 1.
+    #[getter]
+    fn id(&self) -> u64 {
+        self.job.id
+    }
+
+    #[getter]
+    fn priority(&self) -> i32 {
+        self.job.priority
+    }
+
+    /// "queued", "running", "paused", "done", "failed" or "cancelled".
+    fn status(&self) -> &'static str {
+        self.job.status().as_str()
+    }
+
+    /// Whether the job is held back by `pause`, until `resume`.
+    #[getter]
+    fn paused(&self) -> bool {
+        self.job.pause.is_paused()
+    }
+
+    /// Stops dispatching the job's items until `resume`; the transfers in flight
+    /// run to their end, or with `parts` stop before their next part. A queued
+    /// job paused this way takes its running slot but starts nothing. Returns
+    /// whether the job was still unfinished.
+    #[pyo3(signature = (parts=false))]
+    fn pause(&self, parts: bool) -> bool {
+        if self.done() {
+            return false;
+        }
+        self.job.pause.pause(parts);
+        true
+    }
+
+    /// Lets a paused job carry on where it stopped. Returns whether the job was
+    /// paused.
+    fn resume(&self) -> bool {
+        let paused = self.job.pause.is_paused();
+        self.job.pause.resume();
+        paused
+    }
+
+    fn progress(&self) -> TransferProgress {
+        self.job.progress.clone()
+    }
+
+    /// Whether the job is done, failed or cancelled.
+    fn done(&self) -> bool {
+        self.job.state.lock().unwrap().status.is_finished()
+    }
+
+    /// Cancels the job unless it already finished; returns whether it did.
+    fn cancel(&self) -> bool {
+        self.job.cancel()
+    }
+
+    /// Waits for the job to finish and returns its results, raising the error of
+    /// a failed or cancelled job. Waits forever without a `timeout`.
+    #[pyo3(signature = (timeout=None))]
+    fn result(&self, py: Python<'_>, timeout: Option<f64>) -> PyResult<Results> {
+        let deadline = match timeout {
+            Some(seconds) if !(seconds.is_finite() && seconds >= 0.0) => {
+                return Err(PyValueError::new_err("timeout must not be negative"))
+            }
+            Some(seconds) => Some(Instant::now() + Duration::from_secs_f64(seconds)),
+            None => None,
+        };
+
+        loop {
+            let wait = deadline.map_or(SIGNAL_CHECK_INTERVAL, |deadline| {
+                deadline
+                    .saturating_duration_since(Instant::now())
+                    .min(SIGNAL_CHECK_INTERVAL)
+            });
+            let finished = py.allow_threads(|| {
+                let state = self.job.state.lock().unwrap();
+                let (state, _) = self
+                    .job
+                    .finished
+                    .wait_timeout_while(state, wait, |state| !state.status.is_finished())
+                    .unwrap();
+                state.status.is_finished()
+            });
+            if finished {
+                break;
+            }
+            if deadline.is_some_and(|deadline| Instant::now() >= deadline) {
+                return Err(PyTimeoutError::new_err(format!(
+                    "Job {} did not finish within {}s",
+                    self.job.id,
+                    timeout.unwrap_or_default()
+                )));
+            }
+            py.check_signals()?;
+        }
+
+        match &self.job.state.lock().unwrap().outcome {
+            Some(Ok(results)) => Ok(results.clone()),
+            Some(Err(error)) => Err(error.clone_ref(py)),
+            None => unreachable!("finished jobs have an outcome"),
+        }
+    }
+
+    fn __repr__(&self) -> String {
+        format!("JobHandle(id={}, status={:?})", self.job.id, self.status())
+    }
+}
@@ -0,0 +1,216 @@
+use std::collections::HashMap;
+use std::time::SystemTime;
+
+use aws_credential_types::provider::{ProvideCredentials, SharedCredentialsProvider};
+use aws_sigv4::http_request::{sign, SignableBody, SignableRequest, SigningSettings};
+use aws_sigv4::sign::v4;
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use serde::Deserialize;
+use serde_json::json;
+
+use crate::error::{error_chain, TransferError};
+use crate::s3_config::S3Config;
+
+/// A data key as GenerateDataKey returns it: in the clear, to encrypt with, and
+/// wrapped by the KMS key, to store next to the data.
+pub struct DataKey {
+    pub plaintext: Vec<u8>,
+    pub ciphertext: Vec<u8>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct GenerateDataKeyResponse {
+    plaintext: String,
+    ciphertext_blob: String,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct DecryptResponse {
+    plaintext: String,
+}
+
+#[derive(Deserialize)]
+struct ErrorResponse {
+    #[serde(rename = "__type")]
+    kind: Option<String>,
+    #[serde(alias = "Message")]
+    message: Option<String>,
+}
+
+/// Just enough of a KMS client to wrap and unwrap data keys, signing its requests
+/// with the credentials and region of an S3 client.
+#[derive(Debug, Clone)]
+pub struct KmsClient {
+    http: reqwest::Client,
+    endpoint_url: String,
+    region_name: String,
+    credentials: Option<SharedCredentialsProvider>,
+}
+
+impl KmsClient {
+    /// A client for the region of `s3_config`, at `endpoint_url` or the regional
+    /// KMS endpoint.
+    pub fn new(s3_config: &S3Config, endpoint_url: Option<String>) -> Self {
+        let region_name = s3_config.region_name.clone();
+        Self {
+            http: reqwest::Client::new(),
+            endpoint_url: endpoint_url
+                .unwrap_or_else(|| format!("https://kms.{}.amazonaws.com", region_name)),
+            region_name,
+            credentials: s3_config.credentials.clone(),
+        }
+    }
+
+    /// Creates a 256-bit data key under `key_id`, bound to `context`.
+    pub async fn generate_data_key(
+        &self,
+        key_id: &str,
+        context: &HashMap<String, String>,
+    ) -> Result<DataKey, TransferError> {
+        let request = json!({
+            "KeyId": key_id,
+            "KeySpec": "AES_256",
+            "EncryptionContext": context,
+        });
+        let context = format!("Failed to generate a data key with KMS key '{}'", key_id);
+        let response: GenerateDataKeyResponse =
+            self.call("GenerateDataKey", &request, &context).await?;
+        Ok(DataKey {
+            plaintext: decode(&response.plaintext)?,
+            ciphertext: decode(&response.ciphertext_blob)?,
+        })
+    }
+
+    /// Unwraps a data key, which KMS only does given the `context` it was bound to.
+    pub async fn decrypt(
+        &self,
+        ciphertext: &[u8],
+        context: &HashMap<String, String>,
+    ) -> Result<Vec<u8>, TransferError> {
+        let request = json!({
+            "CiphertextBlob": BASE64.encode(ciphertext),
+            "EncryptionContext": context,
+        });
+        let response: DecryptResponse = self
+            .call(
+                "Decrypt",
+                &request,
+                "Failed to decrypt the data key with KMS",
+            )
+            .await?;
+        decode(&response.plaintext)
+    }
+
+    /// Calls a KMS operation, describing failures with `context`.
+    async fn call<T: for<'de> Deserialize<'de>>(
+        &self,
+        operation: &str,
+        request: &serde_json::Value,
+        context: &str,
+    ) -> Result<T, TransferError> {
+        let body = request.to_string().into_bytes();
+        let target = format!("TrentService.{}", operation);
+        let mut headers = vec![
+            ("content-type", "application/x-amz-json-1.1".to_string()),
+            ("x-amz-target", target),
+        ];
+        headers.extend(self.signature(&headers, &body).await?);
+
+        let mut http_request = self.http.post(&self.endpoint_url).body(body);
+        for (name, value) in headers {
+            http_request = http_request.header(name, value);
+        }
+        let response = http_request
+            .send()
+            .await
+            .map_err(|e| TransferError::from_http(context, e))?;
+
+        let status = response.status().as_u16();
+        let text = response
+            .text()
+            .await
+            .map_err(|e| TransferError::from_http(context, e))?;
+        if !(200..300).contains(&status) {
+            return Err(kms_error(context, status, &text));
+        }
+        serde_json::from_str(&text)
+            .map_err(|e| TransferError::new(format!("{}: invalid response: {}", context, e)))
+    }
+
+    /// The SigV4 headers authenticating a request with `headers` and `body`.
+    async fn signature(
+        &self,
+        headers: &[(&str, String)],
+        body: &[u8],
+    ) -> Result<Vec<(&'static str, String)>, TransferError> {
+        let credentials = self
+            .credentials
+            .as_ref()
+            .ok_or_else(|| TransferError::new("No AWS credentials to sign KMS requests with"))?
+            .provide_credentials()
+            .await
+            .map_err(|e| {
+                TransferError::new(format!(
+                    "Failed to load AWS credentials: {}",
+                    error_chain(&e)
+                ))
+            })?;
+
+        let identity = credentials.into();
+        let params = v4::SigningParams::builder()
+            .identity(&identity)
+            .region(&self.region_name)
+            .name("kms")
+            .time(SystemTime::now())
+            .settings(SigningSettings::default())
+            .build()
+            .map_err(|e| TransferError::new(format!("Failed to sign KMS request: {}", e)))?
+            .into();
+        let signable = SignableRequest::new(
+            "POST",
+            &self.endpoint_url,
+            headers.iter().map(|(name, value)| (*name, value.as_str())),
+            SignableBody::Bytes(body),
+        )
+        .and_then(|signable| sign(signable, &params))
+        .map_err(|e| TransferError::new(format!("Failed to sign KMS request: {}", e)))?;
+
+        let (instructions, _) = signable.into_parts();
+        let (signed_headers, _) = instructions.into_parts();
+        Ok(signed_headers
+            .into_iter()
+            .map(|header| (header.name(), header.value().to_string()))
+            .collect())
+    }
+}
+
+fn decode(value: &str) -> Result<Vec<u8>, TransferError> {
+    BASE64
+        .decode(value)
+        .map_err(|e| TransferError::new(format!("Invalid base64 in KMS response: {}", e)))
+}
+
+/// The error of a KMS error response, whose `__type` names the exception, e.g.
+/// "NotFoundException" or "com.amazonaws.kms#ThrottlingException".
+fn kms_error(context: &str, status: u16, body: &str) -> TransferError {
+    let response = serde_json::from_str::<ErrorResponse>(body).ok();
+    let code = response
+        .as_ref()
+        .and_then(|response| response.kind.as_deref())
+        .map(|kind| kind.rsplit('#').next().unwrap_or(kind).to_string());
+
+    // The status alone decides whether it's transient; the code is KMS's own.
+    let mut error = TransferError::from_status(context, status, "");
+    if let Some(code) = &code {
+        error.message = format!("{}: HTTP {} ({})", context, status, code);
+        error.retryable |= code == "ThrottlingException";
+    }
+    if let Some(message) = response.and_then(|response| response.message) {
+        error.message = format!("{}: {}", error.message, message);
+    }
+    error.code = code;
+    error
+}
@@ -1,20 +1,131 @@
 use pyo3::prelude::*;
 
+mod access_points;
+mod asyncio;
+mod attributes;
+mod audit;
+mod batch_input;
+mod batch_iterator;
+mod bundles;
+mod byte_range;
+mod checksums;
+mod chunk_source;
+mod client;
+mod conditions;
+mod credentials;
+mod decompress;
+mod disk_space;
+mod encryption;
+mod error;
+mod exceptions;
+mod extract;
+mod failures;
+mod filters;
+mod integrity;
+mod interceptor;
+mod inventory;
+mod jobs;
+mod kms;
+mod limiter;
+mod listing;
+mod memory_budget;
+mod metadata;
+mod metrics;
+mod object_stream;
+mod objects;
+mod overwrite;
+mod pause;
+mod plan;
+mod preflight;
+mod progress;
+mod rate_limit;
+mod request_log;
+mod restore;
 mod results;
+mod retry;
+mod run_report;
+mod runtime;
 mod s3_config;
 mod s3_downloader;
+mod s3_file;
 mod s3_uploader;
+mod s3_uri;
+mod scheduling;
+mod select;
+mod session;
+mod settings;
+mod sync;
+mod transfer_manifest;
+mod upload_options;
 
-use results::Results;
+use batch_iterator::BatchIterator;
+use bundles::ArchiveMode;
+use client::S3Client;
+use encryption::ClientEncryption;
+use jobs::{JobHandle, TransferManager};
+use limiter::TransferLimiter;
+use listing::{ObjectInfo, ObjectListing, ObjectVersion};
+use metadata::ObjectMetadata;
+use object_stream::ObjectStream;
+use plan::TransferPlan;
+use preflight::PreflightReport;
+use progress::TransferProgress;
+use results::{FailedItem, ResultItem, Results};
+use retry::RetryPolicy;
 use s3_config::S3Config;
 use s3_downloader::S3Downloader;
+use s3_file::S3File;
 use s3_uploader::S3Uploader;
+use session::TransferSession;
+use sync::SyncReport;
+use upload_options::UploadOptions;
 
 #[pymodule(gil_used = false)]
 fn robinzhon(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    // Forward Rust diagnostics to the `robinzhon` logger hierarchy. Only logger
+    // objects are cached so later changes to levels and handlers are honoured.
+    // Installing fails only if another logger is already set for this process.
+    let _ = pyo3_log::Logger::new(m.py(), pyo3_log::Caching::Loggers)?.install();
+
     m.add_class::<S3Config>()?;
     m.add_class::<Results>()?;
+    m.add_class::<FailedItem>()?;
+    m.add_class::<ResultItem>()?;
+    m.add_class::<BatchIterator>()?;
+    m.add_class::<TransferProgress>()?;
+    m.add_class::<RetryPolicy>()?;
+    m.add_class::<TransferLimiter>()?;
     m.add_class::<S3Downloader>()?;
+    m.add_class::<ObjectStream>()?;
+    m.add_class::<S3File>()?;
+    m.add_class::<ObjectInfo>()?;
+    m.add_class::<ObjectListing>()?;
+    m.add_class::<ObjectMetadata>()?;
+    m.add_class::<ObjectVersion>()?;
     m.add_class::<S3Uploader>()?;
+    m.add_class::<UploadOptions>()?;
+    m.add_class::<ArchiveMode>()?;
+    m.add_class::<S3Client>()?;
+    m.add_class::<ClientEncryption>()?;
+    m.add_class::<SyncReport>()?;
+    m.add_class::<PreflightReport>()?;
+    m.add_class::<TransferPlan>()?;
+    m.add_class::<TransferSession>()?;
+    m.add_class::<TransferManager>()?;
+    m.add_class::<JobHandle>()?;
+    exceptions::register(m)?;
+
+    m.add_function(wrap_pyfunction!(metrics::get_metrics, m)?)?;
+    m.add_function(wrap_pyfunction!(metrics::reset_metrics, m)?)?;
+    let set_metrics_callback = wrap_pyfunction!(metrics::set_metrics_callback, m)?;
+    m.add_function(set_metrics_callback.clone())?;
+    m.add_function(wrap_pyfunction!(request_log::set_log_level, m)?)?;
+    m.add_function(wrap_pyfunction!(audit::verify_audit_log, m)?)?;
+
+    let drain = wrap_pyfunction!(asyncio::drain_pending_tasks, m)?;
+    let atexit = m.py().import("atexit")?;
+    atexit.call_method1("register", (drain,))?;
+    // The metrics thread mustn't call into an interpreter shutting down.
+    atexit.call_method1("register", (set_metrics_callback, m.py().None()))?;
     Ok(())
 }
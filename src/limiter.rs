@@ -0,0 +1,64 @@
+use std::sync::Arc;
+
+use pyo3::exceptions::PyValueError;
+use pyo3::{pyclass, pymethods, PyResult};
+use tokio::sync::{Semaphore, SemaphorePermit};
+
+/// Budget of in-flight transfers shared by every downloader and uploader it is
+/// given to, on top of their own `max_concurrent_*` limits.
+///
+/// Each object transfer holds one slot while it runs, so instances sharing a
+/// limiter never have more than `max_in_flight` objects in flight together.
+#[pyclass(frozen, module = "robinzhon")]
+#[derive(Debug, Clone)]
+pub struct TransferLimiter {
+    pub semaphore: Arc<Semaphore>,
+    #[pyo3(get)]
+    pub max_in_flight: usize,
+}
+
+#[pymethods]
+impl TransferLimiter {
+    #[new]
+    fn new(max_in_flight: usize) -> PyResult<Self> {
+        if max_in_flight == 0 {
+            return Err(PyValueError::new_err(
+                "max_in_flight must be greater than zero",
+            ));
+        }
+        Ok(Self {
+            semaphore: Arc::new(Semaphore::new(max_in_flight)),
+            max_in_flight,
+        })
+    }
+
+    /// Transfers currently holding a slot, across every instance.
+    #[getter]
+    fn in_flight(&self) -> usize {
+        self.max_in_flight - self.semaphore.available_permits()
+    }
+
+    /// Pickles the limiter as its size. Slots can't be shared across processes,
+    /// so an unpickled limiter is a budget of its own.
+    fn __getnewargs__(&self) -> (usize,) {
+        (self.max_in_flight,)
+    }
+
+    fn __repr__(&self) -> String {
+        format!(
+            "TransferLimiter(max_in_flight={}, in_flight={})",
+            self.max_in_flight,
+            self.in_flight()
+        )
+    }
+}
+
+/// Waits for a slot of `limiter`, if the transfer is limited by one. The slot is
+/// released when the returned permit is dropped.
+pub async fn acquire(limiter: Option<&Semaphore>) -> Option<SemaphorePermit<'_>> {
+    match limiter {
+        // The semaphore is never closed.
+        Some(limiter) => limiter.acquire().await.ok(),
+        None => None,
+    }
+}
@@ -0,0 +1,433 @@
+use std::collections::VecDeque;
+use std::sync::Arc;
+
+use aws_sdk_s3::operation::list_object_versions::ListObjectVersionsOutput;
+use aws_sdk_s3::operation::list_objects_v2::ListObjectsV2Output;
+use aws_sdk_s3::types::{CommonPrefix, DeleteMarkerEntry, Object, ObjectVersion as Version};
+use pyo3::types::{PyDateTime, PyTzInfo};
+use pyo3::{pyclass, pymethods, Bound, PyRef, PyResult, Python};
+
+use crate::error::TransferError;
+use crate::filters::{ObjectFilter, PathFilter};
+use crate::retry::RetryPolicy;
+use crate::runtime::{block_on_interruptible, ProcessRuntime};
+use crate::s3_config::S3Config;
+
+/// An entry of a bucket listing: an object, or a common prefix when listing with
+/// a delimiter.
+#[pyclass(frozen, module = "robinzhon")]
+#[derive(Debug, Clone)]
+pub struct ObjectInfo {
+    #[pyo3(get)]
+    pub key: String,
+    #[pyo3(get)]
+    pub size: u64,
+    #[pyo3(get)]
+    pub etag: Option<String>,
+    /// Seconds since the Unix epoch.
+    pub last_modified: Option<f64>,
+    #[pyo3(get)]
+    pub storage_class: Option<String>,
+    #[pyo3(get)]
+    pub is_prefix: bool,
+}
+
+impl From<Object> for ObjectInfo {
+    fn from(object: Object) -> Self {
+        Self {
+            key: object.key.unwrap_or_default(),
+            size: object.size.unwrap_or_default().max(0) as u64,
+            etag: object.e_tag,
+            last_modified: object.last_modified.map(|time| time.as_secs_f64()),
+            storage_class: object.storage_class.map(|class| class.as_str().to_string()),
+            is_prefix: false,
+        }
+    }
+}
+
+impl From<CommonPrefix> for ObjectInfo {
+    fn from(prefix: CommonPrefix) -> Self {
+        Self {
+            key: prefix.prefix.unwrap_or_default(),
+            size: 0,
+            etag: None,
+            last_modified: None,
+            storage_class: None,
+            is_prefix: true,
+        }
+    }
+}
+
+#[pymethods]
+impl ObjectInfo {
+    #[getter]
+    fn last_modified<'py>(&self, py: Python<'py>) -> PyResult<Option<Bound<'py, PyDateTime>>> {
+        utc_datetime(py, self.last_modified)
+    }
+
+    fn __repr__(&self) -> String {
+        format!(
+            "ObjectInfo(key={:?}, size={}, etag={}, storage_class={}, is_prefix={})",
+            self.key,
+            self.size,
+            self.etag
+                .as_deref()
+                .map_or_else(|| "None".to_string(), |etag| format!("{:?}", etag)),
+            self.storage_class
+                .as_deref()
+                .map_or_else(|| "None".to_string(), |class| format!("{:?}", class)),
+            if self.is_prefix { "True" } else { "False" }
+        )
+    }
+}
+
+/// A version of an object in a versioned bucket, or a delete marker hiding the
+/// versions before it.
+#[pyclass(frozen, module = "robinzhon")]
+#[derive(Debug, Clone)]
+pub struct ObjectVersion {
+    #[pyo3(get)]
+    pub key: String,
+    #[pyo3(get)]
+    pub version_id: String,
+    #[pyo3(get)]
+    pub size: u64,
+    #[pyo3(get)]
+    pub etag: Option<String>,
+    /// Seconds since the Unix epoch.
+    pub last_modified: Option<f64>,
+    #[pyo3(get)]
+    pub is_latest: bool,
+    #[pyo3(get)]
+    pub is_delete_marker: bool,
+    #[pyo3(get)]
+    pub storage_class: Option<String>,
+}
+
+impl From<Version> for ObjectVersion {
+    fn from(version: Version) -> Self {
+        Self {
+            key: version.key.unwrap_or_default(),
+            version_id: version.version_id.unwrap_or_default(),
+            size: version.size.unwrap_or_default().max(0) as u64,
+            etag: version.e_tag,
+            last_modified: version.last_modified.map(|time| time.as_secs_f64()),
+            is_latest: version.is_latest.unwrap_or_default(),
+            is_delete_marker: false,
+            storage_class: version
+                .storage_class
+                .map(|class| class.as_str().to_string()),
+        }
+    }
+}
+
+impl From<DeleteMarkerEntry> for ObjectVersion {
+    fn from(marker: DeleteMarkerEntry) -> Self {
+        Self {
+            key: marker.key.unwrap_or_default(),
+            version_id: marker.version_id.unwrap_or_default(),
+            size: 0,
+            etag: None,
+            last_modified: marker.last_modified.map(|time| time.as_secs_f64()),
+            is_latest: marker.is_latest.unwrap_or_default(),
+            is_delete_marker: true,
+            storage_class: None,
+        }
+    }
+}
+
+#[pymethods]
+impl ObjectVersion {
+    #[getter]
+    fn last_modified<'py>(&self, py: Python<'py>) -> PyResult<Option<Bound<'py, PyDateTime>>> {
+        utc_datetime(py, self.last_modified)
+    }
+
+    fn __repr__(&self) -> String {
+        format!(
+            "ObjectVersion(key={:?}, version_id={:?}, size={}, is_latest={}, is_delete_marker={})",
+            self.key,
+            self.version_id,
+            self.size,
+            if self.is_latest { "True" } else { "False" },
+            if self.is_delete_marker {
+                "True"
+            } else {
+                "False"
+            }
+        )
+    }
+}
+
+/// Timezone-aware UTC datetime of a Unix timestamp in seconds.
+pub fn utc_datetime(
+    py: Python<'_>,
+    timestamp: Option<f64>,
+) -> PyResult<Option<Bound<'_, PyDateTime>>> {
+    let Some(timestamp) = timestamp else {
+        return Ok(None);
+    };
+    let utc = PyTzInfo::utc(py)?.to_owned();
+    PyDateTime::from_timestamp(py, timestamp, Some(&utc)).map(Some)
+}
+
+/// What to list: a bucket, optionally narrowed to a prefix and grouped by a
+/// delimiter.
+#[derive(Debug, Clone)]
+pub struct ListRequest {
+    pub bucket_name: String,
+    pub prefix: Option<String>,
+    pub delimiter: Option<String>,
+}
+
+impl ListRequest {
+    /// Fetches the page after `continuation_token`, or the first one.
+    ///
+    /// Returns the entries of the page in key order, common prefixes included, and
+    /// the token of the next page if there is one.
+    pub async fn page(
+        &self,
+        s3_config: &S3Config,
+        continuation_token: Option<String>,
+    ) -> Result<(Vec<ObjectInfo>, Option<String>), TransferError> {
+        let ListObjectsV2Output {
+            contents,
+            common_prefixes,
+            next_continuation_token,
+            is_truncated,
+            ..
+        } = s3_config
+            .client_for(&self.bucket_name)
+            .list_objects_v2()
+            .bucket(&self.bucket_name)
+            .set_prefix(self.prefix.clone())
+            .set_delimiter(self.delimiter.clone())
+            .set_continuation_token(continuation_token)
+            .send()
+            .await
+            .map_err(|e| {
+                TransferError::from_sdk(
+                    format!("Failed to list objects in bucket '{}'", self.bucket_name),
+                    &e,
+                )
+            })?;
+
+        let mut entries: Vec<ObjectInfo> = contents
+            .unwrap_or_default()
+            .into_iter()
+            .map(ObjectInfo::from)
+            .chain(
+                common_prefixes
+                    .unwrap_or_default()
+                    .into_iter()
+                    .map(ObjectInfo::from),
+            )
+            .collect();
+        entries.sort_by(|a, b| a.key.cmp(&b.key));
+
+        let next_page = next_continuation_token.filter(|_| is_truncated.unwrap_or_default());
+        Ok((entries, next_page))
+    }
+
+    /// Fetches every page, retrying each one according to `retry_policy`.
+    pub async fn all(
+        &self,
+        s3_config: &S3Config,
+        retry_policy: &RetryPolicy,
+    ) -> Result<Vec<ObjectInfo>, TransferError> {
+        let mut entries = Vec::new();
+        let mut token = None;
+        loop {
+            let (page, next_page) = s3_config
+                .run(&self.bucket_name, retry_policy, || {
+                    self.page(s3_config, token.clone())
+                })
+                .await?;
+            entries.extend(page);
+            match next_page {
+                Some(next_page) => token = Some(next_page),
+                None => return Ok(entries),
+            }
+        }
+    }
+}
+
+/// Lists every version and delete marker of the objects under `prefix`, ordered
+/// by key and, within a key, newest first, retrying each page according to
+/// `retry_policy`.
+pub async fn list_object_versions(
+    s3_config: &S3Config,
+    bucket_name: &str,
+    prefix: Option<&str>,
+    retry_policy: &RetryPolicy,
+) -> Result<Vec<ObjectVersion>, TransferError> {
+    let mut versions = Vec::new();
+    let mut markers = (None, None);
+    loop {
+        let ListObjectVersionsOutput {
+            versions: page,
+            delete_markers,
+            is_truncated,
+            next_key_marker,
+            next_version_id_marker,
+            ..
+        } = s3_config
+            .run(bucket_name, retry_policy, || async {
+                s3_config
+                    .client_for(bucket_name)
+                    .list_object_versions()
+                    .bucket(bucket_name)
+                    .set_prefix(prefix.map(str::to_string))
+                    .set_key_marker(markers.0.clone())
+                    .set_version_id_marker(markers.1.clone())
+                    .send()
+                    .await
+                    .map_err(|e| {
+                        TransferError::from_sdk(
+                            format!("Failed to list object versions in bucket '{}'", bucket_name),
+                            &e,
+                        )
+                    })
+            })
+            .await?;
+
+        let mut entries: Vec<ObjectVersion> = page
+            .unwrap_or_default()
+            .into_iter()
+            .map(ObjectVersion::from)
+            .chain(
+                delete_markers
+                    .unwrap_or_default()
+                    .into_iter()
+                    .map(ObjectVersion::from),
+            )
+            .collect();
+        // Versions and delete markers come in separate lists; interleave them
+        // back into the order S3 keeps them in.
+        entries.sort_by(|a, b| {
+            a.key.cmp(&b.key).then(
+                b.last_modified
+                    .partial_cmp(&a.last_modified)
+                    .unwrap_or(std::cmp::Ordering::Equal),
+            )
+        });
+        versions.extend(entries);
+
+        if !is_truncated.unwrap_or_default() || next_key_marker.is_none() {
+            return Ok(versions);
+        }
+        markers = (next_key_marker, next_version_id_marker);
+    }
+}
+
+/// Lists every object under `prefix` along with its path relative to the
+/// prefix's last `/`, keeping only those whose relative path passes `filter`
+/// and that pass `objects`.
+pub async fn list_prefix(
+    s3_config: &S3Config,
+    bucket_name: &str,
+    prefix: &str,
+    filter: &PathFilter,
+    objects: &ObjectFilter,
+    retry_policy: &RetryPolicy,
+) -> Result<Vec<(String, ObjectInfo)>, TransferError> {
+    let request = ListRequest {
+        bucket_name: bucket_name.to_string(),
+        prefix: objects.narrowed_prefix(Some(prefix)),
+        delimiter: None,
+    };
+    let listed = request.all(s3_config, retry_policy).await?;
+    Ok(select_objects(listed, prefix, filter, objects))
+}
+
+/// The objects of `listed` below `prefix` that `filter` and `objects` keep,
+/// paired with their paths relative to the parent of `prefix`.
+pub fn select_objects(
+    listed: impl IntoIterator<Item = ObjectInfo>,
+    prefix: &str,
+    filter: &PathFilter,
+    objects: &ObjectFilter,
+) -> Vec<(String, ObjectInfo)> {
+    let parent_len = prefix.rfind('/').map_or(0, |index| index + 1);
+    listed
+        .into_iter()
+        // Zero-byte "folder" markers created by consoles don't stand for files.
+        .filter(|object| {
+            object.key.starts_with(prefix) && !object.key.ends_with('/') && objects.matches(object)
+        })
+        .map(|object| (object.key[parent_len..].to_string(), object))
+        .filter(|(relative_path, _)| filter.matches(relative_path))
+        .collect()
+}
+
+/// Lazy iterator over a bucket listing; each page of ListObjectsV2 is requested
+/// only once the previous one has been consumed. Entries failing `filter` are
+/// dropped before they reach Python.
+#[pyclass(module = "robinzhon")]
+pub struct ObjectListing {
+    runtime: Arc<ProcessRuntime>,
+    s3_config: Arc<S3Config>,
+    retry_policy: RetryPolicy,
+    request: ListRequest,
+    filter: ObjectFilter,
+    entries: VecDeque<ObjectInfo>,
+    /// `None` before the first page; `Some(None)` once the last page was fetched.
+    next_page: Option<Option<String>>,
+}
+
+impl ObjectListing {
+    pub fn new(
+        runtime: Arc<ProcessRuntime>,
+        s3_config: Arc<S3Config>,
+        retry_policy: RetryPolicy,
+        mut request: ListRequest,
+        filter: ObjectFilter,
+    ) -> Self {
+        request.prefix = filter.narrowed_prefix(request.prefix.as_deref());
+        Self {
+            runtime,
+            s3_config,
+            retry_policy,
+            request,
+            filter,
+            entries: VecDeque::new(),
+            next_page: None,
+        }
+    }
+}
+
+#[pymethods]
+impl ObjectListing {
+    fn __iter__(slf: PyRef<'_, Self>) -> PyRef<'_, Self> {
+        slf
+    }
+
+    fn __next__(&mut self, py: Python<'_>) -> PyResult<Option<ObjectInfo>> {
+        while self.entries.is_empty() {
+            let token = match &self.next_page {
+                None => None,
+                Some(Some(token)) => Some(token.clone()),
+                Some(None) => return Ok(None),
+            };
+
+            let (entries, next_page) = block_on_interruptible(py, &self.runtime, {
+                let (s3_config, retry_policy, request) =
+                    (&self.s3_config, &self.retry_policy, &self.request);
+                async move {
+                    s3_config
+                        .run(&request.bucket_name, retry_policy, || {
+                            request.page(s3_config, token.clone())
+                        })
+                        .await
+                }
+            })??;
+
+            let filter = &self.filter;
+            self.entries
+                .extend(entries.into_iter().filter(|entry| filter.matches(entry)));
+            self.next_page = Some(next_page);
+        }
+
+        Ok(self.entries.pop_front())
+    }
+}
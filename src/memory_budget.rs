@@ -0,0 +1,100 @@
+use pyo3::exceptions::PyValueError;
+use pyo3::PyResult;
+use tokio::sync::{Semaphore, SemaphorePermit};
+
+/// Bytes each permit of a `MemoryBudget` stands for, so that budgets past the
+/// 4 GiB a single `acquire_many` can take still fit.
+const PERMIT_BYTES: u64 = 1024;
+
+/// Cap on the bytes the downloads of an instance buffer at once, across all of
+/// them: a semaphore whose permits stand for bytes.
+///
+/// A download reserves what it is about to buffer before it reads the body of
+/// its response, which leaves the rest of the body waiting on the connection,
+/// and holds the reservation until it is done with the bytes. It never waits
+/// for more while it holds some, so downloads can't starve each other for good;
+/// one needing more than the whole budget waits for all of it and runs alone.
+/// A body of unknown length grows its reservation as it arrives instead.
+#[derive(Debug)]
+pub struct MemoryBudget {
+    permits: Semaphore,
+    capacity: u32,
+}
+
+impl MemoryBudget {
+    pub fn new(max_in_flight_bytes: Option<u64>) -> PyResult<Option<Self>> {
+        let max_bytes = match max_in_flight_bytes {
+            None => return Ok(None),
+            Some(0) => {
+                return Err(PyValueError::new_err(
+                    "max_in_flight_bytes must be greater than zero",
+                ))
+            }
+            Some(max_bytes) => max_bytes,
+        };
+        let capacity = max_bytes
+            .div_ceil(PERMIT_BYTES)
+            .min(Semaphore::MAX_PERMITS.min(u32::MAX as usize) as u64)
+            as u32;
+        Ok(Some(Self {
+            permits: Semaphore::new(capacity as usize),
+            capacity,
+        }))
+    }
+}
+
+/// Waits until `bytes` of `budget` are free and takes them, if downloads are
+/// limited by one. They are given back when the returned permit is dropped.
+pub async fn reserve(budget: Option<&MemoryBudget>, bytes: u64) -> Option<SemaphorePermit<'_>> {
+    let budget = budget?;
+    let permits = bytes.div_ceil(PERMIT_BYTES).min(budget.capacity as u64) as u32;
+    // The semaphore is never closed.
+    budget.permits.acquire_many(permits).await.ok()
+}
+
+/// A reservation that grows with a body buffered in memory whose length isn't
+/// known up front.
+pub struct Reservation<'a> {
+    budget: Option<&'a MemoryBudget>,
+    permit: Option<SemaphorePermit<'a>>,
+}
+
+impl<'a> Reservation<'a> {
+    /// Waits for `bytes` of `budget`, like `reserve`.
+    pub async fn new(budget: Option<&'a MemoryBudget>, bytes: u64) -> Self {
+        Self {
+            budget,
+            permit: reserve(budget, bytes).await,
+        }
+    }
+
+    /// Grows the reservation to cover `bytes`, at least doubling it so a body
+    /// arriving in small chunks doesn't go back to the budget for each. When the
+    /// budget can't spare that right away, what is held is given back and the
+    /// new total waited for at once, so growing bodies can't deadlock.
+    pub async fn grow_to(&mut self, bytes: u64) {
+        let Some(budget) = self.budget else {
+            return;
+        };
+        let held = self
+            .permit
+            .as_ref()
+            .map_or(0, |permit| permit.num_permits()) as u32;
+        let needed = bytes.div_ceil(PERMIT_BYTES).min(budget.capacity as u64) as u32;
+        if needed <= held {
+            return;
+        }
+        let wanted = needed.max(held.saturating_mul(2)).min(budget.capacity);
+        match budget.permits.try_acquire_many(wanted - held) {
+            Ok(more) => match &mut self.permit {
+                Some(permit) => permit.merge(more),
+                None => self.permit = Some(more),
+            },
+            Err(_) => {
+                self.permit = None;
+                // The semaphore is never closed.
+                self.permit = budget.permits.acquire_many(needed).await.ok();
+            }
+        }
+    }
+}
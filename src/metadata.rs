@@ -0,0 +1,190 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use aws_sdk_s3::operation::head_object::HeadObjectOutput;
+use futures::future;
+use futures::stream::{Stream, StreamExt};
+use log::warn;
+use pyo3::types::PyDateTime;
+use pyo3::{pyclass, pymethods, Bound, PyResult, Python};
+
+use crate::batch_input::until_error;
+use crate::error::TransferError;
+use crate::failures::{until_limit, FailureLimit};
+use crate::listing::utc_datetime;
+use crate::progress::ProgressState;
+use crate::rate_limit::paced;
+use crate::retry::RetryPolicy;
+use crate::s3_config::S3Config;
+use crate::settings::TransferSettings;
+
+/// What a HEAD request tells about an object.
+#[pyclass(frozen, module = "robinzhon")]
+#[derive(Debug, Clone)]
+pub struct ObjectMetadata {
+    #[pyo3(get)]
+    pub key: String,
+    #[pyo3(get)]
+    pub size: u64,
+    #[pyo3(get)]
+    pub etag: Option<String>,
+    #[pyo3(get)]
+    pub content_type: Option<String>,
+    /// Seconds since the Unix epoch.
+    pub last_modified: Option<f64>,
+    /// User metadata, without the `x-amz-meta-` prefix.
+    #[pyo3(get)]
+    pub metadata: HashMap<String, String>,
+    /// `None` for STANDARD, which S3 leaves out of HEAD responses.
+    #[pyo3(get)]
+    pub storage_class: Option<String>,
+    /// The `x-amz-restore` header of archived objects a restore was requested for.
+    #[pyo3(get)]
+    pub restore: Option<String>,
+}
+
+impl ObjectMetadata {
+    fn new(key: &str, head: HeadObjectOutput) -> Self {
+        Self {
+            key: key.to_string(),
+            size: head.content_length.unwrap_or_default().max(0) as u64,
+            etag: head.e_tag,
+            content_type: head.content_type,
+            last_modified: head.last_modified.map(|time| time.as_secs_f64()),
+            metadata: head.metadata.unwrap_or_default(),
+            storage_class: head.storage_class.map(|class| class.as_str().to_string()),
+            restore: head.restore,
+        }
+    }
+}
+
+#[pymethods]
+impl ObjectMetadata {
+    #[getter]
+    fn last_modified<'py>(&self, py: Python<'py>) -> PyResult<Option<Bound<'py, PyDateTime>>> {
+        utc_datetime(py, self.last_modified)
+    }
+
+    fn __repr__(&self) -> String {
+        format!(
+            "ObjectMetadata(key={:?}, size={}, etag={}, content_type={})",
+            self.key,
+            self.size,
+            self.etag
+                .as_deref()
+                .map_or_else(|| "None".to_string(), |etag| format!("{:?}", etag)),
+            self.content_type
+                .as_deref()
+                .map_or_else(|| "None".to_string(), |kind| format!("{:?}", kind)),
+        )
+    }
+}
+
+/// Fetches the metadata of `object_key`, or of one of its versions, retrying
+/// according to `retry_policy`.
+pub async fn head_object(
+    s3_config: &S3Config,
+    bucket_name: &str,
+    object_key: &str,
+    version_id: Option<&str>,
+    retry_policy: &RetryPolicy,
+) -> Result<ObjectMetadata, TransferError> {
+    let head = s3_config
+        .run(bucket_name, retry_policy, || async {
+            s3_config
+                .client_for(bucket_name)
+                .head_object()
+                .bucket(bucket_name)
+                .key(object_key)
+                .set_version_id(version_id.map(str::to_string))
+                .send()
+                .await
+                .map_err(|e| {
+                    TransferError::from_sdk(
+                        format!("Failed to get metadata of S3 object '{}'", object_key),
+                        &e,
+                    )
+                })
+        })
+        .await?;
+    Ok(ObjectMetadata::new(object_key, head))
+}
+
+/// Whether `object_key` exists. A 404 means it doesn't; any other failure is an
+/// error.
+pub async fn object_exists(
+    s3_config: &S3Config,
+    bucket_name: &str,
+    object_key: &str,
+    retry_policy: &RetryPolicy,
+) -> Result<bool, TransferError> {
+    match head_object(s3_config, bucket_name, object_key, None, retry_policy).await {
+        Ok(_) => Ok(true),
+        Err(error) if error.http_status == Some(404) => Ok(false),
+        Err(error) => Err(error),
+    }
+}
+
+/// Fetches the metadata of several objects concurrently, as `object_keys` yields
+/// their keys; `total` is how many there are, when known. Objects that can't be
+/// described, missing ones included, are logged and left out, and counted
+/// against the failure limit of `settings`. An error from `object_keys`, or the
+/// limit being reached, is returned once the requests already sent are done.
+pub async fn head_objects(
+    s3_config: Arc<S3Config>,
+    bucket_name: String,
+    object_keys: impl Stream<Item = PyResult<String>> + Send,
+    total: Option<usize>,
+    settings: TransferSettings,
+    progress: Arc<ProgressState>,
+) -> PyResult<HashMap<String, ObjectMetadata>> {
+    progress.start(total.unwrap_or_default());
+
+    let mut input_error = None;
+    let failures = FailureLimit::new(settings.failure_limit);
+    let (mut failed, mut first_failure) = (0, None);
+    let mut described = HashMap::with_capacity(total.unwrap_or_default());
+    let retry_policy = &settings.retry_policy;
+    let (s3_config, bucket_name, progress) = (&s3_config, &bucket_name, &progress);
+
+    let object_keys = until_limit(until_error(object_keys, &mut input_error), &failures);
+    paced(
+        object_keys.map(|object_key| {
+            if total.is_none() {
+                progress.add_files_total(1);
+            }
+            async move {
+                let outcome =
+                    head_object(s3_config, bucket_name, &object_key, None, retry_policy).await;
+                progress.record(&outcome);
+                (object_key, outcome)
+            }
+        }),
+        settings.requests.as_deref(),
+    )
+    .buffer_unordered(settings.max_concurrent)
+    .for_each(|(object_key, outcome)| {
+        match outcome {
+            Ok(metadata) => {
+                described.insert(object_key, metadata);
+            }
+            Err(error) => {
+                warn!("{}", error);
+                failed += 1;
+                first_failure.get_or_insert((object_key, error));
+                failures.update(failed);
+            }
+        }
+        future::ready(())
+    })
+    .await;
+
+    progress.finish();
+    let first_failure = first_failure
+        .as_ref()
+        .map(|(object_key, error)| (object_key.as_str(), error));
+    match (failures.error_after(failed, first_failure), input_error) {
+        (Some(error), _) | (None, Some(error)) => Err(error),
+        (None, None) => Ok(described),
+    }
+}
@@ -0,0 +1,241 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{mpsc, Mutex};
+use std::time::{Duration, Instant};
+
+use aws_sdk_s3::config::interceptors::{
+    BeforeDeserializationInterceptorContextRef, BeforeTransmitInterceptorContextRef,
+    FinalizerInterceptorContextRef,
+};
+use aws_sdk_s3::config::{ConfigBag, Intercept, RuntimeComponents};
+use aws_sdk_s3::error::BoxError;
+use aws_smithy_types::config_bag::{Storable, StoreReplace};
+use log::warn;
+use pyo3::exceptions::{PyRuntimeError, PyTypeError, PyValueError};
+use pyo3::types::{PyAnyMethods, PyDict, PyDictMethods};
+use pyo3::{pyfunction, Bound, PyAny, PyResult, Python};
+
+/// Upper bounds, in seconds, of the buckets of the request latency histogram;
+/// the last bucket, `+Inf`, takes the rest.
+const LATENCY_BUCKETS: [f64; 11] = [
+    0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0,
+];
+
+/// Seconds between two calls of the metrics callback by default.
+const DEFAULT_INTERVAL: f64 = 10.0;
+
+/// Counters of every request the S3 clients of the process send, whichever
+/// client sent them.
+static METRICS: Metrics = Metrics::new();
+
+/// Sender keeping the thread that calls the metrics callback alive; dropping
+/// it stops the thread.
+static REPORTER: Mutex<Option<mpsc::Sender<()>>> = Mutex::new(None);
+
+struct Metrics {
+    requests: AtomicU64,
+    /// Requests answered with an error status or not answered at all.
+    request_errors: AtomicU64,
+    /// Transfers retried by a `RetryPolicy`, on top of the SDK's own retries,
+    /// which count as requests.
+    retries: AtomicU64,
+    bytes_in: AtomicU64,
+    bytes_out: AtomicU64,
+    /// Requests by the first latency bucket they fit in, `+Inf` last.
+    latency_buckets: [AtomicU64; LATENCY_BUCKETS.len() + 1],
+    latency_micros: AtomicU64,
+}
+
+impl Metrics {
+    const fn new() -> Self {
+        Self {
+            requests: AtomicU64::new(0),
+            request_errors: AtomicU64::new(0),
+            retries: AtomicU64::new(0),
+            bytes_in: AtomicU64::new(0),
+            bytes_out: AtomicU64::new(0),
+            latency_buckets: [const { AtomicU64::new(0) }; LATENCY_BUCKETS.len() + 1],
+            latency_micros: AtomicU64::new(0),
+        }
+    }
+
+    fn observe_latency(&self, latency: Duration) {
+        let seconds = latency.as_secs_f64();
+        let bucket = LATENCY_BUCKETS
+            .iter()
+            .position(|&bound| seconds <= bound)
+            .unwrap_or(LATENCY_BUCKETS.len());
+        self.latency_buckets[bucket].fetch_add(1, Ordering::Relaxed);
+        self.latency_micros
+            .fetch_add(latency.as_micros() as u64, Ordering::Relaxed);
+    }
+
+    fn reset(&self) {
+        let counters = [
+            &self.requests,
+            &self.request_errors,
+            &self.retries,
+            &self.bytes_in,
+            &self.bytes_out,
+            &self.latency_micros,
+        ];
+        for counter in counters.into_iter().chain(&self.latency_buckets) {
+            counter.store(0, Ordering::Relaxed);
+        }
+    }
+
+    /// The counters as a dict, the histogram in the shape Prometheus exposes
+    /// it: cumulative counts by upper bound, with their sum and count.
+    fn to_dict<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyDict>> {
+        let load = |counter: &AtomicU64| counter.load(Ordering::Relaxed);
+        let dict = PyDict::new(py);
+        dict.set_item("requests_total", load(&self.requests))?;
+        dict.set_item("request_errors_total", load(&self.request_errors))?;
+        dict.set_item("retries_total", load(&self.retries))?;
+        dict.set_item("bytes_in_total", load(&self.bytes_in))?;
+        dict.set_item("bytes_out_total", load(&self.bytes_out))?;
+
+        let buckets = PyDict::new(py);
+        let mut count = 0;
+        let bounds = LATENCY_BUCKETS.iter().copied().chain([f64::INFINITY]);
+        for (bound, bucket) in bounds.zip(&self.latency_buckets) {
+            count += load(bucket);
+            buckets.set_item(bound, count)?;
+        }
+        let latency = PyDict::new(py);
+        latency.set_item("buckets", buckets)?;
+        latency.set_item("sum", load(&self.latency_micros) as f64 / 1e6)?;
+        latency.set_item("count", count)?;
+        dict.set_item("request_duration_seconds", latency)?;
+        Ok(dict)
+    }
+}
+
+/// Counts a transfer retried by a `RetryPolicy`.
+pub fn record_retry() {
+    METRICS.retries.fetch_add(1, Ordering::Relaxed);
+}
+
+/// When the request of an attempt was sent.
+#[derive(Debug, Clone)]
+struct SentAt(Instant);
+
+impl Storable for SentAt {
+    type Storer = StoreReplace<Self>;
+}
+
+/// Interceptor of every S3 client, feeding the process metrics. Bytes are
+/// counted as the Content-Length of requests and responses.
+#[derive(Debug)]
+pub struct RequestMetrics;
+
+impl Intercept for RequestMetrics {
+    fn name(&self) -> &'static str {
+        "RequestMetrics"
+    }
+
+    fn read_before_transmit(
+        &self,
+        context: &BeforeTransmitInterceptorContextRef<'_>,
+        _runtime_components: &RuntimeComponents,
+        cfg: &mut ConfigBag,
+    ) -> Result<(), BoxError> {
+        METRICS.requests.fetch_add(1, Ordering::Relaxed);
+        let sent = context.request().body().content_length().unwrap_or(0);
+        METRICS.bytes_out.fetch_add(sent, Ordering::Relaxed);
+        cfg.interceptor_state().store_put(SentAt(Instant::now()));
+        Ok(())
+    }
+
+    fn read_after_transmit(
+        &self,
+        context: &BeforeDeserializationInterceptorContextRef<'_>,
+        _runtime_components: &RuntimeComponents,
+        cfg: &mut ConfigBag,
+    ) -> Result<(), BoxError> {
+        if let Some(SentAt(sent_at)) = cfg.load::<SentAt>() {
+            METRICS.observe_latency(sent_at.elapsed());
+        }
+        let response = context.response();
+        if response.status().as_u16() >= 400 {
+            METRICS.request_errors.fetch_add(1, Ordering::Relaxed);
+        }
+        let received = response
+            .headers()
+            .get("content-length")
+            .and_then(|length| length.parse::<u64>().ok())
+            .unwrap_or(0);
+        METRICS.bytes_in.fetch_add(received, Ordering::Relaxed);
+        Ok(())
+    }
+
+    // Requests that got no response at all, such as refused connections or
+    // timeouts, only show up here.
+    fn read_after_attempt(
+        &self,
+        context: &FinalizerInterceptorContextRef<'_>,
+        _runtime_components: &RuntimeComponents,
+        cfg: &mut ConfigBag,
+    ) -> Result<(), BoxError> {
+        if context.response().is_none() && cfg.load::<SentAt>().is_some() {
+            METRICS.request_errors.fetch_add(1, Ordering::Relaxed);
+        }
+        Ok(())
+    }
+}
+
+/// The request metrics of the process so far, as a dict of counters and a
+/// latency histogram.
+#[pyfunction]
+pub fn get_metrics(py: Python<'_>) -> PyResult<Bound<'_, PyDict>> {
+    METRICS.to_dict(py)
+}
+
+/// Sets every metric back to zero.
+#[pyfunction]
+pub fn reset_metrics() {
+    METRICS.reset();
+}
+
+/// Calls `callback` with the metrics every `interval` seconds, from a thread of
+/// its own, replacing the callback set before. `None` stops the calls.
+#[pyfunction]
+#[pyo3(signature = (callback, interval=DEFAULT_INTERVAL))]
+pub fn set_metrics_callback(callback: Option<Bound<'_, PyAny>>, interval: f64) -> PyResult<()> {
+    if !(interval > 0.0 && interval.is_finite()) {
+        return Err(PyValueError::new_err("interval must be a positive number"));
+    }
+    if callback
+        .as_ref()
+        .is_some_and(|callback| !callback.is_callable())
+    {
+        return Err(PyTypeError::new_err("callback must be callable"));
+    }
+
+    let mut reporter = REPORTER.lock().unwrap();
+    // Dropping the sender of the previous callback stops its thread.
+    *reporter = None;
+    let Some(callback) = callback.map(Bound::unbind) else {
+        return Ok(());
+    };
+    let (sender, stopped) = mpsc::channel::<()>();
+    let interval = Duration::from_secs_f64(interval);
+    std::thread::Builder::new()
+        .name("robinzhon-metrics".to_string())
+        .spawn(move || {
+            while let Err(mpsc::RecvTimeoutError::Timeout) = stopped.recv_timeout(interval) {
+                Python::with_gil(|py| {
+                    let reported = METRICS
+                        .to_dict(py)
+                        .and_then(|metrics| callback.call1(py, (metrics,)));
+                    if let Err(error) = reported {
+                        warn!("Metrics callback failed: {}", error);
+                    }
+                });
+            }
+        })
+        .map_err(|e| {
+            PyRuntimeError::new_err(format!("Failed to start the metrics thread: {}", e))
+        })?;
+    *reporter = Some(sender);
+    Ok(())
+}
@@ -0,0 +1,185 @@
+use std::borrow::Cow;
+use std::future::Future;
+use std::sync::Arc;
+
+use aws_sdk_s3::primitives::ByteStream;
+use bytes::{Bytes, BytesMut};
+use pyo3::types::PyTuple;
+use pyo3::{pyclass, pymethods, Bound, PyErr, PyRef, PyRefMut, PyResult, Python};
+use tokio::sync::mpsc;
+use tokio::task::JoinHandle;
+
+use crate::error::TransferError;
+use crate::rate_limit::{throttle, TokenBucket};
+use crate::retry::RetryPolicy;
+use crate::runtime::{block_on_interruptible, ProcessRuntime};
+use crate::s3_config::S3Config;
+
+/// Chunks buffered ahead of the consumer before the download pauses.
+const READAHEAD_CHUNKS: usize = 4;
+
+/// Iterator over the contents of an S3 object, or the records an S3 Select
+/// query returns, yielding `bytes` chunks as they arrive instead of buffering
+/// the whole body.
+///
+/// A background task on the downloader's runtime reads the response and feeds a
+/// small bounded channel, so a slow consumer applies backpressure to the download.
+#[pyclass(module = "robinzhon")]
+pub struct ObjectStream {
+    runtime: Arc<ProcessRuntime>,
+    receiver: Option<mpsc::Receiver<Result<Bytes, TransferError>>>,
+    task: Option<JoinHandle<()>>,
+}
+
+impl ObjectStream {
+    pub fn start(
+        runtime: Arc<ProcessRuntime>,
+        s3_config: Arc<S3Config>,
+        bucket_name: String,
+        object_key: String,
+        chunk_size: usize,
+        retry_policy: RetryPolicy,
+        bandwidth: Option<Arc<TokenBucket>>,
+    ) -> Self {
+        Self::spawn(runtime, |sender| async move {
+            let body = s3_config
+                .run(&bucket_name, &retry_policy, || {
+                    Self::open(&s3_config, &bucket_name, &object_key)
+                })
+                .await;
+            match body {
+                Ok(body) => {
+                    let bandwidth = bandwidth.as_deref();
+                    Self::forward(body, &object_key, chunk_size, bandwidth, &sender).await
+                }
+                Err(error) => {
+                    let _ = sender.send(Err(error)).await;
+                }
+            }
+        })
+    }
+
+    /// Yields what the task built by `produce` sends, as it sends it. The task
+    /// runs on `runtime` and is aborted when the iterator closes.
+    pub fn spawn<F, Fut>(runtime: Arc<ProcessRuntime>, produce: F) -> Self
+    where
+        F: FnOnce(mpsc::Sender<Result<Bytes, TransferError>>) -> Fut,
+        Fut: Future<Output = ()> + Send + 'static,
+    {
+        let (sender, receiver) = mpsc::channel(READAHEAD_CHUNKS);
+        let task = runtime.spawn(produce(sender));
+
+        Self {
+            runtime,
+            receiver: Some(receiver),
+            task: Some(task),
+        }
+    }
+
+    async fn open(
+        s3_config: &S3Config,
+        bucket_name: &str,
+        object_key: &str,
+    ) -> Result<ByteStream, TransferError> {
+        s3_config
+            .client
+            .get_object()
+            .bucket(bucket_name)
+            .key(object_key)
+            .send()
+            .await
+            .map(|response| response.body)
+            .map_err(|e| {
+                TransferError::from_sdk(format!("Failed to get S3 object '{}'", object_key), &e)
+            })
+    }
+
+    /// Re-slices the body into `chunk_size` pieces; only the last one may be shorter.
+    async fn forward(
+        mut body: ByteStream,
+        object_key: &str,
+        chunk_size: usize,
+        bandwidth: Option<&TokenBucket>,
+        sender: &mpsc::Sender<Result<Bytes, TransferError>>,
+    ) {
+        let mut buffer = BytesMut::with_capacity(chunk_size);
+        loop {
+            match body.try_next().await {
+                Ok(Some(bytes)) => {
+                    throttle(bandwidth, bytes.len() as u64).await;
+                    buffer.extend_from_slice(&bytes);
+                    while buffer.len() >= chunk_size {
+                        let chunk = buffer.split_to(chunk_size).freeze();
+                        if sender.send(Ok(chunk)).await.is_err() {
+                            // The consumer went away; stop downloading.
+                            return;
+                        }
+                    }
+                }
+                Ok(None) => {
+                    if !buffer.is_empty() {
+                        let _ = sender.send(Ok(buffer.freeze())).await;
+                    }
+                    return;
+                }
+                Err(e) => {
+                    let error = TransferError::new(format!(
+                        "Failed to read S3 response body for '{}': {}",
+                        object_key, e
+                    ));
+                    let _ = sender.send(Err(error)).await;
+                    return;
+                }
+            }
+        }
+    }
+}
+
+#[pymethods]
+impl ObjectStream {
+    fn __iter__(slf: PyRef<'_, Self>) -> PyRef<'_, Self> {
+        slf
+    }
+
+    fn __next__(&mut self, py: Python<'_>) -> PyResult<Option<Cow<'static, [u8]>>> {
+        let Some(receiver) = self.receiver.as_mut() else {
+            return Ok(None);
+        };
+
+        match block_on_interruptible(py, &self.runtime, receiver.recv())? {
+            Some(Ok(chunk)) => Ok(Some(Cow::Owned(chunk.into()))),
+            Some(Err(error)) => {
+                self.close();
+                Err(PyErr::from(error))
+            }
+            None => {
+                self.close();
+                Ok(None)
+            }
+        }
+    }
+
+    /// Stops the download; the iterator is exhausted afterwards.
+    fn close(&mut self) {
+        self.receiver = None;
+        if let Some(task) = self.task.take() {
+            task.abort();
+        }
+    }
+
+    fn __enter__(slf: PyRefMut<'_, Self>) -> PyRefMut<'_, Self> {
+        slf
+    }
+
+    #[pyo3(signature = (*_args))]
+    fn __exit__(&mut self, _args: &Bound<'_, PyTuple>) -> bool {
+        self.close();
+        false
+    }
+}
+
+impl Drop for ObjectStream {
+    fn drop(&mut self) {
+        self.close();
+    }
+}
@@ -0,0 +1,255 @@
+use std::collections::HashSet;
+use std::time::Duration;
+
+use aws_sdk_s3::presigning::PresigningConfig;
+use aws_sdk_s3::types::{Delete, ObjectIdentifier};
+use futures::stream::{Stream, StreamExt};
+use log::warn;
+use pyo3::exceptions::PyValueError;
+use pyo3::types::PyAnyMethods;
+use pyo3::{Bound, FromPyObject, PyAny, PyResult};
+
+use crate::batch_input::until_error;
+use crate::error::TransferError;
+use crate::retry::RetryPolicy;
+use crate::s3_config::S3Config;
+use crate::s3_uri::is_arn;
+
+/// Longest validity S3 accepts for a presigned URL: seven days.
+pub const MAX_PRESIGN_EXPIRY: Duration = Duration::from_secs(7 * 24 * 60 * 60);
+
+/// The request a presigned URL stands for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PresignMethod {
+    Get,
+    Put,
+}
+
+impl<'py> FromPyObject<'py> for PresignMethod {
+    fn extract_bound(value: &Bound<'py, PyAny>) -> PyResult<Self> {
+        match value.extract::<String>()?.to_ascii_lowercase().as_str() {
+            "get" => Ok(Self::Get),
+            "put" => Ok(Self::Put),
+            other => Err(PyValueError::new_err(format!(
+                "Invalid presign method '{}', expected 'get' or 'put'",
+                other
+            ))),
+        }
+    }
+}
+
+/// Checks the validity of a presigned URL, given in seconds from Python.
+pub fn presign_expiry(expires_in: f64) -> PyResult<Duration> {
+    if expires_in.is_finite() && expires_in > 0.0 && expires_in <= MAX_PRESIGN_EXPIRY.as_secs_f64()
+    {
+        Ok(Duration::from_secs_f64(expires_in))
+    } else {
+        Err(PyValueError::new_err(format!(
+            "expires_in must be between 0 and {} seconds",
+            MAX_PRESIGN_EXPIRY.as_secs()
+        )))
+    }
+}
+
+/// Deletes one object, or one version of it. Deleting a key that doesn't exist
+/// succeeds, as it does in S3.
+pub async fn delete_object(
+    s3_config: &S3Config,
+    bucket_name: &str,
+    object_key: &str,
+    version_id: Option<&str>,
+    retry_policy: &RetryPolicy,
+) -> Result<(), TransferError> {
+    s3_config
+        .run(bucket_name, retry_policy, || async {
+            s3_config
+                .client_for(bucket_name)
+                .delete_object()
+                .bucket(bucket_name)
+                .key(object_key)
+                .set_version_id(version_id.map(str::to_string))
+                .send()
+                .await
+                .map_err(|e| {
+                    TransferError::from_sdk(
+                        format!("Failed to delete S3 object '{}'", object_key),
+                        &e,
+                    )
+                })
+        })
+        .await?;
+    Ok(())
+}
+
+/// Deletes the keys `object_keys` yields in batches of 1000, each sent as soon
+/// as it is read, returning the keys that were deleted. Failures are logged and
+/// skipped; an error from `object_keys` is returned once the keys read before
+/// it are deleted.
+pub async fn delete_objects(
+    s3_config: &S3Config,
+    bucket_name: &str,
+    object_keys: impl Stream<Item = PyResult<String>> + Send,
+    retry_policy: &RetryPolicy,
+) -> PyResult<Vec<String>> {
+    let mut input_error = None;
+    let mut deleted = Vec::new();
+    let mut batches = until_error(object_keys, &mut input_error)
+        .chunks(1000)
+        .boxed();
+    while let Some(batch) = batches.next().await {
+        let objects = batch
+            .iter()
+            .map(|key| ObjectIdentifier::builder().key(key).build())
+            .collect::<Result<Vec<_>, _>>();
+        let request = match objects.and_then(|objects| {
+            Delete::builder()
+                .set_objects(Some(objects))
+                .quiet(true)
+                .build()
+        }) {
+            Ok(request) => request,
+            Err(e) => {
+                warn!("Failed to build delete request: {}", e);
+                continue;
+            }
+        };
+
+        let response = s3_config
+            .run(bucket_name, retry_policy, || async {
+                s3_config
+                    .client_for(bucket_name)
+                    .delete_objects()
+                    .bucket(bucket_name)
+                    .delete(request.clone())
+                    .send()
+                    .await
+                    .map_err(|e| {
+                        TransferError::from_sdk(
+                            format!("Failed to delete objects in bucket '{}'", bucket_name),
+                            &e,
+                        )
+                    })
+            })
+            .await;
+
+        match response {
+            Ok(response) => {
+                let failed: HashSet<&str> = response
+                    .errors()
+                    .iter()
+                    .filter_map(|error| {
+                        warn!(
+                            "Failed to delete '{}': {}",
+                            error.key().unwrap_or_default(),
+                            error.message().unwrap_or_default()
+                        );
+                        error.key()
+                    })
+                    .collect();
+                deleted.extend(
+                    batch
+                        .iter()
+                        .filter(|key| !failed.contains(key.as_str()))
+                        .cloned(),
+                );
+            }
+            Err(error) => warn!("{}", error),
+        }
+    }
+
+    drop(batches);
+    match input_error {
+        Some(error) => Err(error),
+        None => Ok(deleted),
+    }
+}
+
+/// Copies an object within S3 with a single CopyObject request, which S3 allows
+/// for objects of up to 5 GiB. Metadata and headers are copied with it.
+pub async fn copy_object(
+    s3_config: &S3Config,
+    source_bucket: &str,
+    source_key: &str,
+    bucket_name: &str,
+    object_key: &str,
+    retry_policy: &RetryPolicy,
+) -> Result<(), TransferError> {
+    // Through an access point, the source is `<access point ARN>/object/<key>`.
+    let copy_source = if is_arn(source_bucket) {
+        encode_key(&format!("{}/object/{}", source_bucket, source_key))
+    } else {
+        format!("{}/{}", source_bucket, encode_key(source_key))
+    };
+    s3_config
+        .run(bucket_name, retry_policy, || async {
+            s3_config
+                .client_for(bucket_name)
+                .copy_object()
+                .copy_source(&copy_source)
+                .bucket(bucket_name)
+                .key(object_key)
+                .send()
+                .await
+                .map_err(|e| {
+                    TransferError::from_sdk(
+                        format!(
+                            "Failed to copy S3 object '{}' to '{}'",
+                            source_key, object_key
+                        ),
+                        &e,
+                    )
+                })
+        })
+        .await?;
+    Ok(())
+}
+
+/// A URL anyone holding it can GET or PUT the object with until it expires,
+/// signed with the client's credentials. Nothing is sent to S3.
+pub async fn presign(
+    s3_config: &S3Config,
+    bucket_name: &str,
+    object_key: &str,
+    method: PresignMethod,
+    expires_in: Duration,
+) -> Result<String, TransferError> {
+    let config = PresigningConfig::expires_in(expires_in)
+        .map_err(|e| TransferError::new(format!("Invalid presigning configuration: {}", e)))?;
+    let failed = |e: &dyn std::fmt::Display| {
+        TransferError::new(format!(
+            "Failed to presign S3 object '{}': {}",
+            object_key, e
+        ))
+    };
+    let request = match method {
+        PresignMethod::Get => s3_config
+            .client_for(bucket_name)
+            .get_object()
+            .bucket(bucket_name)
+            .key(object_key)
+            .presigned(config)
+            .await
+            .map_err(|e| failed(&e))?,
+        PresignMethod::Put => s3_config
+            .client_for(bucket_name)
+            .put_object()
+            .bucket(bucket_name)
+            .key(object_key)
+            .presigned(config)
+            .await
+            .map_err(|e| failed(&e))?,
+    };
+    Ok(request.uri().to_string())
+}
+
+/// Percent-encodes a key for the `x-amz-copy-source` header, keeping its `/`.
+fn encode_key(key: &str) -> String {
+    key.bytes()
+        .map(|byte| match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' | b'/' => {
+                (byte as char).to_string()
+            }
+            _ => format!("%{:02X}", byte),
+        })
+        .collect()
+}
@@ -0,0 +1,126 @@
+use std::path::Path;
+
+use pyo3::exceptions::PyValueError;
+use pyo3::types::PyAnyMethods;
+use pyo3::{Bound, FromPyObject, PyAny, PyResult};
+
+use crate::error::TransferError;
+use crate::listing::ObjectInfo;
+use crate::metadata::head_object;
+use crate::retry::RetryPolicy;
+use crate::s3_config::S3Config;
+use crate::sync::{file_md5, plain_md5};
+
+/// What a batch download does when the destination file already exists.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum OverwritePolicy {
+    /// Always download, replacing the file.
+    #[default]
+    Always,
+    /// Never replace an existing file.
+    Never,
+    /// Replace the file only if its size differs from the object's.
+    IfSizeDiffers,
+    /// Replace the file only if its content differs from the object's, judged by
+    /// MD5 against the ETag. ETags that aren't plain MD5s (multipart uploads,
+    /// SSE-KMS) can't be checked this way and fall back to the size.
+    IfEtagDiffers,
+}
+
+impl<'py> FromPyObject<'py> for OverwritePolicy {
+    fn extract_bound(value: &Bound<'py, PyAny>) -> PyResult<Self> {
+        match value.extract::<String>()?.as_str() {
+            "always" => Ok(Self::Always),
+            "never" => Ok(Self::Never),
+            "if_size_differs" => Ok(Self::IfSizeDiffers),
+            "if_etag_differs" => Ok(Self::IfEtagDiffers),
+            other => Err(PyValueError::new_err(format!(
+                "Invalid overwrite policy '{}', expected 'always', 'never', 'if_size_differs' or 'if_etag_differs'",
+                other
+            ))),
+        }
+    }
+}
+
+impl OverwritePolicy {
+    /// Whether the download of `object_key` to `local_path` can be skipped because
+    /// the file already there satisfies the policy. Comparing policies cost a HEAD
+    /// request, made only when the file exists.
+    pub async fn keeps_existing(
+        self,
+        s3_config: &S3Config,
+        bucket_name: &str,
+        object_key: &str,
+        version_id: Option<&str>,
+        local_path: &str,
+        retry_policy: &RetryPolicy,
+    ) -> Result<bool, TransferError> {
+        let Some(local_size) = self.existing_size(local_path).await else {
+            return Ok(false);
+        };
+        if self == Self::Never {
+            return Ok(true);
+        }
+        let head =
+            head_object(s3_config, bucket_name, object_key, version_id, retry_policy).await?;
+        self.matches(head.size, head.etag.as_deref(), local_size, local_path)
+            .await
+    }
+
+    /// `keeps_existing` judging by the object's listing, without a request.
+    pub async fn keeps_listed(
+        self,
+        object: &ObjectInfo,
+        local_path: &str,
+    ) -> Result<bool, TransferError> {
+        self.keeps_known(object.size, object.etag.as_deref(), local_path)
+            .await
+    }
+
+    /// `keeps_existing` judging by the `size` and `etag` of an object already
+    /// looked up.
+    pub async fn keeps_known(
+        self,
+        size: u64,
+        etag: Option<&str>,
+        local_path: &str,
+    ) -> Result<bool, TransferError> {
+        let Some(local_size) = self.existing_size(local_path).await else {
+            return Ok(false);
+        };
+        if self == Self::Never {
+            return Ok(true);
+        }
+        self.matches(size, etag, local_size, local_path).await
+    }
+
+    /// Size of the file at `local_path`, unless there is none or the policy
+    /// replaces it anyway.
+    async fn existing_size(self, local_path: &str) -> Option<u64> {
+        if self == Self::Always {
+            return None;
+        }
+        let metadata = tokio::fs::metadata(local_path).await.ok()?;
+        metadata.is_file().then_some(metadata.len())
+    }
+
+    /// Whether the file matches an object of `size` and `etag` closely enough
+    /// for the policy to keep it.
+    async fn matches(
+        self,
+        size: u64,
+        etag: Option<&str>,
+        local_size: u64,
+        local_path: &str,
+    ) -> Result<bool, TransferError> {
+        if size != local_size {
+            return Ok(false);
+        }
+        match (self, etag.and_then(plain_md5)) {
+            (Self::IfEtagDiffers, Some(remote_md5)) => Ok(file_md5(Path::new(local_path))
+                .await?
+                .eq_ignore_ascii_case(remote_md5)),
+            _ => Ok(true),
+        }
+    }
+}
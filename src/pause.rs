@@ -0,0 +1,73 @@
+use futures::stream::{Stream, StreamExt};
+use tokio::sync::watch;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+struct PauseState {
+    paused: bool,
+    /// Whether objects in flight also stop at their next part.
+    parts: bool,
+}
+
+/// Holds back the work of a paused batch until it is resumed: new items are
+/// not dispatched, and with `parts` neither are the next parts of objects
+/// transferred in parts. Requests already sent run to their end.
+#[derive(Debug)]
+pub struct PauseGate {
+    state: watch::Sender<PauseState>,
+}
+
+impl Default for PauseGate {
+    fn default() -> Self {
+        Self {
+            state: watch::Sender::new(PauseState::default()),
+        }
+    }
+}
+
+impl PauseGate {
+    pub fn pause(&self, parts: bool) {
+        self.state.send_replace(PauseState {
+            paused: true,
+            parts,
+        });
+    }
+
+    pub fn resume(&self) {
+        self.state.send_replace(PauseState::default());
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.state.borrow().paused
+    }
+
+    async fn wait_while(&self, held: impl Fn(&PauseState) -> bool) {
+        // The sender lives as long as the gate, so the channel never closes.
+        let _ = self.state.subscribe().wait_for(|state| !held(state)).await;
+    }
+}
+
+/// Waits for the next part of an object to be allowed, if the transfer can be
+/// paused.
+pub async fn before_part(gate: Option<&PauseGate>) {
+    if let Some(gate) = gate {
+        gate.wait_while(|state| state.paused && state.parts).await;
+    }
+}
+
+/// Holds back each item of `stream` while `gate` is paused, so no new item
+/// reaches the concurrent dispatch downstream until it is resumed.
+pub fn gated<'a, S>(
+    stream: S,
+    gate: Option<&'a PauseGate>,
+) -> impl Stream<Item = S::Item> + Send + 'a
+where
+    S: Stream + Send + 'a,
+    S::Item: Send,
+{
+    stream.then(move |item| async move {
+        if let Some(gate) = gate {
+            gate.wait_while(|state| state.paused).await;
+        }
+        item
+    })
+}
@@ -0,0 +1,106 @@
+use pyo3::{pyclass, pymethods, IntoPyObject};
+
+use crate::results::{FailedItem, FailedTransfer};
+
+/// What a batch method returns: its outcome or, given `dry_run=True`, the plan
+/// of what it would have done.
+#[derive(IntoPyObject)]
+pub enum Planned<T> {
+    Done(T),
+    Plan(TransferPlan),
+}
+
+/// What a batch would transfer and delete, worked out by listing and comparing
+/// only: no object or file was read, written or deleted to make it.
+#[pyclass(frozen, module = "robinzhon")]
+#[derive(Debug)]
+pub struct TransferPlan {
+    /// `"download"`, `"upload"` or `"delete"`.
+    #[pyo3(get)]
+    kind: &'static str,
+    /// `(source, destination)` of every item that would be transferred.
+    #[pyo3(get)]
+    transfers: Vec<(String, String)>,
+    /// Objects, or local files, that would be deleted.
+    #[pyo3(get)]
+    deletions: Vec<String>,
+    /// Items that would be left alone because they are up to date.
+    #[pyo3(get)]
+    skipped: Vec<String>,
+    /// Items that would fail whatever S3 answers, such as keys that map to no
+    /// file or files that can't be read.
+    #[pyo3(get)]
+    failed: Vec<FailedItem>,
+    /// Bytes the transfers would move, as far as the listing or the local files
+    /// tell.
+    #[pyo3(get)]
+    total_bytes: u64,
+}
+
+impl TransferPlan {
+    pub fn download() -> Self {
+        Self::new("download")
+    }
+
+    pub fn upload() -> Self {
+        Self::new("upload")
+    }
+
+    /// The plan of deleting `object_keys`. Deletions don't move bytes, so sizes
+    /// aren't looked up.
+    pub fn delete(object_keys: Vec<String>) -> Self {
+        Self {
+            deletions: object_keys,
+            ..Self::new("delete")
+        }
+    }
+
+    fn new(kind: &'static str) -> Self {
+        Self {
+            kind,
+            transfers: Vec::new(),
+            deletions: Vec::new(),
+            skipped: Vec::new(),
+            failed: Vec::new(),
+            total_bytes: 0,
+        }
+    }
+
+    pub fn transfer(&mut self, source: String, destination: String, size: u64) {
+        self.transfers.push((source, destination));
+        self.total_bytes += size;
+    }
+
+    pub fn skip(&mut self, name: String) {
+        self.skipped.push(name);
+    }
+
+    pub fn fail(&mut self, failure: FailedTransfer) {
+        self.failed
+            .push(FailedItem::new(failure.name, failure.error));
+    }
+
+    pub fn deleting(self, deletions: Vec<String>) -> Self {
+        Self { deletions, ..self }
+    }
+}
+
+#[pymethods]
+impl TransferPlan {
+    /// Number of items that would be transferred or deleted.
+    fn __len__(&self) -> usize {
+        self.transfers.len() + self.deletions.len()
+    }
+
+    fn __repr__(&self) -> String {
+        format!(
+            "TransferPlan(kind={:?}, transfers={}, deletions={}, skipped={}, failed={}, total_bytes={})",
+            self.kind,
+            self.transfers.len(),
+            self.deletions.len(),
+            self.skipped.len(),
+            self.failed.len(),
+            self.total_bytes
+        )
+    }
+}
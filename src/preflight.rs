@@ -0,0 +1,193 @@
+use aws_credential_types::provider::ProvideCredentials;
+use aws_sdk_s3::primitives::ByteStream;
+use pyo3::{pyclass, pymethods};
+
+use crate::error::{error_chain, TransferError};
+use crate::s3_config::S3Config;
+
+/// Key the write check's PUT targets; its condition keeps it from being created.
+const PROBE_KEY: &str = ".robinzhon-preflight";
+
+/// ETag the write check's PUT is conditioned on, which no object has.
+const PROBE_ETAG: &str = "\"00000000000000000000000000000000\"";
+
+/// What `S3Client.preflight` found out about a bucket. Checks that couldn't run,
+/// because an earlier one failed, are `None`.
+#[pyclass(frozen, module = "robinzhon")]
+#[derive(Debug, Clone, Default)]
+pub struct PreflightReport {
+    #[pyo3(get)]
+    bucket_name: String,
+    /// Whether credentials could be loaded; `None` for anonymous clients.
+    #[pyo3(get)]
+    credentials: Option<bool>,
+    #[pyo3(get)]
+    bucket_exists: Option<bool>,
+    /// Region S3 reports the bucket in.
+    #[pyo3(get)]
+    bucket_region: Option<String>,
+    /// Whether the bucket is in the client's region; requests for it are
+    /// redirected when it isn't.
+    #[pyo3(get)]
+    region_matches: Option<bool>,
+    /// Whether HeadBucket, which needs `s3:ListBucket`, is allowed.
+    #[pyo3(get)]
+    can_list: Option<bool>,
+    /// Whether PutObject is allowed, as a conditional PUT that writes nothing
+    /// tells.
+    #[pyo3(get)]
+    can_write: Option<bool>,
+    /// Why a batch on the bucket would fail, one line per problem found.
+    #[pyo3(get)]
+    problems: Vec<String>,
+}
+
+#[pymethods]
+impl PreflightReport {
+    /// Whether no problem was found.
+    #[getter]
+    fn ok(&self) -> bool {
+        self.problems.is_empty()
+    }
+
+    fn __bool__(&self) -> bool {
+        self.ok()
+    }
+
+    fn __repr__(&self) -> String {
+        format!(
+            "PreflightReport(bucket_name='{}', ok={}, problems={:?})",
+            self.bucket_name,
+            self.ok(),
+            self.problems
+        )
+    }
+}
+
+/// Checks, in order, that credentials load, that `bucket_name` exists, which
+/// region it is in and that the client may list it and, with `check_write`,
+/// write to it. Stops at the first check a batch couldn't get past.
+pub async fn preflight(
+    s3_config: &S3Config,
+    bucket_name: &str,
+    check_write: bool,
+) -> PreflightReport {
+    let mut report = PreflightReport {
+        bucket_name: bucket_name.to_string(),
+        ..PreflightReport::default()
+    };
+
+    if let Some(credentials) = &s3_config.credentials {
+        if let Err(e) = credentials.provide_credentials().await {
+            report.credentials = Some(false);
+            report.problems.push(format!(
+                "Failed to load AWS credentials: {}",
+                error_chain(&e)
+            ));
+            return report;
+        }
+        report.credentials = Some(true);
+    } else if !s3_config.anonymous {
+        report.credentials = Some(false);
+        report.problems.push("No AWS credentials found".to_string());
+        return report;
+    }
+
+    match s3_config
+        .client
+        .head_bucket()
+        .bucket(bucket_name)
+        .send()
+        .await
+    {
+        Ok(output) => {
+            report.bucket_exists = Some(true);
+            report.can_list = Some(true);
+            report.bucket_region = output.bucket_region().map(str::to_string);
+        }
+        Err(e) => {
+            let error = TransferError::from_sdk(
+                format!("HeadBucket on bucket '{}' failed", bucket_name),
+                &e,
+            );
+            report.bucket_region = error.bucket_region.clone();
+            match error.http_status {
+                Some(404) => {
+                    report.bucket_exists = Some(false);
+                    report
+                        .problems
+                        .push(format!("Bucket '{}' does not exist", bucket_name));
+                    return report;
+                }
+                Some(403) => {
+                    report.bucket_exists = Some(true);
+                    report.can_list = Some(false);
+                    report.problems.push(format!(
+                        "Not allowed to list bucket '{}' (s3:ListBucket)",
+                        bucket_name
+                    ));
+                }
+                // S3 redirects requests for buckets of other regions.
+                Some(301) if report.bucket_region.is_some() => {
+                    report.bucket_exists = Some(true);
+                }
+                _ => {
+                    report.problems.push(error.message);
+                    return report;
+                }
+            }
+        }
+    }
+
+    report.region_matches = report
+        .bucket_region
+        .as_ref()
+        .map(|region| *region == s3_config.region_name);
+    if let Some(region) = report.bucket_region.clone() {
+        s3_config.use_region(bucket_name, region);
+    }
+
+    if check_write {
+        let probe = s3_config
+            .client_for(bucket_name)
+            .put_object()
+            .bucket(bucket_name)
+            .key(PROBE_KEY)
+            .if_match(PROBE_ETAG)
+            .body(ByteStream::from_static(b""))
+            .send()
+            .await;
+        match probe {
+            Ok(_) => {
+                // Stores that ignore the condition create the probe; remove it.
+                let _ = s3_config
+                    .client_for(bucket_name)
+                    .delete_object()
+                    .bucket(bucket_name)
+                    .key(PROBE_KEY)
+                    .send()
+                    .await;
+                report.can_write = Some(true);
+            }
+            Err(e) => {
+                let error = TransferError::from_sdk(
+                    format!("Write check on bucket '{}' failed", bucket_name),
+                    &e,
+                );
+                match error.http_status {
+                    // The condition failed, so the PUT itself was allowed.
+                    Some(404) | Some(412) => report.can_write = Some(true),
+                    Some(403) => {
+                        report.can_write = Some(false);
+                        report.problems.push(format!(
+                            "Not allowed to write to bucket '{}' (s3:PutObject)",
+                            bucket_name
+                        ));
+                    }
+                    _ => report.problems.push(error.message),
+                }
+            }
+        }
+    }
+    report
+}
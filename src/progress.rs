@@ -0,0 +1,246 @@
+use std::sync::atomic::{AtomicU32, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+
+use pyo3::{pyclass, pymethods};
+
+/// Shared counters updated by a running batch.
+#[derive(Debug, Default)]
+pub struct ProgressState {
+    files_total: AtomicU64,
+    files_completed: AtomicU64,
+    files_failed: AtomicU64,
+    bytes_transferred: AtomicU64,
+    bytes_total: AtomicU64,
+    /// Times the item of this state was tried, retries included.
+    attempts: AtomicU32,
+    started_at: Mutex<Option<Instant>>,
+    finished_at: Mutex<Option<Instant>>,
+    /// Batch the bytes counted here also add up to, for the state of one item.
+    parent: Option<Arc<ProgressState>>,
+}
+
+impl ProgressState {
+    /// Counts the bytes of one item of the batch of `parent`, adding them to its
+    /// counters as well.
+    pub fn item_of(parent: &Arc<ProgressState>) -> Self {
+        Self {
+            parent: Some(Arc::clone(parent)),
+            ..Self::default()
+        }
+    }
+
+    pub fn start(&self, files_total: usize) {
+        self.files_total
+            .store(files_total as u64, Ordering::Relaxed);
+        *self.started_at.lock().unwrap() = Some(Instant::now());
+        *self.finished_at.lock().unwrap() = None;
+    }
+
+    /// Grows the total of a batch whose size wasn't known when it started.
+    pub fn add_files_total(&self, files: usize) {
+        self.files_total.fetch_add(files as u64, Ordering::Relaxed);
+    }
+
+    pub fn finish(&self) {
+        *self.finished_at.lock().unwrap() = Some(Instant::now());
+    }
+
+    pub fn add_bytes(&self, bytes: u64) {
+        self.bytes_transferred.fetch_add(bytes, Ordering::Relaxed);
+        if let Some(parent) = &self.parent {
+            parent.add_bytes(bytes);
+        }
+    }
+
+    pub fn add_total_bytes(&self, bytes: u64) {
+        self.bytes_total.fetch_add(bytes, Ordering::Relaxed);
+        if let Some(parent) = &self.parent {
+            parent.add_total_bytes(bytes);
+        }
+    }
+
+    /// Takes back bytes counted by an attempt that didn't complete.
+    fn remove_bytes(&self, transferred: u64, total: u64) {
+        self.bytes_transferred
+            .fetch_sub(transferred, Ordering::Relaxed);
+        self.bytes_total.fetch_sub(total, Ordering::Relaxed);
+        if let Some(parent) = &self.parent {
+            parent.remove_bytes(transferred, total);
+        }
+    }
+
+    pub fn bytes_transferred(&self) -> u64 {
+        self.bytes_transferred.load(Ordering::Relaxed)
+    }
+
+    /// Counts another try at the item of this state.
+    pub fn start_attempt(&self) {
+        self.attempts.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn attempts(&self) -> u32 {
+        self.attempts.load(Ordering::Relaxed)
+    }
+
+    pub fn complete_file(&self) {
+        self.files_completed.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn fail_file(&self) {
+        self.files_failed.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn record<T, E>(&self, outcome: &Result<T, E>) {
+        match outcome {
+            Ok(_) => self.complete_file(),
+            Err(_) => self.fail_file(),
+        }
+    }
+
+    fn elapsed_seconds(&self) -> f64 {
+        let started_at = *self.started_at.lock().unwrap();
+        let finished_at = *self.finished_at.lock().unwrap();
+        match (started_at, finished_at) {
+            (Some(start), Some(end)) => end.duration_since(start).as_secs_f64(),
+            (Some(start), None) => start.elapsed().as_secs_f64(),
+            _ => 0.0,
+        }
+    }
+}
+
+#[pyclass(frozen, module = "robinzhon")]
+#[derive(Debug, Clone, Default)]
+pub struct TransferProgress {
+    pub state: Arc<ProgressState>,
+}
+
+#[pymethods]
+impl TransferProgress {
+    #[new]
+    fn new() -> Self {
+        Self::default()
+    }
+
+    #[getter]
+    fn files_total(&self) -> u64 {
+        self.state.files_total.load(Ordering::Relaxed)
+    }
+
+    #[getter]
+    fn files_completed(&self) -> u64 {
+        self.state.files_completed.load(Ordering::Relaxed)
+    }
+
+    #[getter]
+    fn files_failed(&self) -> u64 {
+        self.state.files_failed.load(Ordering::Relaxed)
+    }
+
+    #[getter]
+    fn bytes_transferred(&self) -> u64 {
+        self.state.bytes_transferred.load(Ordering::Relaxed)
+    }
+
+    #[getter]
+    fn bytes_total(&self) -> u64 {
+        self.state.bytes_total.load(Ordering::Relaxed)
+    }
+
+    #[getter]
+    fn elapsed_seconds(&self) -> f64 {
+        self.state.elapsed_seconds()
+    }
+
+    #[getter]
+    fn is_finished(&self) -> bool {
+        self.state.finished_at.lock().unwrap().is_some()
+    }
+
+    /// Average throughput since the batch started, in bytes per second.
+    #[getter]
+    fn throughput(&self) -> f64 {
+        let elapsed = self.elapsed_seconds();
+        if elapsed == 0.0 {
+            0.0
+        } else {
+            self.bytes_transferred() as f64 / elapsed
+        }
+    }
+
+    /// Estimated seconds until the batch finishes, based on the file completion rate.
+    #[getter]
+    fn eta_seconds(&self) -> Option<f64> {
+        let done = self.files_completed() + self.files_failed();
+        let total = self.files_total();
+        if self.is_finished() || done >= total {
+            return Some(0.0);
+        }
+        if done == 0 {
+            return None;
+        }
+        let per_file = self.elapsed_seconds() / done as f64;
+        Some(per_file * (total - done) as f64)
+    }
+
+    fn __repr__(&self) -> String {
+        format!(
+            "TransferProgress(files={}/{}, failed={}, bytes={})",
+            self.files_completed(),
+            self.files_total(),
+            self.files_failed(),
+            self.bytes_transferred()
+        )
+    }
+}
+
+/// Byte counters contributed by a single transfer attempt. They are rolled back
+/// unless the attempt completes, so retries and cancellations don't inflate totals.
+pub struct AttemptProgress<'a> {
+    progress: &'a ProgressState,
+    transferred: u64,
+    total: u64,
+    committed: bool,
+}
+
+impl<'a> AttemptProgress<'a> {
+    pub fn new(progress: &'a ProgressState) -> Self {
+        Self {
+            progress,
+            transferred: 0,
+            total: 0,
+            committed: false,
+        }
+    }
+
+    pub fn add_bytes(&mut self, bytes: u64) {
+        self.transferred += bytes;
+        self.progress.add_bytes(bytes);
+    }
+
+    pub fn add_total_bytes(&mut self, bytes: u64) {
+        self.total += bytes;
+        self.progress.add_total_bytes(bytes);
+    }
+
+    /// Takes over rolling back bytes that another, already committed attempt
+    /// reported, e.g. one part of a file downloaded in several ranges.
+    pub fn absorb(&mut self, transferred: u64, total: u64) {
+        self.transferred += transferred;
+        self.total += total;
+    }
+
+    /// Keeps the reported bytes and returns them as `(transferred, total)`.
+    pub fn commit(mut self) -> (u64, u64) {
+        self.committed = true;
+        (self.transferred, self.total)
+    }
+}
+
+impl Drop for AttemptProgress<'_> {
+    fn drop(&mut self) {
+        if !self.committed {
+            self.progress.remove_bytes(self.transferred, self.total);
+        }
+    }
+}
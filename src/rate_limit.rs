@@ -0,0 +1,218 @@
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::task::{ready, Context, Poll};
+use std::time::{Duration, Instant};
+
+use aws_sdk_s3::primitives::ByteStream;
+use aws_smithy_types::body::{Error as BodyError, SdkBody};
+use bytes::Bytes;
+use futures::stream::{Stream, StreamExt};
+use http_body::{Body, Frame, SizeHint};
+use pyo3::exceptions::PyValueError;
+use pyo3::PyResult;
+use tokio::time::Sleep;
+
+/// Most bytes of a throttled body sent before the next wait, so a large part
+/// is paced as it goes out rather than leaving at full speed.
+const THROTTLE_CHUNK: usize = 64 * 1024;
+
+/// Token bucket shared by every transfer of a downloader or uploader.
+///
+/// Tokens refill at `rate` per second up to `capacity`. Taking more tokens than
+/// are available leaves the bucket in debt, and the caller sleeps until the debt
+/// is paid back, so amounts larger than the capacity are still paced correctly.
+#[derive(Debug)]
+pub struct TokenBucket {
+    rate: f64,
+    capacity: f64,
+    state: Mutex<BucketState>,
+}
+
+#[derive(Debug)]
+struct BucketState {
+    tokens: f64,
+    refilled_at: Instant,
+}
+
+impl TokenBucket {
+    pub fn new(rate: f64, capacity: f64) -> Self {
+        Self {
+            rate,
+            capacity,
+            state: Mutex::new(BucketState {
+                tokens: capacity,
+                refilled_at: Instant::now(),
+            }),
+        }
+    }
+
+    /// A bucket of `bytes_per_sec`, allowing bursts of up to one second of traffic.
+    pub fn bandwidth(bytes_per_sec: Option<u64>) -> PyResult<Option<Self>> {
+        match bytes_per_sec {
+            None => Ok(None),
+            Some(0) => Err(PyValueError::new_err(
+                "max_bandwidth_bytes_per_sec must be greater than zero",
+            )),
+            Some(rate) => Ok(Some(Self::new(rate as f64, rate as f64))),
+        }
+    }
+
+    /// A bucket of `requests_per_sec` API calls, allowing bursts of `burst` calls,
+    /// or about one second of them when unset.
+    pub fn requests(requests_per_sec: Option<f64>, burst: Option<u32>) -> PyResult<Option<Self>> {
+        let rate = match (requests_per_sec, burst) {
+            (None, None) => return Ok(None),
+            (None, Some(_)) => {
+                return Err(PyValueError::new_err(
+                    "request_burst requires max_requests_per_sec",
+                ))
+            }
+            (Some(rate), _) if !(rate.is_finite() && rate > 0.0) => {
+                return Err(PyValueError::new_err(
+                    "max_requests_per_sec must be a positive number",
+                ))
+            }
+            (Some(rate), _) => rate,
+        };
+        let capacity = match burst {
+            Some(0) => {
+                return Err(PyValueError::new_err(
+                    "request_burst must be greater than zero",
+                ))
+            }
+            Some(burst) => burst as f64,
+            None => rate.ceil(),
+        };
+        Ok(Some(Self::new(rate, capacity)))
+    }
+
+    /// Takes `amount` tokens, waiting for as long as the bucket is in debt.
+    pub async fn take(&self, amount: u64) {
+        let wait = self.reserve(amount);
+        if !wait.is_zero() {
+            tokio::time::sleep(wait).await;
+        }
+    }
+
+    /// Takes `amount` tokens and returns how long the caller must wait for the
+    /// debt they leave to be paid back.
+    fn reserve(&self, amount: u64) -> Duration {
+        let mut state = self.state.lock().unwrap();
+        let now = Instant::now();
+        let refill = now.duration_since(state.refilled_at).as_secs_f64() * self.rate;
+        state.tokens = (state.tokens + refill).min(self.capacity) - amount as f64;
+        state.refilled_at = now;
+        if state.tokens < 0.0 {
+            Duration::from_secs_f64(-state.tokens / self.rate)
+        } else {
+            Duration::ZERO
+        }
+    }
+}
+
+/// Takes `amount` tokens from `bucket`, if the transfer is limited by one.
+pub async fn throttle(bucket: Option<&TokenBucket>, amount: u64) {
+    if let Some(bucket) = bucket {
+        bucket.take(amount).await;
+    }
+}
+
+/// `body`, sent no faster than `bandwidth` allows, if the transfer is limited by
+/// one. The body stays retryable if it was.
+pub fn throttled(body: ByteStream, bandwidth: Option<&Arc<TokenBucket>>) -> ByteStream {
+    let Some(bandwidth) = bandwidth else {
+        return body;
+    };
+    let bandwidth = Arc::clone(bandwidth);
+    ByteStream::new(body.into_inner().map_preserve_contents(move |body| {
+        SdkBody::from_body_1_x(ThrottledBody {
+            inner: body,
+            bandwidth: Arc::clone(&bandwidth),
+            pending: Bytes::new(),
+            waiting: None,
+        })
+    }))
+}
+
+/// A request body handed to the connection a chunk at a time, each one held
+/// back until a token bucket has room for it.
+struct ThrottledBody {
+    inner: SdkBody,
+    bandwidth: Arc<TokenBucket>,
+    /// What is left of the last frame of `inner`.
+    pending: Bytes,
+    /// The next chunk, and the wait before it may be sent.
+    waiting: Option<(Pin<Box<Sleep>>, Bytes)>,
+}
+
+impl Body for ThrottledBody {
+    type Data = Bytes;
+    type Error = BodyError;
+
+    fn poll_frame(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<Frame<Bytes>, BodyError>>> {
+        let this = self.get_mut();
+        if let Some((wait, _)) = &mut this.waiting {
+            ready!(wait.as_mut().poll(cx));
+            let (_, chunk) = this.waiting.take().unwrap();
+            return Poll::Ready(Some(Ok(Frame::data(chunk))));
+        }
+        if this.pending.is_empty() {
+            match ready!(Pin::new(&mut this.inner).poll_frame(cx)) {
+                Some(Ok(frame)) => match frame.into_data() {
+                    Ok(data) => this.pending = data,
+                    Err(frame) => return Poll::Ready(Some(Ok(frame))),
+                },
+                other => return Poll::Ready(other),
+            }
+        }
+        let chunk = this
+            .pending
+            .split_to(THROTTLE_CHUNK.min(this.pending.len()));
+        let wait = this.bandwidth.reserve(chunk.len() as u64);
+        if wait.is_zero() {
+            return Poll::Ready(Some(Ok(Frame::data(chunk))));
+        }
+        let mut wait = Box::pin(tokio::time::sleep(wait));
+        if wait.as_mut().poll(cx).is_ready() {
+            return Poll::Ready(Some(Ok(Frame::data(chunk))));
+        }
+        this.waiting = Some((wait, chunk));
+        Poll::Pending
+    }
+
+    fn is_end_stream(&self) -> bool {
+        self.waiting.is_none() && self.pending.is_empty() && self.inner.is_end_stream()
+    }
+
+    fn size_hint(&self) -> SizeHint {
+        let waiting = self.waiting.as_ref().map_or(0, |(_, chunk)| chunk.len());
+        let buffered = (self.pending.len() + waiting) as u64;
+        let inner = Body::size_hint(&self.inner);
+        let mut hint = SizeHint::new();
+        hint.set_lower(inner.lower() + buffered);
+        if let Some(upper) = inner.upper() {
+            hint.set_upper(upper + buffered);
+        }
+        hint
+    }
+}
+
+/// Holds back each item of `stream` until `requests` allows one more request, so
+/// items only reach the concurrent dispatch downstream at the allowed rate.
+pub fn paced<'a, S>(
+    stream: S,
+    requests: Option<&'a TokenBucket>,
+) -> impl Stream<Item = S::Item> + Send + 'a
+where
+    S: Stream + Send + 'a,
+    S::Item: Send,
+{
+    stream.then(move |item| async move {
+        throttle(requests, 1).await;
+        item
+    })
+}
@@ -0,0 +1,134 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Instant;
+
+use aws_sdk_s3::config::interceptors::{
+    BeforeTransmitInterceptorContextRef, FinalizerInterceptorContextRef,
+};
+use aws_sdk_s3::config::{ConfigBag, Intercept, RuntimeComponents};
+use aws_sdk_s3::error::BoxError;
+use aws_smithy_types::config_bag::{Storable, StoreReplace};
+use log::debug;
+use pyo3::exceptions::PyValueError;
+use pyo3::types::{PyAnyMethods, PyModule};
+use pyo3::{pyfunction, Bound, PyAny, PyResult};
+
+/// `logging.DEBUG`, the level at which every request is logged.
+const DEBUG: i32 = 10;
+
+/// Whether requests are logged, which `set_log_level` turns on with the debug
+/// level. Checked before touching the logger, which takes the GIL.
+static VERBOSE: AtomicBool = AtomicBool::new(false);
+
+/// The attempt of an operation whose request is in flight, and when it was sent.
+#[derive(Debug, Clone)]
+struct Attempt {
+    number: u32,
+    sent_at: Instant,
+}
+
+impl Storable for Attempt {
+    type Storer = StoreReplace<Self>;
+}
+
+/// Interceptor of every S3 client logging each request it sends, once signed,
+/// and how it went, when `set_log_level` asks for debug logs.
+#[derive(Debug)]
+pub struct RequestLog;
+
+impl Intercept for RequestLog {
+    fn name(&self) -> &'static str {
+        "RequestLog"
+    }
+
+    fn read_before_transmit(
+        &self,
+        context: &BeforeTransmitInterceptorContextRef<'_>,
+        _runtime_components: &RuntimeComponents,
+        cfg: &mut ConfigBag,
+    ) -> Result<(), BoxError> {
+        if !VERBOSE.load(Ordering::Relaxed) {
+            return Ok(());
+        }
+        // The interceptor state lasts for the whole operation, so the attempts
+        // the SDK retries count up from the first.
+        let number = cfg
+            .load::<Attempt>()
+            .map_or(1, |attempt| attempt.number + 1);
+        let request = context.request();
+        debug!(
+            "Sending {} {} (attempt {})",
+            request.method(),
+            without_query(request.uri()),
+            number
+        );
+        cfg.interceptor_state().store_put(Attempt {
+            number,
+            sent_at: Instant::now(),
+        });
+        Ok(())
+    }
+
+    fn read_after_attempt(
+        &self,
+        context: &FinalizerInterceptorContextRef<'_>,
+        _runtime_components: &RuntimeComponents,
+        cfg: &mut ConfigBag,
+    ) -> Result<(), BoxError> {
+        if !VERBOSE.load(Ordering::Relaxed) {
+            return Ok(());
+        }
+        let (Some(attempt), Some(request)) = (cfg.load::<Attempt>(), context.request()) else {
+            return Ok(());
+        };
+        let elapsed_ms = attempt.sent_at.elapsed().as_secs_f64() * 1000.0;
+        let url = without_query(request.uri());
+        match context.response() {
+            Some(response) => debug!(
+                "{} {} (attempt {}) answered {} in {:.1} ms",
+                request.method(),
+                url,
+                attempt.number,
+                response.status().as_u16(),
+                elapsed_ms
+            ),
+            None => debug!(
+                "{} {} (attempt {}) got no response after {:.1} ms",
+                request.method(),
+                url,
+                attempt.number,
+                elapsed_ms
+            ),
+        }
+        Ok(())
+    }
+}
+
+/// The URL of a request without its query string, which holds the signature
+/// of presigned requests.
+fn without_query(uri: &str) -> &str {
+    uri.split_once('?').map_or(uri, |(url, _)| url)
+}
+
+/// Sets the level of the `robinzhon` logger, by name or number as `logging`
+/// takes them. At `"DEBUG"` or below every request is logged too, with its URL,
+/// attempt number and timing.
+#[pyfunction]
+pub fn set_log_level(level: &Bound<'_, PyAny>) -> PyResult<()> {
+    let logging = PyModule::import(level.py(), "logging")?;
+    let number = match level.extract::<i32>() {
+        Ok(number) => number,
+        // For a known level name `getLevelName` returns its number.
+        Err(_) => {
+            let name = level.extract::<String>()?.to_ascii_uppercase();
+            logging
+                .call_method1("getLevelName", (&name,))?
+                .extract::<i32>()
+                .map_err(|_| PyValueError::new_err(format!("Unknown log level '{}'", name)))?
+        }
+    };
+    logging
+        .call_method1("getLogger", ("robinzhon",))?
+        .call_method1("setLevel", (number,))?;
+    VERBOSE.store(number > 0 && number <= DEBUG, Ordering::Relaxed);
+    Ok(())
+}
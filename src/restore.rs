@@ -0,0 +1,291 @@
+use std::time::Duration;
+
+use aws_sdk_s3::types::{GlacierJobParameters, RestoreRequest, Tier};
+use futures::future;
+use futures::stream::{self, Stream, StreamExt};
+use log::warn;
+use pyo3::exceptions::PyValueError;
+use pyo3::types::PyAnyMethods;
+use pyo3::{Bound, FromPyObject, PyAny, PyResult};
+use tokio::time::{sleep, Instant};
+
+use crate::batch_input::until_error;
+use crate::error::TransferError;
+use crate::failures::{until_limit, FailureLimit};
+use crate::metadata::{head_object, ObjectMetadata};
+use crate::progress::ProgressState;
+use crate::rate_limit::paced;
+use crate::results::{Completed, FailedTransfer, RustOperationResult};
+use crate::retry::RetryPolicy;
+use crate::s3_config::S3Config;
+use crate::settings::TransferSettings;
+
+/// Storage classes whose objects must be restored before they can be read.
+const ARCHIVE_CLASSES: &[&str] = &["GLACIER", "DEEP_ARCHIVE"];
+
+/// How fast archived objects are restored, and at what price.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum RestoreTier {
+    /// Within hours: 3-5 for GLACIER, up to 12 for DEEP_ARCHIVE.
+    #[default]
+    Standard,
+    /// Cheapest, within 5-12 hours for GLACIER and 48 for DEEP_ARCHIVE.
+    Bulk,
+    /// Within minutes; not available for DEEP_ARCHIVE.
+    Expedited,
+}
+
+impl<'py> FromPyObject<'py> for RestoreTier {
+    fn extract_bound(value: &Bound<'py, PyAny>) -> PyResult<Self> {
+        match value.extract::<String>()?.as_str() {
+            "standard" => Ok(Self::Standard),
+            "bulk" => Ok(Self::Bulk),
+            "expedited" => Ok(Self::Expedited),
+            other => Err(PyValueError::new_err(format!(
+                "Invalid restore tier '{}', expected 'standard', 'bulk' or 'expedited'",
+                other
+            ))),
+        }
+    }
+}
+
+impl From<RestoreTier> for Tier {
+    fn from(tier: RestoreTier) -> Self {
+        match tier {
+            RestoreTier::Standard => Tier::Standard,
+            RestoreTier::Bulk => Tier::Bulk,
+            RestoreTier::Expedited => Tier::Expedited,
+        }
+    }
+}
+
+/// Checks the number of days a restored copy is kept, which S3 requires to be
+/// at least one.
+pub fn restore_days(days: i32) -> PyResult<i32> {
+    if days < 1 {
+        return Err(PyValueError::new_err("days must be at least 1"));
+    }
+    Ok(days)
+}
+
+/// The polling interval and optional timeout of `wait_for_restore`, in seconds.
+pub fn poll_durations(
+    poll_interval: f64,
+    timeout: Option<f64>,
+) -> PyResult<(Duration, Option<Duration>)> {
+    if !(poll_interval > 0.0 && poll_interval.is_finite()) {
+        return Err(PyValueError::new_err(
+            "poll_interval must be a positive number of seconds",
+        ));
+    }
+    let timeout = timeout
+        .map(|timeout| {
+            Duration::try_from_secs_f64(timeout).map_err(|_| {
+                PyValueError::new_err("timeout must be a non-negative number of seconds")
+            })
+        })
+        .transpose()?;
+    Ok((Duration::from_secs_f64(poll_interval), timeout))
+}
+
+/// Where an object stands as far as reading it is concerned.
+enum RestoreState {
+    /// Readable: not archived, or restored.
+    Available,
+    InProgress,
+    /// Archived in the given storage class, with no restore requested.
+    NotRequested(String),
+}
+
+impl RestoreState {
+    /// Reads the state off the `x-amz-restore` header, which S3 sets once a
+    /// restore was requested: `ongoing-request="true"` while it runs, then
+    /// `ongoing-request="false", expiry-date="..."`.
+    fn of(metadata: &ObjectMetadata) -> Self {
+        match (&metadata.restore, &metadata.storage_class) {
+            (Some(restore), _) if restore.contains("ongoing-request=\"true\"") => Self::InProgress,
+            (None, Some(class)) if ARCHIVE_CLASSES.contains(&class.as_str()) => {
+                Self::NotRequested(class.clone())
+            }
+            _ => Self::Available,
+        }
+    }
+}
+
+/// Requests a temporary copy of an archived object, readable for `days`. A restore
+/// already under way is left alone and reported as skipped.
+async fn restore_object(
+    s3_config: &S3Config,
+    bucket_name: &str,
+    object_key: &str,
+    tier: RestoreTier,
+    days: i32,
+    retry_policy: &RetryPolicy,
+) -> Result<Completed, TransferError> {
+    let parameters = GlacierJobParameters::builder()
+        .tier(tier.into())
+        .build()
+        .map_err(|e| TransferError::new(e.to_string()))?;
+    let request = RestoreRequest::builder()
+        .days(days)
+        .glacier_job_parameters(parameters)
+        .build();
+
+    let outcome = s3_config
+        .run(bucket_name, retry_policy, || async {
+            s3_config
+                .client_for(bucket_name)
+                .restore_object()
+                .bucket(bucket_name)
+                .key(object_key)
+                .restore_request(request.clone())
+                .send()
+                .await
+                .map_err(|e| {
+                    TransferError::from_sdk(
+                        format!("Failed to restore S3 object '{}'", object_key),
+                        &e,
+                    )
+                })
+        })
+        .await;
+
+    match outcome {
+        Ok(_) => Ok(Completed::Transferred(object_key.to_string())),
+        Err(error) if error.code.as_deref() == Some("RestoreAlreadyInProgress") => {
+            Ok(Completed::Skipped(object_key.to_string()))
+        }
+        Err(error) => Err(error),
+    }
+}
+
+/// Requests the restore of several archived objects concurrently, as
+/// `object_keys` yields their keys; `total` is how many there are, when known.
+/// An error from `object_keys`, or the failure limit of `settings` being
+/// reached, is returned once the requests already sent are done.
+#[allow(clippy::too_many_arguments)]
+pub async fn restore_objects(
+    s3_config: &S3Config,
+    bucket_name: &str,
+    object_keys: impl Stream<Item = PyResult<String>> + Send,
+    total: Option<usize>,
+    tier: RestoreTier,
+    days: i32,
+    settings: &TransferSettings,
+    progress: &ProgressState,
+) -> PyResult<RustOperationResult> {
+    progress.start(total.unwrap_or_default());
+
+    let mut input_error = None;
+    let failures = FailureLimit::new(settings.failure_limit);
+    let mut result = RustOperationResult::default();
+    let retry_policy = &settings.retry_policy;
+
+    let object_keys = until_limit(until_error(object_keys, &mut input_error), &failures);
+    paced(
+        object_keys.map(|object_key| {
+            if total.is_none() {
+                progress.add_files_total(1);
+            }
+            async move {
+                let outcome = restore_object(
+                    s3_config,
+                    bucket_name,
+                    &object_key,
+                    tier,
+                    days,
+                    retry_policy,
+                )
+                .await;
+                progress.record(&outcome);
+                outcome.map_err(|error| {
+                    FailedTransfer::new(&object_key, (object_key.clone(), String::new()), error)
+                })
+            }
+        }),
+        settings.requests.as_deref(),
+    )
+    .buffer_unordered(settings.max_concurrent)
+    .for_each(|outcome| {
+        result.record(outcome);
+        failures.update(result.failed.len());
+        future::ready(())
+    })
+    .await;
+
+    progress.finish();
+    if !result.failed.is_empty() {
+        warn!("{} restores failed", result.failed.len());
+        for failure in &result.failed {
+            warn!("  {}: {}", failure.name, failure.error);
+        }
+    }
+    match (failures.error(&result), input_error) {
+        (Some(error), _) | (None, Some(error)) => Err(error),
+        (None, None) => Ok(result),
+    }
+}
+
+/// Polls the objects with HEAD requests every `poll_interval` until they are all
+/// readable. Fails if one of them is archived with no restore requested, since it
+/// would never become readable, or if `timeout` runs out first.
+pub async fn wait_for_restore(
+    s3_config: &S3Config,
+    bucket_name: &str,
+    object_keys: Vec<String>,
+    poll_interval: Duration,
+    timeout: Option<Duration>,
+    settings: &TransferSettings,
+) -> Result<(), TransferError> {
+    let deadline = timeout.map(|timeout| Instant::now() + timeout);
+    let retry_policy = &settings.retry_policy;
+    let mut pending = object_keys;
+
+    loop {
+        let states: Vec<_> = stream::iter(pending.iter().cloned().map(|object_key| async move {
+            head_object(s3_config, bucket_name, &object_key, None, retry_policy)
+                .await
+                .map(|metadata| RestoreState::of(&metadata))
+        }))
+        .buffered(settings.max_concurrent)
+        .collect()
+        .await;
+
+        let mut restoring = Vec::new();
+        for (object_key, state) in pending.into_iter().zip(states) {
+            match state? {
+                RestoreState::Available => {}
+                RestoreState::InProgress => restoring.push(object_key),
+                RestoreState::NotRequested(class) => {
+                    return Err(TransferError::new(format!(
+                        "S3 object '{}' is archived in {} and no restore was requested",
+                        object_key, class
+                    )))
+                }
+            }
+        }
+        pending = restoring;
+        if pending.is_empty() {
+            return Ok(());
+        }
+
+        let wait = match deadline {
+            Some(deadline) => {
+                let left = deadline.saturating_duration_since(Instant::now());
+                if left.is_zero() {
+                    return Err(TransferError {
+                        timed_out: true,
+                        ..TransferError::new(format!(
+                            "Timed out waiting for the restore of {} S3 objects, including '{}'",
+                            pending.len(),
+                            pending[0]
+                        ))
+                    });
+                }
+                poll_interval.min(left)
+            }
+            None => poll_interval,
+        };
+        sleep(wait).await;
+    }
+}
@@ -1,40 +1,1029 @@
-use pyo3::{pyclass, pymethods};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 
+use pyo3::exceptions::{PyIndexError, PyValueError};
+use pyo3::types::{PyAnyMethods, PyDict, PyDictMethods, PyIterator, PyList};
+use pyo3::{pyclass, pymethods, Bound, FromPyObject, Py, PyErr, PyResult, Python};
+
+use serde::{Deserialize, Serialize};
+use tokio::sync::mpsc;
+
+use crate::byte_range::ByteRange;
+use crate::conditions::GetConditions;
+use crate::error::TransferError;
+use crate::progress::ProgressState;
+use crate::run_report::{write_report, ReportFormat, ReportSummary};
+use crate::session::SessionJournal;
+use crate::upload_options::UploadOptions;
+
+/// An item of a batch, as needed to run it (again).
+#[derive(Debug, Clone)]
+pub struct TransferRequest {
+    /// S3 key or URI for downloads, local path for uploads.
+    pub source: String,
+    pub destination: String,
+    /// Version of the object to download; the current one when `None`.
+    pub version_id: Option<String>,
+    /// When the download can be skipped because the local copy is current.
+    pub conditions: GetConditions,
+    /// Slice of the object to download; all of it when `None`.
+    pub range: Option<ByteRange>,
+    /// Upload only if the key doesn't exist yet.
+    pub create_only: bool,
+    /// Headers and metadata of the uploaded object.
+    pub upload_options: Arc<UploadOptions>,
+}
+
+impl From<(String, String)> for TransferRequest {
+    fn from((source, destination): (String, String)) -> Self {
+        Self {
+            source,
+            destination,
+            version_id: None,
+            conditions: GetConditions::default(),
+            range: None,
+            create_only: false,
+            upload_options: Arc::default(),
+        }
+    }
+}
+
+/// A failed item: the name reported back to Python, the request needed to run it
+/// again, and why it failed.
+#[derive(Debug)]
+pub struct FailedTransfer {
+    pub name: String,
+    pub request: TransferRequest,
+    pub error: TransferError,
+}
+
+impl FailedTransfer {
+    pub fn new(name: &str, request: impl Into<TransferRequest>, error: TransferError) -> Self {
+        Self {
+            name: name.to_string(),
+            request: request.into(),
+            error,
+        }
+    }
+}
+
+/// A checksum S3 stored with an uploaded object: the algorithm and its
+/// base64-encoded value.
+pub type StoredChecksum = (String, String);
+
+/// What S3 reported storing for an uploaded item.
+#[derive(Debug, Default)]
+pub struct StoredObject {
+    /// Server-side encryption S3 applied.
+    pub encryption: Option<String>,
+    pub checksum: Option<StoredChecksum>,
+    pub etag: Option<String>,
+    /// Version of the object, in a versioned bucket.
+    pub version_id: Option<String>,
+}
+
+/// An item of a batch that didn't fail.
+#[derive(Debug)]
+pub enum Completed {
+    Transferred(String),
+    /// Uploaded, with what S3 reported storing.
+    Uploaded {
+        path: String,
+        stored: StoredObject,
+    },
+    /// Downloaded and stored decompressed, with the size of the object and
+    /// of the file it inflated to.
+    Decompressed {
+        path: String,
+        compressed_bytes: u64,
+        decompressed_bytes: u64,
+    },
+    /// Left alone because the destination already had it.
+    Skipped(String),
+}
+
+impl Completed {
+    /// An uploaded item, with what S3 reported storing, if anything.
+    pub fn uploaded(path: String, stored: StoredObject) -> Self {
+        match stored {
+            StoredObject {
+                encryption: None,
+                checksum: None,
+                etag: None,
+                version_id: None,
+            } => Self::Transferred(path),
+            stored => Self::Uploaded { path, stored },
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        match self {
+            Self::Transferred(path)
+            | Self::Uploaded { path, .. }
+            | Self::Decompressed { path, .. }
+            | Self::Skipped(path) => path,
+        }
+    }
+
+    pub fn into_name(self) -> String {
+        match self {
+            Self::Transferred(path)
+            | Self::Uploaded { path, .. }
+            | Self::Decompressed { path, .. }
+            | Self::Skipped(path) => path,
+        }
+    }
+}
+
+impl From<String> for Completed {
+    fn from(path: String) -> Self {
+        Self::Transferred(path)
+    }
+}
+
+/// The bytes an item moved, when and how long it ran and how many times it was
+/// tried, failures included.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct ItemStats {
+    pub bytes: u64,
+    pub elapsed_seconds: f64,
+    /// Unix times the item started and finished at, zero when it wasn't timed.
+    #[serde(default)]
+    pub started_at: f64,
+    #[serde(default)]
+    pub finished_at: f64,
+    /// Zero for items that weren't transferred.
+    #[serde(default)]
+    pub attempts: u32,
+}
+
+impl ItemStats {
+    /// The stats of an item started at `started`, whose bytes and attempts
+    /// `progress` counted.
+    pub fn measure(started: Instant, progress: &ProgressState) -> Self {
+        Self::since(started, progress.bytes_transferred(), progress.attempts())
+    }
+
+    /// The stats of an item started at `started` and finishing now.
+    pub fn since(started: Instant, bytes: u64, attempts: u32) -> Self {
+        let elapsed_seconds = started.elapsed().as_secs_f64();
+        let finished_at = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs_f64();
+        Self {
+            bytes,
+            elapsed_seconds,
+            started_at: finished_at - elapsed_seconds,
+            finished_at,
+            attempts,
+        }
+    }
+
+    /// The stats of an item, unless they are the zeroes of an untimed one.
+    pub fn timed(self) -> Option<Self> {
+        (self.finished_at > 0.0).then_some(self)
+    }
+}
+
+/// An item of a batch as it ran, besides its outcome.
 #[derive(Debug)]
+pub struct ItemReport {
+    /// Position of the item in the input of the batch.
+    pub index: usize,
+    /// S3 key or URI for downloads, local path for uploads.
+    pub source: String,
+    pub destination: String,
+    pub stats: ItemStats,
+}
+
+#[derive(Debug, Default)]
 pub struct RustOperationResult {
     pub successful: Vec<String>,
-    pub failed: Vec<(String, String)>,
+    pub failed: Vec<FailedTransfer>,
+    pub skipped: Vec<String>,
+    pub encryption: HashMap<String, String>,
+    pub checksums: HashMap<String, StoredChecksum>,
+    pub etags: HashMap<String, String>,
+    pub version_ids: HashMap<String, String>,
+    pub decompressed: HashMap<String, (u64, u64)>,
+    pub destinations: HashMap<String, String>,
+    /// Stats of every item, by the name it is reported under.
+    pub stats: HashMap<String, ItemStats>,
+    /// Input position of every item, by the name it is reported under.
+    pub positions: HashMap<String, usize>,
+    /// Wall-clock time of the batch.
+    pub elapsed: Duration,
+    /// Session checkpointing the outcome of every item, if the batch runs in one.
+    pub journal: Option<Arc<SessionJournal>>,
+    /// Where every recorded item is sent as it finishes, if the batch is iterated.
+    pub events: Option<mpsc::UnboundedSender<ResultItem>>,
+}
+
+impl RustOperationResult {
+    /// A result whose items are also checkpointed to `journal`.
+    pub fn journaled(journal: Arc<SessionJournal>) -> Self {
+        Self {
+            journal: Some(journal),
+            ..Self::default()
+        }
+    }
+
+    /// A result that also sends every item it records to `events`.
+    pub fn streaming(events: mpsc::UnboundedSender<ResultItem>) -> Self {
+        Self {
+            events: Some(events),
+            ..Self::default()
+        }
+    }
+
+    pub fn record(&mut self, outcome: Result<impl Into<Completed>, FailedTransfer>) {
+        let outcome = outcome.map(Into::into);
+        if let Some(journal) = &self.journal {
+            journal.record(&outcome);
+        }
+        match outcome {
+            Ok(Completed::Transferred(path)) => self.successful.push(path),
+            Ok(Completed::Uploaded { path, stored }) => {
+                if let Some(algorithm) = stored.encryption {
+                    self.encryption.insert(path.clone(), algorithm);
+                }
+                if let Some(checksum) = stored.checksum {
+                    self.checksums.insert(path.clone(), checksum);
+                }
+                if let Some(etag) = stored.etag {
+                    self.etags.insert(path.clone(), etag);
+                }
+                if let Some(version_id) = stored.version_id {
+                    self.version_ids.insert(path.clone(), version_id);
+                }
+                self.successful.push(path);
+            }
+            Ok(Completed::Decompressed {
+                path,
+                compressed_bytes,
+                decompressed_bytes,
+            }) => {
+                self.decompressed
+                    .insert(path.clone(), (compressed_bytes, decompressed_bytes));
+                self.successful.push(path);
+            }
+            Ok(Completed::Skipped(path)) => self.skipped.push(path),
+            Err(failure) => self.failed.push(failure),
+        }
+    }
+
+    /// Records the outcome of the item of `report`, with its stats, and the pair
+    /// it was transferred between unless it failed.
+    pub fn record_transfer(
+        &mut self,
+        report: ItemReport,
+        outcome: Result<impl Into<Completed>, FailedTransfer>,
+    ) {
+        let outcome = outcome.map(Into::into);
+        if let Some(events) = &self.events {
+            // A consumer that went away doesn't stop the batch.
+            let _ = events.send(ResultItem::finished(&report, &outcome));
+        }
+        let name = match &outcome {
+            Ok(completed) => completed.name(),
+            Err(failure) => &failure.name,
+        };
+        self.stats.insert(name.to_string(), report.stats);
+        self.positions.insert(name.to_string(), report.index);
+        if outcome.is_ok() {
+            self.destinations.insert(report.source, report.destination);
+        }
+        self.record(outcome);
+    }
+
+    pub fn take(&mut self) -> Self {
+        std::mem::take(self)
+    }
+
+    /// Adds the items of `other`, a part of this batch whose positions start at
+    /// `offset` in it.
+    pub fn absorb(&mut self, other: RustOperationResult, offset: usize) {
+        self.successful.extend(other.successful);
+        self.failed.extend(other.failed);
+        self.skipped.extend(other.skipped);
+        self.encryption.extend(other.encryption);
+        self.checksums.extend(other.checksums);
+        self.etags.extend(other.etags);
+        self.version_ids.extend(other.version_ids);
+        self.decompressed.extend(other.decompressed);
+        self.destinations.extend(other.destinations);
+        self.stats.extend(other.stats);
+        self.positions.extend(
+            other
+                .positions
+                .into_iter()
+                .map(|(name, index)| (name, offset + index)),
+        );
+    }
+}
+
+/// Attaches whatever a batch finished before it was interrupted to the raised error
+/// as `partial_results`, so callers catching `KeyboardInterrupt` keep the accounting.
+pub fn with_partial_results(
+    py: Python<'_>,
+    error: PyErr,
+    collected: &Mutex<RustOperationResult>,
+) -> PyErr {
+    let partial = Results::from(collected.lock().unwrap().take());
+    if let Ok(partial) = Py::new(py, partial) {
+        let _ = error.value(py).setattr("partial_results", partial);
+    }
+    error
+}
+
+impl From<RustOperationResult> for Results {
+    fn from(result: RustOperationResult) -> Self {
+        let (failed, failed_requests) = result
+            .failed
+            .into_iter()
+            .map(|failure| {
+                (
+                    FailedItem::new(failure.name, failure.error),
+                    failure.request,
+                )
+            })
+            .unzip();
+
+        Self {
+            successful: result.successful,
+            failed,
+            skipped: result.skipped,
+            encryption: result.encryption,
+            checksums: result.checksums,
+            etags: result.etags,
+            version_ids: result.version_ids,
+            decompressed: result.decompressed,
+            destinations: result.destinations,
+            stats: result.stats,
+            positions: result.positions,
+            elapsed_seconds: result.elapsed.as_secs_f64(),
+            failed_requests,
+            origin: None,
+            item_cache: OnceLock::new(),
+        }
+    }
+}
+
+/// Why a single item of a batch failed.
+#[pyclass(frozen, module = "robinzhon")]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FailedItem {
+    /// S3 key for downloads, local path for uploads.
+    #[pyo3(get)]
+    pub key: String,
+    #[pyo3(get)]
+    pub error_message: String,
+    #[pyo3(get)]
+    pub error_code: Option<String>,
+    #[pyo3(get)]
+    pub http_status: Option<u16>,
+    #[pyo3(get)]
+    pub retryable: bool,
+    /// `x-amz-request-id` of the failed response, for support tickets.
+    #[pyo3(get)]
+    #[serde(default)]
+    pub request_id: Option<String>,
+    /// `x-amz-id-2` of the failed response.
+    #[pyo3(get)]
+    #[serde(default)]
+    pub extended_request_id: Option<String>,
+}
+
+impl FailedItem {
+    pub fn new(key: String, error: TransferError) -> Self {
+        Self {
+            key,
+            error_message: error.message,
+            error_code: error.code,
+            http_status: error.http_status,
+            retryable: error.retryable,
+            request_id: error.request_id.map(String::from),
+            extended_request_id: error.extended_request_id.map(String::from),
+        }
+    }
+
+    fn to_dict<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyDict>> {
+        let dict = PyDict::new(py);
+        dict.set_item("key", &self.key)?;
+        dict.set_item("error_message", &self.error_message)?;
+        dict.set_item("error_code", &self.error_code)?;
+        dict.set_item("http_status", self.http_status)?;
+        dict.set_item("retryable", self.retryable)?;
+        dict.set_item("request_id", &self.request_id)?;
+        dict.set_item("extended_request_id", &self.extended_request_id)?;
+        Ok(dict)
+    }
+}
+
+#[pymethods]
+impl FailedItem {
+    fn __repr__(&self) -> String {
+        format!(
+            "FailedItem(key={:?}, error_code={}, http_status={}, retryable={})",
+            self.key,
+            self.error_code
+                .as_deref()
+                .map_or_else(|| "None".to_string(), |code| format!("{:?}", code)),
+            self.http_status
+                .map_or_else(|| "None".to_string(), |status| status.to_string()),
+            if self.retryable { "True" } else { "False" }
+        )
+    }
+
+    fn __str__(&self) -> String {
+        format!("{}: {}", self.key, self.error_message)
+    }
+}
+
+/// Failed entries accepted by the `Results` constructor: full items, or bare keys
+/// as in earlier releases.
+#[derive(FromPyObject)]
+pub enum FailedEntry {
+    Item(FailedItem),
+    Key(String),
+}
+
+impl From<FailedEntry> for FailedItem {
+    fn from(entry: FailedEntry) -> Self {
+        match entry {
+            FailedEntry::Item(item) => item,
+            FailedEntry::Key(key) => FailedItem::new(key, TransferError::default()),
+        }
+    }
+}
+
+/// One item of a batch and how it ended, as `Results` iterates them.
+#[pyclass(frozen, module = "robinzhon")]
+#[derive(Debug, Clone)]
+pub struct ResultItem {
+    /// Local path, or S3 key for failed downloads, as in the lists of `Results`.
+    #[pyo3(get)]
+    pub name: String,
+    /// "successful", "skipped" or "failed".
+    #[pyo3(get)]
+    pub status: &'static str,
+    /// S3 key for downloads, local path for uploads, when known.
+    #[pyo3(get)]
+    pub source: Option<String>,
+    #[pyo3(get)]
+    pub destination: Option<String>,
+    /// Why the item failed.
+    #[pyo3(get)]
+    pub error: Option<FailedItem>,
+    /// Position of the item in the input of its batch.
+    #[pyo3(get)]
+    pub index: Option<usize>,
+    pub stats: Option<ItemStats>,
+    /// ETag S3 returned for an uploaded item.
+    #[pyo3(get)]
+    pub etag: Option<String>,
+    /// Version S3 gave an uploaded item, in a versioned bucket.
+    #[pyo3(get)]
+    pub version_id: Option<String>,
+}
+
+impl ResultItem {
+    /// The item of `report`, as it just finished with `outcome`.
+    pub fn finished(report: &ItemReport, outcome: &Result<Completed, FailedTransfer>) -> Self {
+        match outcome {
+            Ok(completed) => {
+                let stored = match completed {
+                    Completed::Uploaded { stored, .. } => Some(stored),
+                    _ => None,
+                };
+                Self {
+                    name: completed.name().to_string(),
+                    status: match completed {
+                        Completed::Skipped(_) => "skipped",
+                        _ => "successful",
+                    },
+                    source: Some(report.source.clone()),
+                    destination: Some(report.destination.clone()),
+                    error: None,
+                    index: Some(report.index),
+                    stats: Some(report.stats),
+                    etag: stored.and_then(|stored| stored.etag.clone()),
+                    version_id: stored.and_then(|stored| stored.version_id.clone()),
+                }
+            }
+            Err(failure) => Self {
+                name: failure.name.clone(),
+                status: "failed",
+                source: Some(failure.request.source.clone()),
+                destination: Some(failure.request.destination.clone())
+                    .filter(|destination| !destination.is_empty()),
+                error: Some(FailedItem::new(failure.name.clone(), failure.error.clone())),
+                index: Some(report.index),
+                stats: Some(report.stats),
+                etag: None,
+                version_id: None,
+            },
+        }
+    }
+}
+
+#[pymethods]
+impl ResultItem {
+    /// Bytes the item moved, if the batch measured it.
+    #[getter]
+    fn bytes(&self) -> Option<u64> {
+        self.stats.map(|stats| stats.bytes)
+    }
+
+    /// Wall-clock seconds the item took, retries included.
+    #[getter]
+    fn elapsed_seconds(&self) -> Option<f64> {
+        self.stats.map(|stats| stats.elapsed_seconds)
+    }
+
+    /// Unix time the item started at, if the batch timed it.
+    #[getter]
+    fn started_at(&self) -> Option<f64> {
+        self.stats
+            .and_then(ItemStats::timed)
+            .map(|stats| stats.started_at)
+    }
+
+    /// Unix time the item finished at, if the batch timed it.
+    #[getter]
+    fn finished_at(&self) -> Option<f64> {
+        self.stats
+            .and_then(ItemStats::timed)
+            .map(|stats| stats.finished_at)
+    }
+
+    /// Times the item was tried, retries included, if the batch timed it.
+    #[getter]
+    fn attempts(&self) -> Option<u32> {
+        self.stats
+            .and_then(ItemStats::timed)
+            .map(|stats| stats.attempts)
+    }
+
+    fn __repr__(&self) -> String {
+        format!("ResultItem(name={:?}, status={:?})", self.name, self.status)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TransferKind {
+    Download,
+    Upload,
+}
+
+/// The bucket of a batch, if it had a single one, and the requests to retry.
+pub type RetryRequests = (Option<String>, Vec<TransferRequest>);
+
+/// The outcome of a batch as it is serialized, without what it takes to retry
+/// its failed items.
+#[derive(Serialize, Deserialize)]
+struct ResultsRecord {
+    successful: Vec<String>,
+    failed: Vec<FailedItem>,
+    skipped: Vec<String>,
+    #[serde(default)]
+    encryption: HashMap<String, String>,
+    #[serde(default)]
+    checksums: HashMap<String, StoredChecksum>,
+    #[serde(default)]
+    etags: HashMap<String, String>,
+    #[serde(default)]
+    version_ids: HashMap<String, String>,
+    #[serde(default)]
+    decompressed: HashMap<String, (u64, u64)>,
+    #[serde(default)]
+    destinations: HashMap<String, String>,
+    #[serde(default)]
+    stats: HashMap<String, ItemStats>,
+    #[serde(default)]
+    positions: HashMap<String, usize>,
+    #[serde(default)]
+    elapsed_seconds: f64,
+}
+
+/// Where a batch came from, so its failed items can be retried later.
+#[derive(Debug, Clone)]
+pub struct ResultsOrigin {
+    kind: TransferKind,
+    /// `None` for batches of `s3://` URIs.
+    bucket_name: Option<String>,
 }
 
-#[pyclass]
+#[pyclass(module = "robinzhon")]
 #[derive(Debug, Clone)]
 pub struct Results {
     #[pyo3(get)]
     pub successful: Vec<String>,
     #[pyo3(get)]
-    pub failed: Vec<String>,
+    pub failed: Vec<FailedItem>,
+    /// Destinations left alone because they were already up to date.
+    #[pyo3(get)]
+    pub skipped: Vec<String>,
+    /// Server-side encryption S3 applied to uploaded items, by local path.
+    #[pyo3(get)]
+    pub encryption: HashMap<String, String>,
+    /// Checksum S3 stored with uploaded items, as `(algorithm, value)`, by local
+    /// path.
+    #[pyo3(get)]
+    pub checksums: HashMap<String, StoredChecksum>,
+    /// ETag S3 returned for uploaded items, by local path.
+    #[pyo3(get)]
+    pub etags: HashMap<String, String>,
+    /// Version S3 gave uploaded items in a versioned bucket, by local path.
+    #[pyo3(get)]
+    pub version_ids: HashMap<String, String>,
+    /// Size of the object and of the file it inflated to, for downloaded items
+    /// stored decompressed, by local path.
+    #[pyo3(get)]
+    pub decompressed: HashMap<String, (u64, u64)>,
+    /// Destination of each item transferred or skipped, by its source.
+    destinations: HashMap<String, String>,
+    /// Stats of every item, by the name it is reported under.
+    stats: HashMap<String, ItemStats>,
+    /// Input position of every item, by the name it is reported under.
+    positions: HashMap<String, usize>,
+    /// Wall-clock time of the batch, and of its retries.
+    #[pyo3(get)]
+    pub elapsed_seconds: f64,
+    failed_requests: Vec<TransferRequest>,
+    origin: Option<ResultsOrigin>,
+    /// `items`, built on first use so indexing them one by one stays cheap.
+    item_cache: OnceLock<Vec<ResultItem>>,
+}
+
+impl Results {
+    /// Remembers the batch these results belong to, enabling `retry_failed`.
+    pub fn with_origin(mut self, kind: TransferKind, bucket_name: Option<&str>) -> Self {
+        self.origin = Some(ResultsOrigin {
+            kind,
+            bucket_name: bucket_name.map(str::to_string),
+        });
+        self
+    }
+
+    /// The kind of batch these results came from, if they carry one.
+    pub fn kind(&self) -> Option<TransferKind> {
+        self.origin.as_ref().map(|origin| origin.kind)
+    }
+
+    /// The bucket and requests of the failed items, if these results came from a
+    /// batch of the given kind.
+    pub fn retry_requests(&self, kind: TransferKind) -> PyResult<RetryRequests> {
+        match &self.origin {
+            Some(origin) if origin.kind == kind => {
+                Ok((origin.bucket_name.clone(), self.failed_requests.clone()))
+            }
+            Some(_) => Err(PyValueError::new_err(format!(
+                "Results were not produced by {}",
+                match kind {
+                    TransferKind::Download => "an S3Downloader",
+                    TransferKind::Upload => "an S3Uploader",
+                }
+            ))),
+            None => Err(PyValueError::new_err(
+                "Results carry no batch context to retry; only results returned by a batch method can be retried",
+            )),
+        }
+    }
+
+    fn record(&self) -> ResultsRecord {
+        ResultsRecord {
+            successful: self.successful.clone(),
+            failed: self.failed.clone(),
+            skipped: self.skipped.clone(),
+            encryption: self.encryption.clone(),
+            checksums: self.checksums.clone(),
+            etags: self.etags.clone(),
+            version_ids: self.version_ids.clone(),
+            decompressed: self.decompressed.clone(),
+            destinations: self.destinations.clone(),
+            stats: self.stats.clone(),
+            positions: self.positions.clone(),
+            elapsed_seconds: self.elapsed_seconds,
+        }
+    }
+
+    /// Every item: the successful ones, then the skipped, then the failed.
+    fn items(&self) -> &[ResultItem] {
+        self.item_cache.get_or_init(|| self.build_items())
+    }
+
+    fn build_items(&self) -> Vec<ResultItem> {
+        // Items are listed under their local path, the destination of downloads
+        // and the source of uploads.
+        let pairs: HashMap<&str, (&String, &String)> = self
+            .destinations
+            .iter()
+            .flat_map(|(source, destination)| {
+                [
+                    (source.as_str(), (source, destination)),
+                    (destination.as_str(), (source, destination)),
+                ]
+            })
+            .collect();
+        let kept = |status, names: &[String]| {
+            names
+                .iter()
+                .map(|name| {
+                    let pair = pairs.get(name.as_str());
+                    ResultItem {
+                        name: name.clone(),
+                        status,
+                        source: pair.map(|(source, _)| source.to_string()),
+                        destination: pair.map(|(_, destination)| destination.to_string()),
+                        error: None,
+                        index: self.positions.get(name).copied(),
+                        stats: self.stats.get(name).copied(),
+                        etag: self.etags.get(name).cloned(),
+                        version_id: self.version_ids.get(name).cloned(),
+                    }
+                })
+                .collect::<Vec<_>>()
+        };
+
+        let mut items = kept("successful", &self.successful);
+        items.extend(kept("skipped", &self.skipped));
+        items.extend(self.failed.iter().enumerate().map(|(position, item)| {
+            let request = self.failed_requests.get(position);
+            ResultItem {
+                name: item.key.clone(),
+                status: "failed",
+                source: request.map(|request| request.source.clone()),
+                destination: request
+                    .map(|request| request.destination.clone())
+                    .filter(|destination| !destination.is_empty()),
+                error: Some(item.clone()),
+                index: self.positions.get(&item.key).copied(),
+                stats: self.stats.get(&item.key).copied(),
+                etag: None,
+                version_id: None,
+            }
+        }));
+        items
+    }
+
+    /// Folds a retry of the failed items back in: successes are appended and the
+    /// failures replaced by whatever still failed.
+    pub fn merge_retry(&mut self, retried: Results) {
+        // A retry runs the failed items in the order they are listed, so its
+        // positions index them.
+        let original: Vec<Option<usize>> = self
+            .failed
+            .iter()
+            .map(|item| self.positions.get(&item.key).copied())
+            .collect();
+        self.positions.extend(
+            retried
+                .positions
+                .iter()
+                .filter_map(|(name, &index)| Some((name.clone(), original.get(index).copied()??))),
+        );
+        self.successful.extend(retried.successful);
+        self.skipped.extend(retried.skipped);
+        self.encryption.extend(retried.encryption);
+        self.checksums.extend(retried.checksums);
+        self.etags.extend(retried.etags);
+        self.version_ids.extend(retried.version_ids);
+        self.decompressed.extend(retried.decompressed);
+        self.destinations.extend(retried.destinations);
+        self.stats.extend(retried.stats);
+        self.elapsed_seconds += retried.elapsed_seconds;
+        self.failed = retried.failed;
+        self.failed_requests = retried.failed_requests;
+        self.item_cache = OnceLock::new();
+    }
 }
 
 #[pymethods]
 impl Results {
     #[new]
-    pub fn new(successful: Vec<String>, failed: Vec<String>) -> Self {
-        Self { successful, failed }
+    #[pyo3(signature = (successful, failed, skipped=Vec::new()))]
+    pub fn new(successful: Vec<String>, failed: Vec<FailedEntry>, skipped: Vec<String>) -> Self {
+        Self {
+            successful,
+            failed: failed.into_iter().map(FailedItem::from).collect(),
+            skipped,
+            encryption: HashMap::new(),
+            checksums: HashMap::new(),
+            etags: HashMap::new(),
+            version_ids: HashMap::new(),
+            decompressed: HashMap::new(),
+            destinations: HashMap::new(),
+            stats: HashMap::new(),
+            positions: HashMap::new(),
+            elapsed_seconds: 0.0,
+            failed_requests: Vec::new(),
+            origin: None,
+            item_cache: OnceLock::new(),
+        }
+    }
+
+    /// Keys (or local paths, for uploads) of the failed items.
+    #[getter]
+    fn failed_keys(&self) -> Vec<String> {
+        self.failed.iter().map(|item| item.key.clone()).collect()
+    }
+
+    /// Items transferred or skipped, from source to destination: local path by
+    /// key for downloads, key by local path for uploads.
+    fn as_dict(&self) -> HashMap<String, String> {
+        self.destinations.clone()
+    }
+
+    /// The outcome as plain Python values, ready for `json.dumps` or a database
+    /// row.
+    fn to_dict<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyDict>> {
+        let dict = PyDict::new(py);
+        dict.set_item("successful", &self.successful)?;
+        let failed = self
+            .failed
+            .iter()
+            .map(|item| item.to_dict(py))
+            .collect::<PyResult<Vec<_>>>()?;
+        dict.set_item("failed", failed)?;
+        dict.set_item("skipped", &self.skipped)?;
+        dict.set_item("encryption", &self.encryption)?;
+        dict.set_item("checksums", &self.checksums)?;
+        dict.set_item("etags", &self.etags)?;
+        dict.set_item("version_ids", &self.version_ids)?;
+        dict.set_item("decompressed", &self.decompressed)?;
+        dict.set_item("destinations", &self.destinations)?;
+        let stats = PyDict::new(py);
+        for (name, item) in &self.stats {
+            let item_stats = PyDict::new(py);
+            item_stats.set_item("bytes", item.bytes)?;
+            item_stats.set_item("elapsed_seconds", item.elapsed_seconds)?;
+            if let Some(item) = item.timed() {
+                item_stats.set_item("started_at", item.started_at)?;
+                item_stats.set_item("finished_at", item.finished_at)?;
+                item_stats.set_item("attempts", item.attempts)?;
+            }
+            stats.set_item(name, item_stats)?;
+        }
+        dict.set_item("stats", stats)?;
+        dict.set_item("positions", &self.positions)?;
+        dict.set_item("elapsed_seconds", self.elapsed_seconds)?;
+        Ok(dict)
+    }
+
+    /// The outcome as JSON, the same shape as `to_dict`, indented by `indent`
+    /// spaces if given.
+    #[pyo3(signature = (indent=None))]
+    fn to_json(&self, indent: Option<usize>) -> PyResult<String> {
+        let record = self.record();
+        let json = match indent {
+            None => serde_json::to_vec(&record),
+            Some(indent) => {
+                let indent = vec![b' '; indent];
+                let mut json = Vec::new();
+                let formatter = serde_json::ser::PrettyFormatter::with_indent(&indent);
+                let mut serializer = serde_json::Serializer::with_formatter(&mut json, formatter);
+                record.serialize(&mut serializer).map(|()| json)
+            }
+        }
+        .map_err(|e| PyValueError::new_err(format!("Failed to serialize results: {}", e)))?;
+        Ok(String::from_utf8(json).expect("serde_json writes UTF-8"))
+    }
+
+    /// Writes a report of every item, in input order, to `path`: a JSON document
+    /// with the totals of the batch, or a CSV file with a row per item.
+    #[pyo3(signature = (path, format=ReportFormat::Json))]
+    fn write_report(&self, path: &str, format: ReportFormat) -> PyResult<()> {
+        let summary = ReportSummary {
+            total: self.total_count(),
+            successful: self.successful.len(),
+            failed: self.failed.len(),
+            skipped: self.skipped.len(),
+            total_bytes: self.total_bytes(),
+            elapsed_seconds: self.elapsed_seconds,
+            throughput_mbps: self.throughput_mbps(),
+        };
+        write_report(path, format, summary, &self.in_input_order())
+    }
+
+    /// Pickles the outcome; the unpickled results can't be passed to
+    /// `retry_failed`, which needs the requests of the original batch.
+    fn __getstate__(&self) -> PyResult<String> {
+        self.to_json(None)
+    }
+
+    fn __setstate__(&mut self, state: &str) -> PyResult<()> {
+        let record: ResultsRecord = serde_json::from_str(state)
+            .map_err(|e| PyValueError::new_err(format!("Invalid pickled results: {}", e)))?;
+        *self = Self {
+            successful: record.successful,
+            failed: record.failed,
+            skipped: record.skipped,
+            encryption: record.encryption,
+            checksums: record.checksums,
+            etags: record.etags,
+            version_ids: record.version_ids,
+            decompressed: record.decompressed,
+            destinations: record.destinations,
+            stats: record.stats,
+            positions: record.positions,
+            elapsed_seconds: record.elapsed_seconds,
+            failed_requests: Vec::new(),
+            origin: None,
+            item_cache: OnceLock::new(),
+        };
+        Ok(())
+    }
+
+    fn __getnewargs__(&self) -> (Vec<String>, Vec<String>) {
+        (Vec::new(), Vec::new())
+    }
+
+    /// Bytes moved by the items of the batch; bytes of failed attempts don't
+    /// count.
+    #[getter]
+    fn total_bytes(&self) -> u64 {
+        self.stats.values().map(|item| item.bytes).sum()
+    }
+
+    /// Average throughput of the batch in megabytes (10^6 bytes) per second.
+    #[getter]
+    fn throughput_mbps(&self) -> f64 {
+        if self.elapsed_seconds == 0.0 {
+            0.0
+        } else {
+            self.total_bytes() as f64 / self.elapsed_seconds / 1_000_000.0
+        }
+    }
+
+    /// Every item in the order the batch was given them, whatever order they
+    /// finished in. Items whose position is unknown come last.
+    fn in_input_order(&self) -> Vec<ResultItem> {
+        let mut items = self.items().to_vec();
+        items.sort_by_key(|item| item.index.unwrap_or(usize::MAX));
+        items
+    }
+
+    fn __len__(&self) -> usize {
+        self.total_count()
+    }
+
+    /// Iterates `ResultItem`s: the successful items, then the skipped, then the
+    /// failed.
+    fn __iter__<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyIterator>> {
+        PyList::new(py, self.items().iter().cloned())?
+            .as_any()
+            .try_iter()
+    }
+
+    /// The item at `index` in iteration order; negative indices count from the
+    /// end.
+    fn __getitem__(&self, index: isize) -> PyResult<ResultItem> {
+        let items = self.items();
+        let position = if index < 0 {
+            index + items.len() as isize
+        } else {
+            index
+        };
+        if position < 0 || position as usize >= items.len() {
+            return Err(PyIndexError::new_err("Results index out of range"));
+        }
+        Ok(items[position as usize].clone())
+    }
+
+    /// Whether an item was handled under `name`, its local path or key, whatever
+    /// its outcome.
+    fn __contains__(&self, name: &str) -> bool {
+        self.successful
+            .iter()
+            .chain(&self.skipped)
+            .chain(self.failed.iter().map(|item| &item.key))
+            .any(|item| item == name)
+            || self.destinations.contains_key(name)
+            || self
+                .failed_requests
+                .iter()
+                .any(|request| request.source == name || request.destination == name)
     }
 
     pub fn __repr__(&self) -> String {
         format!(
-            "DownloadResults(successful={}, failed={})",
+            "DownloadResults(successful={}, failed={}, skipped={})",
             self.successful.len(),
-            self.failed.len()
+            self.failed.len(),
+            self.skipped.len()
         )
     }
 
     fn __str__(&self) -> String {
         format!(
-            "DownloadResults: {} successful, {} failed",
+            "DownloadResults: {} successful, {} failed, {} skipped",
             self.successful.len(),
-            self.failed.len()
+            self.failed.len(),
+            self.skipped.len()
         )
     }
 
@@ -51,14 +1040,15 @@ impl Results {
     }
 
     fn total_count(&self) -> usize {
-        self.successful.len() + self.failed.len()
+        self.successful.len() + self.failed.len() + self.skipped.len()
     }
 
+    /// Share of items that didn't fail; skipped items count as successes.
     fn success_rate(&self) -> f64 {
         if self.total_count() == 0 {
             0.0
         } else {
-            self.successful.len() as f64 / self.total_count() as f64
+            (self.successful.len() + self.skipped.len()) as f64 / self.total_count() as f64
         }
     }
 }
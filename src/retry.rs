@@ -0,0 +1,129 @@
+use std::future::Future;
+use std::time::Duration;
+
+use log::debug;
+use pyo3::exceptions::PyValueError;
+use pyo3::{pyclass, pymethods, PyResult};
+
+use crate::error::TransferError;
+use crate::metrics::record_retry;
+
+/// Exponential backoff applied to whole transfers on transient failures.
+///
+/// This sits on top of the SDK's own request retries and also covers failures the
+/// SDK can't retry, such as a connection dropping halfway through a response body.
+#[pyclass(frozen, module = "robinzhon")]
+#[derive(Debug, Clone)]
+pub struct RetryPolicy {
+    #[pyo3(get)]
+    pub max_attempts: u32,
+    #[pyo3(get)]
+    pub base_delay: f64,
+    #[pyo3(get)]
+    pub max_delay: f64,
+    #[pyo3(get)]
+    pub jitter: bool,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: 3,
+            base_delay: 0.1,
+            max_delay: 20.0,
+            jitter: true,
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Backoff before retrying after the given (1-based) failed attempt. With jitter
+    /// enabled the delay is drawn uniformly from zero up to the exponential cap.
+    pub fn delay_for(&self, attempt: u32) -> Duration {
+        let exponent = attempt.saturating_sub(1).min(32) as i32;
+        let capped = (self.base_delay * 2f64.powi(exponent)).min(self.max_delay);
+        let delay = if self.jitter {
+            capped * fastrand::f64()
+        } else {
+            capped
+        };
+        // Delays are validated on construction; this only guards the arithmetic.
+        Duration::try_from_secs_f64(delay).unwrap_or(Duration::ZERO)
+    }
+
+    /// Runs `operation` until it succeeds, fails with a non-retryable error, or the
+    /// attempts are exhausted.
+    pub async fn run<T, F, Fut>(&self, mut operation: F) -> Result<T, TransferError>
+    where
+        F: FnMut() -> Fut,
+        Fut: Future<Output = Result<T, TransferError>>,
+    {
+        let mut attempt = 1;
+        loop {
+            match operation().await {
+                Err(error) if error.retryable && attempt < self.max_attempts => {
+                    let delay = self.delay_for(attempt);
+                    debug!(
+                        "Attempt {}/{} failed, retrying in {:.2}s: {}",
+                        attempt,
+                        self.max_attempts,
+                        delay.as_secs_f64(),
+                        error
+                    );
+                    record_retry();
+                    tokio::time::sleep(delay).await;
+                    attempt += 1;
+                }
+                outcome => return outcome,
+            }
+        }
+    }
+}
+
+#[pymethods]
+impl RetryPolicy {
+    #[new]
+    #[pyo3(signature = (max_attempts=3, base_delay=0.1, max_delay=20.0, jitter=true))]
+    fn new(max_attempts: u32, base_delay: f64, max_delay: f64, jitter: bool) -> PyResult<Self> {
+        if max_attempts == 0 {
+            return Err(PyValueError::new_err(
+                "max_attempts must be greater than zero",
+            ));
+        }
+        if ![base_delay, max_delay]
+            .iter()
+            .all(|delay| delay.is_finite() && *delay >= 0.0)
+        {
+            return Err(PyValueError::new_err(
+                "retry delays must be finite and not negative",
+            ));
+        }
+
+        Ok(Self {
+            max_attempts,
+            base_delay,
+            max_delay,
+            jitter,
+        })
+    }
+
+    /// Pickles the policy as its constructor arguments.
+    fn __getnewargs__(&self) -> (u32, f64, f64, bool) {
+        (
+            self.max_attempts,
+            self.base_delay,
+            self.max_delay,
+            self.jitter,
+        )
+    }
+
+    fn __repr__(&self) -> String {
+        format!(
+            "RetryPolicy(max_attempts={}, base_delay={:?}, max_delay={:?}, jitter={})",
+            self.max_attempts,
+            self.base_delay,
+            self.max_delay,
+            if self.jitter { "True" } else { "False" }
+        )
+    }
+}
@@ -1,30 +1,462 @@
-from typing import List, Tuple
+from datetime import datetime
+from typing import (
+    Any,
+    BinaryIO,
+    Callable,
+    Dict,
+    Iterable,
+    Iterator,
+    List,
+    Literal,
+    Optional,
+    Tuple,
+    Union,
+)
+
+OverwritePolicy = Literal["always", "never", "if_size_differs", "if_etag_differs"]
+"""
+What batch downloads do with a destination file that already exists.
+
+- "always": download it again
+- "never": keep it, whatever its content
+- "if_size_differs": keep it if it has the object's size
+- "if_etag_differs": keep it if its MD5 matches the object's ETag. ETags of
+  multipart or SSE-KMS uploads aren't MD5s; for those only the size is compared
+
+Every policy but "always" and "never" costs a HEAD request per existing file.
+"""
+
+Scheduling = Literal["fifo", "largest_first", "smallest_first"]
+"""
+The order a batch starts its items in.
+
+- "fifo": the order they were given or listed in
+- "largest_first": biggest first, so that the longest transfers don't start
+  last and hold up the end of a batch of mixed sizes
+- "smallest_first": smallest first, so that as many items as possible are done
+  early
+
+Items of the same size keep their order.
+"""
+
+RestoreTier = Literal["standard", "bulk", "expedited"]
+"""
+How fast archived objects are restored, from fastest and most expensive to
+cheapest: "expedited" (minutes; not for DEEP_ARCHIVE), "standard" (hours) and
+"bulk" (up to two days for DEEP_ARCHIVE).
+"""
+
+SymlinkPolicy = Literal["follow", "skip", "preserve"]
+"""
+What transfers do with symbolic links.
+
+- "follow": uploads send the file a link points to; downloads write every object
+  as a file
+- "skip": links, and objects stored from links, are left out and reported in
+  `Results.skipped`
+- "preserve": uploads store a link as an object holding its target, with the mode
+  of a link in its "mode" metadata as s3fs does; downloads recreate the link
+"""
+
+PresignMethod = Literal["get", "put"]
+"""
+The request a presigned URL allows: downloading the object or uploading it.
+"""
+
+CredentialsProvider = Callable[
+    [], Tuple[str, str, Optional[str], Optional[Union[datetime, float]]]
+]
+"""
+A callable handing out AWS credentials as `(access_key, secret_key,
+session_token, expiry)`, with `expiry` an aware `datetime` or seconds since the
+epoch.
+"""
+
+UploadItem = Union[Tuple[str, str], Tuple[str, str, "UploadOptions"]]
+"""
+An item of an upload batch: `(local_path, key)`, or `(local_path, key, options)`
+to override the batch's `UploadOptions` for that item.
+"""
+
+ByteRange = Tuple[int, Optional[int]]
+"""
+A slice of an object as `(offset, length)`: `length` bytes from `offset`, or up
+to the end of the object when `length` is None. A negative `offset` selects the
+last `-offset` bytes, in which case `length` must be None.
+"""
+
+SelectInputFormat = Literal["csv", "json", "jsonl", "parquet"]
+"""
+How the object an S3 Select query reads is stored: CSV, a single JSON
+document, one JSON document per line, or Parquet.
+"""
+
+ArchiveFormat = Literal["tar", "tar.gz", "tgz", "zip"]
+"""
+How an archive `download_and_extract` unpacks, or `ArchiveMode` packs, is
+packed: a plain tar, a gzipped tar ("tgz" is the same) or a zip.
+"""
+
+DownloadItem = Union[
+    Tuple[str, str],
+    Tuple[str, str, Optional[str]],
+    Tuple[str, str, ByteRange],
+    Tuple[str, str, Optional[str], ByteRange],
+]
+"""
+An item of `download_multiple_files_with_paths`: `(key, local_path)`, or
+`(key, local_path, version_id)` for a given version of the object, either
+followed by a `ByteRange` to download only that slice of it.
+"""
+
+
+class RobinzhonError(RuntimeError):
+    """
+    Base class for errors raised by robinzhon transfers.
+
+    It derives from `RuntimeError`, so existing `except RuntimeError` handlers keep
+    working.
+
+    Attributes:
+        request_id: `x-amz-request-id` of the S3 response the error came from,
+                    which AWS support asks for; None when there was none
+        extended_request_id: `x-amz-id-2` of that response, asked for along
+                             with the request ID
+    """
+
+    request_id: Optional[str]
+    extended_request_id: Optional[str]
+
+
+class NoSuchKeyError(RobinzhonError):
+    """The requested S3 object does not exist."""
+
+
+class AccessDeniedError(RobinzhonError):
+    """The credentials in use are not allowed to perform the request."""
+
+
+class BucketNotFoundError(RobinzhonError):
+    """The requested bucket does not exist."""
+
+
+class PreconditionFailedError(RobinzhonError):
+    """
+    The object did not meet a condition of the request, such as a key that
+    already exists when uploading with `if_none_match=True`.
+    """
+
+
+class TimeoutError(RobinzhonError):
+    """The request did not complete in time."""
+
+
+class SsoLoginRequiredError(RobinzhonError):
+    """
+    Credentials couldn't be loaded because the AWS SSO (IAM Identity Center)
+    session they come from has expired or was never started. The message names
+    the `aws sso login` command that starts a new one.
+    """
+
+
+class BatchAbortedError(RobinzhonError):
+    """
+    A batch stopped early after reaching its `fail_fast` or `max_failures`
+    limit. The message names the first failure; for blocking calls, the items
+    finished before the batch stopped are attached as `partial_results`.
+    """
+
+
+class PartialFailureError(RobinzhonError):
+    """
+    Some items of a batch failed and it was run with `raise_on_failure=True`.
+
+    Attributes:
+        results: The `Results` of the whole batch, which can be passed to
+                 `retry_failed`
+    """
+
+    results: Results
+
+
+class InsufficientSpaceError(RobinzhonError):
+    """
+    A batch download run with `check_space=True` would not fit in the free space
+    of its destination, so nothing was downloaded.
+
+    Attributes:
+        path: The destination directory
+        required_bytes: Bytes the batch would add to the disk
+        available_bytes: Bytes free on the filesystem of `path`
+    """
+
+    path: str
+    required_bytes: int
+    available_bytes: int
+
+
+class FailedItem:
+    """
+    Details about a single item that failed in a batch.
+
+    Attributes:
+        key: S3 object key for downloads, local path for uploads
+        error_message: Human-readable description of the failure
+        error_code: S3 error code (e.g. "NoSuchKey", "AccessDenied"), if S3 returned one
+        http_status: HTTP status of the failed response, if one was received
+        retryable: Whether the failure looked transient. Retryable failures were
+                   already retried according to the instance's `RetryPolicy`
+        request_id: `x-amz-request-id` of the failed response, for support
+                    tickets, if a response was received
+        extended_request_id: `x-amz-id-2` of the failed response, if any
+
+    Example:
+        >>> for item in result.failed:
+        ...     print(item.key, item.error_code, item.error_message)
+    """
+
+    key: str
+    error_message: str
+    error_code: Optional[str]
+    http_status: Optional[int]
+    retryable: bool
+    request_id: Optional[str]
+    extended_request_id: Optional[str]
+
+    def __str__(self) -> str:
+        """`key: error_message`."""
+        ...
+
+    def __repr__(self) -> str:
+        """Developer representation showing the key and error classification."""
+        ...
+
+
+class ResultItem:
+    """
+    One item of a batch and how it ended, as iterating `Results` yields them.
+
+    Attributes:
+        name: Local path, or S3 object key for failed downloads, as listed in
+             `Results.successful`, `skipped` or `failed`
+        status: "successful", "skipped" or "failed"
+        source: S3 object key for downloads, local path for uploads, if known
+        destination: Local path for downloads, S3 object key for uploads, if known
+        error: `FailedItem` with the reason of a failed item, else None
+        index: Position of the item in the input of its batch, if known. Items
+              of a `TransferManager` job count after their sort by priority
+        bytes: Bytes the item moved, if the batch measured it
+        elapsed_seconds: Wall-clock seconds the item took, retries included,
+                        if the batch measured it
+        started_at: Unix time the item started at, if the batch timed it
+        finished_at: Unix time the item finished at, if the batch timed it
+        attempts: Times the item was tried, retries included, if the batch
+                 timed it; 0 for items left alone
+        etag: For uploads, the ETag S3 returned for the object, if any
+        version_id: For uploads to versioned buckets, the version S3 created
+    """
+
+    name: str
+    status: Literal["successful", "skipped", "failed"]
+    source: Optional[str]
+    destination: Optional[str]
+    error: Optional[FailedItem]
+    index: Optional[int]
+    bytes: Optional[int]
+    elapsed_seconds: Optional[float]
+    started_at: Optional[float]
+    finished_at: Optional[float]
+    attempts: Optional[int]
+    etag: Optional[str]
+    version_id: Optional[str]
+
+    def __repr__(self) -> str:
+        """Developer representation showing the name and status."""
+        ...
 
 
 class Results:
     """
     Results of a task operation.
 
+    Results can be pickled, e.g. to send them to another process, with every
+    item and its error details. Unpickled results can't be passed to
+    `retry_failed`, which needs the requests of the original batch.
+
     This class contains the results of downloading/uploading multiple files, with separate
     lists for successful and failed downloads/uploads, plus utility methods for checking
     the overall status.
 
     Attributes:
         successful: List of local file paths where files were successfully downloaded/uploaded
-        failed: List of S3 object keys or local paths that failed to download/upload
+        failed: `FailedItem` details for each S3 object key or local path that failed
+        failed_keys: Just the S3 object keys or local paths that failed
+        skipped: Local paths left alone because the overwrite policy kept them,
+                or symbolic links skipped by the `symlinks` policy
+        encryption: For uploads, the server-side encryption S3 applied to each
+                   uploaded local path ("AES256", "aws:kms"...)
+        checksums: For uploads, the `(algorithm, base64 value)` checksum S3
+                  stored with each uploaded local path, e.g.
+                  `("CRC32", "DUoRhQ==")`
+        etags: For uploads, the ETag S3 returned for each uploaded local path
+        version_ids: For uploads to versioned buckets, the version ID of the
+                    object created for each uploaded local path
+        decompressed: For downloads with `auto_decompress`, the
+                     `(compressed, decompressed)` sizes in bytes of each local
+                     path stored decompressed
+        total_bytes: Bytes moved by the items of the batch; failed attempts
+                    don't count
+        elapsed_seconds: Wall-clock seconds of the batch, plus those of its
+                        `retry_failed` runs
+        throughput_mbps: `total_bytes` per `elapsed_seconds`, in megabytes
+                        (10^6 bytes) per second
     """
 
     successful: List[str]
-    failed: List[str]
+    failed: List[FailedItem]
+    failed_keys: List[str]
+    skipped: List[str]
+    encryption: Dict[str, str]
+    checksums: Dict[str, Tuple[str, str]]
+    etags: Dict[str, str]
+    version_ids: Dict[str, str]
+    decompressed: Dict[str, Tuple[int, int]]
+    total_bytes: int
+    elapsed_seconds: float
+    throughput_mbps: float
 
-    def __init__(self, successful: List[str], failed: List[str]) -> None:
+    def __init__(
+        self,
+        successful: List[str],
+        failed: List[Union[FailedItem, str]],
+        skipped: List[str] = [],
+    ) -> None:
         """
         Initialize results.
 
         Args:
             successful: List of successfully downloaded/uploaded file paths
-            failed: List of failed S3 object keys or local paths
+            failed: Failed items, or bare S3 object keys / local paths
+            skipped: File paths that were left alone
+        """
+        ...
+
+    def as_dict(self) -> Dict[str, str]:
+        """
+        Map each item transferred or skipped from its source to its destination.
+
+        Returns:
+            Dict[str, str]: Local path by S3 object key (or URL or URI, as
+                           given) for downloads, S3 object key by local path
+                           for uploads. Failed items are left out
+
+        Example:
+            >>> result = downloader.download_multiple_files("bucket", keys, "./out")
+            >>> for key, local_path in result.as_dict().items():
+            ...     index(key, local_path)
+        """
+        ...
+
+    def in_input_order(self) -> List[ResultItem]:
+        """
+        Every item in the order the batch was given them, whatever order they
+        finished in. Retried items keep their original position; items whose
+        position is unknown come last.
+
+        Example:
+            >>> items = downloader.download_multiple_files_with_paths("bucket", pairs)
+            >>> for (key, path), item in zip(pairs, items.in_input_order()):
+            ...     assert item.source == key
+        """
+        ...
+
+    def to_dict(self) -> Dict[str, Any]:
+        """
+        The outcome as plain Python values, to log or store.
+
+        Returns:
+            Dict[str, Any]: `successful`, `skipped`, `encryption`, `checksums`,
+                           `etags`, `version_ids`, `decompressed`,
+                           `elapsed_seconds` and `destinations` (as
+                           `as_dict()`) as above, `failed` as a list of dicts
+                           with the fields of `FailedItem`, `stats`, the
+                           `bytes` and `elapsed_seconds` of each item by name,
+                           with its `started_at`, `finished_at` and `attempts`
+                           when timed,
+                           and `positions`, the input index of each item by name
+
+        Example:
+            >>> db.insert("batches", result.to_dict())
+        """
+        ...
+
+    def to_json(self, indent: Optional[int] = None) -> str:
+        """
+        The outcome as JSON, in the shape of `to_dict()`.
+
+        Args:
+            indent: Spaces to indent nested values by (default=None, compact)
+        """
+        ...
+
+    def write_report(
+        self, path: str, format: Literal["json", "csv"] = "json"
+    ) -> None:
+        """
+        Write a report of every item, in input order, to attach to pipeline
+        logs or keep for audits. Any file at `path` is replaced.
+
+        Each item is reported with its `item` name, `status`, `source`,
+        `destination`, `bytes`, `duration_seconds`, `attempts`, `started_at`
+        and `finished_at` (Unix times), and for failed items the `error`
+        message, `error_code`, `http_status` and `request_id`. Values the batch
+        didn't measure are null in JSON and empty in CSV.
+
+        Args:
+            path: File to write the report to
+            format: `"json"` for a document with a `summary` of the batch
+                   (`total`, `successful`, `failed`, `skipped`, `total_bytes`,
+                   `elapsed_seconds`, `throughput_mbps`), its `generated_at`
+                   time and an `items` array; `"csv"` for a header and a row
+                   per item
+
+        Raises:
+            ValueError: If `format` is neither "json" nor "csv"
+            RobinzhonError: If the file can't be written
+
+        Example:
+            >>> result = downloader.download_prefix("my-bucket", "daily/", "./daily")
+            >>> result.write_report(f"reports/{run_id}.csv", format="csv")
+        """
+        ...
+
+    def __len__(self) -> int:
+        """Number of items handled, as `total_count()`."""
+        ...
+
+    def __iter__(self) -> Iterator[ResultItem]:
         """
+        Iterate the items: the successful ones, then the skipped, then the
+        failed.
+
+        Example:
+            >>> for item in result:
+            ...     print(item.status, item.source, item.destination)
+        """
+        ...
+
+    def __getitem__(self, index: int) -> ResultItem:
+        """
+        The item at `index`, in iteration order. Negative indices count from
+        the end.
+
+        Raises:
+            IndexError: If `index` is out of range
+        """
+        ...
+
+    def __contains__(self, name: str) -> bool:
+        """Whether an item was handled under `name`, a local path or S3 object key."""
         ...
 
     def is_complete_success(self) -> bool:
@@ -67,13 +499,14 @@ class Results:
         Get the total number of download attempts.
 
         Returns:
-            Total number of files attempted (successful + failed)
+            Total number of files handled (successful + failed + skipped)
         """
         ...
 
     def success_rate(self) -> float:
         """
-        Calculate the success rate of the downloads.
+        Calculate the success rate of the downloads. Skipped files count as
+        successes.
 
         Returns:
             Success rate as a float between 0.0 and 1.0
@@ -93,183 +526,3098 @@ class Results:
         ...
 
 
-class S3Config:
+class BatchIterator(Iterator[ResultItem]):
     """
-    Internal AWS S3 configuration class.
+    Iterator over a batch running in the background, returned by the `iter_`
+    variants of the batch methods. It yields the `ResultItem` of every item as
+    the item finishes, so wrapping it in `tqdm` shows a live progress bar.
 
-    This class is used internally by S3Downloader to manage AWS S3 client configuration.
-    You typically don't need to interact with this class directly.
+    `len()` is the number of items when the input of the batch has one, and
+    raises `TypeError` otherwise. Once the iterator is exhausted, `results`
+    holds the `Results` of the whole batch. Closing the iterator, or garbage
+    collecting it, cancels the transfers still running.
 
-    Note:
-        The configuration includes settings like region, access keys, and secret keys,
-        which are essential for authenticating and connecting to AWS S3. Ensure that
-        your AWS credentials are correctly configured in your environment or through
-        the AWS configuration files.
+    Example:
+        >>> from tqdm import tqdm
+        >>> batch = downloader.iter_download_multiple_files("my-bucket", keys, "./downloads")
+        >>> for item in tqdm(batch):
+        ...     if item.status == "failed":
+        ...         print(item.name, item.error.error_message)
+        >>> batch.results.success_rate()
     """
 
-    ...
+    results: Optional[Results]
 
+    def __iter__(self) -> "BatchIterator": ...
 
-class S3Downloader:
+    def __next__(self) -> ResultItem:
+        """
+        Wait for the next item to finish and return it.
+
+        Raises:
+            StopIteration: Once every item finished
+            BatchAbortedError: If the batch stopped at its `fail_fast` or
+                              `max_failures` limit, after the items that
+                              finished before it
+            Exception: Whatever the input of the batch raised while being
+                      iterated
+        """
+        ...
+
+    def __len__(self) -> int: ...
+
+    def close(self) -> None:
+        """Cancel the transfers still running. The iterator is exhausted afterwards."""
+        ...
+
+    def __enter__(self) -> "BatchIterator": ...
+
+    def __exit__(self, *args: object) -> bool: ...
+
+
+class TransferProgress:
     """
-    High-performance AWS S3 file downloader with concurrent download capabilities.
+    Live progress of a running batch transfer.
 
-    This class provides methods to download files from AWS S3 buckets, with support
-    for both single file downloads and concurrent batch downloads using async I/O
-    for optimal network utilization and performance.
+    Pass an instance to any batch method through its `progress` argument and poll
+    it from another thread (or task) while the batch runs. Batch methods release
+    the GIL, so polling threads keep running during the transfer.
+
+    Attributes:
+        files_total: Number of items in the batch
+        files_completed: Items transferred successfully so far
+        files_failed: Items that failed so far
+        bytes_transferred: Bytes transferred so far
+        bytes_total: Total bytes of the items started so far
+        elapsed_seconds: Seconds since the batch started
+        is_finished: Whether the batch has finished
+        throughput: Average throughput in bytes per second
+        eta_seconds: Estimated seconds remaining, or None until an item finishes
 
     Example:
-        >>> downloader = S3Downloader("us-east-1")
-        >>> downloader.download_file("my-bucket", "path/to/file.txt", "./local-file.txt")
-        './local-file.txt'
+        >>> progress = TransferProgress()
+        >>> worker = threading.Thread(
+        ...     target=downloader.download_multiple_files,
+        ...     args=("my-bucket", keys, "./downloads"),
+        ...     kwargs={"progress": progress},
+        ... )
+        >>> worker.start()
+        >>> while worker.is_alive():
+        ...     print(f"{progress.files_completed}/{progress.files_total}")
+        ...     time.sleep(1)
     """
 
-    def __init__(self, region_name: str, max_concurrent_downloads: int = 5) -> None:
-        """
-        Initialize the S3 downloader with the specified AWS region.
+    files_total: int
+    files_completed: int
+    files_failed: int
+    bytes_transferred: int
+    bytes_total: int
+    elapsed_seconds: float
+    is_finished: bool
+    throughput: float
+    eta_seconds: Optional[float]
 
-        Args:
-            region_name: AWS region name (e.g., "us-east-1", "eu-west-1")
-            max_concurrent_downloads: Maximum concurrent downloads from S3. Default = 5
+    def __init__(self) -> None: ...
 
-        Example:
-            >>> downloader = S3Downloader("us-east-1")
-        """
+    def __repr__(self) -> str:
+        """Developer representation showing counts."""
         ...
 
-    def download_file(
-        self, bucket_name: str, object_key: str, path_to_store: str
-    ) -> str:
-        """
-        Download a single file from S3 to the local filesystem.
 
-        Args:
-            bucket_name: Name of the S3 bucket
-            object_key: S3 object key (path to the file in the bucket)
-            path_to_store: Local file path where the downloaded file will be stored
+class RetryPolicy:
+    """
+    Exponential backoff applied to each transferred file.
 
-        Returns:
-            The local file path where the file was stored
+    Transient failures (HTTP 500/502/503/504, `SlowDown`, throttling, timeouts and
+    connections dropped mid-transfer) are retried; anything else, such as a missing
+    key or a local filesystem error, fails the item immediately.
 
-        Raises:
-            RuntimeError: If the download fails due to network issues, permissions,
-                         or file system errors
+    Attributes:
+        max_attempts: Total attempts per file, including the first one
+        base_delay: Delay in seconds before the first retry, doubled on every attempt
+        max_delay: Upper bound in seconds for a single delay
+        jitter: Randomize each delay between zero and its computed value
 
-        Example:
-            >>> downloader = S3Downloader("us-east-1")
-            >>> result = downloader.download_file(
-            ...     "my-bucket",
-            ...     "data/file.csv",
-            ...     "./downloads/file.csv"
-            ... )
-            >>> print(result)
-            './downloads/file.csv'
+    Example:
+        >>> policy = RetryPolicy(max_attempts=5, base_delay=0.5)
+        >>> downloader = S3Downloader("us-east-1", retry_policy=policy)
+    """
+
+    max_attempts: int
+    base_delay: float
+    max_delay: float
+    jitter: bool
+
+    def __init__(
+        self,
+        max_attempts: int = 3,
+        base_delay: float = 0.1,
+        max_delay: float = 20.0,
+        jitter: bool = True,
+    ) -> None:
         """
-        ...
+        Build a retry policy.
 
-    def download_multiple_files(
-        self, bucket_name: str, object_keys: List[str], base_directory: str
-    ) -> Results:
+        Raises:
+            ValueError: If `max_attempts` is zero or a delay is negative or not
+                finite
         """
-        Download multiple files from S3 concurrently to a base directory.
+        ...
 
-        Files are downloaded concurrently using async I/O for optimal network utilization
-        and significantly improved performance compared to sequential downloads. This approach
-        is highly efficient for I/O-bound operations like file downloads.
+    def __repr__(self) -> str:
+        """Developer representation showing the policy."""
+        ...
 
-        **Resilient Error Handling**: If some files fail to download, the operation continues
-        and returns both successful and failed downloads for programmatic handling.
 
-        Args:
-            bucket_name: Name of the S3 bucket
-            object_keys: List of S3 object keys to download
-            base_directory: Local directory where all files will be stored
+class TransferLimiter:
+    """
+    Budget of in-flight transfers shared between downloader and uploader instances.
 
-        Returns:
-            DownloadResults object with successful and failed download lists
+    Every instance enforces its own `max_concurrent_*` limit; instances given the
+    same limiter also never have more than `max_in_flight` objects in flight
+    together, so several of them in one process don't oversubscribe the network.
+    Slots aren't shared across processes: an unpickled limiter is a budget of
+    its own.
 
-        Raises:
-            RuntimeError: If the base directory cannot be created or other system errors
+    Attributes:
+        max_in_flight: Objects that may be transferred at once, across instances
+        in_flight: Objects being transferred right now
 
-        Note:
-            Performance scales with network bandwidth and connection limits rather than
-            CPU cores, making this highly efficient for downloading many files.
-            Failed downloads are logged as warnings and available in result.failed.
+    Example:
+        >>> limiter = TransferLimiter(64)
+        >>> downloader = S3Downloader("us-east-1", max_concurrent_downloads=48, limiter=limiter)
+        >>> uploader = S3Uploader("us-east-1", max_concurrent_uploads=48, limiter=limiter)
+    """
 
-        Example:
-            >>> downloader = S3Downloader("us-east-1")
-            >>> files = [
-            ...     "data/file1.csv",          # exists
-            ...     "data/missing-file.json",  # doesn't exist
-            ...     "logs/app.log"            # exists
-            ... ]
-            >>> result = downloader.download_multiple_files(
-            ...     "my-bucket",
-            ...     files,
-            ...     "./downloads"
-            ... )
-            # Warning: 1 downloads failed:
-            #   data/missing-file.json: S3 object not found
-            >>> print(f"Success: {len(result.successful)}, Failed: {len(result.failed)}")
-            Success: 2, Failed: 1
-            >>> if result.has_failures():
-            ...     print(f"Failed files: {result.failed}")
-            Failed files: ['data/missing-file.json']
-            >>> for path in result.successful:
-            ...     print(f"Downloaded: {path}")
+    max_in_flight: int
+    in_flight: int
+
+    def __init__(self, max_in_flight: int) -> None:
         """
-        ...
+        Build a limiter.
 
-    def download_multiple_files_with_paths(
-        self, bucket_name: str, downloads: List[Tuple[str, str]]
-    ) -> Results:
+        Raises:
+            ValueError: If `max_in_flight` is zero
         """
-        Download multiple files from S3 concurrently with custom local paths.
+        ...
 
-        This method allows you to specify custom local paths for each downloaded file,
-        providing full control over the destination file structure. Downloads are
+    def __repr__(self) -> str:
+        """Developer representation showing the budget."""
+        ...
+
+
+class TransferSession:
+    """
+    A batch checkpointed to a local JSON file as its items finish.
+
+    If the process is killed, a new `TransferSession` on the same file picks the
+    batch up with `resume()`: items already done are not transferred again.
+    Files larger than a part are uploaded in a multipart upload whose ID and
+    parts are checkpointed, and downloads checkpoint how much of their file is
+    written, about once a part; an item in flight when the process died
+    continues from there. Uploads with client-side encryption are sent whole
+    and start over. Checkpoints are written at most once a second, so what
+    finished less than a second before the process died is transferred again.
+
+    Attributes:
+        path: The checkpoint file
+        kind: "download" or "upload"; None before the first batch
+        bucket_name: The bucket of the batch
+        pending: `(source, destination)` of the items not finished yet
+        done: `(source, destination)` of the items transferred or skipped
+        failed: `(source, destination)` of the items that failed last time
+
+    Example:
+        >>> session = TransferSession("./nightly.json")
+        >>> session.download(downloader, "my-bucket", [("a.csv", "./a.csv")])
+        >>> # after a crash, in a new process
+        >>> TransferSession("./nightly.json").resume(downloader)
+    """
+
+    path: str
+    kind: Optional[Literal["download", "upload"]]
+    bucket_name: Optional[str]
+    pending: List[Tuple[str, str]]
+    done: List[Tuple[str, str]]
+    failed: List[Tuple[str, str]]
+
+    def __init__(self, path: str) -> None:
+        """
+        Open the session saved at `path`, or an empty one if there is none.
+
+        Raises:
+            ValueError: If `path` exists but is not a session file
+        """
+        ...
+
+    def download(
+        self,
+        downloader: "S3Downloader",
+        bucket_name: str,
+        downloads: List[Tuple[str, str]],
+        progress: Optional[TransferProgress] = None,
+    ) -> Results:
+        """
+        Start a batch of `(key, local_path)` downloads, replacing whatever the
+        session held, and run it.
+        """
+        ...
+
+    def upload(
+        self,
+        uploader: "S3Uploader",
+        bucket_name: str,
+        paths_and_keys: List[Tuple[str, str]],
+        progress: Optional[TransferProgress] = None,
+    ) -> Results:
+        """
+        Start a batch of `(local_path, key)` uploads, replacing whatever the
+        session held, and run it.
+        """
+        ...
+
+    def resume(
+        self,
+        client: Union["S3Downloader", "S3Uploader"],
+        progress: Optional[TransferProgress] = None,
+    ) -> Results:
+        """
+        Run the items of the batch that are not done yet, pending and failed
+        alike.
+
+        Args:
+            client: An `S3Downloader` for a batch of downloads, an `S3Uploader`
+                   for a batch of uploads
+
+        Raises:
+            ValueError: If the session holds no batch, or `client` is of the wrong
+                       kind
+        """
+        ...
+
+    def is_complete(self) -> bool:
+        """Check if every item of the batch is done."""
+        ...
+
+    def __repr__(self) -> str:
+        """Developer representation showing the item counts."""
+        ...
+
+
+JobStatus = Literal["queued", "running", "paused", "done", "failed", "cancelled"]
+"""
+Where a queued job is: "paused" jobs are running but dispatch nothing new;
+"done" jobs ran to the end, even if some of their items failed; "failed" jobs
+were stopped by an error of the batch as a whole.
+"""
+
+
+class JobHandle:
+    """
+    A batch submitted to a `TransferManager`.
+
+    Attributes:
+        id: Number of the job within its manager, starting at 1
+        priority: The priority the job was submitted with
+        paused: Whether the job is held back by `pause`
+    """
+
+    id: int
+    priority: int
+    paused: bool
+
+    def status(self) -> JobStatus:
+        """Where the job is."""
+        ...
+
+    def progress(self) -> TransferProgress:
+        """Live progress of the job's batch."""
+        ...
+
+    def done(self) -> bool:
+        """Check if the job is done, failed or cancelled."""
+        ...
+
+    def cancel(self) -> bool:
+        """
+        Cancel the job, whether queued or running. Transfers in flight are
+        stopped and their partially written files removed.
+
+        Returns:
+            False if the job had already finished
+        """
+        ...
+
+    def pause(self, parts: bool = False) -> bool:
+        """
+        Stop dispatching the job's items until `resume`. Transfers in flight run
+        to their end; a queued job paused this way takes its slot but starts
+        nothing.
+
+        Args:
+            parts: Also stop objects transferred in parts before their next
+                part (default=False)
+
+        Returns:
+            False if the job had already finished
+        """
+        ...
+
+    def resume(self) -> bool:
+        """
+        Let a paused job carry on where it stopped.
+
+        Returns:
+            False if the job wasn't paused
+        """
+        ...
+
+    def result(self, timeout: Optional[float] = None) -> Results:
+        """
+        Wait for the job to finish and return its results.
+
+        Args:
+            timeout: Seconds to wait at most (default=wait until it finishes)
+
+        Raises:
+            TimeoutError: The builtin one, if the job is still going after `timeout`
+            RobinzhonError: If the job was cancelled or failed
+        """
+        ...
+
+    def __repr__(self) -> str: ...
+
+
+class TransferManager:
+    """
+    Runs batches in the background on a shared async runtime, so the caller gets a
+    `JobHandle` back at once instead of blocking.
+
+    Jobs start by priority, then in the order they were submitted, at most
+    `max_running_jobs` at a time; each one still transfers its items with the
+    concurrency of its client. A job that is already running is not interrupted
+    by one of higher priority.
+
+    Example:
+        >>> manager = TransferManager()
+        >>> job = manager.submit(downloader, "my-bucket", [("a.csv", "./a.csv")])
+        >>> job.status()
+        'running'
+        >>> results = job.result()
+    """
+
+    max_running_jobs: int
+
+    def __init__(self, max_running_jobs: int = 1) -> None:
+        """
+        Raises:
+            ValueError: If `max_running_jobs` is zero
+        """
+        ...
+
+    def submit(
+        self,
+        client: Union["S3Downloader", "S3Uploader"],
+        bucket_name: str,
+        items: List[Union[Tuple[str, str], Tuple[str, str, int]]],
+        priority: int = 0,
+    ) -> JobHandle:
+        """
+        Queue a batch and return at once.
+
+        Args:
+            client: Who runs the batch, with its settings
+            bucket_name: The bucket of every item
+            items: `(key, local_path)` downloads for an `S3Downloader`, or
+                  `(local_path, key)` uploads for an `S3Uploader`. A third
+                  element gives the item a priority: items of higher priority
+                  are dispatched first (default=0)
+            priority: Jobs of higher priority start before those queued earlier
+                     (default=0)
+
+        Raises:
+            ValueError: If `client` is neither
+        """
+        ...
+
+    def __repr__(self) -> str: ...
+
+
+class S3Config:
+    """
+    AWS S3 client configuration.
+
+    A configured client can be shared between `S3Downloader` and `S3Uploader` so
+    credentials are resolved once and both classes reuse the same connection pool.
+    A pickled configuration builds a new client when unpickled, resolving
+    credentials again in the process it is unpickled in.
+
+    Attributes:
+        region_name: AWS region the client was built for
+        endpoint_url: Custom endpoint URL, if any
+        force_path_style: Whether path-style addressing is used
+        anonymous: Whether requests are sent unsigned
+        use_dualstack_endpoint: Whether IPv6-capable dual-stack endpoints are used
+        use_fips_endpoint: Whether FIPS endpoints are used
+        use_accelerate_endpoint: Whether S3 Transfer Acceleration is used
+        connect_timeout: Seconds allowed to establish a connection, if set
+        read_timeout: Seconds allowed for a response to start arriving, if set
+        operation_timeout: Seconds allowed for a whole operation, if set
+        operation_attempt_timeout: Seconds allowed for each attempt, if set
+        max_idle_connections_per_host: Idle connections kept per host, if set
+        role_arn: Role the client assumes, if any
+        role_session_name: Session name used when assuming the role, if set
+        external_id: External ID given when assuming the role, if set
+        role_duration: Seconds each session of the role lasts, if set
+        profile_name: Profile of the shared AWS config files in use, if set
+        credentials_provider: Callable credentials are asked from, if set
+        headers: Headers added to every request
+        before_request: Hook called before each request is signed, if set
+        after_response: Hook called with each response, if set
+        app_name: Application name appended to the user agent, if set
+
+    Note:
+        Credentials are resolved with the standard AWS provider chain (environment
+        variables, shared config/credentials files, instance metadata...). Ensure that
+        your AWS credentials are correctly configured in your environment or through
+        the AWS configuration files, or pass `anonymous=True` to read public buckets
+        without any.
+
+        Credentials that expire, such as STS sessions, SSO tokens, instance roles
+        and assumed roles, are loaded again five minutes before they expire, so
+        batches can run for longer than the session they started with. A request
+        S3 rejects as signed with expired credentials is sent again once new ones
+        are loaded. Static session credentials from environment variables can't be
+        renewed this way.
+
+        SSO (IAM Identity Center) profiles are supported, including `sso_session`
+        profiles whose token is refreshed from the SSO cache. When the SSO session
+        has expired or was never started, requests raise `SsoLoginRequiredError`,
+        whose message names the `aws sso login` command to run.
+
+        `headers`, `before_request` and `after_response` apply to the requests
+        sent through the AWS SDK, not to presigned URL downloads or KMS requests
+        of client-side encryption. An exception raised by a hook fails the
+        request it was called for.
+
+        Requests for a bucket in another region than `region_name` are sent again to
+        the bucket's region, which is remembered for the bucket by every instance
+        sharing the configuration.
+
+        Wherever a bucket name is expected, an access point ARN
+        (`arn:aws:s3:<region>:<account>:accesspoint/<name>`), an access point
+        alias or a Multi-Region Access Point ARN or alias (`<alias>.mrap`) can be
+        given instead, also in `s3://` URIs. Multi-Region Access Point requests
+        are signed with SigV4A. An alias is expanded into the access point's ARN
+        with the account of the credentials, which STS is asked for once, so
+        access points of other accounts need their ARN. Access points can't be
+        reached with `force_path_style`, and Multi-Region Access Points not with
+        an `endpoint_url`, FIPS, dual-stack or accelerate endpoints.
+
+    Example:
+        >>> config = S3Config("us-east-1")
+        >>> downloader = S3Downloader(config=config)
+        >>> uploader = S3Uploader(config=config)
+    """
+
+    region_name: str
+    endpoint_url: Optional[str]
+    force_path_style: bool
+    anonymous: bool
+    use_dualstack_endpoint: bool
+    use_fips_endpoint: bool
+    use_accelerate_endpoint: bool
+    connect_timeout: Optional[float]
+    read_timeout: Optional[float]
+    operation_timeout: Optional[float]
+    operation_attempt_timeout: Optional[float]
+    max_idle_connections_per_host: Optional[int]
+    role_arn: Optional[str]
+    role_session_name: Optional[str]
+    external_id: Optional[str]
+    role_duration: Optional[float]
+    profile_name: Optional[str]
+    credentials_provider: Optional[CredentialsProvider]
+    headers: Dict[str, str]
+    before_request: Optional[Callable[[str, str, Dict[str, str]], Optional[Dict[str, str]]]]
+    after_response: Optional[Callable[[int, Dict[str, str]], None]]
+    app_name: Optional[str]
+
+    def __init__(
+        self,
+        region_name: str,
+        endpoint_url: Optional[str] = None,
+        force_path_style: bool = False,
+        anonymous: bool = False,
+        use_dualstack_endpoint: bool = False,
+        use_fips_endpoint: bool = False,
+        use_accelerate_endpoint: bool = False,
+        connect_timeout: Optional[float] = None,
+        read_timeout: Optional[float] = None,
+        operation_timeout: Optional[float] = None,
+        operation_attempt_timeout: Optional[float] = None,
+        max_idle_connections_per_host: Optional[int] = None,
+        role_arn: Optional[str] = None,
+        role_session_name: Optional[str] = None,
+        external_id: Optional[str] = None,
+        role_duration: Optional[float] = None,
+        profile_name: Optional[str] = None,
+        credentials_provider: Optional[CredentialsProvider] = None,
+        headers: Optional[Dict[str, str]] = None,
+        before_request: Optional[
+            Callable[[str, str, Dict[str, str]], Optional[Dict[str, str]]]
+        ] = None,
+        after_response: Optional[Callable[[int, Dict[str, str]], None]] = None,
+        app_name: Optional[str] = None,
+    ) -> None:
+        """
+        Build an S3 client configuration.
+
+        Args:
+            region_name: AWS region name (e.g., "us-east-1", "eu-west-1")
+            endpoint_url: Custom endpoint for S3-compatible stores. Default = AWS endpoint
+            force_path_style: Use path-style addressing. Default = False
+            anonymous: Send unsigned requests, without resolving any credentials,
+                      to read public buckets. Default = False
+            use_dualstack_endpoint: Use endpoints reachable over IPv6 as well as
+                                   IPv4, for IPv6-only networks. Default = False
+            use_fips_endpoint: Use FIPS 140-validated endpoints, as GovCloud
+                              deployments require. Default = False
+            use_accelerate_endpoint: Use S3 Transfer Acceleration; the bucket must
+                                    have it enabled. Default = False
+            connect_timeout: Seconds to wait for a connection to be established.
+                            Default = SDK default
+            read_timeout: Seconds to wait for a response to start arriving.
+                         Default = no limit
+            operation_timeout: Seconds a whole operation may take, retries
+                              included. Default = no limit
+            operation_attempt_timeout: Seconds each attempt of an operation may
+                                      take. Default = no limit
+
+            These timeouts end once the headers of a response arrive; its body
+            is read without a time limit.
+            max_idle_connections_per_host: Idle connections kept open per host
+                                          for reuse. Default = no limit
+            role_arn: ARN of a role to assume with STS, using the credentials
+                     resolved from the environment; it is assumed again whenever
+                     its session runs out. Default = None
+            role_session_name: Session name to assume the role with, which the
+                              role's account sees in CloudTrail.
+                              Default = "robinzhon-<unix time>"
+            external_id: External ID the role's trust policy requires, as
+                        accounts granting access to a third party usually
+                        ask for. Default = None
+            role_duration: Seconds each session of the role lasts, between 900
+                          and 43200. Default = 3600
+            profile_name: Profile of the shared AWS config files to read
+                         credentials and settings from, such as an SSO profile.
+                         Default = `AWS_PROFILE`, or `default`
+            credentials_provider: Callable returning `(access_key, secret_key,
+                                 session_token, expiry)`, for credential brokers
+                                 the AWS provider chain doesn't know, or
+                                 botocore credentials. It is
+                                 called from a worker thread when credentials
+                                 are first needed and again shortly before
+                                 `expiry`, an aware `datetime` or seconds since
+                                 the epoch; `session_token` and `expiry` may be
+                                 None. `role_arn` assumes its role with these
+                                 credentials. Default = None
+            headers: Headers added to every request the client sends, such as
+                    tracing IDs or the auth headers of an S3-compatible
+                    gateway. They are signed like the others. Default = None
+            before_request: Called with `(method, url, headers)` of every
+                           request before it is signed, from a worker thread
+                           holding the GIL; the headers it returns, if any, are
+                           added to the request. Default = None
+            after_response: Called with `(status, headers)` of every response,
+                           retries and errors included, before it is read.
+                           Default = None
+            app_name: Name of the application, appended to the user agent as
+                     `app/<app_name>` so that S3 server access logs and
+                     CloudTrail attribute its traffic. Letters, digits and
+                     ``!#$%&'*+-.^_`|~`` only. Default = None
+
+        Raises:
+            ValueError: If `use_accelerate_endpoint` is combined with
+                       `force_path_style` or `use_fips_endpoint`, a timeout
+                       is not a positive number, `role_duration` is out of
+                       range, the role arguments are given without
+                       `role_arn`, `role_arn` or `credentials_provider` is
+                       given with `anonymous`, or a header or `app_name` is
+                       invalid
+            TypeError: If `credentials_provider` is neither callable nor
+                      botocore credentials, or a hook is not callable
+        """
+        ...
+
+    @staticmethod
+    def from_boto3(session: Any, **kwargs: Any) -> "S3Config":
+        """
+        Build a configuration from what an application already set up with boto3.
+
+        From a `boto3.Session` (or botocore session), the region, credentials and
+        custom endpoint (`AWS_ENDPOINT_URL`, `endpoint_url` in the config file)
+        are taken; refreshable credentials keep refreshing through botocore. Plain
+        botocore credentials, frozen or not, can be given instead, with
+        `region_name` in `kwargs`.
+
+        Args:
+            session: A boto3 or botocore session, or botocore credentials
+            **kwargs: Other `S3Config` arguments, which take precedence over what
+                     the session says
+
+        Returns:
+            S3Config: A configuration signing with the session's credentials
+
+        Raises:
+            ValueError: If the session has no credentials, or no region is known
+
+        Example:
+            >>> session = boto3.Session(profile_name="analytics")
+            >>> uploader = S3Uploader(config=S3Config.from_boto3(session))
+        """
+        ...
+
+    def __repr__(self) -> str:
+        """Developer representation showing the configuration."""
+        ...
+
+
+class ObjectStream(Iterator[bytes]):
+    """
+    Iterator over the contents of an S3 object, returned by `S3Downloader.stream`,
+    or over the records of a query, returned by `S3Downloader.select`.
+
+    Chunks are downloaded in the background a few at a time ahead of the consumer,
+    so memory use stays bounded no matter how large the object is. The download
+    stops when the iterator is closed, exhausted or garbage collected.
+
+    Example:
+        >>> with downloader.stream("my-bucket", "logs/huge.log") as chunks:
+        ...     for chunk in chunks:
+        ...         process(chunk)
+    """
+
+    def __iter__(self) -> "ObjectStream": ...
+
+    def __next__(self) -> bytes:
+        """
+        Return the next chunk.
+
+        Raises:
+            StopIteration: Once the whole object has been read
+            RobinzhonError: If the request or the connection fails
+        """
+        ...
+
+    def close(self) -> None:
+        """Stop the download. The iterator is exhausted afterwards."""
+        ...
+
+    def __enter__(self) -> "ObjectStream": ...
+
+    def __exit__(self, *args: object) -> bool: ...
+
+
+class ObjectInfo:
+    """
+    An entry of a bucket listing, yielded by `S3Downloader.list_objects`.
+
+    When listing with a delimiter, keys grouped under a common prefix are reported
+    once as an entry with `is_prefix=True`, whose `key` is the prefix and whose
+    other attributes are empty.
+    """
+
+    @property
+    def key(self) -> str: ...
+    @property
+    def size(self) -> int: ...
+    @property
+    def etag(self) -> Optional[str]:
+        """ETag as returned by S3, including the surrounding quotes."""
+        ...
+    @property
+    def last_modified(self) -> Optional[datetime]:
+        """Timezone-aware (UTC) modification time."""
+        ...
+    @property
+    def storage_class(self) -> Optional[str]: ...
+    @property
+    def is_prefix(self) -> bool: ...
+    def __repr__(self) -> str: ...
+
+
+class ObjectMetadata:
+    """
+    Metadata of an object, returned by `S3Downloader.head`.
+    """
+
+    @property
+    def key(self) -> str: ...
+    @property
+    def size(self) -> int: ...
+    @property
+    def etag(self) -> Optional[str]:
+        """ETag as returned by S3, including the surrounding quotes."""
+        ...
+    @property
+    def content_type(self) -> Optional[str]: ...
+    @property
+    def last_modified(self) -> Optional[datetime]:
+        """Timezone-aware (UTC) modification time."""
+        ...
+    @property
+    def metadata(self) -> Dict[str, str]:
+        """User metadata, keyed without the `x-amz-meta-` prefix."""
+        ...
+    @property
+    def storage_class(self) -> Optional[str]:
+        """Storage class; `None` for STANDARD, which S3 does not report."""
+        ...
+    @property
+    def restore(self) -> Optional[str]:
+        """
+        Restore status of an archived object, as S3 reports it:
+        'ongoing-request="true"' while a restore runs, then
+        'ongoing-request="false", expiry-date="..."'. `None` if no restore was
+        requested.
+        """
+        ...
+    def __repr__(self) -> str: ...
+
+
+class ObjectVersion:
+    """
+    A version of an object, returned by `S3Downloader.list_object_versions`.
+
+    Delete markers are listed too, with `is_delete_marker=True` and a `size` of 0.
+    An object whose latest version is a delete marker looks deleted, but its
+    earlier versions can still be downloaded by `version_id`.
+    """
+
+    @property
+    def key(self) -> str: ...
+    @property
+    def version_id(self) -> str:
+        """Version ID; "null" for objects written before versioning was enabled."""
+        ...
+    @property
+    def size(self) -> int: ...
+    @property
+    def etag(self) -> Optional[str]:
+        """ETag as returned by S3, including the surrounding quotes."""
+        ...
+    @property
+    def last_modified(self) -> Optional[datetime]:
+        """Timezone-aware (UTC) time the version was written."""
+        ...
+    @property
+    def is_latest(self) -> bool: ...
+    @property
+    def is_delete_marker(self) -> bool: ...
+    @property
+    def storage_class(self) -> Optional[str]: ...
+    def __repr__(self) -> str: ...
+
+
+class ObjectListing(Iterator[ObjectInfo]):
+    """
+    Lazy iterator over a bucket listing, returned by `S3Downloader.list_objects`.
+
+    Pages of up to 1000 keys are requested one at a time, as the previous page is
+    consumed, so iterating over a large bucket never holds the whole listing.
+    """
+
+    def __iter__(self) -> "ObjectListing": ...
+
+    def __next__(self) -> ObjectInfo:
+        """
+        Return the next entry, in key order within each page.
+
+        Raises:
+            StopIteration: Once the listing is exhausted
+            RobinzhonError: If a page request fails (`BucketNotFoundError`,
+                           `AccessDeniedError`...)
+        """
+        ...
+
+
+class SyncReport:
+    """
+    Outcome of `S3Downloader.sync_down` or `S3Uploader.sync_up`.
+
+    Attributes:
+        results: `Results` of the transfers the sync ran; failed items can be
+                passed to `retry_failed`
+        new: Keys transferred because the destination didn't have them
+        changed: Keys transferred because the destination's copy was outdated
+        skipped: Keys left alone because the destination was already up to date
+        deleted: What `delete=True` removed from the destination: local paths for
+                `sync_down`, keys for `sync_up`
+    """
+
+    results: Results
+    new: List[str]
+    changed: List[str]
+    skipped: List[str]
+    deleted: List[str]
+
+    def __repr__(self) -> str: ...
+
+
+class TransferPlan:
+    """
+    What a batch method given `dry_run=True` would do, worked out by listing
+    and comparing only: no object or local file is read, written or deleted to
+    make it, beyond the MD5s `overwrite="if_etag_differs"` and
+    `sync_up(checksum=True)` compare. Batches of keys, URIs or manifest rows,
+    which no listing covers, send a HEAD request per object instead.
+
+    Attributes:
+        kind: "download", "upload" or "delete"
+        transfers: `(source, destination)` of every item that would be
+                  transferred: `(key, local_path)` for downloads and
+                  `(local_path, key)` for uploads
+        deletions: Keys, or for `sync_down` local paths, that would be deleted
+        skipped: Items left alone because they are up to date: local paths for
+                downloads, keys for syncs
+        failed: `FailedItem`s for items bound to fail whatever S3 answers, such
+               as keys that map outside the directory or files that can't be read
+        total_bytes: Bytes the transfers would move, from the listing or the
+                    HEAD requests for downloads and from the files for
+                    uploads; deletions count for none
+
+    `len(plan)` is the number of transfers and deletions.
+
+    Example:
+        >>> plan = downloader.sync_down("my-bucket", "site/", "./site", delete=True, dry_run=True)
+        >>> print(f"{len(plan.transfers)} files, {plan.total_bytes / 1e9:.1f} GB, "
+        ...       f"{len(plan.deletions)} deletions")
+    """
+
+    kind: Literal["download", "upload", "delete"]
+    transfers: List[Tuple[str, str]]
+    deletions: List[str]
+    skipped: List[str]
+    failed: List[FailedItem]
+    total_bytes: int
+
+    def __len__(self) -> int: ...
+    def __repr__(self) -> str: ...
+
+
+class PreflightReport:
+    """
+    Outcome of `S3Client.preflight`. Checks run in order and stop at the first
+    one a batch couldn't get past; those that didn't run are `None`.
+
+    Attributes:
+        bucket_name: The bucket checked
+        credentials: Whether AWS credentials could be loaded; `None` for
+                    anonymous clients
+        bucket_exists: Whether the bucket exists
+        bucket_region: The region S3 reports the bucket in
+        region_matches: Whether `bucket_region` is the client's region; requests
+                        for a bucket in another region are redirected there
+        can_list: Whether HeadBucket is allowed, which takes `s3:ListBucket`
+        can_write: Whether PutObject is allowed, found out with a conditional
+                  PUT of zero bytes that creates nothing
+        problems: One line per problem found; empty when all is well
+        ok: Whether no problem was found, also the truth value of the report
+    """
+
+    bucket_name: str
+    credentials: Optional[bool]
+    bucket_exists: Optional[bool]
+    bucket_region: Optional[str]
+    region_matches: Optional[bool]
+    can_list: Optional[bool]
+    can_write: Optional[bool]
+    problems: List[str]
+    ok: bool
+
+    def __bool__(self) -> bool: ...
+    def __repr__(self) -> str: ...
+
+
+class S3File:
+    """
+    Read-only, seekable file object over an S3 object, returned by
+    `S3Downloader.open`.
+
+    Reads are served from an internal buffer refilled with ranged GETs, so only
+    the parts of the object actually read are downloaded. It can be handed to
+    anything that expects a binary file, such as `zipfile.ZipFile`.
+
+    Example:
+        >>> with downloader.open("my-bucket", "archives/data.zip") as f:
+        ...     with zipfile.ZipFile(f) as archive:
+        ...         names = archive.namelist()
+    """
+
+    @property
+    def bucket_name(self) -> str: ...
+    @property
+    def key(self) -> str: ...
+    @property
+    def size(self) -> int:
+        """Size of the object in bytes, as seen when it was opened."""
+        ...
+    @property
+    def closed(self) -> bool: ...
+
+    def read(self, size: Optional[int] = -1) -> bytes:
+        """
+        Read up to `size` bytes, or everything left if `size` is negative or None.
+
+        Raises:
+            ValueError: If the file is closed
+            RobinzhonError: If the request fails, including when the object was
+                           overwritten after it was opened
+        """
+        ...
+
+    def seek(self, offset: int, whence: int = 0) -> int:
+        """
+        Move to `offset`, relative to the start (0), current position (1) or
+        end (2) of the object, and return the new position.
+        """
+        ...
+
+    def tell(self) -> int: ...
+    def readable(self) -> bool: ...
+    def seekable(self) -> bool: ...
+    def writable(self) -> bool: ...
+    def close(self) -> None: ...
+    def __enter__(self) -> "S3File": ...
+    def __exit__(self, *args: object) -> bool: ...
+
+
+class S3Downloader:
+    """
+    High-performance AWS S3 file downloader with concurrent download capabilities.
+
+    This class provides methods to download files from AWS S3 buckets, with support
+    for both single file downloads and concurrent batch downloads using async I/O
+    for optimal network utilization and performance.
+
+    A downloader can be called from many threads at once, in parallel on
+    free-threaded CPython.
+
+    Downloaders can be pickled, e.g. to hand them to `multiprocessing` workers:
+    they are pickled as their constructor arguments and build a new runtime and
+    client when unpickled.
+
+    Example:
+        >>> downloader = S3Downloader("us-east-1")
+        >>> downloader.download_file("my-bucket", "path/to/file.txt", "./local-file.txt")
+        './local-file.txt'
+    """
+
+    def __init__(
+        self,
+        region_name: Optional[str] = None,
+        max_concurrent_downloads: int = 5,
+        endpoint_url: Optional[str] = None,
+        force_path_style: bool = False,
+        config: Optional[S3Config] = None,
+        runtime_threads: Optional[int] = None,
+        retry_policy: Optional[RetryPolicy] = None,
+        part_size: int = 8 * 1024 * 1024,
+        part_concurrency: int = 8,
+        client_encryption: Optional[ClientEncryption] = None,
+        max_bandwidth_bytes_per_sec: Optional[int] = None,
+        max_requests_per_sec: Optional[float] = None,
+        request_burst: Optional[int] = None,
+        limiter: Optional[TransferLimiter] = None,
+        write_buffer_size: int = 8 * 1024,
+        verify_checksum: bool = False,
+        auto_decompress: bool = False,
+        preserve_mtime: bool = False,
+        preserve_permissions: bool = False,
+        symlinks: SymlinkPolicy = "follow",
+        audit_log: Optional[str] = None,
+        max_in_flight_bytes: Optional[int] = None,
+    ) -> None:
+        """
+        Initialize the S3 downloader with the specified AWS region.
+
+        Args:
+            region_name: AWS region name (e.g., "us-east-1", "eu-west-1")
+            max_concurrent_downloads: Maximum concurrent downloads from S3. Default = 5
+            endpoint_url: Custom endpoint for S3-compatible stores (MinIO, LocalStack,
+                         Cloudflare R2, DigitalOcean Spaces...). Default = AWS endpoint
+            force_path_style: Use path-style addressing (`endpoint/bucket/key`) instead
+                             of virtual-hosted style. Most self-hosted stores need this
+            config: Existing `S3Config` to reuse. When given, `region_name`
+                   is ignored, and `endpoint_url` or `force_path_style`
+                   raise ValueError
+            runtime_threads: Number of worker threads of the multi-threaded Tokio
+                            runtime backing this instance. Default = one per CPU core
+            retry_policy: How transient failures are retried. Default = `RetryPolicy()`
+            part_size: Objects larger than this are fetched as several ranged GETs of
+                      this many bytes, written in place into a preallocated file.
+                      Default = 8 MiB
+            part_concurrency: Ranged GETs in flight per object. 1 downloads every
+                             object over a single connection. Default = 8
+            client_encryption: Decrypt client-side encrypted objects on download.
+                              They are fetched with one GET and decrypted as
+                              they arrive. Objects stored in the clear are left
+                              as they are, unless it requires encryption
+            max_bandwidth_bytes_per_sec: Cap on the bytes per second received by
+                                        all downloads of this instance together.
+                                        Default = unlimited
+            max_requests_per_sec: Cap on the objects a batch starts per second,
+                                 to stay clear of S3 "503 SlowDown" throttling
+                                 on batches of many small objects.
+                                 Default = unlimited
+            request_burst: Objects that may start at once after an idle period.
+                          Default = one second worth of `max_requests_per_sec`
+            limiter: `TransferLimiter` shared with other instances, capping the
+                    objects they transfer together. Default = no shared cap
+            write_buffer_size: Bytes buffered before they are written to the
+                              local file. Files are preallocated to their full
+                              size when it is known. Default = 8 KiB
+            verify_checksum: Check every download against the MD5 its ETag
+                            stands for, and against the CRC/SHA checksums S3
+                            stored with it, if any. A mismatch fails the item,
+                            removes the file and is retried. Multipart ETags
+                            are checked on the boundaries of the parts the
+                            object was uploaded in, sized by a HEAD of its
+                            first part, when it is downloaded in parts;
+                            SSE-KMS and SSE-C ETags, which aren't MD5s, only
+                            through stored checksums.
+                            Default = False
+            auto_decompress: Store objects uploaded with `Content-Encoding: gzip`,
+                            or whose key ends in `.gz`, decompressed, inflating
+                            them as they arrive. Both sizes are recorded in
+                            `Results.decompressed`. Ranges, client-side
+                            encrypted objects and `sync_down` are left
+                            compressed. Default = False
+            preserve_mtime: Set the modification time of every downloaded
+                           file to the one its uploader recorded in the
+                           "mtime" metadata (see `S3Uploader`), or else to the
+                           object's LastModified, for tools that detect
+                           changes by mtime. Ranges are left alone.
+                           Default = False
+            preserve_permissions: Give every downloaded file the read, write
+                                 and execute bits recorded in its "mode"
+                                 metadata by an uploader with
+                                 `preserve_permissions`, or by s3fs. Setuid,
+                                 setgid and sticky bits are never restored.
+                                 No-op outside POSIX systems.
+                                 Default = False
+            symlinks: "preserve" recreates the links an uploader stored with
+                     `symlinks="preserve"`; "skip" leaves them out. Links with
+                     an absolute target, or one leading out of the directory a
+                     batch downloads into (out of the link's own directory for
+                     downloads to explicit paths), fail instead of being
+                     created, and files are never written through a link
+                     below that directory. Other platforms than POSIX keep
+                     their target as a file.
+                     Default = "follow", a file holding the link's target
+            audit_log: Path of a JSON Lines file every file download is
+                      appended to, with its time, bucket, key, local path,
+                      result, size and SHA-256; see `verify_audit_log`.
+                      Clients naming the same file share it.
+                      Default = no audit log
+            max_in_flight_bytes: Cap on the bytes every download of the
+                                instance buffers together: the write buffer of
+                                each stream to a file, and whole bodies
+                                `download_bytes` reads into memory. A download
+                                waits for room before reading its body, and one
+                                larger than the cap runs alone; a chunked body,
+                                of unknown length, waits for more room as it
+                                arrives. Streamed objects and archive
+                                extraction aren't counted.
+                                Default = unlimited
+
+        Raises:
+            ValueError: If neither `region_name` nor `config` is provided, if
+                       `runtime_threads`, `part_size`, `part_concurrency`,
+                       `max_bandwidth_bytes_per_sec`, `request_burst`,
+                       `max_in_flight_bytes` or `write_buffer_size` is zero,
+                       or if `max_requests_per_sec` is not positive
+            RobinzhonError: If `audit_log` can't be opened for appending
+
+        Example:
+            >>> downloader = S3Downloader("us-east-1")
+            >>> minio = S3Downloader(
+            ...     "us-east-1",
+            ...     endpoint_url="http://localhost:9000",
+            ...     force_path_style=True,
+            ... )
+        """
+        ...
+
+    def reinitialize(self) -> None:
+        """
+        Rebuild the async runtime and the S3 client in a process forked after
+        this downloader was created, which can't use the parent's threads and
+        connections. Until then, calls in the child raise `RobinzhonError`,
+        awaitable ones and `TransferManager.submit` included.
+
+        Fork while no transfers are running: the `limiter` slots they hold stay
+        taken in the child.
+        """
+        ...
+
+    def download_file(
+        self,
+        bucket_name: str,
+        object_key: str,
+        path_to_store: str,
+        version_id: Optional[str] = None,
+        if_none_match: Optional[str] = None,
+        if_modified_since: Optional[datetime] = None,
+    ) -> Optional[str]:
+        """
+        Download a single file from S3 to the local filesystem.
+
+        Args:
+            bucket_name: Name of the S3 bucket
+            object_key: S3 object key (path to the file in the bucket)
+            path_to_store: Local file path where the downloaded file will be stored
+            version_id: Version of the object to download, in a versioned bucket;
+                       the current version by default (see `list_object_versions`)
+            if_none_match: ETag of the copy already at `path_to_store`; the
+                          object is only downloaded if its ETag differs
+            if_modified_since: Only download the object if it was modified after
+                              this time. Naive datetimes are taken as local time
+
+        Returns:
+            The local file path where the file was stored, or None if
+            `if_none_match` or `if_modified_since` showed the object unchanged,
+            in which case the file at `path_to_store` is left untouched
+
+        Raises:
+            NoSuchKeyError: If the object does not exist
+            BucketNotFoundError: If the bucket does not exist
+            AccessDeniedError: If the credentials may not read the object
+            TimeoutError: If the request times out
+            RobinzhonError: If the download fails for any other reason, such as
+                           network or file system errors
+
+        Example:
+            >>> downloader = S3Downloader("us-east-1")
+            >>> result = downloader.download_file(
+            ...     "my-bucket",
+            ...     "data/file.csv",
+            ...     "./downloads/file.csv"
+            ... )
+            >>> print(result)
+            './downloads/file.csv'
+            >>> etag = downloader.head("my-bucket", "data/file.csv").etag
+            >>> downloader.download_file(
+            ...     "my-bucket", "data/file.csv", "./downloads/file.csv", if_none_match=etag
+            ... ) is None
+            True
+        """
+        ...
+
+    def download_range(
+        self,
+        bucket_name: str,
+        object_key: str,
+        path_to_store: str,
+        offset: int,
+        length: Optional[int] = None,
+        version_id: Optional[str] = None,
+    ) -> str:
+        """
+        Download only a slice of an object, such as the footer of a Parquet file
+        or a segment of a video, with a ranged GET.
+
+        Args:
+            bucket_name: Name of the S3 bucket
+            object_key: S3 object key
+            path_to_store: Local file path the slice is written to
+            offset: Position of the first byte to download. A negative offset
+                   selects the last `-offset` bytes of the object
+            length: Number of bytes to download; up to the end of the object
+                   when None. A range running past the end is cut short there
+            version_id: Version of the object to read; the current one by default
+
+        Returns:
+            The local file path where the slice was stored
+
+        Raises:
+            ValueError: If `length` is not positive, or given with a negative
+                       `offset`
+            NoSuchKeyError: If the object does not exist
+            RobinzhonError: If the range starts past the end of the object (S3
+                           answers 416), client-side encryption is enabled, or
+                           the download fails for any other reason
+
+        Note:
+            The slice is fetched in one GET and checked against its
+            Content-Length only: the ETag and checksums S3 stores describe the
+            whole object.
+
+        Example:
+            >>> downloader.download_range(
+            ...     "my-bucket", "data/table.parquet", "./footer.bin", -8
+            ... )
+            './footer.bin'
+            >>> downloader.download_range(
+            ...     "my-bucket", "videos/clip.mp4", "./segment.mp4", 1_048_576, 4_194_304
+            ... )
+            './segment.mp4'
+        """
+        ...
+
+    def download_ranges(
+        self,
+        bucket_name: str,
+        object_key: str,
+        ranges: List[ByteRange],
+        path_to_store: Optional[str] = None,
+        version_id: Optional[str] = None,
+    ) -> Union[str, List[bytes]]:
+        """
+        Download several slices of one object concurrently, such as the column
+        chunks of a Parquet file.
+
+        Args:
+            bucket_name: Name of the S3 bucket
+            object_key: S3 object key
+            ranges: `(offset, length)` slices to download (see `ByteRange` and
+                   `download_range`); they may overlap
+            path_to_store: File the slices are written into, each at its offset
+                          in the object, with zeros in between. Without one the
+                          slices are returned instead
+            version_id: Version of the object to read; the current one by default
+
+        Returns:
+            `path_to_store`, or the slices as a list of bytes in the order of
+            `ranges`
+
+        Raises:
+            ValueError: If `ranges` is empty or one of them is invalid
+            NoSuchKeyError: If the object does not exist
+            RobinzhonError: If a range starts past the end of the object, the
+                           object changed while its ranges were downloaded,
+                           client-side encryption is enabled, or the download
+                           fails for any other reason
+
+        Note:
+            The first range is fetched alone; the others follow concurrently,
+            `part_concurrency` at a time, with `If-Match` on its ETag so that
+            all slices come from the same version of the object. Slices are
+            held in memory until they are written.
+
+        Example:
+            >>> footer, header = downloader.download_ranges(
+            ...     "my-bucket", "data/table.parquet", [(-8, None), (0, 4)]
+            ... )
+            >>> header
+            b'PAR1'
+        """
+        ...
+
+    def download_and_extract(
+        self,
+        bucket_name: str,
+        object_key: str,
+        dest_dir: str,
+        format: ArchiveFormat = "tar.gz",
+    ) -> Results:
+        """
+        Download an archive and unpack it below `dest_dir` as it arrives, so
+        multi-GB archives never touch the disk packed.
+
+        Args:
+            bucket_name: Name of the S3 bucket
+            object_key: S3 object key of the archive
+            dest_dir: Directory the members are unpacked into, created if needed
+            format: How the archive is packed (see `ArchiveFormat`).
+                   Default = "tar.gz"
+
+        Returns:
+            Results: The unpacked files in `successful`, and `as_dict()`
+                    mapping each member name to its local path. Members whose
+                    path leaves `dest_dir` are in `failed`; tar links and
+                    special files are left out and listed in `skipped`
+
+        Raises:
+            ValueError: If `format` is unknown
+            NoSuchKeyError: If the object does not exist
+            RobinzhonError: If the archive is corrupt or not in `format`, a
+                           member can't be written, client-side encryption is
+                           enabled, or the download fails for any other reason
+
+        Note:
+            An attempt that fails is retried from the start of the archive,
+            unpacking over the files of the one before. Zip members are read
+            from their local headers, so zips written as a stream, whose sizes
+            only follow the data, can't be unpacked this way. The returned
+            `Results` can't be retried with `retry_failed`.
+
+        Example:
+            >>> results = downloader.download_and_extract(
+            ...     "my-bucket", "datasets/images.tar.gz", "./images"
+            ... )
+            >>> len(results.successful)
+            12000
+        """
+        ...
+
+    def download_archived(
+        self,
+        bucket_name: str,
+        manifest_key: str,
+        dest_dir: str,
+    ) -> Results:
+        """
+        Download the files an `ArchiveMode` upload packed, by unpacking every
+        bundle its manifest names below `dest_dir`. Each file lands at its key,
+        relative to `dest_dir`.
+
+        Args:
+            bucket_name: Name of the S3 bucket
+            manifest_key: Key of the manifest, `{prefix}manifest.json` for the
+                         `prefix` of the `ArchiveMode`
+            dest_dir: Directory the files are unpacked into, created if needed
+
+        Returns:
+            Results: The unpacked files in `successful`, and `as_dict()`
+                    mapping each key to its local path. The files of a bundle
+                    that can't be downloaded or unpacked are in `failed`, by key
+
+        Raises:
+            NoSuchKeyError: If the manifest does not exist
+            RobinzhonError: If the manifest is not one `ArchiveMode` wrote, or
+                           it can't be downloaded for any other reason
+
+        Note:
+            Bundles are unpacked concurrently, up to the downloader's
+            `max_concurrent_downloads`. The returned `Results` can't be retried
+            with `retry_failed`.
+
+        Example:
+            >>> results = downloader.download_archived(
+            ...     "my-bucket", "bundles/2024-06-01/manifest.json", "./restored"
+            ... )
+        """
+        ...
+
+    def download_multiple_files(
+        self,
+        bucket_name: str,
+        object_keys: Iterable[str],
+        base_directory: str,
+        progress: Optional[TransferProgress] = None,
+        overwrite: OverwritePolicy = "always",
+        preserve_structure: bool = False,
+        fail_fast: bool = False,
+        max_failures: Optional[int] = None,
+        raise_on_failure: bool = False,
+        checksum_manifest: Optional[str] = None,
+        expected_sha256: Optional[Dict[str, str]] = None,
+        check_space: bool = False,
+        scheduling: Scheduling = "fifo",
+        dry_run: bool = False,
+    ) -> Union[Results, TransferPlan]:
+        """
+        Download multiple files from S3 concurrently to a base directory.
+
+        Files are downloaded concurrently using async I/O for optimal network utilization
+        and significantly improved performance compared to sequential downloads. This approach
+        is highly efficient for I/O-bound operations like file downloads.
+
+        **Resilient Error Handling**: If some files fail to download, the operation continues
+        and returns both successful and failed downloads for programmatic handling.
+
+        Args:
+            bucket_name: Name of the S3 bucket
+            object_keys: S3 object keys to download: a list, or any iterable such
+                        as a generator, consumed lazily as the batch has room for
+                        more downloads
+            base_directory: Local directory where all files will be stored
+            progress: Optional `TransferProgress` updated while the batch runs
+            overwrite: What to do when a destination file already exists:
+                      "always" (default) downloads it again, "never" keeps it,
+                      "if_size_differs" and "if_etag_differs" keep it unless it
+                      differs from the object (see `OverwritePolicy`). Kept files
+                      are listed in `Results.skipped`
+            preserve_structure: Store each object at its full key below
+                               `base_directory` ("a/data.csv" becomes
+                               `base_directory/a/data.csv`) instead of at its file
+                               name alone
+            fail_fast: Stop the batch at its first failed item; the same as
+                      `max_failures=1`
+            max_failures: Stop the batch once this many items have failed. No
+                         new items are started; the ones in flight finish
+            raise_on_failure: Raise `PartialFailureError` instead of returning
+                             when any item failed
+            checksum_manifest: Path of a `SHA256SUMS` file to write once the batch
+                              is done, listing the SHA-256 of every file it
+                              downloaded as `sha256sum` does. Digests are computed
+                              as the files are written. Files kept by `overwrite`
+                              are not read, so they aren't listed
+            expected_sha256: Hex SHA-256 digest each key's file must have, by key.
+                            A download that doesn't match fails, and its file is
+                            removed; keys not listed aren't checked
+            check_space: Before downloading anything, look up the size of every
+                        object with a HEAD request and check that they fit in
+                        the free space of `base_directory`. `object_keys` is
+                        read whole first. Files written over only count for
+                        what the object adds to them; objects that can't be
+                        looked up aren't counted. POSIX systems only; elsewhere
+                        nothing is checked
+            scheduling: Order to start the downloads in; see `Scheduling`.
+                       Any other than "fifo" reads `object_keys` whole and
+                       looks up every object's size with a HEAD request first,
+                       as `check_space` does. Default = "fifo"
+            dry_run: Only look up every object with a HEAD request and return
+                    the `TransferPlan` of the downloads, judging `overwrite` by
+                    what it answers. `object_keys` is read whole first
+
+        Returns:
+            DownloadResults object with successful and failed download lists.
+            With `dry_run`, a `TransferPlan`
+
+        Raises:
+            ValueError: If `overwrite` is not a known policy, `max_failures` is 0,
+                       `fail_fast` is combined with another `max_failures`, or a
+                       digest of `expected_sha256` isn't 64 hex digits
+            BatchAbortedError: If the batch stopped at its failure limit, with the
+                              items finished so far as `partial_results`
+            PartialFailureError: If `raise_on_failure` is set and any item failed,
+                                with the batch's `Results` as `results`
+            TypeError: If `object_keys` is a single string rather than an iterable of keys
+            InsufficientSpaceError: If `check_space` is set and the batch doesn't
+                                   fit, with the bytes it needs and the bytes
+                                   free; nothing is downloaded
+            RobinzhonError: If the base directory cannot be created or other system errors
+            Exception: Whatever `object_keys` raises while being iterated stops the
+                      batch: the downloads in flight finish and the exception is
+                      raised with the items finished so far as `partial_results`
+            KeyboardInterrupt: If interrupted with Ctrl-C. Outstanding transfers are
+                              cancelled, partially written files are removed, and the
+                              items finished so far are attached as `partial_results`
+
+        Note:
+            Performance scales with network bandwidth and connection limits rather than
+            CPU cores, making this highly efficient for downloading many files.
+            Failed downloads are logged as warnings and available in result.failed.
+            Keys that map to the same local file (like "a/data.csv" and
+            "b/data.csv" without `preserve_structure`) are not allowed to overwrite
+            each other: the first one is downloaded, the others reported as failed.
+            Keys are normalized ("a//./b" is stored as "a/b") and keys that would
+            resolve outside `base_directory`, like "../../etc/cron.d/job", are
+            reported as failed instead of being written.
+
+        Example:
+            >>> downloader = S3Downloader("us-east-1")
+            >>> files = [
+            ...     "data/file1.csv",          # exists
+            ...     "data/missing-file.json",  # doesn't exist
+            ...     "logs/app.log"            # exists
+            ... ]
+            >>> result = downloader.download_multiple_files(
+            ...     "my-bucket",
+            ...     files,
+            ...     "./downloads"
+            ... )
+            # Warning: 1 downloads failed:
+            #   data/missing-file.json: S3 object not found
+            >>> print(f"Success: {len(result.successful)}, Failed: {len(result.failed)}")
+            Success: 2, Failed: 1
+            >>> if result.has_failures():
+            ...     print(f"Failed files: {result.failed_keys}")
+            Failed files: ['data/missing-file.json']
+            >>> for path in result.successful:
+            ...     print(f"Downloaded: {path}")
+        """
+        ...
+
+    def download_multiple_files_with_paths(
+        self,
+        bucket_name: str,
+        downloads: Iterable[DownloadItem],
+        progress: Optional[TransferProgress] = None,
+        overwrite: OverwritePolicy = "always",
+        if_none_match: Optional[Dict[str, str]] = None,
+        if_modified_since: Optional[datetime] = None,
+        fail_fast: bool = False,
+        max_failures: Optional[int] = None,
+        raise_on_failure: bool = False,
+        checksum_manifest: Optional[str] = None,
+        expected_sha256: Optional[Dict[str, str]] = None,
+        dry_run: bool = False,
+    ) -> Union[Results, TransferPlan]:
+        """
+        Download multiple files from S3 concurrently with custom local paths.
+
+        This method allows you to specify custom local paths for each downloaded file,
+        providing full control over the destination file structure. Downloads are
         executed concurrently using async I/O for maximum performance.
 
-        **Resilient Error Handling**: If some files fail to download, the operation continues
-        and returns both successful and failed downloads for programmatic handling.
+        **Resilient Error Handling**: If some files fail to download, the operation continues
+        and returns both successful and failed downloads for programmatic handling.
+
+        Args:
+            bucket_name: Name of the S3 bucket
+            downloads: Tuples of (object_key, local_path) - S3 key and desired
+                      local file path - or (object_key, local_path, version_id)
+                      to download a given version of the object. Either may end
+                      with an `(offset, length)` `ByteRange` to download only
+                      that slice (see `download_range`). Any iterable is
+                      accepted and consumed lazily
+            progress: Optional `TransferProgress` updated while the batch runs
+            overwrite: What to do when a destination file already exists:
+                      "always" (default) downloads it again, "never" keeps it,
+                      "if_size_differs" and "if_etag_differs" keep it unless it
+                      differs from the object (see `OverwritePolicy`). Kept files
+                      are listed in `Results.skipped`
+            if_none_match: ETag of the copy already at each key's local path, by
+                          key, as remembered from a previous run. Objects whose
+                          ETag still matches are not transferred; their local
+                          paths are listed in `Results.skipped`
+            if_modified_since: Only transfer objects modified after this time;
+                              the others are listed in `Results.skipped`. Naive
+                              datetimes are taken as local time
+            checksum_manifest: Path of a `SHA256SUMS` file to write once the batch
+                              is done; see `download_multiple_files`
+            expected_sha256: SHA-256 each key's file must have, by key; see
+                            `download_multiple_files`
+            dry_run: Only return the `TransferPlan` of the downloads; see
+                    `download_multiple_files`. `if_none_match` and
+                    `if_modified_since` are judged by the HEAD requests
+
+        Returns:
+            DownloadResults object with successful and failed download lists.
+            With `dry_run`, a `TransferPlan`
+
+        Raises:
+            ValueError: If `overwrite` is not a known policy
+            RobinzhonError: If directories cannot be created or other system errors
+            KeyboardInterrupt: If interrupted with Ctrl-C; see `download_multiple_files`
+            Exception: Whatever `downloads` raises while being iterated; see
+                      `download_multiple_files`
+            BatchAbortedError: If the batch stopped at its `fail_fast` or
+                              `max_failures` limit; see `download_multiple_files`
+
+        Note:
+            Directories are automatically created as needed for each file path.
+            If several downloads share a local path, only the first one runs; the
+            others are reported as failed.
+            The size and ETag of an object don't describe a slice of it, so for
+            ranged items "if_size_differs" and "if_etag_differs" download
+            again like "always".
+            Performance is optimized for I/O-bound operations with concurrent execution.
+            Failed downloads are logged as warnings and available in result.failed.
+
+        Example:
+            >>> downloader = S3Downloader("us-east-1")
+            >>> downloads = [
+            ...     ("data/input.csv", "./processed/input_data.csv"),        # exists
+            ...     ("config/missing.json", "./config/app_settings.json"),  # doesn't exist
+            ...     ("logs/2024/app.log", "./logs/application.log")         # exists
+            ... ]
+            >>> result = downloader.download_multiple_files_with_paths(
+            ...     "my-bucket",
+            ...     downloads
+            ... )
+            # Warning: 1 downloads failed:
+            #   config/missing.json: S3 object not found
+            >>> print(f"Success rate: {result.success_rate():.1%}")
+            Success rate: 66.7%
+            >>> if not result.is_complete_success():
+            ...     print("Some downloads failed, checking what to retry...")
+            ...     for item in result.failed:
+            ...         print(f"  Retry: {item.key} ({item.error_message})")
+        """
+        ...
+
+    def iter_download_multiple_files(
+        self,
+        bucket_name: str,
+        object_keys: Iterable[str],
+        base_directory: str,
+        overwrite: OverwritePolicy = "always",
+        preserve_structure: bool = False,
+        fail_fast: bool = False,
+        max_failures: Optional[int] = None,
+    ) -> BatchIterator:
+        """
+        Start `download_multiple_files` in the background and iterate its items
+        as they finish.
+
+        Example:
+            >>> for item in tqdm(downloader.iter_download_multiple_files("my-bucket", keys, "./downloads")):
+            ...     pass
+        """
+        ...
+
+    def iter_download_multiple_files_with_paths(
+        self,
+        bucket_name: str,
+        downloads: Iterable[DownloadItem],
+        overwrite: OverwritePolicy = "always",
+        fail_fast: bool = False,
+        max_failures: Optional[int] = None,
+    ) -> BatchIterator:
+        """
+        Start `download_multiple_files_with_paths` in the background and iterate
+        its items as they finish.
+        """
+        ...
+
+    def download_multiple_objects(
+        self,
+        items: Iterable[Tuple[str, str, str]],
+        progress: Optional[TransferProgress] = None,
+        overwrite: OverwritePolicy = "always",
+        fail_fast: bool = False,
+        max_failures: Optional[int] = None,
+        raise_on_failure: bool = False,
+        dry_run: bool = False,
+    ) -> Union[Results, TransferPlan]:
+        """
+        Download objects from several buckets in one concurrent batch.
+
+        Behaves like `download_multiple_files_with_paths`, with each item naming
+        its own bucket. Failed items are reported by their `s3://bucket/key` URI
+        so that keys shared by several buckets stay distinguishable.
+
+        Args:
+            items: Tuples of (bucket_name, object_key, local_path). Any iterable
+                  is accepted and consumed lazily
+            progress: Optional `TransferProgress` updated while the batch runs
+            overwrite: What to do when a destination file already exists (see
+                      `OverwritePolicy`)
+            dry_run: Only return the `TransferPlan` of the downloads; see
+                    `download_multiple_files`
+
+        Example:
+            >>> result = downloader.download_multiple_objects([
+            ...     ("raw-data", "2024/a.csv", "./data/a.csv"),
+            ...     ("archive", "2023/b.csv", "./data/b.csv"),
+            ... ])
+        """
+        ...
+
+    def download_from_manifest(
+        self,
+        manifest_path: str,
+        bucket_name: Optional[str] = None,
+        format: Optional[Literal["csv", "jsonl"]] = None,
+        progress: Optional[TransferProgress] = None,
+        overwrite: OverwritePolicy = "always",
+        fail_fast: bool = False,
+        max_failures: Optional[int] = None,
+        raise_on_failure: bool = False,
+        dry_run: bool = False,
+    ) -> Union[Results, TransferPlan]:
+        """
+        Download the objects a manifest file lists, in one concurrent batch.
+
+        A CSV manifest starts with a header naming its `key` and `local_path`
+        columns, and optionally a `bucket` column; a JSON Lines manifest holds one
+        object per line with `key`, `local_path` and optionally `bucket` fields.
+        The manifest is read as the batch runs, so it may list millions of
+        objects. Behaves like `download_multiple_objects`: failed items are
+        reported by their `s3://bucket/key` URI.
+
+        Args:
+            manifest_path: Path of the manifest file
+            bucket_name: Bucket of the rows that don't name one
+            format: `"csv"` or `"jsonl"`; by default guessed from the extension
+                   (`.csv`, `.jsonl` or `.ndjson`)
+            progress: Optional `TransferProgress` updated while the batch runs
+            overwrite: What to do when a destination file already exists (see
+                      `OverwritePolicy`)
+            dry_run: Only return the `TransferPlan` of the downloads; see
+                    `download_multiple_files`
+
+        Raises:
+            ValueError: If the format can't be told, or a row is malformed or
+                       names no bucket when `bucket_name` is not given. Rows
+                       before it may already have been downloaded
+            RobinzhonError: If the manifest can't be read
+
+        Example:
+            >>> # manifest.csv:
+            >>> #   bucket,key,local_path
+            >>> #   raw-data,2024/a.csv,./data/a.csv
+            >>> result = downloader.download_from_manifest("manifest.csv")
+        """
+        ...
+
+    def download_presigned_urls(
+        self,
+        urls_and_paths: Iterable[Tuple[str, str]],
+        progress: Optional[TransferProgress] = None,
+        fail_fast: bool = False,
+        max_failures: Optional[int] = None,
+        raise_on_failure: bool = False,
+    ) -> Results:
+        """
+        Download presigned URLs concurrently with plain, unsigned GETs.
+
+        No credentials are needed: the signature in each URL authorizes its
+        request. Downloads are retried according to the downloader's `RetryPolicy`
+        and reported in `Results` like any other batch, but items are named by
+        their URL without the query string, so signatures never end up in results
+        or logs. Because presigned URLs expire, these results cannot be passed to
+        `retry_failed`.
+
+        Args:
+            urls_and_paths: Tuples of (presigned_url, local_path). Any iterable is
+                           accepted and consumed lazily
+            progress: Optional `TransferProgress` updated while the batch runs
+
+        Example:
+            >>> downloader = S3Downloader("us-east-1")
+            >>> result = downloader.download_presigned_urls(
+            ...     [(url, "./data/a.csv") for url in partner_urls]
+            ... )
+        """
+        ...
+
+    def download_uri(self, uri: str, path_to_store: str) -> str:
+        """
+        Download the object an `s3://bucket/key` URI points to.
+
+        Same as `download_file(bucket, key, path_to_store)`.
+
+        Raises:
+            ValueError: If `uri` is not a valid `s3://bucket/key` URI
+            RobinzhonError: As `download_file`
+
+        Example:
+            >>> downloader.download_uri("s3://my-bucket/data/file.csv", "./file.csv")
+        """
+        ...
+
+    def download_multiple_uris(
+        self,
+        uris: Iterable[str],
+        base_directory: str,
+        progress: Optional[TransferProgress] = None,
+        overwrite: OverwritePolicy = "always",
+        preserve_structure: bool = False,
+        fail_fast: bool = False,
+        max_failures: Optional[int] = None,
+        raise_on_failure: bool = False,
+        dry_run: bool = False,
+    ) -> Union[Results, TransferPlan]:
+        """
+        Download objects given as `s3://bucket/key` URIs, which may point to
+        different buckets, into `base_directory`.
+
+        Behaves like `download_multiple_files`, `dry_run` included, with keys
+        replaced by URIs: `preserve_structure` recreates the key path, without
+        the bucket name, and `Results` lists failures by URI. Malformed URIs are reported as failed items
+        rather than raised, so the rest of the batch still runs.
+
+        Example:
+            >>> result = downloader.download_multiple_uris(
+            ...     ["s3://raw-data/2024/a.csv", "s3://archive/b.csv"], "./downloads"
+            ... )
+        """
+        ...
+
+    def download_prefix(
+        self,
+        bucket_name: str,
+        prefix: str,
+        base_directory: str,
+        include: Optional[List[str]] = None,
+        exclude: Optional[List[str]] = None,
+        progress: Optional[TransferProgress] = None,
+        overwrite: OverwritePolicy = "always",
+        fail_fast: bool = False,
+        max_failures: Optional[int] = None,
+        raise_on_failure: bool = False,
+        min_size: Optional[int] = None,
+        max_size: Optional[int] = None,
+        modified_after: Optional[datetime] = None,
+        modified_before: Optional[datetime] = None,
+        key_pattern: Optional[str] = None,
+        checksum_manifest: Optional[str] = None,
+        expected_sha256: Optional[Dict[str, str]] = None,
+        dry_run: bool = False,
+        check_space: bool = False,
+        scheduling: Scheduling = "fifo",
+    ) -> Union[Results, TransferPlan]:
+        """
+        Download every object under `prefix`, recreating the key hierarchy locally.
+
+        Keys are listed first (paginating as needed), then downloaded concurrently
+        like `download_multiple_files_with_paths`. Each file is stored at its key
+        relative to the part of `prefix` up to its last "/", so both "logs/" and
+        "logs/2024" write "logs/2024-01/app.log" as `base_directory/2024-01/app.log`.
+        Keys ending in "/" (folder markers) are skipped, and keys that would
+        resolve outside `base_directory` (through ".." segments) are reported as
+        failed.
+
+        Args:
+            bucket_name: Name of the S3 bucket
+            prefix: Key prefix to download; "" downloads the whole bucket
+            base_directory: Local directory the hierarchy is recreated in
+            include: Glob patterns; only keys matching at least one are downloaded
+            exclude: Glob patterns; keys matching any of them are skipped
+            progress: Optional `TransferProgress` updated while the batch runs
+            overwrite: Policy for files already present; see `download_multiple_files`
+            min_size: Only download objects of at least this many bytes
+            max_size: Only download objects of at most this many bytes
+            modified_after: Only download objects last modified after this time
+            modified_before: Only download objects last modified before this time
+            key_pattern: Regular expression; only keys it matches are downloaded.
+                        See `list_objects`
+            checksum_manifest: Path of a `SHA256SUMS` file to write once the batch
+                              is done; see `download_multiple_files`
+            expected_sha256: SHA-256 each key's file must have, by key; see
+                            `download_multiple_files`
+            dry_run: Only list the objects and return the `TransferPlan` of
+                    their downloads, judging `overwrite` by the listing
+            check_space: Check that the listed objects fit in the free space
+                        of `base_directory` before downloading any; see
+                        `download_multiple_files`. No extra request is sent
+            scheduling: Order to start the downloads in, by their listed size;
+                       see `Scheduling`. Default = "fifo", in key order
+
+        Patterns are matched against the relative path each key is stored at, and
+        `*` also matches "/": `*.parquet` selects Parquet files at any depth, and
+        `**/tmp/*` anything inside a `tmp` directory.
+
+        Returns:
+            A `Results` instance; it can be passed to `retry_failed`. With
+            `dry_run`, a `TransferPlan`
+
+        Raises:
+            ValueError: If a glob or key pattern, `overwrite`, or the size or time
+                       bounds are invalid
+            RobinzhonError: If the listing fails (`BucketNotFoundError`,
+                           `AccessDeniedError`...)
+            InsufficientSpaceError: If `check_space` is set and the objects
+                                   don't fit
+            KeyboardInterrupt: If interrupted with Ctrl-C; see `download_multiple_files`
+            BatchAbortedError: If the batch stopped at its `fail_fast` or
+                              `max_failures` limit; see `download_multiple_files`
+
+        Example:
+            >>> result = downloader.download_prefix("my-bucket", "datasets/v2/", "./v2")
+            >>> result = downloader.download_prefix(
+            ...     "my-bucket", "datasets/v2/", "./v2",
+            ...     include=["*.parquet"], exclude=["**/tmp/*"],
+            ... )
+            >>> # Resuming: only fetch what's missing or incomplete
+            >>> result = downloader.download_prefix(
+            ...     "my-bucket", "datasets/v2/", "./v2", overwrite="if_size_differs"
+            ... )
+            >>> # Only last week's non-empty logs
+            >>> result = downloader.download_prefix(
+            ...     "my-bucket", "logs/", "./logs",
+            ...     min_size=1, modified_after=datetime.now() - timedelta(days=7),
+            ... )
+        """
+        ...
+
+    def download_from_inventory(
+        self,
+        manifest_uri: str,
+        base_directory: str,
+        prefix: str = "",
+        include: Optional[List[str]] = None,
+        exclude: Optional[List[str]] = None,
+        progress: Optional[TransferProgress] = None,
+        overwrite: OverwritePolicy = "always",
+        fail_fast: bool = False,
+        max_failures: Optional[int] = None,
+        raise_on_failure: bool = False,
+        min_size: Optional[int] = None,
+        max_size: Optional[int] = None,
+        modified_after: Optional[datetime] = None,
+        modified_before: Optional[datetime] = None,
+        key_pattern: Optional[str] = None,
+    ) -> Results:
+        """
+        Download the objects an S3 Inventory report lists, without listing the
+        bucket.
+
+        Listing a bucket of billions of objects takes days; its inventory report
+        lists them all in a few data files. The report's `manifest.json` is read
+        first, then its data files one after another, each parsed in Rust while
+        the objects of the previous one download, so the report is never held in
+        memory whole. Objects are downloaded from the bucket the report lists,
+        selected and laid out below `base_directory` as `download_prefix` does.
+        Delete markers and noncurrent versions of versioned reports are skipped.
+
+        Args:
+            manifest_uri: `s3://` URI of the report's `manifest.json`
+            base_directory: Local directory the hierarchy is recreated in
+            prefix: Only download keys under this prefix; "" downloads every
+                   object of the report
+            include: Glob patterns; see `download_prefix`
+            exclude: Glob patterns; see `download_prefix`
+            progress: Optional `TransferProgress` updated while the batch runs
+            overwrite: Policy for files already present; see `download_multiple_files`
+            min_size: Only download objects of at least this many bytes
+            max_size: Only download objects of at most this many bytes
+            modified_after: Only download objects last modified after this time
+            modified_before: Only download objects last modified before this time
+            key_pattern: Regular expression; only keys it matches are downloaded
+
+        The size and time filters use the report's `Size` and `LastModifiedDate`
+        fields, so they only apply when the inventory was configured with them.
+
+        Returns:
+            A `Results` instance; it can be passed to `retry_failed`
+
+        Raises:
+            ValueError: If `manifest_uri` is not an `s3://bucket/key` URI, or a
+                       filter is invalid
+            RobinzhonError: If the manifest can't be downloaded or read, names a
+                           format other than CSV or Parquet, or a data file can't
+                           be read. A data file failing stops the batch once the
+                           downloads already started are done
+
+        Example:
+            >>> result = downloader.download_from_inventory(
+            ...     "s3://inventories/my-bucket/daily/2024-06-01T01-00Z/manifest.json",
+            ...     "./restore",
+            ...     prefix="datasets/",
+            ...     include=["*.parquet"],
+            ... )
+        """
+        ...
+
+    def download_latest(
+        self,
+        bucket_name: str,
+        prefix: str,
+        n: int,
+        base_directory: str,
+        include: Optional[List[str]] = None,
+        exclude: Optional[List[str]] = None,
+        progress: Optional[TransferProgress] = None,
+        overwrite: OverwritePolicy = "always",
+        fail_fast: bool = False,
+        max_failures: Optional[int] = None,
+        raise_on_failure: bool = False,
+        dry_run: bool = False,
+        check_space: bool = False,
+        scheduling: Scheduling = "fifo",
+    ) -> Union[Results, TransferPlan]:
+        """
+        Download the `n` most recently modified objects under `prefix`.
+
+        The prefix is listed and sorted by LastModified in Rust, then only the
+        newest `n` objects are downloaded, stored as in `download_prefix`. Objects
+        modified at the same time are taken in key order. `include` and `exclude`
+        are applied before picking the newest, so `include=["*.tar.gz"]` gets the
+        latest archives even when other files were written after them.
+
+        Args:
+            bucket_name: Name of the S3 bucket
+            prefix: Key prefix to pick the objects from
+            n: How many objects to download; fewer when the prefix holds fewer
+            base_directory: Local directory the objects are stored below
+            include: Glob patterns; only keys matching at least one are considered
+            exclude: Glob patterns; keys matching any of them are ignored
+            progress: Optional `TransferProgress` updated while the batch runs
+            overwrite: Policy for files already present; see `download_multiple_files`
+            dry_run: Only return the `TransferPlan` of the downloads; see
+                    `download_prefix`
+            check_space: Check that the objects fit in the free space of
+                        `base_directory` first; see `download_prefix`
+            scheduling: Order to start the downloads in once the newest `n`
+                       are picked; see `download_prefix`. Default = "fifo",
+                       newest first
+
+        Returns:
+            A `Results` instance listing the objects newest first, unless
+            `scheduling` orders them otherwise; it can be passed to
+            `retry_failed`. With `dry_run`, a `TransferPlan`
+
+        Raises:
+            ValueError: If `n` is zero, or a glob pattern or `overwrite` is invalid
+            RobinzhonError: If the listing fails (`BucketNotFoundError`,
+                           `AccessDeniedError`...)
+            InsufficientSpaceError: If `check_space` is set and the objects
+                                   don't fit
+            BatchAbortedError: If the batch stopped at its `fail_fast` or
+                              `max_failures` limit; see `download_multiple_files`
+
+        Example:
+            >>> # The latest nightly backup
+            >>> result = downloader.download_latest("my-bucket", "backups/", 1, "./restore")
+            >>> # The last three partitions of a dataset
+            >>> result = downloader.download_latest(
+            ...     "my-bucket", "events/", 3, "./events", include=["*.parquet"]
+            ... )
+        """
+        ...
+
+    def sync_down(
+        self,
+        bucket_name: str,
+        prefix: str,
+        local_dir: str,
+        delete: bool = False,
+        include: Optional[List[str]] = None,
+        exclude: Optional[List[str]] = None,
+        progress: Optional[TransferProgress] = None,
+        fail_fast: bool = False,
+        max_failures: Optional[int] = None,
+        raise_on_failure: bool = False,
+        dry_run: bool = False,
+        check_space: bool = False,
+        scheduling: Scheduling = "fifo",
+    ) -> Union[SyncReport, TransferPlan]:
+        """
+        Bring `local_dir` up to date with the objects under `prefix`, rsync-style.
+
+        Objects are mapped to local paths as in `download_prefix`. Only objects
+        whose local copy is missing or outdated are downloaded:
+
+        - Files recorded by a previous sync and unchanged locally since (same size
+          and modification time) are compared by ETag.
+        - Other files are current if they have the object's size and are at least
+          as recent as it.
+
+        The record is kept in a `.robinzhon-sync.json` file at the root of
+        `local_dir`; deleting it only makes the next sync rely on size and time.
+
+        Args:
+            bucket_name: Name of the S3 bucket
+            prefix: Key prefix to mirror
+            local_dir: Local directory to bring up to date
+            delete: Also delete local files that have no matching object
+            include: Glob patterns selecting the paths to sync, as in `download_prefix`
+            exclude: Glob patterns of paths to leave alone, locally and remotely
+            progress: Optional `TransferProgress` updated while the downloads run
+            dry_run: Only compare and return the `TransferPlan` of the sync,
+                    its `deletions` being the local files `delete` would remove
+            check_space: Check that the objects to download fit in the free
+                        space of `local_dir` before the sync changes anything;
+                        see `download_prefix`. Files left to delete aren't
+                        counted as freed
+            scheduling: Order to start the downloads in, by their listed size;
+                       see `Scheduling`. Default = "fifo"
+
+        Returns:
+            A `SyncReport` of the downloads, skipped keys and deleted files. With
+            `dry_run`, a `TransferPlan`
+
+        Raises:
+            ValueError: If a glob pattern is invalid
+            RobinzhonError: If the listing fails or `local_dir` cannot be read
+            InsufficientSpaceError: If `check_space` is set and the downloads
+                                   don't fit
+            KeyboardInterrupt: If interrupted with Ctrl-C; see `download_multiple_files`
+            BatchAbortedError: If the batch stopped at its `fail_fast` or
+                              `max_failures` limit; see `download_multiple_files`
+
+        Example:
+            >>> report = downloader.sync_down("my-bucket", "site/", "./site", delete=True)
+            >>> print(len(report.results.successful), "updated,", len(report.skipped), "unchanged")
+        """
+        ...
+
+    def verify_against_manifest(self, manifest_path: str) -> Results:
+        """
+        Check the files a `SHA256SUMS` manifest lists against their digests.
+
+        The manifest may come from `checksum_manifest` or from `sha256sum`; its
+        relative paths are taken from the current directory, as `sha256sum -c`
+        does. Only local files are read.
+
+        Args:
+            manifest_path: Path of the manifest
+
+        Returns:
+            A `Results` listing, by path, the files that match as successful and
+            those that don't, or can't be read, as failed. It can't be passed to
+            `retry_failed`
+
+        Raises:
+            ValueError: If a line of the manifest isn't `<sha256>  <path>`
+            RobinzhonError: If the manifest can't be read
+
+        Example:
+            >>> downloader.download_prefix(
+            ...     "my-bucket", "datasets/v2/", "./v2", checksum_manifest="v2.sha256"
+            ... )
+            >>> # Later, or on another machine the files were copied to
+            >>> check = downloader.verify_against_manifest("v2.sha256")
+            >>> for item in check.failed:
+            ...     print(item.key, item.error_message)
+        """
+        ...
+
+    def retry_failed(
+        self,
+        results: Results,
+        progress: Optional[TransferProgress] = None,
+        fail_fast: bool = False,
+        max_failures: Optional[int] = None,
+        raise_on_failure: bool = False,
+    ) -> Results:
+        """
+        Download the failed items of a previous batch again.
+
+        Each failed key is fetched to the local path it was originally headed for.
+        The outcome is merged into `results`: new successes are appended to
+        `successful` and `failed` is replaced by the items that still failed.
+
+        Args:
+            results: `Results` returned by one of this class's batch methods
+            progress: Optional `TransferProgress` updated while the retry runs
+
+        Returns:
+            The same `results` object, updated in place
+
+        Raises:
+            ValueError: If `results` did not come from an `S3Downloader` batch
+
+        Example:
+            >>> result = downloader.download_multiple_files("my-bucket", keys, "./data")
+            >>> while result.has_failures():
+            ...     downloader.retry_failed(result)
+        """
+        ...
+
+    def download_bytes(self, bucket_name: str, object_key: str) -> bytes:
+        """
+        Download an object into memory without touching the filesystem.
+
+        Args:
+            bucket_name: Name of the S3 bucket
+            object_key: S3 object key
+
+        Returns:
+            The object contents
+
+        Raises:
+            NoSuchKeyError: If the object does not exist
+            RobinzhonError: If the download fails for any other reason
+
+        Example:
+            >>> data = downloader.download_bytes("my-bucket", "config/app.json")
+            >>> config = json.loads(data)
+        """
+        ...
+
+    def download_multiple_bytes(
+        self,
+        bucket_name: str,
+        object_keys: Iterable[str],
+        progress: Optional[TransferProgress] = None,
+        fail_fast: bool = False,
+        max_failures: Optional[int] = None,
+    ) -> Dict[str, bytes]:
+        """
+        Download several objects into memory concurrently.
+
+        Args:
+            bucket_name: Name of the S3 bucket
+            object_keys: S3 object keys to fetch: a list, or any iterable such as a
+                        generator, consumed lazily as the batch has room for
+                        more downloads
+            progress: Optional `TransferProgress` updated while the batch runs
+            fail_fast: Stop the batch at its first failed key; the same as
+                      `max_failures=1`
+            max_failures: Stop the batch once this many keys have failed. No new
+                         downloads are started; the ones in flight finish
+
+        Returns:
+            A dict mapping each successfully downloaded key to its contents. Keys
+            that failed are logged as warnings and left out of the dict.
+
+        Raises:
+            ValueError: If `max_failures` is 0 or `fail_fast` is combined with
+                       another `max_failures`
+            BatchAbortedError: If the batch stopped at its `fail_fast` or
+                              `max_failures` limit. The contents downloaded
+                              before it stopped are not returned
+
+        Example:
+            >>> contents = downloader.download_multiple_bytes(
+            ...     "my-bucket", ["data/a.json", "data/b.json"]
+            ... )
+            >>> missing = set(keys) - contents.keys()
+        """
+        ...
+
+    def head(self, bucket_name: str, object_key: str) -> ObjectMetadata:
+        """
+        Fetch the metadata of an object without downloading it.
+
+        Raises:
+            NoSuchKeyError: If the object does not exist
+            AccessDeniedError: If the credentials may not read the object
+            RobinzhonError: If the request fails for any other reason
+        """
+        ...
+
+    def exists(self, bucket_name: str, object_key: str) -> bool:
+        """
+        Whether an object exists, checked with a single HEAD request.
+
+        Returns False when S3 answers 404, which a missing bucket does too. Any
+        other failure, such as access denied, is raised.
+        """
+        ...
+
+    def head_multiple(
+        self,
+        bucket_name: str,
+        object_keys: Iterable[str],
+        progress: Optional[TransferProgress] = None,
+        fail_fast: bool = False,
+        max_failures: Optional[int] = None,
+    ) -> Dict[str, ObjectMetadata]:
+        """
+        Fetch the metadata of several objects concurrently, for instance to
+        estimate the size of a batch before running it.
+
+        Args:
+            bucket_name: Name of the S3 bucket
+            object_keys: S3 object keys to describe; any iterable, generators
+                        included, is accepted and consumed lazily
+            progress: Optional `TransferProgress` updated while the batch runs
+            fail_fast: Stop the batch at the first key that can't be described;
+                      the same as `max_failures=1`
+            max_failures: Stop the batch once this many keys couldn't be
+                         described. No new requests are sent; the ones in flight
+                         finish
+
+        Returns:
+            A dict mapping each key to its metadata. Keys that could not be
+            described, missing ones included, are logged as warnings and left out.
+
+        Raises:
+            ValueError: If `max_failures` is 0 or `fail_fast` is combined with
+                       another `max_failures`
+            BatchAbortedError: If the batch stopped at its `fail_fast` or
+                              `max_failures` limit. The metadata fetched before
+                              it stopped is not returned
+
+        Example:
+            >>> metadata = downloader.head_multiple("my-bucket", keys)
+            >>> total_bytes = sum(m.size for m in metadata.values())
+        """
+        ...
+
+    def restore_objects(
+        self,
+        bucket_name: str,
+        object_keys: Iterable[str],
+        tier: RestoreTier = "standard",
+        days: int = 1,
+        progress: Optional[TransferProgress] = None,
+        fail_fast: bool = False,
+        max_failures: Optional[int] = None,
+    ) -> Results:
+        """
+        Request temporary copies of objects archived in GLACIER or DEEP_ARCHIVE,
+        so they can be downloaded.
+
+        Restores take minutes to hours depending on `tier`; use
+        `wait_for_restore` to block until they are done.
+
+        Args:
+            bucket_name: Name of the S3 bucket
+            object_keys: Keys of the archived objects; any iterable, generators
+                        included, is accepted and consumed lazily
+            tier: Restore speed (default="standard")
+            days: Days the restored copies stay readable (default=1)
+            progress: Optional `TransferProgress` updated while requests are sent
+            fail_fast: Stop the batch at its first failed restore request; the
+                      same as `max_failures=1`
+            max_failures: Stop the batch once this many restore requests have
+                         failed. No new requests are sent; the ones in flight
+                         finish
+
+        Returns:
+            A `Results` instance: keys whose restore was requested are in
+            `successful`, keys already being restored in `skipped`. These results
+            can't be passed to `retry_failed`.
+
+        Raises:
+            ValueError: If `tier` is unknown, `days` is less than 1, `max_failures`
+                       is 0 or `fail_fast` is combined with another `max_failures`
+            BatchAbortedError: If the batch stopped at its `fail_fast` or
+                              `max_failures` limit
+        """
+        ...
+
+    def wait_for_restore(
+        self,
+        bucket_name: str,
+        object_keys: List[str],
+        poll_interval: float = 60.0,
+        timeout: Optional[float] = None,
+    ) -> None:
+        """
+        Block until the restores of the objects have completed, checking them with
+        HEAD requests every `poll_interval` seconds. Objects that are not archived
+        count as restored.
+
+        Raises:
+            TimeoutError: If restores are still running after `timeout` seconds
+            RobinzhonError: If an object is archived and no restore was requested
+                           for it, or a HEAD request fails
+            KeyboardInterrupt: If interrupted with Ctrl-C
+
+        Example:
+            >>> keys = ["logs/2019-01.gz", "logs/2019-02.gz"]
+            >>> downloader.restore_objects("archive", keys, tier="bulk", days=3)
+            >>> downloader.wait_for_restore("archive", keys, poll_interval=600)
+            >>> downloader.download_multiple_files("archive", keys, "./logs")
+        """
+        ...
+
+    def list_object_versions(
+        self, bucket_name: str, prefix: Optional[str] = None
+    ) -> List[ObjectVersion]:
+        """
+        List every version and delete marker of the objects of a versioned
+        bucket.
+
+        Args:
+            bucket_name: Name of the S3 bucket
+            prefix: Only list keys starting with this prefix
+
+        Returns:
+            The versions, ordered by key and, within a key, newest first
+
+        Raises:
+            BucketNotFoundError: If the bucket does not exist
+            AccessDeniedError: If the credentials may not list versions
+            RobinzhonError: If a page request fails for any other reason
+
+        Example:
+            Restore a prefix as it was at a point in time:
+
+            >>> latest = {}
+            >>> for version in downloader.list_object_versions("my-bucket", "data/"):
+            ...     if version.last_modified <= restore_point:
+            ...         latest.setdefault(version.key, version)
+            >>> downloader.download_multiple_files_with_paths(
+            ...     "my-bucket",
+            ...     [
+            ...         (v.key, f"./restore/{v.key}", v.version_id)
+            ...         for v in latest.values()
+            ...         if not v.is_delete_marker
+            ...     ],
+            ... )
+        """
+        ...
+
+    def list_objects(
+        self,
+        bucket_name: str,
+        prefix: Optional[str] = None,
+        delimiter: Optional[str] = None,
+        min_size: Optional[int] = None,
+        max_size: Optional[int] = None,
+        modified_after: Optional[datetime] = None,
+        modified_before: Optional[datetime] = None,
+        key_pattern: Optional[str] = None,
+    ) -> ObjectListing:
+        """
+        List the objects of a bucket.
+
+        Args:
+            bucket_name: Name of the S3 bucket
+            prefix: Only list keys starting with this prefix
+            delimiter: Group keys sharing a prefix up to this delimiter (usually
+                      "/") into a single `is_prefix` entry, like a directory
+            min_size: Only list objects of at least this many bytes
+            max_size: Only list objects of at most this many bytes
+            modified_after: Only list objects last modified after this time
+            modified_before: Only list objects last modified before this time
+            key_pattern: Regular expression (Rust `regex` syntax) searched for
+                        anywhere in the key; anchor it with "^" to match from
+                        the start
+
+        Filters are evaluated in Rust as each page arrives, so objects that fail
+        them never reach Python. A `key_pattern` anchored with "^" and starting
+        with literal text also narrows the listing itself: `"^logs/2024-.*\\.gz$"`
+        only lists keys under "logs/2024-". Common prefixes are always kept.
+
+        Returns:
+            A lazy `ObjectListing` iterator of `ObjectInfo`. No request is made
+            until it is first advanced
+
+        Raises:
+            ValueError: If `key_pattern` is not a valid regular expression,
+                       `min_size` is larger than `max_size`, or `modified_after`
+                       isn't earlier than `modified_before`
+
+        Example:
+            >>> for obj in downloader.list_objects("my-bucket", prefix="logs/2024/"):
+            ...     print(obj.key, obj.size, obj.last_modified)
+            >>> folders = [
+            ...     entry.key
+            ...     for entry in downloader.list_objects("my-bucket", "data/", "/")
+            ...     if entry.is_prefix
+            ... ]
+            >>> large = downloader.list_objects(
+            ...     "my-bucket", "exports/", min_size=100 * 1024 * 1024,
+            ...     key_pattern=r"\\.parquet$",
+            ... )
+        """
+        ...
+
+    def stream(
+        self, bucket_name: str, object_key: str, chunk_size: int = 1024 * 1024
+    ) -> ObjectStream:
+        """
+        Iterate over an object's contents as `bytes` chunks as they arrive.
+
+        Args:
+            bucket_name: Name of the S3 bucket
+            object_key: S3 object key
+            chunk_size: Size of each yielded chunk; only the last one may be
+                       shorter. Default = 1 MiB
+
+        Returns:
+            An `ObjectStream` iterator. Errors, such as a missing key, are raised
+            from the first call to `next()`
+
+        Raises:
+            ValueError: If `chunk_size` is zero
+
+        Example:
+            >>> buffer = b""
+            >>> for chunk in downloader.stream("my-bucket", "data/huge.csv"):
+            ...     buffer += chunk
+            ...     *lines, buffer = buffer.split(b"\n")
+            ...     for line in lines:
+            ...         handle(line)
+        """
+        ...
+
+    def select(
+        self,
+        bucket_name: str,
+        object_key: str,
+        sql: str,
+        input_format: SelectInputFormat = "csv",
+        output_format: Literal["csv", "json"] = "json",
+        compression: Optional[Literal["gzip", "bzip2"]] = None,
+        csv_header: bool = True,
+        rows: bool = False,
+    ) -> ObjectStream:
+        """
+        Run an S3 Select query over an object and iterate over the records it
+        selects as S3 filters them, so pulling a few columns out of a huge CSV
+        or JSON object only transfers those.
+
+        Args:
+            bucket_name: Name of the S3 bucket
+            object_key: S3 object key
+            sql: The query, e.g. `SELECT s.id, s.total FROM S3Object s WHERE
+                s.total > '100'`
+            input_format: How the object is stored (see `SelectInputFormat`).
+                         Default = "csv"
+            output_format: "json" for one JSON object per record, "csv" for one
+                          CSV line per record. Default = "json"
+            compression: "gzip" or "bzip2" for a compressed CSV or JSON object
+            csv_header: The first line of a CSV object names its columns, which
+                       the query can then refer to. Default = True
+            rows: Yield one record per item, without its newline, instead of
+                 the chunks of records as S3 sends them. Default = False
+
+        Returns:
+            An `ObjectStream` iterator of `bytes`. Errors, such as a missing key
+            or an invalid query, are raised from the first call to `next()`
+
+        Raises:
+            ValueError: If `input_format`, `output_format` or `compression` is
+                       unknown
+
+        Note:
+            A failed request is retried under the downloader's `retry_policy`;
+            an error while the records are streaming ends the iterator with it,
+            as the query can't be resumed. With `rows`, CSV records holding
+            quoted newlines are split at them.
+
+        Example:
+            >>> import json
+            >>> records = downloader.select(
+            ...     "my-bucket", "logs/huge.csv.gz",
+            ...     "SELECT s.user_id FROM S3Object s WHERE s.status = '500'",
+            ...     compression="gzip", rows=True,
+            ... )
+            >>> user_ids = {json.loads(row)["user_id"] for row in records}
+        """
+        ...
+
+    def open(
+        self, bucket_name: str, object_key: str, buffer_size: int = 1024 * 1024
+    ) -> S3File:
+        """
+        Open an object as a read-only, seekable file object.
+
+        Args:
+            bucket_name: Name of the S3 bucket
+            object_key: S3 object key
+            buffer_size: Minimum number of bytes fetched per request. Default = 1 MiB
+
+        Returns:
+            An `S3File` positioned at the start of the object
+
+        Raises:
+            ValueError: If `buffer_size` is zero
+            NoSuchKeyError: If the object does not exist
+            RobinzhonError: If the request fails for another reason
+
+        Example:
+            >>> with downloader.open("my-bucket", "data/table.parquet") as f:
+            ...     f.seek(-8, 2)
+            ...     footer = f.read(8)
+        """
+        ...
+
+    async def download_file_async(
+        self,
+        bucket_name: str,
+        object_key: str,
+        path_to_store: str,
+        version_id: Optional[str] = None,
+        if_none_match: Optional[str] = None,
+        if_modified_since: Optional[datetime] = None,
+    ) -> Optional[str]:
+        """
+        Awaitable variant of `download_file`.
+
+        The transfer runs on the embedded Tokio runtime, so the asyncio event loop
+        stays free while the download is in progress.
+
+        Example:
+            >>> path = await downloader.download_file_async(
+            ...     "my-bucket", "data/file.csv", "./downloads/file.csv"
+            ... )
+        """
+        ...
+
+    async def download_range_async(
+        self,
+        bucket_name: str,
+        object_key: str,
+        path_to_store: str,
+        offset: int,
+        length: Optional[int] = None,
+        version_id: Optional[str] = None,
+    ) -> str:
+        """
+        Awaitable variant of `download_range`.
+        """
+        ...
+
+    async def download_ranges_async(
+        self,
+        bucket_name: str,
+        object_key: str,
+        ranges: List[ByteRange],
+        path_to_store: Optional[str] = None,
+        version_id: Optional[str] = None,
+    ) -> Union[str, List[bytes]]:
+        """
+        Awaitable variant of `download_ranges`.
+        """
+        ...
+
+    async def download_and_extract_async(
+        self,
+        bucket_name: str,
+        object_key: str,
+        dest_dir: str,
+        format: ArchiveFormat = "tar.gz",
+    ) -> Results:
+        """
+        Awaitable variant of `download_and_extract`.
+        """
+        ...
+
+    async def download_archived_async(
+        self,
+        bucket_name: str,
+        manifest_key: str,
+        dest_dir: str,
+    ) -> Results:
+        """
+        Awaitable variant of `download_archived`.
+        """
+        ...
+
+    async def download_multiple_files_async(
+        self,
+        bucket_name: str,
+        object_keys: Iterable[str],
+        base_directory: str,
+        progress: Optional[TransferProgress] = None,
+        overwrite: OverwritePolicy = "always",
+        preserve_structure: bool = False,
+        fail_fast: bool = False,
+        max_failures: Optional[int] = None,
+        raise_on_failure: bool = False,
+        checksum_manifest: Optional[str] = None,
+        expected_sha256: Optional[Dict[str, str]] = None,
+        check_space: bool = False,
+        scheduling: Scheduling = "fifo",
+        dry_run: bool = False,
+    ) -> Union[Results, TransferPlan]:
+        """
+        Awaitable variant of `download_multiple_files`.
+
+        Example:
+            >>> result = await downloader.download_multiple_files_async(
+            ...     "my-bucket", ["data/a.csv", "data/b.csv"], "./downloads"
+            ... )
+        """
+        ...
+
+    async def download_multiple_files_with_paths_async(
+        self,
+        bucket_name: str,
+        downloads: Iterable[DownloadItem],
+        progress: Optional[TransferProgress] = None,
+        overwrite: OverwritePolicy = "always",
+        if_none_match: Optional[Dict[str, str]] = None,
+        if_modified_since: Optional[datetime] = None,
+        fail_fast: bool = False,
+        max_failures: Optional[int] = None,
+        raise_on_failure: bool = False,
+        checksum_manifest: Optional[str] = None,
+        expected_sha256: Optional[Dict[str, str]] = None,
+        dry_run: bool = False,
+    ) -> Union[Results, TransferPlan]:
+        """
+        Awaitable variant of `download_multiple_files_with_paths`.
+        """
+        ...
+
+    async def download_multiple_objects_async(
+        self,
+        items: Iterable[Tuple[str, str, str]],
+        progress: Optional[TransferProgress] = None,
+        overwrite: OverwritePolicy = "always",
+        fail_fast: bool = False,
+        max_failures: Optional[int] = None,
+        raise_on_failure: bool = False,
+        dry_run: bool = False,
+    ) -> Union[Results, TransferPlan]:
+        """
+        Awaitable variant of `download_multiple_objects`.
+        """
+        ...
+
+    async def download_presigned_urls_async(
+        self,
+        urls_and_paths: Iterable[Tuple[str, str]],
+        progress: Optional[TransferProgress] = None,
+        fail_fast: bool = False,
+        max_failures: Optional[int] = None,
+        raise_on_failure: bool = False,
+    ) -> Results:
+        """
+        Awaitable variant of `download_presigned_urls`.
+        """
+        ...
+
+    async def download_uri_async(self, uri: str, path_to_store: str) -> str:
+        """
+        Awaitable variant of `download_uri`.
+        """
+        ...
+
+    async def download_multiple_uris_async(
+        self,
+        uris: Iterable[str],
+        base_directory: str,
+        progress: Optional[TransferProgress] = None,
+        overwrite: OverwritePolicy = "always",
+        preserve_structure: bool = False,
+        fail_fast: bool = False,
+        max_failures: Optional[int] = None,
+        raise_on_failure: bool = False,
+        dry_run: bool = False,
+    ) -> Union[Results, TransferPlan]:
+        """
+        Awaitable variant of `download_multiple_uris`.
+        """
+        ...
+
+    async def download_prefix_async(
+        self,
+        bucket_name: str,
+        prefix: str,
+        base_directory: str,
+        include: Optional[List[str]] = None,
+        exclude: Optional[List[str]] = None,
+        progress: Optional[TransferProgress] = None,
+        overwrite: OverwritePolicy = "always",
+        fail_fast: bool = False,
+        max_failures: Optional[int] = None,
+        raise_on_failure: bool = False,
+        min_size: Optional[int] = None,
+        max_size: Optional[int] = None,
+        modified_after: Optional[datetime] = None,
+        modified_before: Optional[datetime] = None,
+        key_pattern: Optional[str] = None,
+        checksum_manifest: Optional[str] = None,
+        expected_sha256: Optional[Dict[str, str]] = None,
+        dry_run: bool = False,
+        check_space: bool = False,
+        scheduling: Scheduling = "fifo",
+    ) -> Union[Results, TransferPlan]:
+        """
+        Awaitable variant of `download_prefix`.
+        """
+        ...
+
+    async def download_latest_async(
+        self,
+        bucket_name: str,
+        prefix: str,
+        n: int,
+        base_directory: str,
+        include: Optional[List[str]] = None,
+        exclude: Optional[List[str]] = None,
+        progress: Optional[TransferProgress] = None,
+        overwrite: OverwritePolicy = "always",
+        fail_fast: bool = False,
+        max_failures: Optional[int] = None,
+        raise_on_failure: bool = False,
+        dry_run: bool = False,
+        check_space: bool = False,
+        scheduling: Scheduling = "fifo",
+    ) -> Union[Results, TransferPlan]:
+        """
+        Awaitable variant of `download_latest`.
+        """
+        ...
+
+    async def sync_down_async(
+        self,
+        bucket_name: str,
+        prefix: str,
+        local_dir: str,
+        delete: bool = False,
+        include: Optional[List[str]] = None,
+        exclude: Optional[List[str]] = None,
+        progress: Optional[TransferProgress] = None,
+        fail_fast: bool = False,
+        max_failures: Optional[int] = None,
+        raise_on_failure: bool = False,
+        dry_run: bool = False,
+        check_space: bool = False,
+        scheduling: Scheduling = "fifo",
+    ) -> Union[SyncReport, TransferPlan]:
+        """
+        Awaitable variant of `sync_down`.
+        """
+        ...
+
+    async def verify_against_manifest_async(self, manifest_path: str) -> Results:
+        """
+        Awaitable variant of `verify_against_manifest`.
+        """
+        ...
+
+    async def download_bytes_async(self, bucket_name: str, object_key: str) -> bytes:
+        """
+        Awaitable variant of `download_bytes`.
+        """
+        ...
+
+    async def download_multiple_bytes_async(
+        self,
+        bucket_name: str,
+        object_keys: Iterable[str],
+        progress: Optional[TransferProgress] = None,
+        fail_fast: bool = False,
+        max_failures: Optional[int] = None,
+    ) -> Dict[str, bytes]:
+        """
+        Awaitable variant of `download_multiple_bytes`.
+        """
+        ...
+
+    async def head_async(self, bucket_name: str, object_key: str) -> ObjectMetadata:
+        """
+        Awaitable variant of `head`.
+        """
+        ...
+
+    async def exists_async(self, bucket_name: str, object_key: str) -> bool:
+        """
+        Awaitable variant of `exists`.
+        """
+        ...
+
+    async def head_multiple_async(
+        self,
+        bucket_name: str,
+        object_keys: Iterable[str],
+        progress: Optional[TransferProgress] = None,
+        fail_fast: bool = False,
+        max_failures: Optional[int] = None,
+    ) -> Dict[str, ObjectMetadata]:
+        """
+        Awaitable variant of `head_multiple`.
+        """
+        ...
+
+    async def restore_objects_async(
+        self,
+        bucket_name: str,
+        object_keys: Iterable[str],
+        tier: RestoreTier = "standard",
+        days: int = 1,
+        progress: Optional[TransferProgress] = None,
+        fail_fast: bool = False,
+        max_failures: Optional[int] = None,
+    ) -> Results:
+        """
+        Awaitable variant of `restore_objects`.
+        """
+        ...
+
+    async def wait_for_restore_async(
+        self,
+        bucket_name: str,
+        object_keys: List[str],
+        poll_interval: float = 60.0,
+        timeout: Optional[float] = None,
+    ) -> None:
+        """
+        Awaitable variant of `wait_for_restore`.
+        """
+        ...
+
+class UploadOptions:
+    """
+    User metadata and headers stored with uploaded objects.
+
+    Unless `content_type` is given, the Content-Type is guessed from the key's
+    extension ("report.csv" is uploaded as "text/csv"); pass
+    `guess_content_type=False` to leave it to S3. Keys S3 can't guess end up as
+    "binary/octet-stream".
+
+    Example:
+        >>> options = UploadOptions(
+        ...     metadata={"owner": "data-team"},
+        ...     cache_control="public, max-age=3600",
+        ... )
+        >>> uploader.upload_multiple_files("my-bucket", [
+        ...     ("./site/index.html", "index.html"),
+        ...     ("./site/app.js.gz", "app.js", UploadOptions(
+        ...         content_type="text/javascript", content_encoding="gzip"
+        ...     )),
+        ... ], options=options)
+    """
+
+    def __init__(
+        self,
+        metadata: Optional[Dict[str, str]] = None,
+        content_type: Optional[str] = None,
+        content_encoding: Optional[str] = None,
+        content_disposition: Optional[str] = None,
+        cache_control: Optional[str] = None,
+        storage_class: Optional[str] = None,
+        sse: Optional[str] = None,
+        kms_key_id: Optional[str] = None,
+        acl: Optional[str] = None,
+        expected_bucket_owner: Optional[str] = None,
+        guess_content_type: bool = True,
+        checksum_algorithm: Optional[str] = None,
+        checksum_type: Optional[str] = None,
+    ) -> None:
+        """
+        Args:
+            metadata: User metadata, stored as `x-amz-meta-*` headers
+            content_type: Content-Type header (default=guessed from the key)
+            content_encoding: Content-Encoding header, e.g. "gzip"
+            content_disposition: Content-Disposition header, e.g.
+                                'attachment; filename="report.csv"'
+            cache_control: Cache-Control header, e.g. "max-age=3600"
+            storage_class: Storage class, e.g. "STANDARD_IA", "GLACIER" or
+                          "DEEP_ARCHIVE" (default=the bucket's, usually STANDARD)
+            sse: Server-side encryption, overriding the uploader's: "AES256",
+                "aws:kms" or "aws:kms:dsse"
+            kms_key_id: KMS key ID or ARN to encrypt with; implies "aws:kms"
+            acl: Canned ACL, e.g. "bucket-owner-full-control" when delivering
+                into a bucket of another account
+            expected_bucket_owner: Account ID the bucket must belong to; uploads
+                                  to a bucket of any other account fail with
+                                  `AccessDeniedError`
+            guess_content_type: Guess the Content-Type from the key's extension
+                               when none is given (default=True)
+            checksum_algorithm: Checksum S3 verifies and stores with the object:
+                               "CRC32", "CRC32C", "CRC64NVME", "SHA1" or
+                               "SHA256" (default=the SDK's, CRC32). Results
+                               report the stored value in `checksums`
+            checksum_type: For multipart uploads, "FULL_OBJECT" to checksum the
+                          whole body or "COMPOSITE" to checksum the checksums
+                          of the parts (default=S3's for the algorithm)
+
+        Raises:
+            ValueError: If `storage_class`, `sse`, `acl`, `checksum_algorithm` or
+                       `checksum_type` is not one S3 knows, `kms_key_id` is given
+                       with an `sse` other than KMS, or `checksum_type` without
+                       `checksum_algorithm`
+        """
+        ...
+    @property
+    def metadata(self) -> Optional[Dict[str, str]]: ...
+    @property
+    def content_type(self) -> Optional[str]: ...
+    @property
+    def content_encoding(self) -> Optional[str]: ...
+    @property
+    def content_disposition(self) -> Optional[str]: ...
+    @property
+    def cache_control(self) -> Optional[str]: ...
+    @property
+    def storage_class(self) -> Optional[str]: ...
+    @property
+    def sse(self) -> Optional[str]: ...
+    @property
+    def kms_key_id(self) -> Optional[str]: ...
+    @property
+    def acl(self) -> Optional[str]: ...
+    @property
+    def expected_bucket_owner(self) -> Optional[str]: ...
+    @property
+    def guess_content_type(self) -> bool: ...
+    @property
+    def checksum_algorithm(self) -> Optional[str]: ...
+    @property
+    def checksum_type(self) -> Optional[str]: ...
+    def __repr__(self) -> str: ...
+
+
+class ArchiveMode:
+    """
+    Packs the small files of an `upload_multiple_files` batch into archives,
+    "bundles", so that millions of tiny files take thousands of requests.
+
+    Files up to `max_file_size` bytes are packed, in input order, into bundles
+    holding up to `bundle_size` bytes of files each, uploaded as
+    `{prefix}bundle-00001.tar` and so on. Every file is named by its key inside
+    its bundle. Once the bundles are up, `{prefix}manifest.json` maps every
+    packed key to its bundle:
+
+        {"format": "tar", "members": {"logs/a.json": "batch-7/bundle-00001.tar"}}
+
+    Larger files are uploaded as objects of their own, as without
+    `archive_mode`. `S3Downloader.download_archived` unpacks the bundles of a
+    manifest back into files.
+
+    Attributes:
+        prefix: Key prefix of the bundles and the manifest, e.g. "batch-7/"
+        format: How bundles are packed (see `ArchiveFormat`)
+        max_file_size: Largest file, in bytes, packed into a bundle
+        bundle_size: Bytes of files a bundle holds at most, unless a single
+                    file is larger
+
+    Example:
+        >>> mode = ArchiveMode("bundles/2024-06-01/", format="tar.gz")
+        >>> results = uploader.upload_multiple_files(
+        ...     "my-bucket", paths_and_keys, archive_mode=mode
+        ... )
+    """
+
+    prefix: str
+    format: str
+    max_file_size: int
+    bundle_size: int
+
+    def __init__(
+        self,
+        prefix: str,
+        format: ArchiveFormat = "tar",
+        max_file_size: int = 1024 * 1024,
+        bundle_size: int = 64 * 1024 * 1024,
+    ) -> None:
+        """
+        Build an archive mode.
+
+        Raises:
+            ValueError: If `format` is unknown, `bundle_size` is zero or above
+                       5 GiB, or `max_file_size` is above `bundle_size`
+        """
+        ...
+
+    def __repr__(self) -> str:
+        """Developer representation showing the mode."""
+        ...
+
+
+class ClientEncryption:
+    """
+    Client-side envelope encryption with KMS.
+
+    Every object is encrypted with AES-256-GCM under a data key of its own, which
+    KMS generates under `kms_key_id` and wraps. The wrapped key, the IV and the
+    encryption context are stored in the object's metadata, in the format of the
+    AWS S3 Encryption Client v2, so objects written by one can be read by the
+    other. This is not the message format of the AWS Encryption SDK. S3 only ever
+    sees ciphertext.
+
+    Bodies are encrypted as they are read and decrypted as they arrive, so files
+    of any size are never held in memory. A download is written out before its
+    body authenticates at the end, and the file is removed if it doesn't.
+    Objects without encryption metadata are downloaded as they are, unless
+    `require_encryption` is set.
 
-        Args:
-            bucket_name: Name of the S3 bucket
-            downloads: List of tuples where each tuple contains:
-                      (object_key, local_path) - S3 key and desired local file path
+    KMS requests are signed with the credentials and region of the uploader or
+    downloader the encryption is given to.
 
-        Returns:
-            DownloadResults object with successful and failed download lists
+    Limitations: `sync` and `overwrite="if_size_differs"` compare local files
+    with the encrypted size; `stream`, `open`, ranged reads and presigned URLs
+    return ciphertext.
 
-        Raises:
-            RuntimeError: If directories cannot be created or other system errors
+    Example:
+        >>> encryption = ClientEncryption(
+        ...     "alias/backups", encryption_context={"team": "data"}
+        ... )
+        >>> uploader = S3Uploader("us-east-1", client_encryption=encryption)
+        >>> downloader = S3Downloader("us-east-1", client_encryption=encryption)
+    """
 
-        Note:
-            Directories are automatically created as needed for each file path.
-            Performance is optimized for I/O-bound operations with concurrent execution.
-            Failed downloads are logged as warnings and available in result.failed.
+    def __init__(
+        self,
+        kms_key_id: Optional[str] = None,
+        encryption_context: Optional[Dict[str, str]] = None,
+        kms_endpoint_url: Optional[str] = None,
+        require_encryption: bool = False,
+    ) -> None:
+        """
+        Args:
+            kms_key_id: KMS key ID, ARN or alias data keys are generated under.
+                       Required to upload; downloads read the key off the object
+            encryption_context: Extra context the data keys are bound to, stored
+                               in the clear with each object
+            kms_endpoint_url: KMS endpoint (default=the regional AWS endpoint)
+            require_encryption: Fail downloads of objects without encryption
+                               metadata instead of passing them through
+                               (default=False)
 
-        Example:
-            >>> downloader = S3Downloader("us-east-1")
-            >>> downloads = [
-            ...     ("data/input.csv", "./processed/input_data.csv"),        # exists
-            ...     ("config/missing.json", "./config/app_settings.json"),  # doesn't exist
-            ...     ("logs/2024/app.log", "./logs/application.log")         # exists
-            ... ]
-            >>> result = downloader.download_multiple_files_with_paths(
-            ...     "my-bucket",
-            ...     downloads
-            ... )
-            # Warning: 1 downloads failed:
-            #   config/missing.json: S3 object not found
-            >>> print(f"Success rate: {result.success_rate():.1%}")
-            Success rate: 66.7%
-            >>> if not result.is_complete_success():
-            ...     print("Some downloads failed, checking what to retry...")
-            ...     for failed_key in result.failed:
-            ...         print(f"  Retry: {failed_key}")
+        Raises:
+            ValueError: If `encryption_context` uses the reserved
+                       "aws:x-amz-cek-alg" key
         """
         ...
+    @property
+    def kms_key_id(self) -> Optional[str]: ...
+    @property
+    def encryption_context(self) -> Dict[str, str]: ...
+    @property
+    def kms_endpoint_url(self) -> Optional[str]: ...
+    @property
+    def require_encryption(self) -> bool: ...
+    def __repr__(self) -> str: ...
 
 
 class S3Uploader:
@@ -278,40 +3626,865 @@ class S3Uploader:
 
     This mirrors the native `S3Uploader` pyclass provided by the compiled extension.
 
+    Uploaders can be called from many threads at once, and pickled like
+    downloaders, their `sse` and `kms_key_id` included.
+
     Example:
         >>> uploader = S3Uploader("us-east-1")
         >>> uploader.upload_file("my-bucket", "dest/key.txt", "./local.txt")
         './local.txt'
     """
 
-    def __init__(self, region_name: str, max_concurrent_uploads: int = 5) -> None:
+    def __init__(
+        self,
+        region_name: Optional[str] = None,
+        max_concurrent_uploads: int = 5,
+        endpoint_url: Optional[str] = None,
+        force_path_style: bool = False,
+        config: Optional[S3Config] = None,
+        runtime_threads: Optional[int] = None,
+        retry_policy: Optional[RetryPolicy] = None,
+        part_size: int = 8 * 1024 * 1024,
+        part_concurrency: int = 8,
+        sse: Optional[str] = None,
+        kms_key_id: Optional[str] = None,
+        client_encryption: Optional[ClientEncryption] = None,
+        max_bandwidth_bytes_per_sec: Optional[int] = None,
+        max_requests_per_sec: Optional[float] = None,
+        request_burst: Optional[int] = None,
+        limiter: Optional[TransferLimiter] = None,
+        preserve_mtime: bool = False,
+        preserve_permissions: bool = False,
+        symlinks: SymlinkPolicy = "follow",
+        audit_log: Optional[str] = None,
+    ) -> None:
         """
         Initialize the S3 uploader.
 
         Args:
             region_name: AWS region name (e.g. "us-east-1")
             max_concurrent_uploads: Maximum concurrent uploads (default=5)
+            endpoint_url: Custom endpoint for S3-compatible stores (default=AWS)
+            force_path_style: Use path-style addressing (default=False)
+            config: Existing `S3Config` to reuse instead of building a new client;
+                   `endpoint_url` and `force_path_style` can't be combined
+                   with it
+            runtime_threads: Worker threads for a multi-threaded Tokio runtime
+                            (default=None, a single worker thread)
+            retry_policy: How transient failures are retried (default=RetryPolicy())
+            part_size: Size of each part when `upload_fileobj` uses a multipart
+                      upload. S3 requires at least 5 MiB (default=8 MiB)
+            part_concurrency: Parts uploaded in parallel per object (default=8)
+            sse: Server-side encryption of every upload: "AES256" (SSE-S3),
+                "aws:kms" or "aws:kms:dsse" (default=the bucket's default)
+            kms_key_id: KMS key ID or ARN to encrypt with; implies "aws:kms"
+                       (default=the AWS managed key)
+            client_encryption: Encrypt every upload before it leaves the machine.
+                              Files are encrypted as they are read and sent
+                              with a single PUT, so they are limited to 5 GiB;
+                              `upload_fileobj` bodies larger than a part are
+                              encrypted part by part in a multipart upload
+            max_bandwidth_bytes_per_sec: Cap on the bytes per second sent by all
+                                        uploads of this instance together. The
+                                        body of every request is paced as it is
+                                        sent, parts included (default=unlimited)
+            max_requests_per_sec: Cap on the files a batch starts uploading per
+                                 second, to avoid S3 "503 SlowDown" throttling
+                                 (default=unlimited)
+            request_burst: Files that may start at once after an idle period
+                          (default=one second worth of `max_requests_per_sec`)
+            limiter: `TransferLimiter` shared with other instances, capping the
+                    files they upload together (default=no shared cap)
+            preserve_mtime: Record the modification time of every uploaded
+                           file in the "mtime" user metadata, in seconds since
+                           the epoch, for downloads with `preserve_mtime` to
+                           restore (default=False)
+            preserve_permissions: Record the read, write and execute bits of
+                                 every uploaded file in the "mode" user
+                                 metadata, as s3fs does (default=False)
+            symlinks: What to do with a local path that is a symbolic link:
+                     upload the file it points to, skip it, or store the link
+                     itself; see `SymlinkPolicy` (default="follow")
+            audit_log: Path of a JSON Lines file every file upload is appended
+                      to, with its time, bucket, key, local path, result,
+                      size, SHA-256 and ETag; see `verify_audit_log`
+                      (default=None, no audit log)
+
+        Raises:
+            ValueError: If neither `region_name` nor `config` is provided, if
+                       `runtime_threads`, `part_size`, `part_concurrency`,
+                       `max_bandwidth_bytes_per_sec` or `request_burst` is zero,
+                       if `max_requests_per_sec` is not positive, if `sse` is
+                       invalid, or if `client_encryption` has no
+                       `kms_key_id`
+            RobinzhonError: If `audit_log` can't be opened for appending
+        """
+        ...
+
+    def reinitialize(self) -> None:
+        """
+        Rebuild the async runtime and the S3 client in a process forked after
+        this uploader was created, which can't use the parent's threads and
+        connections. Until then, calls in the child raise `RobinzhonError`,
+        awaitable ones and `TransferManager.submit` included.
+
+        Fork while no transfers are running: the `limiter` slots they hold stay
+        taken in the child.
         """
         ...
 
-    def upload_file(self, bucket_name: str, object_key: str, local_path: str) -> str:
+    def upload_file(
+        self,
+        bucket_name: str,
+        object_key: str,
+        local_path: str,
+        if_none_match: bool = False,
+        options: Optional[UploadOptions] = None,
+        details: bool = False,
+    ) -> Union[str, ResultItem]:
         """
         Upload a single file to S3 and return the provided `local_path` on success.
 
-        Raises RuntimeError on failure.
+        With `details=True` the `ResultItem` of the upload is returned instead,
+        with the `etag` and `version_id` S3 returned for the new object.
+
+        `options` sets the object's user metadata and headers; by default only its
+        Content-Type is set, guessed from the key's extension.
+
+        With `if_none_match=True` the object is only created, never replaced: if
+        the key already exists, S3 rejects the upload and `PreconditionFailedError`
+        is raised. Concurrent producers writing the same key can't silently
+        overwrite each other this way; exactly one of them succeeds.
+
+        Raises a `RobinzhonError` subclass on failure (`BucketNotFoundError`,
+        `AccessDeniedError`, `PreconditionFailedError`, `TimeoutError`...).
+
+        Example:
+            >>> try:
+            ...     uploader.upload_file("my-bucket", "locks/job-42", "./lock", if_none_match=True)
+            ... except PreconditionFailedError:
+            ...     print("another worker got there first")
+        """
+        ...
+
+    def upload_fileobj(
+        self,
+        bucket_name: str,
+        object_key: str,
+        fileobj: Union[BinaryIO, Iterable[bytes]],
+        if_none_match: bool = False,
+        options: Optional[UploadOptions] = None,
+    ) -> str:
+        """
+        Upload everything read from `fileobj` and return `object_key` on success.
+
+        `fileobj` is either a binary file object, read with `read(part_size)` until
+        it returns `b""`, or an iterable of bytes-like chunks of any size. A body
+        that fits in one part is sent with a single PUT; larger ones are streamed as
+        a multipart upload with `part_concurrency` parts in flight, so memory use
+        stays bounded. The multipart upload is aborted if anything fails.
+
+        With `if_none_match=True`, an existing key fails the upload with
+        `PreconditionFailedError`, as in `upload_file`; for multipart uploads the
+        check happens when the upload is completed, after the parts were sent.
+
+        Raises:
+            TypeError: If `fileobj` is neither readable nor iterable, or produces
+                      something other than bytes
+            PreconditionFailedError: If `if_none_match` is set and the key exists
+            RobinzhonError: If a request fails. Errors raised by `fileobj` itself
+                           are propagated unchanged
+
+        Example:
+            >>> proc = subprocess.Popen(["tar", "c", "data/"], stdout=subprocess.PIPE)
+            >>> uploader.upload_fileobj("my-bucket", "backups/data.tar", proc.stdout)
+            'backups/data.tar'
         """
         ...
 
-    def upload_multiple_files(self, bucket_name: str, paths_and_keys: List[Tuple[str, str]]) -> Results:
+    def upload_multiple_files(
+        self,
+        bucket_name: str,
+        paths_and_keys: Iterable[UploadItem],
+        progress: Optional[TransferProgress] = None,
+        if_none_match: bool = False,
+        options: Optional[UploadOptions] = None,
+        fail_fast: bool = False,
+        max_failures: Optional[int] = None,
+        raise_on_failure: bool = False,
+        archive_mode: Optional[ArchiveMode] = None,
+        dry_run: bool = False,
+    ) -> Union[Results, TransferPlan]:
         """
         Upload multiple local files to `bucket_name` concurrently.
 
         Args:
             bucket_name: Name of the S3 bucket
-            paths_and_keys: List of tuples (local_path, object_key)
+            paths_and_keys: Tuples of (local_path, object_key). Any iterable, such
+                           as a generator, is accepted and consumed lazily
+            progress: Optional `TransferProgress` updated while the batch runs
+            if_none_match: Only create objects, never replace them. Items whose
+                          key already exists fail with `http_status` 412 and
+                          `error_code` "PreconditionFailed"; `retry_failed` keeps
+                          the condition
+            options: Metadata and headers for every object. An item given as
+                    `(local_path, object_key, options)` overrides them field by
+                    field, and its metadata is added to the batch's
+            fail_fast: Stop the batch at its first failed upload; the same as
+                      `max_failures=1`
+            max_failures: Stop the batch once this many uploads have failed. No
+                         new uploads are started; the ones in flight finish
+            raise_on_failure: Raise `PartialFailureError` instead of returning
+                             when any upload failed
+            archive_mode: Pack small files into bundles with a manifest instead
+                         of uploading each as an object (see `ArchiveMode`)
+            dry_run: Only look at the files and return the `TransferPlan` of
+                    their uploads, file by file even with `archive_mode`
 
         Returns:
             A `Results` instance describing successful and failed uploads. The
-            `failed` list contains the local paths that failed to upload.
+            `failed` list contains the local paths that failed to upload. With
+            `dry_run`, a `TransferPlan`.
+
+        Raises:
+            ValueError: If `max_failures` is 0, or `fail_fast` is combined with
+                       another `max_failures`, or `archive_mode` is given to an
+                       uploader with client-side encryption
+            BatchAbortedError: If the batch stopped at its failure limit, with the
+                              items finished so far as `partial_results`
+            PartialFailureError: If `raise_on_failure` is set and any item failed,
+                                with the batch's `Results` as `results`
+            KeyboardInterrupt: If interrupted with Ctrl-C. Outstanding uploads are
+                              cancelled and the items finished so far are attached
+                              as `partial_results`
+            Exception: Whatever `paths_and_keys` raises while being iterated stops
+                      the batch: the uploads in flight finish and the exception is
+                      raised with the items finished so far as `partial_results`
+
+        Note:
+            With `archive_mode`, `paths_and_keys` is read in full before anything
+            is uploaded, to plan the bundles. A packed file is reported uploaded,
+            under its key in `as_dict()`, once both its bundle and the manifest
+            are; a bundle that fails fails all its files. Bundles and the
+            manifest get the batch's `options`, and `if_none_match` applies to
+            them; the options of single items only apply to the files uploaded
+            as objects of their own. Failed items retried with `retry_failed`
+            are uploaded as objects of their own.
+        """
+        ...
+
+    def iter_upload_multiple_files(
+        self,
+        bucket_name: str,
+        paths_and_keys: Iterable[UploadItem],
+        if_none_match: bool = False,
+        options: Optional[UploadOptions] = None,
+        fail_fast: bool = False,
+        max_failures: Optional[int] = None,
+    ) -> BatchIterator:
+        """
+        Start `upload_multiple_files` in the background and iterate its items as
+        they finish.
+
+        Example:
+            >>> for item in tqdm(uploader.iter_upload_multiple_files("my-bucket", paths_and_keys)):
+            ...     pass
+        """
+        ...
+
+    def upload_uri(
+        self,
+        uri: str,
+        local_path: str,
+        if_none_match: bool = False,
+        options: Optional[UploadOptions] = None,
+        details: bool = False,
+    ) -> Union[str, ResultItem]:
+        """
+        Upload `local_path` to the object an `s3://bucket/key` URI points to.
+
+        Same as `upload_file(bucket, key, local_path, if_none_match, options, details)`; raises `ValueError` if
+        `uri` is not a valid `s3://bucket/key` URI.
+        """
+        ...
+
+    def upload_multiple_uris(
+        self,
+        paths_and_uris: Iterable[UploadItem],
+        progress: Optional[TransferProgress] = None,
+        if_none_match: bool = False,
+        options: Optional[UploadOptions] = None,
+        fail_fast: bool = False,
+        max_failures: Optional[int] = None,
+        raise_on_failure: bool = False,
+        dry_run: bool = False,
+    ) -> Union[Results, TransferPlan]:
+        """
+        Upload `(local_path, uri)` pairs whose `s3://bucket/key` URIs may point
+        to different buckets.
+
+        Behaves like `upload_multiple_files`, `dry_run` included. Malformed URIs are reported as failed
+        items rather than raised, so the rest of the batch still runs.
+        """
+        ...
+
+    def upload_from_manifest(
+        self,
+        manifest_path: str,
+        bucket_name: Optional[str] = None,
+        format: Optional[Literal["csv", "jsonl"]] = None,
+        progress: Optional[TransferProgress] = None,
+        if_none_match: bool = False,
+        options: Optional[UploadOptions] = None,
+        fail_fast: bool = False,
+        max_failures: Optional[int] = None,
+        raise_on_failure: bool = False,
+        dry_run: bool = False,
+    ) -> Union[Results, TransferPlan]:
+        """
+        Upload the files a manifest file lists, in one concurrent batch.
+
+        The manifest is written as for `S3Downloader.download_from_manifest`, a
+        CSV file with `key`, `local_path` and optional `bucket` columns or JSON
+        Lines with the same fields, and is read as the batch runs. Behaves like
+        `upload_multiple_uris`, `dry_run` included: failed items are reported by
+        their local path.
+
+        Args:
+            manifest_path: Path of the manifest file
+            bucket_name: Bucket of the rows that don't name one
+            format: `"csv"` or `"jsonl"`; by default guessed from the extension
+                   (`.csv`, `.jsonl` or `.ndjson`)
+
+        Raises:
+            ValueError: If the format can't be told, or a row is malformed or
+                       names no bucket when `bucket_name` is not given. Rows
+                       before it may already have been uploaded
+            RobinzhonError: If the manifest can't be read
+        """
+        ...
+
+    def upload_directory(
+        self,
+        local_dir: str,
+        bucket_name: str,
+        prefix: str,
+        include: Optional[List[str]] = None,
+        exclude: Optional[List[str]] = None,
+        progress: Optional[TransferProgress] = None,
+        if_none_match: bool = False,
+        options: Optional[UploadOptions] = None,
+        fail_fast: bool = False,
+        max_failures: Optional[int] = None,
+        raise_on_failure: bool = False,
+        dry_run: bool = False,
+    ) -> Union[Results, TransferPlan]:
+        """
+        Upload every file below `local_dir` to `prefix`, in one concurrent batch.
+
+        `prefix` is treated as a folder: `local_dir/a/b.txt` is uploaded to
+        `prefix/a/b.txt`. The glob patterns are matched against the path relative
+        to `local_dir` as the directory is walked, with `/` separators; `*`
+        matches across them, so `"*.parquet"` selects Parquet files at any depth.
+        Unlike `sync_up`, nothing is compared with the bucket: every selected file
+        is uploaded. Otherwise behaves like `upload_multiple_files`, `dry_run`
+        included.
+
+        Args:
+            local_dir: Local directory to upload
+            bucket_name: Name of the S3 bucket
+            prefix: Key prefix the files are uploaded under
+            include: Glob patterns selecting the relative paths to upload
+            exclude: Glob patterns of relative paths to leave out
+            progress: Optional `TransferProgress` updated while the uploads run
+            if_none_match: Only create objects; existing keys fail with
+                          `PreconditionFailedError`
+            options: Metadata and headers for every uploaded object
+            dry_run: Only walk the directory and return the `TransferPlan`
+
+        Returns:
+            `Results` keyed by local path, or with `dry_run` a `TransferPlan`
+
+        Raises:
+            ValueError: If a glob pattern is invalid
+            RobinzhonError: If `local_dir` is not a directory or can't be read
+
+        Example:
+            >>> res = uploader.upload_directory(
+            ...     "./v2", "my-bucket", "datasets/v2", include=["*.parquet"]
+            ... )
+        """
+        ...
+
+    def sync_up(
+        self,
+        local_dir: str,
+        bucket_name: str,
+        prefix: str,
+        delete: bool = False,
+        checksum: bool = False,
+        include: Optional[List[str]] = None,
+        exclude: Optional[List[str]] = None,
+        progress: Optional[TransferProgress] = None,
+        options: Optional[UploadOptions] = None,
+        fail_fast: bool = False,
+        max_failures: Optional[int] = None,
+        raise_on_failure: bool = False,
+        dry_run: bool = False,
+        scheduling: Scheduling = "fifo",
+    ) -> Union[SyncReport, TransferPlan]:
+        """
+        Bring the objects under `prefix` up to date with `local_dir`, rsync-style.
+
+        `prefix` is treated as a folder: `local_dir/a/b.txt` maps to the key
+        `prefix/a/b.txt`. A file is uploaded when no object has its key, when the
+        sizes differ, or when it was modified after the object was written. With
+        `checksum=True`, files of the same size are compared by MD5 against the
+        object's ETag instead, which ignores touched-but-identical files. ETags of
+        multipart uploads are not MD5s; those objects fall back to the time check.
+
+        Args:
+            local_dir: Local directory to mirror
+            bucket_name: Name of the S3 bucket
+            prefix: Key prefix the directory is mirrored under
+            delete: Also delete objects under `prefix` that have no local file
+            checksum: Compare same-size files by MD5 (reads every such file)
+            include: Glob patterns selecting the relative paths to sync
+            exclude: Glob patterns of relative paths to leave alone, locally and
+                    remotely
+            progress: Optional `TransferProgress` updated while the uploads run
+            options: Metadata and headers for every uploaded object
+            dry_run: Only compare and return the `TransferPlan` of the sync,
+                    its `deletions` being the keys `delete` would remove
+            scheduling: Order to start the uploads in, by file size; see
+                       `Scheduling` (default="fifo")
+
+        Returns:
+            A `SyncReport` of the uploads, skipped keys and deleted keys. Objects
+            that could not be deleted are logged and left out of `deleted`. With
+            `dry_run`, a `TransferPlan`
+
+        Raises:
+            ValueError: If a glob pattern is invalid
+            RobinzhonError: If `local_dir` is not a directory or the listing fails
+            KeyboardInterrupt: If interrupted with Ctrl-C; see `upload_multiple_files`
+            BatchAbortedError: If the batch stopped at its `fail_fast` or
+                              `max_failures` limit; extraneous objects are
+                              not deleted then
+
+        Example:
+            >>> report = uploader.sync_up("./site", "my-bucket", "site", delete=True)
+            >>> print(report.new, report.changed, report.deleted)
+        """
+        ...
+
+    def retry_failed(
+        self,
+        results: Results,
+        progress: Optional[TransferProgress] = None,
+        fail_fast: bool = False,
+        max_failures: Optional[int] = None,
+        raise_on_failure: bool = False,
+    ) -> Results:
+        """
+        Upload the failed items of a previous batch again, to their original keys.
+
+        New successes are appended to `results.successful` and `results.failed` is
+        replaced by the items that still failed. Returns the same `results` object.
+
+        Raises:
+            ValueError: If `results` did not come from an `S3Uploader` batch
+        """
+        ...
+
+    async def upload_file_async(
+        self,
+        bucket_name: str,
+        object_key: str,
+        local_path: str,
+        if_none_match: bool = False,
+        options: Optional[UploadOptions] = None,
+        details: bool = False,
+    ) -> Union[str, ResultItem]:
+        """
+        Awaitable variant of `upload_file`, running on the embedded Tokio runtime.
+        """
+        ...
+
+    async def upload_multiple_files_async(
+        self,
+        bucket_name: str,
+        paths_and_keys: Iterable[UploadItem],
+        progress: Optional[TransferProgress] = None,
+        if_none_match: bool = False,
+        options: Optional[UploadOptions] = None,
+        fail_fast: bool = False,
+        max_failures: Optional[int] = None,
+        raise_on_failure: bool = False,
+        archive_mode: Optional[ArchiveMode] = None,
+        dry_run: bool = False,
+    ) -> Union[Results, TransferPlan]:
+        """
+        Awaitable variant of `upload_multiple_files`.
+        """
+        ...
+
+    async def upload_uri_async(
+        self,
+        uri: str,
+        local_path: str,
+        if_none_match: bool = False,
+        options: Optional[UploadOptions] = None,
+        details: bool = False,
+    ) -> Union[str, ResultItem]:
+        """
+        Awaitable variant of `upload_uri`.
+        """
+        ...
+
+    async def upload_multiple_uris_async(
+        self,
+        paths_and_uris: Iterable[UploadItem],
+        progress: Optional[TransferProgress] = None,
+        if_none_match: bool = False,
+        options: Optional[UploadOptions] = None,
+        fail_fast: bool = False,
+        max_failures: Optional[int] = None,
+        raise_on_failure: bool = False,
+        dry_run: bool = False,
+    ) -> Union[Results, TransferPlan]:
+        """
+        Awaitable variant of `upload_multiple_uris`.
+        """
+        ...
+
+    async def sync_up_async(
+        self,
+        local_dir: str,
+        bucket_name: str,
+        prefix: str,
+        delete: bool = False,
+        checksum: bool = False,
+        include: Optional[List[str]] = None,
+        exclude: Optional[List[str]] = None,
+        progress: Optional[TransferProgress] = None,
+        options: Optional[UploadOptions] = None,
+        fail_fast: bool = False,
+        max_failures: Optional[int] = None,
+        raise_on_failure: bool = False,
+        dry_run: bool = False,
+        scheduling: Scheduling = "fifo",
+    ) -> Union[SyncReport, TransferPlan]:
+        """
+        Awaitable variant of `sync_up`.
+        """
+        ...
+
+class S3Client:
+    """
+    Downloads, uploads and object operations on one S3 client, runtime and set
+    of rate limits.
+
+    The client holds an `S3Downloader` and an `S3Uploader` sharing them, and
+    answers every method of both: `client.download_file(...)` and
+    `client.upload_file(...)` run on the same connections. Limits such as
+    `max_bandwidth_bytes_per_sec` apply to both directions together.
+
+    Example:
+        >>> client = S3Client("us-east-1")
+        >>> client.upload_file("my-bucket", "dest/key.txt", "./local.txt")
+        './local.txt'
+        >>> client.copy_object("my-bucket", "dest/key.txt", "my-bucket", "copy.txt")
+    """
+
+    def __init__(
+        self,
+        region_name: Optional[str] = None,
+        endpoint_url: Optional[str] = None,
+        force_path_style: bool = False,
+        config: Optional[S3Config] = None,
+        max_concurrent: int = 5,
+        runtime_threads: Optional[int] = None,
+        retry_policy: Optional[RetryPolicy] = None,
+        part_size: int = 8 * 1024 * 1024,
+        part_concurrency: int = 8,
+        max_bandwidth_bytes_per_sec: Optional[int] = None,
+        max_requests_per_sec: Optional[float] = None,
+        request_burst: Optional[int] = None,
+        limiter: Optional[TransferLimiter] = None,
+        audit_log: Optional[str] = None,
+        max_in_flight_bytes: Optional[int] = None,
+    ) -> None:
+        """
+        Initialize the S3 client.
+
+        Args:
+            region_name: AWS region name (e.g. "us-east-1")
+            endpoint_url: Custom endpoint for S3-compatible stores (default=AWS)
+            force_path_style: Use path-style addressing (default=False)
+            config: Existing `S3Config` to reuse instead of building a new client;
+                   `endpoint_url` and `force_path_style` can't be combined
+                   with it
+            max_concurrent: Maximum concurrent transfers of a batch, in either
+                           direction (default=5)
+            runtime_threads: Worker threads of the Tokio runtime
+                            (default=None, one per CPU core)
+            retry_policy: How transient failures are retried (default=RetryPolicy())
+            part_size: Size of each part of large transfers (default=8 MiB)
+            part_concurrency: Parts transferred in parallel per object (default=8)
+            max_bandwidth_bytes_per_sec: Cap on the bytes per second of every
+                                        transfer of the client together
+                                        (default=unlimited)
+            max_requests_per_sec: Cap on the items a batch starts per second
+                                 (default=unlimited)
+            request_burst: Items that may start at once after an idle period
+                          (default=one second worth of `max_requests_per_sec`)
+            limiter: `TransferLimiter` shared with other instances, capping the
+                    objects they transfer together (default=no shared cap)
+            audit_log: Path of a JSON Lines file every file download and
+                      upload is appended to; see `verify_audit_log`
+                      (default=None, no audit log)
+            max_in_flight_bytes: Cap on the bytes downloads buffer together;
+                                see `S3Downloader` (default=unlimited)
+
+        Raises:
+            ValueError: If neither `region_name` nor `config` is provided, or if
+                       a setting is invalid, as for `S3Downloader`
+            RobinzhonError: If `audit_log` can't be opened for appending
+        """
+        ...
+
+    @property
+    def downloader(self) -> S3Downloader:
+        """The downloader the client runs downloads, listings and HEAD requests with."""
+        ...
+
+    @property
+    def uploader(self) -> S3Uploader:
+        """The uploader the client runs uploads with, on the downloader's client."""
+        ...
+
+    def __getattr__(self, name: str) -> Any:
+        """
+        Methods of `S3Downloader`, then of `S3Uploader`, such as `download_file`,
+        `list_objects`, `head` or `upload_multiple_files`.
+        """
+        ...
+
+    def retry_failed(
+        self,
+        results: Results,
+        progress: Optional[TransferProgress] = None,
+        fail_fast: bool = False,
+        max_failures: Optional[int] = None,
+        raise_on_failure: bool = False,
+    ) -> Results:
+        """
+        Transfer the failed items of `results` again, with the downloader or the
+        uploader depending on the batch they came from, and merge the outcome.
+        """
+        ...
+
+    def reinitialize(self) -> None:
+        """
+        Rebuild the async runtime and the S3 client shared by both directions,
+        in a process forked after this client was created.
+        """
+        ...
+
+    def preflight(self, bucket_name: str, check_write: bool = True) -> PreflightReport:
+        """
+        Check that a batch on `bucket_name` can run before starting it.
+
+        Verifies that credentials load, that the bucket exists, which region it
+        is in, and that the client may list it and, with `check_write`, write to
+        it, so that a misconfigured job fails right away rather than partway
+        through. Problems are reported, not raised.
+
+        Args:
+            bucket_name: Name of the S3 bucket
+            check_write: Also check that PutObject is allowed, with a conditional
+                        PUT of zero bytes to `.robinzhon-preflight` that S3 rejects
+                        without creating anything (default=True). Stores that
+                        ignore the condition create the object, which is then
+                        deleted
+
+        Example:
+            >>> report = client.preflight("my-bucket")
+            >>> if not report:
+            ...     raise SystemExit("\n".join(report.problems))
+        """
+        ...
+
+    def delete_object(
+        self, bucket_name: str, object_key: str, version_id: Optional[str] = None
+    ) -> None:
+        """
+        Delete an object, or one version of it.
+
+        Deleting a key that doesn't exist succeeds, as it does in S3.
+
+        Raises:
+            RobinzhonError: If the request fails
         """
         ...
+
+    def delete_objects(
+        self, bucket_name: str, object_keys: Iterable[str], dry_run: bool = False
+    ) -> Union[List[str], TransferPlan]:
+        """
+        Delete objects, 1000 keys per request.
+
+        Args:
+            bucket_name: Name of the S3 bucket
+            object_keys: Keys of the objects to delete: a list, or any iterable
+                        such as a generator, read 1000 keys at a time, each
+                        request sent before the next keys are read
+            dry_run: Delete nothing and return the `TransferPlan` of the
+                    deletions
+
+        Returns:
+            List[str]: The keys deleted. Keys that couldn't be are logged and
+                      left out. With `dry_run`, a `TransferPlan`
+        """
+        ...
+
+    def copy_object(
+        self, source_bucket: str, source_key: str, bucket_name: str, object_key: str
+    ) -> None:
+        """
+        Copy an object within S3, across buckets too, with its metadata.
+
+        The copy is a single request, which S3 limits to objects of 5 GiB.
+
+        Raises:
+            RobinzhonError: If the request fails
+        """
+        ...
+
+    def presign(
+        self,
+        bucket_name: str,
+        object_key: str,
+        method: PresignMethod = "get",
+        expires_in: float = 3600,
+    ) -> str:
+        """
+        A URL to download ("get") or upload ("put") the object with, without
+        credentials, signed with the client's. No request is sent.
+
+        Args:
+            bucket_name: Bucket of the object
+            object_key: Key of the object
+            method: "get" or "put" (default="get")
+            expires_in: Seconds the URL stays valid, up to seven days
+                       (default=3600)
+
+        Raises:
+            ValueError: If `method` is unknown or `expires_in` is out of range
+        """
+        ...
+
+
+def get_metrics() -> Dict[str, Any]:
+    """
+    Metrics of every request the S3 clients of the process sent so far,
+    whichever client sent them, for dashboards of robinzhon-driven pipelines.
+
+    Returns:
+        Dict[str, Any]: Prometheus-style counters `requests_total`,
+                       `request_errors_total` (error statuses and requests
+                       that got no response), `retries_total` (transfers
+                       retried by a `RetryPolicy`; the SDK's own retries count
+                       as requests), `bytes_in_total` and `bytes_out_total`
+                       (the Content-Length of responses and requests), and
+                       the `request_duration_seconds` histogram: `buckets`,
+                       the cumulative count of requests by upper bound in
+                       seconds (the last one `float("inf")`), with their
+                       `sum` and `count`
+
+    Note:
+        Presigned URL downloads don't go through the S3 clients, so they
+        aren't counted.
+
+    Example:
+        >>> metrics = robinzhon.get_metrics()
+        >>> metrics["requests_total"], metrics["request_duration_seconds"]["sum"]
+    """
+    ...
+
+
+def reset_metrics() -> None:
+    """Set every metric back to zero."""
+    ...
+
+
+def set_metrics_callback(
+    callback: Optional[Callable[[Dict[str, Any]], None]],
+    interval: float = 10.0,
+) -> None:
+    """
+    Call `callback` with the metrics, as `get_metrics()` returns them, every
+    `interval` seconds, from a thread of its own. Setting a callback replaces
+    the one set before; `None` stops the calls. Exceptions `callback` raises
+    are logged and the calls go on.
+
+    Raises:
+        TypeError: If `callback` isn't callable
+        ValueError: If `interval` isn't a positive number
+
+    Example:
+        >>> gauge = prometheus_client.Gauge("robinzhon_bytes_in", "Bytes downloaded")
+        >>> robinzhon.set_metrics_callback(lambda m: gauge.set(m["bytes_in_total"]), interval=5)
+    """
+    ...
+
+
+def set_log_level(level: Union[int, str]) -> None:
+    """
+    Set the level of the `robinzhon` logger, which can be changed again at any
+    time. At `"DEBUG"` or below, every request the S3 clients send is logged
+    too: its method and URL (without the query string, which holds the
+    signature of presigned requests), the number of the attempt and how long
+    it took to be answered.
+
+    Args:
+        level: A level of the `logging` module, such as `logging.DEBUG`, or
+               its name, in any case
+
+    Raises:
+        ValueError: If `level` isn't the name of a level
+
+    Example:
+        >>> logging.basicConfig()
+        >>> robinzhon.set_log_level("debug")
+        >>> downloader.download_file("my-bucket", "data/file.txt", "./file.txt")
+        DEBUG:robinzhon.request_log:Sending GET https://my-bucket.s3.us-east-1.amazonaws.com/data/file.txt (attempt 1)
+        DEBUG:robinzhon.request_log:GET https://my-bucket.s3.us-east-1.amazonaws.com/data/file.txt (attempt 1) answered 200 in 41.3 ms
+        >>> robinzhon.set_log_level("warning")
+    """
+    ...
+
+
+def verify_audit_log(path: str) -> int:
+    """
+    Check that no entry of the audit log at `path` was edited, inserted or
+    removed since it was written, and return how many entries it holds.
+
+    Every entry stores, in `prev`, the SHA-256 of the line before it (64 zeros
+    for the first), so a changed line breaks the chain at the line after it.
+    Entries cut from the end of the log leave no trace; keep the count this
+    returns elsewhere to tell.
+
+    Raises:
+        ValueError: If the chain is broken, naming the first line that doesn't
+                   follow from the one before it
+        RobinzhonError: If the file can't be read
+
+    Example:
+        >>> downloader = S3Downloader("us-east-1", audit_log="./audit.jsonl")
+        >>> downloader.download_file("my-bucket", "data/file.txt", "./file.txt")
+        >>> robinzhon.verify_audit_log("./audit.jsonl")
+        1
+    """
+    ...
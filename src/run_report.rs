@@ -0,0 +1,178 @@
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use pyo3::exceptions::PyValueError;
+use pyo3::types::PyAnyMethods;
+use pyo3::{Bound, FromPyObject, PyAny, PyResult};
+use serde::Serialize;
+
+use crate::exceptions::RobinzhonError;
+use crate::results::ResultItem;
+
+/// How `Results.write_report` writes a report.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReportFormat {
+    /// A document with the totals of the batch and an array of its items.
+    Json,
+    /// A header, then one row per item.
+    Csv,
+}
+
+impl<'py> FromPyObject<'py> for ReportFormat {
+    fn extract_bound(value: &Bound<'py, PyAny>) -> PyResult<Self> {
+        match value.extract::<String>()?.as_str() {
+            "json" => Ok(Self::Json),
+            "csv" => Ok(Self::Csv),
+            other => Err(PyValueError::new_err(format!(
+                "Invalid report format '{}', expected 'json' or 'csv'",
+                other
+            ))),
+        }
+    }
+}
+
+/// Totals of a batch, heading its JSON report.
+#[derive(Debug, Serialize)]
+pub struct ReportSummary {
+    pub total: usize,
+    pub successful: usize,
+    pub failed: usize,
+    pub skipped: usize,
+    pub total_bytes: u64,
+    pub elapsed_seconds: f64,
+    pub throughput_mbps: f64,
+}
+
+/// One item of a batch and how it ended, as a report lists it. Fields a batch
+/// didn't measure are left empty.
+#[derive(Debug, Serialize)]
+struct ReportRow<'a> {
+    item: &'a str,
+    status: &'static str,
+    source: Option<&'a str>,
+    destination: Option<&'a str>,
+    bytes: Option<u64>,
+    duration_seconds: Option<f64>,
+    attempts: Option<u32>,
+    started_at: Option<f64>,
+    finished_at: Option<f64>,
+    error: Option<&'a str>,
+    error_code: Option<&'a str>,
+    http_status: Option<u16>,
+    request_id: Option<&'a str>,
+}
+
+/// Columns of a CSV report, in the order of the fields of `ReportRow`.
+const CSV_COLUMNS: [&str; 13] = [
+    "item",
+    "status",
+    "source",
+    "destination",
+    "bytes",
+    "duration_seconds",
+    "attempts",
+    "started_at",
+    "finished_at",
+    "error",
+    "error_code",
+    "http_status",
+    "request_id",
+];
+
+impl<'a> ReportRow<'a> {
+    fn new(item: &'a ResultItem) -> Self {
+        let timed = item.stats.and_then(|stats| stats.timed());
+        let error = item.error.as_ref();
+        Self {
+            item: &item.name,
+            status: item.status,
+            source: item.source.as_deref(),
+            destination: item.destination.as_deref(),
+            bytes: item.stats.map(|stats| stats.bytes),
+            duration_seconds: item.stats.map(|stats| stats.elapsed_seconds),
+            attempts: timed.map(|stats| stats.attempts),
+            started_at: timed.map(|stats| stats.started_at),
+            finished_at: timed.map(|stats| stats.finished_at),
+            error: error.map(|error| error.error_message.as_str()),
+            error_code: error.and_then(|error| error.error_code.as_deref()),
+            http_status: error.and_then(|error| error.http_status),
+            request_id: error.and_then(|error| error.request_id.as_deref()),
+        }
+    }
+
+    fn csv_fields(&self) -> [String; 13] {
+        fn text<T: ToString>(value: Option<T>) -> String {
+            value.map(|value| value.to_string()).unwrap_or_default()
+        }
+        [
+            self.item.to_string(),
+            self.status.to_string(),
+            text(self.source),
+            text(self.destination),
+            text(self.bytes),
+            text(self.duration_seconds),
+            text(self.attempts),
+            text(self.started_at),
+            text(self.finished_at),
+            text(self.error),
+            text(self.error_code),
+            text(self.http_status),
+            text(self.request_id),
+        ]
+    }
+}
+
+#[derive(Serialize)]
+struct JsonReport<'a> {
+    /// Unix time the report was written at.
+    generated_at: f64,
+    summary: ReportSummary,
+    items: Vec<ReportRow<'a>>,
+}
+
+/// Writes the report of a batch with `items` to `path`, replacing any file
+/// there.
+pub fn write_report(
+    path: &str,
+    format: ReportFormat,
+    summary: ReportSummary,
+    items: &[ResultItem],
+) -> PyResult<()> {
+    let write_error = |e: &dyn std::fmt::Display| {
+        RobinzhonError::new_err(format!("Failed to write report '{}': {}", path, e))
+    };
+    let mut writer = BufWriter::new(File::create(path).map_err(|e| write_error(&e))?);
+    let rows = items.iter().map(ReportRow::new);
+    match format {
+        ReportFormat::Json => {
+            let report = JsonReport {
+                generated_at: SystemTime::now()
+                    .duration_since(UNIX_EPOCH)
+                    .unwrap_or_default()
+                    .as_secs_f64(),
+                summary,
+                items: rows.collect(),
+            };
+            serde_json::to_writer_pretty(&mut writer, &report).map_err(|e| write_error(&e))?;
+            writeln!(writer).map_err(|e| write_error(&e))?;
+        }
+        ReportFormat::Csv => {
+            writeln!(writer, "{}", CSV_COLUMNS.join(",")).map_err(|e| write_error(&e))?;
+            for row in rows {
+                let fields = row.csv_fields().map(|field| csv_field(&field));
+                writeln!(writer, "{}", fields.join(",")).map_err(|e| write_error(&e))?;
+            }
+        }
+    }
+    writer.flush().map_err(|e| write_error(&e))
+}
+
+/// `field` quoted for CSV when it holds a separator, a quote or a line break.
+fn csv_field(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
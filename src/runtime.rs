@@ -0,0 +1,121 @@
+use std::future::Future;
+use std::ops::Deref;
+use std::time::Duration;
+
+use pyo3::exceptions::{PyRuntimeError, PyValueError};
+use pyo3::{PyResult, Python};
+use tokio::runtime::{Builder, Runtime};
+
+use crate::exceptions::RobinzhonError;
+
+/// How often a blocking call wakes up to let Python handle pending signals.
+pub const SIGNAL_CHECK_INTERVAL: Duration = Duration::from_millis(100);
+
+/// A Tokio runtime and the process it was built in.
+///
+/// Worker threads don't survive `fork()`: in a child process the runtime looks
+/// intact but never runs its tasks, so using it there fails instead of hanging.
+pub struct ProcessRuntime {
+    /// Only taken when the runtime is dropped.
+    runtime: Option<Runtime>,
+    pid: u32,
+}
+
+impl ProcessRuntime {
+    /// Fails when called from a process forked after the runtime was built.
+    pub fn check_process(&self) -> PyResult<()> {
+        if self.pid == std::process::id() {
+            Ok(())
+        } else {
+            Err(RobinzhonError::new_err(
+                "This client was created before os.fork(); call reinitialize() on it in the child process",
+            ))
+        }
+    }
+}
+
+impl Deref for ProcessRuntime {
+    type Target = Runtime;
+
+    fn deref(&self) -> &Runtime {
+        self.runtime
+            .as_ref()
+            .expect("the runtime is only taken on drop")
+    }
+}
+
+impl Drop for ProcessRuntime {
+    fn drop(&mut self) {
+        let Some(runtime) = self.runtime.take() else {
+            return;
+        };
+        // Shutting down waits for worker threads a forked child doesn't have, so
+        // there the runtime is leaked instead.
+        if self.pid != std::process::id() {
+            std::mem::forget(runtime);
+            return;
+        }
+        // The last reference usually goes with the GIL held, while a worker may
+        // be waiting for it to resolve an awaitable; waiting for that worker
+        // would deadlock.
+        runtime.shutdown_background();
+    }
+}
+
+/// Builds the Tokio runtime that backs a downloader/uploader for its whole lifetime.
+///
+/// `runtime_threads` selects a multi-threaded runtime with that many workers; when
+/// unset, the class-specific `default` builder is used instead.
+pub fn build_runtime(runtime_threads: Option<usize>, default: Builder) -> PyResult<ProcessRuntime> {
+    let mut builder = match runtime_threads {
+        Some(0) => {
+            return Err(PyValueError::new_err(
+                "runtime_threads must be greater than zero",
+            ))
+        }
+        Some(threads) => {
+            let mut builder = Builder::new_multi_thread();
+            builder.worker_threads(threads);
+            builder
+        }
+        None => default,
+    };
+
+    let runtime = builder
+        .enable_all()
+        .build()
+        .map_err(|e| PyRuntimeError::new_err(format!("Failed to create async runtime: {}", e)))?;
+    Ok(ProcessRuntime {
+        runtime: Some(runtime),
+        pid: std::process::id(),
+    })
+}
+
+/// Drives `fut` to completion with the GIL released, periodically checking for Python
+/// signals. A pending `KeyboardInterrupt` drops the future, cancelling every
+/// outstanding transfer, and is returned as the error.
+pub fn block_on_interruptible<F>(
+    py: Python<'_>,
+    runtime: &ProcessRuntime,
+    fut: F,
+) -> PyResult<F::Output>
+where
+    F: Future + Send,
+    F::Output: Send,
+{
+    runtime.check_process()?;
+    let mut fut = Box::pin(fut);
+    loop {
+        let output = py.allow_threads(|| {
+            runtime.block_on(async {
+                tokio::time::timeout(SIGNAL_CHECK_INTERVAL, &mut fut)
+                    .await
+                    .ok()
+            })
+        });
+        if let Some(output) = output {
+            return Ok(output);
+        }
+        py.check_signals()?;
+    }
+}
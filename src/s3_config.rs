@@ -1,19 +1,728 @@
-use aws_config::{BehaviorVersion, Region};
+use aws_config::timeout::TimeoutConfig;
+use aws_config::{AppName, BehaviorVersion, Region};
+use aws_credential_types::provider::SharedCredentialsProvider;
+use aws_sdk_s3::config::IdentityCache;
 use aws_sdk_s3::{self as s3};
-use pyo3::pyclass;
+use aws_sdk_sts as sts;
+use aws_smithy_http_client::hyper_014::HyperClientBuilder;
+use log::debug;
+use pyo3::exceptions::PyValueError;
+use pyo3::types::{PyAnyMethods, PyDict, PyDictMethods};
+use pyo3::{pyclass, pymethods, Bound, Py, PyAny, PyResult, Python};
+use std::collections::{BTreeMap, HashMap};
+use std::future::Future;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
 
-#[pyclass]
+use crate::access_points::{is_mrap_alias, AccessPointResolver};
+use crate::credentials::{AssumeRole, CredentialSource, PythonCredentials, RefreshingCredentials};
+use crate::error::TransferError;
+use crate::interceptor::RequestHooks;
+use crate::metrics::RequestMetrics;
+use crate::request_log::RequestLog;
+use crate::retry::RetryPolicy;
+use crate::s3_uri::is_arn;
+
+#[pyclass(module = "robinzhon")]
+#[derive(Clone)]
 pub struct S3Config {
     pub client: s3::Client,
+    /// Credentials of the client, to sign requests to other services with.
+    pub credentials: Option<SharedCredentialsProvider>,
+    /// Cache of `credentials`, emptied when S3 rejects them as expired.
+    credential_cache: Option<RefreshingCredentials>,
+    /// Profile, role and callback the credentials come from.
+    pub credential_source: CredentialSource,
+    /// Headers and Python hooks applied to every request.
+    pub hooks: RequestHooks,
+    /// Name of the application, appended to the user agent of every request.
+    #[pyo3(get)]
+    pub app_name: Option<String>,
+    #[pyo3(get)]
+    pub region_name: String,
+    #[pyo3(get)]
+    pub endpoint_url: Option<String>,
+    #[pyo3(get)]
+    pub force_path_style: bool,
+    /// Whether requests are sent unsigned, as anyone may read public buckets.
+    #[pyo3(get)]
+    pub anonymous: bool,
+    #[pyo3(get)]
+    pub use_dualstack_endpoint: bool,
+    #[pyo3(get)]
+    pub use_fips_endpoint: bool,
+    #[pyo3(get)]
+    pub use_accelerate_endpoint: bool,
+    pub tuning: ConnectionTuning,
+    /// Clients for the buckets S3 redirected to a region other than
+    /// `region_name`, by bucket name.
+    regional: Arc<Mutex<HashMap<String, s3::Client>>>,
+    /// Process the client was built in; its pooled connections are not shared
+    /// with processes forked from it.
+    pid: u32,
+}
+
+/// Switches of an `S3Config` beyond its region and endpoint, all off by default.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ClientFlags {
+    pub force_path_style: bool,
+    pub anonymous: bool,
+    /// Endpoints reachable over IPv6 as well as IPv4.
+    pub use_dualstack_endpoint: bool,
+    /// FIPS 140-validated endpoints, as GovCloud requires.
+    pub use_fips_endpoint: bool,
+    /// S3 Transfer Acceleration, for buckets that have it enabled.
+    pub use_accelerate_endpoint: bool,
+}
+
+impl ClientFlags {
+    /// Rejects the combinations S3 has no endpoints for.
+    fn check(&self) -> PyResult<()> {
+        if self.use_accelerate_endpoint && self.force_path_style {
+            return Err(PyValueError::new_err(
+                "use_accelerate_endpoint can't be used with force_path_style",
+            ));
+        }
+        if self.use_accelerate_endpoint && self.use_fips_endpoint {
+            return Err(PyValueError::new_err(
+                "use_accelerate_endpoint can't be used with use_fips_endpoint",
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// Timeouts and connection pool limits of an `S3Config`; unset values keep the
+/// SDK defaults.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ConnectionTuning {
+    pub connect_timeout: Option<Duration>,
+    pub read_timeout: Option<Duration>,
+    /// Bound on a whole operation, retries included.
+    pub operation_timeout: Option<Duration>,
+    /// Bound on each single attempt of an operation.
+    pub operation_attempt_timeout: Option<Duration>,
+    pub max_idle_connections_per_host: Option<usize>,
+}
+
+impl ConnectionTuning {
+    fn timeout_config(&self) -> TimeoutConfig {
+        let mut builder = TimeoutConfig::builder();
+        if let Some(timeout) = self.connect_timeout {
+            builder = builder.connect_timeout(timeout);
+        }
+        if let Some(timeout) = self.read_timeout {
+            builder = builder.read_timeout(timeout);
+        }
+        if let Some(timeout) = self.operation_timeout {
+            builder = builder.operation_timeout(timeout);
+        }
+        if let Some(timeout) = self.operation_attempt_timeout {
+            builder = builder.operation_attempt_timeout(timeout);
+        }
+        builder.build()
+    }
+}
+
+/// Converts a timeout given in seconds from Python.
+fn timeout_from_secs(name: &str, seconds: Option<f64>) -> PyResult<Option<Duration>> {
+    match seconds {
+        None => Ok(None),
+        Some(seconds) if seconds.is_finite() && seconds > 0.0 => {
+            Ok(Some(Duration::from_secs_f64(seconds)))
+        }
+        Some(_) => Err(PyValueError::new_err(format!(
+            "{} must be a positive number of seconds",
+            name
+        ))),
+    }
 }
 
 impl S3Config {
-    pub async fn new(region_name: String) -> Self {
-        let config = aws_config::defaults(BehaviorVersion::latest())
-            .region(Region::new(region_name))
-            .load()
-            .await;
-        let client = s3::Client::new(&config);
-        Self { client }
+    pub async fn new(
+        region_name: String,
+        endpoint_url: Option<String>,
+        flags: ClientFlags,
+        tuning: ConnectionTuning,
+        credential_source: CredentialSource,
+        hooks: RequestHooks,
+        app_name: Option<String>,
+    ) -> Self {
+        let mut loader = aws_config::defaults(BehaviorVersion::latest())
+            .region(Region::new(region_name.clone()))
+            .use_dual_stack(flags.use_dualstack_endpoint)
+            .use_fips(flags.use_fips_endpoint)
+            .timeout_config(tuning.timeout_config());
+        if let Some(max_idle) = tuning.max_idle_connections_per_host {
+            let mut hyper_builder = hyper::Client::builder();
+            hyper_builder.pool_max_idle_per_host(max_idle);
+            loader = loader.http_client(
+                HyperClientBuilder::new()
+                    .hyper_builder(hyper_builder)
+                    .build_https(),
+            );
+        }
+        if let Some(endpoint_url) = &endpoint_url {
+            loader = loader.endpoint_url(endpoint_url);
+        }
+        if let Some(app_name) = app_name.clone().and_then(|name| AppName::new(name).ok()) {
+            loader = loader.app_name(app_name);
+        }
+        if let Some(profile_name) = &credential_source.profile_name {
+            loader = loader.profile_name(profile_name);
+        }
+        if let Some(callback) = &credential_source.callback {
+            loader = loader.credentials_provider(callback.clone());
+        }
+        if flags.anonymous {
+            loader = loader.no_credentials();
+        }
+        let config = loader.load().await;
+
+        let source = match &credential_source.assume_role {
+            Some(assume_role) => Some(assume_role.provider(&config).await),
+            None => config.credentials_provider(),
+        };
+        let credential_cache = source.map(|source| {
+            RefreshingCredentials::new(source, credential_source.profile_name.clone())
+        });
+        let mut builder = s3::config::Builder::from(&config)
+            .force_path_style(flags.force_path_style)
+            .accelerate(flags.use_accelerate_endpoint)
+            .interceptor(RequestMetrics)
+            .interceptor(RequestLog);
+        if let Some(credential_cache) = &credential_cache {
+            // The cache above replaces the SDK's, which can't be emptied early.
+            builder = builder
+                .credentials_provider(credential_cache.clone())
+                .identity_cache(IdentityCache::no_cache());
+        }
+        if !hooks.is_empty() {
+            builder = builder.interceptor(hooks.clone());
+        }
+        // Multi-Region Access Points only exist on AWS.
+        if endpoint_url.is_none() {
+            let mut sts_builder = sts::config::Builder::from(&config);
+            if let Some(credential_cache) = &credential_cache {
+                sts_builder = sts_builder.credentials_provider(credential_cache.clone());
+            }
+            let sts = sts::Client::from_conf(sts_builder.build());
+            builder = builder.endpoint_resolver(AccessPointResolver::new(sts));
+        }
+        let client = s3::Client::from_conf(builder.build());
+        Self {
+            client,
+            credentials: credential_cache.clone().map(SharedCredentialsProvider::new),
+            credential_cache,
+            credential_source,
+            hooks,
+            app_name,
+            region_name,
+            endpoint_url,
+            force_path_style: flags.force_path_style,
+            anonymous: flags.anonymous,
+            use_dualstack_endpoint: flags.use_dualstack_endpoint,
+            use_fips_endpoint: flags.use_fips_endpoint,
+            use_accelerate_endpoint: flags.use_accelerate_endpoint,
+            tuning,
+            regional: Arc::default(),
+            pid: std::process::id(),
+        }
+    }
+
+    /// Resolves the configuration on a dedicated thread so it can be called from
+    /// synchronous Python entry points regardless of any surrounding runtime.
+    pub fn new_blocking(
+        region_name: String,
+        endpoint_url: Option<String>,
+        flags: ClientFlags,
+        tuning: ConnectionTuning,
+        credential_source: CredentialSource,
+        hooks: RequestHooks,
+        app_name: Option<String>,
+    ) -> Self {
+        std::thread::spawn(move || {
+            let rt = tokio::runtime::Runtime::new().unwrap();
+            rt.block_on(S3Config::new(
+                region_name,
+                endpoint_url,
+                flags,
+                tuning,
+                credential_source,
+                hooks,
+                app_name,
+            ))
+        })
+        .join()
+        .unwrap()
+    }
+
+    /// A new client with the same settings, its own connections and freshly
+    /// resolved credentials.
+    pub fn rebuilt(&self) -> Self {
+        Self::new_blocking(
+            self.region_name.clone(),
+            self.endpoint_url.clone(),
+            ClientFlags {
+                force_path_style: self.force_path_style,
+                anonymous: self.anonymous,
+                use_dualstack_endpoint: self.use_dualstack_endpoint,
+                use_fips_endpoint: self.use_fips_endpoint,
+                use_accelerate_endpoint: self.use_accelerate_endpoint,
+            },
+            self.tuning,
+            self.credential_source.clone(),
+            self.hooks.clone(),
+            self.app_name.clone(),
+        )
+    }
+
+    /// The client to reach `bucket_name` with: the configured one, or one for
+    /// the bucket's own region once S3 redirected a request for it there.
+    pub fn client_for(&self, bucket_name: &str) -> s3::Client {
+        self.regional
+            .lock()
+            .unwrap()
+            .get(bucket_name)
+            .cloned()
+            .unwrap_or_else(|| self.client.clone())
+    }
+
+    /// Runs `operation`, which sends its requests with `client_for(bucket_name)`,
+    /// under `retry_policy`. When S3 rejects the credentials as expired, new ones
+    /// are loaded, and when it answers that the bucket lives in another region,
+    /// that region is looked up and remembered; either way the operation runs
+    /// again.
+    pub async fn run<T, F, Fut>(
+        &self,
+        bucket_name: &str,
+        retry_policy: &RetryPolicy,
+        mut operation: F,
+    ) -> Result<T, TransferError>
+    where
+        F: FnMut() -> Fut,
+        Fut: Future<Output = Result<T, TransferError>>,
+    {
+        let mut outcome = retry_policy.run(&mut operation).await;
+        if let Err(error) = &outcome {
+            if error.expired_credentials && self.refresh_credentials().await {
+                outcome = retry_policy.run(&mut operation).await;
+            }
+        }
+        if let Err(error) = &outcome {
+            if error.wrong_region && self.follow_redirect(bucket_name, error).await {
+                outcome = retry_policy.run(&mut operation).await;
+            }
+        }
+        outcome
+    }
+
+    /// Makes the next request load new credentials. Returns whether the
+    /// client has any to load.
+    async fn refresh_credentials(&self) -> bool {
+        match &self.credential_cache {
+            Some(credential_cache) => {
+                credential_cache.invalidate().await;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Sends the requests for `bucket_name` to the region `error` redirected
+    /// them to, asking S3 when the response didn't say. Returns whether that
+    /// changed the client for the bucket.
+    async fn follow_redirect(&self, bucket_name: &str, error: &TransferError) -> bool {
+        // Access point ARNs name their region, and Multi-Region Access Points
+        // route requests themselves.
+        if is_arn(bucket_name) || is_mrap_alias(bucket_name) {
+            return false;
+        }
+        let region = match &error.bucket_region {
+            Some(region) => Some(region.clone()),
+            None => self.bucket_region(bucket_name).await,
+        };
+        match region {
+            Some(region) => self.use_region(bucket_name, region),
+            None => false,
+        }
+    }
+
+    /// Sends the requests for `bucket_name` to `region` from now on. Returns
+    /// whether that changed the client for the bucket.
+    pub fn use_region(&self, bucket_name: &str, region: String) -> bool {
+        let client = self.client_for(bucket_name);
+        if client
+            .config()
+            .region()
+            .is_some_and(|current| current.to_string() == region)
+        {
+            return false;
+        }
+
+        debug!(
+            "Bucket '{}' is in region '{}'; sending its requests there",
+            bucket_name, region
+        );
+        let config = self
+            .client
+            .config()
+            .to_builder()
+            .region(Region::new(region))
+            .build();
+        self.regional
+            .lock()
+            .unwrap()
+            .insert(bucket_name.to_string(), s3::Client::from_conf(config));
+        true
+    }
+
+    /// The region S3 reports `bucket_name` in. HeadBucket tells it whatever
+    /// region it is sent to, in a redirect if need be.
+    pub async fn bucket_region(&self, bucket_name: &str) -> Option<String> {
+        match self.client.head_bucket().bucket(bucket_name).send().await {
+            Ok(output) => output.bucket_region().map(str::to_string),
+            Err(e) => e
+                .raw_response()
+                .and_then(|r| r.headers().get("x-amz-bucket-region"))
+                .map(str::to_string),
+        }
+    }
+
+    /// Picks the shared `config` when one is given, otherwise builds a new client
+    /// from the individual constructor arguments. A `config` created before
+    /// `os.fork()` is rebuilt rather than shared with the parent process.
+    ///
+    /// The endpoint arguments can't be combined with a `config`, which already
+    /// settles them.
+    pub fn from_args(
+        config: Option<&S3Config>,
+        region_name: Option<&str>,
+        endpoint_url: Option<String>,
+        force_path_style: bool,
+    ) -> PyResult<Arc<Self>> {
+        if config.is_some() && (endpoint_url.is_some() || force_path_style) {
+            return Err(PyValueError::new_err(
+                "endpoint_url and force_path_style can't be combined with config; \
+                 set them on the S3Config instead",
+            ));
+        }
+        match (config, region_name) {
+            (Some(config), _) if config.pid != std::process::id() => Ok(Arc::new(config.rebuilt())),
+            (Some(config), _) => Ok(Arc::new(config.clone())),
+            (None, Some(region_name)) => Ok(Arc::new(Self::new_blocking(
+                region_name.to_string(),
+                endpoint_url,
+                ClientFlags {
+                    force_path_style,
+                    ..ClientFlags::default()
+                },
+                ConnectionTuning::default(),
+                CredentialSource::default(),
+                RequestHooks::default(),
+                None,
+            ))),
+            (None, None) => Err(PyValueError::new_err(
+                "Either region_name or config must be provided",
+            )),
+        }
+    }
+}
+
+/// Region a boto3 or botocore session is configured for.
+fn session_region(session: &Bound<'_, PyAny>) -> PyResult<Option<String>> {
+    if session.hasattr("region_name")? {
+        session.getattr("region_name")?.extract()
+    } else {
+        session
+            .call_method1("get_config_variable", ("region",))?
+            .extract()
+    }
+}
+
+/// Endpoint S3 clients of a session are configured with, when it isn't one of
+/// AWS's own.
+fn session_endpoint(session: &Bound<'_, PyAny>) -> PyResult<Option<String>> {
+    let client = if session.hasattr("client")? {
+        session.call_method1("client", ("s3",))?
+    } else {
+        session.call_method1("create_client", ("s3",))?
+    };
+    let endpoint_url: Option<String> =
+        client.getattr("meta")?.getattr("endpoint_url")?.extract()?;
+    Ok(endpoint_url.filter(|url| !url.contains(".amazonaws.com")))
+}
+
+#[pymethods]
+impl S3Config {
+    #[new]
+    #[pyo3(signature = (region_name, endpoint_url=None, force_path_style=false, anonymous=false, use_dualstack_endpoint=false, use_fips_endpoint=false, use_accelerate_endpoint=false, connect_timeout=None, read_timeout=None, operation_timeout=None, operation_attempt_timeout=None, max_idle_connections_per_host=None, role_arn=None, role_session_name=None, external_id=None, role_duration=None, profile_name=None, credentials_provider=None, headers=None, before_request=None, after_response=None, app_name=None))]
+    #[allow(clippy::too_many_arguments)]
+    fn py_new(
+        region_name: &str,
+        endpoint_url: Option<String>,
+        force_path_style: bool,
+        anonymous: bool,
+        use_dualstack_endpoint: bool,
+        use_fips_endpoint: bool,
+        use_accelerate_endpoint: bool,
+        connect_timeout: Option<f64>,
+        read_timeout: Option<f64>,
+        operation_timeout: Option<f64>,
+        operation_attempt_timeout: Option<f64>,
+        max_idle_connections_per_host: Option<usize>,
+        role_arn: Option<String>,
+        role_session_name: Option<String>,
+        external_id: Option<String>,
+        role_duration: Option<f64>,
+        profile_name: Option<String>,
+        credentials_provider: Option<Bound<'_, PyAny>>,
+        headers: Option<BTreeMap<String, String>>,
+        before_request: Option<Bound<'_, PyAny>>,
+        after_response: Option<Bound<'_, PyAny>>,
+        app_name: Option<String>,
+    ) -> PyResult<Self> {
+        let flags = ClientFlags {
+            force_path_style,
+            anonymous,
+            use_dualstack_endpoint,
+            use_fips_endpoint,
+            use_accelerate_endpoint,
+        };
+        flags.check()?;
+        let tuning = ConnectionTuning {
+            connect_timeout: timeout_from_secs("connect_timeout", connect_timeout)?,
+            read_timeout: timeout_from_secs("read_timeout", read_timeout)?,
+            operation_timeout: timeout_from_secs("operation_timeout", operation_timeout)?,
+            operation_attempt_timeout: timeout_from_secs(
+                "operation_attempt_timeout",
+                operation_attempt_timeout,
+            )?,
+            max_idle_connections_per_host,
+        };
+        let credential_source = CredentialSource {
+            profile_name,
+            assume_role: AssumeRole::from_args(
+                role_arn,
+                role_session_name,
+                external_id,
+                role_duration,
+            )?,
+            callback: credentials_provider
+                .map(PythonCredentials::new)
+                .transpose()?,
+        };
+        if anonymous && credential_source.assume_role.is_some() {
+            return Err(PyValueError::new_err(
+                "role_arn can't be used with anonymous",
+            ));
+        }
+        if anonymous && credential_source.callback.is_some() {
+            return Err(PyValueError::new_err(
+                "credentials_provider can't be used with anonymous",
+            ));
+        }
+        if let Some(name) = &app_name {
+            AppName::new(name.clone()).map_err(|e| {
+                PyValueError::new_err(format!("Invalid app_name '{}': {}", name, e))
+            })?;
+        }
+        Ok(Self::new_blocking(
+            region_name.to_string(),
+            endpoint_url,
+            flags,
+            tuning,
+            credential_source,
+            RequestHooks::from_args(headers, before_request, after_response)?,
+            app_name,
+        ))
+    }
+
+    /// Builds a configuration from a boto3 or botocore session, taking its
+    /// region, credentials and custom endpoint, or from botocore credentials.
+    /// `kwargs` go to the constructor and take precedence.
+    #[staticmethod]
+    #[pyo3(signature = (session, **kwargs))]
+    fn from_boto3<'py>(
+        session: &Bound<'py, PyAny>,
+        kwargs: Option<&Bound<'py, PyDict>>,
+    ) -> PyResult<Bound<'py, PyAny>> {
+        let py = session.py();
+        let arguments = PyDict::new(py);
+        if session.hasattr("get_credentials")? {
+            let credentials = session.call_method0("get_credentials")?;
+            if credentials.is_none() {
+                return Err(PyValueError::new_err("The session has no credentials"));
+            }
+            arguments.set_item("credentials_provider", credentials)?;
+            arguments.set_item("region_name", session_region(session)?)?;
+            arguments.set_item("endpoint_url", session_endpoint(session)?)?;
+        } else {
+            arguments.set_item("credentials_provider", session)?;
+        }
+        if let Some(kwargs) = kwargs {
+            arguments.update(kwargs.as_mapping())?;
+        }
+        if arguments
+            .get_item("region_name")?
+            .is_none_or(|region_name| region_name.is_none())
+        {
+            return Err(PyValueError::new_err(
+                "region_name must be given when the session has no region",
+            ));
+        }
+        py.get_type::<S3Config>().call((), Some(&arguments))
+    }
+
+    #[getter]
+    fn connect_timeout(&self) -> Option<f64> {
+        self.tuning.connect_timeout.map(|d| d.as_secs_f64())
+    }
+
+    #[getter]
+    fn read_timeout(&self) -> Option<f64> {
+        self.tuning.read_timeout.map(|d| d.as_secs_f64())
+    }
+
+    #[getter]
+    fn operation_timeout(&self) -> Option<f64> {
+        self.tuning.operation_timeout.map(|d| d.as_secs_f64())
+    }
+
+    #[getter]
+    fn operation_attempt_timeout(&self) -> Option<f64> {
+        self.tuning
+            .operation_attempt_timeout
+            .map(|d| d.as_secs_f64())
+    }
+
+    #[getter]
+    fn max_idle_connections_per_host(&self) -> Option<usize> {
+        self.tuning.max_idle_connections_per_host
+    }
+
+    #[getter]
+    fn profile_name(&self) -> Option<String> {
+        self.credential_source.profile_name.clone()
+    }
+
+    #[getter]
+    fn credentials_provider(&self, py: Python<'_>) -> Option<Py<PyAny>> {
+        self.credential_source
+            .callback
+            .as_ref()
+            .map(|callback| callback.object(py))
+    }
+
+    #[getter]
+    fn headers(&self) -> BTreeMap<String, String> {
+        self.hooks.headers.clone()
+    }
+
+    #[getter]
+    fn before_request(&self, py: Python<'_>) -> Option<Py<PyAny>> {
+        self.hooks
+            .before_request
+            .as_ref()
+            .map(|hook| hook.clone_ref(py))
+    }
+
+    #[getter]
+    fn after_response(&self, py: Python<'_>) -> Option<Py<PyAny>> {
+        self.hooks
+            .after_response
+            .as_ref()
+            .map(|hook| hook.clone_ref(py))
+    }
+
+    #[getter]
+    fn role_arn(&self) -> Option<String> {
+        self.credential_source
+            .assume_role
+            .as_ref()
+            .map(|role| role.role_arn.clone())
+    }
+
+    #[getter]
+    fn role_session_name(&self) -> Option<String> {
+        self.credential_source
+            .assume_role
+            .as_ref()
+            .and_then(|role| role.session_name.clone())
+    }
+
+    #[getter]
+    fn external_id(&self) -> Option<String> {
+        self.credential_source
+            .assume_role
+            .as_ref()
+            .and_then(|role| role.external_id.clone())
+    }
+
+    #[getter]
+    fn role_duration(&self) -> Option<f64> {
+        self.credential_source
+            .assume_role
+            .as_ref()
+            .and_then(|role| role.duration)
+            .map(|d| d.as_secs_f64())
+    }
+
+    /// Pickles the configuration as its constructor arguments; unpickling
+    /// builds a new client, resolving credentials again where it runs.
+    fn __getnewargs_ex__<'py>(&self, py: Python<'py>) -> PyResult<((String,), Bound<'py, PyDict>)> {
+        let kwargs = PyDict::new(py);
+        kwargs.set_item("endpoint_url", &self.endpoint_url)?;
+        kwargs.set_item("force_path_style", self.force_path_style)?;
+        kwargs.set_item("anonymous", self.anonymous)?;
+        kwargs.set_item("use_dualstack_endpoint", self.use_dualstack_endpoint)?;
+        kwargs.set_item("use_fips_endpoint", self.use_fips_endpoint)?;
+        kwargs.set_item("use_accelerate_endpoint", self.use_accelerate_endpoint)?;
+        kwargs.set_item("connect_timeout", self.connect_timeout())?;
+        kwargs.set_item("read_timeout", self.read_timeout())?;
+        kwargs.set_item("operation_timeout", self.operation_timeout())?;
+        kwargs.set_item(
+            "operation_attempt_timeout",
+            self.operation_attempt_timeout(),
+        )?;
+        kwargs.set_item(
+            "max_idle_connections_per_host",
+            self.max_idle_connections_per_host(),
+        )?;
+        kwargs.set_item("role_arn", self.role_arn())?;
+        kwargs.set_item("role_session_name", self.role_session_name())?;
+        kwargs.set_item("external_id", self.external_id())?;
+        kwargs.set_item("role_duration", self.role_duration())?;
+        kwargs.set_item("profile_name", &self.credential_source.profile_name)?;
+        kwargs.set_item("headers", self.headers())?;
+        kwargs.set_item("app_name", &self.app_name)?;
+        kwargs.set_item("before_request", self.before_request(py))?;
+        kwargs.set_item("after_response", self.after_response(py))?;
+        kwargs.set_item(
+            "credentials_provider",
+            self.credential_source
+                .callback
+                .as_ref()
+                .map(|callback| callback.object(py)),
+        )?;
+        Ok(((self.region_name.clone(),), kwargs))
+    }
+
+    fn __repr__(&self) -> String {
+        let endpoint_url = match &self.endpoint_url {
+            Some(url) => format!("'{}'", url),
+            None => "None".to_string(),
+        };
+        let boolean = |value: bool| if value { "True" } else { "False" };
+        format!(
+            "S3Config(region_name='{}', endpoint_url={}, force_path_style={}, anonymous={}, use_dualstack_endpoint={}, use_fips_endpoint={}, use_accelerate_endpoint={})",
+            self.region_name,
+            endpoint_url,
+            boolean(self.force_path_style),
+            boolean(self.anonymous),
+            boolean(self.use_dualstack_endpoint),
+            boolean(self.use_fips_endpoint),
+            boolean(self.use_accelerate_endpoint)
+        )
     }
 }
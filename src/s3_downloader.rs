@@ -1,290 +1,5213 @@
-use std::path::Path;
-use std::sync::Arc;
+use std::borrow::Cow;
+use std::collections::hash_map::Entry;
+use std::collections::{HashMap, HashSet};
+use std::future::Future;
+use std::io::SeekFrom;
+use std::path::{Component, Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
 
-use crate::results::{Results, RustOperationResult};
+use crate::asyncio::future_into_py;
+use crate::attributes::{check_destination, FileAttributes, SymlinkPolicy};
+use crate::audit::AuditLog;
+use crate::batch_input::{ready_items, until_error, BatchInput};
+use crate::batch_iterator::BatchIterator;
+use crate::bundles::Manifest;
+use crate::byte_range::{content_range_start, ByteRange};
+use crate::checksums::{verify_manifest, with_manifest, Sha256Sums};
+use crate::conditions::{is_not_modified, GetConditions, Timestamp};
+use crate::decompress::{is_gzipped, Gunzip};
+use crate::disk_space::ensure_space;
+use crate::encryption::{ClientEncryption, Envelope};
+use crate::error::TransferError;
+use crate::exceptions::RobinzhonError;
+use crate::extract::{self, ArchiveFormat, BodyReader, QUEUED_CHUNKS};
+use crate::failures::{checked, failure_limit, partial_failure, until_limit, FailureLimit};
+use crate::filters::{ObjectFilter, PathFilter};
+use crate::integrity::{
+    check_length, check_md5, digest_etag, encrypted_etag, request_checksums, BodyDigest,
+};
+use crate::inventory::{InventoryManifest, InventoryReader};
+use crate::limiter::{acquire, TransferLimiter};
+use crate::listing::{
+    list_object_versions, list_prefix, select_objects, ListRequest, ObjectInfo, ObjectListing,
+    ObjectVersion,
+};
+use crate::memory_budget::{reserve, MemoryBudget, Reservation};
+use crate::metadata::{head_object, head_objects, object_exists, ObjectMetadata};
+use crate::object_stream::ObjectStream;
+use crate::overwrite::OverwritePolicy;
+use crate::pause::{before_part, gated, PauseGate};
+use crate::plan::{Planned, TransferPlan};
+use crate::progress::{AttemptProgress, ProgressState, TransferProgress};
+use crate::rate_limit::{paced, throttle, TokenBucket};
+use crate::restore::{
+    poll_durations, restore_days, restore_objects, wait_for_restore, RestoreTier,
+};
+use crate::results::{
+    with_partial_results, Completed, FailedTransfer, ItemReport, ItemStats, Results,
+    RustOperationResult, TransferKind, TransferRequest,
+};
+use crate::retry::RetryPolicy;
+use crate::runtime::{block_on_interruptible, build_runtime};
 use crate::s3_config::S3Config;
-use futures::stream::{self, StreamExt};
-use pyo3::exceptions::PyRuntimeError;
-use pyo3::{pyclass, pymethods, PyResult};
-use tokio::fs::File;
-use tokio::io::{AsyncWriteExt, BufWriter};
+use crate::s3_file::S3File;
+use crate::s3_uri::S3Uri;
+use crate::scheduling::Scheduling;
+use crate::select::{SelectCompression, SelectInput, SelectOutput, SelectQuery};
+use crate::session::SessionJournal;
+use crate::settings::{
+    ClientState, SharedState, TransferSettings, DEFAULT_CHUNK_SIZE, DEFAULT_PART_CONCURRENCY,
+    DEFAULT_PART_SIZE, DEFAULT_WRITE_BUFFER_SIZE,
+};
+use crate::sync::{
+    composite_md5, delete_extraneous, file_composite_md5, file_md5, needs_download, plain_md5,
+    walk_local, LocalFile, SyncCache, SyncChanges, SyncReport,
+};
+use crate::transfer_manifest::{read_manifest, ManifestFormat};
+use aws_sdk_s3::primitives::ByteStream;
+use futures::future;
+use futures::stream::{self, BoxStream, Stream, StreamExt, TryStreamExt};
+use log::warn;
+use pyo3::exceptions::PyValueError;
+use pyo3::types::{PyDict, PyDictMethods};
+use pyo3::{
+    pyclass, pymethods, Bound, FromPyObject, IntoPyObject, Py, PyAny, PyErr, PyRef, PyResult,
+    Python,
+};
+use ring::digest::{Context, SHA256};
+use tokio::fs::{File, OpenOptions};
+use tokio::io::{AsyncSeekExt, AsyncWriteExt, BufWriter};
+use tokio::runtime::Builder;
+use tokio::sync::mpsc;
 
-#[pyclass]
+/// Safe to call from many threads at once, free-threaded builds included:
+/// every call runs with a snapshot of the client state.
+#[pyclass(frozen, module = "robinzhon")]
 pub struct S3Downloader {
-    s3_config: Arc<S3Config>,
-    max_concurrent_downloads: usize,
+    state: SharedState,
+    /// Constructor arguments the instance is built from again by `reinitialize`
+    /// and when it is unpickled.
+    runtime_threads: Option<usize>,
+    client_encryption: Option<ClientEncryption>,
+    max_bandwidth_bytes_per_sec: Option<u64>,
+    max_requests_per_sec: Option<f64>,
+    request_burst: Option<u32>,
+    limiter: Option<TransferLimiter>,
+    max_in_flight_bytes: Option<u64>,
+}
+
+/// Removes a partially written download unless the transfer completes.
+struct PartialFileGuard<'a> {
+    path: &'a str,
+    committed: bool,
+}
+
+impl<'a> PartialFileGuard<'a> {
+    fn new(path: &'a str) -> Self {
+        Self {
+            path,
+            committed: false,
+        }
+    }
+
+    fn commit(mut self) {
+        self.committed = true;
+    }
+}
+
+impl Drop for PartialFileGuard<'_> {
+    fn drop(&mut self) {
+        if !self.committed {
+            let _ = std::fs::remove_file(self.path);
+        }
+    }
+}
+
+/// Sizes a file about to be written to the `length` announced by the response,
+/// so it is laid out in one go rather than grown write by write.
+async fn preallocate(file: &File, local_path: &str, length: u64) -> Result<(), TransferError> {
+    file.set_len(length)
+        .await
+        .map_err(|e| TransferError::new(format!("Failed to allocate file '{}': {}", local_path, e)))
+}
+
+/// Bytes to reserve for a response body read into memory: those its
+/// Content-Length announces or, for a chunked body that announces none, a write
+/// buffer's worth to start with, which `read_body` grows as the body arrives.
+fn body_length(content_length: Option<i64>, write_buffer_size: usize) -> u64 {
+    content_length.map_or(write_buffer_size as u64, |length| length.max(0) as u64)
+}
+
+/// Reads a GET response body into memory, counting it in `attempt_progress`
+/// and growing `reserved` to cover it.
+async fn read_body(
+    mut body: ByteStream,
+    content_length: Option<i64>,
+    object_key: &str,
+    attempt_progress: &mut AttemptProgress<'_>,
+    bandwidth: Option<&TokenBucket>,
+    reserved: &mut Reservation<'_>,
+) -> Result<Vec<u8>, TransferError> {
+    let content_length = content_length.map(|length| length.max(0) as u64);
+    attempt_progress.add_total_bytes(content_length.unwrap_or_default());
+
+    let mut buffer = Vec::with_capacity(content_length.unwrap_or_default() as usize);
+    while let Some(bytes) = body.try_next().await.map_err(|e| {
+        TransferError::retryable(format!(
+            "Failed to read S3 response body for '{}': {}",
+            object_key, e
+        ))
+    })? {
+        buffer.extend_from_slice(&bytes);
+        reserved.grow_to(buffer.len() as u64).await;
+        attempt_progress.add_bytes(bytes.len() as u64);
+        throttle(bandwidth, bytes.len() as u64).await;
+    }
+    check_length(object_key, content_length, buffer.len() as u64)?;
+    Ok(buffer)
+}
+
+/// Joins an object key, or the part of it below a listed prefix, to the
+/// directory it is downloaded into.
+///
+/// Keys are untrusted input: empty and `.` segments are dropped and `..` segments
+/// resolved. Returns `None` for keys that would land outside `base_directory` (or
+/// on it), and for segments the platform reads as more than a plain name, such as
+/// `C:` or `a\b` on Windows.
+pub fn contained_path(base_directory: &Path, relative_key: &str) -> Option<PathBuf> {
+    let mut segments = Vec::new();
+    for segment in relative_key.split('/') {
+        match segment {
+            "" | "." => {}
+            ".." => {
+                segments.pop()?;
+            }
+            _ => {
+                let mut components = Path::new(segment).components();
+                match (components.next(), components.next()) {
+                    (Some(Component::Normal(_)), None) => segments.push(segment),
+                    _ => return None,
+                }
+            }
+        }
+    }
+
+    (!segments.is_empty()).then(|| base_directory.join(segments.iter().collect::<PathBuf>()))
+}
+
+/// A `(key, local_path)` download of a batch, or the failure of a key that
+/// couldn't be given a local path.
+type DownloadRequest = Result<TransferRequest, Box<FailedTransfer>>;
+
+/// An item of `download_multiple_files_with_paths`: `(key, local_path)` for the
+/// latest version of the object, or `(key, local_path, version_id)` for a given
+/// one, either followed by `(offset, length)` to download only that slice.
+#[derive(FromPyObject)]
+enum DownloadItem {
+    VersionedSlice(String, String, Option<String>, ByteRange),
+    Versioned(String, String, Option<String>),
+    Slice(String, String, ByteRange),
+    Latest(String, String),
+}
+
+impl From<DownloadItem> for TransferRequest {
+    fn from(item: DownloadItem) -> Self {
+        let (source, destination, version_id, range) = match item {
+            DownloadItem::VersionedSlice(source, destination, version_id, range) => {
+                (source, destination, version_id, Some(range))
+            }
+            DownloadItem::Versioned(source, destination, version_id) => {
+                (source, destination, version_id, None)
+            }
+            DownloadItem::Slice(source, destination, range) => {
+                (source, destination, None, Some(range))
+            }
+            DownloadItem::Latest(source, destination) => (source, destination, None, None),
+        };
+        Self {
+            version_id,
+            range,
+            ..Self::from((source, destination))
+        }
+    }
+}
+
+/// What `download_ranges` returns: the file the slices were written into, or
+/// the slices themselves in the order they were asked for.
+#[derive(IntoPyObject)]
+enum Slices {
+    Stored(String),
+    Buffers(Vec<Cow<'static, [u8]>>),
+}
+
+/// A slice of an object held in memory, and where it starts in the object.
+struct Slice {
+    start: u64,
+    bytes: Vec<u8>,
+    etag: Option<String>,
+}
+
+/// The failure reported for an object `contained_path` refused to place.
+fn rejected_key(object_key: &str, base_directory: &Path) -> Box<FailedTransfer> {
+    let error = TransferError::new(format!(
+        "Object key '{}' does not map to a file inside '{}'",
+        object_key,
+        base_directory.display()
+    ));
+    Box::new(FailedTransfer::new(
+        object_key,
+        (object_key.to_string(), String::new()),
+        error,
+    ))
+}
+
+/// The request of a `(bucket, key, local_path)` item of a batch spanning buckets,
+/// which addresses the object by URI.
+fn object_download(
+    (bucket_name, object_key, local_path): (String, String, String),
+) -> DownloadRequest {
+    let object = S3Uri {
+        bucket_name,
+        object_key,
+    };
+    Ok(TransferRequest::from((object.to_string(), local_path)))
+}
+
+/// The requests of `download_multiple_files_with_paths` items, each with the
+/// conditions under which it is transferred. `etags` are keyed by object key.
+fn conditional_downloads(
+    downloads: BatchInput<DownloadItem>,
+    etags: Option<HashMap<String, String>>,
+    modified_since: Option<Timestamp>,
+) -> impl Stream<Item = PyResult<DownloadRequest>> + Send {
+    let etags = etags.unwrap_or_default();
+    downloads.into_stream().map_ok(move |item| {
+        let mut request = TransferRequest::from(item);
+        request.conditions =
+            GetConditions::new(etags.get(&request.source).cloned(), modified_since);
+        Ok(request)
+    })
+}
+
+/// A presigned URL without its query string, which carries the signature.
+fn unsigned_url(url: &str) -> &str {
+    url.split_once('?').map_or(url, |(unsigned, _)| unsigned)
+}
+
+/// What `sync_down` has to do, worked out before anything is transferred.
+#[derive(Default)]
+struct SyncDownPlan {
+    /// Every object to download, or to report as unstorable.
+    downloads: Vec<DownloadRequest>,
+    /// Keys to download, by reason, and keys already up to date.
+    changes: SyncChanges,
+    /// Relative path, ETag and size of each pending download, by local path.
+    pending: HashMap<String, (String, Option<String>, u64)>,
+    /// Cache entries of the skipped files; downloads are added once they succeed.
+    cache: SyncCache,
+    /// Relative paths of local files with no matching object.
+    extraneous: Vec<String>,
+}
+
+impl SyncDownPlan {
+    /// What running the sync would do, for a dry run.
+    fn into_transfer_plan(self, local_dir: &Path) -> TransferPlan {
+        let mut plan = TransferPlan::download();
+        for download in self.downloads {
+            match download {
+                Ok(request) => {
+                    let size = self
+                        .pending
+                        .get(&request.destination)
+                        .map_or(0, |(_, _, size)| *size);
+                    plan.transfer(request.source, request.destination, size);
+                }
+                Err(failure) => plan.fail(*failure),
+            }
+        }
+        for object_key in self.changes.skipped {
+            plan.skip(object_key);
+        }
+        let deletions = self
+            .extraneous
+            .iter()
+            .map(|relative_path| local_dir.join(relative_path).to_string_lossy().to_string())
+            .collect();
+        plan.deleting(deletions)
+    }
+}
+
+/// The first range of an object fetched in parts, and what it revealed about it.
+struct FirstPart {
+    object_size: u64,
+    length: u64,
+    etag: Option<String>,
+    /// The ETag, unless server-side encryption keeps it from being an MD5.
+    digest: Option<String>,
+    attributes: FileAttributes,
+    transferred: u64,
+    total: u64,
+}
+
+/// Where a single-GET download was cut short: the ETag of the object and how
+/// many bytes of it are already on disk.
+struct ResumePoint {
+    etag: String,
+    offset: u64,
+}
+
+/// Downloads one object as concurrent ranged GETs written straight into their
+/// offsets of a preallocated file.
+#[derive(Clone, Copy)]
+struct RangedDownload<'a> {
+    s3_config: &'a S3Config,
+    bucket_name: &'a str,
+    object_key: &'a str,
+    version_id: Option<&'a str>,
+    conditions: &'a GetConditions,
+    local_path: &'a str,
+    progress: &'a ProgressState,
+    bandwidth: Option<&'a TokenBucket>,
+    memory: Option<&'a MemoryBudget>,
+    write_buffer_size: usize,
+    verify_checksum: bool,
+}
+
+impl RangedDownload<'_> {
+    /// Returns `Ok(None)` for empty objects, which can't be fetched by range,
+    /// and for objects to decompress, which only inflate from the start.
+    async fn run(self, settings: &TransferSettings) -> Result<Option<Completed>, TransferError> {
+        let retry_policy = &settings.retry_policy;
+        let first = match self
+            .s3_config
+            .run(self.bucket_name, retry_policy, || {
+                self.first_part(settings.part_size, settings.auto_decompress)
+            })
+            .await
+        {
+            Ok(Some(first)) => first,
+            Ok(None) => return Ok(None),
+            Err(error) if error.code.as_deref() == Some("InvalidRange") => return Ok(None),
+            Err(error) => return Err(error),
+        };
+
+        let guard = PartialFileGuard::new(self.local_path);
+        let mut file_progress = AttemptProgress::new(self.progress);
+        file_progress.absorb(first.transferred, first.total);
+
+        let etag = first.etag.as_deref();
+        let ranges = (first.length..first.object_size)
+            .step_by(settings.part_size as usize)
+            .map(|start| {
+                (
+                    start,
+                    (start + settings.part_size).min(first.object_size) - 1,
+                )
+            });
+
+        // A session checkpoints how far the parts written so far reach
+        // without a gap.
+        let journal = settings.journal.as_deref();
+        let mut written = HashMap::new();
+        let mut contiguous = first.length;
+        if let (Some(journal), Some(etag)) = (journal, etag) {
+            journal.downloaded_to(self.local_path, etag, contiguous);
+        }
+
+        let pause = settings.pause.as_deref();
+        stream::iter(ranges.map(|(start, end)| async move {
+            before_part(pause).await;
+            self.s3_config
+                .run(self.bucket_name, retry_policy, move || {
+                    self.part(start, end, etag)
+                })
+                .await
+                .map(|progress| (start, end, progress))
+        }))
+        .buffer_unordered(settings.part_concurrency)
+        .try_for_each(|(start, end, (transferred, total))| {
+            file_progress.absorb(transferred, total);
+            if let (Some(journal), Some(etag)) = (journal, etag) {
+                written.insert(start, end + 1);
+                let reached = contiguous;
+                while let Some(end) = written.remove(&contiguous) {
+                    contiguous = end;
+                }
+                if contiguous > reached {
+                    journal.downloaded_to(self.local_path, etag, contiguous);
+                }
+            }
+            future::ready(Ok(()))
+        })
+        .await?;
+
+        // Parts are written out of order, so the file is hashed once complete.
+        let digest = first.digest.as_deref().filter(|_| self.verify_checksum);
+        if let Some(expected) = digest.and_then(plain_md5) {
+            let actual = file_md5(Path::new(self.local_path)).await?;
+            check_md5(self.object_key, expected, &actual)?;
+        } else if let Some((expected, parts)) = digest.and_then(composite_md5) {
+            // The ETag of a multipart upload hashes the parts it was uploaded
+            // in, whose size only S3 knows. An object whose first part can't
+            // be sized relies on the checksums of each part instead.
+            let uploaded_part_size = self.uploaded_part_size(etag).await;
+            if let Some(part_size) = uploaded_part_size
+                .filter(|&part_size| parts == first.object_size.div_ceil(part_size))
+            {
+                let actual = file_composite_md5(Path::new(self.local_path), part_size).await?;
+                check_md5(self.object_key, expected, &actual)?;
+            }
+        }
+        if let Some(sums) = settings.sha256.as_deref() {
+            sums.check(self.object_key, self.local_path, None).await?;
+        }
+        let kept = first.attributes.apply(self.local_path, settings).await?;
+
+        guard.commit();
+        file_progress.commit();
+        let path = self.local_path.to_string();
+        Ok(Some(if kept {
+            Completed::Transferred(path)
+        } else {
+            Completed::Skipped(path)
+        }))
+    }
+
+    /// Returns `Ok(None)`, before creating the file, when `auto_decompress`
+    /// finds the object gzip-encoded.
+    async fn first_part(
+        self,
+        part_size: u64,
+        auto_decompress: bool,
+    ) -> Result<Option<FirstPart>, TransferError> {
+        self.progress.start_attempt();
+        let request = self
+            .s3_config
+            .client_for(self.bucket_name)
+            .get_object()
+            .bucket(self.bucket_name)
+            .key(self.object_key)
+            .set_version_id(self.version_id.map(str::to_string))
+            .range(format!("bytes=0-{}", part_size - 1));
+        let request = request_checksums(self.conditions.apply(request), self.verify_checksum);
+        let response = request.send().await.map_err(|e| {
+            TransferError::from_sdk(format!("Failed to get S3 object '{}'", self.object_key), &e)
+        })?;
+        if auto_decompress && is_gzipped(self.object_key, response.content_encoding()) {
+            return Ok(None);
+        }
+
+        let content_length = response.content_length.map(|length| length.max(0) as u64);
+        let length = content_length.unwrap_or_default();
+        // Stores that ignore `Range` answer with the whole object.
+        let object_size = response
+            .content_range()
+            .and_then(|range| range.rsplit_once('/'))
+            .and_then(|(_, size)| size.parse().ok())
+            .unwrap_or(length);
+
+        let mut attempt_progress = AttemptProgress::new(self.progress);
+        attempt_progress.add_total_bytes(object_size);
+
+        let file = File::create(self.local_path).await.map_err(|e| {
+            TransferError::new(format!(
+                "Failed to create file '{}': {}",
+                self.local_path, e
+            ))
+        })?;
+        let guard = PartialFileGuard::new(self.local_path);
+        preallocate(&file, self.local_path, object_size).await?;
+
+        let digest = digest_etag(&response).map(str::to_string);
+        self.write_body(file, response.body, content_length, &mut attempt_progress)
+            .await?;
+
+        guard.commit();
+        let (transferred, total) = attempt_progress.commit();
+        Ok(Some(FirstPart {
+            object_size,
+            length,
+            digest,
+            etag: response.e_tag,
+            attributes: FileAttributes::of(
+                response.metadata.as_ref(),
+                response.last_modified.as_ref(),
+            ),
+            transferred,
+            total,
+        }))
+    }
+
+    /// Size of the first part the object was uploaded in, from a HEAD of that
+    /// part; `None` if it can't be had.
+    async fn uploaded_part_size(self, etag: Option<&str>) -> Option<u64> {
+        let response = self
+            .s3_config
+            .client_for(self.bucket_name)
+            .head_object()
+            .bucket(self.bucket_name)
+            .key(self.object_key)
+            .set_version_id(self.version_id.map(str::to_string))
+            .part_number(1)
+            .set_if_match(etag.map(str::to_string))
+            .send()
+            .await
+            .ok()?;
+        response
+            .content_length
+            .filter(|&length| length > 0)
+            .map(|length| length as u64)
+    }
+
+    async fn part(
+        self,
+        start: u64,
+        end: u64,
+        etag: Option<&str>,
+    ) -> Result<(u64, u64), TransferError> {
+        // `If-Match` makes sure every part comes from the same object version.
+        let request = self
+            .s3_config
+            .client_for(self.bucket_name)
+            .get_object()
+            .bucket(self.bucket_name)
+            .key(self.object_key)
+            .set_version_id(self.version_id.map(str::to_string))
+            .range(format!("bytes={}-{}", start, end))
+            .set_if_match(etag.map(str::to_string));
+        let response = request_checksums(request, self.verify_checksum)
+            .send()
+            .await
+            .map_err(|e| {
+                TransferError::from_sdk(
+                    format!(
+                        "Failed to get bytes {}-{} of S3 object '{}'",
+                        start, end, self.object_key
+                    ),
+                    &e,
+                )
+            })?;
+
+        let mut attempt_progress = AttemptProgress::new(self.progress);
+        let mut file = OpenOptions::new()
+            .write(true)
+            .open(self.local_path)
+            .await
+            .map_err(|e| {
+                TransferError::new(format!("Failed to open file '{}': {}", self.local_path, e))
+            })?;
+        file.seek(SeekFrom::Start(start)).await.map_err(|e| {
+            TransferError::new(format!(
+                "Failed to seek in file '{}': {}",
+                self.local_path, e
+            ))
+        })?;
+
+        let content_length = response.content_length.map(|length| length.max(0) as u64);
+        self.write_body(file, response.body, content_length, &mut attempt_progress)
+            .await?;
+        Ok(attempt_progress.commit())
+    }
+
+    async fn write_body(
+        self,
+        file: File,
+        mut body: ByteStream,
+        content_length: Option<u64>,
+        attempt_progress: &mut AttemptProgress<'_>,
+    ) -> Result<(), TransferError> {
+        let _reserved = reserve(self.memory, self.write_buffer_size as u64).await;
+        let mut writer = BufWriter::with_capacity(self.write_buffer_size, file);
+        let mut received = 0;
+
+        while let Some(bytes) = body.try_next().await.map_err(|e| {
+            TransferError::retryable(format!(
+                "Failed to read S3 response body for '{}': {}",
+                self.object_key, e
+            ))
+        })? {
+            writer.write_all(&bytes).await.map_err(|e| {
+                TransferError::new(format!(
+                    "Failed to write to file '{}': {}",
+                    self.local_path, e
+                ))
+            })?;
+            received += bytes.len() as u64;
+            attempt_progress.add_bytes(bytes.len() as u64);
+            throttle(self.bandwidth, bytes.len() as u64).await;
+        }
+
+        writer.flush().await.map_err(|e| {
+            TransferError::new(format!("Failed to flush file '{}': {}", self.local_path, e))
+        })?;
+        check_length(self.object_key, content_length, received)
+    }
 }
 
 impl S3Downloader {
+    pub fn state(&self) -> Arc<ClientState> {
+        self.state.get()
+    }
+
+    /// Downloads the object to `local_path`, decompressed when `auto_decompress`
+    /// finds it gzipped. When `conditions` don't hold, fails with the "304 Not
+    /// Modified" error and leaves `local_path` untouched. The outcome is appended
+    /// to the audit log, if the downloader keeps one.
+    #[allow(clippy::too_many_arguments)]
     async fn download_single_file(
         s3_config: Arc<S3Config>,
         bucket_name: &str,
         object_key: &str,
+        version_id: Option<&str>,
+        conditions: &GetConditions,
+        local_path: &str,
+        progress: &ProgressState,
+        settings: &TransferSettings,
+    ) -> Result<Completed, TransferError> {
+        let downloaded = Self::download_to_file(
+            s3_config,
+            bucket_name,
+            object_key,
+            version_id,
+            conditions,
+            local_path,
+            progress,
+            settings,
+        )
+        .await;
+        if let Some(audit) = settings.audit.as_deref() {
+            audit
+                .record(
+                    TransferKind::Download,
+                    bucket_name,
+                    object_key,
+                    version_id,
+                    local_path,
+                    &downloaded,
+                )
+                .await;
+        }
+        downloaded
+    }
+
+    /// `download_single_file`, left out of the audit log.
+    #[allow(clippy::too_many_arguments)]
+    async fn download_to_file(
+        s3_config: Arc<S3Config>,
+        bucket_name: &str,
+        object_key: &str,
+        version_id: Option<&str>,
+        conditions: &GetConditions,
+        local_path: &str,
+        progress: &ProgressState,
+        settings: &TransferSettings,
+    ) -> Result<Completed, TransferError> {
+        let _slot = acquire(settings.limiter.as_deref()).await;
+        if let Some(envelope) = settings.client_encryption.as_deref() {
+            return s3_config
+                .run(bucket_name, &settings.retry_policy, || {
+                    Self::download_decrypted_attempt(
+                        &s3_config,
+                        bucket_name,
+                        object_key,
+                        version_id,
+                        conditions,
+                        local_path,
+                        progress,
+                        envelope,
+                        settings,
+                    )
+                })
+                .await;
+        }
+
+        // A session continues a download from where it checkpointed the file,
+        // with a single GET for the rest of the object.
+        let mut checkpoint = settings
+            .journal
+            .as_deref()
+            .and_then(|journal| journal.download_checkpoint(local_path));
+        if checkpoint.is_some() && tokio::fs::metadata(local_path).await.is_err() {
+            checkpoint = None;
+        }
+
+        if checkpoint.is_none()
+            && settings.part_concurrency > 1
+            && !(settings.auto_decompress && is_gzipped(object_key, None))
+        {
+            let ranged = RangedDownload {
+                s3_config: &s3_config,
+                bucket_name,
+                object_key,
+                version_id,
+                conditions,
+                local_path,
+                progress,
+                bandwidth: settings.bandwidth.as_deref(),
+                memory: settings.memory.as_deref(),
+                write_buffer_size: settings.write_buffer_size,
+                verify_checksum: settings.verify_checksum,
+            };
+            if let Some(completed) = ranged.run(settings).await? {
+                return Ok(completed);
+            }
+        }
+
+        let resume = Mutex::new(checkpoint.map(|checkpoint| ResumePoint {
+            etag: checkpoint.etag,
+            offset: checkpoint.offset,
+        }));
+        let downloaded = s3_config
+            .run(bucket_name, &settings.retry_policy, || {
+                Self::download_attempt(
+                    &s3_config,
+                    bucket_name,
+                    object_key,
+                    version_id,
+                    conditions,
+                    local_path,
+                    progress,
+                    settings,
+                    &resume,
+                )
+            })
+            .await;
+        // A file kept to resume from is only a fragment once retries run out.
+        let interrupted = resume.lock().unwrap().is_some();
+        if downloaded.is_err() && interrupted {
+            let _ = tokio::fs::remove_file(local_path).await;
+        }
+        downloaded
+    }
+
+    /// Downloads the `range` slice of the object to `local_path`, in a single GET
+    /// per attempt. The ETag and checksums S3 keeps describe the whole object, so
+    /// a slice is only checked against its Content-Length.
+    #[allow(clippy::too_many_arguments)]
+    async fn download_slice(
+        s3_config: &S3Config,
+        bucket_name: &str,
+        object_key: &str,
+        version_id: Option<&str>,
+        conditions: &GetConditions,
+        range: ByteRange,
+        local_path: &str,
+        progress: &ProgressState,
+        settings: &TransferSettings,
+    ) -> Result<String, TransferError> {
+        let _slot = acquire(settings.limiter.as_deref()).await;
+        // AES-GCM authenticates the whole ciphertext, which a slice isn't.
+        if settings.client_encryption.is_some() {
+            return Err(TransferError::new(format!(
+                "Can't download a range of '{}' with client-side encryption",
+                object_key
+            )));
+        }
+        s3_config
+            .run(bucket_name, &settings.retry_policy, || {
+                Self::download_slice_attempt(
+                    s3_config,
+                    bucket_name,
+                    object_key,
+                    version_id,
+                    conditions,
+                    range,
+                    local_path,
+                    progress,
+                    settings,
+                )
+            })
+            .await
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    async fn download_slice_attempt(
+        s3_config: &S3Config,
+        bucket_name: &str,
+        object_key: &str,
+        version_id: Option<&str>,
+        conditions: &GetConditions,
+        range: ByteRange,
         local_path: &str,
-    ) -> Result<String, String> {
+        progress: &ProgressState,
+        settings: &TransferSettings,
+    ) -> Result<String, TransferError> {
+        progress.start_attempt();
+        let request = s3_config
+            .client_for(bucket_name)
+            .get_object()
+            .bucket(bucket_name)
+            .key(object_key)
+            .set_version_id(version_id.map(str::to_string))
+            .range(range.header());
+        let response = conditions.apply(request).send().await.map_err(|e| {
+            TransferError::from_sdk(
+                format!("Failed to get range of S3 object '{}'", object_key),
+                &e,
+            )
+        })?;
+
+        let mut attempt_progress = AttemptProgress::new(progress);
+        let content_length = response.content_length.map(|length| length.max(0) as u64);
+        if let Some(content_length) = content_length {
+            attempt_progress.add_total_bytes(content_length);
+        }
+        let file = File::create(local_path).await.map_err(|e| {
+            TransferError::new(format!("Failed to create file '{}': {}", local_path, e))
+        })?;
+        let guard = PartialFileGuard::new(local_path);
+        let _reserved = reserve(
+            settings.memory.as_deref(),
+            settings.write_buffer_size as u64,
+        )
+        .await;
+        let mut writer = BufWriter::with_capacity(settings.write_buffer_size, file);
+        let mut sha256 = settings.sha256.as_ref().map(|_| Context::new(&SHA256));
+        let mut body = response.body;
+        let mut received = 0;
+        while let Some(bytes) = body.try_next().await.map_err(|e| {
+            TransferError::retryable(format!(
+                "Failed to read S3 response body for '{}': {}",
+                object_key, e
+            ))
+        })? {
+            writer.write_all(&bytes).await.map_err(|e| {
+                TransferError::new(format!("Failed to write to file '{}': {}", local_path, e))
+            })?;
+            if let Some(sha256) = &mut sha256 {
+                sha256.update(&bytes);
+            }
+            received += bytes.len() as u64;
+            attempt_progress.add_bytes(bytes.len() as u64);
+            throttle(settings.bandwidth.as_deref(), bytes.len() as u64).await;
+        }
+        writer.flush().await.map_err(|e| {
+            TransferError::new(format!("Failed to flush file '{}': {}", local_path, e))
+        })?;
+        check_length(object_key, content_length, received)?;
+        if let Some(sums) = settings.sha256.as_deref() {
+            sums.check(object_key, local_path, sha256).await?;
+        }
+
+        guard.commit();
+        attempt_progress.commit();
+        Ok(local_path.to_string())
+    }
+
+    /// Downloads the `ranges` slices of the object into memory, concurrently
+    /// after the first. The others are fetched with `If-Match` on the ETag of the
+    /// first, so that all of them come from the same version of the object.
+    #[allow(clippy::too_many_arguments)]
+    async fn download_slices(
+        s3_config: &S3Config,
+        bucket_name: &str,
+        object_key: &str,
+        version_id: Option<&str>,
+        ranges: &[ByteRange],
+        progress: &ProgressState,
+        settings: &TransferSettings,
+    ) -> Result<Vec<Slice>, TransferError> {
+        let _slot = acquire(settings.limiter.as_deref()).await;
+        if settings.client_encryption.is_some() {
+            return Err(TransferError::new(format!(
+                "Can't download a range of '{}' with client-side encryption",
+                object_key
+            )));
+        }
+        let Some((&first, rest)) = ranges.split_first() else {
+            return Ok(Vec::new());
+        };
+        let first = Self::fetch_slice(
+            s3_config,
+            bucket_name,
+            object_key,
+            version_id,
+            first,
+            None,
+            progress,
+            settings,
+        )
+        .await?;
+        let etag = first.etag.clone();
+        let pause = settings.pause.as_deref();
+        let rest: Vec<Slice> = stream::iter(rest.iter().copied().map(|range| {
+            let etag = etag.clone();
+            async move {
+                before_part(pause).await;
+                Self::fetch_slice(
+                    s3_config,
+                    bucket_name,
+                    object_key,
+                    version_id,
+                    range,
+                    etag,
+                    progress,
+                    settings,
+                )
+                .await
+            }
+        }))
+        .buffered(settings.part_concurrency)
+        .try_collect()
+        .await?;
+
+        let mut slices = vec![first];
+        slices.extend(rest);
+        Ok(slices)
+    }
+
+    /// Fetches the `range` slice of the object into memory, retrying it as
+    /// `settings` says. With `etag`, only from that version of the object.
+    #[allow(clippy::too_many_arguments)]
+    async fn fetch_slice(
+        s3_config: &S3Config,
+        bucket_name: &str,
+        object_key: &str,
+        version_id: Option<&str>,
+        range: ByteRange,
+        etag: Option<String>,
+        progress: &ProgressState,
+        settings: &TransferSettings,
+    ) -> Result<Slice, TransferError> {
+        s3_config
+            .run(bucket_name, &settings.retry_policy, || {
+                Self::slice_attempt(
+                    s3_config,
+                    bucket_name,
+                    object_key,
+                    version_id,
+                    range,
+                    etag.clone(),
+                    progress,
+                    settings,
+                )
+            })
+            .await
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    async fn slice_attempt(
+        s3_config: &S3Config,
+        bucket_name: &str,
+        object_key: &str,
+        version_id: Option<&str>,
+        range: ByteRange,
+        if_match: Option<String>,
+        progress: &ProgressState,
+        settings: &TransferSettings,
+    ) -> Result<Slice, TransferError> {
+        let checks_version = if_match.is_some();
+        let response = s3_config
+            .client_for(bucket_name)
+            .get_object()
+            .bucket(bucket_name)
+            .key(object_key)
+            .set_version_id(version_id.map(str::to_string))
+            .range(range.header())
+            .set_if_match(if_match)
+            .send()
+            .await
+            .map_err(|e| {
+                let error = TransferError::from_sdk(
+                    format!("Failed to get range of S3 object '{}'", object_key),
+                    &e,
+                );
+                if checks_version && error.http_status == Some(412) {
+                    TransferError::new(format!(
+                        "S3 object '{}' changed while its ranges were downloaded",
+                        object_key
+                    ))
+                } else {
+                    error
+                }
+            })?;
+
+        // Stores that ignore `Range` answer with the whole object.
+        let start = response
+            .content_range()
+            .and_then(content_range_start)
+            .unwrap_or(0);
+        let mut attempt_progress = AttemptProgress::new(progress);
+        let mut reserved = Reservation::new(
+            settings.memory.as_deref(),
+            body_length(response.content_length, settings.write_buffer_size),
+        )
+        .await;
+        let bytes = read_body(
+            response.body,
+            response.content_length,
+            object_key,
+            &mut attempt_progress,
+            settings.bandwidth.as_deref(),
+            &mut reserved,
+        )
+        .await?;
+        attempt_progress.commit();
+        Ok(Slice {
+            start,
+            bytes,
+            etag: response.e_tag,
+        })
+    }
+
+    /// Writes `slices` at their offsets of a new file at `local_path`, leaving
+    /// the bytes between them as zeros.
+    async fn write_slices(local_path: &str, slices: &[Slice]) -> Result<String, TransferError> {
+        let mut file = File::create(local_path).await.map_err(|e| {
+            TransferError::new(format!("Failed to create file '{}': {}", local_path, e))
+        })?;
+        let guard = PartialFileGuard::new(local_path);
+        for slice in slices {
+            file.seek(SeekFrom::Start(slice.start)).await.map_err(|e| {
+                TransferError::new(format!("Failed to seek in file '{}': {}", local_path, e))
+            })?;
+            file.write_all(&slice.bytes).await.map_err(|e| {
+                TransferError::new(format!("Failed to write to file '{}': {}", local_path, e))
+            })?;
+        }
+        file.flush().await.map_err(|e| {
+            TransferError::new(format!("Failed to flush file '{}': {}", local_path, e))
+        })?;
+        guard.commit();
+        Ok(local_path.to_string())
+    }
+
+    /// Downloads the `ranges` slices of the object, then writes them into
+    /// `local_path` or hands them back.
+    #[allow(clippy::too_many_arguments)]
+    async fn download_ranges_to(
+        s3_config: &S3Config,
+        bucket_name: &str,
+        object_key: &str,
+        version_id: Option<&str>,
+        ranges: &[ByteRange],
+        local_path: Option<&str>,
+        settings: &TransferSettings,
+    ) -> Result<Slices, TransferError> {
+        let slices = Self::download_slices(
+            s3_config,
+            bucket_name,
+            object_key,
+            version_id,
+            ranges,
+            &ProgressState::default(),
+            settings,
+        )
+        .await?;
+        match local_path {
+            Some(local_path) => Self::write_slices(local_path, &slices)
+                .await
+                .map(Slices::Stored),
+            None => Ok(Slices::Buffers(
+                slices
+                    .into_iter()
+                    .map(|slice| Cow::Owned(slice.bytes))
+                    .collect(),
+            )),
+        }
+    }
+
+    /// Downloads an archive and unpacks it below `dest_dir` as its body arrives,
+    /// so the packed archive is never stored. Each attempt unpacks it from the
+    /// start, over the files of the attempt before.
+    async fn download_extracted(
+        s3_config: &S3Config,
+        bucket_name: &str,
+        object_key: &str,
+        format: ArchiveFormat,
+        dest_dir: &str,
+        settings: &TransferSettings,
+    ) -> Result<RustOperationResult, TransferError> {
+        let _slot = acquire(settings.limiter.as_deref()).await;
+        if settings.client_encryption.is_some() {
+            return Err(TransferError::new(format!(
+                "Can't extract '{}' with client-side encryption",
+                object_key
+            )));
+        }
+        tokio::fs::create_dir_all(dest_dir).await.map_err(|e| {
+            TransferError::new(format!("Failed to create directory '{}': {}", dest_dir, e))
+        })?;
+
+        let started = Instant::now();
+        let mut result = s3_config
+            .run(bucket_name, &settings.retry_policy, || {
+                Self::extract_attempt(
+                    s3_config,
+                    bucket_name,
+                    object_key,
+                    format,
+                    dest_dir,
+                    settings,
+                )
+            })
+            .await?;
+        result.elapsed = started.elapsed();
+        Ok(result)
+    }
+
+    /// Downloads the manifest `manifest_key` of an `archive_mode` upload and
+    /// unpacks every bundle it names below `dest_dir`, so that every packed file
+    /// lands at its key. A bundle that can't be downloaded fails all its files.
+    async fn download_archived_bundles(
+        s3_config: &S3Config,
+        bucket_name: &str,
+        manifest_key: &str,
+        dest_dir: &str,
+        settings: &TransferSettings,
+    ) -> Result<RustOperationResult, TransferError> {
+        let started = Instant::now();
+        let body = Self::download_bytes_single(
+            s3_config,
+            bucket_name,
+            manifest_key,
+            &ProgressState::default(),
+            settings,
+        )
+        .await?;
+        let manifest: Manifest = serde_json::from_slice(&body).map_err(|e| {
+            TransferError::new(format!("Invalid manifest '{}': {}", manifest_key, e))
+        })?;
+        let format = ArchiveFormat::from_name(&manifest.format).ok_or_else(|| {
+            TransferError::new(format!(
+                "Manifest '{}' has unknown archive format '{}'",
+                manifest_key, manifest.format
+            ))
+        })?;
+
+        // Files are numbered through the bundles in order, starting from 0.
+        let mut first = 0;
+        let bundles: Vec<(usize, String, Vec<String>)> = manifest
+            .bundles()
+            .into_iter()
+            .map(|(bundle_key, keys)| {
+                first += keys.len();
+                let keys: Vec<String> = keys.into_iter().map(str::to_string).collect();
+                (first - keys.len(), bundle_key.to_string(), keys)
+            })
+            .collect();
+        let mut result = RustOperationResult::default();
+        stream::iter(bundles)
+            .map(|(first, bundle_key, keys)| async move {
+                let unpacked = Self::download_extracted(
+                    s3_config,
+                    bucket_name,
+                    &bundle_key,
+                    format,
+                    dest_dir,
+                    settings,
+                )
+                .await;
+                (first, keys, unpacked)
+            })
+            .buffer_unordered(settings.max_concurrent)
+            .for_each(|(first, keys, unpacked)| {
+                match unpacked {
+                    Ok(unpacked) => result.absorb(unpacked, first),
+                    Err(error) => {
+                        for (position, key) in keys.into_iter().enumerate() {
+                            let report = ItemReport {
+                                index: first + position,
+                                source: key.clone(),
+                                destination: String::new(),
+                                stats: ItemStats::default(),
+                            };
+                            let failure = FailedTransfer::new(
+                                &key,
+                                (key.clone(), dest_dir.to_string()),
+                                error.clone(),
+                            );
+                            result.record_transfer(report, Err::<String, _>(failure));
+                        }
+                    }
+                }
+                future::ready(())
+            })
+            .await;
+        result.elapsed = started.elapsed();
+        Ok(result)
+    }
+
+    /// Feeds the body to an extractor on a blocking thread, a few chunks ahead
+    /// of it at most.
+    async fn extract_attempt(
+        s3_config: &S3Config,
+        bucket_name: &str,
+        object_key: &str,
+        format: ArchiveFormat,
+        dest_dir: &str,
+        settings: &TransferSettings,
+    ) -> Result<RustOperationResult, TransferError> {
         let response = s3_config
-            .client
+            .client_for(bucket_name)
             .get_object()
             .bucket(bucket_name)
             .key(object_key)
             .send()
             .await
-            .map_err(|e| format!("Failed to get S3 object '{}': {}", object_key, e))?;
+            .map_err(|e| {
+                TransferError::from_sdk(format!("Failed to get S3 object '{}'", object_key), &e)
+            })?;
+        let content_length = response.content_length.map(|length| length.max(0) as u64);
+
+        let (sender, chunks) = mpsc::channel(QUEUED_CHUNKS);
+        let unpacking = tokio::task::spawn_blocking({
+            let dest_dir = PathBuf::from(dest_dir);
+            let object_key = object_key.to_string();
+            move || extract::unpack(format, BodyReader::new(chunks), &dest_dir, &object_key)
+        });
+
+        let mut body = response.body;
+        let received = async {
+            let mut received = 0;
+            while let Some(bytes) = body.try_next().await.map_err(|e| {
+                TransferError::retryable(format!(
+                    "Failed to read S3 response body for '{}': {}",
+                    object_key, e
+                ))
+            })? {
+                received += bytes.len() as u64;
+                throttle(settings.bandwidth.as_deref(), bytes.len() as u64).await;
+                // The extractor stops at the end of the archive, before what
+                // trails it, such as the central directory of a zip, or at
+                // the first error, which it reports.
+                if sender.send(bytes).await.is_err() {
+                    return Ok(());
+                }
+            }
+            check_length(object_key, content_length, received)
+        }
+        .await;
+        drop(sender);
+
+        let unpacked = unpacking.await.map_err(|e| {
+            TransferError::new(format!(
+                "Failed to extract S3 object '{}': {}",
+                object_key, e
+            ))
+        })?;
+        // A body cut short cuts the archive short too; its own error says why.
+        received?;
+        unpacked
+    }
+
+    /// Downloads the object, or its `range` slice, unless `overwrite` keeps the
+    /// file already at `local_path`, or `conditions` show it is current.
+    #[allow(clippy::too_many_arguments)]
+    async fn download_or_skip(
+        s3_config: Arc<S3Config>,
+        bucket_name: &str,
+        object_key: &str,
+        version_id: Option<&str>,
+        conditions: &GetConditions,
+        range: Option<ByteRange>,
+        local_path: &str,
+        progress: &ProgressState,
+        settings: &TransferSettings,
+        overwrite: OverwritePolicy,
+    ) -> Result<Completed, TransferError> {
+        // The size and ETag of the object say nothing about a file holding a
+        // slice of it, so only `never` spares such a file.
+        let overwrite = match overwrite {
+            OverwritePolicy::Never => overwrite,
+            _ if range.is_some() => OverwritePolicy::Always,
+            _ => overwrite,
+        };
+        let keeps_existing = overwrite
+            .keeps_existing(
+                &s3_config,
+                bucket_name,
+                object_key,
+                version_id,
+                local_path,
+                &settings.retry_policy,
+            )
+            .await?;
+        if keeps_existing {
+            return Ok(Completed::Skipped(local_path.to_string()));
+        }
+
+        let downloaded = match range {
+            Some(range) => Self::download_slice(
+                &s3_config,
+                bucket_name,
+                object_key,
+                version_id,
+                conditions,
+                range,
+                local_path,
+                progress,
+                settings,
+            )
+            .await
+            .map(Completed::Transferred),
+            None => {
+                Self::download_single_file(
+                    s3_config,
+                    bucket_name,
+                    object_key,
+                    version_id,
+                    conditions,
+                    local_path,
+                    progress,
+                    settings,
+                )
+                .await
+            }
+        };
+        match downloaded {
+            Err(error) if is_not_modified(&error) => Ok(Completed::Skipped(local_path.to_string())),
+            downloaded => downloaded,
+        }
+    }
+
+    /// Downloads the object to `local_path` in a single GET. When an earlier
+    /// attempt was cut short mid-body, only the rest of the object is fetched and
+    /// written after the bytes that arrived, provided its ETag hasn't changed.
+    /// A body inflated on the way to the file can't be resumed, as the state of
+    /// the decoder is lost with the attempt.
+    #[allow(clippy::too_many_arguments)]
+    async fn download_attempt(
+        s3_config: &S3Config,
+        bucket_name: &str,
+        object_key: &str,
+        version_id: Option<&str>,
+        conditions: &GetConditions,
+        local_path: &str,
+        progress: &ProgressState,
+        settings: &TransferSettings,
+        resume: &Mutex<Option<ResumePoint>>,
+    ) -> Result<Completed, TransferError> {
+        progress.start_attempt();
+        let resume_point = resume.lock().unwrap().take();
+        let request = s3_config
+            .client_for(bucket_name)
+            .get_object()
+            .bucket(bucket_name)
+            .key(object_key)
+            .set_version_id(version_id.map(str::to_string));
+        // The conditions were met by the attempt that started the file.
+        let request = match &resume_point {
+            Some(point) => request
+                .range(format!("bytes={}-", point.offset))
+                .if_match(&point.etag),
+            None => conditions.apply(request),
+        };
+        // Whatever goes wrong with a resumed attempt, the next one starts over.
+        let resumed = resume_point
+            .as_ref()
+            .map(|_| PartialFileGuard::new(local_path));
+        let request = request_checksums(request, settings.verify_checksum);
+        let response = request.send().await.map_err(|e| {
+            let error =
+                TransferError::from_sdk(format!("Failed to get S3 object '{}'", object_key), &e);
+            if resumed.is_some() && error.http_status == Some(412) {
+                TransferError::retryable(format!(
+                    "S3 object '{}' changed before its download could be resumed",
+                    object_key
+                ))
+            } else {
+                error
+            }
+        })?;
+
+        // A resumed body has to start where the file stops. A store that ignores
+        // `Range` sends the whole object again, which replaces the partial file.
+        let resume_point = match resume_point {
+            Some(point) => match response.content_range().and_then(content_range_start) {
+                Some(start) if start == point.offset => Some(point),
+                None => None,
+                Some(start) => {
+                    return Err(TransferError::retryable(format!(
+                        "Resumed download of '{}' started at byte {} instead of {}",
+                        object_key, start, point.offset
+                    )))
+                }
+            },
+            None => None,
+        };
+        let resumed = resumed.filter(|_| resume_point.is_some());
+        let offset = resume_point.as_ref().map_or(0, |point| point.offset);
+        let mut gunzip = (settings.auto_decompress
+            && resume_point.is_none()
+            && is_gzipped(object_key, response.content_encoding()))
+        .then(|| Gunzip::new(object_key));
+        let attributes =
+            FileAttributes::of(response.metadata.as_ref(), response.last_modified.as_ref());
+        let mut attempt_progress = AttemptProgress::new(progress);
+        let content_length = response.content_length.map(|length| length.max(0) as u64);
+        if let Some(content_length) = content_length {
+            attempt_progress.add_total_bytes(offset + content_length);
+            attempt_progress.add_bytes(offset);
+        }
+
+        let (file, guard) = match resumed {
+            Some(guard) => {
+                let mut file = OpenOptions::new()
+                    .write(true)
+                    .open(local_path)
+                    .await
+                    .map_err(|e| {
+                        TransferError::new(format!("Failed to open file '{}': {}", local_path, e))
+                    })?;
+                file.seek(SeekFrom::Start(offset)).await.map_err(|e| {
+                    TransferError::new(format!("Failed to seek in file '{}': {}", local_path, e))
+                })?;
+                (file, guard)
+            }
+            None => {
+                let file = File::create(local_path).await.map_err(|e| {
+                    TransferError::new(format!("Failed to create file '{}': {}", local_path, e))
+                })?;
+                let guard = PartialFileGuard::new(local_path);
+                if let Some(content_length) = content_length.filter(|_| gunzip.is_none()) {
+                    preallocate(&file, local_path, content_length).await?;
+                }
+                (file, guard)
+            }
+        };
+
+        let _reserved = reserve(
+            settings.memory.as_deref(),
+            settings.write_buffer_size as u64,
+        )
+        .await;
+        let mut writer = BufWriter::with_capacity(settings.write_buffer_size, file);
+        // A resumed body is only the tail of the object; the whole file is hashed
+        // once complete instead.
+        let mut digest = BodyDigest::new(
+            digest_etag(&response).filter(|_| resume_point.is_none()),
+            settings.verify_checksum,
+        );
+        let resumed_digest = digest_etag(&response)
+            .filter(|_| resume_point.is_some())
+            .and_then(plain_md5)
+            .map(str::to_string);
+        let mut sha256 = settings
+            .sha256
+            .as_ref()
+            .filter(|_| resume_point.is_none())
+            .map(|_| Context::new(&SHA256));
+        let etag = response.e_tag;
+        let mut body = response.body;
+        let mut received = 0;
+        // A session checkpoints the file about once a part.
+        let journal = settings.journal.as_deref().filter(|_| gunzip.is_none());
+        let mut checkpointed = 0;
+
+        loop {
+            let bytes = match body.try_next().await {
+                Ok(Some(bytes)) => bytes,
+                Ok(None) => break,
+                Err(e) => {
+                    // What arrived is kept for the next attempt to continue from.
+                    if let Some(etag) = etag.filter(|_| gunzip.is_none()) {
+                        if writer.flush().await.is_ok() {
+                            *resume.lock().unwrap() = Some(ResumePoint {
+                                etag,
+                                offset: offset + received,
+                            });
+                            guard.commit();
+                        }
+                    }
+                    return Err(TransferError::retryable(format!(
+                        "Failed to read S3 response body for '{}': {}",
+                        object_key, e
+                    )));
+                }
+            };
+            let output = match &mut gunzip {
+                Some(gunzip) => Cow::Owned(gunzip.inflate(&bytes)?),
+                None => Cow::Borrowed(&bytes[..]),
+            };
+            writer.write_all(&output).await.map_err(|e| {
+                TransferError::new(format!("Failed to write to file '{}': {}", local_path, e))
+            })?;
+            if let Some(sha256) = &mut sha256 {
+                sha256.update(&output);
+            }
+            digest.update(&bytes);
+            received += bytes.len() as u64;
+            if let (Some(journal), Some(etag)) = (journal, etag.as_deref()) {
+                if received - checkpointed >= settings.part_size && writer.flush().await.is_ok() {
+                    journal.downloaded_to(local_path, etag, offset + received);
+                    checkpointed = received;
+                }
+            }
+            attempt_progress.add_bytes(bytes.len() as u64);
+            throttle(settings.bandwidth.as_deref(), bytes.len() as u64).await;
+        }
+
+        // A short body leaves the preallocated file at full size, so the bytes
+        // are counted rather than the file measured.
+        check_length(object_key, content_length, received)?;
+        if let Some(gunzip) = &mut gunzip {
+            let rest = gunzip.finish()?;
+            writer.write_all(&rest).await.map_err(|e| {
+                TransferError::new(format!("Failed to write to file '{}': {}", local_path, e))
+            })?;
+            if let Some(sha256) = &mut sha256 {
+                sha256.update(&rest);
+            }
+        }
+        writer.flush().await.map_err(|e| {
+            TransferError::new(format!("Failed to flush file '{}': {}", local_path, e))
+        })?;
+        digest.verify(object_key)?;
+        if let Some(expected) = resumed_digest.filter(|_| settings.verify_checksum) {
+            let actual = file_md5(Path::new(local_path)).await?;
+            check_md5(object_key, &expected, &actual)?;
+        }
+        // Like the MD5, the SHA-256 of a resumed file is taken from the file.
+        if let Some(sums) = settings.sha256.as_deref() {
+            sums.check(object_key, local_path, sha256).await?;
+        }
+        let kept = attributes.apply(local_path, settings).await?;
+
+        guard.commit();
+        attempt_progress.commit();
+        let path = local_path.to_string();
+        Ok(match gunzip {
+            _ if !kept => Completed::Skipped(path),
+            Some(gunzip) => Completed::Decompressed {
+                path,
+                compressed_bytes: gunzip.compressed_bytes,
+                decompressed_bytes: gunzip.decompressed_bytes,
+            },
+            None => Completed::Transferred(path),
+        })
+    }
+
+    /// Downloads a client-side encrypted object to `local_path`, decrypting it as
+    /// it arrives. The file is removed unless the whole body authenticates.
+    #[allow(clippy::too_many_arguments)]
+    async fn download_decrypted_attempt(
+        s3_config: &S3Config,
+        bucket_name: &str,
+        object_key: &str,
+        version_id: Option<&str>,
+        conditions: &GetConditions,
+        local_path: &str,
+        progress: &ProgressState,
+        envelope: &Envelope,
+        settings: &TransferSettings,
+    ) -> Result<Completed, TransferError> {
+        progress.start_attempt();
+        let request = s3_config
+            .client_for(bucket_name)
+            .get_object()
+            .bucket(bucket_name)
+            .key(object_key)
+            .set_version_id(version_id.map(str::to_string));
+        let request = request_checksums(conditions.apply(request), settings.verify_checksum);
+        let response = request.send().await.map_err(|e| {
+            TransferError::from_sdk(format!("Failed to get S3 object '{}'", object_key), &e)
+        })?;
+
+        // The plaintext is written out as it is decrypted; a body that doesn't
+        // authenticate once it ends has its file removed.
+        let mut opener = envelope
+            .opener(object_key, response.metadata.as_ref())
+            .await?;
+        let attributes =
+            FileAttributes::of(response.metadata.as_ref(), response.last_modified.as_ref());
+        let mut attempt_progress = AttemptProgress::new(progress);
+        let content_length = response.content_length.map(|length| length.max(0) as u64);
+        if let Some(content_length) = content_length {
+            attempt_progress.add_total_bytes(content_length);
+        }
+
+        let file = File::create(local_path).await.map_err(|e| {
+            TransferError::new(format!("Failed to create file '{}': {}", local_path, e))
+        })?;
+        let guard = PartialFileGuard::new(local_path);
+        let _reserved = reserve(
+            settings.memory.as_deref(),
+            settings.write_buffer_size as u64,
+        )
+        .await;
+        let mut writer = BufWriter::with_capacity(settings.write_buffer_size, file);
+        let mut digest = BodyDigest::new(digest_etag(&response), settings.verify_checksum);
+        let mut sha256 = settings.sha256.as_ref().map(|_| Context::new(&SHA256));
+        let mut body = response.body;
+        let mut received = 0;
+
+        while let Some(bytes) = body.try_next().await.map_err(|e| {
+            TransferError::retryable(format!(
+                "Failed to read S3 response body for '{}': {}",
+                object_key, e
+            ))
+        })? {
+            let plaintext = match &mut opener {
+                Some(opener) => Cow::Owned(opener.update(&bytes)),
+                None => Cow::Borrowed(&bytes[..]),
+            };
+            writer.write_all(&plaintext).await.map_err(|e| {
+                TransferError::new(format!("Failed to write to file '{}': {}", local_path, e))
+            })?;
+            if let Some(sha256) = &mut sha256 {
+                sha256.update(&plaintext);
+            }
+            digest.update(&bytes);
+            received += bytes.len() as u64;
+            attempt_progress.add_bytes(bytes.len() as u64);
+            throttle(settings.bandwidth.as_deref(), bytes.len() as u64).await;
+        }
+
+        check_length(object_key, content_length, received)?;
+        if let Some(opener) = opener {
+            opener.finish()?;
+        }
+        writer.flush().await.map_err(|e| {
+            TransferError::new(format!("Failed to flush file '{}': {}", local_path, e))
+        })?;
+        digest.verify(object_key)?;
+        if let Some(sums) = settings.sha256.as_deref() {
+            sums.check(object_key, local_path, sha256).await?;
+        }
+        let kept = attributes.apply(local_path, settings).await?;
+
+        guard.commit();
+        attempt_progress.commit();
+        let path = local_path.to_string();
+        Ok(if kept {
+            Completed::Transferred(path)
+        } else {
+            Completed::Skipped(path)
+        })
+    }
+
+    /// Downloads `object_keys` below `base_directory`, either flattened to their
+    /// file names or, with `preserve_structure`, at their full key path. Without a
+    /// `bucket_name`, the keys are `s3://` URIs.
+    #[allow(clippy::too_many_arguments)]
+    async fn download_files_concurrent(
+        s3_config: Arc<S3Config>,
+        bucket_name: Option<&str>,
+        object_keys: impl Stream<Item = PyResult<String>> + Send,
+        total: Option<usize>,
+        base_directory: &str,
+        preserve_structure: bool,
+        settings: TransferSettings,
+        overwrite: OverwritePolicy,
+        progress: Arc<ProgressState>,
+        collected: Arc<Mutex<RustOperationResult>>,
+    ) -> PyResult<RustOperationResult> {
+        tokio::fs::create_dir_all(base_directory)
+            .await
+            .map_err(|e| {
+                RobinzhonError::new_err(format!(
+                    "Failed to create directory '{}': {}",
+                    base_directory, e
+                ))
+            })?;
+
+        let base_directory = Path::new(base_directory);
+        let downloads = object_keys.map(|object_key| {
+            object_key.map(|object_key| {
+                Self::key_download(bucket_name, base_directory, preserve_structure, object_key)
+            })
+        });
+
+        Self::download_files_concurrent_with_paths(
+            s3_config,
+            bucket_name.map(str::to_string),
+            downloads,
+            total,
+            settings.below(base_directory),
+            overwrite,
+            progress,
+            collected,
+        )
+        .await
+    }
+
+    /// The download of `object_key` below `base_directory`: to its whole key
+    /// path with `preserve_structure`, or else to its file name.
+    fn key_download(
+        bucket_name: Option<&str>,
+        base_directory: &Path,
+        preserve_structure: bool,
+        object_key: String,
+    ) -> DownloadRequest {
+        let key_path = match S3Uri::locate(bucket_name, &object_key) {
+            Ok(object) => object.object_key,
+            Err(error) => {
+                let request = (object_key.clone(), String::new());
+                return Err(Box::new(FailedTransfer::new(&object_key, request, error)));
+            }
+        };
+        let relative_path = if preserve_structure {
+            key_path.as_str()
+        } else {
+            key_path.rsplit('/').next().unwrap_or_default()
+        };
+        match contained_path(base_directory, relative_path) {
+            Some(local_path) => Ok(TransferRequest::from((
+                object_key,
+                local_path.to_string_lossy().to_string(),
+            ))),
+            None => Err(rejected_key(&object_key, base_directory)),
+        }
+    }
+
+    /// The keys of a batch, and how many there are when that is known. With
+    /// `check_space`, or a `scheduling` other than FIFO, every key is pulled and
+    /// its object's size looked up with a HEAD request first: to check that the
+    /// batch fits below `base_directory` before anything is downloaded, and to
+    /// order the keys. Objects that can't be described count as empty; their
+    /// downloads fail anyway.
+    #[allow(clippy::too_many_arguments)]
+    async fn batch_keys(
+        s3_config: &S3Config,
+        bucket_name: Option<&str>,
+        object_keys: BatchInput<String>,
+        base_directory: &str,
+        preserve_structure: bool,
+        settings: &TransferSettings,
+        check_space: bool,
+        scheduling: Scheduling,
+    ) -> PyResult<(BoxStream<'static, PyResult<String>>, Option<usize>)> {
+        if !check_space && scheduling == Scheduling::Fifo {
+            let total = object_keys.len();
+            return Ok((object_keys.into_stream().boxed(), total));
+        }
+        let object_keys: Vec<String> = object_keys.into_stream().try_collect().await?;
+        let base_directory = Path::new(base_directory);
+        let mut sized: Vec<(String, Option<(u64, PathBuf)>)> = paced(
+            stream::iter(object_keys).map(|object_key| async move {
+                let size = Self::key_size(
+                    s3_config,
+                    bucket_name,
+                    base_directory,
+                    preserve_structure,
+                    &object_key,
+                    &settings.retry_policy,
+                )
+                .await;
+                (object_key, size)
+            }),
+            settings.requests.as_deref(),
+        )
+        .buffered(settings.max_concurrent)
+        .collect()
+        .await;
+        if check_space {
+            let sizes = sized.iter().filter_map(|(_, size)| size.clone()).collect();
+            ensure_space(base_directory, sizes).await?;
+        }
+        scheduling.order(&mut sized, |(_, size)| {
+            size.as_ref().map_or(0, |(size, _)| *size)
+        });
+
+        let total = sized.len();
+        let object_keys = sized.into_iter().map(|(object_key, _)| Ok(object_key));
+        Ok((stream::iter(object_keys).boxed(), Some(total)))
+    }
+
+    /// Size of the object `object_key` names, from a HEAD request, and the local
+    /// path `key_download` gives it; `None` when either can't be had.
+    async fn key_size(
+        s3_config: &S3Config,
+        bucket_name: Option<&str>,
+        base_directory: &Path,
+        preserve_structure: bool,
+        object_key: &str,
+        retry_policy: &RetryPolicy,
+    ) -> Option<(u64, PathBuf)> {
+        let request = Self::key_download(
+            bucket_name,
+            base_directory,
+            preserve_structure,
+            object_key.to_string(),
+        )
+        .ok()?;
+        let object = S3Uri::locate(bucket_name, object_key).ok()?;
+        let metadata = head_object(
+            s3_config,
+            &object.bucket_name,
+            &object.object_key,
+            None,
+            retry_policy,
+        )
+        .await
+        .ok()?;
+        Some((metadata.size, PathBuf::from(request.destination)))
+    }
+
+    /// Downloads every request as `downloads` yields it; requests that already
+    /// failed (keys that can't be stored) are recorded as they are. When several
+    /// keys share a local path, only the first is downloaded; the others are
+    /// reported as failed rather than silently overwriting it.
+    ///
+    /// An error from `downloads` stops the batch: the transfers in flight finish,
+    /// their outcome stays in `collected`, and the error is returned.
+    #[allow(clippy::too_many_arguments)]
+    async fn download_files_concurrent_with_paths(
+        s3_config: Arc<S3Config>,
+        bucket_name: Option<String>,
+        downloads: impl Stream<Item = PyResult<DownloadRequest>> + Send,
+        total: Option<usize>,
+        settings: TransferSettings,
+        overwrite: OverwritePolicy,
+        progress: Arc<ProgressState>,
+        collected: Arc<Mutex<RustOperationResult>>,
+    ) -> PyResult<RustOperationResult> {
+        let settings = &settings;
+        let batch_started = Instant::now();
+        progress.start(total.unwrap_or_default());
+
+        let mut input_error = None;
+        let failures = FailureLimit::new(settings.failure_limit);
+        let mut destinations: HashMap<PathBuf, String> = HashMap::new();
+        let requests = settings.requests.as_deref();
+        let downloads = gated(
+            until_error(downloads, &mut input_error),
+            settings.pause.as_deref(),
+        );
+        let downloads = until_limit(downloads, &failures);
+        paced(downloads, requests)
+            .enumerate()
+            .map(|(index, request)| {
+                if total.is_none() {
+                    progress.add_files_total(1);
+                }
+                let request = request.map(|request| {
+                    let local_path = &request.destination;
+                    let claimed_by = match destinations.entry(PathBuf::from(local_path)) {
+                        _ if local_path.is_empty() => None,
+                        Entry::Occupied(entry) => Some(entry.get().clone()),
+                        Entry::Vacant(entry) => {
+                            entry.insert(request.source.clone());
+                            None
+                        }
+                    };
+                    (request, claimed_by)
+                });
+                (index, request)
+            })
+            .map(|(index, request)| {
+                let s3_config = Arc::clone(&s3_config);
+                let bucket_name = bucket_name.clone();
+                let progress = Arc::clone(&progress);
+
+                async move {
+                    let started = Instant::now();
+                    let item_progress = ProgressState::item_of(&progress);
+                    let (source, destination) = match &request {
+                        Ok((request, _)) => (request.source.clone(), request.destination.clone()),
+                        Err(failure) => (failure.request.source.clone(), String::new()),
+                    };
+                    let outcome = match request {
+                        Ok((request, claimed_by)) => {
+                            let (object_key, local_path) = (&request.source, &request.destination);
+                            let prepared = match claimed_by {
+                                Some(other_key) => Err(TransferError::new(format!(
+                                    "'{}' would overwrite '{}', the destination of '{}'",
+                                    object_key, local_path, other_key
+                                ))),
+                                None => S3Uri::locate(bucket_name.as_deref(), object_key),
+                            };
+                            // Retried requests of keys that couldn't be placed have
+                            // no local path.
+                            let prepared = match prepared {
+                                Ok(_) if local_path.is_empty() => Err(TransferError::new(format!(
+                                    "No local path to download '{}' to",
+                                    object_key
+                                ))),
+                                Ok(object) => match check_destination(local_path, settings).await {
+                                    Ok(()) => Self::create_parent_directory(local_path)
+                                        .await
+                                        .map(|()| object),
+                                    Err(error) => Err(error),
+                                },
+                                Err(error) => Err(error),
+                            };
+                            match prepared {
+                                Ok(object) => {
+                                    Self::download_or_skip(
+                                        s3_config,
+                                        &object.bucket_name,
+                                        &object.object_key,
+                                        request.version_id.as_deref(),
+                                        &request.conditions,
+                                        request.range,
+                                        local_path,
+                                        &item_progress,
+                                        settings,
+                                        overwrite,
+                                    )
+                                    .await
+                                }
+                                Err(error) => Err(error),
+                            }
+                            .map_err(|error| {
+                                FailedTransfer::new(&request.source.clone(), request, error)
+                            })
+                        }
+                        Err(failure) => Err(*failure),
+                    };
+                    progress.record(&outcome);
+                    let report = ItemReport {
+                        index,
+                        source,
+                        destination,
+                        stats: ItemStats::measure(started, &item_progress),
+                    };
+                    (report, outcome)
+                }
+            })
+            .buffer_unordered(settings.max_concurrent)
+            .for_each(|(report, outcome)| {
+                let mut collected = collected.lock().unwrap();
+                collected.record_transfer(report, outcome);
+                failures.update(collected.failed.len());
+                future::ready(())
+            })
+            .await;
+
+        progress.finish();
+        let mut collected = collected.lock().unwrap();
+        collected.elapsed = batch_started.elapsed();
+        match (failures.error(&collected), input_error) {
+            (Some(error), _) | (None, Some(error)) => Err(error),
+            (None, None) => Ok(collected.take()),
+        }
+    }
+
+    /// Lists every object under `prefix` with its path relative to it. With
+    /// `newest`, only that many of the most recently modified objects are kept,
+    /// newest first.
+    async fn prefix_objects(
+        s3_config: &S3Config,
+        bucket_name: &str,
+        prefix: &str,
+        filter: &PathFilter,
+        objects: &ObjectFilter,
+        newest: Option<usize>,
+        retry_policy: &RetryPolicy,
+    ) -> Result<Vec<(String, ObjectInfo)>, TransferError> {
+        let mut objects = list_prefix(
+            s3_config,
+            bucket_name,
+            prefix,
+            filter,
+            objects,
+            retry_policy,
+        )
+        .await?;
+        if let Some(newest) = newest {
+            // The sort is stable, so objects modified at the same time stay in
+            // key order.
+            objects.sort_by(|(_, a), (_, b)| {
+                b.last_modified
+                    .partial_cmp(&a.last_modified)
+                    .unwrap_or(std::cmp::Ordering::Equal)
+            });
+            objects.truncate(newest);
+        }
+        Ok(objects)
+    }
+
+    /// Pairs every listed object with its local path below `base_directory`.
+    /// Objects whose key would escape it are returned as failures.
+    fn prefix_downloads(
+        objects: Vec<(String, ObjectInfo)>,
+        base_directory: &str,
+    ) -> Vec<DownloadRequest> {
+        let base_directory = Path::new(base_directory);
+        objects
+            .into_iter()
+            .map(
+                |(relative_path, object)| match contained_path(base_directory, &relative_path) {
+                    Some(local_path) => Ok(TransferRequest::from((
+                        object.key,
+                        local_path.to_string_lossy().to_string(),
+                    ))),
+                    None => Err(rejected_key(&object.key, base_directory)),
+                },
+            )
+            .collect()
+    }
+
+    /// Sizes and local paths of the downloads of listed `objects`, as
+    /// `prefix_downloads` lays them out, for `ensure_space`.
+    fn prefix_sizes(objects: &[(String, ObjectInfo)], base_directory: &str) -> Vec<(u64, PathBuf)> {
+        let base_directory = Path::new(base_directory);
+        objects
+            .iter()
+            .filter_map(|(relative_path, object)| {
+                Some((object.size, contained_path(base_directory, relative_path)?))
+            })
+            .collect()
+    }
+
+    /// Downloads and parses the S3 Inventory manifest at `manifest`.
+    async fn inventory_manifest(
+        s3_config: &S3Config,
+        manifest: &S3Uri,
+        settings: &TransferSettings,
+    ) -> Result<(InventoryManifest, InventoryReader), TransferError> {
+        let body = Self::download_bytes_single(
+            s3_config,
+            &manifest.bucket_name,
+            &manifest.object_key,
+            &ProgressState::default(),
+            settings,
+        )
+        .await?;
+        let inventory = InventoryManifest::parse(&body, &manifest.object_key)?;
+        let reader = inventory.reader(&manifest.object_key)?;
+        Ok((inventory, reader))
+    }
+
+    /// The downloads of the objects the data files `data_keys` of an inventory
+    /// report list under `prefix`, laid out as `prefix_downloads` lays them out.
+    /// Data files are read one after another, the next one fetched while the
+    /// objects of the current one download; one that can't be read ends the
+    /// stream with its error.
+    fn inventory_downloads<'a>(
+        s3_config: &'a S3Config,
+        settings: &'a TransferSettings,
+        inventory_bucket: &'a str,
+        data_keys: Vec<String>,
+        reader: InventoryReader,
+        (prefix, filter, objects): (&'a str, &'a PathFilter, &'a ObjectFilter),
+        base_directory: &'a str,
+    ) -> impl Stream<Item = PyResult<DownloadRequest>> + Send + 'a {
+        stream::iter(data_keys)
+            .map(move |data_key| {
+                let reader = reader.clone();
+                async move {
+                    let body = Self::download_bytes_single(
+                        s3_config,
+                        inventory_bucket,
+                        &data_key,
+                        &ProgressState::default(),
+                        settings,
+                    )
+                    .await?;
+                    let parsing = tokio::task::spawn_blocking({
+                        let data_key = data_key.clone();
+                        move || reader.objects(body, &data_key)
+                    });
+                    parsing.await.unwrap_or_else(|e| {
+                        Err(TransferError::new(format!(
+                            "Failed to read inventory file '{}': {}",
+                            data_key, e
+                        )))
+                    })
+                }
+            })
+            .buffered(2)
+            .flat_map(move |listed| match listed {
+                Ok(listed) => {
+                    let selected = select_objects(listed, prefix, filter, objects);
+                    let downloads = Self::prefix_downloads(selected, base_directory);
+                    stream::iter(downloads.into_iter().map(Ok)).left_stream()
+                }
+                Err(error) => stream::iter([Err(PyErr::from(error))]).right_stream(),
+            })
+    }
+
+    /// The plan of `prefix_downloads`: files `overwrite` keeps are skipped, as
+    /// the listing tells, and the rest downloaded.
+    async fn plan_prefix_downloads(
+        objects: Vec<(String, ObjectInfo)>,
+        base_directory: &str,
+        overwrite: OverwritePolicy,
+    ) -> TransferPlan {
+        let mut plan = TransferPlan::download();
+        let base_directory = Path::new(base_directory);
+        for (relative_path, object) in objects {
+            let Some(local_path) = contained_path(base_directory, &relative_path) else {
+                plan.fail(*rejected_key(&object.key, base_directory));
+                continue;
+            };
+            let local_path = local_path.to_string_lossy().to_string();
+            match overwrite.keeps_listed(&object, &local_path).await {
+                Ok(true) => plan.skip(local_path),
+                Ok(false) => plan.transfer(object.key, local_path, object.size),
+                Err(error) => {
+                    let request = (object.key.clone(), local_path);
+                    plan.fail(FailedTransfer::new(&object.key, request, error));
+                }
+            }
+        }
+        plan
+    }
+
+    /// The plan of downloading `downloads`, which no listing tells about: each
+    /// object is looked up with a HEAD request, and skipped when `overwrite` or
+    /// the conditions of its request spare the file already there. As when
+    /// downloading, a key whose local path another key already took fails.
+    async fn plan_downloads(
+        s3_config: &S3Config,
+        bucket_name: Option<&str>,
+        downloads: impl Stream<Item = PyResult<DownloadRequest>>,
+        overwrite: OverwritePolicy,
+        settings: &TransferSettings,
+    ) -> PyResult<TransferPlan> {
+        let downloads: Vec<DownloadRequest> = downloads.try_collect().await?;
+        let mut claimed = HashMap::new();
+        let mut plan = TransferPlan::download();
+        let mut requests = Vec::with_capacity(downloads.len());
+        for download in downloads {
+            match download {
+                Ok(request) => match claimed.entry(request.destination.clone()) {
+                    Entry::Occupied(entry) => {
+                        let error = TransferError::new(format!(
+                            "'{}' would overwrite '{}', the destination of '{}'",
+                            request.source,
+                            request.destination,
+                            entry.get()
+                        ));
+                        plan.fail(FailedTransfer::new(&request.source.clone(), request, error));
+                    }
+                    Entry::Vacant(entry) => {
+                        entry.insert(request.source.clone());
+                        requests.push(request);
+                    }
+                },
+                Err(failure) => plan.fail(*failure),
+            }
+        }
+
+        let outcomes: Vec<_> = paced(
+            stream::iter(requests).map(|request| async move {
+                let outcome = Self::plan_download(
+                    s3_config,
+                    bucket_name,
+                    &request,
+                    overwrite,
+                    &settings.retry_policy,
+                )
+                .await;
+                (request, outcome)
+            }),
+            settings.requests.as_deref(),
+        )
+        .buffered(settings.max_concurrent)
+        .collect()
+        .await;
+        for (request, outcome) in outcomes {
+            match outcome {
+                Ok(Some(size)) => plan.transfer(request.source, request.destination, size),
+                Ok(None) => plan.skip(request.destination),
+                Err(error) => {
+                    plan.fail(FailedTransfer::new(&request.source.clone(), request, error))
+                }
+            }
+        }
+        Ok(plan)
+    }
+
+    /// Bytes the download of `request` would move, from a HEAD request of its
+    /// object, or `None` when it would be skipped.
+    async fn plan_download(
+        s3_config: &S3Config,
+        bucket_name: Option<&str>,
+        request: &TransferRequest,
+        overwrite: OverwritePolicy,
+        retry_policy: &RetryPolicy,
+    ) -> Result<Option<u64>, TransferError> {
+        let object = S3Uri::locate(bucket_name, &request.source)?;
+        let head = head_object(
+            s3_config,
+            &object.bucket_name,
+            &object.object_key,
+            request.version_id.as_deref(),
+            retry_policy,
+        )
+        .await?;
+        // What a conditional GET would answer "304 Not Modified" to.
+        let conditions = &request.conditions;
+        let current = match (&conditions.if_none_match, &head.etag) {
+            (Some(held), Some(etag)) => held.trim_matches('"') == etag.trim_matches('"'),
+            _ => false,
+        } || conditions
+            .if_modified_since
+            .zip(head.last_modified)
+            .is_some_and(|(since, modified)| modified <= since.as_secs_f64());
+        // As in `download_or_skip`, only `never` spares a file holding a slice.
+        let overwrite = match request.range {
+            Some(_) if overwrite != OverwritePolicy::Never => OverwritePolicy::Always,
+            _ => overwrite,
+        };
+        if current
+            || overwrite
+                .keeps_known(head.size, head.etag.as_deref(), &request.destination)
+                .await?
+        {
+            return Ok(None);
+        }
+        Ok(Some(
+            request
+                .range
+                .map_or(head.size, |range| range.length_in(head.size)),
+        ))
+    }
+
+    /// Works out which objects under `prefix` are missing or outdated in
+    /// `local_dir`, and, when `delete` is set, which local files have no object.
+    async fn plan_sync_down(
+        s3_config: &S3Config,
+        bucket_name: &str,
+        prefix: &str,
+        local_dir: &Path,
+        filter: &PathFilter,
+        delete: bool,
+        retry_policy: &RetryPolicy,
+    ) -> Result<SyncDownPlan, TransferError> {
+        let objects = list_prefix(
+            s3_config,
+            bucket_name,
+            prefix,
+            filter,
+            &ObjectFilter::default(),
+            retry_policy,
+        )
+        .await?;
+
+        let previous = SyncCache::load(local_dir, bucket_name, prefix).await;
+        let mut plan = SyncDownPlan {
+            cache: SyncCache::new(bucket_name, prefix),
+            ..SyncDownPlan::default()
+        };
+        let mut remote_paths = HashSet::with_capacity(objects.len());
+
+        for (relative_path, object) in objects {
+            let Some(local_path) = contained_path(local_dir, &relative_path) else {
+                plan.downloads
+                    .push(Err(rejected_key(&object.key, local_dir)));
+                continue;
+            };
+            let local = LocalFile::stat(&local_path).await;
+            match local {
+                Some(local)
+                    if !needs_download(&object, Some(local), previous.get(&relative_path)) =>
+                {
+                    plan.cache.insert(relative_path.clone(), object.etag, local);
+                    plan.changes.skipped.push(object.key);
+                }
+                _ => {
+                    match local {
+                        Some(_) => plan.changes.changed.push(object.key.clone()),
+                        None => plan.changes.new.push(object.key.clone()),
+                    }
+                    let local_path = local_path.to_string_lossy().to_string();
+                    plan.pending.insert(
+                        local_path.clone(),
+                        (relative_path.clone(), object.etag, object.size),
+                    );
+                    plan.downloads
+                        .push(Ok(TransferRequest::from((object.key, local_path))));
+                }
+            }
+            remote_paths.insert(relative_path);
+        }
+
+        if delete {
+            plan.extraneous = walk_local(local_dir, filter)
+                .await?
+                .into_iter()
+                .filter(|relative_path| !remote_paths.contains(relative_path))
+                .collect();
+        }
+        Ok(plan)
+    }
+
+    /// Runs a planned sync: downloads, then deletions, then the cache update.
+    /// With `check_space`, nothing is run unless the downloads fit in the free
+    /// space of `local_dir`; they start in the order `scheduling` puts them in.
+    #[allow(clippy::too_many_arguments)]
+    async fn sync_down_concurrent(
+        s3_config: Arc<S3Config>,
+        bucket_name: String,
+        prefix: String,
+        local_dir: PathBuf,
+        filter: PathFilter,
+        delete: bool,
+        check_space: bool,
+        scheduling: Scheduling,
+        settings: TransferSettings,
+        progress: Arc<ProgressState>,
+        collected: Arc<Mutex<RustOperationResult>>,
+    ) -> PyResult<(RustOperationResult, SyncChanges)> {
+        let SyncDownPlan {
+            mut downloads,
+            mut changes,
+            mut pending,
+            mut cache,
+            extraneous,
+        } = Self::plan_sync_down(
+            &s3_config,
+            &bucket_name,
+            &prefix,
+            &local_dir,
+            &filter,
+            delete,
+            &settings.retry_policy,
+        )
+        .await?;
+        if check_space {
+            let sizes = pending
+                .iter()
+                .map(|(local_path, (_, _, size))| (*size, PathBuf::from(local_path)))
+                .collect();
+            ensure_space(&local_dir, sizes).await?;
+        }
+        scheduling.order(&mut downloads, |download| {
+            download
+                .as_ref()
+                .ok()
+                .and_then(|request| pending.get(&request.destination))
+                .map_or(0, |(_, _, size)| *size)
+        });
+        tokio::fs::create_dir_all(&local_dir).await.map_err(|e| {
+            TransferError::new(format!(
+                "Failed to create directory '{}': {}",
+                local_dir.display(),
+                e
+            ))
+        })?;
+
+        let total = downloads.len();
+        // Sync compares files with the objects' sizes and ETags, which a file
+        // stored decompressed would never match.
+        let settings = TransferSettings {
+            auto_decompress: false,
+            ..settings
+        }
+        .below(&local_dir);
+        let result = Self::download_files_concurrent_with_paths(
+            s3_config,
+            Some(bucket_name),
+            ready_items(downloads),
+            Some(total),
+            settings,
+            OverwritePolicy::Always,
+            progress,
+            collected,
+        )
+        .await?;
+
+        for local_path in &result.successful {
+            if let Some((relative_path, etag, _)) = pending.remove(local_path) {
+                if let Some(local) = LocalFile::stat(Path::new(local_path)).await {
+                    cache.insert(relative_path, etag, local);
+                }
+            }
+        }
+        changes.deleted = delete_extraneous(&local_dir, extraneous).await;
+        cache.save(&local_dir).await;
+
+        Ok((result, changes))
+    }
+
+    async fn download_bytes_single(
+        s3_config: &S3Config,
+        bucket_name: &str,
+        object_key: &str,
+        progress: &ProgressState,
+        settings: &TransferSettings,
+    ) -> Result<Vec<u8>, TransferError> {
+        let _slot = acquire(settings.limiter.as_deref()).await;
+        s3_config
+            .run(bucket_name, &settings.retry_policy, || {
+                Self::download_bytes_attempt(s3_config, bucket_name, object_key, progress, settings)
+            })
+            .await
+    }
+
+    async fn download_bytes_attempt(
+        s3_config: &S3Config,
+        bucket_name: &str,
+        object_key: &str,
+        progress: &ProgressState,
+        settings: &TransferSettings,
+    ) -> Result<Vec<u8>, TransferError> {
+        let request = s3_config
+            .client_for(bucket_name)
+            .get_object()
+            .bucket(bucket_name)
+            .key(object_key);
+        let response = request_checksums(request, settings.verify_checksum)
+            .send()
+            .await
+            .map_err(|e| {
+                TransferError::from_sdk(format!("Failed to get S3 object '{}'", object_key), &e)
+            })?;
+
+        let mut reserved = Reservation::new(
+            settings.memory.as_deref(),
+            body_length(response.content_length, settings.write_buffer_size),
+        )
+        .await;
+        let mut digest = BodyDigest::new(digest_etag(&response), settings.verify_checksum);
+        let mut attempt_progress = AttemptProgress::new(progress);
+        let mut buffer = read_body(
+            response.body,
+            response.content_length,
+            object_key,
+            &mut attempt_progress,
+            settings.bandwidth.as_deref(),
+            &mut reserved,
+        )
+        .await?;
+        digest.update(&buffer);
+        digest.verify(object_key)?;
+        if let Some(envelope) = settings.client_encryption.as_deref() {
+            buffer = envelope
+                .open(object_key, buffer, response.metadata.as_ref())
+                .await?;
+        }
+
+        attempt_progress.commit();
+        Ok(buffer)
+    }
+
+    /// Downloads every key `object_keys` yields into memory; `total` is how many
+    /// there are, when known. Keys that fail are logged and left out, and counted
+    /// against the failure limit of `settings`. An error from `object_keys`, or
+    /// the limit being reached, is returned once the downloads already started
+    /// finish.
+    async fn download_bytes_concurrent(
+        s3_config: Arc<S3Config>,
+        bucket_name: String,
+        object_keys: impl Stream<Item = PyResult<String>> + Send,
+        total: Option<usize>,
+        settings: TransferSettings,
+        progress: Arc<ProgressState>,
+    ) -> PyResult<HashMap<String, Cow<'static, [u8]>>> {
+        progress.start(total.unwrap_or_default());
+
+        let mut input_error = None;
+        let failures = FailureLimit::new(settings.failure_limit);
+        let (mut failed, mut first_failure) = (0, None);
+        let mut contents = HashMap::with_capacity(total.unwrap_or_default());
+        let (s3_config, bucket_name, progress, settings) =
+            (&s3_config, &bucket_name, &progress, &settings);
+
+        let object_keys = until_limit(until_error(object_keys, &mut input_error), &failures);
+        paced(
+            object_keys.map(|object_key| {
+                if total.is_none() {
+                    progress.add_files_total(1);
+                }
+                async move {
+                    let outcome = Self::download_bytes_single(
+                        s3_config,
+                        bucket_name,
+                        &object_key,
+                        progress,
+                        settings,
+                    )
+                    .await;
+                    progress.record(&outcome);
+                    (object_key, outcome)
+                }
+            }),
+            settings.requests.as_deref(),
+        )
+        .buffer_unordered(settings.max_concurrent)
+        .for_each(|(object_key, outcome)| {
+            match outcome {
+                Ok(data) => {
+                    contents.insert(object_key, Cow::Owned(data));
+                }
+                Err(error) => {
+                    warn!("{}", error);
+                    failed += 1;
+                    first_failure.get_or_insert((object_key, error));
+                    failures.update(failed);
+                }
+            }
+            future::ready(())
+        })
+        .await;
+
+        progress.finish();
+        let first_failure = first_failure
+            .as_ref()
+            .map(|(object_key, error)| (object_key.as_str(), error));
+        match (failures.error_after(failed, first_failure), input_error) {
+            (Some(error), _) | (None, Some(error)) => Err(error),
+            (None, None) => Ok(contents),
+        }
+    }
+
+    /// Runs a batch of downloads to completion, blocking. Whatever finished before
+    /// an interruption or an error from `downloads` is attached to the raised
+    /// error as `partial_results`.
+    #[allow(clippy::too_many_arguments)]
+    fn run_downloads(
+        &self,
+        py: Python<'_>,
+        bucket_name: Option<&str>,
+        downloads: impl Stream<Item = PyResult<DownloadRequest>> + Send,
+        total: Option<usize>,
+        progress: Option<TransferProgress>,
+        overwrite: OverwritePolicy,
+        failure_limit: Option<usize>,
+        sha256: Option<Arc<Sha256Sums>>,
+        destination: Option<&Path>,
+    ) -> PyResult<Results> {
+        self.run_downloads_into(
+            py,
+            RustOperationResult::default(),
+            bucket_name,
+            downloads,
+            total,
+            progress,
+            overwrite,
+            failure_limit,
+            sha256,
+            destination,
+        )
+    }
+
+    /// The plan of a batch given `dry_run`, made by `plan_downloads`.
+    fn plan(
+        &self,
+        py: Python<'_>,
+        bucket_name: Option<&str>,
+        downloads: impl Stream<Item = PyResult<DownloadRequest>> + Send,
+        overwrite: OverwritePolicy,
+    ) -> PyResult<Planned<Results>> {
+        let state = &self.state();
+        block_on_interruptible(
+            py,
+            &state.runtime,
+            Self::plan_downloads(
+                &state.s3_config,
+                bucket_name,
+                downloads,
+                overwrite,
+                &state.settings,
+            ),
+        )?
+        .map(Planned::Plan)
+    }
+
+    /// Runs the downloads of a `TransferSession`, checkpointing each item to
+    /// `journal` as it finishes.
+    pub fn run_journaled(
+        &self,
+        py: Python<'_>,
+        bucket_name: Option<&str>,
+        requests: Vec<TransferRequest>,
+        progress: Option<TransferProgress>,
+        journal: Arc<SessionJournal>,
+    ) -> PyResult<Results> {
+        let total = requests.len();
+        self.run_downloads_into(
+            py,
+            RustOperationResult::journaled(journal),
+            bucket_name,
+            ready_items(requests.into_iter().map(Ok).collect()),
+            Some(total),
+            progress,
+            OverwritePolicy::Always,
+            None,
+            None,
+            None,
+        )
+    }
+
+    /// A batch of downloads that runs on its own once spawned, for a
+    /// `TransferManager` job, held back whenever `pause` is paused.
+    pub fn detached_batch(
+        &self,
+        bucket_name: String,
+        requests: Vec<TransferRequest>,
+        progress: Arc<ProgressState>,
+        pause: Arc<PauseGate>,
+    ) -> impl Future<Output = PyResult<Results>> + Send + 'static {
+        let state = &self.state();
+        let s3_config = Arc::clone(&state.s3_config);
+        let settings = TransferSettings {
+            pause: Some(pause),
+            ..state.settings.clone()
+        };
+
+        async move {
+            let total = requests.len();
+            Self::download_files_concurrent_with_paths(
+                s3_config,
+                Some(bucket_name.clone()),
+                ready_items(requests.into_iter().map(Ok).collect()),
+                Some(total),
+                settings,
+                OverwritePolicy::Always,
+                progress,
+                Arc::default(),
+            )
+            .await
+            .map(|result| Self::into_results(result, Some(&bucket_name)))
+        }
+    }
+
+    /// Runs a batch of downloads, recording its items into `collected`.
+    #[allow(clippy::too_many_arguments)]
+    fn run_downloads_into(
+        &self,
+        py: Python<'_>,
+        collected: RustOperationResult,
+        bucket_name: Option<&str>,
+        downloads: impl Stream<Item = PyResult<DownloadRequest>> + Send,
+        total: Option<usize>,
+        progress: Option<TransferProgress>,
+        overwrite: OverwritePolicy,
+        failure_limit: Option<usize>,
+        sha256: Option<Arc<Sha256Sums>>,
+        destination: Option<&Path>,
+    ) -> PyResult<Results> {
+        let state = &self.state();
+        let mut settings = state
+            .settings
+            .clone()
+            .stopping_after(failure_limit)
+            .hashing_into(sha256)
+            .checkpointed_to(collected.journal.clone());
+        let collected = Arc::new(Mutex::new(collected));
+        if let Some(destination) = destination {
+            settings = settings.below(destination);
+        }
+
+        block_on_interruptible(
+            py,
+            &state.runtime,
+            Self::download_files_concurrent_with_paths(
+                Arc::clone(&state.s3_config),
+                bucket_name.map(str::to_string),
+                downloads,
+                total,
+                settings,
+                overwrite,
+                progress.unwrap_or_default().state,
+                Arc::clone(&collected),
+            ),
+        )
+        .and_then(|result| result)
+        .map(|result| Self::into_results(result, bucket_name))
+        .map_err(|e| with_partial_results(py, e, &collected))
+    }
+
+    fn into_results(download_result: RustOperationResult, bucket_name: Option<&str>) -> Results {
+        Self::log_failures(&download_result);
+        Results::from(download_result).with_origin(TransferKind::Download, bucket_name)
+    }
+
+    /// The files unpacked from an archive, which can't be retried one by one.
+    fn extracted_results(unpacked: RustOperationResult) -> Results {
+        Self::log_failures(&unpacked);
+        Results::from(unpacked)
+    }
+
+    fn log_failures(download_result: &RustOperationResult) {
+        if !download_result.failed.is_empty() {
+            warn!("{} downloads failed", download_result.failed.len());
+            for failure in &download_result.failed {
+                warn!("  {}: {}", failure.name, failure.error);
+            }
+        }
+    }
+
+    /// Downloads `(url, local_path)` pairs with plain, unsigned GETs of presigned
+    /// URLs. Items are reported by their URL without the query string, which
+    /// holds the signature.
+    ///
+    /// An error from `urls_and_paths` stops the batch: the downloads in flight
+    /// finish, their outcome stays in `collected`, and the error is returned.
+    async fn download_presigned_concurrent(
+        urls_and_paths: impl Stream<Item = PyResult<(String, String)>> + Send,
+        total: Option<usize>,
+        settings: TransferSettings,
+        progress: Arc<ProgressState>,
+        collected: Arc<Mutex<RustOperationResult>>,
+    ) -> PyResult<RustOperationResult> {
+        let client = reqwest::Client::builder()
+            .build()
+            .map_err(|e| RobinzhonError::new_err(format!("Failed to create HTTP client: {}", e)))?;
+        let (client, retry_policy) = (&client, &settings.retry_policy);
+        let bandwidth = settings.bandwidth.as_deref();
+        let memory = settings.memory.as_deref();
+        let write_buffer_size = settings.write_buffer_size;
+        let verify_checksum = settings.verify_checksum;
+        let limiter = settings.limiter.as_deref();
+        let batch_started = Instant::now();
+        progress.start(total.unwrap_or_default());
+
+        let mut input_error = None;
+        let failures = FailureLimit::new(settings.failure_limit);
+        let requests = settings.requests.as_deref();
+        let urls_and_paths = until_limit(until_error(urls_and_paths, &mut input_error), &failures);
+        paced(urls_and_paths, requests)
+            .enumerate()
+            .map(|(index, (url, local_path))| {
+                if total.is_none() {
+                    progress.add_files_total(1);
+                }
+                let progress = Arc::clone(&progress);
+
+                async move {
+                    let started = Instant::now();
+                    let item_progress = ProgressState::item_of(&progress);
+                    let name = unsigned_url(&url);
+                    let _slot = acquire(limiter).await;
+                    let outcome = match Self::create_parent_directory(&local_path).await {
+                        Ok(()) => {
+                            retry_policy
+                                .run(|| {
+                                    Self::download_presigned_attempt(
+                                        client,
+                                        &url,
+                                        &local_path,
+                                        &item_progress,
+                                        bandwidth,
+                                        memory,
+                                        write_buffer_size,
+                                        verify_checksum,
+                                    )
+                                })
+                                .await
+                        }
+                        Err(error) => Err(error),
+                    }
+                    .map_err(|error| {
+                        FailedTransfer::new(name, (name.to_string(), local_path.clone()), error)
+                    });
+                    progress.record(&outcome);
+                    let report = ItemReport {
+                        index,
+                        source: name.to_string(),
+                        destination: local_path,
+                        stats: ItemStats::measure(started, &item_progress),
+                    };
+                    (report, outcome)
+                }
+            })
+            .buffer_unordered(settings.max_concurrent)
+            .for_each(|(report, outcome)| {
+                let mut collected = collected.lock().unwrap();
+                collected.record_transfer(report, outcome);
+                failures.update(collected.failed.len());
+                future::ready(())
+            })
+            .await;
+
+        progress.finish();
+        let mut collected = collected.lock().unwrap();
+        collected.elapsed = batch_started.elapsed();
+        match (failures.error(&collected), input_error) {
+            (Some(error), _) | (None, Some(error)) => Err(error),
+            (None, None) => Ok(collected.take()),
+        }
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    async fn download_presigned_attempt(
+        client: &reqwest::Client,
+        url: &str,
+        local_path: &str,
+        progress: &ProgressState,
+        bandwidth: Option<&TokenBucket>,
+        memory: Option<&MemoryBudget>,
+        write_buffer_size: usize,
+        verify_checksum: bool,
+    ) -> Result<String, TransferError> {
+        progress.start_attempt();
+        let name = unsigned_url(url);
+        let response = client
+            .get(url)
+            .send()
+            .await
+            .map_err(|e| TransferError::from_http(format!("Failed to get '{}'", name), e))?;
+
+        let status = response.status();
+        if !status.is_success() {
+            let body = response.text().await.unwrap_or_default();
+            return Err(TransferError::from_status(
+                format!("Failed to get '{}'", name),
+                status.as_u16(),
+                &body,
+            ));
+        }
+
+        let mut attempt_progress = AttemptProgress::new(progress);
+        let content_length = response.content_length();
+        if let Some(content_length) = content_length {
+            attempt_progress.add_total_bytes(content_length);
+        }
+
+        let file = File::create(local_path).await.map_err(|e| {
+            TransferError::new(format!("Failed to create file '{}': {}", local_path, e))
+        })?;
+        let guard = PartialFileGuard::new(local_path);
+        if let Some(content_length) = content_length {
+            preallocate(&file, local_path, content_length).await?;
+        }
+
+        let _reserved = reserve(memory, write_buffer_size as u64).await;
+        let mut writer = BufWriter::with_capacity(write_buffer_size, file);
+        let header = |name: &str| {
+            response
+                .headers()
+                .get(name)
+                .and_then(|value| value.to_str().ok())
+        };
+        let etag = header("etag").filter(|_| {
+            !encrypted_etag(
+                header("x-amz-server-side-encryption"),
+                header("x-amz-server-side-encryption-customer-algorithm"),
+            )
+        });
+        let mut digest = BodyDigest::new(etag, verify_checksum);
+        let mut body = response.bytes_stream();
+        let mut received = 0;
+
+        while let Some(bytes) = body.try_next().await.map_err(|e| {
+            TransferError::from_http(format!("Failed to read response body for '{}'", name), e)
+        })? {
+            writer.write_all(&bytes).await.map_err(|e| {
+                TransferError::new(format!("Failed to write to file '{}': {}", local_path, e))
+            })?;
+            digest.update(&bytes);
+            received += bytes.len() as u64;
+            attempt_progress.add_bytes(bytes.len() as u64);
+            throttle(bandwidth, bytes.len() as u64).await;
+        }
+
+        writer.flush().await.map_err(|e| {
+            TransferError::new(format!("Failed to flush file '{}': {}", local_path, e))
+        })?;
+        check_length(name, content_length, received)?;
+        digest.verify(name)?;
+
+        guard.commit();
+        attempt_progress.commit();
+        Ok(local_path.to_string())
+    }
+
+    async fn create_parent_directory(local_path: &str) -> Result<(), TransferError> {
+        match Path::new(local_path).parent() {
+            Some(parent) => tokio::fs::create_dir_all(parent).await.map_err(|e| {
+                TransferError::new(format!(
+                    "Failed to create directory '{}': {}",
+                    parent.display(),
+                    e
+                ))
+            }),
+            None => Ok(()),
+        }
+    }
+}
+
+#[pymethods]
+impl S3Downloader {
+    #[new]
+    #[pyo3(signature = (region_name=None, max_concurrent_downloads=5, endpoint_url=None, force_path_style=false, config=None, runtime_threads=None, retry_policy=None, part_size=DEFAULT_PART_SIZE, part_concurrency=DEFAULT_PART_CONCURRENCY, client_encryption=None, max_bandwidth_bytes_per_sec=None, max_requests_per_sec=None, request_burst=None, limiter=None, write_buffer_size=DEFAULT_WRITE_BUFFER_SIZE, verify_checksum=false, auto_decompress=false, preserve_mtime=false, preserve_permissions=false, symlinks=SymlinkPolicy::Follow, audit_log=None, max_in_flight_bytes=None))]
+    #[allow(clippy::too_many_arguments)]
+    fn new(
+        region_name: Option<&str>,
+        max_concurrent_downloads: usize,
+        endpoint_url: Option<String>,
+        force_path_style: bool,
+        config: Option<PyRef<'_, S3Config>>,
+        runtime_threads: Option<usize>,
+        retry_policy: Option<RetryPolicy>,
+        part_size: u64,
+        part_concurrency: usize,
+        client_encryption: Option<ClientEncryption>,
+        max_bandwidth_bytes_per_sec: Option<u64>,
+        max_requests_per_sec: Option<f64>,
+        request_burst: Option<u32>,
+        limiter: Option<TransferLimiter>,
+        write_buffer_size: usize,
+        verify_checksum: bool,
+        auto_decompress: bool,
+        preserve_mtime: bool,
+        preserve_permissions: bool,
+        symlinks: SymlinkPolicy,
+        audit_log: Option<String>,
+        max_in_flight_bytes: Option<u64>,
+    ) -> PyResult<Self> {
+        if part_size == 0 {
+            return Err(PyValueError::new_err("part_size must be greater than zero"));
+        }
+        if write_buffer_size == 0 {
+            return Err(PyValueError::new_err(
+                "write_buffer_size must be greater than zero",
+            ));
+        }
+        if part_concurrency == 0 {
+            return Err(PyValueError::new_err(
+                "part_concurrency must be greater than zero",
+            ));
+        }
+
+        let s3_config = S3Config::from_args(
+            config.as_deref(),
+            region_name,
+            endpoint_url,
+            force_path_style,
+        )?;
+
+        let runtime = Arc::new(build_runtime(runtime_threads, Builder::new_multi_thread())?);
+        let envelope = client_encryption
+            .as_ref()
+            .map(|encryption| encryption.envelope(&s3_config, false).map(Arc::new))
+            .transpose()?;
+        let bandwidth = TokenBucket::bandwidth(max_bandwidth_bytes_per_sec)?.map(Arc::new);
+        let requests = TokenBucket::requests(max_requests_per_sec, request_burst)?.map(Arc::new);
+        let memory = MemoryBudget::new(max_in_flight_bytes)?.map(Arc::new);
+        let audit = audit_log.as_deref().map(AuditLog::open).transpose()?;
+
+        Ok(Self {
+            state: SharedState::new(ClientState {
+                settings: TransferSettings {
+                    part_size,
+                    part_concurrency,
+                    client_encryption: envelope,
+                    bandwidth,
+                    requests,
+                    limiter: limiter
+                        .as_ref()
+                        .map(|limiter| Arc::clone(&limiter.semaphore)),
+                    memory,
+                    write_buffer_size,
+                    verify_checksum,
+                    auto_decompress,
+                    preserve_mtime,
+                    preserve_permissions,
+                    symlinks,
+                    audit,
+                    ..TransferSettings::new(
+                        max_concurrent_downloads,
+                        retry_policy.unwrap_or_default(),
+                    )
+                },
+                s3_config,
+                runtime,
+            }),
+            runtime_threads,
+            client_encryption,
+            max_bandwidth_bytes_per_sec,
+            max_requests_per_sec,
+            request_burst,
+            limiter,
+            max_in_flight_bytes,
+        })
+    }
+
+    /// Pickles the downloader as its constructor arguments, its `S3Config`
+    /// included; unpickling builds a new runtime and client.
+    fn __getnewargs_ex__<'py>(&self, py: Python<'py>) -> PyResult<((), Bound<'py, PyDict>)> {
+        let state = self.state();
+        let settings = &state.settings;
+        let kwargs = PyDict::new(py);
+        kwargs.set_item("config", (*state.s3_config).clone())?;
+        kwargs.set_item("max_concurrent_downloads", settings.max_concurrent)?;
+        kwargs.set_item("runtime_threads", self.runtime_threads)?;
+        kwargs.set_item("retry_policy", settings.retry_policy.clone())?;
+        kwargs.set_item("part_size", settings.part_size)?;
+        kwargs.set_item("part_concurrency", settings.part_concurrency)?;
+        kwargs.set_item("client_encryption", self.client_encryption.clone())?;
+        kwargs.set_item(
+            "max_bandwidth_bytes_per_sec",
+            self.max_bandwidth_bytes_per_sec,
+        )?;
+        kwargs.set_item("max_requests_per_sec", self.max_requests_per_sec)?;
+        kwargs.set_item("request_burst", self.request_burst)?;
+        kwargs.set_item("limiter", self.limiter.clone())?;
+        kwargs.set_item("write_buffer_size", settings.write_buffer_size)?;
+        kwargs.set_item("verify_checksum", settings.verify_checksum)?;
+        kwargs.set_item("auto_decompress", settings.auto_decompress)?;
+        kwargs.set_item("preserve_mtime", settings.preserve_mtime)?;
+        kwargs.set_item("preserve_permissions", settings.preserve_permissions)?;
+        kwargs.set_item("symlinks", settings.symlinks.as_str())?;
+        kwargs.set_item(
+            "audit_log",
+            settings
+                .audit
+                .as_deref()
+                .map(|audit| audit.path().to_string_lossy().into_owned()),
+        )?;
+        kwargs.set_item("max_in_flight_bytes", self.max_in_flight_bytes)?;
+        Ok(((), kwargs))
+    }
+
+    /// Rebuilds the async runtime and the S3 client, for a process forked after
+    /// the downloader was created, whose threads and connections it can't use.
+    /// Fork while no transfers are running: their limiter slots stay taken in
+    /// the child.
+    fn reinitialize(&self) -> PyResult<()> {
+        let state = self.state();
+        let s3_config = Arc::new(state.s3_config.rebuilt());
+        let envelope = self
+            .client_encryption
+            .as_ref()
+            .map(|encryption| encryption.envelope(&s3_config, false).map(Arc::new))
+            .transpose()?;
+        let runtime = Arc::new(build_runtime(
+            self.runtime_threads,
+            Builder::new_multi_thread(),
+        )?);
+        self.state.replace(ClientState {
+            settings: TransferSettings {
+                client_encryption: envelope,
+                ..state.settings.clone()
+            },
+            s3_config,
+            runtime,
+        });
+        Ok(())
+    }
+
+    /// Returns `None` when `if_none_match` or `if_modified_since` show the file
+    /// already at `path_to_store` is current, leaving it untouched.
+    #[pyo3(signature=(bucket_name, object_key, path_to_store, version_id=None, if_none_match=None, if_modified_since=None))]
+    #[allow(clippy::too_many_arguments)]
+    fn download_file(
+        &self,
+        py: Python<'_>,
+        bucket_name: &str,
+        object_key: &str,
+        path_to_store: &str,
+        version_id: Option<&str>,
+        if_none_match: Option<String>,
+        if_modified_since: Option<Timestamp>,
+    ) -> PyResult<Option<String>> {
+        let state = &self.state();
+        let s3_config = Arc::clone(&state.s3_config);
+        let bucket_name = bucket_name.to_string();
+        let object_key = object_key.to_string();
+        let version_id = version_id.map(str::to_string);
+        let conditions = GetConditions::new(if_none_match, if_modified_since);
+        let path_to_store = path_to_store.to_string();
+        let settings = state.settings.clone();
+
+        match block_on_interruptible(py, &state.runtime, async move {
+            Self::download_single_file(
+                s3_config,
+                &bucket_name,
+                &object_key,
+                version_id.as_deref(),
+                &conditions,
+                &path_to_store,
+                &ProgressState::default(),
+                &settings,
+            )
+            .await
+        })? {
+            Err(error) if is_not_modified(&error) => Ok(None),
+            downloaded => downloaded
+                .map(|completed| Some(completed.into_name()))
+                .map_err(PyErr::from),
+        }
+    }
+
+    /// Downloads `length` bytes of the object from `offset` (to its end when
+    /// `length` is `None`, or its last `-offset` bytes when `offset` is negative).
+    #[pyo3(signature=(bucket_name, object_key, path_to_store, offset, length=None, version_id=None))]
+    #[allow(clippy::too_many_arguments)]
+    fn download_range(
+        &self,
+        py: Python<'_>,
+        bucket_name: &str,
+        object_key: &str,
+        path_to_store: &str,
+        offset: i64,
+        length: Option<u64>,
+        version_id: Option<&str>,
+    ) -> PyResult<String> {
+        let range = ByteRange::new(offset, length)?;
+        let state = &self.state();
+        let s3_config = Arc::clone(&state.s3_config);
+        let settings = state.settings.clone();
+
+        block_on_interruptible(py, &state.runtime, async move {
+            Self::download_slice(
+                &s3_config,
+                bucket_name,
+                object_key,
+                version_id,
+                &GetConditions::default(),
+                range,
+                path_to_store,
+                &ProgressState::default(),
+                &settings,
+            )
+            .await
+        })?
+        .map_err(PyErr::from)
+    }
+
+    /// Downloads several slices of the object concurrently, into their offsets of
+    /// `path_to_store` or, without one, as a list of buffers.
+    #[pyo3(signature=(bucket_name, object_key, ranges, path_to_store=None, version_id=None))]
+    fn download_ranges(
+        &self,
+        py: Python<'_>,
+        bucket_name: &str,
+        object_key: &str,
+        ranges: Vec<ByteRange>,
+        path_to_store: Option<&str>,
+        version_id: Option<&str>,
+    ) -> PyResult<Slices> {
+        if ranges.is_empty() {
+            return Err(PyValueError::new_err("ranges must not be empty"));
+        }
+        let state = &self.state();
+        let s3_config = Arc::clone(&state.s3_config);
+        let settings = &state.settings;
+
+        block_on_interruptible(
+            py,
+            &state.runtime,
+            Self::download_ranges_to(
+                &s3_config,
+                bucket_name,
+                object_key,
+                version_id,
+                &ranges,
+                path_to_store,
+                settings,
+            ),
+        )?
+        .map_err(PyErr::from)
+    }
+
+    /// Downloads a tar, gzipped tar or zip archive and unpacks it below
+    /// `dest_dir` as it arrives.
+    #[pyo3(signature = (bucket_name, object_key, dest_dir, format=ArchiveFormat::TarGz))]
+    fn download_and_extract(
+        &self,
+        py: Python<'_>,
+        bucket_name: &str,
+        object_key: &str,
+        dest_dir: &str,
+        format: ArchiveFormat,
+    ) -> PyResult<Results> {
+        let state = &self.state();
+        let settings = &state.settings;
+
+        block_on_interruptible(
+            py,
+            &state.runtime,
+            Self::download_extracted(
+                &state.s3_config,
+                bucket_name,
+                object_key,
+                format,
+                dest_dir,
+                settings,
+            ),
+        )?
+        .map(Self::extracted_results)
+        .map_err(PyErr::from)
+    }
+
+    /// Downloads the files an `archive_mode` upload packed, by unpacking the
+    /// bundles its manifest `manifest_key` names below `dest_dir`.
+    #[pyo3(signature = (bucket_name, manifest_key, dest_dir))]
+    fn download_archived(
+        &self,
+        py: Python<'_>,
+        bucket_name: &str,
+        manifest_key: &str,
+        dest_dir: &str,
+    ) -> PyResult<Results> {
+        let state = &self.state();
+
+        block_on_interruptible(
+            py,
+            &state.runtime,
+            Self::download_archived_bundles(
+                &state.s3_config,
+                bucket_name,
+                manifest_key,
+                dest_dir,
+                &state.settings,
+            ),
+        )?
+        .map(Self::extracted_results)
+        .map_err(PyErr::from)
+    }
+
+    /// With `dry_run`, each object is only looked up with a HEAD request and
+    /// the plan of the downloads returned.
+    #[pyo3(signature = (bucket_name, object_keys, base_directory, progress=None, overwrite=OverwritePolicy::Always, preserve_structure=false, fail_fast=false, max_failures=None, raise_on_failure=false, checksum_manifest=None, expected_sha256=None, check_space=false, scheduling=Scheduling::Fifo, dry_run=false))]
+    #[allow(clippy::too_many_arguments)]
+    fn download_multiple_files(
+        &self,
+        py: Python<'_>,
+        bucket_name: &str,
+        object_keys: BatchInput<String>,
+        base_directory: &str,
+        progress: Option<TransferProgress>,
+        overwrite: OverwritePolicy,
+        preserve_structure: bool,
+        fail_fast: bool,
+        max_failures: Option<usize>,
+        raise_on_failure: bool,
+        checksum_manifest: Option<String>,
+        expected_sha256: Option<HashMap<String, String>>,
+        check_space: bool,
+        scheduling: Scheduling,
+        dry_run: bool,
+    ) -> PyResult<Planned<Results>> {
+        let failure_limit = failure_limit(fail_fast, max_failures)?;
+        let sha256 = Sha256Sums::requested(checksum_manifest.is_some(), expected_sha256)?;
+        if dry_run {
+            let base_directory = Path::new(base_directory);
+            let downloads = object_keys.into_stream().map_ok(|object_key| {
+                Self::key_download(
+                    Some(bucket_name),
+                    base_directory,
+                    preserve_structure,
+                    object_key,
+                )
+            });
+            return self.plan(py, Some(bucket_name), downloads, overwrite);
+        }
+        let state = &self.state();
+        let collected = Arc::new(Mutex::new(RustOperationResult::default()));
+        let (object_keys, total) = block_on_interruptible(
+            py,
+            &state.runtime,
+            Self::batch_keys(
+                &state.s3_config,
+                Some(bucket_name),
+                object_keys,
+                base_directory,
+                preserve_structure,
+                &state.settings,
+                check_space,
+                scheduling,
+            ),
+        )??;
+
+        block_on_interruptible(
+            py,
+            &state.runtime,
+            Self::download_files_concurrent(
+                Arc::clone(&state.s3_config),
+                Some(bucket_name),
+                object_keys,
+                total,
+                base_directory,
+                preserve_structure,
+                state
+                    .settings
+                    .clone()
+                    .stopping_after(failure_limit)
+                    .hashing_into(sha256.clone()),
+                overwrite,
+                progress.unwrap_or_default().state,
+                Arc::clone(&collected),
+            ),
+        )
+        .and_then(|result| result)
+        .map(|result| Self::into_results(result, Some(bucket_name)))
+        .map_err(|e| with_partial_results(py, e, &collected))
+        .and_then(|results| with_manifest(results, sha256.as_deref(), checksum_manifest.as_deref()))
+        .and_then(|results| checked(raise_on_failure, results))
+        .map(Planned::Done)
+    }
+
+    /// `if_none_match` maps keys to the ETag of the copy already at their local
+    /// path; those copies, and any unmodified since `if_modified_since`, are
+    /// left alone and reported as skipped. With `dry_run`, only the plan of the
+    /// downloads is returned, as `download_multiple_files` makes it.
+    #[pyo3(signature = (bucket_name, downloads, progress=None, overwrite=OverwritePolicy::Always, if_none_match=None, if_modified_since=None, fail_fast=false, max_failures=None, raise_on_failure=false, checksum_manifest=None, expected_sha256=None, dry_run=false))]
+    #[allow(clippy::too_many_arguments)]
+    fn download_multiple_files_with_paths(
+        &self,
+        py: Python<'_>,
+        bucket_name: &str,
+        downloads: BatchInput<DownloadItem>,
+        progress: Option<TransferProgress>,
+        overwrite: OverwritePolicy,
+        if_none_match: Option<HashMap<String, String>>,
+        if_modified_since: Option<Timestamp>,
+        fail_fast: bool,
+        max_failures: Option<usize>,
+        raise_on_failure: bool,
+        checksum_manifest: Option<String>,
+        expected_sha256: Option<HashMap<String, String>>,
+        dry_run: bool,
+    ) -> PyResult<Planned<Results>> {
+        let failure_limit = failure_limit(fail_fast, max_failures)?;
+        let sha256 = Sha256Sums::requested(checksum_manifest.is_some(), expected_sha256)?;
+        let total = downloads.len();
+        let downloads = conditional_downloads(downloads, if_none_match, if_modified_since);
+        if dry_run {
+            return self.plan(py, Some(bucket_name), downloads, overwrite);
+        }
+        self.run_downloads(
+            py,
+            Some(bucket_name),
+            downloads,
+            total,
+            progress,
+            overwrite,
+            failure_limit,
+            sha256.clone(),
+            None,
+        )
+        .and_then(|results| with_manifest(results, sha256.as_deref(), checksum_manifest.as_deref()))
+        .and_then(|results| checked(raise_on_failure, results))
+        .map(Planned::Done)
+    }
+
+    /// `download_multiple_files` running in the background, iterated item by
+    /// item as they finish.
+    #[pyo3(signature = (bucket_name, object_keys, base_directory, overwrite=OverwritePolicy::Always, preserve_structure=false, fail_fast=false, max_failures=None))]
+    #[allow(clippy::too_many_arguments)]
+    fn iter_download_multiple_files(
+        &self,
+        bucket_name: String,
+        object_keys: BatchInput<String>,
+        base_directory: String,
+        overwrite: OverwritePolicy,
+        preserve_structure: bool,
+        fail_fast: bool,
+        max_failures: Option<usize>,
+    ) -> PyResult<BatchIterator> {
+        let state = &self.state();
+        let s3_config = Arc::clone(&state.s3_config);
+        let settings = state
+            .settings
+            .clone()
+            .stopping_after(failure_limit(fail_fast, max_failures)?);
+        let total = object_keys.len();
+
+        Ok(BatchIterator::spawn(
+            Arc::clone(&state.runtime),
+            total,
+            move |collected| async move {
+                Self::download_files_concurrent(
+                    s3_config,
+                    Some(&bucket_name),
+                    object_keys.into_stream(),
+                    total,
+                    &base_directory,
+                    preserve_structure,
+                    settings,
+                    overwrite,
+                    Arc::default(),
+                    collected,
+                )
+                .await
+                .map(|result| Self::into_results(result, Some(&bucket_name)))
+            },
+        ))
+    }
+
+    /// `download_multiple_files_with_paths` running in the background, iterated
+    /// item by item as they finish.
+    #[pyo3(signature = (bucket_name, downloads, overwrite=OverwritePolicy::Always, fail_fast=false, max_failures=None))]
+    fn iter_download_multiple_files_with_paths(
+        &self,
+        bucket_name: String,
+        downloads: BatchInput<DownloadItem>,
+        overwrite: OverwritePolicy,
+        fail_fast: bool,
+        max_failures: Option<usize>,
+    ) -> PyResult<BatchIterator> {
+        let state = &self.state();
+        let s3_config = Arc::clone(&state.s3_config);
+        let settings = state
+            .settings
+            .clone()
+            .stopping_after(failure_limit(fail_fast, max_failures)?);
+        let total = downloads.len();
+        let downloads = conditional_downloads(downloads, None, None);
+
+        Ok(BatchIterator::spawn(
+            Arc::clone(&state.runtime),
+            total,
+            move |collected| async move {
+                Self::download_files_concurrent_with_paths(
+                    s3_config,
+                    Some(bucket_name.clone()),
+                    downloads,
+                    total,
+                    settings,
+                    overwrite,
+                    Arc::default(),
+                    collected,
+                )
+                .await
+                .map(|result| Self::into_results(result, Some(&bucket_name)))
+            },
+        ))
+    }
+
+    /// Downloads `(bucket, key, local_path)` items, which may come from different
+    /// buckets, in one batch. Items are reported by their `s3://` URI. With
+    /// `dry_run`, only the plan of the downloads is returned.
+    #[pyo3(signature = (items, progress=None, overwrite=OverwritePolicy::Always, fail_fast=false, max_failures=None, raise_on_failure=false, dry_run=false))]
+    #[allow(clippy::too_many_arguments)]
+    fn download_multiple_objects(
+        &self,
+        py: Python<'_>,
+        items: BatchInput<(String, String, String)>,
+        progress: Option<TransferProgress>,
+        overwrite: OverwritePolicy,
+        fail_fast: bool,
+        max_failures: Option<usize>,
+        raise_on_failure: bool,
+        dry_run: bool,
+    ) -> PyResult<Planned<Results>> {
+        let failure_limit = failure_limit(fail_fast, max_failures)?;
+        let total = items.len();
+        let downloads = items.into_stream().map_ok(object_download);
+        if dry_run {
+            return self.plan(py, None, downloads, overwrite);
+        }
+        self.run_downloads(
+            py,
+            None,
+            downloads,
+            total,
+            progress,
+            overwrite,
+            failure_limit,
+            None,
+            None,
+        )
+        .and_then(|results| checked(raise_on_failure, results))
+        .map(Planned::Done)
+    }
+
+    /// Downloads the objects a CSV or JSON Lines manifest lists, reading the
+    /// manifest as the batch goes. Rows that name no bucket are in
+    /// `bucket_name`. Items are reported by their `s3://` URI, as
+    /// `download_multiple_objects` reports them, `dry_run` included.
+    #[pyo3(signature = (manifest_path, bucket_name=None, format=None, progress=None, overwrite=OverwritePolicy::Always, fail_fast=false, max_failures=None, raise_on_failure=false, dry_run=false))]
+    #[allow(clippy::too_many_arguments)]
+    fn download_from_manifest(
+        &self,
+        py: Python<'_>,
+        manifest_path: String,
+        bucket_name: Option<String>,
+        format: Option<ManifestFormat>,
+        progress: Option<TransferProgress>,
+        overwrite: OverwritePolicy,
+        fail_fast: bool,
+        max_failures: Option<usize>,
+        raise_on_failure: bool,
+        dry_run: bool,
+    ) -> PyResult<Planned<Results>> {
+        let format = ManifestFormat::of(&manifest_path, format)?;
+        let failure_limit = failure_limit(fail_fast, max_failures)?;
+        let downloads = read_manifest(manifest_path, format, bucket_name).map_ok(|entry| {
+            Ok(TransferRequest::from((
+                entry.object.to_string(),
+                entry.local_path,
+            )))
+        });
+        if dry_run {
+            return self.plan(py, None, downloads, overwrite);
+        }
+        self.run_downloads(
+            py,
+            None,
+            downloads,
+            None,
+            progress,
+            overwrite,
+            failure_limit,
+            None,
+            None,
+        )
+        .and_then(|results| checked(raise_on_failure, results))
+        .map(Planned::Done)
+    }
+
+    /// Downloads `(url, local_path)` pairs of presigned URLs with plain GETs, needing
+    /// no credentials. The results can't be retried with `retry_failed`, since the
+    /// URLs may have expired by then.
+    #[pyo3(signature = (urls_and_paths, progress=None, fail_fast=false, max_failures=None, raise_on_failure=false))]
+    #[allow(clippy::too_many_arguments)]
+    fn download_presigned_urls(
+        &self,
+        py: Python<'_>,
+        urls_and_paths: BatchInput<(String, String)>,
+        progress: Option<TransferProgress>,
+        fail_fast: bool,
+        max_failures: Option<usize>,
+        raise_on_failure: bool,
+    ) -> PyResult<Results> {
+        let failure_limit = failure_limit(fail_fast, max_failures)?;
+        let state = &self.state();
+        let collected = Arc::new(Mutex::new(RustOperationResult::default()));
+        let total = urls_and_paths.len();
+
+        block_on_interruptible(
+            py,
+            &state.runtime,
+            Self::download_presigned_concurrent(
+                urls_and_paths.into_stream(),
+                total,
+                state.settings.clone().stopping_after(failure_limit),
+                progress.unwrap_or_default().state,
+                Arc::clone(&collected),
+            ),
+        )
+        .and_then(|result| result)
+        .map(|result| {
+            Self::log_failures(&result);
+            Results::from(result)
+        })
+        .map_err(|e| with_partial_results(py, e, &collected))
+        .and_then(|results| checked(raise_on_failure, results))
+    }
+
+    /// Downloads the object `uri` points to.
+    #[pyo3(signature = (uri, path_to_store))]
+    fn download_uri(&self, py: Python<'_>, uri: S3Uri, path_to_store: &str) -> PyResult<String> {
+        let state = &self.state();
+        let s3_config = Arc::clone(&state.s3_config);
+        let settings = state.settings.clone();
+
+        block_on_interruptible(py, &state.runtime, async move {
+            Self::download_single_file(
+                s3_config,
+                &uri.bucket_name,
+                &uri.object_key,
+                None,
+                &GetConditions::default(),
+                path_to_store,
+                &ProgressState::default(),
+                &settings,
+            )
+            .await
+        })?
+        .map(Completed::into_name)
+        .map_err(PyErr::from)
+    }
+
+    /// Downloads `s3://` URIs below `base_directory`, as `download_multiple_files`
+    /// does keys, `dry_run` included; the URIs may point to different buckets.
+    #[pyo3(signature = (uris, base_directory, progress=None, overwrite=OverwritePolicy::Always, preserve_structure=false, fail_fast=false, max_failures=None, raise_on_failure=false, dry_run=false))]
+    #[allow(clippy::too_many_arguments)]
+    fn download_multiple_uris(
+        &self,
+        py: Python<'_>,
+        uris: BatchInput<String>,
+        base_directory: &str,
+        progress: Option<TransferProgress>,
+        overwrite: OverwritePolicy,
+        preserve_structure: bool,
+        fail_fast: bool,
+        max_failures: Option<usize>,
+        raise_on_failure: bool,
+        dry_run: bool,
+    ) -> PyResult<Planned<Results>> {
+        let failure_limit = failure_limit(fail_fast, max_failures)?;
+        if dry_run {
+            let base_directory = Path::new(base_directory);
+            let downloads = uris
+                .into_stream()
+                .map_ok(|uri| Self::key_download(None, base_directory, preserve_structure, uri));
+            return self.plan(py, None, downloads, overwrite);
+        }
+        let state = &self.state();
+        let collected = Arc::new(Mutex::new(RustOperationResult::default()));
+        let total = uris.len();
+
+        block_on_interruptible(
+            py,
+            &state.runtime,
+            Self::download_files_concurrent(
+                Arc::clone(&state.s3_config),
+                None,
+                uris.into_stream(),
+                total,
+                base_directory,
+                preserve_structure,
+                state.settings.clone().stopping_after(failure_limit),
+                overwrite,
+                progress.unwrap_or_default().state,
+                Arc::clone(&collected),
+            ),
+        )
+        .and_then(|result| result)
+        .map(|result| Self::into_results(result, None))
+        .map_err(|e| with_partial_results(py, e, &collected))
+        .and_then(|results| checked(raise_on_failure, results))
+        .map(Planned::Done)
+    }
+
+    /// Downloads every object under `prefix`, recreating the key hierarchy below
+    /// `base_directory`. With `dry_run`, the objects are only listed and the plan
+    /// of their downloads returned.
+    #[pyo3(signature = (bucket_name, prefix, base_directory, include=None, exclude=None, progress=None, overwrite=OverwritePolicy::Always, fail_fast=false, max_failures=None, raise_on_failure=false, min_size=None, max_size=None, modified_after=None, modified_before=None, key_pattern=None, checksum_manifest=None, expected_sha256=None, dry_run=false, check_space=false, scheduling=Scheduling::Fifo))]
+    #[allow(clippy::too_many_arguments)]
+    fn download_prefix(
+        &self,
+        py: Python<'_>,
+        bucket_name: &str,
+        prefix: &str,
+        base_directory: &str,
+        include: Option<Vec<String>>,
+        exclude: Option<Vec<String>>,
+        progress: Option<TransferProgress>,
+        overwrite: OverwritePolicy,
+        fail_fast: bool,
+        max_failures: Option<usize>,
+        raise_on_failure: bool,
+        min_size: Option<u64>,
+        max_size: Option<u64>,
+        modified_after: Option<Timestamp>,
+        modified_before: Option<Timestamp>,
+        key_pattern: Option<String>,
+        checksum_manifest: Option<String>,
+        expected_sha256: Option<HashMap<String, String>>,
+        dry_run: bool,
+        check_space: bool,
+        scheduling: Scheduling,
+    ) -> PyResult<Planned<Results>> {
+        let failure_limit = failure_limit(fail_fast, max_failures)?;
+        let sha256 = Sha256Sums::requested(checksum_manifest.is_some(), expected_sha256)?;
+        let state = &self.state();
+        let filter = PathFilter::new(include, exclude)?;
+        let objects = ObjectFilter::new(
+            min_size,
+            max_size,
+            modified_after,
+            modified_before,
+            key_pattern,
+        )?;
+        let mut objects = block_on_interruptible(
+            py,
+            &state.runtime,
+            Self::prefix_objects(
+                &state.s3_config,
+                bucket_name,
+                prefix,
+                &filter,
+                &objects,
+                None,
+                &state.settings.retry_policy,
+            ),
+        )??;
+        scheduling.order(&mut objects, |(_, object)| object.size);
+        if dry_run {
+            return block_on_interruptible(
+                py,
+                &state.runtime,
+                Self::plan_prefix_downloads(objects, base_directory, overwrite),
+            )
+            .map(Planned::Plan);
+        }
+        if check_space {
+            let sizes = Self::prefix_sizes(&objects, base_directory);
+            block_on_interruptible(
+                py,
+                &state.runtime,
+                ensure_space(Path::new(base_directory), sizes),
+            )??;
+        }
+
+        let downloads = Self::prefix_downloads(objects, base_directory);
+        let total = downloads.len();
+        self.run_downloads(
+            py,
+            Some(bucket_name),
+            ready_items(downloads),
+            Some(total),
+            progress,
+            overwrite,
+            failure_limit,
+            sha256.clone(),
+            Some(Path::new(base_directory)),
+        )
+        .and_then(|results| with_manifest(results, sha256.as_deref(), checksum_manifest.as_deref()))
+        .and_then(|results| checked(raise_on_failure, results))
+        .map(Planned::Done)
+    }
+
+    /// Downloads the objects the S3 Inventory report `manifest_uri` lists,
+    /// reading its CSV or Parquet data files as the batch goes instead of
+    /// listing the bucket. Objects are selected and laid out below
+    /// `base_directory` as `download_prefix` does; delete markers and
+    /// noncurrent versions are left out.
+    #[pyo3(signature = (manifest_uri, base_directory, prefix="", include=None, exclude=None, progress=None, overwrite=OverwritePolicy::Always, fail_fast=false, max_failures=None, raise_on_failure=false, min_size=None, max_size=None, modified_after=None, modified_before=None, key_pattern=None))]
+    #[allow(clippy::too_many_arguments)]
+    fn download_from_inventory(
+        &self,
+        py: Python<'_>,
+        manifest_uri: S3Uri,
+        base_directory: &str,
+        prefix: &str,
+        include: Option<Vec<String>>,
+        exclude: Option<Vec<String>>,
+        progress: Option<TransferProgress>,
+        overwrite: OverwritePolicy,
+        fail_fast: bool,
+        max_failures: Option<usize>,
+        raise_on_failure: bool,
+        min_size: Option<u64>,
+        max_size: Option<u64>,
+        modified_after: Option<Timestamp>,
+        modified_before: Option<Timestamp>,
+        key_pattern: Option<String>,
+    ) -> PyResult<Results> {
+        let failure_limit = failure_limit(fail_fast, max_failures)?;
+        let filter = PathFilter::new(include, exclude)?;
+        let objects = ObjectFilter::new(
+            min_size,
+            max_size,
+            modified_after,
+            modified_before,
+            key_pattern,
+        )?;
+        let state = &self.state();
+        let (inventory, reader) = block_on_interruptible(
+            py,
+            &state.runtime,
+            Self::inventory_manifest(&state.s3_config, &manifest_uri, &state.settings),
+        )??;
+
+        let downloads = Self::inventory_downloads(
+            &state.s3_config,
+            &state.settings,
+            &manifest_uri.bucket_name,
+            inventory.data_keys(),
+            reader,
+            (prefix, &filter, &objects),
+            base_directory,
+        );
+        self.run_downloads(
+            py,
+            Some(inventory.source_bucket.as_str()),
+            downloads,
+            None,
+            progress,
+            overwrite,
+            failure_limit,
+            None,
+            Some(Path::new(base_directory)),
+        )
+        .and_then(|results| checked(raise_on_failure, results))
+    }
+
+    /// Downloads the `n` most recently modified objects under `prefix`, laid out
+    /// below `base_directory` as `download_prefix` does, `dry_run` included.
+    #[pyo3(signature = (bucket_name, prefix, n, base_directory, include=None, exclude=None, progress=None, overwrite=OverwritePolicy::Always, fail_fast=false, max_failures=None, raise_on_failure=false, dry_run=false, check_space=false, scheduling=Scheduling::Fifo))]
+    #[allow(clippy::too_many_arguments)]
+    fn download_latest(
+        &self,
+        py: Python<'_>,
+        bucket_name: &str,
+        prefix: &str,
+        n: usize,
+        base_directory: &str,
+        include: Option<Vec<String>>,
+        exclude: Option<Vec<String>>,
+        progress: Option<TransferProgress>,
+        overwrite: OverwritePolicy,
+        fail_fast: bool,
+        max_failures: Option<usize>,
+        raise_on_failure: bool,
+        dry_run: bool,
+        check_space: bool,
+        scheduling: Scheduling,
+    ) -> PyResult<Planned<Results>> {
+        if n == 0 {
+            return Err(PyValueError::new_err("n must be greater than zero"));
+        }
+        let failure_limit = failure_limit(fail_fast, max_failures)?;
+        let state = &self.state();
+        let filter = PathFilter::new(include, exclude)?;
+        let mut objects = block_on_interruptible(
+            py,
+            &state.runtime,
+            Self::prefix_objects(
+                &state.s3_config,
+                bucket_name,
+                prefix,
+                &filter,
+                &ObjectFilter::default(),
+                Some(n),
+                &state.settings.retry_policy,
+            ),
+        )??;
+        scheduling.order(&mut objects, |(_, object)| object.size);
+        if dry_run {
+            return block_on_interruptible(
+                py,
+                &state.runtime,
+                Self::plan_prefix_downloads(objects, base_directory, overwrite),
+            )
+            .map(Planned::Plan);
+        }
+        if check_space {
+            let sizes = Self::prefix_sizes(&objects, base_directory);
+            block_on_interruptible(
+                py,
+                &state.runtime,
+                ensure_space(Path::new(base_directory), sizes),
+            )??;
+        }
+
+        let downloads = Self::prefix_downloads(objects, base_directory);
+        let total = downloads.len();
+        self.run_downloads(
+            py,
+            Some(bucket_name),
+            ready_items(downloads),
+            Some(total),
+            progress,
+            overwrite,
+            failure_limit,
+            None,
+            Some(Path::new(base_directory)),
+        )
+        .and_then(|results| checked(raise_on_failure, results))
+        .map(Planned::Done)
+    }
+
+    /// Brings `local_dir` up to date with the objects under `prefix`, downloading
+    /// only what is missing or changed. With `dry_run`, only the plan of the
+    /// sync is returned.
+    #[pyo3(signature = (bucket_name, prefix, local_dir, delete=false, include=None, exclude=None, progress=None, fail_fast=false, max_failures=None, raise_on_failure=false, dry_run=false, check_space=false, scheduling=Scheduling::Fifo))]
+    #[allow(clippy::too_many_arguments)]
+    fn sync_down(
+        &self,
+        py: Python<'_>,
+        bucket_name: &str,
+        prefix: &str,
+        local_dir: &str,
+        delete: bool,
+        include: Option<Vec<String>>,
+        exclude: Option<Vec<String>>,
+        progress: Option<TransferProgress>,
+        fail_fast: bool,
+        max_failures: Option<usize>,
+        raise_on_failure: bool,
+        dry_run: bool,
+        check_space: bool,
+        scheduling: Scheduling,
+    ) -> PyResult<Planned<SyncReport>> {
+        let failure_limit = failure_limit(fail_fast, max_failures)?;
+        let state = &self.state();
+        let filter = PathFilter::new(include, exclude)?;
+        if dry_run {
+            let local_dir = Path::new(local_dir);
+            let plan = block_on_interruptible(
+                py,
+                &state.runtime,
+                Self::plan_sync_down(
+                    &state.s3_config,
+                    bucket_name,
+                    prefix,
+                    local_dir,
+                    &filter,
+                    delete,
+                    &state.settings.retry_policy,
+                ),
+            )??;
+            return Ok(Planned::Plan(plan.into_transfer_plan(local_dir)));
+        }
+        let collected = Arc::new(Mutex::new(RustOperationResult::default()));
+
+        let (result, changes) = block_on_interruptible(
+            py,
+            &state.runtime,
+            Self::sync_down_concurrent(
+                Arc::clone(&state.s3_config),
+                bucket_name.to_string(),
+                prefix.to_string(),
+                PathBuf::from(local_dir),
+                filter,
+                delete,
+                check_space,
+                scheduling,
+                state.settings.clone().stopping_after(failure_limit),
+                progress.unwrap_or_default().state,
+                Arc::clone(&collected),
+            ),
+        )
+        .map_err(|e| with_partial_results(py, e, &collected))??;
+
+        let results = checked(
+            raise_on_failure,
+            Self::into_results(result, Some(bucket_name)),
+        )?;
+        let results = Py::new(py, results)?;
+        Ok(Planned::Done(SyncReport::new(results, changes)))
+    }
+
+    /// Checks the files listed in a `SHA256SUMS` manifest against their digests,
+    /// reporting each as successful or failed. No object is read.
+    fn verify_against_manifest(&self, py: Python<'_>, manifest_path: &str) -> PyResult<Results> {
+        let state = &self.state();
+        block_on_interruptible(
+            py,
+            &state.runtime,
+            verify_manifest(manifest_path, state.settings.max_concurrent),
+        )?
+        .map(Results::from)
+    }
+
+    /// Downloads the failed items of `results` again and merges the outcome into it.
+    #[pyo3(signature = (results, progress=None, fail_fast=false, max_failures=None, raise_on_failure=false))]
+    #[allow(clippy::too_many_arguments)]
+    fn retry_failed<'py>(
+        &self,
+        py: Python<'py>,
+        results: Bound<'py, Results>,
+        progress: Option<TransferProgress>,
+        fail_fast: bool,
+        max_failures: Option<usize>,
+        raise_on_failure: bool,
+    ) -> PyResult<Bound<'py, Results>> {
+        let failure_limit = failure_limit(fail_fast, max_failures)?;
+        let (bucket_name, downloads) = results.borrow().retry_requests(TransferKind::Download)?;
+        let total = downloads.len();
+        let retried = self.run_downloads(
+            py,
+            bucket_name.as_deref(),
+            ready_items(downloads.into_iter().map(Ok).collect()),
+            Some(total),
+            progress,
+            OverwritePolicy::Always,
+            failure_limit,
+            None,
+            None,
+        )?;
+        results.borrow_mut().merge_retry(retried);
+        if raise_on_failure && !results.borrow().failed.is_empty() {
+            return Err(partial_failure(&results));
+        }
+        Ok(results)
+    }
+
+    #[pyo3(signature = (bucket_name, object_key))]
+    fn download_bytes(
+        &self,
+        py: Python<'_>,
+        bucket_name: &str,
+        object_key: &str,
+    ) -> PyResult<Cow<'static, [u8]>> {
+        let state = &self.state();
+        let s3_config = Arc::clone(&state.s3_config);
+        let settings = &state.settings;
+
+        block_on_interruptible(py, &state.runtime, async move {
+            Self::download_bytes_single(
+                &s3_config,
+                bucket_name,
+                object_key,
+                &ProgressState::default(),
+                settings,
+            )
+            .await
+        })?
+        .map(Cow::Owned)
+        .map_err(PyErr::from)
+    }
+
+    #[pyo3(signature = (bucket_name, object_keys, progress=None, fail_fast=false, max_failures=None))]
+    fn download_multiple_bytes(
+        &self,
+        py: Python<'_>,
+        bucket_name: String,
+        object_keys: BatchInput<String>,
+        progress: Option<TransferProgress>,
+        fail_fast: bool,
+        max_failures: Option<usize>,
+    ) -> PyResult<HashMap<String, Cow<'static, [u8]>>> {
+        let state = &self.state();
+        let failure_limit = failure_limit(fail_fast, max_failures)?;
+        let total = object_keys.len();
+        block_on_interruptible(
+            py,
+            &state.runtime,
+            Self::download_bytes_concurrent(
+                Arc::clone(&state.s3_config),
+                bucket_name,
+                object_keys.into_stream(),
+                total,
+                state.settings.clone().stopping_after(failure_limit),
+                progress.unwrap_or_default().state,
+            ),
+        )?
+    }
+
+    /// Fetches the metadata of an object with a HEAD request.
+    #[pyo3(signature = (bucket_name, object_key))]
+    fn head(
+        &self,
+        py: Python<'_>,
+        bucket_name: &str,
+        object_key: &str,
+    ) -> PyResult<ObjectMetadata> {
+        let state = &self.state();
+        let retry_policy = &state.settings.retry_policy;
+        block_on_interruptible(
+            py,
+            &state.runtime,
+            head_object(
+                &state.s3_config,
+                bucket_name,
+                object_key,
+                None,
+                retry_policy,
+            ),
+        )?
+        .map_err(PyErr::from)
+    }
+
+    /// Whether an object exists, without downloading it.
+    #[pyo3(signature = (bucket_name, object_key))]
+    fn exists(&self, py: Python<'_>, bucket_name: &str, object_key: &str) -> PyResult<bool> {
+        let state = &self.state();
+        let retry_policy = &state.settings.retry_policy;
+        block_on_interruptible(
+            py,
+            &state.runtime,
+            object_exists(&state.s3_config, bucket_name, object_key, retry_policy),
+        )?
+        .map_err(PyErr::from)
+    }
+
+    /// Fetches the metadata of several objects concurrently, leaving out the ones
+    /// that can't be described.
+    #[pyo3(signature = (bucket_name, object_keys, progress=None, fail_fast=false, max_failures=None))]
+    fn head_multiple(
+        &self,
+        py: Python<'_>,
+        bucket_name: String,
+        object_keys: BatchInput<String>,
+        progress: Option<TransferProgress>,
+        fail_fast: bool,
+        max_failures: Option<usize>,
+    ) -> PyResult<HashMap<String, ObjectMetadata>> {
+        let state = &self.state();
+        let failure_limit = failure_limit(fail_fast, max_failures)?;
+        let total = object_keys.len();
+        block_on_interruptible(
+            py,
+            &state.runtime,
+            head_objects(
+                Arc::clone(&state.s3_config),
+                bucket_name,
+                object_keys.into_stream(),
+                total,
+                state.settings.clone().stopping_after(failure_limit),
+                progress.unwrap_or_default().state,
+            ),
+        )?
+    }
+
+    /// Requests temporary copies of archived objects, readable for `days`.
+    /// Objects already being restored are reported as skipped.
+    #[pyo3(signature = (bucket_name, object_keys, tier=RestoreTier::Standard, days=1, progress=None, fail_fast=false, max_failures=None))]
+    #[allow(clippy::too_many_arguments)]
+    fn restore_objects(
+        &self,
+        py: Python<'_>,
+        bucket_name: &str,
+        object_keys: BatchInput<String>,
+        tier: RestoreTier,
+        days: i32,
+        progress: Option<TransferProgress>,
+        fail_fast: bool,
+        max_failures: Option<usize>,
+    ) -> PyResult<Results> {
+        let state = &self.state();
+        let days = restore_days(days)?;
+        let settings = state
+            .settings
+            .clone()
+            .stopping_after(failure_limit(fail_fast, max_failures)?);
+        let progress = progress.unwrap_or_default().state;
+        let total = object_keys.len();
+        let result = block_on_interruptible(
+            py,
+            &state.runtime,
+            restore_objects(
+                &state.s3_config,
+                bucket_name,
+                object_keys.into_stream(),
+                total,
+                tier,
+                days,
+                &settings,
+                &progress,
+            ),
+        )??;
+        Ok(Results::from(result))
+    }
+
+    /// Blocks until the restores of the objects have completed, polling every
+    /// `poll_interval` seconds.
+    #[pyo3(signature = (bucket_name, object_keys, poll_interval=60.0, timeout=None))]
+    fn wait_for_restore(
+        &self,
+        py: Python<'_>,
+        bucket_name: &str,
+        object_keys: Vec<String>,
+        poll_interval: f64,
+        timeout: Option<f64>,
+    ) -> PyResult<()> {
+        let state = &self.state();
+        let (poll_interval, timeout) = poll_durations(poll_interval, timeout)?;
+        block_on_interruptible(
+            py,
+            &state.runtime,
+            wait_for_restore(
+                &state.s3_config,
+                bucket_name,
+                object_keys,
+                poll_interval,
+                timeout,
+                &state.settings,
+            ),
+        )?
+        .map_err(PyErr::from)
+    }
+
+    /// Lists the objects of a bucket, lazily, one ListObjectsV2 page at a time,
+    /// keeping those that pass the size, time and key filters.
+    #[pyo3(signature = (bucket_name, prefix=None, delimiter=None, min_size=None, max_size=None, modified_after=None, modified_before=None, key_pattern=None))]
+    #[allow(clippy::too_many_arguments)]
+    fn list_objects(
+        &self,
+        bucket_name: String,
+        prefix: Option<String>,
+        delimiter: Option<String>,
+        min_size: Option<u64>,
+        max_size: Option<u64>,
+        modified_after: Option<Timestamp>,
+        modified_before: Option<Timestamp>,
+        key_pattern: Option<String>,
+    ) -> PyResult<ObjectListing> {
+        let filter = ObjectFilter::new(
+            min_size,
+            max_size,
+            modified_after,
+            modified_before,
+            key_pattern,
+        )?;
+        let state = &self.state();
+        Ok(ObjectListing::new(
+            Arc::clone(&state.runtime),
+            Arc::clone(&state.s3_config),
+            state.settings.retry_policy.clone(),
+            ListRequest {
+                bucket_name,
+                prefix,
+                delimiter,
+            },
+            filter,
+        ))
+    }
+
+    /// Lists every version and delete marker of the objects under `prefix`.
+    #[pyo3(signature = (bucket_name, prefix=None))]
+    fn list_object_versions(
+        &self,
+        py: Python<'_>,
+        bucket_name: &str,
+        prefix: Option<&str>,
+    ) -> PyResult<Vec<ObjectVersion>> {
+        let state = &self.state();
+        let (s3_config, retry_policy) = (&state.s3_config, &state.settings.retry_policy);
+        block_on_interruptible(py, &state.runtime, async move {
+            list_object_versions(s3_config, bucket_name, prefix, retry_policy).await
+        })?
+        .map_err(PyErr::from)
+    }
+
+    /// Iterates over the object's contents in `chunk_size` pieces as they arrive.
+    #[pyo3(signature = (bucket_name, object_key, chunk_size=DEFAULT_CHUNK_SIZE))]
+    fn stream(
+        &self,
+        bucket_name: String,
+        object_key: String,
+        chunk_size: usize,
+    ) -> PyResult<ObjectStream> {
+        let state = &self.state();
+        if chunk_size == 0 {
+            return Err(PyValueError::new_err(
+                "chunk_size must be greater than zero",
+            ));
+        }
+
+        Ok(ObjectStream::start(
+            Arc::clone(&state.runtime),
+            Arc::clone(&state.s3_config),
+            bucket_name,
+            object_key,
+            chunk_size,
+            state.settings.retry_policy.clone(),
+            state.settings.bandwidth.clone(),
+        ))
+    }
+
+    /// Runs an S3 Select query over the object, iterating over the records it
+    /// selects as they arrive.
+    #[pyo3(signature = (bucket_name, object_key, sql, input_format=SelectInput::Csv, output_format=SelectOutput::Json, compression=None, csv_header=true, rows=false))]
+    #[allow(clippy::too_many_arguments)]
+    fn select(
+        &self,
+        bucket_name: String,
+        object_key: String,
+        sql: String,
+        input_format: SelectInput,
+        output_format: SelectOutput,
+        compression: Option<SelectCompression>,
+        csv_header: bool,
+        rows: bool,
+    ) -> ObjectStream {
+        let state = &self.state();
+        let query = SelectQuery {
+            sql,
+            input: input_format,
+            output: output_format,
+            compression,
+            csv_header,
+        };
+        query.start(
+            Arc::clone(&state.runtime),
+            Arc::clone(&state.s3_config),
+            bucket_name,
+            object_key,
+            rows,
+            state.settings.retry_policy.clone(),
+            state.settings.bandwidth.clone(),
+        )
+    }
+
+    /// Opens the object as a read-only, seekable file object.
+    #[pyo3(signature = (bucket_name, object_key, buffer_size=DEFAULT_CHUNK_SIZE as u64))]
+    fn open(
+        &self,
+        py: Python<'_>,
+        bucket_name: String,
+        object_key: String,
+        buffer_size: u64,
+    ) -> PyResult<S3File> {
+        let state = &self.state();
+        if buffer_size == 0 {
+            return Err(PyValueError::new_err(
+                "buffer_size must be greater than zero",
+            ));
+        }
+
+        S3File::open(
+            py,
+            Arc::clone(&state.runtime),
+            Arc::clone(&state.s3_config),
+            state.settings.retry_policy.clone(),
+            bucket_name,
+            object_key,
+            buffer_size,
+        )
+    }
+
+    #[pyo3(signature=(bucket_name, object_key, path_to_store, version_id=None, if_none_match=None, if_modified_since=None))]
+    #[allow(clippy::too_many_arguments)]
+    fn download_file_async<'py>(
+        &self,
+        py: Python<'py>,
+        bucket_name: String,
+        object_key: String,
+        path_to_store: String,
+        version_id: Option<String>,
+        if_none_match: Option<String>,
+        if_modified_since: Option<Timestamp>,
+    ) -> PyResult<Bound<'py, PyAny>> {
+        let state = &self.state();
+        let s3_config = Arc::clone(&state.s3_config);
+        let settings = state.settings.clone();
+        let conditions = GetConditions::new(if_none_match, if_modified_since);
 
-        let file = File::create(local_path)
+        future_into_py(py, &state.runtime, async move {
+            match Self::download_single_file(
+                s3_config,
+                &bucket_name,
+                &object_key,
+                version_id.as_deref(),
+                &conditions,
+                &path_to_store,
+                &ProgressState::default(),
+                &settings,
+            )
             .await
-            .map_err(|e| format!("Failed to create file '{}': {}", local_path, e))?;
+            {
+                Err(error) if is_not_modified(&error) => Ok(None),
+                downloaded => downloaded
+                    .map(|completed| Some(completed.into_name()))
+                    .map_err(PyErr::from),
+            }
+        })
+    }
 
-        let mut writer = BufWriter::new(file);
-        let mut body = response.body;
+    #[pyo3(signature=(bucket_name, object_key, path_to_store, offset, length=None, version_id=None))]
+    #[allow(clippy::too_many_arguments)]
+    fn download_range_async<'py>(
+        &self,
+        py: Python<'py>,
+        bucket_name: String,
+        object_key: String,
+        path_to_store: String,
+        offset: i64,
+        length: Option<u64>,
+        version_id: Option<String>,
+    ) -> PyResult<Bound<'py, PyAny>> {
+        let range = ByteRange::new(offset, length)?;
+        let state = &self.state();
+        let s3_config = Arc::clone(&state.s3_config);
+        let settings = state.settings.clone();
 
-        while let Some(bytes) = body.try_next().await.map_err(|e| {
-            format!(
-                "Failed to read S3 response body for '{}': {}",
-                object_key, e
+        future_into_py(py, &state.runtime, async move {
+            Self::download_slice(
+                &s3_config,
+                &bucket_name,
+                &object_key,
+                version_id.as_deref(),
+                &GetConditions::default(),
+                range,
+                &path_to_store,
+                &ProgressState::default(),
+                &settings,
             )
-        })? {
-            writer
-                .write_all(&bytes)
-                .await
-                .map_err(|e| format!("Failed to write to file '{}': {}", local_path, e))?;
+            .await
+            .map_err(PyErr::from)
+        })
+    }
+
+    #[pyo3(signature=(bucket_name, object_key, ranges, path_to_store=None, version_id=None))]
+    fn download_ranges_async<'py>(
+        &self,
+        py: Python<'py>,
+        bucket_name: String,
+        object_key: String,
+        ranges: Vec<ByteRange>,
+        path_to_store: Option<String>,
+        version_id: Option<String>,
+    ) -> PyResult<Bound<'py, PyAny>> {
+        if ranges.is_empty() {
+            return Err(PyValueError::new_err("ranges must not be empty"));
         }
+        let state = &self.state();
+        let s3_config = Arc::clone(&state.s3_config);
+        let settings = state.settings.clone();
 
-        writer
-            .flush()
+        future_into_py(py, &state.runtime, async move {
+            Self::download_ranges_to(
+                &s3_config,
+                &bucket_name,
+                &object_key,
+                version_id.as_deref(),
+                &ranges,
+                path_to_store.as_deref(),
+                &settings,
+            )
             .await
-            .map_err(|e| format!("Failed to flush file '{}': {}", local_path, e))?;
+            .map_err(PyErr::from)
+        })
+    }
 
-        Ok(local_path.to_string())
+    #[pyo3(signature = (bucket_name, object_key, dest_dir, format=ArchiveFormat::TarGz))]
+    fn download_and_extract_async<'py>(
+        &self,
+        py: Python<'py>,
+        bucket_name: String,
+        object_key: String,
+        dest_dir: String,
+        format: ArchiveFormat,
+    ) -> PyResult<Bound<'py, PyAny>> {
+        let state = &self.state();
+        let s3_config = Arc::clone(&state.s3_config);
+        let settings = state.settings.clone();
+
+        future_into_py(py, &state.runtime, async move {
+            Self::download_extracted(
+                &s3_config,
+                &bucket_name,
+                &object_key,
+                format,
+                &dest_dir,
+                &settings,
+            )
+            .await
+            .map(Self::extracted_results)
+            .map_err(PyErr::from)
+        })
     }
 
-    async fn download_files_concurrent(
-        s3_config: Arc<S3Config>,
-        bucket_name: &str,
-        object_keys: Vec<String>,
-        base_directory: &str,
-        max_concurrent: usize,
-    ) -> Result<RustOperationResult, String> {
-        tokio::fs::create_dir_all(base_directory)
+    #[pyo3(signature = (bucket_name, manifest_key, dest_dir))]
+    fn download_archived_async<'py>(
+        &self,
+        py: Python<'py>,
+        bucket_name: String,
+        manifest_key: String,
+        dest_dir: String,
+    ) -> PyResult<Bound<'py, PyAny>> {
+        let state = &self.state();
+        let s3_config = Arc::clone(&state.s3_config);
+        let settings = state.settings.clone();
+
+        future_into_py(py, &state.runtime, async move {
+            Self::download_archived_bundles(
+                &s3_config,
+                &bucket_name,
+                &manifest_key,
+                &dest_dir,
+                &settings,
+            )
             .await
-            .map_err(|e| format!("Failed to create directory '{}': {}", base_directory, e))?;
+            .map(Self::extracted_results)
+            .map_err(PyErr::from)
+        })
+    }
 
-        let results: Vec<_> = stream::iter(object_keys.into_iter().map(|object_key| {
-            let s3_config = Arc::clone(&s3_config);
+    #[pyo3(signature = (bucket_name, object_keys, base_directory, progress=None, overwrite=OverwritePolicy::Always, preserve_structure=false, fail_fast=false, max_failures=None, raise_on_failure=false, checksum_manifest=None, expected_sha256=None, check_space=false, scheduling=Scheduling::Fifo, dry_run=false))]
+    #[allow(clippy::too_many_arguments)]
+    fn download_multiple_files_async<'py>(
+        &self,
+        py: Python<'py>,
+        bucket_name: String,
+        object_keys: BatchInput<String>,
+        base_directory: String,
+        progress: Option<TransferProgress>,
+        overwrite: OverwritePolicy,
+        preserve_structure: bool,
+        fail_fast: bool,
+        max_failures: Option<usize>,
+        raise_on_failure: bool,
+        checksum_manifest: Option<String>,
+        expected_sha256: Option<HashMap<String, String>>,
+        check_space: bool,
+        scheduling: Scheduling,
+        dry_run: bool,
+    ) -> PyResult<Bound<'py, PyAny>> {
+        let state = &self.state();
+        let s3_config = Arc::clone(&state.s3_config);
+        let sha256 = Sha256Sums::requested(checksum_manifest.is_some(), expected_sha256)?;
+        let settings = state
+            .settings
+            .clone()
+            .stopping_after(failure_limit(fail_fast, max_failures)?)
+            .hashing_into(sha256.clone());
+        let progress = progress.unwrap_or_default().state;
 
-            async move {
-                let file_name = Path::new(&object_key)
-                    .file_name()
-                    .and_then(|name| name.to_str())
-                    .unwrap_or(&object_key);
+        future_into_py(py, &state.runtime, async move {
+            if dry_run {
+                let base_directory = Path::new(&base_directory);
+                let downloads = object_keys.into_stream().map_ok(|object_key| {
+                    Self::key_download(
+                        Some(&bucket_name),
+                        base_directory,
+                        preserve_structure,
+                        object_key,
+                    )
+                });
+                return Self::plan_downloads(
+                    &s3_config,
+                    Some(&bucket_name),
+                    downloads,
+                    overwrite,
+                    &settings,
+                )
+                .await
+                .map(Planned::Plan);
+            }
+            let (object_keys, total) = Self::batch_keys(
+                &s3_config,
+                Some(&bucket_name),
+                object_keys,
+                &base_directory,
+                preserve_structure,
+                &settings,
+                check_space,
+                scheduling,
+            )
+            .await?;
+            Self::download_files_concurrent(
+                s3_config,
+                Some(&bucket_name),
+                object_keys,
+                total,
+                &base_directory,
+                preserve_structure,
+                settings,
+                overwrite,
+                progress,
+                Arc::default(),
+            )
+            .await
+            .map(|result| Self::into_results(result, Some(&bucket_name)))
+            .and_then(|results| {
+                with_manifest(results, sha256.as_deref(), checksum_manifest.as_deref())
+            })
+            .and_then(|results| checked(raise_on_failure, results))
+            .map(Planned::Done)
+        })
+    }
 
-                let local_path = Path::new(&base_directory).join(file_name);
-                let local_path_str = local_path.to_string_lossy().to_string();
+    #[pyo3(signature = (bucket_name, downloads, progress=None, overwrite=OverwritePolicy::Always, if_none_match=None, if_modified_since=None, fail_fast=false, max_failures=None, raise_on_failure=false, checksum_manifest=None, expected_sha256=None, dry_run=false))]
+    #[allow(clippy::too_many_arguments)]
+    fn download_multiple_files_with_paths_async<'py>(
+        &self,
+        py: Python<'py>,
+        bucket_name: String,
+        downloads: BatchInput<DownloadItem>,
+        progress: Option<TransferProgress>,
+        overwrite: OverwritePolicy,
+        if_none_match: Option<HashMap<String, String>>,
+        if_modified_since: Option<Timestamp>,
+        fail_fast: bool,
+        max_failures: Option<usize>,
+        raise_on_failure: bool,
+        checksum_manifest: Option<String>,
+        expected_sha256: Option<HashMap<String, String>>,
+        dry_run: bool,
+    ) -> PyResult<Bound<'py, PyAny>> {
+        let state = &self.state();
+        let s3_config = Arc::clone(&state.s3_config);
+        let sha256 = Sha256Sums::requested(checksum_manifest.is_some(), expected_sha256)?;
+        let settings = state
+            .settings
+            .clone()
+            .stopping_after(failure_limit(fail_fast, max_failures)?)
+            .hashing_into(sha256.clone());
+        let progress = progress.unwrap_or_default().state;
 
-                match Self::download_single_file(
-                    s3_config,
-                    bucket_name,
-                    &object_key,
-                    &local_path_str,
+        future_into_py(py, &state.runtime, async move {
+            let total = downloads.len();
+            let downloads = conditional_downloads(downloads, if_none_match, if_modified_since);
+            if dry_run {
+                return Self::plan_downloads(
+                    &s3_config,
+                    Some(&bucket_name),
+                    downloads,
+                    overwrite,
+                    &settings,
                 )
                 .await
-                {
-                    Ok(path) => (Some(path), None),
-                    Err(error) => (None, Some((object_key, error))),
-                }
+                .map(Planned::Plan);
             }
-        }))
-        .buffer_unordered(max_concurrent)
-        .collect()
-        .await;
+            Self::download_files_concurrent_with_paths(
+                s3_config,
+                Some(bucket_name.clone()),
+                downloads,
+                total,
+                settings,
+                overwrite,
+                progress,
+                Arc::default(),
+            )
+            .await
+            .map(|result| Self::into_results(result, Some(&bucket_name)))
+            .and_then(|results| {
+                with_manifest(results, sha256.as_deref(), checksum_manifest.as_deref())
+            })
+            .and_then(|results| checked(raise_on_failure, results))
+            .map(Planned::Done)
+        })
+    }
 
-        let mut successful = Vec::new();
-        let mut failed = Vec::new();
+    #[pyo3(signature = (items, progress=None, overwrite=OverwritePolicy::Always, fail_fast=false, max_failures=None, raise_on_failure=false, dry_run=false))]
+    #[allow(clippy::too_many_arguments)]
+    fn download_multiple_objects_async<'py>(
+        &self,
+        py: Python<'py>,
+        items: BatchInput<(String, String, String)>,
+        progress: Option<TransferProgress>,
+        overwrite: OverwritePolicy,
+        fail_fast: bool,
+        max_failures: Option<usize>,
+        raise_on_failure: bool,
+        dry_run: bool,
+    ) -> PyResult<Bound<'py, PyAny>> {
+        let state = &self.state();
+        let s3_config = Arc::clone(&state.s3_config);
+        let settings = state
+            .settings
+            .clone()
+            .stopping_after(failure_limit(fail_fast, max_failures)?);
+        let progress = progress.unwrap_or_default().state;
 
-        for (success, error) in results {
-            match (success, error) {
-                (Some(path), None) => successful.push(path),
-                (None, Some((key, error))) => failed.push((key, error)),
-                _ => unreachable!(),
+        future_into_py(py, &state.runtime, async move {
+            let total = items.len();
+            let downloads = items.into_stream().map_ok(object_download);
+            if dry_run {
+                return Self::plan_downloads(&s3_config, None, downloads, overwrite, &settings)
+                    .await
+                    .map(Planned::Plan);
             }
-        }
-        Ok(RustOperationResult { successful, failed })
+            Self::download_files_concurrent_with_paths(
+                s3_config,
+                None,
+                downloads,
+                total,
+                settings,
+                overwrite,
+                progress,
+                Arc::default(),
+            )
+            .await
+            .map(|result| Self::into_results(result, None))
+            .and_then(|results| checked(raise_on_failure, results))
+            .map(Planned::Done)
+        })
     }
 
-    async fn download_files_concurrent_with_paths(
-        s3_config: Arc<S3Config>,
-        bucket_name: String,
-        downloads: Vec<(String, String)>,
-        max_concurrent: usize,
-    ) -> Result<RustOperationResult, String> {
-        let results: Vec<_> =
-            stream::iter(downloads.into_iter().map(|(object_key, local_path)| {
-                let s3_config = Arc::clone(&s3_config);
-                let bucket_name = bucket_name.clone();
+    #[pyo3(signature = (urls_and_paths, progress=None, fail_fast=false, max_failures=None, raise_on_failure=false))]
+    #[allow(clippy::too_many_arguments)]
+    fn download_presigned_urls_async<'py>(
+        &self,
+        py: Python<'py>,
+        urls_and_paths: BatchInput<(String, String)>,
+        progress: Option<TransferProgress>,
+        fail_fast: bool,
+        max_failures: Option<usize>,
+        raise_on_failure: bool,
+    ) -> PyResult<Bound<'py, PyAny>> {
+        let state = &self.state();
+        let settings = state
+            .settings
+            .clone()
+            .stopping_after(failure_limit(fail_fast, max_failures)?);
+        let progress = progress.unwrap_or_default().state;
 
-                async move {
-                    if let Some(parent) = Path::new(&local_path).parent() {
-                        if let Err(e) = tokio::fs::create_dir_all(parent).await {
-                            return (
-                                None,
-                                Some((
-                                    object_key,
-                                    format!(
-                                        "Failed to create directory '{}': {}",
-                                        parent.display(),
-                                        e
-                                    ),
-                                )),
-                            );
-                        }
-                    }
-                    match Self::download_single_file(
-                        s3_config,
-                        &bucket_name,
-                        &object_key,
-                        &local_path,
-                    )
+        future_into_py(py, &state.runtime, async move {
+            let total = urls_and_paths.len();
+            let result = Self::download_presigned_concurrent(
+                urls_and_paths.into_stream(),
+                total,
+                settings,
+                progress,
+                Arc::default(),
+            )
+            .await?;
+            Self::log_failures(&result);
+            checked(raise_on_failure, Results::from(result))
+        })
+    }
+
+    #[pyo3(signature = (uri, path_to_store))]
+    fn download_uri_async<'py>(
+        &self,
+        py: Python<'py>,
+        uri: S3Uri,
+        path_to_store: String,
+    ) -> PyResult<Bound<'py, PyAny>> {
+        let state = &self.state();
+        let s3_config = Arc::clone(&state.s3_config);
+        let settings = state.settings.clone();
+
+        future_into_py(py, &state.runtime, async move {
+            Self::download_single_file(
+                s3_config,
+                &uri.bucket_name,
+                &uri.object_key,
+                None,
+                &GetConditions::default(),
+                &path_to_store,
+                &ProgressState::default(),
+                &settings,
+            )
+            .await
+            .map(Completed::into_name)
+            .map_err(PyErr::from)
+        })
+    }
+
+    #[pyo3(signature = (uris, base_directory, progress=None, overwrite=OverwritePolicy::Always, preserve_structure=false, fail_fast=false, max_failures=None, raise_on_failure=false, dry_run=false))]
+    #[allow(clippy::too_many_arguments)]
+    fn download_multiple_uris_async<'py>(
+        &self,
+        py: Python<'py>,
+        uris: BatchInput<String>,
+        base_directory: String,
+        progress: Option<TransferProgress>,
+        overwrite: OverwritePolicy,
+        preserve_structure: bool,
+        fail_fast: bool,
+        max_failures: Option<usize>,
+        raise_on_failure: bool,
+        dry_run: bool,
+    ) -> PyResult<Bound<'py, PyAny>> {
+        let state = &self.state();
+        let s3_config = Arc::clone(&state.s3_config);
+        let settings = state
+            .settings
+            .clone()
+            .stopping_after(failure_limit(fail_fast, max_failures)?);
+        let progress = progress.unwrap_or_default().state;
+
+        future_into_py(py, &state.runtime, async move {
+            if dry_run {
+                let base_directory = Path::new(&base_directory);
+                let downloads = uris.into_stream().map_ok(|uri| {
+                    Self::key_download(None, base_directory, preserve_structure, uri)
+                });
+                return Self::plan_downloads(&s3_config, None, downloads, overwrite, &settings)
                     .await
-                    {
-                        Ok(path) => (Some(path), None),
-                        Err(error) => (None, Some((object_key, error))),
-                    }
-                }
-            }))
-            .buffer_unordered(max_concurrent)
-            .collect()
-            .await;
+                    .map(Planned::Plan);
+            }
+            let total = uris.len();
+            Self::download_files_concurrent(
+                s3_config,
+                None,
+                uris.into_stream(),
+                total,
+                &base_directory,
+                preserve_structure,
+                settings,
+                overwrite,
+                progress,
+                Arc::default(),
+            )
+            .await
+            .map(|result| Self::into_results(result, None))
+            .and_then(|results| checked(raise_on_failure, results))
+            .map(Planned::Done)
+        })
+    }
 
-        let mut successful = Vec::new();
-        let mut failed = Vec::new();
+    #[pyo3(signature = (bucket_name, prefix, base_directory, include=None, exclude=None, progress=None, overwrite=OverwritePolicy::Always, fail_fast=false, max_failures=None, raise_on_failure=false, min_size=None, max_size=None, modified_after=None, modified_before=None, key_pattern=None, checksum_manifest=None, expected_sha256=None, dry_run=false, check_space=false, scheduling=Scheduling::Fifo))]
+    #[allow(clippy::too_many_arguments)]
+    fn download_prefix_async<'py>(
+        &self,
+        py: Python<'py>,
+        bucket_name: String,
+        prefix: String,
+        base_directory: String,
+        include: Option<Vec<String>>,
+        exclude: Option<Vec<String>>,
+        progress: Option<TransferProgress>,
+        overwrite: OverwritePolicy,
+        fail_fast: bool,
+        max_failures: Option<usize>,
+        raise_on_failure: bool,
+        min_size: Option<u64>,
+        max_size: Option<u64>,
+        modified_after: Option<Timestamp>,
+        modified_before: Option<Timestamp>,
+        key_pattern: Option<String>,
+        checksum_manifest: Option<String>,
+        expected_sha256: Option<HashMap<String, String>>,
+        dry_run: bool,
+        check_space: bool,
+        scheduling: Scheduling,
+    ) -> PyResult<Bound<'py, PyAny>> {
+        let state = &self.state();
+        let filter = PathFilter::new(include, exclude)?;
+        let objects = ObjectFilter::new(
+            min_size,
+            max_size,
+            modified_after,
+            modified_before,
+            key_pattern,
+        )?;
+        let s3_config = Arc::clone(&state.s3_config);
+        let sha256 = Sha256Sums::requested(checksum_manifest.is_some(), expected_sha256)?;
+        let settings = state
+            .settings
+            .clone()
+            .stopping_after(failure_limit(fail_fast, max_failures)?)
+            .hashing_into(sha256.clone());
+        let progress = progress.unwrap_or_default().state;
 
-        for (success, error) in results {
-            match (success, error) {
-                (Some(path), None) => successful.push(path),
-                (None, Some((key, error))) => failed.push((key, error)),
-                _ => unreachable!(),
+        future_into_py(py, &state.runtime, async move {
+            let mut objects = Self::prefix_objects(
+                &s3_config,
+                &bucket_name,
+                &prefix,
+                &filter,
+                &objects,
+                None,
+                &settings.retry_policy,
+            )
+            .await?;
+            scheduling.order(&mut objects, |(_, object)| object.size);
+            if dry_run {
+                let plan = Self::plan_prefix_downloads(objects, &base_directory, overwrite);
+                return Ok(Planned::Plan(plan.await));
             }
+            if check_space {
+                let sizes = Self::prefix_sizes(&objects, &base_directory);
+                ensure_space(Path::new(&base_directory), sizes).await?;
+            }
+            let downloads = Self::prefix_downloads(objects, &base_directory);
+
+            let total = downloads.len();
+            Self::download_files_concurrent_with_paths(
+                s3_config,
+                Some(bucket_name.clone()),
+                ready_items(downloads),
+                Some(total),
+                settings.below(Path::new(&base_directory)),
+                overwrite,
+                progress,
+                Arc::default(),
+            )
+            .await
+            .map(|result| Self::into_results(result, Some(&bucket_name)))
+            .and_then(|results| {
+                with_manifest(results, sha256.as_deref(), checksum_manifest.as_deref())
+            })
+            .and_then(|results| checked(raise_on_failure, results))
+            .map(Planned::Done)
+        })
+    }
+
+    #[pyo3(signature = (bucket_name, prefix, n, base_directory, include=None, exclude=None, progress=None, overwrite=OverwritePolicy::Always, fail_fast=false, max_failures=None, raise_on_failure=false, dry_run=false, check_space=false, scheduling=Scheduling::Fifo))]
+    #[allow(clippy::too_many_arguments)]
+    fn download_latest_async<'py>(
+        &self,
+        py: Python<'py>,
+        bucket_name: String,
+        prefix: String,
+        n: usize,
+        base_directory: String,
+        include: Option<Vec<String>>,
+        exclude: Option<Vec<String>>,
+        progress: Option<TransferProgress>,
+        overwrite: OverwritePolicy,
+        fail_fast: bool,
+        max_failures: Option<usize>,
+        raise_on_failure: bool,
+        dry_run: bool,
+        check_space: bool,
+        scheduling: Scheduling,
+    ) -> PyResult<Bound<'py, PyAny>> {
+        if n == 0 {
+            return Err(PyValueError::new_err("n must be greater than zero"));
         }
+        let state = &self.state();
+        let filter = PathFilter::new(include, exclude)?;
+        let s3_config = Arc::clone(&state.s3_config);
+        let settings = state
+            .settings
+            .clone()
+            .stopping_after(failure_limit(fail_fast, max_failures)?);
+        let progress = progress.unwrap_or_default().state;
+
+        future_into_py(py, &state.runtime, async move {
+            let mut objects = Self::prefix_objects(
+                &s3_config,
+                &bucket_name,
+                &prefix,
+                &filter,
+                &ObjectFilter::default(),
+                Some(n),
+                &settings.retry_policy,
+            )
+            .await?;
+            scheduling.order(&mut objects, |(_, object)| object.size);
+            if dry_run {
+                let plan = Self::plan_prefix_downloads(objects, &base_directory, overwrite);
+                return Ok(Planned::Plan(plan.await));
+            }
+            if check_space {
+                let sizes = Self::prefix_sizes(&objects, &base_directory);
+                ensure_space(Path::new(&base_directory), sizes).await?;
+            }
+            let downloads = Self::prefix_downloads(objects, &base_directory);
 
-        Ok(RustOperationResult { successful, failed })
+            let total = downloads.len();
+            Self::download_files_concurrent_with_paths(
+                s3_config,
+                Some(bucket_name.clone()),
+                ready_items(downloads),
+                Some(total),
+                settings.below(Path::new(&base_directory)),
+                overwrite,
+                progress,
+                Arc::default(),
+            )
+            .await
+            .map(|result| Self::into_results(result, Some(&bucket_name)))
+            .and_then(|results| checked(raise_on_failure, results))
+            .map(Planned::Done)
+        })
     }
-}
 
-#[pymethods]
-impl S3Downloader {
-    #[new]
-    #[pyo3(signature = (region_name, max_concurrent_downloads=5))]
-    fn new(region_name: &str, max_concurrent_downloads: usize) -> Self {
-        let s3_config = std::thread::spawn({
-            let region_name = region_name.to_string();
-            move || {
-                let rt = tokio::runtime::Runtime::new().unwrap();
-                rt.block_on(S3Config::new(region_name))
+    #[pyo3(signature = (bucket_name, prefix, local_dir, delete=false, include=None, exclude=None, progress=None, fail_fast=false, max_failures=None, raise_on_failure=false, dry_run=false, check_space=false, scheduling=Scheduling::Fifo))]
+    #[allow(clippy::too_many_arguments)]
+    fn sync_down_async<'py>(
+        &self,
+        py: Python<'py>,
+        bucket_name: String,
+        prefix: String,
+        local_dir: String,
+        delete: bool,
+        include: Option<Vec<String>>,
+        exclude: Option<Vec<String>>,
+        progress: Option<TransferProgress>,
+        fail_fast: bool,
+        max_failures: Option<usize>,
+        raise_on_failure: bool,
+        dry_run: bool,
+        check_space: bool,
+        scheduling: Scheduling,
+    ) -> PyResult<Bound<'py, PyAny>> {
+        let state = &self.state();
+        let filter = PathFilter::new(include, exclude)?;
+        let s3_config = Arc::clone(&state.s3_config);
+        let settings = state
+            .settings
+            .clone()
+            .stopping_after(failure_limit(fail_fast, max_failures)?);
+        let progress = progress.unwrap_or_default().state;
+
+        future_into_py(py, &state.runtime, async move {
+            if dry_run {
+                let local_dir = Path::new(&local_dir);
+                let plan = Self::plan_sync_down(
+                    &s3_config,
+                    &bucket_name,
+                    &prefix,
+                    local_dir,
+                    &filter,
+                    delete,
+                    &settings.retry_policy,
+                )
+                .await?;
+                return Ok(Planned::Plan(plan.into_transfer_plan(local_dir)));
             }
+            let (result, changes) = Self::sync_down_concurrent(
+                s3_config,
+                bucket_name.clone(),
+                prefix,
+                PathBuf::from(local_dir),
+                filter,
+                delete,
+                check_space,
+                scheduling,
+                settings,
+                progress,
+                Arc::default(),
+            )
+            .await?;
+
+            let results = checked(
+                raise_on_failure,
+                Self::into_results(result, Some(&bucket_name)),
+            )?;
+            let results = Python::with_gil(|py| Py::new(py, results))?;
+            Ok(Planned::Done(SyncReport::new(results, changes)))
         })
-        .join()
-        .unwrap();
+    }
 
-        Self {
-            s3_config: Arc::new(s3_config),
-            max_concurrent_downloads,
-        }
+    fn verify_against_manifest_async<'py>(
+        &self,
+        py: Python<'py>,
+        manifest_path: String,
+    ) -> PyResult<Bound<'py, PyAny>> {
+        let state = &self.state();
+        let concurrency = state.settings.max_concurrent;
+        future_into_py(py, &state.runtime, async move {
+            verify_manifest(&manifest_path, concurrency)
+                .await
+                .map(Results::from)
+        })
     }
 
-    #[pyo3(signature=(bucket_name, object_key, path_to_store))]
-    fn download_file(
+    #[pyo3(signature = (bucket_name, object_key))]
+    fn download_bytes_async<'py>(
         &self,
-        bucket_name: &str,
-        object_key: &str,
-        path_to_store: &str,
-    ) -> PyResult<String> {
-        let s3_config = Arc::clone(&self.s3_config);
-        let bucket_name = bucket_name.to_string();
-        let object_key = object_key.to_string();
-        let path_to_store = path_to_store.to_string();
+        py: Python<'py>,
+        bucket_name: String,
+        object_key: String,
+    ) -> PyResult<Bound<'py, PyAny>> {
+        let state = &self.state();
+        let s3_config = Arc::clone(&state.s3_config);
+        let settings = state.settings.clone();
+
+        future_into_py(py, &state.runtime, async move {
+            Self::download_bytes_single(
+                &s3_config,
+                &bucket_name,
+                &object_key,
+                &ProgressState::default(),
+                &settings,
+            )
+            .await
+            .map(Cow::<'static, [u8]>::Owned)
+            .map_err(PyErr::from)
+        })
+    }
 
-        let rt = tokio::runtime::Runtime::new()
-            .map_err(|e| PyRuntimeError::new_err(format!("Failed to create runtime: {}", e)))?;
+    #[pyo3(signature = (bucket_name, object_key))]
+    fn head_async<'py>(
+        &self,
+        py: Python<'py>,
+        bucket_name: String,
+        object_key: String,
+    ) -> PyResult<Bound<'py, PyAny>> {
+        let state = &self.state();
+        let s3_config = Arc::clone(&state.s3_config);
+        let retry_policy = state.settings.retry_policy.clone();
 
-        rt.block_on(async move {
-            Self::download_single_file(s3_config, &bucket_name, &object_key, &path_to_store).await
+        future_into_py(py, &state.runtime, async move {
+            head_object(&s3_config, &bucket_name, &object_key, None, &retry_policy)
+                .await
+                .map_err(PyErr::from)
         })
-        .map_err(PyRuntimeError::new_err)
     }
 
-    #[pyo3(signature = (bucket_name, object_keys, base_directory))]
-    fn download_multiple_files(
+    #[pyo3(signature = (bucket_name, object_key))]
+    fn exists_async<'py>(
         &self,
-        bucket_name: &str,
-        object_keys: Vec<String>,
-        base_directory: &str,
-    ) -> PyResult<Results> {
-        let s3_config = Arc::clone(&self.s3_config);
-        let bucket_name = bucket_name.to_string();
-        let base_directory = base_directory.to_string();
-        let max_concurrent = self.max_concurrent_downloads;
+        py: Python<'py>,
+        bucket_name: String,
+        object_key: String,
+    ) -> PyResult<Bound<'py, PyAny>> {
+        let state = &self.state();
+        let s3_config = Arc::clone(&state.s3_config);
+        let retry_policy = state.settings.retry_policy.clone();
+
+        future_into_py(py, &state.runtime, async move {
+            object_exists(&s3_config, &bucket_name, &object_key, &retry_policy)
+                .await
+                .map_err(PyErr::from)
+        })
+    }
 
-        let rt = tokio::runtime::Runtime::new()
-            .map_err(|e| PyRuntimeError::new_err(format!("Failed to create runtime: {}", e)))?;
+    #[pyo3(signature = (bucket_name, object_keys, progress=None, fail_fast=false, max_failures=None))]
+    fn head_multiple_async<'py>(
+        &self,
+        py: Python<'py>,
+        bucket_name: String,
+        object_keys: BatchInput<String>,
+        progress: Option<TransferProgress>,
+        fail_fast: bool,
+        max_failures: Option<usize>,
+    ) -> PyResult<Bound<'py, PyAny>> {
+        let state = &self.state();
+        let s3_config = Arc::clone(&state.s3_config);
+        let settings = state
+            .settings
+            .clone()
+            .stopping_after(failure_limit(fail_fast, max_failures)?);
+        let progress = progress.unwrap_or_default().state;
+        let total = object_keys.len();
 
-        let result = rt.block_on(async move {
-            Self::download_files_concurrent(
+        future_into_py(py, &state.runtime, async move {
+            head_objects(
                 s3_config,
-                &bucket_name,
-                object_keys,
-                &base_directory,
-                max_concurrent,
+                bucket_name,
+                object_keys.into_stream(),
+                total,
+                settings,
+                progress,
             )
             .await
-        });
+        })
+    }
 
-        match result {
-            Ok(download_result) => {
-                let failed: Vec<String> = download_result
-                    .failed
-                    .iter()
-                    .map(|(key, _error)| key.clone())
-                    .collect();
+    #[pyo3(signature = (bucket_name, object_keys, tier=RestoreTier::Standard, days=1, progress=None, fail_fast=false, max_failures=None))]
+    #[allow(clippy::too_many_arguments)]
+    fn restore_objects_async<'py>(
+        &self,
+        py: Python<'py>,
+        bucket_name: String,
+        object_keys: BatchInput<String>,
+        tier: RestoreTier,
+        days: i32,
+        progress: Option<TransferProgress>,
+        fail_fast: bool,
+        max_failures: Option<usize>,
+    ) -> PyResult<Bound<'py, PyAny>> {
+        let state = &self.state();
+        let days = restore_days(days)?;
+        let s3_config = Arc::clone(&state.s3_config);
+        let settings = state
+            .settings
+            .clone()
+            .stopping_after(failure_limit(fail_fast, max_failures)?);
+        let progress = progress.unwrap_or_default().state;
+        let total = object_keys.len();
 
-                Ok(Results::new(download_result.successful, failed))
-            }
-            Err(e) => Err(PyRuntimeError::new_err(e)),
-        }
+        future_into_py(py, &state.runtime, async move {
+            let result = restore_objects(
+                &s3_config,
+                &bucket_name,
+                object_keys.into_stream(),
+                total,
+                tier,
+                days,
+                &settings,
+                &progress,
+            )
+            .await?;
+            Ok(Results::from(result))
+        })
     }
 
-    #[pyo3(signature = (bucket_name, downloads))]
-    fn download_multiple_files_with_paths(
+    #[pyo3(signature = (bucket_name, object_keys, poll_interval=60.0, timeout=None))]
+    fn wait_for_restore_async<'py>(
         &self,
-        bucket_name: &str,
-        downloads: Vec<(String, String)>,
-    ) -> PyResult<Results> {
-        let s3_config = Arc::clone(&self.s3_config);
-        let bucket_name = bucket_name.to_string();
-        let max_concurrent = self.max_concurrent_downloads;
+        py: Python<'py>,
+        bucket_name: String,
+        object_keys: Vec<String>,
+        poll_interval: f64,
+        timeout: Option<f64>,
+    ) -> PyResult<Bound<'py, PyAny>> {
+        let state = &self.state();
+        let (poll_interval, timeout) = poll_durations(poll_interval, timeout)?;
+        let s3_config = Arc::clone(&state.s3_config);
+        let settings = state.settings.clone();
 
-        let rt = tokio::runtime::Runtime::new()
-            .map_err(|e| PyRuntimeError::new_err(format!("Failed to create runtime: {}", e)))?;
+        future_into_py(py, &state.runtime, async move {
+            wait_for_restore(
+                &s3_config,
+                &bucket_name,
+                object_keys,
+                poll_interval,
+                timeout,
+                &settings,
+            )
+            .await
+            .map_err(PyErr::from)
+        })
+    }
 
-        let result = rt.block_on(async move {
-            Self::download_files_concurrent_with_paths(
+    #[pyo3(signature = (bucket_name, object_keys, progress=None, fail_fast=false, max_failures=None))]
+    fn download_multiple_bytes_async<'py>(
+        &self,
+        py: Python<'py>,
+        bucket_name: String,
+        object_keys: BatchInput<String>,
+        progress: Option<TransferProgress>,
+        fail_fast: bool,
+        max_failures: Option<usize>,
+    ) -> PyResult<Bound<'py, PyAny>> {
+        let state = &self.state();
+        let s3_config = Arc::clone(&state.s3_config);
+        let settings = state
+            .settings
+            .clone()
+            .stopping_after(failure_limit(fail_fast, max_failures)?);
+        let progress = progress.unwrap_or_default().state;
+        let total = object_keys.len();
+
+        future_into_py(py, &state.runtime, async move {
+            Self::download_bytes_concurrent(
                 s3_config,
                 bucket_name,
-                downloads,
-                max_concurrent,
+                object_keys.into_stream(),
+                total,
+                settings,
+                progress,
             )
             .await
-        });
-
-        match result {
-            Ok(download_result) => {
-                let failed = download_result
-                    .failed
-                    .iter()
-                    .map(|(key, _error)| key.clone())
-                    .collect();
-                Ok(Results::new(download_result.successful, failed))
-            }
-            Err(e) => Err(PyRuntimeError::new_err(e)),
-        }
+        })
     }
 }
@@ -0,0 +1,279 @@
+use std::borrow::Cow;
+use std::sync::Arc;
+
+use bytes::Bytes;
+use pyo3::exceptions::PyValueError;
+use pyo3::types::PyTuple;
+use pyo3::{pyclass, pymethods, Bound, PyRefMut, PyResult, Python};
+
+use crate::error::TransferError;
+use crate::retry::RetryPolicy;
+use crate::runtime::{block_on_interruptible, ProcessRuntime};
+use crate::s3_config::S3Config;
+
+/// Read-only, seekable file object over an S3 object.
+///
+/// Reads are served from an in-memory window of the object; when a read falls
+/// outside it, the next `buffer_size` bytes (or the whole request, if larger) are
+/// fetched with a ranged GET. Every GET is pinned to the ETag seen when the file
+/// was opened, so an object overwritten mid-read fails loudly instead of mixing
+/// versions.
+#[pyclass(module = "robinzhon")]
+pub struct S3File {
+    runtime: Arc<ProcessRuntime>,
+    s3_config: Arc<S3Config>,
+    retry_policy: RetryPolicy,
+    #[pyo3(get)]
+    bucket_name: String,
+    #[pyo3(get)]
+    key: String,
+    #[pyo3(get)]
+    size: u64,
+    etag: Option<String>,
+    buffer_size: u64,
+    position: u64,
+    buffer: Bytes,
+    buffer_start: u64,
+    #[pyo3(get)]
+    closed: bool,
+}
+
+impl S3File {
+    pub fn open(
+        py: Python<'_>,
+        runtime: Arc<ProcessRuntime>,
+        s3_config: Arc<S3Config>,
+        retry_policy: RetryPolicy,
+        bucket_name: String,
+        key: String,
+        buffer_size: u64,
+    ) -> PyResult<Self> {
+        let head = block_on_interruptible(py, &runtime, {
+            let s3_config = &s3_config;
+            let retry_policy = &retry_policy;
+            let (bucket_name, key) = (&bucket_name, &key);
+            async move {
+                s3_config
+                    .run(bucket_name, retry_policy, || async {
+                        s3_config
+                            .client_for(bucket_name)
+                            .head_object()
+                            .bucket(bucket_name)
+                            .key(key)
+                            .send()
+                            .await
+                            .map_err(|e| {
+                                TransferError::from_sdk(
+                                    format!("Failed to open S3 object '{}'", key),
+                                    &e,
+                                )
+                            })
+                    })
+                    .await
+            }
+        })??;
+
+        Ok(Self {
+            runtime,
+            s3_config,
+            retry_policy,
+            bucket_name,
+            key,
+            size: head.content_length.unwrap_or_default().max(0) as u64,
+            etag: head.e_tag,
+            buffer_size,
+            position: 0,
+            buffer: Bytes::new(),
+            buffer_start: 0,
+            closed: false,
+        })
+    }
+
+    fn check_open(&self) -> PyResult<()> {
+        if self.closed {
+            Err(PyValueError::new_err("I/O operation on closed file."))
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Bytes of the buffered window available at the current position.
+    fn buffered(&self) -> &[u8] {
+        let end = self.buffer_start + self.buffer.len() as u64;
+        if (self.buffer_start..end).contains(&self.position) {
+            &self.buffer[(self.position - self.buffer_start) as usize..]
+        } else {
+            &[]
+        }
+    }
+
+    async fn fetch(
+        s3_config: &S3Config,
+        bucket_name: &str,
+        key: &str,
+        etag: Option<&str>,
+        start: u64,
+        end: u64,
+    ) -> Result<Bytes, TransferError> {
+        let response = s3_config
+            .client_for(bucket_name)
+            .get_object()
+            .bucket(bucket_name)
+            .key(key)
+            .range(format!("bytes={}-{}", start, end))
+            .set_if_match(etag.map(str::to_string))
+            .send()
+            .await
+            .map_err(|e| {
+                TransferError::from_sdk(
+                    format!(
+                        "Failed to read bytes {}-{} of S3 object '{}'",
+                        start, end, key
+                    ),
+                    &e,
+                )
+            })?;
+
+        response
+            .body
+            .collect()
+            .await
+            .map(|data| data.into_bytes())
+            .map_err(|e| {
+                TransferError::retryable(format!(
+                    "Failed to read S3 response body for '{}': {}",
+                    key, e
+                ))
+            })
+    }
+
+    /// Refills the window starting at the current position with at least `wanted`
+    /// bytes, or up to the end of the object.
+    fn fill(&mut self, py: Python<'_>, wanted: u64) -> PyResult<()> {
+        let start = self.position;
+        let end = (start + wanted.max(self.buffer_size)).min(self.size) - 1;
+
+        let data = block_on_interruptible(py, &self.runtime, {
+            let (s3_config, retry_policy) = (&self.s3_config, &self.retry_policy);
+            let (bucket_name, key, etag) = (&self.bucket_name, &self.key, self.etag.as_deref());
+            async move {
+                s3_config
+                    .run(bucket_name, retry_policy, || {
+                        Self::fetch(s3_config, bucket_name, key, etag, start, end)
+                    })
+                    .await
+            }
+        })??;
+
+        self.buffer = data;
+        self.buffer_start = start;
+        Ok(())
+    }
+}
+
+#[pymethods]
+impl S3File {
+    /// Reads up to `size` bytes, or everything left when `size` is negative or None.
+    #[pyo3(signature = (size=-1))]
+    fn read(&mut self, py: Python<'_>, size: Option<i64>) -> PyResult<Cow<'static, [u8]>> {
+        self.check_open()?;
+
+        let remaining = self.size.saturating_sub(self.position);
+        let wanted = match size {
+            Some(size) if size >= 0 => (size as u64).min(remaining),
+            _ => remaining,
+        };
+
+        let mut output = Vec::with_capacity(wanted as usize);
+        while (output.len() as u64) < wanted {
+            let missing = wanted - output.len() as u64;
+            if self.buffered().is_empty() {
+                self.fill(py, missing)?;
+            }
+            let buffered = self.buffered();
+            let take = buffered.len().min(missing as usize);
+            if take == 0 {
+                // The object shrank since it was opened; stop at what's there.
+                break;
+            }
+            output.extend_from_slice(&buffered[..take]);
+            self.position += take as u64;
+        }
+
+        Ok(Cow::Owned(output))
+    }
+
+    /// Moves to `offset`, relative to the start (0), current position (1) or end (2).
+    #[pyo3(signature = (offset, whence=0))]
+    fn seek(&mut self, offset: i64, whence: i32) -> PyResult<u64> {
+        self.check_open()?;
+
+        let base = match whence {
+            0 => 0,
+            1 => self.position as i64,
+            2 => self.size as i64,
+            _ => {
+                return Err(PyValueError::new_err(format!(
+                    "invalid whence ({}, should be 0, 1 or 2)",
+                    whence
+                )))
+            }
+        };
+        let position = base + offset;
+        if position < 0 {
+            return Err(PyValueError::new_err(format!(
+                "negative seek position {}",
+                position
+            )));
+        }
+
+        self.position = position as u64;
+        Ok(self.position)
+    }
+
+    fn tell(&self) -> PyResult<u64> {
+        self.check_open()?;
+        Ok(self.position)
+    }
+
+    fn readable(&self) -> PyResult<bool> {
+        self.check_open()?;
+        Ok(true)
+    }
+
+    fn seekable(&self) -> PyResult<bool> {
+        self.check_open()?;
+        Ok(true)
+    }
+
+    fn writable(&self) -> PyResult<bool> {
+        self.check_open()?;
+        Ok(false)
+    }
+
+    fn close(&mut self) {
+        self.closed = true;
+        self.buffer = Bytes::new();
+    }
+
+    fn __enter__(slf: PyRefMut<'_, Self>) -> PyResult<PyRefMut<'_, Self>> {
+        slf.check_open()?;
+        Ok(slf)
+    }
+
+    #[pyo3(signature = (*_args))]
+    fn __exit__(&mut self, _args: &Bound<'_, PyTuple>) -> bool {
+        self.close();
+        false
+    }
+
+    fn __repr__(&self) -> String {
+        format!(
+            "S3File(bucket_name={:?}, key={:?}, size={}, closed={})",
+            self.bucket_name,
+            self.key,
+            self.size,
+            if self.closed { "True" } else { "False" }
+        )
+    }
+}
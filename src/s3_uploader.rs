@@ -1,169 +1,2638 @@
-use std::sync::Arc;
+use std::collections::HashMap;
+use std::future::Future;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
 
-use crate::results::{Results, RustOperationResult};
+use crate::asyncio::future_into_py;
+use crate::attributes::{file_options, symlink_options, symlink_target, SymlinkPolicy};
+use crate::audit::AuditLog;
+use crate::batch_input::{ready_items, until_error, BatchInput};
+use crate::batch_iterator::BatchIterator;
+use crate::bundles::{self, ArchiveMode, Manifest, Packed};
+use crate::chunk_source::ChunkSource;
+use crate::encryption::{ClientEncryption, EncryptedFile, Envelope, Sealer};
+use crate::error::{error_chain, TransferError};
+use crate::exceptions::RobinzhonError;
+use crate::extract::ArchiveFormat;
+use crate::failures::{checked, failure_limit, partial_failure, until_limit, FailureLimit};
+use crate::filters::{ObjectFilter, PathFilter};
+use crate::integrity::{multipart_stored, put_stored};
+use crate::limiter::{acquire, TransferLimiter};
+use crate::listing::{list_prefix, ObjectInfo};
+use crate::objects::delete_objects;
+use crate::pause::{before_part, gated, PauseGate};
+use crate::plan::{Planned, TransferPlan};
+use crate::progress::{AttemptProgress, ProgressState, TransferProgress};
+use crate::rate_limit::{paced, throttled, TokenBucket};
+use crate::results::{
+    with_partial_results, Completed, FailedTransfer, ItemReport, ItemStats, ResultItem, Results,
+    RustOperationResult, StoredObject, TransferKind, TransferRequest,
+};
+use crate::retry::RetryPolicy;
+use crate::runtime::{block_on_interruptible, build_runtime, ProcessRuntime};
 use crate::s3_config::S3Config;
+use crate::s3_uri::S3Uri;
+use crate::scheduling::Scheduling;
+use crate::session::{SessionJournal, UploadCheckpoint};
+use crate::settings::{
+    ClientState, SharedState, TransferSettings, DEFAULT_PART_CONCURRENCY, DEFAULT_PART_SIZE,
+};
+use crate::sync::{
+    file_md5, needs_upload, plain_md5, walk_local, LocalFile, SyncChanges, SyncReport,
+};
+use crate::transfer_manifest::{read_manifest, ManifestFormat};
+use crate::upload_options::UploadOptions;
+use aws_sdk_s3::operation::upload_part::UploadPartOutput;
+use aws_sdk_s3::types::{ChecksumAlgorithm, CompletedMultipartUpload, CompletedPart};
 use aws_sdk_s3::{operation::put_object::PutObjectOutput, primitives::ByteStream};
-use futures::stream::{self, StreamExt};
-use pyo3::exceptions::PyRuntimeError;
-use pyo3::{pyclass, pymethods, PyResult};
+use aws_smithy_types::body::SdkBody;
+use bytes::Bytes;
+use futures::future;
+use futures::stream::{self, Stream, StreamExt, TryStreamExt};
+use log::warn;
+use pyo3::exceptions::PyValueError;
+use pyo3::types::{PyDict, PyDictMethods};
+use pyo3::{
+    pyclass, pymethods, Bound, FromPyObject, IntoPyObject, Py, PyAny, PyErr, PyRef, PyResult,
+    Python,
+};
+use std::io::SeekFrom;
 use std::path::Path;
+use tokio::io::{AsyncReadExt, AsyncSeekExt};
+use tokio::runtime::Builder;
+use tokio::task::JoinSet;
 
-#[pyclass]
+/// Most parts S3 accepts in a single multipart upload.
+const MAX_PARTS: i32 = 10_000;
+
+/// The `If-None-Match` header of writes that may only create the object.
+fn if_none_match(create_only: bool) -> Option<String> {
+    create_only.then(|| "*".to_string())
+}
+
+/// The entry of an uploaded part in the completion of its multipart upload.
+fn completed_part(
+    part_number: i32,
+    response: UploadPartOutput,
+    checksummed: bool,
+) -> CompletedPart {
+    let part = CompletedPart::builder()
+        .part_number(part_number)
+        .set_e_tag(response.e_tag);
+    // Completing a checksummed upload needs the checksum of every part, which
+    // uploads started without an algorithm must not be given.
+    if !checksummed {
+        return part.build();
+    }
+    part.set_checksum_crc32(response.checksum_crc32)
+        .set_checksum_crc32_c(response.checksum_crc32_c)
+        .set_checksum_crc64_nvme(response.checksum_crc64_nvme)
+        .set_checksum_sha1(response.checksum_sha1)
+        .set_checksum_sha256(response.checksum_sha256)
+        .build()
+}
+
+/// Aborts a multipart upload that failed, so its parts stop being billed. A
+/// failure to abort is only logged; the upload's own error is what matters.
+async fn abort_multipart(
+    s3_config: &S3Config,
+    bucket_name: &str,
+    object_key: &str,
+    upload_id: &str,
+    expected_bucket_owner: Option<&str>,
+) {
+    let aborted = s3_config
+        .client_for(bucket_name)
+        .abort_multipart_upload()
+        .bucket(bucket_name)
+        .key(object_key)
+        .upload_id(upload_id)
+        .set_expected_bucket_owner(expected_bucket_owner.map(str::to_string))
+        .send()
+        .await;
+    if let Err(e) = aborted {
+        warn!(
+            "Failed to abort multipart upload {} of '{}': {}",
+            upload_id,
+            object_key,
+            error_chain(&e)
+        );
+    }
+}
+
+/// Whether S3 still holds the multipart upload `upload_id`, which it drops once
+/// the upload is aborted, completed or expired.
+async fn upload_exists(
+    s3_config: &S3Config,
+    bucket_name: &str,
+    object_key: &str,
+    upload_id: &str,
+    expected_bucket_owner: Option<&str>,
+) -> bool {
+    s3_config
+        .client_for(bucket_name)
+        .list_parts()
+        .bucket(bucket_name)
+        .key(object_key)
+        .upload_id(upload_id)
+        .max_parts(1)
+        .set_expected_bucket_owner(expected_bucket_owner.map(str::to_string))
+        .send()
+        .await
+        .is_ok()
+}
+
+/// Reads `length` bytes of `local_path` from `offset`.
+async fn read_range(local_path: &str, offset: u64, length: u64) -> Result<Bytes, TransferError> {
+    let failed = |e: std::io::Error| {
+        TransferError::new(format!("Failed to read file '{}': {}", local_path, e))
+    };
+    let mut file = tokio::fs::File::open(local_path).await.map_err(failed)?;
+    file.seek(SeekFrom::Start(offset)).await.map_err(failed)?;
+    let mut buffer = vec![0; length as usize];
+    file.read_exact(&mut buffer).await.map_err(failed)?;
+    Ok(Bytes::from(buffer))
+}
+
+/// An item of an upload batch: `(local_path, key)`, or `(local_path, key,
+/// options)` to override the batch's options for this item.
+#[derive(FromPyObject)]
+pub enum UploadItem {
+    WithOptions(String, String, Py<UploadOptions>),
+    Plain(String, String),
+}
+
+/// The requests of upload batch items, all create-only or none, with the batch's
+/// `options` merged into their own.
+fn upload_requests(
+    paths_and_keys: BatchInput<UploadItem>,
+    create_only: bool,
+    options: UploadOptions,
+) -> impl Stream<Item = PyResult<TransferRequest>> + Send {
+    let options = Arc::new(options);
+    paths_and_keys.into_stream().map(move |item| {
+        item.map(|item| {
+            let (item, upload_options) = match item {
+                UploadItem::WithOptions(local_path, key, item_options) => (
+                    (local_path, key),
+                    Arc::new(options.merged(item_options.get())),
+                ),
+                UploadItem::Plain(local_path, key) => ((local_path, key), Arc::clone(&options)),
+            };
+            TransferRequest {
+                create_only,
+                upload_options,
+                ..TransferRequest::from(item)
+            }
+        })
+    })
+}
+
+/// `prefix` as a folder: "backup" maps files to "backup/...".
+fn folder_prefix(prefix: &str) -> String {
+    if prefix.is_empty() || prefix.ends_with('/') {
+        prefix.to_string()
+    } else {
+        format!("{}/", prefix)
+    }
+}
+
+async fn require_directory(local_dir: &Path) -> Result<(), TransferError> {
+    if tokio::fs::metadata(local_dir)
+        .await
+        .is_ok_and(|metadata| metadata.is_dir())
+    {
+        Ok(())
+    } else {
+        Err(TransferError::new(format!(
+            "'{}' is not a directory",
+            local_dir.display()
+        )))
+    }
+}
+
+/// The plan of uploading `requests`: every file that can be read is, and
+/// counts for its size.
+async fn plan_uploads(
+    requests: impl Stream<Item = PyResult<TransferRequest>>,
+) -> PyResult<TransferPlan> {
+    let requests: Vec<TransferRequest> = requests.try_collect().await?;
+    let mut plan = TransferPlan::upload();
+    for request in requests {
+        match tokio::fs::metadata(&request.source).await {
+            Ok(metadata) if metadata.is_file() => {
+                plan.transfer(request.source, request.destination, metadata.len())
+            }
+            Ok(_) => {
+                let error = TransferError::new(format!("'{}' is not a file", request.source));
+                plan.fail(FailedTransfer::new(&request.source.clone(), request, error));
+            }
+            Err(e) => {
+                let error =
+                    TransferError::new(format!("Failed to read file '{}': {}", request.source, e));
+                plan.fail(FailedTransfer::new(&request.source.clone(), request, error));
+            }
+        }
+    }
+    Ok(plan)
+}
+
+/// What `upload_file` returns: the path of the file or, with `details`, the
+/// item of the upload. Only ever built to be handed to Python right away, so the
+/// size of the item doesn't matter.
+#[allow(clippy::large_enum_variant)]
+#[derive(IntoPyObject)]
+enum UploadedFile {
+    Path(String),
+    Item(ResultItem),
+}
+
+impl UploadedFile {
+    fn new(
+        completed: Completed,
+        details: bool,
+        local_path: &str,
+        object_key: &str,
+        stats: ItemStats,
+    ) -> Self {
+        if !details {
+            return Self::Path(completed.into_name());
+        }
+        let report = ItemReport {
+            index: 0,
+            source: local_path.to_string(),
+            destination: object_key.to_string(),
+            stats,
+        };
+        Self::Item(ResultItem::finished(&report, &Ok(completed)))
+    }
+}
+
+/// What `sync_up` has to do, worked out before anything is transferred.
+#[derive(Default)]
+struct SyncUpPlan {
+    /// `(local_path, key, size)` of every file to upload.
+    uploads: Vec<(String, String, u64)>,
+    /// Keys to upload, by reason, and keys already up to date.
+    changes: SyncChanges,
+    /// Keys of objects with no matching local file.
+    extraneous: Vec<String>,
+}
+
+impl SyncUpPlan {
+    /// What running the sync would do, for a dry run.
+    fn into_transfer_plan(self) -> TransferPlan {
+        let mut plan = TransferPlan::upload();
+        for (local_path, object_key, size) in self.uploads {
+            plan.transfer(local_path, object_key, size);
+        }
+        for object_key in self.changes.skipped {
+            plan.skip(object_key);
+        }
+        plan.deleting(self.extraneous)
+    }
+}
+
+/// What an `archive_mode` batch uploads as one object: a file too large to
+/// pack, or a bundle of `(index, request, size)` members.
+enum ArchiveUnit {
+    File(usize, TransferRequest),
+    Bundle(String, Vec<(usize, TransferRequest, u64)>),
+}
+
+/// Safe to call from many threads at once, free-threaded builds included:
+/// every call runs with a snapshot of the client state.
+#[pyclass(frozen, module = "robinzhon")]
 pub struct S3Uploader {
-    s3_config: Arc<S3Config>,
-    max_concurrent_uploads: usize,
+    state: SharedState,
+    /// Options every upload starts from, such as server-side encryption.
+    options: UploadOptions,
+    /// Constructor arguments the instance is built from again by `reinitialize`
+    /// and when it is unpickled.
+    runtime_threads: Option<usize>,
+    client_encryption: Option<ClientEncryption>,
+    max_bandwidth_bytes_per_sec: Option<u64>,
+    max_requests_per_sec: Option<f64>,
+    request_burst: Option<u32>,
+    limiter: Option<TransferLimiter>,
 }
 
 impl S3Uploader {
+    /// A single worker rather than a current-thread runtime: connections pooled by
+    /// blocking calls are driven by this runtime, and must keep being polled when
+    /// the awaitable variants reuse them from the asyncio bridge.
+    fn new_runtime(runtime_threads: Option<usize>) -> PyResult<Arc<ProcessRuntime>> {
+        let mut default = Builder::new_multi_thread();
+        default.worker_threads(1);
+        build_runtime(runtime_threads, default).map(Arc::new)
+    }
+
+    pub fn state(&self) -> Arc<ClientState> {
+        self.state.get()
+    }
+
+    /// Runs the uploader on the S3 client, runtime and rate limits of `shared`,
+    /// keeping its own settings otherwise. `S3Client` serves both directions
+    /// with one of each this way.
+    pub fn share(&self, shared: &ClientState) {
+        let state = self.state();
+        self.state.replace(ClientState {
+            settings: TransferSettings {
+                bandwidth: shared.settings.bandwidth.clone(),
+                requests: shared.settings.requests.clone(),
+                limiter: shared.settings.limiter.clone(),
+                ..state.settings.clone()
+            },
+            s3_config: Arc::clone(&shared.s3_config),
+            runtime: Arc::clone(&shared.runtime),
+        });
+    }
+
+    /// The options of a call, on top of the uploader's.
+    fn upload_options(&self, options: Option<UploadOptions>) -> UploadOptions {
+        match options {
+            Some(options) => self.options.merged(&options),
+            None => self.options.clone(),
+        }
+    }
+
+    /// Uploads `local_path` to `object_key`. With `create_only`, fails with "412
+    /// Precondition Failed" if the key already exists rather than replacing it.
+    /// The outcome is appended to the audit log, if the uploader keeps one.
+    #[allow(clippy::too_many_arguments)]
     async fn upload_single_file(
         s3_config: Arc<S3Config>,
         bucket_name: &str,
         object_key: &str,
         local_path: &str,
-    ) -> Result<String, String> {
-        let body = ByteStream::from_path(Path::new(local_path)).await;
+        create_only: bool,
+        options: &UploadOptions,
+        progress: &ProgressState,
+        settings: &TransferSettings,
+    ) -> Result<Completed, TransferError> {
+        let uploaded = Self::upload_from_file(
+            s3_config,
+            bucket_name,
+            object_key,
+            local_path,
+            create_only,
+            options,
+            progress,
+            settings,
+        )
+        .await;
+        if let Some(audit) = settings.audit.as_deref() {
+            audit
+                .record(
+                    TransferKind::Upload,
+                    bucket_name,
+                    object_key,
+                    None,
+                    local_path,
+                    &uploaded,
+                )
+                .await;
+        }
+        uploaded
+    }
+
+    /// `upload_single_file`, left out of the audit log.
+    #[allow(clippy::too_many_arguments)]
+    async fn upload_from_file(
+        s3_config: Arc<S3Config>,
+        bucket_name: &str,
+        object_key: &str,
+        local_path: &str,
+        create_only: bool,
+        options: &UploadOptions,
+        progress: &ProgressState,
+        settings: &TransferSettings,
+    ) -> Result<Completed, TransferError> {
+        let _slot = acquire(settings.limiter.as_deref()).await;
+        if settings.symlinks != SymlinkPolicy::Follow {
+            if let Some(target) = symlink_target(local_path).await? {
+                return match settings.symlinks {
+                    SymlinkPolicy::Preserve => {
+                        Self::upload_symlink(
+                            &s3_config,
+                            bucket_name,
+                            object_key,
+                            local_path,
+                            target,
+                            create_only,
+                            options,
+                            progress,
+                            settings,
+                        )
+                        .await
+                    }
+                    _ => Ok(Completed::Skipped(local_path.to_string())),
+                };
+            }
+        }
+        let options = &file_options(options, local_path, settings).await?;
+
+        // A session sends files larger than a part in parts, which it
+        // checkpoints so a resumed batch doesn't send them again. Client-side
+        // encryption can't be applied to parts read out of order.
+        if settings.journal.is_some() && settings.client_encryption.is_none() {
+            let file_size = tokio::fs::metadata(local_path)
+                .await
+                .map_err(|e| {
+                    TransferError::new(format!("Failed to read file '{}': {}", local_path, e))
+                })?
+                .len();
+            if file_size > settings.part_size {
+                return Self::upload_file_in_parts(
+                    &s3_config,
+                    bucket_name,
+                    object_key,
+                    local_path,
+                    file_size,
+                    create_only,
+                    options,
+                    progress,
+                    settings,
+                )
+                .await;
+            }
+        }
+
+        s3_config
+            .run(bucket_name, &settings.retry_policy, || {
+                Self::upload_attempt(
+                    &s3_config,
+                    bucket_name,
+                    object_key,
+                    local_path,
+                    create_only,
+                    options,
+                    progress,
+                    settings.client_encryption.as_deref(),
+                    settings.bandwidth.as_ref(),
+                )
+            })
+            .await
+    }
+
+    /// Stores the link at `local_path` as an object holding its `target`, with
+    /// the mode of a link in its metadata, as s3fs does.
+    #[allow(clippy::too_many_arguments)]
+    async fn upload_symlink(
+        s3_config: &S3Config,
+        bucket_name: &str,
+        object_key: &str,
+        local_path: &str,
+        target: Vec<u8>,
+        create_only: bool,
+        options: &UploadOptions,
+        progress: &ProgressState,
+        settings: &TransferSettings,
+    ) -> Result<Completed, TransferError> {
+        let mut options = symlink_options(options);
+        let body = match settings.client_encryption.as_deref() {
+            Some(envelope) => {
+                let sealed = envelope.seal(target, options.metadata.as_ref()).await?;
+                options.metadata = Some(sealed.metadata);
+                Bytes::from(sealed.body)
+            }
+            None => Bytes::from(target),
+        };
+        let response = Self::put_bytes(
+            s3_config,
+            bucket_name,
+            object_key,
+            body,
+            create_only,
+            &options,
+            progress,
+            settings,
+        )
+        .await?;
+        Ok(Completed::uploaded(
+            local_path.to_string(),
+            put_stored(&response),
+        ))
+    }
 
-        let response = s3_config
+    /// Uploads `local_path` in a single PUT. With an `envelope`, the file is
+    /// encrypted as it is sent.
+    #[allow(clippy::too_many_arguments)]
+    async fn upload_attempt(
+        s3_config: &S3Config,
+        bucket_name: &str,
+        object_key: &str,
+        local_path: &str,
+        create_only: bool,
+        options: &UploadOptions,
+        progress: &ProgressState,
+        envelope: Option<&Envelope>,
+        bandwidth: Option<&Arc<TokenBucket>>,
+    ) -> Result<Completed, TransferError> {
+        progress.start_attempt();
+        let (body, metadata) = match envelope {
+            Some(envelope) => {
+                let read_failed = |e: std::io::Error| {
+                    TransferError::new(format!("Failed to read file '{}': {}", local_path, e))
+                };
+                let file = tokio::fs::File::open(local_path)
+                    .await
+                    .map_err(read_failed)?;
+                let length = file.metadata().await.map_err(read_failed)?.len();
+                let (sealer, metadata) = envelope
+                    .sealer(options.metadata.as_ref(), Some(length))
+                    .await?;
+                let body = EncryptedFile::new(file, length, sealer);
+                (
+                    ByteStream::new(SdkBody::from_body_1_x(body)),
+                    Some(metadata),
+                )
+            }
+            None => {
+                let body = ByteStream::from_path(Path::new(local_path))
+                    .await
+                    .map_err(|e| {
+                        TransferError::new(format!(
+                            "Failed to read file '{}': {}",
+                            local_path,
+                            error_chain(&e)
+                        ))
+                    })?;
+                (body, options.metadata.clone())
+            }
+        };
+        let content_length = body.size_hint().1.unwrap_or_default();
+
+        let mut attempt_progress = AttemptProgress::new(progress);
+        attempt_progress.add_total_bytes(content_length);
+
+        let request = s3_config
             .client
             .put_object()
             .bucket(bucket_name)
             .key(object_key)
-            .body(body.unwrap())
+            .body(throttled(body, bandwidth))
+            .set_if_none_match(if_none_match(create_only));
+        let response = options
+            .apply_to_put(request, object_key)
+            .set_metadata(metadata)
             .send()
             .await
-            .map_err(|e| format!("Failed to upload S3 object '{}': '{}'", local_path, e))?;
+            .map_err(|e| {
+                TransferError::from_sdk(format!("Failed to upload S3 object '{}'", local_path), &e)
+            })?;
 
-        let PutObjectOutput { .. } = response;
-        Ok(local_path.to_string())
+        attempt_progress.add_bytes(content_length);
+        attempt_progress.commit();
+        Ok(Completed::uploaded(
+            local_path.to_string(),
+            put_stored(&response),
+        ))
     }
 
-    async fn upload_files_concurrent(
+    /// Uploads `local_path`, of `file_size` bytes, as a multipart upload of
+    /// `part_size` parts read straight from the file, each retried on its own.
+    /// The upload is aborted if a part or its completion fails.
+    #[allow(clippy::too_many_arguments)]
+    async fn upload_file_in_parts(
+        s3_config: &S3Config,
+        bucket_name: &str,
+        object_key: &str,
+        local_path: &str,
+        file_size: u64,
+        create_only: bool,
+        options: &UploadOptions,
+        progress: &ProgressState,
+        settings: &TransferSettings,
+    ) -> Result<Completed, TransferError> {
+        let part_size = settings.part_size;
+        let part_count = file_size.div_ceil(part_size);
+        if part_count > MAX_PARTS as u64 {
+            return Err(TransferError::new(format!(
+                "Upload of '{}' needs more than {} parts; use a larger part_size",
+                local_path, MAX_PARTS
+            )));
+        }
+
+        progress.start_attempt();
+        let retry_policy = &settings.retry_policy;
+        let mut file_progress = AttemptProgress::new(progress);
+        file_progress.add_total_bytes(file_size);
+        let expected_bucket_owner = options.expected_bucket_owner.as_deref();
+
+        // A session continues the upload it checkpointed while S3 still holds it.
+        let journal = settings.journal.as_deref();
+        let mut resumed = journal
+            .and_then(|journal| journal.upload_checkpoint(local_path))
+            .filter(|checkpoint| {
+                checkpoint.file_size == file_size && checkpoint.part_size == part_size
+            });
+        if let Some(checkpoint) = &resumed {
+            let exists = upload_exists(
+                s3_config,
+                bucket_name,
+                object_key,
+                &checkpoint.upload_id,
+                expected_bucket_owner,
+            )
+            .await;
+            if !exists {
+                resumed = None;
+            }
+        }
+        let upload_id = match &resumed {
+            Some(checkpoint) => checkpoint.upload_id.clone(),
+            None => s3_config
+                .run(bucket_name, retry_policy, || async {
+                    let request = s3_config
+                        .client_for(bucket_name)
+                        .create_multipart_upload()
+                        .bucket(bucket_name)
+                        .key(object_key);
+                    options
+                        .apply_to_multipart(request, object_key)
+                        .send()
+                        .await
+                        .map_err(|e| {
+                            TransferError::from_sdk(
+                                format!("Failed to start multipart upload of '{}'", local_path),
+                                &e,
+                            )
+                        })
+                })
+                .await?
+                .upload_id
+                .ok_or_else(|| {
+                    TransferError::new(format!(
+                        "S3 returned no upload id for multipart upload of '{}'",
+                        local_path
+                    ))
+                })?,
+        };
+        let resumed = match resumed {
+            Some(checkpoint) => checkpoint,
+            None => {
+                let checkpoint = UploadCheckpoint {
+                    upload_id: upload_id.clone(),
+                    file_size,
+                    part_size,
+                    parts: Vec::new(),
+                };
+                if let Some(journal) = journal {
+                    journal.start_upload(local_path, checkpoint.clone());
+                }
+                checkpoint
+            }
+        };
+        let remaining: Vec<u64> = (0..part_count)
+            .filter(|&index| !resumed.has_part(index as i32 + 1))
+            .collect();
+        for index in (0..part_count).filter(|&index| resumed.has_part(index as i32 + 1)) {
+            file_progress.add_bytes(part_size.min(file_size - index * part_size));
+        }
+        let uploaded: Vec<CompletedPart> =
+            resumed.parts.into_iter().map(CompletedPart::from).collect();
+
+        let checksum_algorithm = options.checksum_algorithm();
+        let pause = settings.pause.as_deref();
+        let upload_id = upload_id.as_str();
+        let checksum_algorithm = &checksum_algorithm;
+        let parts = stream::iter(remaining.into_iter().map(|index| async move {
+            let offset = index * part_size;
+            let length = part_size.min(file_size - offset);
+            let part_number = index as i32 + 1;
+            before_part(pause).await;
+            let body = read_range(local_path, offset, length).await?;
+            let response = s3_config
+                .run(bucket_name, retry_policy, || async {
+                    s3_config
+                        .client_for(bucket_name)
+                        .upload_part()
+                        .bucket(bucket_name)
+                        .key(object_key)
+                        .upload_id(upload_id)
+                        .part_number(part_number)
+                        .set_expected_bucket_owner(expected_bucket_owner.map(str::to_string))
+                        .set_checksum_algorithm(checksum_algorithm.clone())
+                        .body(throttled(
+                            ByteStream::from(body.clone()),
+                            settings.bandwidth.as_ref(),
+                        ))
+                        .send()
+                        .await
+                        .map_err(|e| {
+                            TransferError::from_sdk(
+                                format!(
+                                    "Failed to upload part {} of '{}'",
+                                    part_number, local_path
+                                ),
+                                &e,
+                            )
+                        })
+                })
+                .await?;
+            let mut part_progress = AttemptProgress::new(progress);
+            part_progress.add_bytes(length);
+            Ok::<_, TransferError>((
+                completed_part(part_number, response, checksum_algorithm.is_some()),
+                part_progress.commit(),
+            ))
+        }))
+        .buffer_unordered(settings.part_concurrency)
+        .try_fold(uploaded, |mut completed, (part, (transferred, total))| {
+            file_progress.absorb(transferred, total);
+            if let Some(journal) = journal {
+                journal.part_uploaded(local_path, &part);
+            }
+            completed.push(part);
+            future::ready(Ok(completed))
+        })
+        .await;
+
+        let completed = match parts {
+            Ok(mut parts) => {
+                parts.sort_by_key(|part| part.part_number);
+                let parts = CompletedMultipartUpload::builder()
+                    .set_parts(Some(parts))
+                    .build();
+                s3_config
+                    .run(bucket_name, retry_policy, || async {
+                        s3_config
+                            .client_for(bucket_name)
+                            .complete_multipart_upload()
+                            .bucket(bucket_name)
+                            .key(object_key)
+                            .upload_id(upload_id)
+                            .multipart_upload(parts.clone())
+                            .set_if_none_match(if_none_match(create_only))
+                            .set_expected_bucket_owner(expected_bucket_owner.map(str::to_string))
+                            .send()
+                            .await
+                            .map_err(|e| {
+                                TransferError::from_sdk(
+                                    format!(
+                                        "Failed to complete multipart upload of '{}'",
+                                        local_path
+                                    ),
+                                    &e,
+                                )
+                            })
+                    })
+                    .await
+            }
+            Err(error) => Err(error),
+        };
+
+        match completed {
+            Ok(response) => {
+                file_progress.commit();
+                Ok(Completed::uploaded(
+                    local_path.to_string(),
+                    multipart_stored(&response),
+                ))
+            }
+            Err(error) => {
+                abort_multipart(
+                    s3_config,
+                    bucket_name,
+                    object_key,
+                    upload_id,
+                    expected_bucket_owner,
+                )
+                .await;
+                Err(error)
+            }
+        }
+    }
+
+    /// Uploads everything `source` produces to `object_key`.
+    ///
+    /// A body that fits in one part is sent with a plain PUT; anything larger goes
+    /// through a multipart upload, which is aborted if a part or the source fails.
+    /// With `create_only`, an existing key fails the PUT or the completion of the
+    /// multipart upload.
+    ///
+    /// With client-side encryption, the parts are encrypted in order as they are
+    /// read, and the last one ends with the tag.
+    async fn upload_stream(
         s3_config: Arc<S3Config>,
         bucket_name: &str,
-        paths_and_keys: Vec<(String, String)>,
-        max_concurrent_uploads: usize,
-    ) -> Result<RustOperationResult, String> {
-        let bucket_name = bucket_name.to_string();
+        object_key: &str,
+        source: Arc<ChunkSource>,
+        create_only: bool,
+        options: &UploadOptions,
+        settings: &TransferSettings,
+    ) -> PyResult<String> {
+        let _slot = acquire(settings.limiter.as_deref()).await;
+        let retry_policy = &settings.retry_policy;
+        let part_size = settings.part_size as usize;
+
+        let envelope = settings.client_encryption.as_deref();
+        let first = match source.next_part(part_size).await? {
+            Some(part) if part.len() == part_size => part,
+            body => {
+                let mut body = body.unwrap_or_default();
+                let mut metadata = options.metadata.clone();
+                if let Some(envelope) = envelope {
+                    let sealed = envelope.seal(body.to_vec(), metadata.as_ref()).await?;
+                    body = Bytes::from(sealed.body);
+                    metadata = Some(sealed.metadata);
+                }
+                s3_config
+                    .run(bucket_name, retry_policy, || async {
+                        let request = s3_config
+                            .client_for(bucket_name)
+                            .put_object()
+                            .bucket(bucket_name)
+                            .key(object_key)
+                            .body(throttled(
+                                ByteStream::from(body.clone()),
+                                settings.bandwidth.as_ref(),
+                            ))
+                            .set_if_none_match(if_none_match(create_only));
+                        options
+                            .apply_to_put(request, object_key)
+                            .set_metadata(metadata.clone())
+                            .send()
+                            .await
+                            .map_err(|e| {
+                                TransferError::from_sdk(
+                                    format!("Failed to upload S3 object '{}'", object_key),
+                                    &e,
+                                )
+                            })
+                    })
+                    .await?;
+                return Ok(object_key.to_string());
+            }
+        };
+
+        // The length of a stream isn't known up front, so it is left out.
+        let (sealer, metadata) = match envelope {
+            Some(envelope) => {
+                let (sealer, metadata) = envelope.sealer(options.metadata.as_ref(), None).await?;
+                (Some(sealer), Some(metadata))
+            }
+            None => (None, options.metadata.clone()),
+        };
+        let upload_id = s3_config
+            .run(bucket_name, retry_policy, || async {
+                let request = s3_config
+                    .client_for(bucket_name)
+                    .create_multipart_upload()
+                    .bucket(bucket_name)
+                    .key(object_key);
+                options
+                    .apply_to_multipart(request, object_key)
+                    .set_metadata(metadata.clone())
+                    .send()
+                    .await
+                    .map_err(|e| {
+                        TransferError::from_sdk(
+                            format!("Failed to start multipart upload of '{}'", object_key),
+                            &e,
+                        )
+                    })
+            })
+            .await?
+            .upload_id
+            .ok_or_else(|| {
+                TransferError::new(format!(
+                    "S3 returned no upload id for multipart upload of '{}'",
+                    object_key
+                ))
+            })?;
+
+        let expected_bucket_owner = options.expected_bucket_owner.as_deref();
+        let parts = Self::upload_parts(
+            &s3_config,
+            bucket_name,
+            object_key,
+            &upload_id,
+            expected_bucket_owner,
+            options.checksum_algorithm(),
+            first,
+            &source,
+            sealer,
+            settings,
+        )
+        .await;
+
+        let completed = match parts {
+            Ok(parts) => {
+                let parts = CompletedMultipartUpload::builder()
+                    .set_parts(Some(parts))
+                    .build();
+                s3_config
+                    .run(bucket_name, retry_policy, || async {
+                        s3_config
+                            .client_for(bucket_name)
+                            .complete_multipart_upload()
+                            .bucket(bucket_name)
+                            .key(object_key)
+                            .upload_id(&upload_id)
+                            .multipart_upload(parts.clone())
+                            .set_if_none_match(if_none_match(create_only))
+                            .set_expected_bucket_owner(expected_bucket_owner.map(str::to_string))
+                            .send()
+                            .await
+                            .map_err(|e| {
+                                TransferError::from_sdk(
+                                    format!(
+                                        "Failed to complete multipart upload of '{}'",
+                                        object_key
+                                    ),
+                                    &e,
+                                )
+                            })
+                    })
+                    .await
+                    .map_err(PyErr::from)
+            }
+            Err(error) => Err(error),
+        };
+
+        if let Err(error) = completed {
+            abort_multipart(
+                &s3_config,
+                bucket_name,
+                object_key,
+                &upload_id,
+                expected_bucket_owner,
+            )
+            .await;
+            return Err(error);
+        }
 
-        let upload_futures = paths_and_keys.iter().map(|(local_path, object_key)| {
-            let bucket_name = bucket_name.clone();
-            let s3_config = Arc::clone(&s3_config);
+        Ok(object_key.to_string())
+    }
+
+    /// Reads parts from `source` and uploads them, keeping `part_concurrency` in
+    /// flight while the next one is read. With a `sealer`, each part is encrypted before it is sent.
+    #[allow(clippy::too_many_arguments)]
+    async fn upload_parts(
+        s3_config: &Arc<S3Config>,
+        bucket_name: &str,
+        object_key: &str,
+        upload_id: &str,
+        expected_bucket_owner: Option<&str>,
+        checksum_algorithm: Option<ChecksumAlgorithm>,
+        first: Bytes,
+        source: &Arc<ChunkSource>,
+        mut sealer: Option<Sealer>,
+        settings: &TransferSettings,
+    ) -> PyResult<Vec<CompletedPart>> {
+        let mut in_flight = JoinSet::new();
+        let mut completed = Vec::new();
+        let mut next = Some(first);
+        let mut part_number = 0;
+
+        while let Some(mut body) = next {
+            // The tag goes at the end of the last part, so the part after one
+            // being encrypted is read first.
+            let following = match sealer.take() {
+                Some(mut part_sealer) => {
+                    let following = source.next_part(settings.part_size as usize).await?;
+                    let mut encrypted = body.to_vec();
+                    part_sealer.update(&mut encrypted);
+                    match following {
+                        Some(_) => sealer = Some(part_sealer),
+                        None => encrypted.extend_from_slice(&part_sealer.finish()),
+                    }
+                    body = Bytes::from(encrypted);
+                    Some(following)
+                }
+                None => None,
+            };
+
+            while in_flight.len() >= settings.part_concurrency {
+                if let Some(part) = in_flight.join_next().await {
+                    completed.push(Self::joined_part(part)?);
+                }
+            }
+
+            before_part(settings.pause.as_deref()).await;
+            part_number += 1;
+            if part_number > MAX_PARTS {
+                return Err(RobinzhonError::new_err(format!(
+                    "Upload of '{}' needs more than {} parts; use a larger part_size",
+                    object_key, MAX_PARTS
+                )));
+            }
+
+            let s3_config = Arc::clone(s3_config);
+            let retry_policy = settings.retry_policy.clone();
+            let bandwidth = settings.bandwidth.clone();
+            let (bucket_name, object_key, upload_id) = (
+                bucket_name.to_string(),
+                object_key.to_string(),
+                upload_id.to_string(),
+            );
+            let expected_bucket_owner = expected_bucket_owner.map(str::to_string);
+            let checksum_algorithm = checksum_algorithm.clone();
+            in_flight.spawn(async move {
+                let response = s3_config
+                    .run(&bucket_name, &retry_policy, || async {
+                        s3_config
+                            .client_for(&bucket_name)
+                            .upload_part()
+                            .bucket(&bucket_name)
+                            .key(&object_key)
+                            .upload_id(&upload_id)
+                            .part_number(part_number)
+                            .set_expected_bucket_owner(expected_bucket_owner.clone())
+                            .set_checksum_algorithm(checksum_algorithm.clone())
+                            .body(throttled(
+                                ByteStream::from(body.clone()),
+                                bandwidth.as_ref(),
+                            ))
+                            .send()
+                            .await
+                            .map_err(|e| {
+                                TransferError::from_sdk(
+                                    format!(
+                                        "Failed to upload part {} of '{}'",
+                                        part_number, object_key
+                                    ),
+                                    &e,
+                                )
+                            })
+                    })
+                    .await?;
+                Ok(completed_part(
+                    part_number,
+                    response,
+                    checksum_algorithm.is_some(),
+                ))
+            });
+
+            next = match following {
+                Some(following) => following,
+                None => source.next_part(settings.part_size as usize).await?,
+            };
+        }
+
+        while let Some(part) = in_flight.join_next().await {
+            completed.push(Self::joined_part(part)?);
+        }
+
+        completed.sort_by_key(|part| part.part_number);
+        Ok(completed)
+    }
+
+    fn joined_part(
+        part: Result<Result<CompletedPart, TransferError>, tokio::task::JoinError>,
+    ) -> PyResult<CompletedPart> {
+        part.map_err(|e| RobinzhonError::new_err(format!("Part upload task failed: {}", e)))?
+            .map_err(PyErr::from)
+    }
+
+    /// Pairs every file of `local_dir` that `filter` selects with its key under
+    /// `prefix`.
+    async fn directory_uploads(
+        local_dir: &Path,
+        prefix: &str,
+        filter: &PathFilter,
+    ) -> Result<Vec<(String, String)>, TransferError> {
+        let prefix = folder_prefix(prefix);
+        require_directory(local_dir).await?;
+        Ok(walk_local(local_dir, filter)
+            .await?
+            .into_iter()
+            .map(|relative_path| {
+                (
+                    local_dir.join(&relative_path).to_string_lossy().to_string(),
+                    format!("{}{}", prefix, relative_path),
+                )
+            })
+            .collect())
+    }
 
-            async move {
-                match Self::upload_single_file(s3_config, &bucket_name, object_key, local_path)
+    /// Works out which files of `local_dir` are missing or outdated under `prefix`,
+    /// and, when `delete` is set, which objects have no local file.
+    #[allow(clippy::too_many_arguments)]
+    async fn plan_sync_up(
+        s3_config: &S3Config,
+        local_dir: &Path,
+        bucket_name: &str,
+        prefix: &str,
+        filter: &PathFilter,
+        delete: bool,
+        checksum: bool,
+        retry_policy: &RetryPolicy,
+    ) -> Result<SyncUpPlan, TransferError> {
+        let prefix = &folder_prefix(prefix);
+        // A missing directory must not read as an empty one, or `delete` would
+        // wipe the prefix.
+        require_directory(local_dir).await?;
+
+        let local_files = walk_local(local_dir, filter).await?;
+        let mut remote: HashMap<String, ObjectInfo> = list_prefix(
+            s3_config,
+            bucket_name,
+            prefix,
+            filter,
+            &ObjectFilter::default(),
+            retry_policy,
+        )
+        .await?
+        .into_iter()
+        .collect();
+
+        let mut plan = SyncUpPlan::default();
+        for relative_path in local_files {
+            let local_path = local_dir.join(&relative_path);
+            let Some(local) = LocalFile::stat(&local_path).await else {
+                // Removed while the directory was being scanned.
+                continue;
+            };
+            let object_key = format!("{}{}", prefix, relative_path);
+            let object = remote.remove(&relative_path);
+
+            let comparable = object.as_ref().is_some_and(|object| {
+                object.size == local.size && object.etag.as_deref().and_then(plain_md5).is_some()
+            });
+            let local_md5 = if checksum && comparable {
+                Some(file_md5(&local_path).await?)
+            } else {
+                None
+            };
+
+            if needs_upload(local, object.as_ref(), local_md5.as_deref()) {
+                match object {
+                    Some(_) => plan.changes.changed.push(object_key.clone()),
+                    None => plan.changes.new.push(object_key.clone()),
+                }
+                plan.uploads.push((
+                    local_path.to_string_lossy().to_string(),
+                    object_key,
+                    local.size,
+                ));
+            } else {
+                plan.changes.skipped.push(object_key);
+            }
+        }
+
+        if delete {
+            plan.extraneous = remote.into_values().map(|object| object.key).collect();
+            plan.extraneous.sort();
+        }
+        Ok(plan)
+    }
+
+    /// Runs a planned sync: uploads, in the order `scheduling` puts them in,
+    /// then deletions.
+    #[allow(clippy::too_many_arguments)]
+    async fn sync_up_concurrent(
+        s3_config: Arc<S3Config>,
+        local_dir: PathBuf,
+        bucket_name: String,
+        prefix: String,
+        filter: PathFilter,
+        delete: bool,
+        checksum: bool,
+        scheduling: Scheduling,
+        options: UploadOptions,
+        settings: TransferSettings,
+        progress: Arc<ProgressState>,
+        collected: Arc<Mutex<RustOperationResult>>,
+    ) -> PyResult<(RustOperationResult, SyncChanges)> {
+        let SyncUpPlan {
+            mut uploads,
+            mut changes,
+            extraneous,
+        } = Self::plan_sync_up(
+            &s3_config,
+            &local_dir,
+            &bucket_name,
+            &prefix,
+            &filter,
+            delete,
+            checksum,
+            &settings.retry_policy,
+        )
+        .await?;
+        scheduling.order(&mut uploads, |(_, _, size)| *size);
+
+        let retry_policy = settings.retry_policy.clone();
+        let total = uploads.len();
+        let upload_options = Arc::new(options);
+        let uploads = uploads
+            .into_iter()
+            .map(|(local_path, object_key, _)| TransferRequest {
+                upload_options: Arc::clone(&upload_options),
+                ..TransferRequest::from((local_path, object_key))
+            })
+            .collect();
+        let result = Self::upload_files_concurrent(
+            Arc::clone(&s3_config),
+            Some(&bucket_name),
+            ready_items(uploads),
+            Some(total),
+            settings,
+            progress,
+            collected,
+        )
+        .await?;
+
+        changes.deleted = delete_objects(
+            &s3_config,
+            &bucket_name,
+            ready_items(extraneous),
+            &retry_policy,
+        )
+        .await?;
+        Ok((result, changes))
+    }
+
+    /// Uploads every request as `paths_and_keys` yields it, from its local path
+    /// source to its key destination. Without a `bucket_name`, the keys are `s3://`
+    /// URIs.
+    ///
+    /// An error from `paths_and_keys` stops the batch: the uploads in flight
+    /// finish, their outcome stays in `collected`, and the error is returned.
+    async fn upload_files_concurrent(
+        s3_config: Arc<S3Config>,
+        bucket_name: Option<&str>,
+        paths_and_keys: impl Stream<Item = PyResult<TransferRequest>> + Send,
+        total: Option<usize>,
+        settings: TransferSettings,
+        progress: Arc<ProgressState>,
+        collected: Arc<Mutex<RustOperationResult>>,
+    ) -> PyResult<RustOperationResult> {
+        let settings = &settings;
+        let bucket_name = bucket_name.map(str::to_string);
+        let batch_started = Instant::now();
+        progress.start(total.unwrap_or_default());
+
+        let mut input_error = None;
+        let requests = settings.requests.as_deref();
+        let failures = FailureLimit::new(settings.failure_limit);
+        let paths_and_keys = gated(
+            until_error(paths_and_keys, &mut input_error),
+            settings.pause.as_deref(),
+        );
+        let paths_and_keys = until_limit(paths_and_keys, &failures);
+        paced(paths_and_keys, requests)
+            .enumerate()
+            .map(|(index, request)| {
+                if total.is_none() {
+                    progress.add_files_total(1);
+                }
+                let bucket_name = bucket_name.clone();
+                let s3_config = Arc::clone(&s3_config);
+                let progress = Arc::clone(&progress);
+
+                async move {
+                    Self::upload_item(
+                        s3_config,
+                        bucket_name.as_deref(),
+                        index,
+                        request,
+                        &progress,
+                        settings,
+                    )
                     .await
+                }
+            })
+            .buffer_unordered(settings.max_concurrent)
+            .for_each(|(report, outcome)| {
+                let mut collected = collected.lock().unwrap();
+                collected.record_transfer(report, outcome);
+                failures.update(collected.failed.len());
+                future::ready(())
+            })
+            .await;
+
+        progress.finish();
+        let mut collected = collected.lock().unwrap();
+        collected.elapsed = batch_started.elapsed();
+        match (failures.error(&collected), input_error) {
+            (Some(error), _) | (None, Some(error)) => Err(error),
+            (None, None) => Ok(collected.take()),
+        }
+    }
+
+    /// Uploads the `index`th request of a batch, counting it in `progress`.
+    async fn upload_item(
+        s3_config: Arc<S3Config>,
+        bucket_name: Option<&str>,
+        index: usize,
+        request: TransferRequest,
+        progress: &Arc<ProgressState>,
+        settings: &TransferSettings,
+    ) -> (ItemReport, Result<Completed, FailedTransfer>) {
+        let started = Instant::now();
+        let item_progress = ProgressState::item_of(progress);
+        let (source, destination) = (request.source.clone(), request.destination.clone());
+        let local_path = &request.source;
+        let outcome = match S3Uri::locate(bucket_name, &request.destination) {
+            Ok(object) => {
+                Self::upload_single_file(
+                    s3_config,
+                    &object.bucket_name,
+                    &object.object_key,
+                    local_path,
+                    request.create_only,
+                    &request.upload_options,
+                    &item_progress,
+                    settings,
+                )
+                .await
+            }
+            Err(error) => Err(error),
+        }
+        .map_err(|error| FailedTransfer::new(&request.source.clone(), request, error));
+        progress.record(&outcome);
+        let report = ItemReport {
+            index,
+            source,
+            destination,
+            stats: ItemStats::measure(started, &item_progress),
+        };
+        (report, outcome)
+    }
+
+    /// Uploads a batch in `archive_mode`: files up to its `max_file_size` are
+    /// packed into bundles, larger ones uploaded as they are, and the manifest of
+    /// the bundles goes last. A packed file only counts as uploaded once the
+    /// manifest naming its bundle is.
+    ///
+    /// Bundles are planned over the whole batch, so `paths_and_keys` is read
+    /// before anything is uploaded. The bundles and the manifest get the options
+    /// of the batch; those of single items only apply to the files uploaded as
+    /// they are.
+    #[allow(clippy::too_many_arguments)]
+    async fn upload_archived(
+        s3_config: Arc<S3Config>,
+        bucket_name: &str,
+        paths_and_keys: BatchInput<UploadItem>,
+        create_only: bool,
+        options: UploadOptions,
+        archive_mode: ArchiveMode,
+        settings: TransferSettings,
+        progress: Arc<ProgressState>,
+        collected: Arc<Mutex<RustOperationResult>>,
+    ) -> PyResult<RustOperationResult> {
+        if settings.client_encryption.is_some() {
+            return Err(PyValueError::new_err(
+                "archive_mode can't be combined with client-side encryption",
+            ));
+        }
+        let batch_started = Instant::now();
+        let requests: Vec<TransferRequest> =
+            upload_requests(paths_and_keys, create_only, options.clone())
+                .try_collect()
+                .await?;
+        progress.start(requests.len());
+
+        let mut units = Vec::new();
+        let mut packable = Vec::new();
+        for (index, request) in requests.into_iter().enumerate() {
+            // Files that can't be read are uploaded as they are, to fail on their own.
+            match tokio::fs::metadata(&request.source).await {
+                Ok(metadata)
+                    if metadata.is_file() && metadata.len() <= archive_mode.max_file_size =>
                 {
-                    Ok(path) => (Some(path), None),
-                    Err(error) => (None, Some((local_path.clone(), error))),
+                    packable.push(((index, request, metadata.len()), metadata.len()));
                 }
+                _ => units.push(ArchiveUnit::File(index, request)),
+            }
+        }
+        units.extend(archive_mode.group(packable).into_iter().enumerate().map(
+            |(number, members)| ArchiveUnit::Bundle(archive_mode.bundle_key(number + 1), members),
+        ));
+
+        let (settings, options, archive_mode) = (&settings, &options, &archive_mode);
+        let progress = &progress;
+        let failures = FailureLimit::new(settings.failure_limit);
+        let mut manifest = Manifest {
+            format: archive_mode.format.name().to_string(),
+            ..Manifest::default()
+        };
+        let mut packed = Vec::new();
+        let units = until_limit(
+            gated(stream::iter(units), settings.pause.as_deref()),
+            &failures,
+        );
+        paced(units, settings.requests.as_deref())
+            .map(|unit| {
+                let s3_config = Arc::clone(&s3_config);
+                async move {
+                    match unit {
+                        ArchiveUnit::File(index, request) => {
+                            let outcome = Self::upload_item(
+                                s3_config,
+                                Some(bucket_name),
+                                index,
+                                request,
+                                progress,
+                                settings,
+                            )
+                            .await;
+                            (None, vec![outcome])
+                        }
+                        ArchiveUnit::Bundle(bundle_key, members) => {
+                            let outcomes = Self::upload_bundle(
+                                &s3_config,
+                                bucket_name,
+                                &bundle_key,
+                                archive_mode.format,
+                                members,
+                                create_only,
+                                options,
+                                progress,
+                                settings,
+                            )
+                            .await;
+                            (Some(bundle_key), outcomes)
+                        }
+                    }
+                }
+            })
+            .buffer_unordered(settings.max_concurrent)
+            .for_each(|(bundle_key, outcomes)| {
+                let mut collected = collected.lock().unwrap();
+                for (report, outcome) in outcomes {
+                    match (&bundle_key, outcome) {
+                        (Some(bundle_key), Ok(completed)) => {
+                            manifest
+                                .members
+                                .insert(report.destination.clone(), bundle_key.clone());
+                            packed.push((report, completed));
+                        }
+                        (_, outcome) => collected.record_transfer(report, outcome),
+                    }
+                }
+                failures.update(collected.failed.len());
+                future::ready(())
+            })
+            .await;
+
+        if !packed.is_empty() {
+            let manifest_key = archive_mode.manifest_key();
+            let body = serde_json::to_vec(&manifest).expect("manifests always serialize");
+            let uploaded = Self::put_bytes(
+                &s3_config,
+                bucket_name,
+                &manifest_key,
+                Bytes::from(body),
+                create_only,
+                options,
+                &ProgressState::default(),
+                settings,
+            )
+            .await;
+            let mut collected = collected.lock().unwrap();
+            for (report, completed) in packed {
+                let outcome = match &uploaded {
+                    Ok(_) => Ok(completed),
+                    Err(error) => {
+                        let request = TransferRequest {
+                            create_only,
+                            upload_options: Arc::new(options.clone()),
+                            ..TransferRequest::from((
+                                report.source.clone(),
+                                report.destination.clone(),
+                            ))
+                        };
+                        Err(FailedTransfer::new(&report.source, request, error.clone()))
+                    }
+                };
+                collected.record_transfer(report, outcome);
             }
+        }
+
+        progress.finish();
+        let mut collected = collected.lock().unwrap();
+        collected.elapsed = batch_started.elapsed();
+        match failures.error(&collected) {
+            Some(error) => Err(error),
+            None => Ok(collected.take()),
+        }
+    }
+
+    /// Packs `members` into the bundle `bundle_key` and uploads it. Every member
+    /// is reported by its local path, with its key as destination; one that
+    /// can't be read fails on its own, while failing to pack or upload the
+    /// bundle fails them all.
+    #[allow(clippy::too_many_arguments)]
+    async fn upload_bundle(
+        s3_config: &S3Config,
+        bucket_name: &str,
+        bundle_key: &str,
+        format: ArchiveFormat,
+        members: Vec<(usize, TransferRequest, u64)>,
+        create_only: bool,
+        options: &UploadOptions,
+        progress: &ProgressState,
+        settings: &TransferSettings,
+    ) -> Vec<(ItemReport, Result<Completed, FailedTransfer>)> {
+        let _slot = acquire(settings.limiter.as_deref()).await;
+        let started = Instant::now();
+        let files: Vec<(String, String)> = members
+            .iter()
+            .map(|(_, request, _)| (request.source.clone(), request.destination.clone()))
+            .collect();
+        let packing_key = bundle_key.to_string();
+        let packed = tokio::task::spawn_blocking(move || {
+            let files: Vec<(&str, &str)> = files
+                .iter()
+                .map(|(local_path, key)| (local_path.as_str(), key.as_str()))
+                .collect();
+            bundles::pack(format, &files, &packing_key)
+        })
+        .await
+        .unwrap_or_else(|e| {
+            Err(TransferError::new(format!(
+                "Failed to pack bundle '{}': {}",
+                bundle_key, e
+            )))
         });
 
-        let results: Vec<_> = stream::iter(upload_futures)
-            .buffer_unordered(max_concurrent_uploads)
+        let attempts = ProgressState::default();
+        let uploaded = match packed {
+            Ok(Packed { body, unreadable }) => {
+                let length = body.len() as u64;
+                let mut attempt_progress = AttemptProgress::new(progress);
+                attempt_progress.add_total_bytes(length);
+                let output = Self::put_bytes(
+                    s3_config,
+                    bucket_name,
+                    bundle_key,
+                    Bytes::from(body),
+                    create_only,
+                    options,
+                    &attempts,
+                    settings,
+                )
+                .await;
+                output.map(|output| {
+                    attempt_progress.add_bytes(length);
+                    attempt_progress.commit();
+                    (output.server_side_encryption, unreadable)
+                })
+            }
+            Err(error) => Err(error),
+        };
+
+        let stats = ItemStats::since(started, 0, attempts.attempts());
+        members
+            .into_iter()
+            .enumerate()
+            .map(|(position, (index, request, size))| {
+                let outcome = match &uploaded {
+                    Ok((encryption, unreadable)) => {
+                        match unreadable.iter().find(|(unread, _)| *unread == position) {
+                            Some((_, error)) => Err(error.clone()),
+                            // The ETag and version of the bundle aren't those of
+                            // its members, which aren't objects of their own.
+                            None => Ok(Completed::uploaded(
+                                request.source.clone(),
+                                StoredObject {
+                                    encryption: encryption
+                                        .as_ref()
+                                        .map(|sse| sse.as_str().to_string()),
+                                    ..StoredObject::default()
+                                },
+                            )),
+                        }
+                    }
+                    Err(error) => Err(error.clone()),
+                };
+                let report = ItemReport {
+                    index,
+                    source: request.source.clone(),
+                    destination: request.destination.clone(),
+                    stats: ItemStats {
+                        bytes: if outcome.is_ok() { size } else { 0 },
+                        ..stats
+                    },
+                };
+                let outcome = outcome
+                    .map_err(|error| FailedTransfer::new(&request.source.clone(), request, error));
+                progress.record(&outcome);
+                (report, outcome)
+            })
             .collect()
-            .await;
+    }
+
+    /// Uploads `body` to `object_key` with a single PUT, counting its attempts
+    /// in `progress`.
+    #[allow(clippy::too_many_arguments)]
+    async fn put_bytes(
+        s3_config: &S3Config,
+        bucket_name: &str,
+        object_key: &str,
+        body: Bytes,
+        create_only: bool,
+        options: &UploadOptions,
+        progress: &ProgressState,
+        settings: &TransferSettings,
+    ) -> Result<PutObjectOutput, TransferError> {
+        let output = s3_config
+            .run(bucket_name, &settings.retry_policy, || async {
+                progress.start_attempt();
+                let request = s3_config
+                    .client_for(bucket_name)
+                    .put_object()
+                    .bucket(bucket_name)
+                    .key(object_key)
+                    .body(throttled(
+                        ByteStream::from(body.clone()),
+                        settings.bandwidth.as_ref(),
+                    ))
+                    .set_if_none_match(if_none_match(create_only));
+                options
+                    .apply_to_put(request, object_key)
+                    .send()
+                    .await
+                    .map_err(|e| {
+                        TransferError::from_sdk(
+                            format!("Failed to upload S3 object '{}'", object_key),
+                            &e,
+                        )
+                    })
+            })
+            .await?;
+        Ok(output)
+    }
+
+    /// Runs a batch in `archive_mode` to completion, blocking, attaching what
+    /// finished before an interruption to the raised error.
+    #[allow(clippy::too_many_arguments)]
+    fn run_archived(
+        &self,
+        py: Python<'_>,
+        bucket_name: &str,
+        paths_and_keys: BatchInput<UploadItem>,
+        create_only: bool,
+        options: UploadOptions,
+        archive_mode: ArchiveMode,
+        progress: Option<TransferProgress>,
+        failure_limit: Option<usize>,
+    ) -> PyResult<Results> {
+        let state = &self.state();
+        let collected = Arc::new(Mutex::new(RustOperationResult::default()));
+
+        block_on_interruptible(
+            py,
+            &state.runtime,
+            Self::upload_archived(
+                Arc::clone(&state.s3_config),
+                bucket_name,
+                paths_and_keys,
+                create_only,
+                options,
+                archive_mode,
+                state.settings.clone().stopping_after(failure_limit),
+                progress.unwrap_or_default().state,
+                Arc::clone(&collected),
+            ),
+        )
+        .and_then(|result| result)
+        .map(|result| Self::into_results(result, Some(bucket_name)))
+        .map_err(|e| with_partial_results(py, e, &collected))
+    }
+
+    /// Runs a batch of uploads to completion, blocking. Whatever finished before an
+    /// interruption or an error from `paths_and_keys` is attached to the raised
+    /// error as `partial_results`.
+    fn run_uploads(
+        &self,
+        py: Python<'_>,
+        bucket_name: Option<&str>,
+        paths_and_keys: impl Stream<Item = PyResult<TransferRequest>> + Send,
+        total: Option<usize>,
+        progress: Option<TransferProgress>,
+        failure_limit: Option<usize>,
+    ) -> PyResult<Results> {
+        self.run_uploads_into(
+            py,
+            RustOperationResult::default(),
+            bucket_name,
+            paths_and_keys,
+            total,
+            progress,
+            failure_limit,
+        )
+    }
+
+    /// Runs the uploads of a `TransferSession`, checkpointing each item to
+    /// `journal` as it finishes.
+    pub fn run_journaled(
+        &self,
+        py: Python<'_>,
+        bucket_name: Option<&str>,
+        requests: Vec<TransferRequest>,
+        progress: Option<TransferProgress>,
+        journal: Arc<SessionJournal>,
+    ) -> PyResult<Results> {
+        let total = requests.len();
+        self.run_uploads_into(
+            py,
+            RustOperationResult::journaled(journal),
+            bucket_name,
+            ready_items(requests),
+            Some(total),
+            progress,
+            None,
+        )
+    }
+
+    /// A batch of uploads with the uploader's options that runs on its own once
+    /// spawned, for a `TransferManager` job, held back whenever `pause` is paused.
+    pub fn detached_batch(
+        &self,
+        bucket_name: String,
+        requests: Vec<TransferRequest>,
+        progress: Arc<ProgressState>,
+        pause: Arc<PauseGate>,
+    ) -> impl Future<Output = PyResult<Results>> + Send + 'static {
+        let state = &self.state();
+        let s3_config = Arc::clone(&state.s3_config);
+        let settings = TransferSettings {
+            pause: Some(pause),
+            ..state.settings.clone()
+        };
+        let options = Arc::new(self.options.clone());
+        let requests: Vec<TransferRequest> = requests
+            .into_iter()
+            .map(|request| TransferRequest {
+                upload_options: Arc::clone(&options),
+                ..request
+            })
+            .collect();
+
+        async move {
+            let total = requests.len();
+            Self::upload_files_concurrent(
+                s3_config,
+                Some(&bucket_name),
+                ready_items(requests),
+                Some(total),
+                settings,
+                progress,
+                Arc::default(),
+            )
+            .await
+            .map(|result| Self::into_results(result, Some(&bucket_name)))
+        }
+    }
 
-        let mut successful = Vec::new();
-        let mut failed = Vec::new();
+    /// Runs a batch of uploads, recording its items into `collected`.
+    #[allow(clippy::too_many_arguments)]
+    fn run_uploads_into(
+        &self,
+        py: Python<'_>,
+        collected: RustOperationResult,
+        bucket_name: Option<&str>,
+        paths_and_keys: impl Stream<Item = PyResult<TransferRequest>> + Send,
+        total: Option<usize>,
+        progress: Option<TransferProgress>,
+        failure_limit: Option<usize>,
+    ) -> PyResult<Results> {
+        let state = &self.state();
+        let settings = state
+            .settings
+            .clone()
+            .stopping_after(failure_limit)
+            .checkpointed_to(collected.journal.clone());
+        let collected = Arc::new(Mutex::new(collected));
+
+        block_on_interruptible(
+            py,
+            &state.runtime,
+            Self::upload_files_concurrent(
+                Arc::clone(&state.s3_config),
+                bucket_name,
+                paths_and_keys,
+                total,
+                settings,
+                progress.unwrap_or_default().state,
+                Arc::clone(&collected),
+            ),
+        )
+        .and_then(|result| result)
+        .map(|result| Self::into_results(result, bucket_name))
+        .map_err(|e| with_partial_results(py, e, &collected))
+    }
 
-        for (success, error) in results {
-            if let Some(path) = success {
-                successful.push(path);
-            } else if let Some((key, error)) = error {
-                failed.push((key, error));
+    fn into_results(upload_result: RustOperationResult, bucket_name: Option<&str>) -> Results {
+        if !upload_result.failed.is_empty() {
+            warn!("{} uploads failed", upload_result.failed.len());
+            for failure in &upload_result.failed {
+                warn!("  {}: {}", failure.name, failure.error);
             }
         }
 
-        Ok(RustOperationResult { successful, failed })
+        Results::from(upload_result).with_origin(TransferKind::Upload, bucket_name)
     }
 }
 
 #[pymethods]
 impl S3Uploader {
     #[new]
-    #[pyo3(signature = (region_name, max_concurrent_uploads=5))]
-    fn new(region_name: &str, max_concurrent_uploads: usize) -> Self {
-        let s3_config = std::thread::spawn({
-            let region_name = region_name.to_string();
-            move || {
-                let rt = tokio::runtime::Runtime::new().unwrap();
-                rt.block_on(S3Config::new(region_name))
-            }
+    #[pyo3(signature = (region_name=None, max_concurrent_uploads=5, endpoint_url=None, force_path_style=false, config=None, runtime_threads=None, retry_policy=None, part_size=DEFAULT_PART_SIZE, part_concurrency=DEFAULT_PART_CONCURRENCY, sse=None, kms_key_id=None, client_encryption=None, max_bandwidth_bytes_per_sec=None, max_requests_per_sec=None, request_burst=None, limiter=None, preserve_mtime=false, preserve_permissions=false, symlinks=SymlinkPolicy::Follow, audit_log=None))]
+    #[allow(clippy::too_many_arguments)]
+    fn new(
+        region_name: Option<&str>,
+        max_concurrent_uploads: usize,
+        endpoint_url: Option<String>,
+        force_path_style: bool,
+        config: Option<PyRef<'_, S3Config>>,
+        runtime_threads: Option<usize>,
+        retry_policy: Option<RetryPolicy>,
+        part_size: u64,
+        part_concurrency: usize,
+        sse: Option<String>,
+        kms_key_id: Option<String>,
+        client_encryption: Option<ClientEncryption>,
+        max_bandwidth_bytes_per_sec: Option<u64>,
+        max_requests_per_sec: Option<f64>,
+        request_burst: Option<u32>,
+        limiter: Option<TransferLimiter>,
+        preserve_mtime: bool,
+        preserve_permissions: bool,
+        symlinks: SymlinkPolicy,
+        audit_log: Option<String>,
+    ) -> PyResult<Self> {
+        if part_size == 0 {
+            return Err(PyValueError::new_err("part_size must be greater than zero"));
+        }
+        if part_concurrency == 0 {
+            return Err(PyValueError::new_err(
+                "part_concurrency must be greater than zero",
+            ));
+        }
+
+        let s3_config = S3Config::from_args(
+            config.as_deref(),
+            region_name,
+            endpoint_url,
+            force_path_style,
+        )?;
+
+        let runtime = Self::new_runtime(runtime_threads)?;
+        let envelope = client_encryption
+            .as_ref()
+            .map(|encryption| encryption.envelope(&s3_config, true).map(Arc::new))
+            .transpose()?;
+        let bandwidth = TokenBucket::bandwidth(max_bandwidth_bytes_per_sec)?.map(Arc::new);
+        let requests = TokenBucket::requests(max_requests_per_sec, request_burst)?.map(Arc::new);
+        let audit = audit_log.as_deref().map(AuditLog::open).transpose()?;
+
+        Ok(Self {
+            state: SharedState::new(ClientState {
+                settings: TransferSettings {
+                    part_size,
+                    part_concurrency,
+                    client_encryption: envelope,
+                    bandwidth,
+                    requests,
+                    limiter: limiter
+                        .as_ref()
+                        .map(|limiter| Arc::clone(&limiter.semaphore)),
+                    preserve_mtime,
+                    preserve_permissions,
+                    symlinks,
+                    audit,
+                    ..TransferSettings::new(
+                        max_concurrent_uploads,
+                        retry_policy.unwrap_or_default(),
+                    )
+                },
+                s3_config,
+                runtime,
+            }),
+            options: UploadOptions::encrypted(sse, kms_key_id)?,
+            runtime_threads,
+            client_encryption,
+            max_bandwidth_bytes_per_sec,
+            max_requests_per_sec,
+            request_burst,
+            limiter,
         })
-        .join()
-        .unwrap();
+    }
 
-        Self {
-            s3_config: Arc::new(s3_config),
-            max_concurrent_uploads,
-        }
+    /// Pickles the uploader as its constructor arguments, its `S3Config`
+    /// included; unpickling builds a new runtime and client.
+    fn __getnewargs_ex__<'py>(&self, py: Python<'py>) -> PyResult<((), Bound<'py, PyDict>)> {
+        let state = self.state();
+        let settings = &state.settings;
+        let kwargs = PyDict::new(py);
+        kwargs.set_item("config", (*state.s3_config).clone())?;
+        kwargs.set_item("max_concurrent_uploads", settings.max_concurrent)?;
+        kwargs.set_item("runtime_threads", self.runtime_threads)?;
+        kwargs.set_item("retry_policy", settings.retry_policy.clone())?;
+        kwargs.set_item("part_size", settings.part_size)?;
+        kwargs.set_item("part_concurrency", settings.part_concurrency)?;
+        kwargs.set_item("sse", &self.options.sse)?;
+        kwargs.set_item("kms_key_id", &self.options.kms_key_id)?;
+        kwargs.set_item("client_encryption", self.client_encryption.clone())?;
+        kwargs.set_item(
+            "max_bandwidth_bytes_per_sec",
+            self.max_bandwidth_bytes_per_sec,
+        )?;
+        kwargs.set_item("max_requests_per_sec", self.max_requests_per_sec)?;
+        kwargs.set_item("request_burst", self.request_burst)?;
+        kwargs.set_item("limiter", self.limiter.clone())?;
+        kwargs.set_item("preserve_mtime", settings.preserve_mtime)?;
+        kwargs.set_item("preserve_permissions", settings.preserve_permissions)?;
+        kwargs.set_item("symlinks", settings.symlinks.as_str())?;
+        kwargs.set_item(
+            "audit_log",
+            settings
+                .audit
+                .as_deref()
+                .map(|audit| audit.path().to_string_lossy().into_owned()),
+        )?;
+        Ok(((), kwargs))
     }
 
-    #[pyo3(signature=(bucket_name, object_key, local_path))]
+    /// Rebuilds the async runtime and the S3 client, for a process forked after
+    /// the uploader was created, whose threads and connections it can't use.
+    /// Fork while no transfers are running: their limiter slots stay taken in
+    /// the child.
+    fn reinitialize(&self) -> PyResult<()> {
+        let state = self.state();
+        let s3_config = Arc::new(state.s3_config.rebuilt());
+        let envelope = self
+            .client_encryption
+            .as_ref()
+            .map(|encryption| encryption.envelope(&s3_config, true).map(Arc::new))
+            .transpose()?;
+        self.state.replace(ClientState {
+            settings: TransferSettings {
+                client_encryption: envelope,
+                ..state.settings.clone()
+            },
+            s3_config,
+            runtime: Self::new_runtime(self.runtime_threads)?,
+        });
+        Ok(())
+    }
+
+    #[pyo3(signature=(bucket_name, object_key, local_path, if_none_match=false, options=None, details=false))]
+    #[allow(clippy::too_many_arguments)]
     fn upload_file(
         &self,
+        py: Python<'_>,
         bucket_name: &str,
         object_key: &str,
         local_path: &str,
+        if_none_match: bool,
+        options: Option<UploadOptions>,
+        details: bool,
+    ) -> PyResult<UploadedFile> {
+        let state = &self.state();
+        let s3_config = Arc::clone(&state.s3_config);
+        let settings = state.settings.clone();
+        let options = self.upload_options(options);
+        let started = Instant::now();
+        let progress = ProgressState::default();
+
+        let result = block_on_interruptible(py, &state.runtime, async {
+            Self::upload_single_file(
+                s3_config,
+                bucket_name,
+                object_key,
+                local_path,
+                if_none_match,
+                &options,
+                &progress,
+                &settings,
+            )
+            .await
+        })?;
+
+        let stats = ItemStats::measure(started, &progress);
+        result
+            .map(|completed| UploadedFile::new(completed, details, local_path, object_key, stats))
+            .map_err(PyErr::from)
+    }
+
+    /// Uploads the contents of a binary file object, or of an iterable of bytes
+    /// chunks, without knowing its size up front.
+    #[pyo3(signature = (bucket_name, object_key, fileobj, if_none_match=false, options=None))]
+    fn upload_fileobj(
+        &self,
+        py: Python<'_>,
+        bucket_name: &str,
+        object_key: &str,
+        fileobj: &Bound<'_, PyAny>,
+        if_none_match: bool,
+        options: Option<UploadOptions>,
     ) -> PyResult<String> {
-        let s3_config = Arc::clone(&self.s3_config);
+        let state = &self.state();
+        let source = ChunkSource::new(fileobj)?;
 
-        let rt = tokio::runtime::Builder::new_current_thread()
-            .enable_all()
-            .build()
-            .map_err(|e| {
-                PyRuntimeError::new_err(format!("Failed to create async runtime: {}", e))
-            })?;
+        block_on_interruptible(
+            py,
+            &state.runtime,
+            Self::upload_stream(
+                Arc::clone(&state.s3_config),
+                bucket_name,
+                object_key,
+                source,
+                if_none_match,
+                &self.upload_options(options),
+                &state.settings,
+            ),
+        )?
+    }
+
+    /// With `if_none_match`, keys that already exist are left as they are and
+    /// their uploads fail with "412 Precondition Failed". `options` apply to every
+    /// item; an item given as `(local_path, key, options)` overrides them. With
+    /// `archive_mode`, small files are packed into bundles. With `dry_run`, the
+    /// files are only looked at and the plan of their uploads returned.
+    #[pyo3(signature = (bucket_name, paths_and_keys, progress=None, if_none_match=false, options=None, fail_fast=false, max_failures=None, raise_on_failure=false, archive_mode=None, dry_run=false))]
+    #[allow(clippy::too_many_arguments)]
+    pub fn upload_multiple_files(
+        &self,
+        py: Python<'_>,
+        bucket_name: &str,
+        paths_and_keys: BatchInput<UploadItem>,
+        progress: Option<TransferProgress>,
+        if_none_match: bool,
+        options: Option<UploadOptions>,
+        fail_fast: bool,
+        max_failures: Option<usize>,
+        raise_on_failure: bool,
+        archive_mode: Option<ArchiveMode>,
+        dry_run: bool,
+    ) -> PyResult<Planned<Results>> {
+        let limit = failure_limit(fail_fast, max_failures)?;
+        let options = self.upload_options(options);
+        if dry_run {
+            let requests = upload_requests(paths_and_keys, if_none_match, options);
+            return block_on_interruptible(py, &self.state().runtime, plan_uploads(requests))?
+                .map(Planned::Plan);
+        }
+        match archive_mode {
+            Some(archive_mode) => self.run_archived(
+                py,
+                bucket_name,
+                paths_and_keys,
+                if_none_match,
+                options,
+                archive_mode,
+                progress,
+                limit,
+            ),
+            None => {
+                let total = paths_and_keys.len();
+                self.run_uploads(
+                    py,
+                    Some(bucket_name),
+                    upload_requests(paths_and_keys, if_none_match, options),
+                    total,
+                    progress,
+                    limit,
+                )
+            }
+        }
+        .and_then(|results| checked(raise_on_failure, results))
+        .map(Planned::Done)
+    }
+
+    /// `upload_multiple_files` running in the background, iterated item by item
+    /// as they finish.
+    #[pyo3(signature = (bucket_name, paths_and_keys, if_none_match=false, options=None, fail_fast=false, max_failures=None))]
+    fn iter_upload_multiple_files(
+        &self,
+        bucket_name: String,
+        paths_and_keys: BatchInput<UploadItem>,
+        if_none_match: bool,
+        options: Option<UploadOptions>,
+        fail_fast: bool,
+        max_failures: Option<usize>,
+    ) -> PyResult<BatchIterator> {
+        let state = &self.state();
+        let s3_config = Arc::clone(&state.s3_config);
+        let options = self.upload_options(options);
+        let settings = state
+            .settings
+            .clone()
+            .stopping_after(failure_limit(fail_fast, max_failures)?);
+        let total = paths_and_keys.len();
+        let uploads = upload_requests(paths_and_keys, if_none_match, options);
+
+        Ok(BatchIterator::spawn(
+            Arc::clone(&state.runtime),
+            total,
+            move |collected| async move {
+                Self::upload_files_concurrent(
+                    s3_config,
+                    Some(&bucket_name),
+                    uploads,
+                    total,
+                    settings,
+                    Arc::default(),
+                    collected,
+                )
+                .await
+                .map(|result| Self::into_results(result, Some(&bucket_name)))
+            },
+        ))
+    }
+
+    /// Uploads `local_path` to the object `uri` points to.
+    #[pyo3(signature = (uri, local_path, if_none_match=false, options=None, details=false))]
+    fn upload_uri(
+        &self,
+        py: Python<'_>,
+        uri: S3Uri,
+        local_path: &str,
+        if_none_match: bool,
+        options: Option<UploadOptions>,
+        details: bool,
+    ) -> PyResult<UploadedFile> {
+        self.upload_file(
+            py,
+            &uri.bucket_name,
+            &uri.object_key,
+            local_path,
+            if_none_match,
+            options,
+            details,
+        )
+    }
+
+    /// Uploads `(local_path, uri)` pairs, whose URIs may point to different buckets.
+    /// With `dry_run`, only the plan of the uploads is returned.
+    #[pyo3(signature = (paths_and_uris, progress=None, if_none_match=false, options=None, fail_fast=false, max_failures=None, raise_on_failure=false, dry_run=false))]
+    #[allow(clippy::too_many_arguments)]
+    fn upload_multiple_uris(
+        &self,
+        py: Python<'_>,
+        paths_and_uris: BatchInput<UploadItem>,
+        progress: Option<TransferProgress>,
+        if_none_match: bool,
+        options: Option<UploadOptions>,
+        fail_fast: bool,
+        max_failures: Option<usize>,
+        raise_on_failure: bool,
+        dry_run: bool,
+    ) -> PyResult<Planned<Results>> {
+        let total = paths_and_uris.len();
+        let failure_limit = failure_limit(fail_fast, max_failures)?;
+        let paths_and_uris =
+            upload_requests(paths_and_uris, if_none_match, self.upload_options(options));
+        if dry_run {
+            return block_on_interruptible(
+                py,
+                &self.state().runtime,
+                plan_uploads(paths_and_uris),
+            )?
+            .map(Planned::Plan);
+        }
+        self.run_uploads(py, None, paths_and_uris, total, progress, failure_limit)
+            .and_then(|results| checked(raise_on_failure, results))
+            .map(Planned::Done)
+    }
 
-        let result = rt.block_on(async move {
-            Self::upload_single_file(s3_config, bucket_name, object_key, local_path).await
+    /// Uploads the files a CSV or JSON Lines manifest lists to their objects,
+    /// reading the manifest as the batch goes. Rows that name no bucket go to
+    /// `bucket_name`. Items are reported by their local path, as
+    /// `upload_multiple_uris` reports them, `dry_run` included.
+    #[pyo3(signature = (manifest_path, bucket_name=None, format=None, progress=None, if_none_match=false, options=None, fail_fast=false, max_failures=None, raise_on_failure=false, dry_run=false))]
+    #[allow(clippy::too_many_arguments)]
+    fn upload_from_manifest(
+        &self,
+        py: Python<'_>,
+        manifest_path: String,
+        bucket_name: Option<String>,
+        format: Option<ManifestFormat>,
+        progress: Option<TransferProgress>,
+        if_none_match: bool,
+        options: Option<UploadOptions>,
+        fail_fast: bool,
+        max_failures: Option<usize>,
+        raise_on_failure: bool,
+        dry_run: bool,
+    ) -> PyResult<Planned<Results>> {
+        let format = ManifestFormat::of(&manifest_path, format)?;
+        let failure_limit = failure_limit(fail_fast, max_failures)?;
+        let upload_options = Arc::new(self.upload_options(options));
+        let uploads = read_manifest(manifest_path, format, bucket_name).map_ok(move |entry| {
+            TransferRequest {
+                create_only: if_none_match,
+                upload_options: Arc::clone(&upload_options),
+                ..TransferRequest::from((entry.local_path, entry.object.to_string()))
+            }
         });
+        if dry_run {
+            return block_on_interruptible(py, &self.state().runtime, plan_uploads(uploads))?
+                .map(Planned::Plan);
+        }
+        self.run_uploads(py, None, uploads, None, progress, failure_limit)
+            .and_then(|results| checked(raise_on_failure, results))
+            .map(Planned::Done)
+    }
 
-        result.map_err(PyRuntimeError::new_err)
+    /// Brings the objects under `prefix` up to date with `local_dir`, uploading only
+    /// new or changed files. With `dry_run`, only the plan of the sync is
+    /// returned.
+    #[pyo3(signature = (local_dir, bucket_name, prefix, delete=false, checksum=false, include=None, exclude=None, progress=None, options=None, fail_fast=false, max_failures=None, raise_on_failure=false, dry_run=false, scheduling=Scheduling::Fifo))]
+    #[allow(clippy::too_many_arguments)]
+    fn sync_up(
+        &self,
+        py: Python<'_>,
+        local_dir: &str,
+        bucket_name: &str,
+        prefix: &str,
+        delete: bool,
+        checksum: bool,
+        include: Option<Vec<String>>,
+        exclude: Option<Vec<String>>,
+        progress: Option<TransferProgress>,
+        options: Option<UploadOptions>,
+        fail_fast: bool,
+        max_failures: Option<usize>,
+        raise_on_failure: bool,
+        dry_run: bool,
+        scheduling: Scheduling,
+    ) -> PyResult<Planned<SyncReport>> {
+        let state = &self.state();
+        let failure_limit = failure_limit(fail_fast, max_failures)?;
+        let filter = PathFilter::new(include, exclude)?;
+        if dry_run {
+            let plan = block_on_interruptible(
+                py,
+                &state.runtime,
+                Self::plan_sync_up(
+                    &state.s3_config,
+                    Path::new(local_dir),
+                    bucket_name,
+                    prefix,
+                    &filter,
+                    delete,
+                    checksum,
+                    &state.settings.retry_policy,
+                ),
+            )??;
+            return Ok(Planned::Plan(plan.into_transfer_plan()));
+        }
+        let collected = Arc::new(Mutex::new(RustOperationResult::default()));
+
+        let (result, changes) = block_on_interruptible(
+            py,
+            &state.runtime,
+            Self::sync_up_concurrent(
+                Arc::clone(&state.s3_config),
+                PathBuf::from(local_dir),
+                bucket_name.to_string(),
+                prefix.to_string(),
+                filter,
+                delete,
+                checksum,
+                scheduling,
+                self.upload_options(options),
+                state.settings.clone().stopping_after(failure_limit),
+                progress.unwrap_or_default().state,
+                Arc::clone(&collected),
+            ),
+        )
+        .map_err(|e| with_partial_results(py, e, &collected))??;
+
+        let results = checked(
+            raise_on_failure,
+            Self::into_results(result, Some(bucket_name)),
+        )?;
+        let results = Py::new(py, results)?;
+        Ok(Planned::Done(SyncReport::new(results, changes)))
     }
 
-    pub fn upload_multiple_files(
+    /// Uploads every file below `local_dir` that the `include` and `exclude`
+    /// patterns select to `prefix`, keyed by its path relative to the directory.
+    /// Unlike `sync_up`, nothing is compared: every selected file is uploaded.
+    #[pyo3(signature = (local_dir, bucket_name, prefix, include=None, exclude=None, progress=None, if_none_match=false, options=None, fail_fast=false, max_failures=None, raise_on_failure=false, dry_run=false))]
+    #[allow(clippy::too_many_arguments)]
+    fn upload_directory(
         &self,
+        py: Python<'_>,
+        local_dir: &str,
         bucket_name: &str,
-        paths_and_keys: Vec<(String, String)>,
-    ) -> PyResult<Results> {
-        let s3_config = Arc::clone(&self.s3_config);
-        let rt = tokio::runtime::Builder::new_current_thread()
-            .enable_all()
-            .build()
-            .map_err(|e| {
-                PyRuntimeError::new_err(format!("Failed to create async runtime: {}", e))
-            })?;
+        prefix: &str,
+        include: Option<Vec<String>>,
+        exclude: Option<Vec<String>>,
+        progress: Option<TransferProgress>,
+        if_none_match: bool,
+        options: Option<UploadOptions>,
+        fail_fast: bool,
+        max_failures: Option<usize>,
+        raise_on_failure: bool,
+        dry_run: bool,
+    ) -> PyResult<Planned<Results>> {
+        let failure_limit = failure_limit(fail_fast, max_failures)?;
+        let filter = PathFilter::new(include, exclude)?;
+        let files = block_on_interruptible(
+            py,
+            &self.state().runtime,
+            Self::directory_uploads(Path::new(local_dir), prefix, &filter),
+        )??;
+        let total = files.len();
+        let upload_options = Arc::new(self.upload_options(options));
+        let uploads = ready_items(
+            files
+                .into_iter()
+                .map(|file| TransferRequest {
+                    create_only: if_none_match,
+                    upload_options: Arc::clone(&upload_options),
+                    ..TransferRequest::from(file)
+                })
+                .collect(),
+        );
+        if dry_run {
+            return block_on_interruptible(py, &self.state().runtime, plan_uploads(uploads))?
+                .map(Planned::Plan);
+        }
+        self.run_uploads(
+            py,
+            Some(bucket_name),
+            uploads,
+            Some(total),
+            progress,
+            failure_limit,
+        )
+        .and_then(|results| checked(raise_on_failure, results))
+        .map(Planned::Done)
+    }
 
-        let result = rt.block_on(async {
-            Self::upload_files_concurrent(
+    /// Uploads the failed items of `results` again and merges the outcome into it.
+    #[pyo3(signature = (results, progress=None, fail_fast=false, max_failures=None, raise_on_failure=false))]
+    #[allow(clippy::too_many_arguments)]
+    fn retry_failed<'py>(
+        &self,
+        py: Python<'py>,
+        results: Bound<'py, Results>,
+        progress: Option<TransferProgress>,
+        fail_fast: bool,
+        max_failures: Option<usize>,
+        raise_on_failure: bool,
+    ) -> PyResult<Bound<'py, Results>> {
+        let failure_limit = failure_limit(fail_fast, max_failures)?;
+        let (bucket_name, paths_and_keys) =
+            results.borrow().retry_requests(TransferKind::Upload)?;
+        let total = paths_and_keys.len();
+        let retried = self.run_uploads(
+            py,
+            bucket_name.as_deref(),
+            ready_items(paths_and_keys),
+            Some(total),
+            progress,
+            failure_limit,
+        )?;
+        results.borrow_mut().merge_retry(retried);
+        if raise_on_failure && !results.borrow().failed.is_empty() {
+            return Err(partial_failure(&results));
+        }
+        Ok(results)
+    }
+
+    #[pyo3(signature=(bucket_name, object_key, local_path, if_none_match=false, options=None, details=false))]
+    #[allow(clippy::too_many_arguments)]
+    fn upload_file_async<'py>(
+        &self,
+        py: Python<'py>,
+        bucket_name: String,
+        object_key: String,
+        local_path: String,
+        if_none_match: bool,
+        options: Option<UploadOptions>,
+        details: bool,
+    ) -> PyResult<Bound<'py, PyAny>> {
+        let state = &self.state();
+        let s3_config = Arc::clone(&state.s3_config);
+        let settings = state.settings.clone();
+        let options = self.upload_options(options);
+
+        future_into_py(py, &state.runtime, async move {
+            let started = Instant::now();
+            let progress = ProgressState::default();
+            let completed = Self::upload_single_file(
                 s3_config,
-                bucket_name,
-                paths_and_keys,
-                self.max_concurrent_uploads,
+                &bucket_name,
+                &object_key,
+                &local_path,
+                if_none_match,
+                &options,
+                &progress,
+                &settings,
             )
-            .await
-        });
+            .await?;
+            let stats = ItemStats::measure(started, &progress);
+            Ok(UploadedFile::new(
+                completed,
+                details,
+                &local_path,
+                &object_key,
+                stats,
+            ))
+        })
+    }
 
-        match result {
-            Ok(upload_result) => {
-                let failed_paths: Vec<String> = upload_result
-                    .failed
-                    .iter()
-                    .map(|(key, _error)| key.clone())
-                    .collect();
-
-                if !failed_paths.is_empty() {
-                    eprintln!("Warning {} uploads failed:", failed_paths.len());
-                    for (path, error) in &upload_result.failed {
-                        eprintln!("  {}: {}", path, error);
-                    }
+    #[pyo3(signature = (local_dir, bucket_name, prefix, delete=false, checksum=false, include=None, exclude=None, progress=None, options=None, fail_fast=false, max_failures=None, raise_on_failure=false, dry_run=false, scheduling=Scheduling::Fifo))]
+    #[allow(clippy::too_many_arguments)]
+    fn sync_up_async<'py>(
+        &self,
+        py: Python<'py>,
+        local_dir: String,
+        bucket_name: String,
+        prefix: String,
+        delete: bool,
+        checksum: bool,
+        include: Option<Vec<String>>,
+        exclude: Option<Vec<String>>,
+        progress: Option<TransferProgress>,
+        options: Option<UploadOptions>,
+        fail_fast: bool,
+        max_failures: Option<usize>,
+        raise_on_failure: bool,
+        dry_run: bool,
+        scheduling: Scheduling,
+    ) -> PyResult<Bound<'py, PyAny>> {
+        let state = &self.state();
+        let filter = PathFilter::new(include, exclude)?;
+        let s3_config = Arc::clone(&state.s3_config);
+        let options = self.upload_options(options);
+        let settings = state
+            .settings
+            .clone()
+            .stopping_after(failure_limit(fail_fast, max_failures)?);
+        let progress = progress.unwrap_or_default().state;
+
+        future_into_py(py, &state.runtime, async move {
+            if dry_run {
+                let plan = Self::plan_sync_up(
+                    &s3_config,
+                    Path::new(&local_dir),
+                    &bucket_name,
+                    &prefix,
+                    &filter,
+                    delete,
+                    checksum,
+                    &settings.retry_policy,
+                )
+                .await?;
+                return Ok(Planned::Plan(plan.into_transfer_plan()));
+            }
+            let (result, changes) = Self::sync_up_concurrent(
+                s3_config,
+                PathBuf::from(local_dir),
+                bucket_name.clone(),
+                prefix,
+                filter,
+                delete,
+                checksum,
+                scheduling,
+                options,
+                settings,
+                progress,
+                Arc::default(),
+            )
+            .await?;
+
+            let results = checked(
+                raise_on_failure,
+                Self::into_results(result, Some(&bucket_name)),
+            )?;
+            let results = Python::with_gil(|py| Py::new(py, results))?;
+            Ok(Planned::Done(SyncReport::new(results, changes)))
+        })
+    }
+
+    #[pyo3(signature = (bucket_name, paths_and_keys, progress=None, if_none_match=false, options=None, fail_fast=false, max_failures=None, raise_on_failure=false, archive_mode=None, dry_run=false))]
+    #[allow(clippy::too_many_arguments)]
+    fn upload_multiple_files_async<'py>(
+        &self,
+        py: Python<'py>,
+        bucket_name: String,
+        paths_and_keys: BatchInput<UploadItem>,
+        progress: Option<TransferProgress>,
+        if_none_match: bool,
+        options: Option<UploadOptions>,
+        fail_fast: bool,
+        max_failures: Option<usize>,
+        raise_on_failure: bool,
+        archive_mode: Option<ArchiveMode>,
+        dry_run: bool,
+    ) -> PyResult<Bound<'py, PyAny>> {
+        let state = &self.state();
+        let s3_config = Arc::clone(&state.s3_config);
+        let options = self.upload_options(options);
+        let settings = state
+            .settings
+            .clone()
+            .stopping_after(failure_limit(fail_fast, max_failures)?);
+        let progress = progress.unwrap_or_default().state;
+
+        future_into_py(py, &state.runtime, async move {
+            if dry_run {
+                let requests = upload_requests(paths_and_keys, if_none_match, options);
+                return plan_uploads(requests).await.map(Planned::Plan);
+            }
+            match archive_mode {
+                Some(archive_mode) => {
+                    Self::upload_archived(
+                        s3_config,
+                        &bucket_name,
+                        paths_and_keys,
+                        if_none_match,
+                        options,
+                        archive_mode,
+                        settings,
+                        progress,
+                        Arc::default(),
+                    )
+                    .await
+                }
+                None => {
+                    let total = paths_and_keys.len();
+                    Self::upload_files_concurrent(
+                        s3_config,
+                        Some(&bucket_name),
+                        upload_requests(paths_and_keys, if_none_match, options),
+                        total,
+                        settings,
+                        progress,
+                        Arc::default(),
+                    )
+                    .await
                 }
+            }
+            .map(|result| Self::into_results(result, Some(&bucket_name)))
+            .and_then(|results| checked(raise_on_failure, results))
+            .map(Planned::Done)
+        })
+    }
 
-                Ok(Results::new(upload_result.successful, failed_paths))
+    #[pyo3(signature = (uri, local_path, if_none_match=false, options=None, details=false))]
+    fn upload_uri_async<'py>(
+        &self,
+        py: Python<'py>,
+        uri: S3Uri,
+        local_path: String,
+        if_none_match: bool,
+        options: Option<UploadOptions>,
+        details: bool,
+    ) -> PyResult<Bound<'py, PyAny>> {
+        self.upload_file_async(
+            py,
+            uri.bucket_name,
+            uri.object_key,
+            local_path,
+            if_none_match,
+            options,
+            details,
+        )
+    }
+
+    #[pyo3(signature = (paths_and_uris, progress=None, if_none_match=false, options=None, fail_fast=false, max_failures=None, raise_on_failure=false, dry_run=false))]
+    #[allow(clippy::too_many_arguments)]
+    fn upload_multiple_uris_async<'py>(
+        &self,
+        py: Python<'py>,
+        paths_and_uris: BatchInput<UploadItem>,
+        progress: Option<TransferProgress>,
+        if_none_match: bool,
+        options: Option<UploadOptions>,
+        fail_fast: bool,
+        max_failures: Option<usize>,
+        raise_on_failure: bool,
+        dry_run: bool,
+    ) -> PyResult<Bound<'py, PyAny>> {
+        let state = &self.state();
+        let s3_config = Arc::clone(&state.s3_config);
+        let options = self.upload_options(options);
+        let settings = state
+            .settings
+            .clone()
+            .stopping_after(failure_limit(fail_fast, max_failures)?);
+        let progress = progress.unwrap_or_default().state;
+
+        future_into_py(py, &state.runtime, async move {
+            let total = paths_and_uris.len();
+            let uploads = upload_requests(paths_and_uris, if_none_match, options);
+            if dry_run {
+                return plan_uploads(uploads).await.map(Planned::Plan);
             }
-            Err(e) => Err(PyRuntimeError::new_err(e)),
-        }
+            Self::upload_files_concurrent(
+                s3_config,
+                None,
+                uploads,
+                total,
+                settings,
+                progress,
+                Arc::default(),
+            )
+            .await
+            .map(|result| Self::into_results(result, None))
+            .and_then(|results| checked(raise_on_failure, results))
+            .map(Planned::Done)
+        })
     }
 }
@@ -0,0 +1,134 @@
+use std::fmt;
+
+use pyo3::exceptions::PyValueError;
+use pyo3::types::PyAnyMethods;
+use pyo3::{Bound, FromPyObject, PyAny, PyResult};
+
+use crate::error::TransferError;
+
+/// An object addressed by an `s3://bucket/key` URI. The bucket may also be an
+/// access point ARN, as in `s3://arn:aws:s3:us-east-1:123456789012:accesspoint/ap/key`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct S3Uri {
+    pub bucket_name: String,
+    pub object_key: String,
+}
+
+impl S3Uri {
+    pub fn parse(uri: &str) -> Result<Self, String> {
+        let invalid = |reason: &str| {
+            format!(
+                "Invalid S3 URI '{}': {}, expected 's3://bucket/key'",
+                uri, reason
+            )
+        };
+
+        let Some(location) = uri.strip_prefix("s3://") else {
+            return Err(invalid("missing the s3:// scheme"));
+        };
+        let (bucket_name, object_key) = if is_arn(location) {
+            split_arn(location)
+        } else {
+            location.split_once('/').unwrap_or((location, ""))
+        };
+        if bucket_name.is_empty() {
+            return Err(invalid("missing bucket name"));
+        }
+        // Legacy buckets may have upper case letters and underscores; anything
+        // else is a typo, not a bucket.
+        let valid = if is_arn(bucket_name) {
+            is_access_point_arn(bucket_name)
+        } else {
+            bucket_name
+                .bytes()
+                .all(|byte| byte.is_ascii_alphanumeric() || b".-_".contains(&byte))
+        };
+        if !valid {
+            return Err(invalid(&format!(
+                "'{}' is not a bucket name or access point ARN",
+                bucket_name
+            )));
+        }
+        if object_key.is_empty() {
+            return Err(invalid("missing object key"));
+        }
+
+        Ok(Self {
+            bucket_name: bucket_name.to_string(),
+            object_key: object_key.to_string(),
+        })
+    }
+
+    /// The object a batch item stands for: `source` is a key in `bucket_name`,
+    /// or an `s3://` URI for batches spanning buckets, which have none.
+    pub fn locate(bucket_name: Option<&str>, source: &str) -> Result<Self, TransferError> {
+        match bucket_name {
+            Some(bucket_name) => Ok(Self {
+                bucket_name: bucket_name.to_string(),
+                object_key: source.to_string(),
+            }),
+            None => Self::parse(source).map_err(TransferError::new),
+        }
+    }
+}
+
+/// Whether `bucket_name` is an ARN, of an access point or Multi-Region Access
+/// Point, rather than the name or alias of a bucket.
+pub fn is_arn(bucket_name: &str) -> bool {
+    bucket_name.starts_with("arn:")
+}
+
+/// Where the resource of an ARN starts, after its five `:`-separated fields.
+fn resource_start(arn: &str) -> usize {
+    arn.match_indices(':')
+        .nth(4)
+        .map_or(arn.len(), |(index, _)| index + 1)
+}
+
+/// Whether the resource of `arn` is an access point, `accesspoint/name`, or an
+/// Outposts access point, `outpost/id/accesspoint/name`.
+fn is_access_point_arn(arn: &str) -> bool {
+    let resource = &arn[resource_start(arn)..];
+    let access_point = match resource.strip_prefix("outpost/") {
+        Some(outpost) => match outpost.split_once('/') {
+            Some((id, access_point)) if !id.is_empty() => access_point,
+            _ => return false,
+        },
+        None => resource,
+    };
+    access_point
+        .strip_prefix("accesspoint/")
+        .is_some_and(|name| !name.is_empty() && !name.contains('/'))
+}
+
+/// Splits `arn:...:accesspoint/name/key` after the access point, whose ARN
+/// holds a `/` of its own, or three for the `outpost/id/accesspoint/name` of
+/// an Outposts access point.
+fn split_arn(location: &str) -> (&str, &str) {
+    let resource_start = resource_start(location);
+    let resource = &location[resource_start..];
+    let separators = if resource.starts_with("outpost/") {
+        3
+    } else {
+        1
+    };
+    match resource.match_indices('/').nth(separators) {
+        Some((index, _)) => (
+            &location[..resource_start + index],
+            &location[resource_start + index + 1..],
+        ),
+        None => (location, ""),
+    }
+}
+
+impl fmt::Display for S3Uri {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "s3://{}/{}", self.bucket_name, self.object_key)
+    }
+}
+
+impl<'py> FromPyObject<'py> for S3Uri {
+    fn extract_bound(value: &Bound<'py, PyAny>) -> PyResult<Self> {
+        Self::parse(&value.extract::<String>()?).map_err(PyValueError::new_err)
+    }
+}
@@ -0,0 +1,44 @@
+use std::cmp::Reverse;
+
+use pyo3::exceptions::PyValueError;
+use pyo3::types::PyAnyMethods;
+use pyo3::{Bound, FromPyObject, PyAny, PyResult};
+
+/// The order a batch starts its items in, for batches that know their sizes.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Scheduling {
+    /// The order the items were given or listed in.
+    #[default]
+    Fifo,
+    /// Biggest items first, so that the longest transfers aren't the last ones
+    /// to start and the batch doesn't end waiting on a few of them.
+    LargestFirst,
+    /// Smallest items first, so that as many as possible are done early.
+    SmallestFirst,
+}
+
+impl<'py> FromPyObject<'py> for Scheduling {
+    fn extract_bound(value: &Bound<'py, PyAny>) -> PyResult<Self> {
+        match value.extract::<String>()?.as_str() {
+            "fifo" => Ok(Self::Fifo),
+            "largest_first" => Ok(Self::LargestFirst),
+            "smallest_first" => Ok(Self::SmallestFirst),
+            other => Err(PyValueError::new_err(format!(
+                "Invalid scheduling '{}', expected 'fifo', 'largest_first' or 'smallest_first'",
+                other
+            ))),
+        }
+    }
+}
+
+impl Scheduling {
+    /// Sorts `items` by the size `size` gives each. Items of the same size keep
+    /// the order they had.
+    pub fn order<T>(self, items: &mut [T], size: impl Fn(&T) -> u64) {
+        match self {
+            Self::Fifo => {}
+            Self::LargestFirst => items.sort_by_key(|item| Reverse(size(item))),
+            Self::SmallestFirst => items.sort_by_key(size),
+        }
+    }
+}
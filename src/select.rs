@@ -0,0 +1,264 @@
+use std::sync::Arc;
+
+use aws_sdk_s3::operation::select_object_content::SelectObjectContentOutput;
+use aws_sdk_s3::types::{
+    CompressionType, CsvInput, CsvOutput, ExpressionType, FileHeaderInfo, InputSerialization,
+    JsonInput, JsonOutput, JsonType, OutputSerialization, ParquetInput,
+    SelectObjectContentEventStream,
+};
+use bytes::{Bytes, BytesMut};
+use pyo3::exceptions::PyValueError;
+use pyo3::types::PyAnyMethods;
+use pyo3::{Bound, FromPyObject, PyAny, PyResult};
+use tokio::sync::mpsc;
+
+use crate::error::{error_chain, TransferError};
+use crate::object_stream::ObjectStream;
+use crate::rate_limit::{throttle, TokenBucket};
+use crate::retry::RetryPolicy;
+use crate::runtime::ProcessRuntime;
+use crate::s3_config::S3Config;
+
+/// How the object a query reads is stored.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SelectInput {
+    Csv,
+    /// A single JSON document.
+    Json,
+    /// One JSON document per line.
+    JsonLines,
+    Parquet,
+}
+
+impl<'py> FromPyObject<'py> for SelectInput {
+    fn extract_bound(value: &Bound<'py, PyAny>) -> PyResult<Self> {
+        match value.extract::<String>()?.as_str() {
+            "csv" => Ok(Self::Csv),
+            "json" => Ok(Self::Json),
+            "jsonl" => Ok(Self::JsonLines),
+            "parquet" => Ok(Self::Parquet),
+            other => Err(PyValueError::new_err(format!(
+                "Invalid input format '{}', expected 'csv', 'json', 'jsonl' or 'parquet'",
+                other
+            ))),
+        }
+    }
+}
+
+/// How the records a query selects are returned, one per line either way.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SelectOutput {
+    Csv,
+    Json,
+}
+
+impl<'py> FromPyObject<'py> for SelectOutput {
+    fn extract_bound(value: &Bound<'py, PyAny>) -> PyResult<Self> {
+        match value.extract::<String>()?.as_str() {
+            "csv" => Ok(Self::Csv),
+            "json" => Ok(Self::Json),
+            other => Err(PyValueError::new_err(format!(
+                "Invalid output format '{}', expected 'csv' or 'json'",
+                other
+            ))),
+        }
+    }
+}
+
+/// How a CSV or JSON object a query reads is compressed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SelectCompression {
+    Gzip,
+    Bzip2,
+}
+
+impl<'py> FromPyObject<'py> for SelectCompression {
+    fn extract_bound(value: &Bound<'py, PyAny>) -> PyResult<Self> {
+        match value.extract::<String>()?.as_str() {
+            "gzip" => Ok(Self::Gzip),
+            "bzip2" => Ok(Self::Bzip2),
+            other => Err(PyValueError::new_err(format!(
+                "Invalid compression '{}', expected 'gzip' or 'bzip2'",
+                other
+            ))),
+        }
+    }
+}
+
+/// An S3 Select query: the SQL and how the object and the records it returns
+/// are serialized.
+pub struct SelectQuery {
+    pub sql: String,
+    pub input: SelectInput,
+    pub output: SelectOutput,
+    pub compression: Option<SelectCompression>,
+    /// Whether the first line of a CSV object names its columns.
+    pub csv_header: bool,
+}
+
+impl SelectQuery {
+    /// Streams the records the query selects from the object: as the chunks S3
+    /// sends them, or one record per item, without its newline, with `rows`.
+    #[allow(clippy::too_many_arguments)]
+    pub fn start(
+        self,
+        runtime: Arc<ProcessRuntime>,
+        s3_config: Arc<S3Config>,
+        bucket_name: String,
+        object_key: String,
+        rows: bool,
+        retry_policy: RetryPolicy,
+        bandwidth: Option<Arc<TokenBucket>>,
+    ) -> ObjectStream {
+        ObjectStream::spawn(runtime, |sender| async move {
+            let output = s3_config
+                .run(&bucket_name, &retry_policy, || {
+                    self.send(&s3_config, &bucket_name, &object_key)
+                })
+                .await;
+            match output {
+                Ok(output) => {
+                    let bandwidth = bandwidth.as_deref();
+                    forward(output, &object_key, rows, bandwidth, &sender).await
+                }
+                Err(error) => {
+                    let _ = sender.send(Err(error)).await;
+                }
+            }
+        })
+    }
+
+    async fn send(
+        &self,
+        s3_config: &S3Config,
+        bucket_name: &str,
+        object_key: &str,
+    ) -> Result<SelectObjectContentOutput, TransferError> {
+        s3_config
+            .client_for(bucket_name)
+            .select_object_content()
+            .bucket(bucket_name)
+            .key(object_key)
+            .expression(&self.sql)
+            .expression_type(ExpressionType::Sql)
+            .input_serialization(self.input_serialization())
+            .output_serialization(self.output_serialization())
+            .send()
+            .await
+            .map_err(|e| {
+                TransferError::from_sdk(
+                    format!("Failed to select from S3 object '{}'", object_key),
+                    &e,
+                )
+            })
+    }
+
+    fn input_serialization(&self) -> InputSerialization {
+        let builder = InputSerialization::builder().set_compression_type(self.compression.map(
+            |compression| match compression {
+                SelectCompression::Gzip => CompressionType::Gzip,
+                SelectCompression::Bzip2 => CompressionType::Bzip2,
+            },
+        ));
+        let json = |json_type| JsonInput::builder().r#type(json_type).build();
+        match self.input {
+            SelectInput::Csv => {
+                let header = if self.csv_header {
+                    FileHeaderInfo::Use
+                } else {
+                    FileHeaderInfo::None
+                };
+                builder.csv(CsvInput::builder().file_header_info(header).build())
+            }
+            SelectInput::Json => builder.json(json(JsonType::Document)),
+            SelectInput::JsonLines => builder.json(json(JsonType::Lines)),
+            SelectInput::Parquet => builder.parquet(ParquetInput::builder().build()),
+        }
+        .build()
+    }
+
+    fn output_serialization(&self) -> OutputSerialization {
+        let builder = OutputSerialization::builder();
+        match self.output {
+            SelectOutput::Csv => builder.csv(CsvOutput::builder().record_delimiter("\n").build()),
+            SelectOutput::Json => {
+                builder.json(JsonOutput::builder().record_delimiter("\n").build())
+            }
+        }
+        .build()
+    }
+}
+
+/// Sends the records of the response as they arrive. S3 ends every complete
+/// response with an `End` event, so one that stops before it fails.
+async fn forward(
+    mut output: SelectObjectContentOutput,
+    object_key: &str,
+    rows: bool,
+    bandwidth: Option<&TokenBucket>,
+    sender: &mpsc::Sender<Result<Bytes, TransferError>>,
+) {
+    let mut pending = BytesMut::new();
+    loop {
+        let event = match output.payload.recv().await {
+            Ok(Some(event)) => event,
+            Ok(None) => {
+                let error = TransferError::new(format!(
+                    "S3 Select results for '{}' ended early",
+                    object_key
+                ));
+                let _ = sender.send(Err(error)).await;
+                return;
+            }
+            Err(e) => {
+                let error = TransferError::new(format!(
+                    "Failed to read S3 Select results for '{}': {}",
+                    object_key,
+                    error_chain(&e)
+                ));
+                let _ = sender.send(Err(error)).await;
+                return;
+            }
+        };
+
+        match event {
+            SelectObjectContentEventStream::Records(records) => {
+                let Some(payload) = records.payload else {
+                    continue;
+                };
+                let payload = Bytes::from(payload.into_inner());
+                throttle(bandwidth, payload.len() as u64).await;
+                let chunks = if rows {
+                    pending.extend_from_slice(&payload);
+                    complete_rows(&mut pending)
+                } else {
+                    vec![payload]
+                };
+                for chunk in chunks {
+                    if sender.send(Ok(chunk)).await.is_err() {
+                        // The consumer went away; stop the query.
+                        return;
+                    }
+                }
+            }
+            SelectObjectContentEventStream::End(_) => {
+                if !pending.is_empty() {
+                    let _ = sender.send(Ok(pending.freeze())).await;
+                }
+                return;
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Takes every complete row off the front of `pending`, without its newline.
+/// Records arrive in chunks of any size, so a row may span several of them.
+fn complete_rows(pending: &mut BytesMut) -> Vec<Bytes> {
+    let mut rows = Vec::new();
+    while let Some(end) = pending.iter().position(|&byte| byte == b'\n') {
+        let row = pending.split_to(end + 1).freeze();
+        rows.push(row.slice(..end));
+    }
+    rows
+}
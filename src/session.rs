@@ -0,0 +1,520 @@
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use aws_sdk_s3::types::CompletedPart;
+use log::warn;
+use pyo3::exceptions::PyValueError;
+use pyo3::types::PyAnyMethods;
+use pyo3::{pyclass, pymethods, Bound, PyAny, PyRef, PyResult, Python};
+use serde::{Deserialize, Serialize};
+
+use crate::progress::TransferProgress;
+use crate::results::{Completed, FailedTransfer, Results, TransferKind, TransferRequest};
+use crate::s3_downloader::S3Downloader;
+use crate::s3_uploader::S3Uploader;
+
+/// Least time between two checkpoints of a running batch, so large batches
+/// don't rewrite their file for every item or part. Items and parts finished
+/// since the last checkpoint are transferred again on resume.
+const CHECKPOINT_INTERVAL: Duration = Duration::from_secs(1);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum ItemStatus {
+    Pending,
+    Done,
+    Failed,
+}
+
+/// A part S3 holds of a multipart upload, with what completing the upload
+/// names it by.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UploadedPart {
+    part_number: i32,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    e_tag: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    checksum_crc32: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    checksum_crc32_c: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    checksum_crc64_nvme: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    checksum_sha1: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    checksum_sha256: Option<String>,
+}
+
+impl From<&CompletedPart> for UploadedPart {
+    fn from(part: &CompletedPart) -> Self {
+        Self {
+            part_number: part.part_number.unwrap_or_default(),
+            e_tag: part.e_tag.clone(),
+            checksum_crc32: part.checksum_crc32.clone(),
+            checksum_crc32_c: part.checksum_crc32_c.clone(),
+            checksum_crc64_nvme: part.checksum_crc64_nvme.clone(),
+            checksum_sha1: part.checksum_sha1.clone(),
+            checksum_sha256: part.checksum_sha256.clone(),
+        }
+    }
+}
+
+impl From<UploadedPart> for CompletedPart {
+    fn from(part: UploadedPart) -> Self {
+        CompletedPart::builder()
+            .part_number(part.part_number)
+            .set_e_tag(part.e_tag)
+            .set_checksum_crc32(part.checksum_crc32)
+            .set_checksum_crc32_c(part.checksum_crc32_c)
+            .set_checksum_crc64_nvme(part.checksum_crc64_nvme)
+            .set_checksum_sha1(part.checksum_sha1)
+            .set_checksum_sha256(part.checksum_sha256)
+            .build()
+    }
+}
+
+/// How far the multipart upload of an item got.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UploadCheckpoint {
+    pub upload_id: String,
+    /// Sizes of the file and its parts when the upload started; an upload is
+    /// only resumed with both unchanged.
+    pub file_size: u64,
+    pub part_size: u64,
+    pub parts: Vec<UploadedPart>,
+}
+
+impl UploadCheckpoint {
+    pub fn has_part(&self, part_number: i32) -> bool {
+        self.parts
+            .iter()
+            .any(|part| part.part_number == part_number)
+    }
+}
+
+/// How far the download of an item got: the file holds the first `offset`
+/// bytes of the object version with `etag`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DownloadCheckpoint {
+    pub etag: String,
+    pub offset: u64,
+}
+
+/// An item of a session's batch and how far it got.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct SessionItem {
+    /// S3 key for downloads, local path for uploads.
+    source: String,
+    destination: String,
+    status: ItemStatus,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    upload: Option<UploadCheckpoint>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    download: Option<DownloadCheckpoint>,
+}
+
+/// What a session's file holds.
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct SessionState {
+    kind: Option<TransferKind>,
+    bucket_name: Option<String>,
+    items: Vec<SessionItem>,
+}
+
+impl SessionState {
+    /// The name a batch reports an item under: its local path.
+    fn name_of<'a>(&self, source: &'a str, destination: &'a str) -> &'a str {
+        match self.kind {
+            Some(TransferKind::Upload) => source,
+            _ => destination,
+        }
+    }
+
+    /// Items by the name a batch reports them under.
+    fn index(&self) -> HashMap<String, Vec<usize>> {
+        let mut index: HashMap<String, Vec<usize>> = HashMap::new();
+        for (position, item) in self.items.iter().enumerate() {
+            let name = self.name_of(&item.source, &item.destination);
+            index.entry(name.to_string()).or_default().push(position);
+        }
+        index
+    }
+
+    fn items_with(&self, status: ItemStatus) -> Vec<(String, String)> {
+        self.items
+            .iter()
+            .filter(|item| item.status == status)
+            .map(|item| (item.source.clone(), item.destination.clone()))
+            .collect()
+    }
+}
+
+#[derive(Debug)]
+struct JournalState {
+    session: SessionState,
+    index: HashMap<String, Vec<usize>>,
+    saved_at: Instant,
+}
+
+impl JournalState {
+    /// The first item reported as `name` that isn't done yet.
+    fn item(&mut self, name: &str) -> Option<&mut SessionItem> {
+        let position = self.index.get(name).and_then(|positions| {
+            positions
+                .iter()
+                .copied()
+                .find(|&position| self.session.items[position].status != ItemStatus::Done)
+        })?;
+        Some(&mut self.session.items[position])
+    }
+}
+
+/// The checkpoint file of a session, updated as the items of its batch finish.
+#[derive(Debug)]
+pub struct SessionJournal {
+    path: PathBuf,
+    state: Mutex<JournalState>,
+}
+
+impl SessionJournal {
+    /// Loads the session saved at `path`, or starts an empty one if there is none.
+    fn load(path: PathBuf) -> PyResult<Self> {
+        let session = match std::fs::read(&path) {
+            Ok(contents) => serde_json::from_slice::<SessionState>(&contents).map_err(|e| {
+                PyValueError::new_err(format!(
+                    "Unreadable transfer session '{}': {}",
+                    path.display(),
+                    e
+                ))
+            })?,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => SessionState::default(),
+            Err(e) => {
+                return Err(PyValueError::new_err(format!(
+                    "Failed to read transfer session '{}': {}",
+                    path.display(),
+                    e
+                )))
+            }
+        };
+        Ok(Self {
+            path,
+            state: Mutex::new(JournalState {
+                index: session.index(),
+                session,
+                saved_at: Instant::now(),
+            }),
+        })
+    }
+
+    /// Replaces the session with a new batch whose items are all pending.
+    fn begin(&self, kind: TransferKind, bucket_name: &str, items: Vec<(String, String)>) {
+        let session = SessionState {
+            kind: Some(kind),
+            bucket_name: Some(bucket_name.to_string()),
+            items: items
+                .into_iter()
+                .map(|(source, destination)| SessionItem {
+                    source,
+                    destination,
+                    status: ItemStatus::Pending,
+                    error: None,
+                    upload: None,
+                    download: None,
+                })
+                .collect(),
+        };
+        let mut state = self.state.lock().unwrap();
+        state.index = session.index();
+        state.session = session;
+        self.write(&mut state);
+    }
+
+    /// The kind, bucket and requests of the items not done yet.
+    fn remaining(&self) -> PyResult<(TransferKind, Option<String>, Vec<TransferRequest>)> {
+        let state = self.state.lock().unwrap();
+        let Some(kind) = state.session.kind else {
+            return Err(PyValueError::new_err(format!(
+                "Transfer session '{}' has no batch to resume",
+                self.path.display()
+            )));
+        };
+        let requests = state
+            .session
+            .items
+            .iter()
+            .filter(|item| item.status != ItemStatus::Done)
+            .map(|item| TransferRequest::from((item.source.clone(), item.destination.clone())))
+            .collect();
+        Ok((kind, state.session.bucket_name.clone(), requests))
+    }
+
+    /// Marks the item of `outcome` done or failed, and checkpoints the session if
+    /// it hasn't been for a while.
+    pub fn record(&self, outcome: &Result<Completed, FailedTransfer>) {
+        let mut state = self.state.lock().unwrap();
+        let (name, status, error) = match outcome {
+            Ok(completed) => (completed.name(), ItemStatus::Done, None),
+            Err(failure) => (
+                state
+                    .session
+                    .name_of(&failure.request.source, &failure.request.destination),
+                ItemStatus::Failed,
+                Some(failure.error.message.clone()),
+            ),
+        };
+
+        // An upload that failed was aborted, and a download that failed had
+        // its file removed, so neither can be resumed.
+        if let Some(item) = state.item(name) {
+            item.status = status;
+            item.error = error;
+            item.upload = None;
+            item.download = None;
+        }
+
+        if state.saved_at.elapsed() >= CHECKPOINT_INTERVAL {
+            self.write(&mut state);
+        }
+    }
+
+    /// The multipart upload the file at `local_path` was left in.
+    pub fn upload_checkpoint(&self, local_path: &str) -> Option<UploadCheckpoint> {
+        let mut state = self.state.lock().unwrap();
+        state.item(local_path)?.upload.clone()
+    }
+
+    /// Records the multipart upload the file at `local_path` is sent in, at once
+    /// so that a resume finds it.
+    pub fn start_upload(&self, local_path: &str, checkpoint: UploadCheckpoint) {
+        let mut state = self.state.lock().unwrap();
+        if let Some(item) = state.item(local_path) {
+            item.upload = Some(checkpoint);
+            self.write(&mut state);
+        }
+    }
+
+    /// Records a part of the multipart upload of `local_path` that S3 now holds.
+    pub fn part_uploaded(&self, local_path: &str, part: &CompletedPart) {
+        let mut state = self.state.lock().unwrap();
+        if let Some(upload) = state.item(local_path).and_then(|item| item.upload.as_mut()) {
+            upload.parts.push(UploadedPart::from(part));
+        }
+        if state.saved_at.elapsed() >= CHECKPOINT_INTERVAL {
+            self.write(&mut state);
+        }
+    }
+
+    /// How much of the object being downloaded to `local_path` the file holds.
+    pub fn download_checkpoint(&self, local_path: &str) -> Option<DownloadCheckpoint> {
+        let mut state = self.state.lock().unwrap();
+        state.item(local_path)?.download.clone()
+    }
+
+    /// Records that the file at `local_path` holds the object up to `offset`.
+    pub fn downloaded_to(&self, local_path: &str, etag: &str, offset: u64) {
+        let mut state = self.state.lock().unwrap();
+        if let Some(item) = state.item(local_path) {
+            item.download = Some(DownloadCheckpoint {
+                etag: etag.to_string(),
+                offset,
+            });
+        }
+        if state.saved_at.elapsed() >= CHECKPOINT_INTERVAL {
+            self.write(&mut state);
+        }
+    }
+
+    /// Checkpoints the session now.
+    pub fn save(&self) {
+        self.write(&mut self.state.lock().unwrap());
+    }
+
+    /// Writes the session through a temporary file, so a process killed midway
+    /// leaves the previous checkpoint intact. A failed write only costs a resume
+    /// some repeated transfers, so it is logged rather than raised.
+    fn write(&self, state: &mut JournalState) {
+        let mut temporary = self.path.clone().into_os_string();
+        temporary.push(".tmp");
+        let written = match serde_json::to_vec(&state.session) {
+            Ok(contents) => match std::fs::write(&temporary, contents) {
+                Ok(()) => std::fs::rename(&temporary, &self.path),
+                Err(e) => Err(e),
+            },
+            Err(e) => Err(e.into()),
+        };
+        if let Err(e) = written {
+            warn!(
+                "Failed to write transfer session '{}': {}",
+                self.path.display(),
+                e
+            );
+        }
+        state.saved_at = Instant::now();
+    }
+}
+
+/// A batch checkpointed to a local JSON file as its items finish, so a process
+/// that is killed can pick the batch up again with `resume`.
+///
+/// Files larger than a part are uploaded in a multipart upload whose ID and
+/// parts are checkpointed, and downloads checkpoint how far their file got, so
+/// an item that was in flight when the process died continues from there.
+#[pyclass(frozen, module = "robinzhon")]
+pub struct TransferSession {
+    journal: Arc<SessionJournal>,
+}
+
+#[pymethods]
+impl TransferSession {
+    #[new]
+    fn new(path: PathBuf) -> PyResult<Self> {
+        Ok(Self {
+            journal: Arc::new(SessionJournal::load(path)?),
+        })
+    }
+
+    /// Starts a batch of `(key, local_path)` downloads, replacing whatever the
+    /// session held.
+    #[pyo3(signature = (downloader, bucket_name, downloads, progress=None))]
+    fn download(
+        &self,
+        py: Python<'_>,
+        downloader: &Bound<'_, S3Downloader>,
+        bucket_name: &str,
+        downloads: Vec<(String, String)>,
+        progress: Option<TransferProgress>,
+    ) -> PyResult<Results> {
+        self.journal
+            .begin(TransferKind::Download, bucket_name, downloads);
+        self.resume(py, downloader.as_any(), progress)
+    }
+
+    /// Starts a batch of `(local_path, key)` uploads, replacing whatever the
+    /// session held.
+    #[pyo3(signature = (uploader, bucket_name, paths_and_keys, progress=None))]
+    fn upload(
+        &self,
+        py: Python<'_>,
+        uploader: &Bound<'_, S3Uploader>,
+        bucket_name: &str,
+        paths_and_keys: Vec<(String, String)>,
+        progress: Option<TransferProgress>,
+    ) -> PyResult<Results> {
+        self.journal
+            .begin(TransferKind::Upload, bucket_name, paths_and_keys);
+        self.resume(py, uploader.as_any(), progress)
+    }
+
+    /// Runs the items of the session's batch that are not done yet, pending and
+    /// failed alike, with `client`: an `S3Downloader` for downloads, an
+    /// `S3Uploader` for uploads.
+    #[pyo3(signature = (client, progress=None))]
+    fn resume(
+        &self,
+        py: Python<'_>,
+        client: &Bound<'_, PyAny>,
+        progress: Option<TransferProgress>,
+    ) -> PyResult<Results> {
+        let (kind, bucket_name, requests) = self.journal.remaining()?;
+        let journal = Arc::clone(&self.journal);
+        let results = match kind {
+            TransferKind::Download => {
+                let downloader = client.extract::<PyRef<'_, S3Downloader>>().map_err(|_| {
+                    PyValueError::new_err("A session of downloads is resumed with an S3Downloader")
+                })?;
+                downloader.run_journaled(py, bucket_name.as_deref(), requests, progress, journal)
+            }
+            TransferKind::Upload => {
+                let uploader = client.extract::<PyRef<'_, S3Uploader>>().map_err(|_| {
+                    PyValueError::new_err("A session of uploads is resumed with an S3Uploader")
+                })?;
+                uploader.run_journaled(py, bucket_name.as_deref(), requests, progress, journal)
+            }
+        };
+        self.journal.save();
+        results
+    }
+
+    #[getter]
+    fn path(&self) -> String {
+        self.journal.path.to_string_lossy().into_owned()
+    }
+
+    /// "download" or "upload"; `None` before the first batch.
+    #[getter]
+    fn kind(&self) -> Option<&'static str> {
+        self.journal
+            .state
+            .lock()
+            .unwrap()
+            .session
+            .kind
+            .map(|kind| match kind {
+                TransferKind::Download => "download",
+                TransferKind::Upload => "upload",
+            })
+    }
+
+    #[getter]
+    fn bucket_name(&self) -> Option<String> {
+        self.journal
+            .state
+            .lock()
+            .unwrap()
+            .session
+            .bucket_name
+            .clone()
+    }
+
+    /// Items not started or not finished, as `(source, destination)`.
+    #[getter]
+    fn pending(&self) -> Vec<(String, String)> {
+        let state = self.journal.state.lock().unwrap();
+        state.session.items_with(ItemStatus::Pending)
+    }
+
+    #[getter]
+    fn done(&self) -> Vec<(String, String)> {
+        let state = self.journal.state.lock().unwrap();
+        state.session.items_with(ItemStatus::Done)
+    }
+
+    #[getter]
+    fn failed(&self) -> Vec<(String, String)> {
+        let state = self.journal.state.lock().unwrap();
+        state.session.items_with(ItemStatus::Failed)
+    }
+
+    /// Whether every item of the batch is done.
+    fn is_complete(&self) -> bool {
+        let state = self.journal.state.lock().unwrap();
+        state
+            .session
+            .items
+            .iter()
+            .all(|item| item.status == ItemStatus::Done)
+    }
+
+    fn __repr__(&self) -> String {
+        let state = self.journal.state.lock().unwrap();
+        let count = |status| {
+            state
+                .session
+                .items
+                .iter()
+                .filter(|item| item.status == status)
+                .count()
+        };
+        format!(
+            "TransferSession(path={:?}, pending={}, done={}, failed={})",
+            self.journal.path.display().to_string(),
+            count(ItemStatus::Pending),
+            count(ItemStatus::Done),
+            count(ItemStatus::Failed)
+        )
+    }
+}
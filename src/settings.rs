@@ -0,0 +1,167 @@
+use std::path::Path;
+use std::sync::{Arc, RwLock};
+
+use tokio::sync::Semaphore;
+
+use crate::attributes::SymlinkPolicy;
+use crate::audit::AuditLog;
+use crate::checksums::Sha256Sums;
+use crate::encryption::Envelope;
+use crate::memory_budget::MemoryBudget;
+use crate::pause::PauseGate;
+use crate::rate_limit::TokenBucket;
+use crate::retry::RetryPolicy;
+use crate::runtime::ProcessRuntime;
+use crate::s3_config::S3Config;
+use crate::session::SessionJournal;
+
+/// Size of each ranged GET when an object is downloaded in parts.
+pub const DEFAULT_PART_SIZE: u64 = 8 * 1024 * 1024;
+/// Ranged GETs kept in flight per object.
+pub const DEFAULT_PART_CONCURRENCY: usize = 8;
+
+/// Size of the chunks yielded when streaming an object.
+pub const DEFAULT_CHUNK_SIZE: usize = 1024 * 1024;
+
+/// Bytes buffered before a download is written to its file, as Tokio's
+/// `BufWriter` does by default.
+pub const DEFAULT_WRITE_BUFFER_SIZE: usize = 8 * 1024;
+
+/// Per-instance knobs shared by every transfer a downloader or uploader runs.
+#[derive(Debug, Clone)]
+pub struct TransferSettings {
+    pub max_concurrent: usize,
+    pub retry_policy: RetryPolicy,
+    pub part_size: u64,
+    pub part_concurrency: usize,
+    /// Client-side encryption of uploads and decryption of downloads.
+    pub client_encryption: Option<Arc<Envelope>>,
+    /// Caps the bytes per second of all transfers together.
+    pub bandwidth: Option<Arc<TokenBucket>>,
+    /// Caps the transfers dispatched per second, each starting with one request.
+    pub requests: Option<Arc<TokenBucket>>,
+    /// Slots shared with other instances; each object transfer holds one.
+    pub limiter: Option<Arc<Semaphore>>,
+    /// Caps the bytes downloads buffer at once, across all of them.
+    pub memory: Option<Arc<MemoryBudget>>,
+    pub write_buffer_size: usize,
+    /// Whether downloads are checked against their ETag and stored checksums.
+    pub verify_checksum: bool,
+    /// Whether gzip-encoded objects and `.gz` keys are stored decompressed.
+    pub auto_decompress: bool,
+    /// Whether downloaded files take the modification time of their object,
+    /// and uploaded objects record the one of their file.
+    pub preserve_mtime: bool,
+    /// Whether uploaded objects record the mode bits of their file, and
+    /// downloaded files get them back.
+    pub preserve_permissions: bool,
+    pub symlinks: SymlinkPolicy,
+    /// Directory a batch downloads below, which the links it restores must
+    /// point inside of and its files mustn't be written through links of.
+    pub destination: Option<Arc<Path>>,
+    /// Holds back the items and parts of a paused `TransferManager` job.
+    pub pause: Option<Arc<PauseGate>>,
+    /// Failed items after which a batch stops dispatching more.
+    pub failure_limit: Option<usize>,
+    /// SHA-256 digests a batch checks its downloads against and collects.
+    pub sha256: Option<Arc<Sha256Sums>>,
+    /// Log every file download or upload is appended to as it ends.
+    pub audit: Option<Arc<AuditLog>>,
+    /// Session a batch checkpoints its multipart uploads and partial downloads
+    /// to, so that they can be resumed.
+    pub journal: Option<Arc<SessionJournal>>,
+}
+
+impl TransferSettings {
+    pub fn new(max_concurrent: usize, retry_policy: RetryPolicy) -> Self {
+        Self {
+            max_concurrent,
+            retry_policy,
+            part_size: DEFAULT_PART_SIZE,
+            part_concurrency: DEFAULT_PART_CONCURRENCY,
+            client_encryption: None,
+            bandwidth: None,
+            requests: None,
+            limiter: None,
+            memory: None,
+            write_buffer_size: DEFAULT_WRITE_BUFFER_SIZE,
+            verify_checksum: false,
+            auto_decompress: false,
+            preserve_mtime: false,
+            preserve_permissions: false,
+            symlinks: SymlinkPolicy::Follow,
+            destination: None,
+            pause: None,
+            failure_limit: None,
+            sha256: None,
+            audit: None,
+            journal: None,
+        }
+    }
+
+    /// The settings of a batch that stops once `failure_limit` items failed.
+    pub fn stopping_after(self, failure_limit: Option<usize>) -> Self {
+        Self {
+            failure_limit,
+            ..self
+        }
+    }
+
+    /// The settings of a batch hashing its downloads into `sha256`.
+    pub fn hashing_into(self, sha256: Option<Arc<Sha256Sums>>) -> Self {
+        Self { sha256, ..self }
+    }
+
+    /// The settings of a batch checkpointed to `journal`.
+    pub fn checkpointed_to(self, journal: Option<Arc<SessionJournal>>) -> Self {
+        Self { journal, ..self }
+    }
+
+    /// The settings of a batch downloading below `directory`.
+    pub fn below(self, directory: &Path) -> Self {
+        Self {
+            destination: Some(Arc::from(directory)),
+            ..self
+        }
+    }
+
+    /// Whether downloads restore attributes of the file an object was uploaded
+    /// from, which needs the metadata of their GET.
+    pub fn preserves_attributes(&self) -> bool {
+        self.preserve_mtime || self.preserve_permissions || self.symlinks != SymlinkPolicy::Follow
+    }
+}
+
+/// What the calls of a downloader or uploader run with.
+pub struct ClientState {
+    pub s3_config: Arc<S3Config>,
+    pub settings: TransferSettings,
+    pub runtime: Arc<ProcessRuntime>,
+}
+
+/// The current `ClientState` of an instance, shared by every thread calling it.
+///
+/// Each call takes the state once and runs with it to the end, so `replace`
+/// doesn't disturb calls in flight on other threads; they finish with the state
+/// they took, and the next calls get the new one.
+pub struct SharedState {
+    current: RwLock<Arc<ClientState>>,
+}
+
+impl SharedState {
+    pub fn new(state: ClientState) -> Self {
+        Self {
+            current: RwLock::new(Arc::new(state)),
+        }
+    }
+
+    /// The state to run a call with. The lock is only held to clone it, never
+    /// across a transfer, so a process forked mid-call can still replace it.
+    pub fn get(&self) -> Arc<ClientState> {
+        Arc::clone(&self.current.read().unwrap())
+    }
+
+    pub fn replace(&self, state: ClientState) {
+        *self.current.write().unwrap() = Arc::new(state);
+    }
+}
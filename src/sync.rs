@@ -0,0 +1,350 @@
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+
+use log::warn;
+use md5::{Digest, Md5};
+use pyo3::{pyclass, pymethods, Py, PyRef, Python};
+use serde::{Deserialize, Serialize};
+use tokio::io::AsyncReadExt;
+
+use crate::error::TransferError;
+use crate::filters::PathFilter;
+use crate::integrity::hex;
+use crate::listing::ObjectInfo;
+use crate::results::Results;
+
+/// File kept at the root of a synced directory, remembering what each local file
+/// was synced from.
+pub const CACHE_FILE_NAME: &str = ".robinzhon-sync.json";
+
+/// Size and modification time of a local file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LocalFile {
+    pub size: u64,
+    pub modified_ns: u64,
+}
+
+impl LocalFile {
+    pub async fn stat(path: &Path) -> Option<Self> {
+        let metadata = tokio::fs::metadata(path).await.ok()?;
+        let modified = metadata.modified().ok()?.duration_since(UNIX_EPOCH).ok()?;
+        metadata.is_file().then_some(Self {
+            size: metadata.len(),
+            modified_ns: modified.as_nanos() as u64,
+        })
+    }
+
+    fn modified_secs(&self) -> f64 {
+        self.modified_ns as f64 / 1e9
+    }
+}
+
+/// The remote object a local file matched the last time it was synced.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CachedFile {
+    pub etag: Option<String>,
+    #[serde(flatten)]
+    pub local: LocalFile,
+}
+
+/// Per-directory record of synced files, keyed by path relative to the directory.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct SyncCache {
+    bucket_name: String,
+    prefix: String,
+    files: BTreeMap<String, CachedFile>,
+}
+
+impl SyncCache {
+    pub fn new(bucket_name: &str, prefix: &str) -> Self {
+        Self {
+            bucket_name: bucket_name.to_string(),
+            prefix: prefix.to_string(),
+            files: BTreeMap::new(),
+        }
+    }
+
+    /// Loads the cache of `local_dir`. A missing or unreadable cache, or one
+    /// written for another bucket or prefix, is treated as empty.
+    pub async fn load(local_dir: &Path, bucket_name: &str, prefix: &str) -> Self {
+        let path = local_dir.join(CACHE_FILE_NAME);
+        let cache = match tokio::fs::read(&path).await {
+            Ok(contents) => serde_json::from_slice::<Self>(&contents).unwrap_or_else(|e| {
+                warn!("Ignoring unreadable sync cache '{}': {}", path.display(), e);
+                Self::default()
+            }),
+            Err(_) => Self::default(),
+        };
+
+        if cache.bucket_name == bucket_name && cache.prefix == prefix {
+            cache
+        } else {
+            Self::new(bucket_name, prefix)
+        }
+    }
+
+    /// Writes the cache next to the synced files. Failing to do so only costs the
+    /// next sync some comparisons, so it is logged rather than raised.
+    pub async fn save(&self, local_dir: &Path) {
+        let path = local_dir.join(CACHE_FILE_NAME);
+        let temporary = local_dir.join(format!("{}.tmp", CACHE_FILE_NAME));
+        let written = match serde_json::to_vec_pretty(self) {
+            Ok(contents) => match tokio::fs::write(&temporary, contents).await {
+                Ok(()) => tokio::fs::rename(&temporary, &path).await,
+                Err(e) => Err(e),
+            },
+            Err(e) => Err(e.into()),
+        };
+        if let Err(e) = written {
+            warn!("Failed to write sync cache '{}': {}", path.display(), e);
+        }
+    }
+
+    pub fn get(&self, relative_path: &str) -> Option<&CachedFile> {
+        self.files.get(relative_path)
+    }
+
+    pub fn insert(&mut self, relative_path: String, etag: Option<String>, local: LocalFile) {
+        self.files.insert(relative_path, CachedFile { etag, local });
+    }
+}
+
+/// Whether the local copy of `remote` is missing or out of date.
+///
+/// When the cache knows the file and it hasn't been touched locally since, the
+/// ETags decide. Otherwise the file is considered current if it has the same size
+/// and is at least as recent as the object.
+pub fn needs_download(
+    remote: &ObjectInfo,
+    local: Option<LocalFile>,
+    cached: Option<&CachedFile>,
+) -> bool {
+    let Some(local) = local else {
+        return true;
+    };
+    match cached {
+        Some(cached) if cached.local == local => cached.etag != remote.etag,
+        Some(_) => true,
+        None => {
+            local.size != remote.size
+                || remote
+                    .last_modified
+                    .is_some_and(|modified| local.modified_secs() < modified)
+        }
+    }
+}
+
+/// Relative, `/`-separated paths of every file below `local_dir` that passes
+/// `filter`, leaving out the sync cache.
+pub async fn walk_local(
+    local_dir: &Path,
+    filter: &PathFilter,
+) -> Result<Vec<String>, TransferError> {
+    let mut files = Vec::new();
+    let mut pending = vec![PathBuf::new()];
+
+    while let Some(relative_dir) = pending.pop() {
+        let directory = local_dir.join(&relative_dir);
+        let mut entries = match tokio::fs::read_dir(&directory).await {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => continue,
+            Err(e) => {
+                return Err(TransferError::new(format!(
+                    "Failed to read directory '{}': {}",
+                    directory.display(),
+                    e
+                )))
+            }
+        };
+
+        while let Some(entry) = entries.next_entry().await.map_err(|e| {
+            TransferError::new(format!(
+                "Failed to read directory '{}': {}",
+                directory.display(),
+                e
+            ))
+        })? {
+            let relative_path = relative_dir.join(entry.file_name());
+            let file_type = entry.file_type().await.map_err(|e| {
+                TransferError::new(format!(
+                    "Failed to inspect '{}': {}",
+                    entry.path().display(),
+                    e
+                ))
+            })?;
+            if file_type.is_dir() {
+                pending.push(relative_path);
+                continue;
+            }
+
+            let relative_path = relative_path
+                .components()
+                .map(|component| component.as_os_str().to_string_lossy())
+                .collect::<Vec<_>>()
+                .join("/");
+            if !relative_path.starts_with(CACHE_FILE_NAME) && filter.matches(&relative_path) {
+                files.push(relative_path);
+            }
+        }
+    }
+
+    files.sort();
+    Ok(files)
+}
+
+/// Removes the given files below `local_dir`, returning the paths that were
+/// deleted. Failures are logged and skipped.
+pub async fn delete_extraneous(local_dir: &Path, relative_paths: Vec<String>) -> Vec<String> {
+    let mut deleted = Vec::new();
+    for relative_path in relative_paths {
+        let path = local_dir.join(&relative_path);
+        match tokio::fs::remove_file(&path).await {
+            Ok(()) => deleted.push(path.to_string_lossy().to_string()),
+            Err(e) => warn!("Failed to delete '{}': {}", path.display(), e),
+        }
+    }
+    deleted
+}
+
+/// Whether `remote` is missing or older than the local file it mirrors.
+///
+/// Files of the same size are compared by `local_md5` when given and the ETag is
+/// a plain MD5 (not a multipart upload's); otherwise a local file modified after
+/// the object was written is considered changed.
+pub fn needs_upload(
+    local: LocalFile,
+    remote: Option<&ObjectInfo>,
+    local_md5: Option<&str>,
+) -> bool {
+    let Some(remote) = remote else {
+        return true;
+    };
+    if local.size != remote.size {
+        return true;
+    }
+    match (local_md5, remote.etag.as_deref().and_then(plain_md5)) {
+        (Some(local_md5), Some(remote_md5)) => !local_md5.eq_ignore_ascii_case(remote_md5),
+        // Listings report modification times in whole seconds.
+        _ => remote
+            .last_modified
+            .is_none_or(|modified| local.modified_secs().floor() > modified),
+    }
+}
+
+/// The MD5 an ETag stands for, if it is one: single-part uploads without
+/// SSE-KMS get the hex digest of their body, quoted.
+pub fn plain_md5(etag: &str) -> Option<&str> {
+    let etag = etag.trim_matches('"');
+    (etag.len() == 32 && etag.bytes().all(|byte| byte.is_ascii_hexdigit())).then_some(etag)
+}
+
+/// The MD5 and part count a multipart ETag stands for, `"<md5>-<parts>"`: the
+/// digest is taken over the concatenated MD5s of the parts.
+pub fn composite_md5(etag: &str) -> Option<(&str, u64)> {
+    let (digest, parts) = etag.trim_matches('"').split_once('-')?;
+    Some((plain_md5(digest)?, parts.parse().ok()?))
+}
+
+/// Hex MD5 digest of a local file, read in chunks.
+pub async fn file_md5(path: &Path) -> Result<String, TransferError> {
+    let read_error = |e: std::io::Error| {
+        TransferError::new(format!("Failed to read file '{}': {}", path.display(), e))
+    };
+    let mut file = tokio::fs::File::open(path).await.map_err(read_error)?;
+    let mut hasher = Md5::new();
+    let mut buffer = vec![0; 1024 * 1024];
+    loop {
+        let read = file.read(&mut buffer).await.map_err(read_error)?;
+        if read == 0 {
+            break;
+        }
+        hasher.update(&buffer[..read]);
+    }
+    Ok(hex(&hasher.finalize()))
+}
+
+/// Hex digest of a local file as a multipart ETag would have it, for parts of
+/// `part_size` bytes.
+pub async fn file_composite_md5(path: &Path, part_size: u64) -> Result<String, TransferError> {
+    let read_error = |e: std::io::Error| {
+        TransferError::new(format!("Failed to read file '{}': {}", path.display(), e))
+    };
+    let mut file = tokio::fs::File::open(path).await.map_err(read_error)?;
+    let mut digests = Md5::new();
+    let mut part = Md5::new();
+    let mut in_part = 0;
+    let mut buffer = vec![0; 1024 * 1024];
+    loop {
+        let wanted = buffer.len().min((part_size - in_part) as usize);
+        let read = file.read(&mut buffer[..wanted]).await.map_err(read_error)?;
+        if read == 0 {
+            break;
+        }
+        part.update(&buffer[..read]);
+        in_part += read as u64;
+        if in_part == part_size {
+            digests.update(part.finalize_reset());
+            in_part = 0;
+        }
+    }
+    if in_part > 0 {
+        digests.update(part.finalize());
+    }
+    Ok(hex(&digests.finalize()))
+}
+
+/// What a sync decided for each item, besides the transfers' own outcome.
+#[derive(Debug, Default)]
+pub struct SyncChanges {
+    /// Items transferred because the destination didn't have them.
+    pub new: Vec<String>,
+    /// Items transferred because the destination's copy was outdated.
+    pub changed: Vec<String>,
+    /// Items already up to date.
+    pub skipped: Vec<String>,
+    /// Items removed from the destination because the source no longer has them.
+    pub deleted: Vec<String>,
+}
+
+/// Outcome of a sync: the transfers it ran, and what it left alone or removed.
+#[pyclass(frozen, module = "robinzhon")]
+pub struct SyncReport {
+    #[pyo3(get)]
+    results: Py<Results>,
+    #[pyo3(get)]
+    new: Vec<String>,
+    #[pyo3(get)]
+    changed: Vec<String>,
+    #[pyo3(get)]
+    skipped: Vec<String>,
+    #[pyo3(get)]
+    deleted: Vec<String>,
+}
+
+impl SyncReport {
+    pub fn new(results: Py<Results>, changes: SyncChanges) -> Self {
+        Self {
+            results,
+            new: changes.new,
+            changed: changes.changed,
+            skipped: changes.skipped,
+            deleted: changes.deleted,
+        }
+    }
+}
+
+#[pymethods]
+impl SyncReport {
+    fn __repr__(slf: PyRef<'_, Self>, py: Python<'_>) -> String {
+        let results = slf.results.borrow(py);
+        format!(
+            "SyncReport(new={}, changed={}, failed={}, skipped={}, deleted={})",
+            slf.new.len(),
+            slf.changed.len(),
+            results.failed.len(),
+            slf.skipped.len(),
+            slf.deleted.len()
+        )
+    }
+}
@@ -0,0 +1,245 @@
+use std::path::Path;
+
+use futures::stream::{self, Stream};
+use pyo3::exceptions::PyValueError;
+use pyo3::types::PyAnyMethods;
+use pyo3::{Bound, FromPyObject, PyAny, PyErr, PyResult};
+use serde::Deserialize;
+use tokio::fs::File;
+use tokio::io::{AsyncBufReadExt, BufReader, Lines};
+
+use crate::exceptions::RobinzhonError;
+use crate::s3_uri::S3Uri;
+
+/// How a manifest of transfers is written.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ManifestFormat {
+    /// A header naming the `bucket`, `key` and `local_path` columns, then one
+    /// row per transfer.
+    Csv,
+    /// One JSON object per line, with `bucket`, `key` and `local_path` fields.
+    JsonLines,
+}
+
+impl<'py> FromPyObject<'py> for ManifestFormat {
+    fn extract_bound(value: &Bound<'py, PyAny>) -> PyResult<Self> {
+        match value.extract::<String>()?.as_str() {
+            "csv" => Ok(Self::Csv),
+            "jsonl" => Ok(Self::JsonLines),
+            other => Err(PyValueError::new_err(format!(
+                "Invalid manifest format '{}', expected 'csv' or 'jsonl'",
+                other
+            ))),
+        }
+    }
+}
+
+impl ManifestFormat {
+    /// The format given, or else the one the extension of `manifest_path` names.
+    pub fn of(manifest_path: &str, format: Option<Self>) -> PyResult<Self> {
+        if let Some(format) = format {
+            return Ok(format);
+        }
+        let extension = Path::new(manifest_path)
+            .extension()
+            .map(|extension| extension.to_string_lossy().to_ascii_lowercase());
+        match extension.as_deref() {
+            Some("csv") => Ok(Self::Csv),
+            Some("jsonl" | "ndjson") => Ok(Self::JsonLines),
+            _ => Err(PyValueError::new_err(format!(
+                "Can't tell the format of manifest '{}' from its extension; pass format='csv' or format='jsonl'",
+                manifest_path
+            ))),
+        }
+    }
+}
+
+/// A transfer a manifest lists: an object and the local file it goes to or
+/// comes from.
+#[derive(Debug, Clone)]
+pub struct ManifestEntry {
+    pub object: S3Uri,
+    pub local_path: String,
+}
+
+#[derive(Deserialize)]
+struct JsonEntry {
+    bucket: Option<String>,
+    key: String,
+    local_path: String,
+}
+
+/// Positions of the columns of a CSV manifest, from its header.
+#[derive(Debug, Clone, Copy)]
+struct Columns {
+    bucket: Option<usize>,
+    key: usize,
+    local_path: usize,
+}
+
+/// Reads a manifest line by line, so that one of millions of rows is never held
+/// in memory at once.
+struct ManifestReader {
+    path: String,
+    format: ManifestFormat,
+    /// Bucket of the rows that don't name one.
+    default_bucket: Option<String>,
+    /// `None` until the first read opens the file.
+    lines: Option<Lines<BufReader<File>>>,
+    columns: Option<Columns>,
+    line_number: usize,
+}
+
+impl ManifestReader {
+    async fn next_entry(&mut self) -> PyResult<Option<ManifestEntry>> {
+        if self.lines.is_none() {
+            let file = File::open(&self.path)
+                .await
+                .map_err(|e| self.read_error(e))?;
+            self.lines = Some(BufReader::new(file).lines());
+        }
+        loop {
+            let next_line = match self.lines.as_mut() {
+                Some(lines) => lines.next_line().await,
+                None => return Ok(None),
+            };
+            let Some(line) = next_line.map_err(|e| self.read_error(e))? else {
+                return Ok(None);
+            };
+            self.line_number += 1;
+            // Spreadsheets often save CSV files with a byte order mark.
+            let line = line.strip_prefix('\u{feff}').unwrap_or(&line);
+            if line.trim().is_empty() {
+                continue;
+            }
+            let entry = match self.format {
+                ManifestFormat::Csv => self.csv_entry(line)?,
+                ManifestFormat::JsonLines => Some(self.json_entry(line)?),
+            };
+            if entry.is_some() {
+                return Ok(entry);
+            }
+        }
+    }
+
+    /// The entry of a CSV row, or `None` for the header.
+    fn csv_entry(&mut self, line: &str) -> PyResult<Option<ManifestEntry>> {
+        let fields = split_csv(line).ok_or_else(|| self.invalid_line("unbalanced quotes"))?;
+        let Some(columns) = self.columns else {
+            self.columns = Some(self.header(&fields)?);
+            return Ok(None);
+        };
+        let field = |index: usize| fields.get(index).cloned().filter(|field| !field.is_empty());
+        let (Some(key), Some(local_path)) = (field(columns.key), field(columns.local_path)) else {
+            return Err(self.invalid_line("it has no key or no local_path"));
+        };
+        let bucket = columns.bucket.and_then(field);
+        self.entry(bucket, key, local_path).map(Some)
+    }
+
+    fn header(&self, fields: &[String]) -> PyResult<Columns> {
+        let column = |name: &str| {
+            fields
+                .iter()
+                .position(|field| field.trim().eq_ignore_ascii_case(name))
+        };
+        match (column("key"), column("local_path")) {
+            (Some(key), Some(local_path)) => Ok(Columns {
+                bucket: column("bucket"),
+                key,
+                local_path,
+            }),
+            _ => Err(self.invalid_line("the header must name 'key' and 'local_path' columns")),
+        }
+    }
+
+    fn json_entry(&self, line: &str) -> PyResult<ManifestEntry> {
+        let entry: JsonEntry =
+            serde_json::from_str(line).map_err(|e| self.invalid_line(&e.to_string()))?;
+        self.entry(entry.bucket, entry.key, entry.local_path)
+    }
+
+    fn entry(
+        &self,
+        bucket: Option<String>,
+        key: String,
+        local_path: String,
+    ) -> PyResult<ManifestEntry> {
+        let bucket_name = bucket
+            .or_else(|| self.default_bucket.clone())
+            .ok_or_else(|| self.invalid_line("it names no bucket, and no bucket_name was given"))?;
+        Ok(ManifestEntry {
+            object: S3Uri {
+                bucket_name,
+                object_key: key,
+            },
+            local_path,
+        })
+    }
+
+    fn invalid_line(&self, reason: &str) -> PyErr {
+        PyValueError::new_err(format!(
+            "Invalid line {} of manifest '{}': {}",
+            self.line_number, self.path, reason
+        ))
+    }
+
+    fn read_error(&self, error: std::io::Error) -> PyErr {
+        RobinzhonError::new_err(format!(
+            "Failed to read manifest '{}': {}",
+            self.path, error
+        ))
+    }
+}
+
+/// Streams the entries of the manifest at `manifest_path` as it is read. Rows
+/// that name no bucket are in `default_bucket`. A row that can't be read ends
+/// the stream with an error naming its line.
+pub fn read_manifest(
+    manifest_path: String,
+    format: ManifestFormat,
+    default_bucket: Option<String>,
+) -> impl Stream<Item = PyResult<ManifestEntry>> + Send {
+    let reader = ManifestReader {
+        path: manifest_path,
+        format,
+        default_bucket,
+        lines: None,
+        columns: None,
+        line_number: 0,
+    };
+    stream::unfold(Some(reader), |reader| async move {
+        let mut reader = reader?;
+        match reader.next_entry().await {
+            Ok(Some(entry)) => Some((Ok(entry), Some(reader))),
+            Ok(None) => None,
+            Err(error) => Some((Err(error), None)),
+        }
+    })
+}
+
+/// The fields of a CSV line. Fields may be quoted, with `""` standing for a
+/// quote inside them; `None` when a quote is left open.
+pub fn split_csv(line: &str) -> Option<Vec<String>> {
+    let mut fields = Vec::new();
+    let mut field = String::new();
+    let mut quoted = false;
+    let mut chars = line.chars().peekable();
+    while let Some(c) = chars.next() {
+        match (c, quoted) {
+            ('"', true) if chars.peek() == Some(&'"') => {
+                chars.next();
+                field.push('"');
+            }
+            ('"', true) => quoted = false,
+            ('"', false) if field.is_empty() => quoted = true,
+            (',', false) => fields.push(std::mem::take(&mut field)),
+            (c, _) => field.push(c),
+        }
+    }
+    if quoted {
+        return None;
+    }
+    fields.push(field);
+    Some(fields)
+}
@@ -0,0 +1,327 @@
+use std::collections::HashMap;
+
+use aws_sdk_s3::operation::create_multipart_upload::builders::CreateMultipartUploadFluentBuilder;
+use aws_sdk_s3::operation::put_object::builders::PutObjectFluentBuilder;
+use aws_sdk_s3::types::{
+    ChecksumAlgorithm, ChecksumType, ObjectCannedAcl, ServerSideEncryption, StorageClass,
+};
+use pyo3::exceptions::PyValueError;
+use pyo3::{pyclass, pymethods, PyResult};
+
+/// Headers and user metadata stored with uploaded objects.
+///
+/// Options given to a batch apply to all its items; options given with an item
+/// override them field by field, and their metadata is added to the batch's.
+#[pyclass(frozen, module = "robinzhon")]
+#[derive(Debug, Clone)]
+pub struct UploadOptions {
+    /// User metadata, stored as `x-amz-meta-*` headers.
+    #[pyo3(get)]
+    pub metadata: Option<HashMap<String, String>>,
+    #[pyo3(get)]
+    pub content_type: Option<String>,
+    #[pyo3(get)]
+    pub content_encoding: Option<String>,
+    #[pyo3(get)]
+    pub content_disposition: Option<String>,
+    #[pyo3(get)]
+    pub cache_control: Option<String>,
+    /// Storage class such as "STANDARD_IA" or "GLACIER"; the bucket's default,
+    /// usually STANDARD, when `None`.
+    #[pyo3(get)]
+    pub storage_class: Option<String>,
+    /// Server-side encryption: "AES256" (SSE-S3), "aws:kms" or "aws:kms:dsse";
+    /// the bucket's default encryption when `None`.
+    #[pyo3(get)]
+    pub sse: Option<String>,
+    /// KMS key to encrypt with under "aws:kms"; the AWS managed key when `None`.
+    #[pyo3(get)]
+    pub kms_key_id: Option<String>,
+    /// Canned ACL such as "bucket-owner-full-control", which uploads into a
+    /// bucket of another account usually need.
+    #[pyo3(get)]
+    pub acl: Option<String>,
+    /// Account ID the bucket must belong to; uploads to a bucket owned by any
+    /// other account fail with "403 Access Denied" instead.
+    #[pyo3(get)]
+    pub expected_bucket_owner: Option<String>,
+    /// Checksum S3 computes and stores with the object: "CRC32", "CRC32C",
+    /// "CRC64NVME", "SHA1" or "SHA256"; the SDK's default, CRC32, when `None`.
+    #[pyo3(get)]
+    pub checksum_algorithm: Option<String>,
+    /// How multipart uploads are checksummed: "FULL_OBJECT" for a checksum of
+    /// the whole body, or "COMPOSITE" for a checksum of the part checksums.
+    #[pyo3(get)]
+    pub checksum_type: Option<String>,
+    /// Whether to infer the Content-Type from the key's extension when none is
+    /// given.
+    #[pyo3(get)]
+    pub guess_content_type: bool,
+}
+
+impl Default for UploadOptions {
+    fn default() -> Self {
+        Self {
+            metadata: None,
+            content_type: None,
+            content_encoding: None,
+            content_disposition: None,
+            cache_control: None,
+            storage_class: None,
+            sse: None,
+            kms_key_id: None,
+            acl: None,
+            expected_bucket_owner: None,
+            checksum_algorithm: None,
+            checksum_type: None,
+            guess_content_type: true,
+        }
+    }
+}
+
+impl UploadOptions {
+    /// These options, overridden by those of a single item.
+    pub fn merged(&self, item: &Self) -> Self {
+        let metadata = match (&self.metadata, &item.metadata) {
+            (Some(batch), Some(item)) => {
+                let mut metadata = batch.clone();
+                metadata.extend(item.iter().map(|(k, v)| (k.clone(), v.clone())));
+                Some(metadata)
+            }
+            (batch, item) => item.clone().or_else(|| batch.clone()),
+        };
+
+        Self {
+            metadata,
+            content_type: item.content_type.clone().or(self.content_type.clone()),
+            content_encoding: item
+                .content_encoding
+                .clone()
+                .or(self.content_encoding.clone()),
+            content_disposition: item
+                .content_disposition
+                .clone()
+                .or(self.content_disposition.clone()),
+            cache_control: item.cache_control.clone().or(self.cache_control.clone()),
+            storage_class: item.storage_class.clone().or(self.storage_class.clone()),
+            // The key belongs to the encryption it was given with.
+            sse: item.sse.clone().or(self.sse.clone()),
+            kms_key_id: match &item.sse {
+                Some(_) => item.kms_key_id.clone(),
+                None => item.kms_key_id.clone().or(self.kms_key_id.clone()),
+            },
+            acl: item.acl.clone().or(self.acl.clone()),
+            expected_bucket_owner: item
+                .expected_bucket_owner
+                .clone()
+                .or(self.expected_bucket_owner.clone()),
+            // The type belongs to the algorithm it was given with.
+            checksum_algorithm: item
+                .checksum_algorithm
+                .clone()
+                .or(self.checksum_algorithm.clone()),
+            checksum_type: match &item.checksum_algorithm {
+                Some(_) => item.checksum_type.clone(),
+                None => item.checksum_type.clone().or(self.checksum_type.clone()),
+            },
+            guess_content_type: self.guess_content_type && item.guess_content_type,
+        }
+    }
+
+    /// Options that only request server-side encryption.
+    pub fn encrypted(sse: Option<String>, kms_key_id: Option<String>) -> PyResult<Self> {
+        Ok(Self {
+            sse: checked_sse(sse, kms_key_id.as_deref())?,
+            kms_key_id,
+            ..Self::default()
+        })
+    }
+
+    /// The Content-Type of `object_key`: the one given, or the one its extension
+    /// suggests.
+    fn content_type_of(&self, object_key: &str) -> Option<String> {
+        self.content_type.clone().or_else(|| {
+            self.guess_content_type
+                .then(|| mime_guess::from_path(object_key).first_raw())
+                .flatten()
+                .map(str::to_string)
+        })
+    }
+
+    pub fn apply_to_put(
+        &self,
+        request: PutObjectFluentBuilder,
+        object_key: &str,
+    ) -> PutObjectFluentBuilder {
+        request
+            .set_metadata(self.metadata.clone())
+            .set_content_type(self.content_type_of(object_key))
+            .set_content_encoding(self.content_encoding.clone())
+            .set_content_disposition(self.content_disposition.clone())
+            .set_cache_control(self.cache_control.clone())
+            .set_storage_class(self.storage_class.as_deref().map(StorageClass::from))
+            .set_server_side_encryption(self.sse.as_deref().map(ServerSideEncryption::from))
+            .set_ssekms_key_id(self.kms_key_id.clone())
+            .set_acl(self.acl.as_deref().map(ObjectCannedAcl::from))
+            .set_expected_bucket_owner(self.expected_bucket_owner.clone())
+            .set_checksum_algorithm(self.checksum_algorithm())
+    }
+
+    pub fn apply_to_multipart(
+        &self,
+        request: CreateMultipartUploadFluentBuilder,
+        object_key: &str,
+    ) -> CreateMultipartUploadFluentBuilder {
+        request
+            .set_metadata(self.metadata.clone())
+            .set_content_type(self.content_type_of(object_key))
+            .set_content_encoding(self.content_encoding.clone())
+            .set_content_disposition(self.content_disposition.clone())
+            .set_cache_control(self.cache_control.clone())
+            .set_storage_class(self.storage_class.as_deref().map(StorageClass::from))
+            .set_server_side_encryption(self.sse.as_deref().map(ServerSideEncryption::from))
+            .set_ssekms_key_id(self.kms_key_id.clone())
+            .set_acl(self.acl.as_deref().map(ObjectCannedAcl::from))
+            .set_expected_bucket_owner(self.expected_bucket_owner.clone())
+            .set_checksum_algorithm(self.checksum_algorithm())
+            .set_checksum_type(self.checksum_type.as_deref().map(ChecksumType::from))
+    }
+
+    /// The checksum algorithm parts and single PUTs are sent with.
+    pub fn checksum_algorithm(&self) -> Option<ChecksumAlgorithm> {
+        self.checksum_algorithm
+            .as_deref()
+            .map(ChecksumAlgorithm::from)
+    }
+}
+
+/// Checks the server-side encryption requested. A KMS key implies "aws:kms", and
+/// makes no sense with any other encryption.
+fn checked_sse(sse: Option<String>, kms_key_id: Option<&str>) -> PyResult<Option<String>> {
+    match (sse, kms_key_id) {
+        (Some(sse), _) if !ServerSideEncryption::values().contains(&sse.as_str()) => {
+            Err(PyValueError::new_err(format!(
+                "Invalid server-side encryption '{}', expected one of {}",
+                sse,
+                ServerSideEncryption::values().join(", ")
+            )))
+        }
+        (Some(sse), Some(_)) if !sse.starts_with("aws:kms") => Err(PyValueError::new_err(format!(
+            "kms_key_id requires sse='aws:kms', not '{}'",
+            sse
+        ))),
+        (None, Some(_)) => Ok(Some(ServerSideEncryption::AwsKms.as_str().to_string())),
+        (sse, _) => Ok(sse),
+    }
+}
+
+/// Checks the checksum requested. A checksum type only applies to the algorithm
+/// it is given with.
+fn checked_checksum(algorithm: Option<&str>, checksum_type: Option<&str>) -> PyResult<()> {
+    if let Some(algorithm) = algorithm {
+        if !ChecksumAlgorithm::values().contains(&algorithm) {
+            return Err(PyValueError::new_err(format!(
+                "Invalid checksum algorithm '{}', expected one of {}",
+                algorithm,
+                ChecksumAlgorithm::values().join(", ")
+            )));
+        }
+    }
+    match (algorithm, checksum_type) {
+        (_, Some(checksum_type)) if !ChecksumType::values().contains(&checksum_type) => {
+            Err(PyValueError::new_err(format!(
+                "Invalid checksum type '{}', expected one of {}",
+                checksum_type,
+                ChecksumType::values().join(", ")
+            )))
+        }
+        (None, Some(_)) => Err(PyValueError::new_err(
+            "checksum_type requires checksum_algorithm",
+        )),
+        _ => Ok(()),
+    }
+}
+
+#[pymethods]
+impl UploadOptions {
+    #[new]
+    #[pyo3(signature = (metadata=None, content_type=None, content_encoding=None, content_disposition=None, cache_control=None, storage_class=None, sse=None, kms_key_id=None, acl=None, expected_bucket_owner=None, guess_content_type=true, checksum_algorithm=None, checksum_type=None))]
+    #[allow(clippy::too_many_arguments)]
+    fn new(
+        metadata: Option<HashMap<String, String>>,
+        content_type: Option<String>,
+        content_encoding: Option<String>,
+        content_disposition: Option<String>,
+        cache_control: Option<String>,
+        storage_class: Option<String>,
+        sse: Option<String>,
+        kms_key_id: Option<String>,
+        acl: Option<String>,
+        expected_bucket_owner: Option<String>,
+        guess_content_type: bool,
+        checksum_algorithm: Option<String>,
+        checksum_type: Option<String>,
+    ) -> PyResult<Self> {
+        if let Some(class) = &storage_class {
+            if !StorageClass::values().contains(&class.as_str()) {
+                return Err(PyValueError::new_err(format!(
+                    "Invalid storage class '{}', expected one of {}",
+                    class,
+                    StorageClass::values().join(", ")
+                )));
+            }
+        }
+        if let Some(acl) = &acl {
+            if !ObjectCannedAcl::values().contains(&acl.as_str()) {
+                return Err(PyValueError::new_err(format!(
+                    "Invalid ACL '{}', expected one of {}",
+                    acl,
+                    ObjectCannedAcl::values().join(", ")
+                )));
+            }
+        }
+        checked_checksum(checksum_algorithm.as_deref(), checksum_type.as_deref())?;
+
+        Ok(Self {
+            metadata,
+            content_type,
+            content_encoding,
+            content_disposition,
+            cache_control,
+            storage_class,
+            sse: checked_sse(sse, kms_key_id.as_deref())?,
+            kms_key_id,
+            acl,
+            expected_bucket_owner,
+            checksum_algorithm,
+            checksum_type,
+            guess_content_type,
+        })
+    }
+
+    fn __repr__(&self) -> String {
+        let optional = |value: &Option<String>| {
+            value
+                .as_deref()
+                .map_or_else(|| "None".to_string(), |value| format!("{:?}", value))
+        };
+        format!(
+            "UploadOptions(metadata={}, content_type={}, content_encoding={}, content_disposition={}, cache_control={}, storage_class={}, sse={}, kms_key_id={}, acl={}, expected_bucket_owner={}, checksum_algorithm={}, checksum_type={}, guess_content_type={})",
+            self.metadata
+                .as_ref()
+                .map_or_else(|| "None".to_string(), |metadata| format!("{:?}", metadata)),
+            optional(&self.content_type),
+            optional(&self.content_encoding),
+            optional(&self.content_disposition),
+            optional(&self.cache_control),
+            optional(&self.storage_class),
+            optional(&self.sse),
+            optional(&self.kms_key_id),
+            optional(&self.acl),
+            optional(&self.expected_bucket_owner),
+            optional(&self.checksum_algorithm),
+            optional(&self.checksum_type),
+            if self.guess_content_type { "True" } else { "False" }
+        )
+    }
+}
@@ -1,4 +1,23 @@
-from robinzhon import S3Downloader, Results
+import _thread
+import asyncio
+import base64
+import csv
+import datetime
+import hashlib
+import http.server
+import itertools
+import json
+import logging
+import os
+import pickle
+import threading
+import time
+import urllib.parse
+
+import pytest
+
+import robinzhon
+from robinzhon import FailedItem, RetryPolicy, S3Downloader, Results, TransferProgress
 
 
 def test_basic_download(mocker):
@@ -139,3 +158,2746 @@ def test_download_results_functionality():
     assert failure_results.has_failures()
     assert failure_results.total_count() == 2
     assert failure_results.success_rate() == 0.0
+
+
+def test_custom_endpoint(mocker):
+    """Test constructing a downloader against an S3-compatible endpoint."""
+
+    mock_download = mocker.patch("robinzhon.S3Downloader.download_file")
+    mock_download.return_value = "./test-object-key"
+
+    client = S3Downloader(
+        "us-east-1",
+        endpoint_url="http://localhost:9000",
+        force_path_style=True,
+    )
+    download = client.download_file(
+        "test-bucket", "test-object-key", "./test-object-key"
+    )
+
+    assert download == "./test-object-key"
+
+
+def test_async_download_failure():
+    """Test that async downloads return awaitables that raise on failure."""
+
+    client = S3Downloader("us-east-1", endpoint_url="http://127.0.0.1:1")
+
+    async def run():
+        await client.download_file_async(
+            "test-bucket", "test-object-key", "./test-object-key"
+        )
+
+    try:
+        asyncio.run(run())
+        assert False, "Expected exception was not raised"
+    except RuntimeError as e:
+        assert "test-object-key" in str(e)
+
+
+def test_transfer_progress_defaults():
+    """Test a fresh progress object before any batch runs."""
+
+    progress = TransferProgress()
+
+    assert progress.files_total == 0
+    assert progress.files_completed == 0
+    assert progress.files_failed == 0
+    assert progress.bytes_transferred == 0
+    assert progress.elapsed_seconds == 0.0
+    assert progress.throughput == 0.0
+    assert not progress.is_finished
+
+
+def test_progress_on_failed_batch(tmp_path):
+    """Test that a batch updates the progress it was given."""
+
+    progress = TransferProgress()
+    client = S3Downloader("us-east-1", endpoint_url="http://127.0.0.1:1")
+
+    results = client.download_multiple_files(
+        "test-bucket", ["a.txt", "b.txt"], str(tmp_path), progress=progress
+    )
+
+    assert results.total_count() == 2
+    assert progress.files_total == 2
+    assert progress.files_failed == 2
+    assert progress.is_finished
+
+
+def test_retry_failed(tmp_path):
+    """Test retrying only the failed items of a batch."""
+
+    client = S3Downloader("us-east-1", endpoint_url="http://127.0.0.1:1")
+
+    results = client.download_multiple_files(
+        "test-bucket", ["a.txt", "b.txt"], str(tmp_path)
+    )
+    retried = client.retry_failed(results)
+
+    assert retried is results
+    assert sorted(results.failed_keys) == ["a.txt", "b.txt"]
+    assert results.successful == []
+
+    try:
+        client.retry_failed(Results([], ["a.txt"]))
+        assert False, "Expected exception was not raised"
+    except ValueError as e:
+        assert "retry" in str(e)
+
+
+def test_failed_item_details(tmp_path):
+    """Test that failures carry their error details."""
+
+    client = S3Downloader(
+        "us-east-1",
+        endpoint_url="http://127.0.0.1:1",
+        retry_policy=RetryPolicy(max_attempts=1),
+    )
+
+    results = client.download_multiple_files("test-bucket", ["a.txt"], str(tmp_path))
+
+    assert results.failed_keys == ["a.txt"]
+    item = results.failed[0]
+    assert isinstance(item, FailedItem)
+    assert item.key == "a.txt"
+    assert "a.txt" in item.error_message
+    assert item.http_status is None
+    assert isinstance(item.retryable, bool)
+
+    legacy = Results(successful=[], failed=["b.txt", item])
+    assert legacy.failed_keys == ["b.txt", "a.txt"]
+    assert legacy.failed[0].error_code is None
+
+
+def test_fail_fast_stops_batch(tmp_path):
+    """Test that a batch stops dispatching items once it reaches its failure limit."""
+
+    client = S3Downloader(
+        "us-east-1",
+        max_concurrent_downloads=1,
+        endpoint_url="http://127.0.0.1:1",
+        retry_policy=RetryPolicy(max_attempts=1),
+    )
+    downloads = [(f"{i}.csv", str(tmp_path / f"{i}.csv")) for i in range(50)]
+
+    with pytest.raises(robinzhon.BatchAbortedError) as aborted:
+        client.download_multiple_files_with_paths(
+            "test-bucket", downloads, fail_fast=True
+        )
+    assert "0.csv" in str(aborted.value)
+    assert 1 <= len(aborted.value.partial_results.failed) < 50
+
+    with pytest.raises(robinzhon.BatchAbortedError) as aborted:
+        client.download_multiple_files_with_paths(
+            "test-bucket", downloads, max_failures=3
+        )
+    assert 3 <= len(aborted.value.partial_results.failed) < 50
+
+    for limits in ({"max_failures": 0}, {"fail_fast": True, "max_failures": 2}):
+        with pytest.raises(ValueError):
+            client.download_multiple_files_with_paths("test-bucket", downloads, **limits)
+
+
+def test_fail_fast_stops_key_batches():
+    """Test that the bytes, HEAD and restore batches stop at their failure limit."""
+
+    client = S3Downloader(
+        "us-east-1",
+        max_concurrent_downloads=1,
+        endpoint_url="http://127.0.0.1:1",
+        retry_policy=RetryPolicy(max_attempts=1),
+    )
+    keys = [f"{i}.csv" for i in range(50)]
+
+    for batch in [
+        client.download_multiple_bytes,
+        client.head_multiple,
+        client.restore_objects,
+    ]:
+        progress = TransferProgress()
+        with pytest.raises(robinzhon.BatchAbortedError, match=r"first: 0\.csv"):
+            batch("test-bucket", iter(keys), progress=progress, fail_fast=True)
+        assert 1 <= progress.files_failed < 50
+
+        with pytest.raises(robinzhon.BatchAbortedError):
+            batch("test-bucket", keys, max_failures=3)
+
+        with pytest.raises(ValueError):
+            batch("test-bucket", keys, max_failures=0)
+
+
+def test_raise_on_failure(tmp_path):
+    """Test that a batch with failed items raises with its results when asked to."""
+
+    client = S3Downloader(
+        "us-east-1",
+        endpoint_url="http://127.0.0.1:1",
+        retry_policy=RetryPolicy(max_attempts=1),
+    )
+    existing = tmp_path / "b.csv"
+    existing.write_text("kept")
+    downloads = [("a.csv", str(tmp_path / "a.csv")), ("b.csv", str(existing))]
+
+    with pytest.raises(robinzhon.PartialFailureError) as error:
+        client.download_multiple_files_with_paths(
+            "test-bucket", downloads, overwrite="never", raise_on_failure=True
+        )
+    results = error.value.results
+    assert results.failed_keys == ["a.csv"]
+    assert results.skipped == [str(existing)]
+    assert "1 of 2 items failed" in str(error.value)
+
+    with pytest.raises(robinzhon.PartialFailureError):
+        client.retry_failed(results, raise_on_failure=True)
+
+    results = client.download_multiple_files_with_paths(
+        "test-bucket", downloads[1:], overwrite="never", raise_on_failure=True
+    )
+    assert results.skipped == [str(existing)]
+
+
+def test_exception_hierarchy():
+    """Test the typed exceptions and their common base."""
+
+    for error in (
+        robinzhon.NoSuchKeyError,
+        robinzhon.AccessDeniedError,
+        robinzhon.BucketNotFoundError,
+        robinzhon.PreconditionFailedError,
+        robinzhon.TimeoutError,
+        robinzhon.BatchAbortedError,
+        robinzhon.PartialFailureError,
+        robinzhon.InsufficientSpaceError,
+    ):
+        assert issubclass(error, robinzhon.RobinzhonError)
+    assert issubclass(robinzhon.RobinzhonError, RuntimeError)
+
+
+def test_typed_download_failure(mocker):
+    """Test catching a typed download failure."""
+
+    mock_download = mocker.patch("robinzhon.S3Downloader.download_file")
+    mock_download.side_effect = robinzhon.NoSuchKeyError("missing")
+
+    client = S3Downloader("us-east-1")
+
+    try:
+        client.download_file("test-bucket", "missing.txt", "./missing.txt")
+        assert False, "Expected exception was not raised"
+    except robinzhon.NoSuchKeyError as e:
+        assert isinstance(e, RuntimeError)
+
+
+def test_failures_are_logged(tmp_path):
+    """Test that batch failures go through the robinzhon logger."""
+
+    records = []
+    handler = logging.Handler()
+    handler.emit = records.append
+    logger = logging.getLogger("robinzhon")
+    logger.addHandler(handler)
+
+    try:
+        client = S3Downloader(
+            "us-east-1",
+            endpoint_url="http://127.0.0.1:1",
+            retry_policy=RetryPolicy(max_attempts=1),
+        )
+        client.download_multiple_files("test-bucket", ["a.txt"], str(tmp_path))
+    finally:
+        logger.removeHandler(handler)
+
+    warnings = [r for r in records if r.levelno == logging.WARNING]
+    assert warnings
+    assert all(r.name.startswith("robinzhon") for r in warnings)
+    assert any("a.txt" in r.getMessage() for r in warnings)
+
+
+def test_part_settings():
+    """Test configuring ranged downloads."""
+
+    assert S3Downloader("us-east-1", part_size=1024, part_concurrency=4) is not None
+    assert S3Downloader("us-east-1", part_concurrency=1) is not None
+
+    assert S3Downloader("us-east-1", write_buffer_size=1024 * 1024) is not None
+    assert S3Downloader("us-east-1", verify_checksum=True) is not None
+
+    for kwargs in (
+        {"part_size": 0},
+        {"part_concurrency": 0},
+        {"write_buffer_size": 0},
+    ):
+        try:
+            S3Downloader("us-east-1", **kwargs)
+            assert False, "Expected exception was not raised"
+        except ValueError as e:
+            assert next(iter(kwargs)) in str(e)
+
+
+def test_download_bytes(mocker):
+    """Test downloading an object into memory."""
+
+    mock_download = mocker.patch("robinzhon.S3Downloader.download_bytes")
+    mock_download.return_value = b"content"
+
+    client = S3Downloader("us-east-1")
+
+    assert client.download_bytes("test-bucket", "key.txt") == b"content"
+    mock_download.assert_called_once_with("test-bucket", "key.txt")
+
+
+def test_download_multiple_bytes_skips_failures():
+    """Test that keys which fail are left out of the returned dict."""
+
+    client = S3Downloader(
+        "us-east-1",
+        endpoint_url="http://127.0.0.1:1",
+        retry_policy=RetryPolicy(max_attempts=1),
+    )
+    progress = TransferProgress()
+
+    contents = client.download_multiple_bytes(
+        "test-bucket", ["a.txt", "b.txt"], progress=progress
+    )
+
+    assert contents == {}
+    assert progress.files_failed == 2
+
+
+def test_stream_failure():
+    """Test that stream errors surface when iterating."""
+
+    client = S3Downloader(
+        "us-east-1",
+        endpoint_url="http://127.0.0.1:1",
+        retry_policy=RetryPolicy(max_attempts=1),
+    )
+
+    stream = client.stream("test-bucket", "a.txt", chunk_size=1024)
+    try:
+        next(stream)
+        assert False, "Expected exception was not raised"
+    except robinzhon.RobinzhonError as e:
+        assert "a.txt" in str(e)
+    assert list(stream) == []
+
+    try:
+        client.stream("test-bucket", "a.txt", chunk_size=0)
+        assert False, "Expected exception was not raised"
+    except ValueError as e:
+        assert "chunk_size" in str(e)
+
+
+def test_select_failure():
+    """Test that S3 Select validates its formats and surfaces errors when iterated."""
+
+    client = S3Downloader(
+        "us-east-1",
+        endpoint_url="http://127.0.0.1:1",
+        retry_policy=RetryPolicy(max_attempts=1),
+    )
+
+    sql = "SELECT s.name FROM S3Object s"
+    with client.select("test-bucket", "data.csv.gz", sql, compression="gzip") as records:
+        try:
+            next(records)
+            assert False, "Expected exception was not raised"
+        except robinzhon.RobinzhonError as e:
+            assert "data.csv.gz" in str(e)
+        assert list(records) == []
+
+    for kwargs in (
+        {"input_format": "xml"},
+        {"output_format": "parquet"},
+        {"compression": "zstd"},
+    ):
+        try:
+            client.select("test-bucket", "data.csv", sql, **kwargs)
+            assert False, "Expected exception was not raised"
+        except ValueError:
+            pass
+
+
+def test_list_objects_failure():
+    """Test that listing is lazy and surfaces errors when iterated."""
+
+    client = S3Downloader(
+        "us-east-1",
+        endpoint_url="http://127.0.0.1:1",
+        retry_policy=RetryPolicy(max_attempts=1),
+    )
+
+    listing = client.list_objects("test-bucket", prefix="data/", delimiter="/")
+    try:
+        next(listing)
+        assert False, "Expected exception was not raised"
+    except robinzhon.RobinzhonError as e:
+        assert "test-bucket" in str(e)
+
+
+def test_listing_filters(tmp_path):
+    """Test that listing filters are validated upfront and applied lazily."""
+
+    client = S3Downloader(
+        "us-east-1",
+        endpoint_url="http://127.0.0.1:1",
+        retry_policy=RetryPolicy(max_attempts=1),
+    )
+
+    week_ago = datetime.datetime.now() - datetime.timedelta(days=7)
+    listing = client.list_objects(
+        "test-bucket",
+        min_size=1,
+        max_size=1024,
+        modified_after=week_ago,
+        key_pattern=r"^data/.*\.csv$",
+    )
+    try:
+        next(listing)
+        assert False, "Expected exception was not raised"
+    except robinzhon.RobinzhonError as e:
+        assert "test-bucket" in str(e)
+
+    for kwargs in (
+        {"min_size": 10, "max_size": 1},
+        {"modified_after": week_ago, "modified_before": week_ago},
+        {"key_pattern": "data/(unclosed"},
+    ):
+        try:
+            client.list_objects("test-bucket", **kwargs)
+            assert False, "Expected exception was not raised"
+        except ValueError:
+            pass
+        try:
+            client.download_prefix("test-bucket", "data/", str(tmp_path), **kwargs)
+            assert False, "Expected exception was not raised"
+        except ValueError:
+            pass
+    assert list(tmp_path.iterdir()) == []
+
+
+def test_download_prefix_listing_failure(tmp_path):
+    """Test that download_prefix raises when the prefix cannot be listed."""
+
+    client = S3Downloader(
+        "us-east-1",
+        endpoint_url="http://127.0.0.1:1",
+        retry_policy=RetryPolicy(max_attempts=1),
+    )
+
+    try:
+        client.download_prefix("test-bucket", "data/", str(tmp_path))
+        assert False, "Expected exception was not raised"
+    except robinzhon.RobinzhonError as e:
+        assert "test-bucket" in str(e)
+    assert list(tmp_path.iterdir()) == []
+
+
+def test_download_latest(tmp_path):
+    """Test that download_latest validates n and raises when the prefix cannot be listed."""
+
+    client = S3Downloader(
+        "us-east-1",
+        endpoint_url="http://127.0.0.1:1",
+        retry_policy=RetryPolicy(max_attempts=1),
+    )
+
+    try:
+        client.download_latest("test-bucket", "backups/", 0, str(tmp_path))
+        assert False, "Expected exception was not raised"
+    except ValueError as e:
+        assert "n must be greater than zero" in str(e)
+
+    try:
+        client.download_latest("test-bucket", "backups/", 2, str(tmp_path))
+        assert False, "Expected exception was not raised"
+    except robinzhon.RobinzhonError as e:
+        assert "test-bucket" in str(e)
+
+    async def latest():
+        return await client.download_latest_async(
+            "test-bucket", "backups/", 2, str(tmp_path), include=["*.tar.gz"]
+        )
+
+    try:
+        asyncio.run(latest())
+        assert False, "Expected exception was not raised"
+    except robinzhon.RobinzhonError as e:
+        assert "test-bucket" in str(e)
+    assert list(tmp_path.iterdir()) == []
+
+
+def test_checksum_manifest(tmp_path):
+    """Test that SHA-256 manifests are written for batches and checked locally."""
+
+    client = S3Downloader(
+        "us-east-1",
+        endpoint_url="http://127.0.0.1:1",
+        retry_policy=RetryPolicy(max_attempts=1),
+    )
+
+    try:
+        client.download_multiple_files(
+            "test-bucket", ["a.txt"], str(tmp_path), expected_sha256={"a.txt": "abc"}
+        )
+        assert False, "Expected exception was not raised"
+    except ValueError as e:
+        assert "Invalid SHA-256 digest 'abc' for 'a.txt'" in str(e)
+
+    manifest = tmp_path / "SHA256SUMS"
+    results = client.download_multiple_files(
+        "test-bucket", ["a.txt"], str(tmp_path / "out"), checksum_manifest=str(manifest)
+    )
+    assert [item.key for item in results.failed] == ["a.txt"]
+    assert manifest.read_text() == ""
+
+    hello = tmp_path / "hello.txt"
+    hello.write_bytes(b"hello")
+    changed = tmp_path / "changed.txt"
+    changed.write_bytes(b"changed")
+    digest = "2cf24dba5fb0a30e26e83b2ac5b9e29e1b161e5c1fa7425e73043362938b9824"
+    manifest.write_text(
+        f"{digest}  {hello}\n{digest.upper()} *{changed}\n\n{digest}  {tmp_path / 'gone'}\n"
+    )
+    results = client.verify_against_manifest(str(manifest))
+    assert results.successful == [str(hello)]
+    failed = {item.key: item.error_message for item in results.failed}
+    assert "SHA-256 mismatch" in failed[str(changed)]
+    assert "Failed to read file" in failed[str(tmp_path / "gone")]
+
+    async def verify():
+        return await client.verify_against_manifest_async(str(manifest))
+
+    assert asyncio.run(verify()).successful == [str(hello)]
+
+    manifest.write_text("not a digest\n")
+    try:
+        client.verify_against_manifest(str(manifest))
+        assert False, "Expected exception was not raised"
+    except ValueError as e:
+        assert "Invalid line 1" in str(e)
+
+
+def test_download_prefix_invalid_glob(tmp_path):
+    """Test that invalid glob patterns are rejected before any request."""
+
+    client = S3Downloader("us-east-1")
+
+    try:
+        client.download_prefix(
+            "test-bucket", "data/", str(tmp_path), include=["data[.csv"]
+        )
+        assert False, "Expected exception was not raised"
+    except ValueError as e:
+        assert "data[.csv" in str(e)
+
+
+def test_sync_down_listing_failure(tmp_path):
+    """Test that sync_down raises before touching the local directory."""
+
+    client = S3Downloader(
+        "us-east-1",
+        endpoint_url="http://127.0.0.1:1",
+        retry_policy=RetryPolicy(max_attempts=1),
+    )
+    local_dir = tmp_path / "mirror"
+
+    try:
+        client.sync_down("test-bucket", "site/", str(local_dir), delete=True)
+        assert False, "Expected exception was not raised"
+    except robinzhon.RobinzhonError as e:
+        assert "test-bucket" in str(e)
+    assert not local_dir.exists()
+
+
+def test_open_failure():
+    """Test that open fails eagerly when the object cannot be reached."""
+
+    client = S3Downloader(
+        "us-east-1",
+        endpoint_url="http://127.0.0.1:1",
+        retry_policy=RetryPolicy(max_attempts=1),
+    )
+
+    try:
+        client.open("test-bucket", "a.txt")
+        assert False, "Expected exception was not raised"
+    except robinzhon.RobinzhonError as e:
+        assert "a.txt" in str(e)
+
+    try:
+        client.open("test-bucket", "a.txt", buffer_size=0)
+        assert False, "Expected exception was not raised"
+    except ValueError as e:
+        assert "buffer_size" in str(e)
+
+
+def test_overwrite_never_skips_existing_files(tmp_path):
+    """Test that overwrite="never" keeps existing files without any request."""
+
+    client = S3Downloader(
+        "us-east-1",
+        endpoint_url="http://127.0.0.1:1",
+        retry_policy=RetryPolicy(max_attempts=1),
+    )
+    (tmp_path / "a.txt").write_bytes(b"local")
+
+    results = client.download_multiple_files(
+        "test-bucket", ["data/a.txt", "data/b.txt"], str(tmp_path), overwrite="never"
+    )
+
+    assert results.skipped == [str(tmp_path / "a.txt")]
+    assert results.failed_keys == ["data/b.txt"]
+    assert results.total_count() == 2
+    assert results.success_rate() == 0.5
+    assert (tmp_path / "a.txt").read_bytes() == b"local"
+
+
+def test_results_as_dict(tmp_path):
+    """Test that results map the key of each item kept or downloaded to its local path."""
+
+    client = S3Downloader(
+        "us-east-1",
+        endpoint_url="http://127.0.0.1:1",
+        retry_policy=RetryPolicy(max_attempts=1),
+    )
+    (tmp_path / "a.txt").write_bytes(b"local")
+
+    results = client.download_multiple_files_with_paths(
+        "test-bucket",
+        [("data/a.txt", str(tmp_path / "a.txt")), ("data/b.txt", str(tmp_path / "b.txt"))],
+        overwrite="never",
+    )
+
+    assert results.as_dict() == {"data/a.txt": str(tmp_path / "a.txt")}
+    assert client.retry_failed(results).as_dict() == {"data/a.txt": str(tmp_path / "a.txt")}
+    assert Results(["a.txt"], []).as_dict() == {}
+
+
+def test_results_collection(tmp_path):
+    """Test that results can be sized, iterated, indexed and searched like a collection."""
+
+    client = S3Downloader(
+        "us-east-1",
+        endpoint_url="http://127.0.0.1:1",
+        retry_policy=RetryPolicy(max_attempts=1),
+    )
+    (tmp_path / "a.txt").write_bytes(b"local")
+
+    results = client.download_multiple_files_with_paths(
+        "test-bucket",
+        [("data/a.txt", str(tmp_path / "a.txt")), ("data/b.txt", str(tmp_path / "b.txt"))],
+        overwrite="never",
+    )
+
+    assert len(results) == 2
+    skipped, failed = list(results)
+    assert (skipped.status, skipped.source, skipped.destination) == (
+        "skipped",
+        "data/a.txt",
+        str(tmp_path / "a.txt"),
+    )
+    assert skipped.error is None
+    assert (failed.status, failed.source, failed.destination) == (
+        "failed",
+        "data/b.txt",
+        str(tmp_path / "b.txt"),
+    )
+    assert failed.error.key == "data/b.txt"
+    assert results[-1].name == "data/b.txt"
+    assert "data/a.txt" in results and str(tmp_path / "b.txt") in results
+    assert "data/c.txt" not in results
+    try:
+        results[2]
+        assert False, "Should have raised IndexError"
+    except IndexError:
+        pass
+
+    legacy = Results(["x.txt"], ["y.txt"])
+    assert [(item.name, item.status) for item in legacy] == [
+        ("x.txt", "successful"),
+        ("y.txt", "failed"),
+    ]
+    assert legacy[0].source is None
+
+    # Indexing doesn't rebuild the items each time.
+    many = Results(["%d.txt" % i for i in range(20000)], [])
+    assert [many[i].name for i in range(len(many))] == [item.name for item in many]
+
+
+def test_results_statistics(tmp_path):
+    """Test that results time every item and the batch, counting no bytes for failures."""
+
+    client = S3Downloader(
+        "us-east-1",
+        endpoint_url="http://127.0.0.1:1",
+        retry_policy=RetryPolicy(max_attempts=1),
+    )
+    (tmp_path / "a.txt").write_bytes(b"local")
+    results = client.download_multiple_files_with_paths(
+        "test-bucket",
+        [("data/a.txt", str(tmp_path / "a.txt")), ("data/b.txt", str(tmp_path / "b.txt"))],
+        overwrite="never",
+    )
+
+    assert results.total_bytes == 0
+    assert results.elapsed_seconds > 0
+    assert results.throughput_mbps == 0.0
+    skipped, failed = results
+    assert (skipped.bytes, failed.bytes) == (0, 0)
+    assert 0 < failed.elapsed_seconds <= results.elapsed_seconds
+    assert (skipped.attempts, failed.attempts) == (0, 1)
+    assert failed.started_at < failed.finished_at <= time.time()
+    # Wall-clock timestamps near 1.7e9 only keep about a microsecond.
+    assert failed.finished_at - failed.started_at == pytest.approx(
+        failed.elapsed_seconds, abs=1e-3
+    )
+    stats = results.to_dict()["stats"]
+    assert set(stats) == {str(tmp_path / "a.txt"), "data/b.txt"}
+    assert stats["data/b.txt"]["attempts"] == 1
+    assert (
+        stats["data/b.txt"]["elapsed_seconds"],
+        stats["data/b.txt"]["started_at"],
+        stats["data/b.txt"]["finished_at"],
+    ) == (failed.elapsed_seconds, failed.started_at, failed.finished_at)
+    unpickled = pickle.loads(pickle.dumps(results))
+    assert unpickled.elapsed_seconds == results.elapsed_seconds
+    assert (
+        unpickled[1].attempts,
+        unpickled[1].elapsed_seconds,
+        unpickled[1].started_at,
+        unpickled[1].finished_at,
+    ) == (1, failed.elapsed_seconds, failed.started_at, failed.finished_at)
+
+    legacy = Results(["x.txt"], [])
+    assert (legacy.total_bytes, legacy.elapsed_seconds) == (0, 0.0)
+    assert legacy[0].bytes is None
+    assert (legacy[0].started_at, legacy[0].attempts) == (None, None)
+
+
+def test_iter_download_multiple_files(tmp_path):
+    """Test that iterated batches yield every item as it finishes, then their results."""
+
+    client = S3Downloader(
+        "us-east-1",
+        endpoint_url="http://127.0.0.1:1",
+        retry_policy=RetryPolicy(max_attempts=1),
+    )
+    keys = ["data/a.txt", "data/b.txt", "data/c.txt"]
+    with client.iter_download_multiple_files("test-bucket", keys, str(tmp_path)) as batch:
+        assert len(batch) == 3
+        assert batch.results is None
+        items = list(batch)
+    assert sorted(item.name for item in items) == keys
+    assert all(item.status == "failed" and item.attempts == 1 for item in items)
+    assert batch.results.failed_keys == [item.name for item in items]
+
+    batch = client.iter_download_multiple_files_with_paths(
+        "test-bucket", ((key, str(tmp_path / key)) for key in keys), fail_fast=True
+    )
+    with pytest.raises(TypeError):
+        len(batch)
+    with pytest.raises(robinzhon.BatchAbortedError):
+        for item in batch:
+            assert item.status == "failed"
+    assert list(batch) == []
+
+
+def test_results_in_input_order(tmp_path):
+    """Test that items can be listed in input order, retries keeping their position."""
+
+    client = S3Downloader(
+        "us-east-1",
+        endpoint_url="http://127.0.0.1:1",
+        retry_policy=RetryPolicy(max_attempts=1),
+        max_concurrent_downloads=8,
+    )
+    pairs = [(f"data/{n}.txt", str(tmp_path / f"{n}.txt")) for n in range(20)]
+    for n in range(0, 20, 3):
+        (tmp_path / f"{n}.txt").write_bytes(b"local")
+
+    results = client.download_multiple_files_with_paths("test-bucket", pairs, overwrite="never")
+    ordered = results.in_input_order()
+    assert [item.index for item in ordered] == list(range(20))
+    assert [item.source for item in ordered] == [key for key, _ in pairs]
+
+    retried = client.retry_failed(results).in_input_order()
+    assert [item.source for item in retried] == [key for key, _ in pairs]
+    assert Results(["x.txt"], []).in_input_order()[0].index is None
+
+
+def test_results_write_report(tmp_path):
+    """Test writing JSON and CSV reports of every item of a batch."""
+
+    client = S3Downloader(
+        "us-east-1",
+        endpoint_url="http://127.0.0.1:1",
+        retry_policy=RetryPolicy(max_attempts=1),
+    )
+    (tmp_path / "a.txt").write_bytes(b"local")
+    results = client.download_multiple_files_with_paths(
+        "test-bucket",
+        [("data/a.txt", str(tmp_path / "a.txt")), ("data/b,c.txt", str(tmp_path / "b.txt"))],
+        overwrite="never",
+    )
+
+    json_report = tmp_path / "report.json"
+    results.write_report(str(json_report))
+    report = json.loads(json_report.read_text())
+    assert report["summary"]["total"] == 2
+    assert report["summary"]["skipped"] == 1 and report["summary"]["failed"] == 1
+    assert [item["status"] for item in report["items"]] == ["skipped", "failed"]
+    failed = report["items"][1]
+    assert failed["item"] == "data/b,c.txt"
+    assert failed["error"] == results.failed[0].error_message
+    assert failed["http_status"] is None and "bytes" in failed
+
+    csv_report = tmp_path / "report.csv"
+    results.write_report(str(csv_report), format="csv")
+    with open(csv_report, newline="") as report_file:
+        rows = list(csv.DictReader(report_file))
+    assert [row["item"] for row in rows] == [str(tmp_path / "a.txt"), "data/b,c.txt"]
+    assert rows[1]["status"] == "failed" and rows[1]["error"]
+    assert rows[0]["error"] == ""
+
+    with pytest.raises(ValueError):
+        results.write_report(str(tmp_path / "report.xml"), format="xml")
+    with pytest.raises(robinzhon.RobinzhonError):
+        results.write_report(str(tmp_path / "missing" / "report.json"))
+
+
+def test_results_serialization(tmp_path):
+    """Test that results serialize to dicts and JSON, and pickle without their retry context."""
+
+    client = S3Downloader(
+        "us-east-1",
+        endpoint_url="http://127.0.0.1:1",
+        retry_policy=RetryPolicy(max_attempts=1),
+    )
+    (tmp_path / "a.txt").write_bytes(b"local")
+    results = client.download_multiple_files_with_paths(
+        "test-bucket",
+        [("data/a.txt", str(tmp_path / "a.txt")), ("data/b.txt", str(tmp_path / "b.txt"))],
+        overwrite="never",
+    )
+
+    as_dict = results.to_dict()
+    assert as_dict["skipped"] == [str(tmp_path / "a.txt")]
+    assert as_dict["destinations"] == {"data/a.txt": str(tmp_path / "a.txt")}
+    (failed,) = as_dict["failed"]
+    assert failed["key"] == "data/b.txt"
+    assert set(failed) == {
+        "key",
+        "error_message",
+        "error_code",
+        "http_status",
+        "retryable",
+        "request_id",
+        "extended_request_id",
+    }
+    assert failed["request_id"] is None
+    assert json.loads(results.to_json()) == as_dict
+    assert "\n  " in results.to_json(indent=2)
+
+    restored = pickle.loads(pickle.dumps(results))
+    assert restored.to_dict() == as_dict
+    assert restored.failed[0].error_message == results.failed[0].error_message
+    try:
+        client.retry_failed(restored)
+        assert False, "Should have raised ValueError"
+    except ValueError:
+        pass
+
+
+def test_invalid_overwrite_policy(tmp_path):
+    """Test that unknown overwrite policies are rejected."""
+
+    client = S3Downloader("us-east-1")
+
+    try:
+        client.download_multiple_files(
+            "test-bucket", ["a.txt"], str(tmp_path), overwrite="sometimes"
+        )
+        assert False, "Expected exception was not raised"
+    except ValueError as e:
+        assert "sometimes" in str(e)
+
+
+def test_download_multiple_files_name_collision(tmp_path):
+    """Test that keys flattened to the same file name don't overwrite each other."""
+
+    client = S3Downloader(
+        "us-east-1",
+        endpoint_url="http://127.0.0.1:1",
+        retry_policy=RetryPolicy(max_attempts=1),
+    )
+
+    results = client.download_multiple_files(
+        "test-bucket", ["a/data.csv", "b/data.csv"], str(tmp_path)
+    )
+    collisions = [item for item in results.failed if "would overwrite" in str(item)]
+
+    assert sorted(results.failed_keys) == ["a/data.csv", "b/data.csv"]
+    assert [item.key for item in collisions] == ["b/data.csv"]
+    assert "a/data.csv" in collisions[0].error_message
+
+    results = client.download_multiple_files(
+        "test-bucket",
+        ["a/data.csv", "b/data.csv"],
+        str(tmp_path),
+        preserve_structure=True,
+    )
+
+    assert not any("would overwrite" in str(item) for item in results.failed)
+    assert (tmp_path / "a").is_dir() and (tmp_path / "b").is_dir()
+
+
+def test_keys_escaping_base_directory_are_rejected(tmp_path):
+    """Test that keys resolving outside base_directory are never written."""
+
+    client = S3Downloader(
+        "us-east-1",
+        endpoint_url="http://127.0.0.1:1",
+        retry_policy=RetryPolicy(max_attempts=1),
+    )
+    base_directory = tmp_path / "downloads"
+    keys = ["../../escape.txt", "data/../../escape.txt", "/data/.."]
+
+    results = client.download_multiple_files(
+        "test-bucket", keys, str(base_directory), preserve_structure=True
+    )
+
+    rejected = [item.key for item in results.failed if "does not map" in str(item)]
+    assert sorted(rejected) == sorted(keys)
+    assert [path.name for path in tmp_path.iterdir()] == ["downloads"]
+    assert list(base_directory.iterdir()) == []
+
+
+def test_download_multiple_files_from_generator(tmp_path):
+    """Test that batch inputs may be generators, consumed until they raise."""
+
+    progress = TransferProgress()
+    client = S3Downloader("us-east-1", endpoint_url="http://127.0.0.1:1")
+
+    results = client.download_multiple_files(
+        "test-bucket", (f"{name}.txt" for name in "ab"), str(tmp_path), progress=progress
+    )
+
+    assert sorted(results.failed_keys) == ["a.txt", "b.txt"]
+    assert progress.files_total == 2
+
+    def broken_keys():
+        yield "a.txt"
+        raise RuntimeError("no more keys")
+
+    try:
+        client.download_multiple_files("test-bucket", broken_keys(), str(tmp_path))
+        assert False, "Expected exception was not raised"
+    except RuntimeError as e:
+        assert str(e) == "no more keys"
+        assert e.partial_results.failed_keys == ["a.txt"]
+
+    try:
+        client.download_multiple_files("test-bucket", "a.txt", str(tmp_path))
+        assert False, "Expected exception was not raised"
+    except TypeError as e:
+        assert "iterable" in str(e)
+
+
+def test_s3_uris(tmp_path):
+    """Test that objects can be addressed by s3:// URIs, and malformed ones are reported."""
+
+    client = S3Downloader(
+        "us-east-1",
+        endpoint_url="http://127.0.0.1:1",
+        retry_policy=RetryPolicy(max_attempts=1),
+    )
+
+    for uri in ["my-bucket/key", "s3://", "s3://my-bucket", "s3://my-bucket/"]:
+        try:
+            client.download_uri(uri, str(tmp_path / "file"))
+            assert False, "Expected exception was not raised"
+        except ValueError as e:
+            assert uri in str(e)
+
+    results = client.download_multiple_uris(
+        ["s3://bucket-a/data/a.txt", "s3://bucket-b/b.txt", "not-a-uri"], str(tmp_path)
+    )
+
+    assert sorted(results.failed_keys) == [
+        "not-a-uri",
+        "s3://bucket-a/data/a.txt",
+        "s3://bucket-b/b.txt",
+    ]
+    failures = {item.key: item for item in results.failed}
+    assert "Invalid S3 URI" in failures["not-a-uri"].error_message
+    assert "Invalid S3 URI" not in failures["s3://bucket-b/b.txt"].error_message
+
+    client.retry_failed(results)
+    assert len(results.failed) == 3
+
+
+def test_access_point_uris(tmp_path):
+    """Test that s3:// URIs may name an access point ARN, keeping the key after it intact."""
+
+    client = S3Downloader(
+        "us-east-1",
+        endpoint_url="http://127.0.0.1:1",
+        retry_policy=RetryPolicy(max_attempts=1),
+    )
+
+    for uri in [
+        "s3://arn:aws:s3:::my-bucket/key",
+        "s3://arn:aws:s3-outposts:us-west-2:123456789012:outpost/op-01ac5d28a6a232904/bucket/b/key",
+    ]:
+        try:
+            client.download_uri(uri, str(tmp_path / "file"))
+            assert False, "Expected exception was not raised"
+        except ValueError as e:
+            assert "access point ARN" in str(e)
+
+    uris = [
+        "s3://arn:aws:s3:us-west-2:123456789012:accesspoint/analytics/data/a.csv",
+        "s3://arn:aws:s3::123456789012:accesspoint/mfzwi23gnjvgw.mrap/data/b.csv",
+        "s3://arn:aws:s3-outposts:us-west-2:123456789012:outpost/op-01ac5d28a6a232904/accesspoint/ap/c.csv",
+    ]
+    results = client.download_multiple_uris(uris, str(tmp_path))
+    assert sorted(results.failed_keys) == sorted(uris)
+    for item in results.failed:
+        assert "Invalid S3 URI" not in item.error_message
+
+
+def test_download_multiple_objects(tmp_path):
+    """Test that a single batch can span buckets, reporting items by URI."""
+
+    progress = TransferProgress()
+    client = S3Downloader(
+        "us-east-1",
+        endpoint_url="http://127.0.0.1:1",
+        retry_policy=RetryPolicy(max_attempts=1),
+    )
+
+    results = client.download_multiple_objects(
+        [
+            ("bucket-a", "data.csv", str(tmp_path / "a.csv")),
+            ("bucket-b", "data.csv", str(tmp_path / "b.csv")),
+        ],
+        progress=progress,
+    )
+
+    assert sorted(results.failed_keys) == [
+        "s3://bucket-a/data.csv",
+        "s3://bucket-b/data.csv",
+    ]
+    assert progress.files_total == 2
+
+    client.retry_failed(results)
+    assert len(results.failed) == 2
+
+
+def test_head_failures():
+    """Test that metadata lookups raise, or leave out, objects they can't reach."""
+
+    client = S3Downloader(
+        "us-east-1",
+        endpoint_url="http://127.0.0.1:1",
+        retry_policy=RetryPolicy(max_attempts=1),
+    )
+
+    for method in (client.head, client.exists):
+        try:
+            method("test-bucket", "data/a.csv")
+            assert False, "Expected exception was not raised"
+        except robinzhon.RobinzhonError as e:
+            assert "data/a.csv" in str(e)
+
+    progress = TransferProgress()
+    assert client.head_multiple("test-bucket", ["a", "b"], progress=progress) == {}
+    assert progress.files_failed == 2
+
+
+def test_download_presigned_urls_failure(tmp_path):
+    """Test that presigned URL failures are reported without their signature."""
+
+    client = S3Downloader("us-east-1", retry_policy=RetryPolicy(max_attempts=1))
+
+    results = client.download_presigned_urls(
+        [("http://127.0.0.1:1/bucket/a.csv?X-Amz-Signature=secret", str(tmp_path / "a.csv"))]
+    )
+
+    assert results.failed_keys == ["http://127.0.0.1:1/bucket/a.csv"]
+    assert results.failed[0].retryable
+    assert "secret" not in results.failed[0].error_message
+    assert not (tmp_path / "a.csv").exists()
+
+
+def test_object_versions_failures(tmp_path):
+    """Test that versioned downloads and version listings fail like their latest-version counterparts."""
+
+    client = S3Downloader(
+        "us-east-1",
+        endpoint_url="http://127.0.0.1:1",
+        retry_policy=RetryPolicy(max_attempts=1),
+    )
+
+    try:
+        client.list_object_versions("test-bucket", prefix="data/")
+        assert False, "Expected exception was not raised"
+    except robinzhon.RobinzhonError as e:
+        assert "test-bucket" in str(e)
+
+    results = client.download_multiple_files_with_paths(
+        "test-bucket",
+        [("data/a.csv", str(tmp_path / "a.csv"), "v1"), ("data/b.csv", str(tmp_path / "b.csv"))],
+    )
+    assert sorted(results.failed_keys) == ["data/a.csv", "data/b.csv"]
+
+
+def test_conditional_downloads(tmp_path):
+    """Test that conditional downloads take datetimes and fail like plain ones."""
+
+    client = S3Downloader(
+        "us-east-1",
+        endpoint_url="http://127.0.0.1:1",
+        retry_policy=RetryPolicy(max_attempts=1),
+    )
+
+    try:
+        client.download_file("test-bucket", "a.csv", str(tmp_path / "a.csv"), if_modified_since="2024-01-01")
+        assert False, "Expected exception was not raised"
+    except TypeError:
+        pass
+
+    results = client.download_multiple_files_with_paths(
+        "test-bucket",
+        [("data/a.csv", str(tmp_path / "a.csv")), ("data/b.csv", str(tmp_path / "b.csv"))],
+        if_none_match={"data/a.csv": '"d41d8cd98f00b204e9800998ecf8427e"'},
+        if_modified_since=datetime.datetime(2024, 1, 1, tzinfo=datetime.timezone.utc),
+    )
+    assert sorted(results.failed_keys) == ["data/a.csv", "data/b.csv"]
+    assert results.skipped == []
+
+
+def test_range_downloads(tmp_path):
+    """Test that range downloads validate their range and fail like whole-object ones."""
+
+    client = S3Downloader(
+        "us-east-1",
+        endpoint_url="http://127.0.0.1:1",
+        retry_policy=RetryPolicy(max_attempts=1),
+    )
+
+    for offset, length in ((0, 0), (-8, 8)):
+        try:
+            client.download_range("test-bucket", "a.parquet", str(tmp_path / "a.bin"), offset, length)
+            assert False, "Expected exception was not raised"
+        except ValueError:
+            pass
+
+    try:
+        client.download_range("test-bucket", "a.parquet", str(tmp_path / "a.bin"), -8)
+        assert False, "Expected exception was not raised"
+    except robinzhon.RobinzhonError as e:
+        assert "a.parquet" in str(e)
+    assert not (tmp_path / "a.bin").exists()
+
+    results = client.download_multiple_files_with_paths(
+        "test-bucket",
+        [
+            ("data/a.mp4", str(tmp_path / "a.mp4"), (0, 1024)),
+            ("data/b.mp4", str(tmp_path / "b.mp4"), "v1", (-1024, None)),
+            ("data/c.mp4", str(tmp_path / "c.mp4")),
+        ],
+    )
+    assert sorted(results.failed_keys) == ["data/a.mp4", "data/b.mp4", "data/c.mp4"]
+
+    retried = client.retry_failed(results)
+    assert sorted(retried.failed_keys) == ["data/a.mp4", "data/b.mp4", "data/c.mp4"]
+
+
+def test_multi_range_downloads(tmp_path):
+    """Test that multi-range downloads validate their ranges and fail as a whole."""
+
+    client = S3Downloader(
+        "us-east-1",
+        endpoint_url="http://127.0.0.1:1",
+        retry_policy=RetryPolicy(max_attempts=1),
+    )
+
+    for ranges in ([], [(0, 4), (8, 0)]):
+        try:
+            client.download_ranges("test-bucket", "a.parquet", ranges)
+            assert False, "Expected exception was not raised"
+        except ValueError:
+            pass
+
+    for path in (None, str(tmp_path / "a.parquet")):
+        try:
+            client.download_ranges("test-bucket", "a.parquet", [(-8, None), (0, 4)], path)
+            assert False, "Expected exception was not raised"
+        except robinzhon.RobinzhonError as e:
+            assert "a.parquet" in str(e)
+    assert not (tmp_path / "a.parquet").exists()
+
+    async def main():
+        return await client.download_ranges_async("test-bucket", "a.parquet", [(0, 4)])
+
+    try:
+        asyncio.run(main())
+        assert False, "Expected exception was not raised"
+    except robinzhon.RobinzhonError:
+        pass
+
+
+def test_download_and_extract(tmp_path):
+    """Test that archive extraction validates its format and fails as a whole."""
+
+    client = S3Downloader(
+        "us-east-1",
+        endpoint_url="http://127.0.0.1:1",
+        retry_policy=RetryPolicy(max_attempts=1),
+    )
+
+    try:
+        client.download_and_extract("test-bucket", "a.rar", str(tmp_path), format="rar")
+        assert False, "Expected exception was not raised"
+    except ValueError:
+        pass
+
+    for key, archive_format in (("a.tar.gz", "tar.gz"), ("a.tar", "tar"), ("a.zip", "zip")):
+        try:
+            client.download_and_extract(
+                "test-bucket", key, str(tmp_path / "out"), format=archive_format
+            )
+            assert False, "Expected exception was not raised"
+        except robinzhon.RobinzhonError as e:
+            assert key in str(e)
+    assert list((tmp_path / "out").iterdir()) == []
+
+    async def main():
+        return await client.download_and_extract_async(
+            "test-bucket", "a.tgz", str(tmp_path / "out")
+        )
+
+    try:
+        asyncio.run(main())
+        assert False, "Expected exception was not raised"
+    except robinzhon.RobinzhonError:
+        pass
+
+
+def test_restore_objects_failures():
+    """Test that restore requests report failed keys and validate their arguments."""
+
+    client = S3Downloader(
+        "us-east-1",
+        endpoint_url="http://127.0.0.1:1",
+        retry_policy=RetryPolicy(max_attempts=1),
+    )
+
+    results = client.restore_objects("test-bucket", ["a.gz", "b.gz"], tier="bulk", days=3)
+    assert sorted(results.failed_keys) == ["a.gz", "b.gz"]
+    assert results.successful == []
+
+    for kwargs in ({"tier": "fast"}, {"days": 0}):
+        try:
+            client.restore_objects("test-bucket", ["a.gz"], **kwargs)
+            assert False, "Expected exception was not raised"
+        except ValueError:
+            pass
+
+    try:
+        client.wait_for_restore("test-bucket", ["a.gz"], poll_interval=0)
+        assert False, "Expected exception was not raised"
+    except ValueError:
+        pass
+
+    try:
+        client.wait_for_restore("test-bucket", ["a.gz"], poll_interval=0.1, timeout=1)
+        assert False, "Expected exception was not raised"
+    except robinzhon.RobinzhonError as e:
+        assert "a.gz" in str(e)
+
+
+@pytest.mark.skipif(not hasattr(os, "fork"), reason="needs os.fork")
+def test_reinitialize_after_fork(tmp_path):
+    """Test that a downloader refuses to run in a forked child until it is reinitialized."""
+
+    client = S3Downloader(
+        "us-east-1",
+        endpoint_url="http://127.0.0.1:1",
+        retry_policy=RetryPolicy(max_attempts=1),
+    )
+    results = client.download_multiple_files_with_paths(
+        "test-bucket", [("a.csv", str(tmp_path / "a.csv"))]
+    )
+    assert results.failed_keys == ["a.csv"]
+
+    pid = os.fork()
+    if pid == 0:
+        status = 1
+        try:
+            try:
+                client.download_multiple_files_with_paths(
+                    "test-bucket", [("a.csv", str(tmp_path / "a.csv"))]
+                )
+            except robinzhon.RobinzhonError as e:
+                if "reinitialize" in str(e):
+                    client.reinitialize()
+                    results = client.download_multiple_files_with_paths(
+                        "test-bucket", [("a.csv", str(tmp_path / "a.csv"))]
+                    )
+                    status = 0 if results.failed_keys == ["a.csv"] else 1
+        finally:
+            os._exit(status)
+
+    _, status = os.waitpid(pid, 0)
+    assert os.WEXITSTATUS(status) == 0
+
+
+@pytest.mark.skipif(not hasattr(os, "fork"), reason="needs os.fork")
+def test_awaitables_after_fork(tmp_path):
+    """Test that awaitables and queued jobs of a forked child fail until it is reinitialized."""
+
+    client = S3Downloader(
+        "us-east-1",
+        endpoint_url="http://127.0.0.1:1",
+        retry_policy=RetryPolicy(max_attempts=1),
+    )
+    items = [("a.csv", str(tmp_path / "a.csv"))]
+
+    async def download():
+        return await client.download_multiple_files_with_paths_async(
+            "test-bucket", items
+        )
+
+    assert asyncio.run(download()).failed_keys == ["a.csv"]
+
+    pid = os.fork()
+    if pid == 0:
+        status = 1
+        try:
+            with pytest.raises(robinzhon.RobinzhonError, match="reinitialize"):
+                asyncio.run(download())
+            with pytest.raises(robinzhon.RobinzhonError, match="reinitialize"):
+                robinzhon.TransferManager().submit(client, "test-bucket", items)
+            client.reinitialize()
+            job = robinzhon.TransferManager().submit(client, "test-bucket", items)
+            if (
+                asyncio.run(download()).failed_keys == ["a.csv"]
+                and job.result(timeout=30).failed_keys == ["a.csv"]
+            ):
+                status = 0
+        finally:
+            os._exit(status)
+
+    # A child hanging on the runtime of its parent is killed rather than awaited.
+    deadline = time.monotonic() + 60
+    while True:
+        waited, status = os.waitpid(pid, os.WNOHANG)
+        if waited:
+            break
+        if time.monotonic() > deadline:
+            os.kill(pid, 9)
+        time.sleep(0.1)
+    assert os.WIFEXITED(status) and os.WEXITSTATUS(status) == 0
+
+
+def test_pickle_downloader(tmp_path):
+    """Test that an unpickled downloader is rebuilt with the same settings."""
+
+    client = pickle.loads(
+        pickle.dumps(
+            S3Downloader(
+                "us-east-1",
+                endpoint_url="http://127.0.0.1:1",
+                retry_policy=RetryPolicy(max_attempts=1),
+                part_size=1024,
+                verify_checksum=True,
+            )
+        )
+    )
+    results = client.download_multiple_files_with_paths(
+        "test-bucket", [("a.csv", str(tmp_path / "a.csv"))]
+    )
+    assert results.failed_keys == ["a.csv"]
+
+
+def test_unreachable_endpoint_is_not_a_redirect(tmp_path):
+    """Test that connection failures aren't taken for a bucket in another region."""
+
+    config = robinzhon.S3Config("eu-west-3", endpoint_url="http://127.0.0.1:1")
+    client = S3Downloader(config=config, retry_policy=RetryPolicy(max_attempts=1))
+    results = client.download_multiple_files_with_paths(
+        "test-bucket", [("a.csv", str(tmp_path / "a.csv"))]
+    )
+    assert results.failed_keys == ["a.csv"]
+    assert config.region_name == "eu-west-3"
+
+
+def test_auto_decompress(tmp_path):
+    """Test that gzipped downloads record both sizes, and none are recorded by default."""
+
+    client = S3Downloader(
+        "us-east-1",
+        endpoint_url="http://127.0.0.1:1",
+        retry_policy=RetryPolicy(max_attempts=1),
+        auto_decompress=True,
+    )
+    results = client.download_multiple_files_with_paths(
+        "test-bucket", [("logs/app.log.gz", str(tmp_path / "app.log"))]
+    )
+    assert results.failed_keys == ["logs/app.log.gz"]
+    assert not (tmp_path / "app.log").exists()
+    assert results.decompressed == {}
+    assert results.to_dict()["decompressed"] == {}
+
+    restored = pickle.loads(pickle.dumps(client))
+    assert restored.download_multiple_files_with_paths(
+        "test-bucket", [("logs/app.log.gz", str(tmp_path / "app.log"))]
+    ).failed_keys == ["logs/app.log.gz"]
+
+
+def test_request_metrics(tmp_path, monkeypatch):
+    """Test counting requests and their errors, and the metrics callback."""
+
+    # Requests are only sent, and seen, once they can be signed.
+    monkeypatch.setenv("AWS_ACCESS_KEY_ID", "test")
+    monkeypatch.setenv("AWS_SECRET_ACCESS_KEY", "test")
+
+    robinzhon.reset_metrics()
+    client = S3Downloader(
+        "us-east-1",
+        endpoint_url="http://127.0.0.1:1",
+        retry_policy=RetryPolicy(max_attempts=1),
+    )
+    client.download_multiple_files("test-bucket", ["a.txt"], str(tmp_path))
+
+    metrics = robinzhon.get_metrics()
+    assert metrics["requests_total"] >= 1
+    assert metrics["request_errors_total"] >= 1
+    assert metrics["retries_total"] == 0
+    assert metrics["bytes_in_total"] == 0
+    latency = metrics["request_duration_seconds"]
+    assert float("inf") in latency["buckets"]
+    assert latency["count"] == list(latency["buckets"].values())[-1]
+
+    robinzhon.reset_metrics()
+    assert robinzhon.get_metrics()["requests_total"] == 0
+
+    with pytest.raises(ValueError):
+        robinzhon.set_metrics_callback(print, interval=0)
+    with pytest.raises(TypeError):
+        robinzhon.set_metrics_callback("not callable")
+
+    reported = []
+    robinzhon.set_metrics_callback(reported.append, interval=0.05)
+    try:
+        time.sleep(0.3)
+    finally:
+        robinzhon.set_metrics_callback(None)
+    assert reported and "requests_total" in reported[0]
+
+
+def test_set_log_level(tmp_path, monkeypatch):
+    """Test logging every request at the debug level, and turning it off."""
+
+    # Requests are only sent, and logged, once they can be signed.
+    monkeypatch.setenv("AWS_ACCESS_KEY_ID", "test")
+    monkeypatch.setenv("AWS_SECRET_ACCESS_KEY", "test")
+
+    records = []
+    handler = logging.Handler()
+    handler.emit = records.append
+    logger = logging.getLogger("robinzhon")
+    logger.addHandler(handler)
+    client = S3Downloader(
+        "us-east-1",
+        endpoint_url="http://127.0.0.1:1",
+        retry_policy=RetryPolicy(max_attempts=1),
+    )
+
+    try:
+        robinzhon.set_log_level("debug")
+        assert logger.level == logging.DEBUG
+        client.download_multiple_files("test-bucket", ["a.txt"], str(tmp_path))
+        requests = [r for r in records if r.name == "robinzhon.request_log"]
+        assert any("attempt 1" in r.getMessage() for r in requests)
+        assert all("127.0.0.1:1" in r.getMessage() for r in requests)
+
+        records.clear()
+        robinzhon.set_log_level(logging.WARNING)
+        client.download_multiple_files("test-bucket", ["b.txt"], str(tmp_path))
+        assert not [r for r in records if r.name == "robinzhon.request_log"]
+    finally:
+        logger.removeHandler(handler)
+        logger.setLevel(logging.NOTSET)
+
+    with pytest.raises(ValueError):
+        robinzhon.set_log_level("chatty")
+
+
+def test_download_from_manifest(tmp_path):
+    """Test downloading the objects a CSV or JSON Lines manifest lists."""
+
+    client = S3Downloader(
+        "us-east-1",
+        endpoint_url="http://127.0.0.1:1",
+        retry_policy=RetryPolicy(max_attempts=1),
+    )
+    csv_manifest = tmp_path / "manifest.csv"
+    csv_manifest.write_text(
+        "bucket,key,local_path\n"
+        f"bucket-a,data.csv,\"{tmp_path / 'a.csv'}\"\n"
+        f",data.csv,{tmp_path / 'b.csv'}\n"
+    )
+    jsonl_manifest = tmp_path / "manifest.jsonl"
+    jsonl_manifest.write_text(
+        json.dumps({"key": "c.csv", "local_path": str(tmp_path / "c.csv")}) + "\n"
+    )
+
+    results = client.download_from_manifest(
+        str(csv_manifest), bucket_name="bucket-b"
+    )
+    assert sorted(results.failed_keys) == [
+        "s3://bucket-a/data.csv",
+        "s3://bucket-b/data.csv",
+    ]
+    results = client.download_from_manifest(str(jsonl_manifest), bucket_name="c")
+    assert results.failed_keys == ["s3://c/c.csv"]
+
+    with pytest.raises(ValueError, match="line 3"):
+        client.download_from_manifest(str(csv_manifest))
+    with pytest.raises(ValueError, match="format"):
+        client.download_from_manifest(str(tmp_path / "manifest.txt"))
+    with pytest.raises(robinzhon.RobinzhonError):
+        client.download_from_manifest(str(tmp_path / "missing.csv"))
+
+
+def test_download_from_inventory_failures(tmp_path):
+    """Test that download_from_inventory raises when the report can't be read."""
+
+    client = S3Downloader(
+        "us-east-1",
+        endpoint_url="http://127.0.0.1:1",
+        retry_policy=RetryPolicy(max_attempts=1),
+    )
+
+    with pytest.raises(robinzhon.RobinzhonError, match="manifest.json"):
+        client.download_from_inventory(
+            "s3://inventories/my-bucket/daily/manifest.json", str(tmp_path)
+        )
+    assert list(tmp_path.iterdir()) == []
+
+    with pytest.raises(ValueError):
+        client.download_from_inventory("inventories/manifest.json", str(tmp_path))
+    with pytest.raises(ValueError):
+        client.download_from_inventory(
+            "s3://inventories/manifest.json", str(tmp_path), key_pattern="("
+        )
+
+
+class FakeS3:
+    """A local endpoint answering the listings, HEADs, GETs and uploads of one
+    bucket.
+
+    `objects` maps keys to their body or, for objects too large to hold, to
+    their size alone; `gets` records the keys of the GETs in the order they came,
+    and `writes` the key of every MiB of body sent, at a pace that lets bodies
+    sent together interleave. With `sized` unset, bodies come without a
+    Content-Length, as chunked ones do, and end with the connection. `uploads`
+    holds the parts of multipart uploads in progress, and `parts` records the
+    key and number of every part uploaded, and `received` the key and time
+    every upload body finished arriving. `part_sizes` holds the size of the
+    parts an object was uploaded in, which a HEAD of one of its parts answers
+    with. `etags` and `headers` override the ETag and add headers to the
+    responses for a key. `ranges` records the Range
+    header of every GET, which is ignored with `ranged` unset. A key in `cuts`
+    has its next body cut short after that many bytes, then `on_cut` is called
+    with the key, and one in `stalls` has its body wait that many seconds after
+    the headers, or in `delays` before them. A key in `errors`, or `""` for the
+    bucket itself, has its next request answered with that status and S3 error
+    code. `requests` records the method, path and headers, lowercased, of every
+    request, and `assumed` the form of every STS AssumeRole. `metadata` holds the
+    `x-amz-meta-*` headers an object was stored with, sent back with it, and
+    `kms` the operation and request of every call to its KMS endpoint, whose
+    data keys are "wrapped" by prefixing them with `wrapped:`.
+    """
+
+    def __init__(self):
+        self.objects = {}
+        self.gets = []
+        self.writes = []
+        self.sized = True
+        self.uploads = {}
+        self.upload_ids = itertools.count()
+        self.parts = []
+        self.received = []
+        self.part_sizes = {}
+        self.etags = {}
+        self.headers = {}
+        self.ranges = []
+        self.ranged = True
+        self.cuts = {}
+        self.on_cut = None
+        self.stalls = {}
+        self.delays = {}
+        self.errors = {}
+        self.requests = []
+        self.assumed = []
+        self.metadata = {}
+        self.kms = []
+        fake = self
+
+        class Handler(http.server.BaseHTTPRequestHandler):
+            def parse_request(self):
+                if not super().parse_request():
+                    return False
+                headers = {name.lower(): value for name, value in self.headers.items()}
+                fake.requests.append((self.command, self.path, headers))
+                return True
+
+            def do_HEAD(self):
+                fake.answer(self, body=False)
+
+            def do_GET(self):
+                fake.answer(self, body=True)
+
+            def do_PUT(self):
+                fake.receive(self)
+
+            def do_POST(self):
+                fake.receive(self)
+
+            def do_DELETE(self):
+                fake.receive(self)
+
+            def log_message(self, *args):
+                pass
+
+        self.server = http.server.ThreadingHTTPServer(("127.0.0.1", 0), Handler)
+        threading.Thread(target=self.server.serve_forever, daemon=True).start()
+        self.endpoint_url = "http://127.0.0.1:%d" % self.server.server_port
+
+    def downloader(self, **kwargs):
+        return S3Downloader(
+            "us-east-1",
+            endpoint_url=self.endpoint_url,
+            force_path_style=True,
+            **{"retry_policy": RetryPolicy(max_attempts=1), **kwargs},
+        )
+
+    def uploader(self, **kwargs):
+        return robinzhon.S3Uploader(
+            "us-east-1",
+            endpoint_url=self.endpoint_url,
+            force_path_style=True,
+            **{"retry_policy": RetryPolicy(max_attempts=1), **kwargs},
+        )
+
+    def receive(self, request):
+        url = urllib.parse.urlsplit(request.path)
+        _, _, key = urllib.parse.unquote(url.path).lstrip("/").partition("/")
+        query = urllib.parse.parse_qs(url.query, keep_blank_values=True)
+        content = self.request_body(request)
+        self.received.append((key, time.monotonic()))
+        if request.command == "POST" and url.path == "/":
+            if "X-Amz-Target" in request.headers:
+                return self.key_management(request, json.loads(content))
+            return self.assume_role(request, urllib.parse.parse_qs(content.decode()))
+        if key in self.errors:
+            return self.fail(request, *self.errors.pop(key))
+        if "If-Match" in request.headers and key not in self.objects:
+            return self.send(request, 412, b"")
+        upload_id = query.get("uploadId", [None])[0]
+        if request.command == "POST" and "uploads" in query:
+            upload_id = "upload-%d" % next(self.upload_ids)
+            self.uploads[upload_id] = {}
+            self.metadata[key] = self.stored_metadata(request)
+            return self.send(
+                request,
+                200,
+                (
+                    "<InitiateMultipartUploadResult><Bucket>test-bucket</Bucket>"
+                    "<Key>%s</Key><UploadId>%s</UploadId>"
+                    "</InitiateMultipartUploadResult>" % (key, upload_id)
+                ).encode(),
+            )
+        if upload_id is not None and upload_id not in self.uploads:
+            return self.send(request, 404, b"")
+        if request.command == "DELETE":
+            self.uploads.pop(upload_id, None)
+            return self.send(request, 204, b"")
+        if request.command == "POST":
+            parts = self.uploads.pop(upload_id)
+            self.objects[key] = b"".join(parts[number] for number in sorted(parts))
+            self.part_sizes[key] = len(parts[min(parts)])
+            return self.send(
+                request,
+                200,
+                (
+                    "<CompleteMultipartUploadResult><Key>%s</Key>"
+                    "<ETag>&quot;%s&quot;</ETag></CompleteMultipartUploadResult>"
+                    % (key, self.etag(key))
+                ).encode(),
+            )
+        etag = hashlib.md5(content).hexdigest()
+        if upload_id is not None:
+            number = int(query["partNumber"][0])
+            self.uploads[upload_id][number] = content
+            self.parts.append((key, number))
+        else:
+            self.objects[key] = content
+            self.metadata[key] = self.stored_metadata(request)
+        self.send(request, 200, b"", {"ETag": '"%s"' % etag, "Content-Length": "0"})
+
+    def stored_metadata(self, request):
+        return {
+            name: value
+            for name, value in request.headers.items()
+            if name.lower().startswith("x-amz-meta-")
+        }
+
+    def key_management(self, request, form):
+        operation = request.headers["X-Amz-Target"].rpartition(".")[2]
+        self.kms.append((operation, form))
+        if operation == "GenerateDataKey":
+            data_key = os.urandom(32)
+            response = {
+                "KeyId": form["KeyId"],
+                "Plaintext": base64.b64encode(data_key).decode(),
+                "CiphertextBlob": base64.b64encode(b"wrapped:" + data_key).decode(),
+            }
+        else:
+            wrapped = base64.b64decode(form["CiphertextBlob"])
+            response = {"Plaintext": base64.b64encode(wrapped[8:]).decode()}
+        self.send(request, 200, json.dumps(response).encode())
+
+    def request_body(self, request):
+        if request.headers.get("Transfer-Encoding") == "chunked":
+            body = b""
+            while True:
+                size = int(request.rfile.readline().split(b";")[0], 16)
+                if not size:
+                    break
+                body += request.rfile.read(size)
+                request.rfile.readline()
+            while request.rfile.readline() not in (b"\r\n", b""):
+                pass
+        else:
+            body = request.rfile.read(int(request.headers.get("Content-Length", 0)))
+        if "aws-chunked" not in request.headers.get("Content-Encoding", ""):
+            return body
+        # The SDK streams bodies as aws-chunked, with their checksum in a trailer.
+        decoded, rest = b"", body
+        while True:
+            line, _, rest = rest.partition(b"\r\n")
+            size = int(line.split(b";")[0], 16)
+            if not size:
+                return decoded
+            decoded, rest = decoded + rest[:size], rest[size + 2 :]
+
+    def answer(self, request, body):
+        url = urllib.parse.urlsplit(request.path)
+        _, _, key = urllib.parse.unquote(url.path).lstrip("/").partition("/")
+        query = urllib.parse.parse_qs(url.query)
+        if key in self.errors:
+            return self.fail(request, *self.errors.pop(key))
+        if not key and "list-type" in query:
+            return self.send(request, 200, self.listing(query.get("prefix", [""])[0]))
+        if not key:
+            return self.send(request, 200, b"", {"x-amz-bucket-region": "us-east-1"})
+        if "uploadId" in query:
+            if query["uploadId"][0] not in self.uploads:
+                return self.send(request, 404, b"")
+            return self.send(request, 200, b"<ListPartsResult></ListPartsResult>")
+        time.sleep(self.delays.get(key, 0))
+        if key not in self.objects:
+            return self.send(request, 404, b"")
+        content = self.objects[key]
+        size = content if isinstance(content, int) else len(content)
+        headers = {"ETag": '"%s"' % self.etag(key), "Content-Length": str(size)}
+        headers.update(self.metadata.get(key, {}))
+        headers.update(self.headers.get(key, {}))
+        if "partNumber" in query:
+            # An object uploaded whole is its own first and only part.
+            part_size = self.part_sizes.get(key, size)
+            start = (int(query["partNumber"][0]) - 1) * part_size
+            headers["Content-Length"] = str(max(0, min(part_size, size - start)))
+            headers["x-amz-mp-parts-count"] = str(-(-size // part_size))
+        if not body:
+            return self.send(request, 200, b"", headers)
+        self.gets.append(key)
+        self.ranges.append(request.headers.get("Range"))
+        if request.headers.get("If-Match", headers["ETag"]) != headers["ETag"]:
+            return self.send(request, 412, b"")
+        start, end = 0, size - 1
+        if "Range" in request.headers and self.ranged:
+            first, _, last = request.headers["Range"].removeprefix("bytes=").partition("-")
+            start, end = int(first), min(int(last or end), end)
+            headers["Content-Range"] = "bytes %d-%d/%d" % (start, end, size)
+        content = content[start : end + 1]
+        headers["Content-Length"] = str(len(content))
+        if not self.sized:
+            del headers["Content-Length"]
+        if key in self.cuts:
+            request.send_response(206 if "Content-Range" in headers else 200)
+            for name, value in headers.items():
+                request.send_header(name, value)
+            request.end_headers()
+            request.wfile.write(content[: self.cuts.pop(key)])
+            request.close_connection = True
+            if self.on_cut:
+                self.on_cut(key)
+            return
+        self.send(
+            request, 206 if "Content-Range" in headers else 200, content, headers, key
+        )
+
+    def fail(self, request, status, code):
+        body = (
+            "<Error><Code>%s</Code><Message>%s</Message><RequestId>REQ123</RequestId>"
+            "<HostId>HOST456</HostId></Error>" % (code, code)
+        ).encode()
+        headers = {
+            "Content-Length": str(len(body)),
+            "x-amz-request-id": "REQ123",
+            "x-amz-id-2": "HOST456",
+        }
+        self.send(request, status, body, headers)
+
+    def assume_role(self, request, form):
+        self.assumed.append({name: values[0] for name, values in form.items()})
+        expiration = datetime.datetime.now(datetime.timezone.utc) + datetime.timedelta(
+            hours=1
+        )
+        body = (
+            '<AssumeRoleResponse xmlns="https://sts.amazonaws.com/doc/2011-06-15/">'
+            "<AssumeRoleResult><Credentials><AccessKeyId>ASSUMED</AccessKeyId>"
+            "<SecretAccessKey>secret</SecretAccessKey><SessionToken>role-token</SessionToken>"
+            "<Expiration>%s</Expiration></Credentials><AssumedRoleUser>"
+            "<AssumedRoleId>AROA:session</AssumedRoleId><Arn>%s</Arn></AssumedRoleUser>"
+            "</AssumeRoleResult></AssumeRoleResponse>"
+            % (expiration.strftime("%Y-%m-%dT%H:%M:%SZ"), form["RoleArn"][0])
+        ).encode()
+        self.send(request, 200, body, {"Content-Length": str(len(body))})
+
+    def etag(self, key):
+        if key in self.etags:
+            return self.etags[key]
+        content = self.objects[key]
+        if isinstance(content, int):
+            content = str(content).encode()
+        return hashlib.md5(content).hexdigest()
+
+    def listing(self, prefix):
+        contents = "".join(
+            "<Contents><Key>%s</Key><LastModified>2024-01-01T00:00:00.000Z</LastModified>"
+            "<ETag>&quot;%s&quot;</ETag><Size>%d</Size><StorageClass>STANDARD</StorageClass>"
+            "</Contents>"
+            % (key, self.etag(key), content if isinstance(content, int) else len(content))
+            for key, content in sorted(self.objects.items())
+            if key.startswith(prefix)
+        )
+        return (
+            '<?xml version="1.0" encoding="UTF-8"?>'
+            '<ListBucketResult xmlns="http://s3.amazonaws.com/doc/2006-03-01/">'
+            "<Name>test-bucket</Name><Prefix>%s</Prefix><MaxKeys>1000</MaxKeys>"
+            "<IsTruncated>false</IsTruncated>%s</ListBucketResult>" % (prefix, contents)
+        ).encode()
+
+    def send(self, request, status, content, headers=None, key=None):
+        request.send_response(status)
+        for name, value in (headers or {"Content-Length": str(len(content))}).items():
+            request.send_header(name, value)
+        request.end_headers()
+        if request.command == "HEAD":
+            return
+        time.sleep(self.stalls.get(key, 0))
+        for start in range(0, len(content), 1 << 20):
+            if start:
+                time.sleep(0.02)
+            request.wfile.write(content[start : start + (1 << 20)])
+            self.writes.append(key)
+
+
+@pytest.fixture
+def fake_s3(monkeypatch):
+    monkeypatch.setenv("AWS_ACCESS_KEY_ID", "test")
+    monkeypatch.setenv("AWS_SECRET_ACCESS_KEY", "test")
+    fake = FakeS3()
+    yield fake
+    fake.server.shutdown()
+
+
+def test_verify_checksum_downloads(tmp_path, fake_s3):
+    """Test that verify_checksum fails items whose body doesn't match their ETag."""
+
+    fake_s3.objects["data/a.txt"] = b"hello"
+    client = fake_s3.downloader(verify_checksum=True)
+    local_path = tmp_path / "a.txt"
+
+    results = client.download_multiple_files(
+        "test-bucket", ["data/a.txt"], str(tmp_path)
+    )
+    assert results.successful == [str(local_path)]
+    assert local_path.read_bytes() == b"hello"
+
+    local_path.unlink()
+    fake_s3.etags["data/a.txt"] = hashlib.md5(b"other").hexdigest()
+    results = client.download_multiple_files(
+        "test-bucket", ["data/a.txt"], str(tmp_path)
+    )
+    assert results.failed_keys == ["data/a.txt"]
+    assert "Checksum mismatch" in results.failed[0].error_message
+    assert not local_path.exists()
+
+    # SSE-KMS and SSE-C ETags aren't MD5s of the body.
+    for headers in (
+        {"x-amz-server-side-encryption": "aws:kms"},
+        {"x-amz-server-side-encryption-customer-algorithm": "AES256"},
+    ):
+        fake_s3.headers["data/a.txt"] = headers
+        results = client.download_multiple_files(
+            "test-bucket", ["data/a.txt"], str(tmp_path)
+        )
+        assert results.successful == [str(local_path)]
+        local_path.unlink()
+
+
+def test_verify_checksum_multipart_etags(tmp_path, fake_s3):
+    """Test that parts fetched by range are checked against a multipart ETag."""
+
+    part_size = 1024 * 1024
+    content = os.urandom(part_size * 2 + 100)
+    parts = [content[start : start + part_size] for start in range(0, len(content), part_size)]
+    composite = hashlib.md5(
+        b"".join(hashlib.md5(part).digest() for part in parts)
+    ).hexdigest()
+    fake_s3.objects["data/big.bin"] = content
+    fake_s3.part_sizes["data/big.bin"] = part_size
+    fake_s3.etags["data/big.bin"] = "%s-%d" % (composite, len(parts))
+    client = fake_s3.downloader(
+        verify_checksum=True, part_size=part_size, part_concurrency=2
+    )
+    local_path = tmp_path / "big.bin"
+
+    results = client.download_multiple_files(
+        "test-bucket", ["data/big.bin"], str(tmp_path)
+    )
+    assert results.successful == [str(local_path)]
+    assert local_path.read_bytes() == content
+    assert fake_s3.gets == ["data/big.bin"] * len(parts)
+
+    local_path.unlink()
+    fake_s3.etags["data/big.bin"] = "%s-%d" % (hashlib.md5(b"other").hexdigest(), len(parts))
+    results = client.download_multiple_files(
+        "test-bucket", ["data/big.bin"], str(tmp_path)
+    )
+    assert results.failed_keys == ["data/big.bin"]
+    assert "Checksum mismatch" in results.failed[0].error_message
+    assert not local_path.exists()
+
+    # Parts that don't add up to the ETag's count can't be checked.
+    fake_s3.etags["data/big.bin"] = "%s-%d" % (hashlib.md5(b"other").hexdigest(), 5)
+    results = client.download_multiple_files(
+        "test-bucket", ["data/big.bin"], str(tmp_path)
+    )
+    assert results.successful == [str(local_path)]
+
+    # Uploaded in parts of another size that give the same count, the file is
+    # hashed on the boundaries of the upload.
+    uploaded_part_size = 768 * 1024
+    content = os.urandom(3 * uploaded_part_size // 2)
+    parts = [
+        content[start : start + uploaded_part_size]
+        for start in range(0, len(content), uploaded_part_size)
+    ]
+    composite = hashlib.md5(
+        b"".join(hashlib.md5(part).digest() for part in parts)
+    ).hexdigest()
+    fake_s3.objects["data/big.bin"] = content
+    fake_s3.part_sizes["data/big.bin"] = uploaded_part_size
+    fake_s3.etags["data/big.bin"] = "%s-%d" % (composite, len(parts))
+    assert len(content) // part_size + 1 == len(parts)
+    results = client.download_multiple_files(
+        "test-bucket", ["data/big.bin"], str(tmp_path)
+    )
+    assert results.successful == [str(local_path)]
+    assert local_path.read_bytes() == content
+
+    local_path.unlink()
+    fake_s3.etags["data/big.bin"] = "%s-%d" % (hashlib.md5(b"other").hexdigest(), len(parts))
+    results = client.download_multiple_files(
+        "test-bucket", ["data/big.bin"], str(tmp_path)
+    )
+    assert "Checksum mismatch" in results.failed[0].error_message
+
+    # Without the size of its parts, the ETag is left unchecked.
+    del fake_s3.part_sizes["data/big.bin"]
+    results = client.download_multiple_files(
+        "test-bucket", ["data/big.bin"], str(tmp_path)
+    )
+    assert results.successful == [str(local_path)]
+
+
+def test_short_body_fails_download(tmp_path, fake_s3):
+    """Test that a body shorter than its Content-Length fails the item and
+    removes the partial file, whether fetched whole or in parts."""
+
+    fake_s3.objects["data/a.bin"] = os.urandom(3 * 1024 * 1024)
+    local_path = tmp_path / "a.bin"
+
+    for part_concurrency in (1, 2):
+        fake_s3.cuts["data/a.bin"] = 1000
+        client = fake_s3.downloader(
+            part_concurrency=part_concurrency, part_size=1024 * 1024
+        )
+        results = client.download_multiple_files(
+            "test-bucket", ["data/a.bin"], str(tmp_path)
+        )
+        assert results.failed_keys == ["data/a.bin"]
+        assert "Failed to read S3 response body" in results.failed[0].error_message
+        assert results.failed[0].retryable
+        assert not local_path.exists()
+
+
+def test_resume_interrupted_download(tmp_path, fake_s3):
+    """Test that a body cut short is resumed from where it stopped, unless the
+    object changed or the store ignores the range."""
+
+    content = os.urandom(3 * 1024 * 1024)
+    fake_s3.objects["data/a.bin"] = content
+    client = fake_s3.downloader(
+        part_concurrency=1,
+        retry_policy=RetryPolicy(max_attempts=3, base_delay=0.01),
+    )
+    local_path = tmp_path / "a.bin"
+
+    fake_s3.cuts["data/a.bin"] = 1000
+    assert client.download_file("test-bucket", "data/a.bin", str(local_path))
+    assert local_path.read_bytes() == content
+    assert fake_s3.ranges == [None, "bytes=1000-"]
+
+    # A changed ETag fails the If-Match, and the download starts over.
+    changed = os.urandom(2 * 1024 * 1024)
+    fake_s3.ranges.clear()
+    fake_s3.cuts["data/a.bin"] = 1000
+    fake_s3.on_cut = lambda key: fake_s3.objects.update({key: changed})
+    assert client.download_file("test-bucket", "data/a.bin", str(local_path))
+    assert local_path.read_bytes() == changed
+    assert fake_s3.ranges == [None, "bytes=1000-", None]
+
+    # The whole object sent again replaces the partial file.
+    fake_s3.ranges.clear()
+    fake_s3.ranged = False
+    fake_s3.on_cut = None
+    fake_s3.objects["data/a.bin"] = content
+    fake_s3.cuts["data/a.bin"] = 1000
+    assert client.download_file("test-bucket", "data/a.bin", str(local_path))
+    assert local_path.read_bytes() == content
+    assert fake_s3.ranges == [None, "bytes=1000-"]
+
+
+def test_keyboard_interrupt_keeps_partial_results(tmp_path, fake_s3):
+    """Test that Ctrl-C stops a batch, removes the file it was writing and
+    attaches what finished to the KeyboardInterrupt."""
+
+    fake_s3.objects = {"data/a.txt": b"a", "data/b.bin": b"b" * 1024}
+    fake_s3.stalls["data/b.bin"] = 5
+    client = fake_s3.downloader(max_concurrent_downloads=1)
+    timer = threading.Timer(1.0, _thread.interrupt_main)
+    timer.start()
+
+    started = time.monotonic()
+    with pytest.raises(KeyboardInterrupt) as raised:
+        client.download_multiple_files(
+            "test-bucket", ["data/a.txt", "data/b.bin"], str(tmp_path)
+        )
+    timer.join()
+    assert time.monotonic() - started < 4
+    assert raised.value.partial_results.successful == [str(tmp_path / "a.txt")]
+    assert (tmp_path / "a.txt").read_bytes() == b"a"
+    assert not (tmp_path / "b.bin").exists()
+
+
+def test_upload_acl_and_expected_bucket_owner(tmp_path, fake_s3):
+    """Test that uploads send their ACL and expected bucket owner."""
+
+    local_path = tmp_path / "a.txt"
+    local_path.write_bytes(b"hello")
+    client = fake_s3.uploader()
+    options = robinzhon.UploadOptions(
+        acl="bucket-owner-full-control", expected_bucket_owner="111122223333"
+    )
+
+    client.upload_file("test-bucket", "data/a.txt", str(local_path), options=options)
+    assert fake_s3.objects["data/a.txt"] == b"hello"
+    [(method, _, headers)] = fake_s3.requests
+    assert method == "PUT"
+    assert headers["x-amz-acl"] == "bucket-owner-full-control"
+    assert headers["x-amz-expected-bucket-owner"] == "111122223333"
+
+    fake_s3.requests.clear()
+    client.upload_file("test-bucket", "data/b.txt", str(local_path))
+    [(_, _, headers)] = fake_s3.requests
+    assert "x-amz-acl" not in headers
+    assert "x-amz-expected-bucket-owner" not in headers
+
+
+def test_anonymous_downloads(tmp_path, fake_s3, monkeypatch):
+    """Test that anonymous clients send unsigned requests, without credentials."""
+
+    monkeypatch.delenv("AWS_ACCESS_KEY_ID")
+    monkeypatch.delenv("AWS_SECRET_ACCESS_KEY")
+    fake_s3.objects["data/a.txt"] = b"public"
+    config = robinzhon.S3Config(
+        "us-east-1",
+        endpoint_url=fake_s3.endpoint_url,
+        force_path_style=True,
+        anonymous=True,
+    )
+    client = S3Downloader(config=config, retry_policy=RetryPolicy(max_attempts=1))
+
+    local_path = tmp_path / "a.txt"
+    assert client.download_file("test-bucket", "data/a.txt", str(local_path))
+    assert local_path.read_bytes() == b"public"
+    assert fake_s3.requests
+    assert all("authorization" not in headers for _, _, headers in fake_s3.requests)
+
+
+def test_read_and_operation_timeouts(tmp_path, fake_s3):
+    """Test that a stalled response fails once its timeouts run out."""
+
+    fake_s3.objects["data/a.bin"] = b"a" * 1024
+    local_path = tmp_path / "a.bin"
+
+    for timeouts in ({"read_timeout": 0.5}, {"operation_timeout": 1}):
+        fake_s3.delays["data/a.bin"] = 5
+        config = robinzhon.S3Config(
+            "us-east-1",
+            endpoint_url=fake_s3.endpoint_url,
+            force_path_style=True,
+            **timeouts,
+        )
+        client = S3Downloader(config=config, retry_policy=RetryPolicy(max_attempts=1))
+        started = time.monotonic()
+        results = client.download_multiple_files(
+            "test-bucket", ["data/a.bin"], str(tmp_path)
+        )
+        assert time.monotonic() - started < 4
+        assert results.failed_keys == ["data/a.bin"]
+        assert not local_path.exists()
+
+    del fake_s3.delays["data/a.bin"]
+    results = client.download_multiple_files("test-bucket", ["data/a.bin"], str(tmp_path))
+    assert results.successful == [str(local_path)]
+
+
+def test_write_buffer_and_preallocation(tmp_path, fake_s3):
+    """Test that a file is sized to its Content-Length before its body arrives,
+    and written through a write buffer of any size."""
+
+    content = os.urandom(3 * 1024 * 1024 + 7)
+    fake_s3.objects["data/a.bin"] = content
+    fake_s3.stalls["data/a.bin"] = 1
+    client = fake_s3.downloader(part_concurrency=1, write_buffer_size=4096)
+    local_path = tmp_path / "a.bin"
+
+    download = threading.Thread(
+        target=client.download_file, args=("test-bucket", "data/a.bin", str(local_path))
+    )
+    download.start()
+    deadline = time.monotonic() + 5
+    while not local_path.exists() and time.monotonic() < deadline:
+        time.sleep(0.01)
+    assert local_path.stat().st_size == len(content)
+    download.join()
+    assert local_path.read_bytes() == content
+
+
+def test_transfer_manager_priorities(tmp_path, fake_s3):
+    """Test that queued jobs start by priority, and their items are dispatched by
+    theirs."""
+
+    for key in ["block", "low", "high", "p0", "p1", "p2"]:
+        fake_s3.objects[f"data/{key}"] = key.encode()
+    fake_s3.stalls["data/block"] = 1
+    client = fake_s3.downloader(max_concurrent_downloads=1)
+    manager = robinzhon.TransferManager(max_running_jobs=1)
+
+    def submit(keys, priority=0):
+        items = [(f"data/{key}", str(tmp_path / key), *rest) for key, *rest in keys]
+        return manager.submit(client, "test-bucket", items, priority=priority)
+
+    jobs = [submit([("block",)])]
+    while fake_s3.gets != ["data/block"]:
+        time.sleep(0.01)
+    jobs += [submit([("low",)]), submit([("high",)], priority=5)]
+    jobs.append(submit([("p0", 0), ("p2", 2), ("p1", 1)], priority=-1))
+    for job in jobs:
+        assert job.result(timeout=30).failed_keys == []
+    assert fake_s3.gets == [
+        f"data/{key}" for key in ["block", "high", "low", "p2", "p1", "p0"]
+    ]
+
+
+def test_preflight(fake_s3):
+    """Test that preflight reports the bucket's region and what the client may do."""
+
+    client = robinzhon.S3Client(
+        "us-east-1", endpoint_url=fake_s3.endpoint_url, force_path_style=True
+    )
+    report = client.preflight("test-bucket")
+    assert report
+    assert report.credentials
+    assert report.bucket_exists
+    assert report.bucket_region == "us-east-1"
+    assert report.region_matches
+    assert report.can_list
+    assert report.can_write
+    assert report.problems == []
+    assert ".robinzhon-preflight" not in fake_s3.objects
+
+    fake_s3.errors[""] = (403, "AccessDenied")
+    fake_s3.errors[".robinzhon-preflight"] = (403, "AccessDenied")
+    report = client.preflight("test-bucket")
+    assert not report
+    assert report.bucket_exists
+    assert report.can_list is False
+    assert report.can_write is False
+    assert len(report.problems) == 2
+
+    report = client.preflight("test-bucket", check_write=False)
+    assert report.can_write is None
+
+
+def test_credentials_provider(tmp_path, fake_s3):
+    """Test that a credentials provider signs requests and is asked again as its
+    credentials near their expiry, and that a request S3 rejects as signed with
+    expired credentials is sent again."""
+
+    calls = []
+
+    def provider():
+        calls.append(threading.current_thread().name)
+        expiry = time.time() + (60 if len(calls) == 1 else 3600)
+        return ("KEY%d" % len(calls), "secret", "token", expiry)
+
+    fake_s3.objects["data/a.txt"] = b"a"
+    fake_s3.objects["data/b.txt"] = b"b"
+    config = robinzhon.S3Config(
+        "us-east-1",
+        endpoint_url=fake_s3.endpoint_url,
+        force_path_style=True,
+        credentials_provider=provider,
+    )
+    client = S3Downloader(config=config, retry_policy=RetryPolicy(max_attempts=1))
+    client.download_file("test-bucket", "data/a.txt", str(tmp_path / "a.txt"))
+    assert len(calls) == 1
+    assert calls[0] != threading.current_thread().name
+    assert "Credential=KEY1/" in fake_s3.requests[-1][2]["authorization"]
+    assert fake_s3.requests[-1][2]["x-amz-security-token"] == "token"
+
+    # The first credentials expire within the refresh margin; the next don't.
+    for _ in range(2):
+        client.download_file("test-bucket", "data/a.txt", str(tmp_path / "a.txt"))
+    assert len(calls) == 2
+    assert "Credential=KEY2/" in fake_s3.requests[-1][2]["authorization"]
+
+    fake_s3.errors["data/b.txt"] = (400, "ExpiredToken")
+    client.download_file("test-bucket", "data/b.txt", str(tmp_path / "b.txt"))
+    assert (tmp_path / "b.txt").read_bytes() == b"b"
+    paths = [path for _, path, _ in fake_s3.requests]
+    assert paths.count("/test-bucket/data/b.txt?x-id=GetObject") == 2
+
+def test_assume_role(tmp_path, fake_s3, monkeypatch):
+    """Test that a client assumes its role with the external ID and session name
+    given, and signs its requests with the role's credentials."""
+
+    monkeypatch.setenv("AWS_ENDPOINT_URL", fake_s3.endpoint_url)
+    fake_s3.objects["data/a.txt"] = b"a"
+    config = robinzhon.S3Config(
+        "us-east-1",
+        endpoint_url=fake_s3.endpoint_url,
+        force_path_style=True,
+        role_arn="arn:aws:iam::123456789012:role/export",
+        external_id="ext-1",
+        role_session_name="nightly",
+    )
+    client = S3Downloader(config=config, retry_policy=RetryPolicy(max_attempts=1))
+    client.download_file("test-bucket", "data/a.txt", str(tmp_path / "a.txt"))
+    assert (tmp_path / "a.txt").read_bytes() == b"a"
+
+    assert len(fake_s3.assumed) == 1
+    assert fake_s3.assumed[0]["Action"] == "AssumeRole"
+    assert fake_s3.assumed[0]["RoleArn"] == "arn:aws:iam::123456789012:role/export"
+    assert fake_s3.assumed[0]["ExternalId"] == "ext-1"
+    assert fake_s3.assumed[0]["RoleSessionName"] == "nightly"
+    _, _, headers = fake_s3.requests[-1]
+    assert "Credential=ASSUMED/" in headers["authorization"]
+    assert headers["x-amz-security-token"] == "role-token"
+
+
+def test_app_name_user_agent(tmp_path, fake_s3):
+    """Test that the application name is sent in the user agent of every request."""
+
+    fake_s3.objects["data/a.txt"] = b"a"
+    config = robinzhon.S3Config(
+        "us-east-1",
+        endpoint_url=fake_s3.endpoint_url,
+        force_path_style=True,
+        app_name="billing-export",
+    )
+    S3Downloader(config=config).download_file(
+        "test-bucket", "data/a.txt", str(tmp_path / "a.txt")
+    )
+    robinzhon.S3Uploader(config=config).upload_file(
+        "test-bucket", "data/b.txt", str(tmp_path / "a.txt")
+    )
+    assert len(fake_s3.requests) == 2
+    for _, _, headers in fake_s3.requests:
+        assert "app/billing-export" in headers["x-amz-user-agent"]
+
+    try:
+        robinzhon.S3Config("us-east-1", app_name="billing export")
+        assert False, "Expected exception was not raised"
+    except ValueError as e:
+        assert "app_name" in str(e)
+
+
+def test_failures_carry_request_ids(tmp_path, fake_s3):
+    """Test that failed items and raised errors carry the request IDs S3 sent."""
+
+    fake_s3.objects["data/a.txt"] = b"a"
+    fake_s3.objects["data/b.txt"] = b"b"
+    fake_s3.errors["data/a.txt"] = (403, "AccessDenied")
+    client = fake_s3.downloader()
+    results = client.download_multiple_files_with_paths(
+        "test-bucket",
+        [("data/a.txt", str(tmp_path / "a.txt")), ("data/b.txt", str(tmp_path / "b.txt"))],
+    )
+    assert results.successful == [str(tmp_path / "b.txt")]
+    [failed] = results.failed
+    assert failed.error_code == "AccessDenied"
+    assert failed.http_status == 403
+    assert failed.request_id == "REQ123"
+    assert failed.extended_request_id == "HOST456"
+
+    fake_s3.errors["data/a.txt"] = (403, "AccessDenied")
+    try:
+        client.download_file("test-bucket", "data/a.txt", str(tmp_path / "a.txt"))
+        assert False, "Expected exception was not raised"
+    except robinzhon.AccessDeniedError as e:
+        assert e.request_id == "REQ123"
+        assert e.extended_request_id == "HOST456"
+
+
+def test_client_encryption_round_trip(tmp_path, fake_s3):
+    """Test that uploads are stored in the S3 Encryption Client v2 format, streamed
+    through the cipher, and that downloads decrypt and authenticate them."""
+
+    try:
+        from cryptography.hazmat.primitives.ciphers.aead import AESGCM
+    except ImportError:
+        AESGCM = None
+
+    def check_stored(key, content):
+        metadata = {
+            name.lower().removeprefix("x-amz-meta-"): value
+            for name, value in fake_s3.metadata[key].items()
+        }
+        assert metadata["x-amz-cek-alg"] == "AES/GCM/NoPadding"
+        assert metadata["x-amz-wrap-alg"] == "kms+context"
+        assert json.loads(metadata["x-amz-matdesc"]) == {
+            "aws:x-amz-cek-alg": "AES/GCM/NoPadding",
+            "team": "data",
+        }
+        assert len(fake_s3.objects[key]) == len(content) + 16
+        assert content not in fake_s3.objects[key]
+        # Any AES-GCM implementation decrypts the body with the unwrapped key.
+        if AESGCM is not None:
+            data_key = base64.b64decode(metadata["x-amz-key-v2"])[len(b"wrapped:") :]
+            iv = base64.b64decode(metadata["x-amz-iv"])
+            assert AESGCM(data_key).decrypt(iv, fake_s3.objects[key], None) == content
+        return metadata
+
+    encryption = robinzhon.ClientEncryption(
+        "alias/backups",
+        encryption_context={"team": "data"},
+        kms_endpoint_url=fake_s3.endpoint_url,
+    )
+    content = os.urandom(6 * 1024 * 1024 + 5)
+    local_file = tmp_path / "secret.bin"
+    local_file.write_bytes(content)
+    uploader = fake_s3.uploader(client_encryption=encryption, part_size=5 * 1024 * 1024)
+    uploader.upload_file("test-bucket", "data/secret.bin", str(local_file))
+    metadata = check_stored("data/secret.bin", content)
+    assert metadata["x-amz-unencrypted-content-length"] == str(len(content))
+    assert fake_s3.kms[0][0] == "GenerateDataKey"
+    assert fake_s3.kms[0][1]["KeyId"] == "alias/backups"
+
+    # A stream longer than a part is encrypted part by part.
+    chunks = [content[i : i + 1000 * 1000] for i in range(0, len(content), 1000 * 1000)]
+    uploader.upload_fileobj("test-bucket", "data/stream.bin", iter(chunks))
+    assert [key for key, _ in fake_s3.parts] == ["data/stream.bin"] * 2
+    check_stored("data/stream.bin", content)
+
+    downloader = fake_s3.downloader(client_encryption=encryption)
+    for key in ["data/secret.bin", "data/stream.bin"]:
+        local_path = tmp_path / "out.bin"
+        downloader.download_file("test-bucket", key, str(local_path))
+        assert local_path.read_bytes() == content
+        local_path.unlink()
+    assert fake_s3.kms[-1][0] == "Decrypt"
+    assert fake_s3.kms[-1][1]["EncryptionContext"]["team"] == "data"
+    assert downloader.download_bytes("test-bucket", "data/secret.bin") == content
+
+    tampered = bytearray(fake_s3.objects["data/secret.bin"])
+    tampered[1000] ^= 1
+    fake_s3.objects["data/secret.bin"] = bytes(tampered)
+    with pytest.raises(RuntimeError, match="doesn't authenticate"):
+        downloader.download_file("test-bucket", "data/secret.bin", str(local_path))
+    assert not local_path.exists()
+
+    # Objects without encryption metadata are left as they are, unless required.
+    fake_s3.objects["data/plain.txt"] = b"plain"
+    downloader.download_file("test-bucket", "data/plain.txt", str(local_path))
+    assert local_path.read_bytes() == b"plain"
+    local_path.unlink()
+    required = fake_s3.downloader(
+        client_encryption=robinzhon.ClientEncryption(
+            kms_endpoint_url=fake_s3.endpoint_url, require_encryption=True
+        )
+    )
+    with pytest.raises(RuntimeError, match="require_encryption"):
+        required.download_file("test-bucket", "data/plain.txt", str(local_path))
+    assert not local_path.exists()
+
+
+def test_session_resumes_in_flight_items(tmp_path, fake_s3):
+    """Test that a resumed session continues the multipart upload and the
+    download that were in flight, from what it checkpointed."""
+
+    part_size = 1024 * 1024
+    content = os.urandom(3 * part_size - 100)
+    local_path = tmp_path / "big.bin"
+    local_path.write_bytes(content)
+    path = tmp_path / "session.json"
+
+    def checkpoint(**upload):
+        item = {"source": str(local_path), "destination": "big.bin", "status": "pending"}
+        item["upload"] = {"file_size": len(content), "part_size": part_size, **upload}
+        path.write_text(
+            json.dumps({"kind": "upload", "bucket_name": "test-bucket", "items": [item]})
+        )
+
+    fake_s3.uploads["upload-prior"] = {1: content[:part_size]}
+    first = {"part_number": 1, "e_tag": '"%s"' % hashlib.md5(content[:part_size]).hexdigest()}
+    checkpoint(upload_id="upload-prior", parts=[first])
+    session = robinzhon.TransferSession(str(path))
+    results = session.resume(fake_s3.uploader(part_size=part_size))
+    assert results.successful == [str(local_path)]
+    assert fake_s3.objects["big.bin"] == content
+    assert sorted(fake_s3.parts) == [("big.bin", 2), ("big.bin", 3)]
+    assert not [r for r in fake_s3.requests if r[0] == "POST" and "uploads" in r[1]]
+    assert session.is_complete()
+    assert "upload-prior" not in path.read_text()
+
+    # An upload S3 no longer holds is started again.
+    fake_s3.parts.clear()
+    checkpoint(upload_id="upload-expired", parts=[first])
+    results = robinzhon.TransferSession(str(path)).resume(
+        fake_s3.uploader(part_size=part_size)
+    )
+    assert results.successful == [str(local_path)]
+    assert fake_s3.objects["big.bin"] == content
+    assert sorted(fake_s3.parts) == [("big.bin", 1), ("big.bin", 2), ("big.bin", 3)]
+
+    fake_s3.objects["data/a.bin"] = content
+    download_path = tmp_path / "a.bin"
+    download_path.write_bytes(content[:1000] + bytes(len(content) - 1000))
+    item = {"source": "data/a.bin", "destination": str(download_path), "status": "pending"}
+    item["download"] = {"etag": '"%s"' % hashlib.md5(content).hexdigest(), "offset": 1000}
+    path.write_text(
+        json.dumps({"kind": "download", "bucket_name": "test-bucket", "items": [item]})
+    )
+    results = robinzhon.TransferSession(str(path)).resume(
+        fake_s3.downloader(part_size=part_size, part_concurrency=2)
+    )
+    assert results.successful == [str(download_path)]
+    assert download_path.read_bytes() == content
+    assert fake_s3.ranges == ["bytes=1000-"]
+
+
+def test_bandwidth_limit_paces_transfers(tmp_path, fake_s3):
+    """Test that max_bandwidth_bytes_per_sec paces the bodies of concurrent
+    uploads and downloads as they are sent, whole files and parts alike."""
+
+    rate = 256 * 1024
+    paths = []
+    for name in ("a.bin", "b.bin"):
+        path = tmp_path / name
+        path.write_bytes(os.urandom(rate * 3 // 2))
+        paths.append(path)
+
+    # The first second of traffic goes out as a burst, the rest at `rate`.
+    client = fake_s3.uploader(max_bandwidth_bytes_per_sec=rate)
+    started = time.monotonic()
+    results = client.upload_multiple_files(
+        "test-bucket", [(str(path), path.name) for path in paths]
+    )
+    assert results.is_complete_success()
+    # The bodies arrive paced, rather than at full speed with a wait after.
+    assert max(at for _, at in fake_s3.received) - started >= 1.5
+    assert time.monotonic() - started < 5
+
+    # Parts in flight together share the limit too.
+    content = os.urandom(rate * 3)
+    client = fake_s3.uploader(max_bandwidth_bytes_per_sec=rate, part_size=rate // 2)
+    started = time.monotonic()
+    assert client.upload_fileobj("test-bucket", "c.bin", [content]) == "c.bin"
+    assert sorted(fake_s3.parts) == [("c.bin", number) for number in range(1, 7)]
+    assert time.monotonic() - started >= 1.5
+    assert fake_s3.objects["c.bin"] == content
+
+    client = fake_s3.downloader(max_bandwidth_bytes_per_sec=rate, part_concurrency=1)
+    started = time.monotonic()
+    results = client.download_multiple_files(
+        "test-bucket", ["a.bin", "b.bin"], str(tmp_path / "copy")
+    )
+    assert results.is_complete_success()
+    assert time.monotonic() - started >= 1.5
+    assert (tmp_path / "copy" / "a.bin").read_bytes() == paths[0].read_bytes()
+
+
+def test_upload_directory_filters(tmp_path, fake_s3):
+    """Test that upload_directory uploads only the files its patterns select."""
+
+    local_dir = tmp_path / "v2"
+    for relative_path in (
+        "a.parquet",
+        "sub/b.parquet",
+        "sub/c.csv",
+        "tmp/x.parquet",
+        "sub/tmp/y.parquet",
+    ):
+        (local_dir / relative_path).parent.mkdir(parents=True, exist_ok=True)
+        (local_dir / relative_path).write_bytes(relative_path.encode())
+    client = fake_s3.uploader()
+
+    results = client.upload_directory(str(local_dir), "test-bucket", "data")
+    assert results.is_complete_success()
+    assert len(fake_s3.objects) == 5
+    assert fake_s3.objects["data/sub/c.csv"] == b"sub/c.csv"
+
+    fake_s3.objects.clear()
+    results = client.upload_directory(
+        str(local_dir), "test-bucket", "data/", include=["*.parquet"]
+    )
+    assert results.total_count() == 4
+    assert sorted(fake_s3.objects) == [
+        "data/a.parquet",
+        "data/sub/b.parquet",
+        "data/sub/tmp/y.parquet",
+        "data/tmp/x.parquet",
+    ]
+
+    fake_s3.objects.clear()
+    results = client.upload_directory(
+        str(local_dir),
+        "test-bucket",
+        "data",
+        include=["*.parquet"],
+        exclude=["**/tmp/*"],
+    )
+    assert sorted(fake_s3.objects) == ["data/a.parquet", "data/sub/b.parquet"]
+    assert sorted(results.successful) == [
+        str(local_dir / "a.parquet"),
+        str(local_dir / "sub" / "b.parquet"),
+    ]
+
+    fake_s3.objects.clear()
+    plan = client.upload_directory(
+        str(local_dir), "test-bucket", "data", exclude=["**/tmp/*"], dry_run=True
+    )
+    assert len(plan.transfers) == 3
+    assert not fake_s3.objects
+
+    with pytest.raises(ValueError):
+        client.upload_directory(str(local_dir), "test-bucket", "data", include=["["])
+    with pytest.raises(robinzhon.RobinzhonError, match="missing"):
+        client.upload_directory(str(tmp_path / "missing"), "test-bucket", "data")
+
+
+def test_download_check_space_refuses_large_batches(tmp_path, fake_s3):
+    """Test that check_space raises before downloading a batch that doesn't fit."""
+
+    fake_s3.objects["data/small.txt"] = b"hello"
+    fake_s3.objects["data/huge.bin"] = 2**62
+    client = fake_s3.downloader()
+    base_directory = tmp_path / "out"
+
+    with pytest.raises(robinzhon.InsufficientSpaceError) as excinfo:
+        client.download_prefix(
+            "test-bucket", "data/", str(base_directory), check_space=True
+        )
+    assert excinfo.value.required_bytes == 2**62 + 5
+    assert excinfo.value.available_bytes < excinfo.value.required_bytes
+    assert excinfo.value.path == str(base_directory)
+    assert isinstance(excinfo.value, robinzhon.RobinzhonError)
+
+    # Keyed batches size their objects with HEAD requests instead.
+    with pytest.raises(robinzhon.InsufficientSpaceError, match="Not enough space"):
+        client.download_multiple_files(
+            "test-bucket",
+            ["data/small.txt", "data/huge.bin"],
+            str(base_directory),
+            check_space=True,
+        )
+    assert fake_s3.gets == []
+    assert not any(base_directory.rglob("*"))
+
+    del fake_s3.objects["data/huge.bin"]
+    results = client.download_prefix(
+        "test-bucket", "data/", str(base_directory), check_space=True
+    )
+    assert results.successful == [str(base_directory / "small.txt")]
+    assert (base_directory / "small.txt").read_bytes() == b"hello"
+
+
+def test_download_check_space(tmp_path):
+    """Test that check_space reads the whole batch and still downloads it when it fits."""
+
+    client = S3Downloader(
+        "us-east-1",
+        endpoint_url="http://127.0.0.1:1",
+        retry_policy=RetryPolicy(max_attempts=1),
+    )
+
+    # Objects that can't be looked up aren't counted, so the batch runs and
+    # its items fail as they would without the check.
+    keys = (key for key in ["a.txt", "b/c.txt"])
+    results = client.download_multiple_files(
+        "test-bucket", keys, str(tmp_path / "out"), check_space=True
+    )
+    assert sorted(results.failed_keys) == ["a.txt", "b/c.txt"]
+
+    with pytest.raises(robinzhon.RobinzhonError):
+        client.download_prefix(
+            "test-bucket", "data/", str(tmp_path / "prefix"), check_space=True
+        )
+    assert not (tmp_path / "prefix").exists()
+
+
+def test_download_scheduling(tmp_path):
+    """Test that scheduling is validated and doesn't drop items it can't size."""
+
+    client = S3Downloader(
+        "us-east-1",
+        endpoint_url="http://127.0.0.1:1",
+        retry_policy=RetryPolicy(max_attempts=1),
+    )
+
+    # Objects whose size can't be looked up count as empty and still run.
+    keys = (key for key in ["a.txt", "b/c.txt", "d.txt"])
+    results = client.download_multiple_files(
+        "test-bucket", keys, str(tmp_path / "out"), scheduling="largest_first"
+    )
+    assert sorted(results.failed_keys) == ["a.txt", "b/c.txt", "d.txt"]
+
+    with pytest.raises(ValueError, match="Invalid scheduling 'biggest'"):
+        client.download_multiple_files(
+            "test-bucket", ["a.txt"], str(tmp_path / "out"), scheduling="biggest"
+        )
+
+
+def test_download_scheduling_order(tmp_path, fake_s3):
+    """Test that scheduling starts downloads by size, keeping ties in order."""
+
+    for key, size in [("a.bin", 3), ("b.bin", 10), ("c.bin", 3), ("d.bin", 1)]:
+        fake_s3.objects["data/" + key] = b"x" * size
+    client = fake_s3.downloader(max_concurrent_downloads=1)
+
+    def started(scheduling, download):
+        fake_s3.gets.clear()
+        download(scheduling, str(tmp_path / scheduling))
+        return [key.removeprefix("data/") for key in dict.fromkeys(fake_s3.gets)]
+
+    def prefix(scheduling, base_directory):
+        client.download_prefix(
+            "test-bucket", "data/", base_directory, scheduling=scheduling
+        )
+
+    assert started("fifo", prefix) == ["a.bin", "b.bin", "c.bin", "d.bin"]
+    assert started("largest_first", prefix) == ["b.bin", "a.bin", "c.bin", "d.bin"]
+    assert started("smallest_first", prefix) == ["d.bin", "a.bin", "c.bin", "b.bin"]
+
+    # Keys given rather than listed are sized with HEAD requests, and ties keep
+    # the order they were given in.
+    def keys(scheduling, base_directory):
+        client.download_multiple_files(
+            "test-bucket",
+            ["data/c.bin", "data/b.bin", "data/a.bin", "data/d.bin"],
+            base_directory,
+            scheduling=scheduling,
+        )
+
+    assert started("largest_first", keys) == ["b.bin", "c.bin", "a.bin", "d.bin"]
+    assert started("smallest_first", keys) == ["d.bin", "c.bin", "a.bin", "b.bin"]
+
+
+def test_max_in_flight_bytes(tmp_path):
+    """Test that a memory budget smaller than the buffers still lets downloads run."""
+
+    with pytest.raises(ValueError, match="max_in_flight_bytes"):
+        S3Downloader("us-east-1", max_in_flight_bytes=0)
+
+    # Streams needing more than the whole budget wait for all of it instead of
+    # failing; unreachable objects fail as they would without one.
+    client = S3Downloader(
+        "us-east-1",
+        endpoint_url="http://127.0.0.1:1",
+        retry_policy=RetryPolicy(max_attempts=1),
+        write_buffer_size=1 << 20,
+        max_in_flight_bytes=1024,
+    )
+    results = client.download_multiple_files(
+        "test-bucket", ["a.txt", "b.txt", "c.txt"], str(tmp_path)
+    )
+    assert sorted(results.failed_keys) == ["a.txt", "b.txt", "c.txt"]
+    assert client.download_multiple_bytes("test-bucket", ["a.txt"]) == {}
+
+    restored = pickle.loads(pickle.dumps(client))
+    assert restored.download_multiple_files(
+        "test-bucket", ["a.txt"], str(tmp_path)
+    ).failed_keys == ["a.txt"]
+
+
+def test_max_in_flight_bytes_blocks_downloads(fake_s3):
+    """Test that downloads wait for room in the memory budget before reading."""
+
+    def sent_after_first_body(**kwargs):
+        # A body waiting for room stalls on its connection a few MiB in, so most
+        # of it is sent only after the body read before it is done.
+        fake_s3.writes.clear()
+        client = fake_s3.downloader(write_buffer_size=1 << 20, **kwargs)
+        keys = ["a.bin", "b.bin"]
+        assert client.download_multiple_bytes("test-bucket", keys) == fake_s3.objects
+        writes = fake_s3.writes
+        done = {key: max(i for i, k in enumerate(writes) if k == key) for key in keys}
+        first = min(keys, key=done.get)
+        return len(writes) - 1 - done[first]
+
+    fake_s3.objects = {"a.bin": b"a" * (16 << 20), "b.bin": b"b" * (16 << 20)}
+    assert sent_after_first_body() < 8
+    # Bodies larger than the whole budget take all of it and run one at a time.
+    assert sent_after_first_body(max_in_flight_bytes=1 << 20) >= 8
+    # Chunked bodies, which announce no length, still count against it, and wait
+    # for more room as they grow past a write buffer.
+    fake_s3.sized = False
+    assert sent_after_first_body(max_in_flight_bytes=1 << 20) >= 8
+    fake_s3.objects = {"a.bin": b"a" * (32 << 20), "b.bin": b"b" * (32 << 20)}
+    assert sent_after_first_body() < 8
+    assert sent_after_first_body(max_in_flight_bytes=4 << 20) >= 8
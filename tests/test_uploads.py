@@ -1,4 +1,34 @@
-from robinzhon import S3Uploader, Results
+import asyncio
+import hashlib
+import io
+import json
+import pickle
+import time
+import urllib.parse
+from concurrent.futures import ThreadPoolExecutor
+
+import pytest
+
+from robinzhon import (
+    ArchiveMode,
+    ClientEncryption,
+    PreconditionFailedError,
+    RetryPolicy,
+    RobinzhonError,
+    S3Client,
+    S3Config,
+    S3Downloader,
+    S3Uploader,
+    Results,
+    SsoLoginRequiredError,
+    TransferLimiter,
+    TransferPlan,
+    TransferProgress,
+    TransferManager,
+    TransferSession,
+    UploadOptions,
+    verify_audit_log,
+)
 
 
 def test_basic_upload(mocker):
@@ -90,3 +120,1210 @@ def test_upload_failure(mocker):
         assert "S3 upload failed" in str(e)
 
     mock_upload.assert_called_once()
+
+
+def test_shared_config(mocker):
+    """Test sharing one S3Config between a downloader and an uploader."""
+
+    config = S3Config("us-east-1", endpoint_url="http://localhost:9000")
+    assert config.region_name == "us-east-1"
+    assert config.endpoint_url == "http://localhost:9000"
+    assert not config.force_path_style
+    assert not config.anonymous
+
+    anonymous = S3Config("us-east-1", anonymous=True)
+    assert anonymous.anonymous
+    assert "anonymous=True" in repr(anonymous)
+
+    dualstack = S3Config("us-east-1", use_dualstack_endpoint=True)
+    assert dualstack.use_dualstack_endpoint
+    assert not dualstack.use_fips_endpoint
+    assert not dualstack.use_accelerate_endpoint
+    for kwargs in (
+        {"use_accelerate_endpoint": True, "force_path_style": True},
+        {"use_accelerate_endpoint": True, "use_fips_endpoint": True},
+    ):
+        try:
+            S3Config("us-east-1", **kwargs)
+            assert False, "Expected exception was not raised"
+        except ValueError:
+            pass
+
+    tuned = S3Config(
+        "us-east-1",
+        connect_timeout=2.5,
+        operation_timeout=60,
+        max_idle_connections_per_host=16,
+    )
+    assert tuned.connect_timeout == 2.5
+    assert tuned.read_timeout is None
+    assert tuned.operation_timeout == 60.0
+    assert tuned.max_idle_connections_per_host == 16
+    try:
+        S3Config("us-east-1", read_timeout=0)
+        assert False, "Expected exception was not raised"
+    except ValueError:
+        pass
+
+    role = S3Config(
+        "us-east-1",
+        role_arn="arn:aws:iam::123456789012:role/ingest",
+        external_id="partner-42",
+        role_session_name="nightly-export",
+        role_duration=900,
+    )
+    assert role.role_arn == "arn:aws:iam::123456789012:role/ingest"
+    assert role.role_session_name == "nightly-export"
+    assert role.external_id == "partner-42"
+    assert role.role_duration == 900.0
+    assert config.role_arn is None
+    for kwargs in (
+        {"role_session_name": "ingest"},
+        {"external_id": "partner-42"},
+        {"role_arn": "arn:aws:iam::123456789012:role/ingest", "role_duration": 60},
+        {"role_arn": "arn:aws:iam::123456789012:role/ingest", "anonymous": True},
+    ):
+        try:
+            S3Config("us-east-1", **kwargs)
+            assert False, "Expected exception was not raised"
+        except ValueError:
+            pass
+
+    mock_upload = mocker.patch("robinzhon.S3Uploader.upload_file")
+    mock_upload.return_value = "./local.txt"
+
+    uploader = S3Uploader(config=config)
+    downloader = S3Downloader(config=config)
+
+    for kwargs in ({"endpoint_url": "http://127.0.0.1:1"}, {"force_path_style": True}):
+        with pytest.raises(ValueError, match="combined with config"):
+            S3Uploader(config=config, **kwargs)
+        with pytest.raises(ValueError, match="combined with config"):
+            S3Downloader(config=config, **kwargs)
+
+    assert uploader.upload_file("test-bucket", "key", "./local.txt") == (
+        "./local.txt"
+    )
+    assert downloader is not None
+
+
+def test_sso_profile_without_session(tmp_path, monkeypatch):
+    """Test that an SSO profile nobody logged in to asks for `aws sso login`."""
+
+    config_file = tmp_path / "config"
+    config_file.write_text(
+        "[profile sso-test]\n"
+        "sso_start_url = https://example.awsapps.com/start\n"
+        "sso_region = us-east-1\n"
+        "sso_account_id = 123456789012\n"
+        "sso_role_name = ReadOnly\n"
+    )
+    for name in ("AWS_ACCESS_KEY_ID", "AWS_SECRET_ACCESS_KEY", "AWS_SESSION_TOKEN"):
+        monkeypatch.delenv(name, raising=False)
+    monkeypatch.setenv("HOME", str(tmp_path))
+    monkeypatch.setenv("AWS_CONFIG_FILE", str(config_file))
+    monkeypatch.setenv("AWS_SHARED_CREDENTIALS_FILE", str(tmp_path / "credentials"))
+
+    config = S3Config(
+        "us-east-1", endpoint_url="http://127.0.0.1:1", profile_name="sso-test"
+    )
+    assert config.profile_name == "sso-test"
+    assert pickle.loads(pickle.dumps(config)).profile_name == "sso-test"
+
+    local_path = tmp_path / "a.txt"
+    local_path.write_text("a")
+    uploader = S3Uploader(config=config, retry_policy=RetryPolicy(max_attempts=1))
+    with pytest.raises(SsoLoginRequiredError, match="aws sso login --profile sso-test"):
+        uploader.upload_file("test-bucket", "a.txt", str(local_path))
+    assert issubclass(SsoLoginRequiredError, RobinzhonError)
+
+
+def test_credentials_provider(tmp_path):
+    """Test that requests are signed with credentials from a Python callable."""
+
+    calls = []
+
+    def provider():
+        calls.append(1)
+        return "AKIDEXAMPLE", "secret", None, time.time() + 3600
+
+    config = S3Config(
+        "us-east-1", endpoint_url="http://127.0.0.1:1", credentials_provider=provider
+    )
+    assert config.credentials_provider is provider
+
+    local_path = tmp_path / "a.txt"
+    local_path.write_text("a")
+    uploader = S3Uploader(config=config, retry_policy=RetryPolicy(max_attempts=1))
+    uploads = uploader.upload_multiple_files(
+        "test-bucket", [(str(local_path), "a.txt"), (str(local_path), "b.txt")]
+    )
+    assert len(uploads.failed) == 2
+    assert len(calls) == 1
+
+    def broken():
+        raise RuntimeError("vault is sealed")
+
+    uploader = S3Uploader(
+        config=S3Config(
+            "us-east-1", endpoint_url="http://127.0.0.1:1", credentials_provider=broken
+        ),
+        retry_policy=RetryPolicy(max_attempts=1),
+    )
+    with pytest.raises(RobinzhonError, match="vault is sealed"):
+        uploader.upload_file("test-bucket", "a.txt", str(local_path))
+
+    with pytest.raises(TypeError):
+        S3Config("us-east-1", credentials_provider="not callable")
+    with pytest.raises(ValueError):
+        S3Config("us-east-1", anonymous=True, credentials_provider=provider)
+
+
+def test_config_from_boto3(tmp_path, monkeypatch):
+    """Test that a boto3 session's region, credentials and endpoint are reused."""
+
+    boto3 = pytest.importorskip("boto3")
+    monkeypatch.setenv("AWS_ENDPOINT_URL", "http://127.0.0.1:1")
+    session = boto3.Session(
+        aws_access_key_id="AKIDEXAMPLE",
+        aws_secret_access_key="secret",
+        region_name="eu-west-1",
+    )
+    config = S3Config.from_boto3(session, force_path_style=True)
+    assert config.region_name == "eu-west-1"
+    assert config.endpoint_url == "http://127.0.0.1:1"
+    assert config.force_path_style
+    assert config.credentials_provider is not None
+
+    local_path = tmp_path / "a.txt"
+    local_path.write_text("a")
+    uploader = S3Uploader(config=config, retry_policy=RetryPolicy(max_attempts=1))
+    with pytest.raises(RobinzhonError):
+        uploader.upload_file("test-bucket", "a.txt", str(local_path))
+
+    frozen = session.get_credentials().get_frozen_credentials()
+    config = S3Config.from_boto3(frozen, region_name="us-east-1")
+    assert config.region_name == "us-east-1"
+    with pytest.raises(ValueError):
+        S3Config.from_boto3(frozen)
+
+
+def test_request_hooks(tmp_path, monkeypatch):
+    """Test that headers and hooks are applied to every request."""
+
+    # Requests are only sent, and seen, once they can be signed.
+    monkeypatch.setenv("AWS_ACCESS_KEY_ID", "test")
+    monkeypatch.setenv("AWS_SECRET_ACCESS_KEY", "test")
+
+    seen = []
+
+    def before_request(method, url, headers):
+        seen.append((method, url, headers))
+        return {"x-request-origin": "tests"}
+
+    config = S3Config(
+        "us-east-1",
+        endpoint_url="http://127.0.0.1:1",
+        headers={"x-trace-id": "abc"},
+        before_request=before_request,
+        after_response=lambda status, headers: None,
+    )
+    assert config.headers == {"x-trace-id": "abc"}
+    assert config.before_request is before_request
+
+    local_path = tmp_path / "a.txt"
+    local_path.write_text("a")
+    uploader = S3Uploader(config=config, retry_policy=RetryPolicy(max_attempts=1))
+    with pytest.raises(RobinzhonError):
+        uploader.upload_file("test-bucket", "a.txt", str(local_path))
+    method, url, headers = seen[0]
+    assert method == "PUT"
+    assert "a.txt" in url
+    assert headers["x-trace-id"] == "abc"
+
+    def broken(method, url, headers):
+        raise RuntimeError("no trace context")
+
+    uploader = S3Uploader(
+        config=S3Config(
+            "us-east-1", endpoint_url="http://127.0.0.1:1", before_request=broken
+        ),
+        retry_policy=RetryPolicy(max_attempts=1),
+    )
+    with pytest.raises(RobinzhonError, match="no trace context"):
+        uploader.upload_file("test-bucket", "a.txt", str(local_path))
+
+    with pytest.raises(ValueError):
+        S3Config("us-east-1", headers={"bad header": "x"})
+
+    named = S3Config(
+        "us-east-1", endpoint_url="http://127.0.0.1:1", app_name="billing-export"
+    )
+    assert named.app_name == "billing-export"
+    assert pickle.loads(pickle.dumps(named)).app_name == "billing-export"
+    with pytest.raises(ValueError):
+        S3Config("us-east-1", app_name="billing export")
+    with pytest.raises(TypeError):
+        S3Config("us-east-1", after_response="not callable")
+
+
+def test_missing_region_and_config():
+    """Test that a region or a config is required."""
+
+    try:
+        S3Uploader()
+        assert False, "Expected exception was not raised"
+    except ValueError as e:
+        assert "region_name" in str(e)
+
+
+def test_runtime_threads():
+    """Test configuring the runtime worker threads."""
+
+    assert S3Uploader("us-east-1", runtime_threads=4) is not None
+    assert S3Downloader("us-east-1", runtime_threads=2) is not None
+
+    try:
+        S3Uploader("us-east-1", runtime_threads=0)
+        assert False, "Expected exception was not raised"
+    except ValueError as e:
+        assert "runtime_threads" in str(e)
+
+
+def test_bandwidth_limit():
+    """Test configuring the bandwidth cap."""
+
+    assert S3Uploader("us-east-1", max_bandwidth_bytes_per_sec=1024) is not None
+    assert S3Downloader("us-east-1", max_bandwidth_bytes_per_sec=1024) is not None
+
+    for client in (S3Uploader, S3Downloader):
+        try:
+            client("us-east-1", max_bandwidth_bytes_per_sec=0)
+            assert False, "Expected exception was not raised"
+        except ValueError as e:
+            assert "max_bandwidth_bytes_per_sec" in str(e)
+
+
+def test_request_rate_limit():
+    """Test configuring the requests-per-second limit."""
+
+    assert S3Uploader("us-east-1", max_requests_per_sec=100) is not None
+    assert (
+        S3Downloader("us-east-1", max_requests_per_sec=0.5, request_burst=10)
+        is not None
+    )
+
+    for kwargs in (
+        {"max_requests_per_sec": 0},
+        {"max_requests_per_sec": -1.0},
+        {"max_requests_per_sec": 10, "request_burst": 0},
+        {"request_burst": 5},
+    ):
+        try:
+            S3Uploader("us-east-1", **kwargs)
+            assert False, "Expected exception was not raised"
+        except ValueError:
+            pass
+
+
+def test_transfer_limiter():
+    """Test sharing a transfer limiter between instances."""
+
+    limiter = TransferLimiter(8)
+    assert limiter.max_in_flight == 8
+    assert limiter.in_flight == 0
+    assert "max_in_flight=8" in repr(limiter)
+
+    assert S3Uploader("us-east-1", limiter=limiter) is not None
+    assert S3Downloader("us-east-1", limiter=limiter) is not None
+
+    try:
+        TransferLimiter(0)
+        assert False, "Expected exception was not raised"
+    except ValueError as e:
+        assert "max_in_flight" in str(e)
+
+
+def test_retry_policy():
+    """Test retry policy defaults, validation and constructor wiring."""
+
+    policy = RetryPolicy()
+    assert policy.max_attempts == 3
+    assert policy.jitter
+
+    custom = RetryPolicy(max_attempts=5, base_delay=0.5, jitter=False)
+    assert S3Uploader("us-east-1", retry_policy=custom) is not None
+    assert S3Downloader("us-east-1", retry_policy=custom) is not None
+
+    try:
+        RetryPolicy(max_attempts=0)
+        assert False, "Expected exception was not raised"
+    except ValueError as e:
+        assert "max_attempts" in str(e)
+
+    for kwargs in (
+        {"base_delay": -1.0},
+        {"base_delay": float("nan")},
+        {"max_delay": float("inf")},
+    ):
+        with pytest.raises(ValueError, match="finite and not negative"):
+            RetryPolicy(**kwargs)
+
+
+def test_upload_fileobj_validation():
+    """Test that upload_fileobj rejects sources it cannot read from."""
+
+    client = S3Uploader(
+        "us-east-1",
+        endpoint_url="http://127.0.0.1:1",
+        retry_policy=RetryPolicy(max_attempts=1),
+    )
+
+    try:
+        client.upload_fileobj("test-bucket", "key", 42)
+        assert False, "Expected exception was not raised"
+    except TypeError as e:
+        assert "int" in str(e)
+
+    try:
+        client.upload_fileobj("test-bucket", "key", ["not bytes"])
+        assert False, "Expected exception was not raised"
+    except TypeError as e:
+        assert "str" in str(e)
+
+    try:
+        client.upload_fileobj("test-bucket", "key", io.BytesIO(b"data"))
+        assert False, "Expected exception was not raised"
+    except RobinzhonError as e:
+        assert "key" in str(e)
+
+    try:
+        S3Uploader("us-east-1", part_size=0)
+        assert False, "Expected exception was not raised"
+    except ValueError as e:
+        assert "part_size" in str(e)
+
+
+def test_sync_up_missing_directory(tmp_path):
+    """Test that sync_up refuses a missing directory instead of treating it as empty."""
+
+    client = S3Uploader(
+        "us-east-1",
+        endpoint_url="http://127.0.0.1:1",
+        retry_policy=RetryPolicy(max_attempts=1),
+    )
+
+    try:
+        client.sync_up(str(tmp_path / "missing"), "test-bucket", "site", delete=True)
+        assert False, "Expected exception was not raised"
+    except RobinzhonError as e:
+        assert "missing" in str(e)
+
+
+def test_create_only_uploads(mocker, tmp_path):
+    """Test that create-only uploads pass the condition along and fail like plain ones."""
+
+    mock_upload = mocker.patch("robinzhon.S3Uploader.upload_file")
+    mock_upload.side_effect = PreconditionFailedError("exists")
+
+    client = S3Uploader("us-east-1")
+
+    try:
+        client.upload_file("test-bucket", "key", "./file.txt", if_none_match=True)
+        assert False, "Expected exception was not raised"
+    except PreconditionFailedError:
+        pass
+    mock_upload.assert_called_once_with("test-bucket", "key", "./file.txt", if_none_match=True)
+
+    local_path = tmp_path / "a.txt"
+    local_path.write_text("a")
+    client = S3Uploader(
+        "us-east-1",
+        endpoint_url="http://127.0.0.1:1",
+        retry_policy=RetryPolicy(max_attempts=1),
+    )
+    results = client.upload_multiple_files(
+        "test-bucket", [(str(local_path), "a.txt")], if_none_match=True
+    )
+    assert results.failed_keys == [str(local_path)]
+
+
+def test_upload_options(tmp_path):
+    """Test UploadOptions defaults and that per-item options are accepted in batches."""
+
+    options = UploadOptions()
+    assert options.metadata is None
+    assert options.content_type is None
+    assert options.guess_content_type is True
+
+    options = UploadOptions(
+        metadata={"owner": "data-team"},
+        content_type="text/csv",
+        cache_control="max-age=60",
+        guess_content_type=False,
+    )
+    assert options.metadata == {"owner": "data-team"}
+    assert options.content_type == "text/csv"
+    assert options.cache_control == "max-age=60"
+    assert "guess_content_type=False" in repr(options)
+    assert UploadOptions(storage_class="GLACIER").storage_class == "GLACIER"
+    try:
+        UploadOptions(storage_class="glacier")
+        assert False, "Expected exception was not raised"
+    except ValueError:
+        pass
+
+    delivery = UploadOptions(
+        acl="bucket-owner-full-control", expected_bucket_owner="111122223333"
+    )
+    assert delivery.acl == "bucket-owner-full-control"
+    assert delivery.expected_bucket_owner == "111122223333"
+    try:
+        UploadOptions(acl="public")
+        assert False, "Expected exception was not raised"
+    except ValueError:
+        pass
+
+    local_path = tmp_path / "a.csv"
+    local_path.write_text("a")
+    client = S3Uploader(
+        "us-east-1",
+        endpoint_url="http://127.0.0.1:1",
+        retry_policy=RetryPolicy(max_attempts=1),
+    )
+    results = client.upload_multiple_files(
+        "test-bucket",
+        [
+            (str(local_path), "a.csv"),
+            (str(local_path), "b.csv", UploadOptions(content_encoding="gzip")),
+        ],
+        options=options,
+    )
+    assert sorted(results.failed_keys) == [str(local_path)] * 2
+
+    try:
+        client.upload_multiple_files(
+            "test-bucket", [(str(local_path), "a.csv", "text/csv")]
+        )
+        assert False, "Expected exception was not raised"
+    except TypeError:
+        pass
+
+
+def test_server_side_encryption_options():
+    """Test that server-side encryption is validated and a KMS key implies SSE-KMS."""
+
+    options = UploadOptions(kms_key_id="arn:aws:kms:us-east-1:111122223333:key/abc")
+    assert options.sse == "aws:kms"
+    assert UploadOptions(sse="AES256").kms_key_id is None
+
+    for kwargs in ({"sse": "aes256"}, {"sse": "AES256", "kms_key_id": "abc"}):
+        try:
+            UploadOptions(**kwargs)
+            assert False, "Expected exception was not raised"
+        except ValueError:
+            pass
+
+    S3Uploader("us-east-1", sse="aws:kms", kms_key_id="abc")
+    try:
+        S3Uploader("us-east-1", sse="KMS")
+        assert False, "Expected exception was not raised"
+    except ValueError:
+        pass
+
+    results = Results(["a.txt"], [])
+    assert results.encryption == {}
+    assert results.checksums == {}
+
+
+def test_upload_etags_and_versions(tmp_path):
+    """Test that results carry the ETags and version IDs of uploads, none for failures."""
+
+    local_path = tmp_path / "a.txt"
+    local_path.write_text("a")
+    uploader = S3Uploader(
+        config=S3Config("us-east-1", endpoint_url="http://127.0.0.1:1"),
+        retry_policy=RetryPolicy(max_attempts=1),
+    )
+    results = uploader.upload_multiple_files("test-bucket", [(str(local_path), "a.txt")])
+    assert (results.etags, results.version_ids) == ({}, {})
+    assert (results[0].etag, results[0].version_id) == (None, None)
+    as_dict = results.to_dict()
+    assert (as_dict["etags"], as_dict["version_ids"]) == ({}, {})
+    assert pickle.loads(pickle.dumps(results)).to_dict() == as_dict
+
+    with pytest.raises(RobinzhonError):
+        uploader.upload_file("test-bucket", "a.txt", str(local_path), details=True)
+    assert Results(["a.txt"], []).etags == {}
+
+
+def test_iter_upload_multiple_files(tmp_path):
+    """Test that iterated upload batches yield items with their sources and keys."""
+
+    local_path = tmp_path / "a.txt"
+    local_path.write_text("a")
+    uploader = S3Uploader(
+        config=S3Config("us-east-1", endpoint_url="http://127.0.0.1:1"),
+        retry_policy=RetryPolicy(max_attempts=1),
+    )
+    batch = uploader.iter_upload_multiple_files(
+        "test-bucket", [(str(local_path), "a.txt"), (str(local_path), "b.txt")]
+    )
+    items = sorted(batch, key=lambda item: item.index)
+    assert [(item.source, item.destination) for item in items] == [
+        (str(local_path), "a.txt"),
+        (str(local_path), "b.txt"),
+    ]
+    assert len(batch.results.failed) == 2
+
+
+def test_checksum_options():
+    """Test that upload checksum algorithms and types are validated."""
+
+    options = UploadOptions(checksum_algorithm="SHA256", checksum_type="FULL_OBJECT")
+    assert options.checksum_algorithm == "SHA256"
+    assert options.checksum_type == "FULL_OBJECT"
+    assert UploadOptions().checksum_algorithm is None
+
+    for kwargs in (
+        {"checksum_algorithm": "md5"},
+        {"checksum_algorithm": "CRC32", "checksum_type": "PARTIAL"},
+        {"checksum_type": "COMPOSITE"},
+    ):
+        try:
+            UploadOptions(**kwargs)
+            assert False, "Expected exception was not raised"
+        except ValueError as e:
+            assert "checksum" in str(e)
+
+
+def test_client_encryption_options(tmp_path):
+    """Test that client-side encryption is validated and failures are reported."""
+
+    encryption = ClientEncryption("alias/backups", encryption_context={"team": "data"})
+    assert encryption.kms_key_id == "alias/backups"
+    assert encryption.encryption_context == {"team": "data"}
+    assert encryption.kms_endpoint_url is None
+    assert not encryption.require_encryption
+    assert "alias/backups" in repr(encryption)
+    required = pickle.loads(pickle.dumps(ClientEncryption(require_encryption=True)))
+    assert required.require_encryption
+
+    try:
+        ClientEncryption("abc", encryption_context={"aws:x-amz-cek-alg": "AES"})
+        assert False, "Expected exception was not raised"
+    except ValueError:
+        pass
+
+    # Decrypting reads the key off each object, encrypting needs one.
+    S3Downloader("us-east-1", client_encryption=ClientEncryption())
+    try:
+        S3Uploader("us-east-1", client_encryption=ClientEncryption())
+        assert False, "Expected exception was not raised"
+    except ValueError as e:
+        assert "kms_key_id" in str(e)
+
+    local_file = tmp_path / "secret.txt"
+    local_file.write_text("secret")
+    client = S3Uploader(
+        "us-east-1",
+        endpoint_url="http://127.0.0.1:1",
+        retry_policy=RetryPolicy(max_attempts=1),
+        client_encryption=ClientEncryption(
+            "abc", kms_endpoint_url="http://127.0.0.1:1"
+        ),
+    )
+    results = client.upload_multiple_files(
+        "test-bucket", [(str(local_file), "secret.txt")]
+    )
+    assert results.failed_keys == [str(local_file)]
+
+
+def test_archive_mode(tmp_path):
+    """Test that archive mode is validated and bundles fail with their files."""
+
+    mode = ArchiveMode("bundles/")
+    assert (mode.prefix, mode.format) == ("bundles/", "tar")
+    assert mode.max_file_size == 1024 * 1024
+    assert mode.bundle_size == 64 * 1024 * 1024
+    assert "bundles/" in repr(mode)
+    assert repr(pickle.loads(pickle.dumps(ArchiveMode("b/", format="tgz")))) == repr(
+        ArchiveMode("b/", format="tar.gz")
+    )
+
+    for kwargs in (
+        {"format": "rar"},
+        {"bundle_size": 0},
+        {"bundle_size": 6 * 1024**3},
+        {"max_file_size": 10, "bundle_size": 5},
+    ):
+        with pytest.raises(ValueError):
+            ArchiveMode("b/", **kwargs)
+
+    small = [tmp_path / f"{name}.txt" for name in "abc"]
+    for path in small:
+        path.write_text("tiny")
+    large = tmp_path / "large.bin"
+    large.write_bytes(b"x" * 64)
+    items = [(str(path), path.name) for path in small + [large]]
+    items.append((str(tmp_path / "missing.txt"), "missing.txt"))
+
+    client = S3Uploader(
+        "us-east-1",
+        endpoint_url="http://127.0.0.1:1",
+        retry_policy=RetryPolicy(max_attempts=1),
+    )
+    mode = ArchiveMode("b/", format="zip", max_file_size=8, bundle_size=8)
+    results = client.upload_multiple_files("test-bucket", items, archive_mode=mode)
+    assert sorted(results.failed_keys) == sorted(path for path, _ in items)
+    errors = {item.key: item.error_message for item in results.failed}
+    assert "b/bundle-00001.zip" in errors[str(small[0])]
+    assert "b/bundle-00002.zip" in errors[str(small[2])]
+    assert "large.bin" in errors[str(large)]
+    assert [item.name for item in results.in_input_order()] == [path for path, _ in items]
+
+    async def upload():
+        return await client.upload_multiple_files_async(
+            "test-bucket", items, archive_mode=mode
+        )
+
+    assert len(asyncio.run(upload()).failed) == len(items)
+
+    encrypted = S3Uploader(
+        "us-east-1",
+        endpoint_url="http://127.0.0.1:1",
+        client_encryption=ClientEncryption("abc"),
+    )
+    with pytest.raises(ValueError):
+        encrypted.upload_multiple_files("test-bucket", items, archive_mode=mode)
+
+    downloader = S3Downloader(
+        "us-east-1",
+        endpoint_url="http://127.0.0.1:1",
+        retry_policy=RetryPolicy(max_attempts=1),
+    )
+    with pytest.raises(RobinzhonError) as raised:
+        downloader.download_archived("test-bucket", "b/manifest.json", str(tmp_path / "out"))
+    assert "b/manifest.json" in str(raised.value)
+
+
+def test_transfer_session(tmp_path):
+    """Test that a session checkpoints its batch and resumes what isn't done."""
+
+    path = tmp_path / "session.json"
+    session = TransferSession(str(path))
+    assert session.kind is None
+    try:
+        session.resume(S3Uploader("us-east-1"))
+        assert False, "Expected exception was not raised"
+    except ValueError as e:
+        assert "no batch" in str(e)
+
+    local_path = tmp_path / "a.txt"
+    local_path.write_text("a")
+    client = S3Uploader(
+        "us-east-1",
+        endpoint_url="http://127.0.0.1:1",
+        retry_policy=RetryPolicy(max_attempts=1),
+    )
+    results = session.upload(client, "test-bucket", [(str(local_path), "a.txt")])
+    assert results.failed_keys == [str(local_path)]
+    assert session.failed == [(str(local_path), "a.txt")]
+    assert not session.is_complete()
+
+    reopened = TransferSession(str(path))
+    assert reopened.kind == "upload"
+    assert reopened.bucket_name == "test-bucket"
+    assert reopened.failed == [(str(local_path), "a.txt")]
+    try:
+        reopened.resume(S3Downloader("us-east-1"))
+        assert False, "Expected exception was not raised"
+    except ValueError as e:
+        assert "S3Uploader" in str(e)
+    assert reopened.resume(client).failed_keys == [str(local_path)]
+
+
+def test_transfer_manager(tmp_path):
+    """Test that queued jobs run in the background and report their results."""
+
+    try:
+        TransferManager(max_running_jobs=0)
+        assert False, "Expected exception was not raised"
+    except ValueError as e:
+        assert "max_running_jobs" in str(e)
+
+    local_path = tmp_path / "a.txt"
+    local_path.write_text("a")
+    client = S3Uploader(
+        "us-east-1",
+        endpoint_url="http://127.0.0.1:1",
+        retry_policy=RetryPolicy(max_attempts=1),
+    )
+    manager = TransferManager()
+    job = manager.submit(client, "test-bucket", [(str(local_path), "a.txt")])
+    assert job.id == 1
+    results = job.result(timeout=30)
+    assert job.done()
+    assert job.status() == "done"
+    assert results.failed_keys == [str(local_path)]
+    assert job.progress().files_failed == 1
+    assert not job.cancel()
+
+    jobs = [
+        manager.submit(client, "test-bucket", [(str(local_path), "b.txt", 1)]),
+        manager.submit(client, "test-bucket", [], priority=5),
+    ]
+    assert jobs[1].priority == 5
+    assert all(job.result(timeout=30) is not None for job in jobs)
+
+    # Enough items that some are still undispatched when the pause lands.
+    items = [(str(local_path), f"c{i}.txt") for i in range(1000)]
+    paused = manager.submit(client, "test-bucket", items)
+    assert paused.pause()
+    assert paused.paused
+    try:
+        paused.result(timeout=0.5)
+        assert False, "Expected exception was not raised"
+    except TimeoutError:
+        pass
+    assert paused.status() in ("queued", "paused")
+    assert paused.resume()
+    assert not paused.paused
+    assert set(paused.result(timeout=30).failed_keys) == {str(local_path)}
+    assert not paused.pause()
+    assert not paused.resume()
+
+    try:
+        manager.submit("not a client", "test-bucket", [])
+        assert False, "Expected exception was not raised"
+    except ValueError:
+        pass
+
+
+def test_pickle_clients(tmp_path):
+    """Test that clients and their settings pickle as their constructor arguments."""
+
+    config = S3Config(
+        "us-east-1",
+        endpoint_url="http://127.0.0.1:1",
+        force_path_style=True,
+        read_timeout=30,
+    )
+    restored = pickle.loads(pickle.dumps(config))
+    assert repr(restored) == repr(config)
+    assert restored.read_timeout == 30
+    role = S3Config("us-east-1", role_arn="arn:aws:iam::123456789012:role/ingest")
+    assert pickle.loads(pickle.dumps(role)).role_arn == role.role_arn
+
+    policy = pickle.loads(pickle.dumps(RetryPolicy(max_attempts=1, jitter=False)))
+    assert (policy.max_attempts, policy.jitter) == (1, False)
+    encryption = pickle.loads(
+        pickle.dumps(ClientEncryption("alias/key", encryption_context={"team": "data"}))
+    )
+    assert encryption.encryption_context == {"team": "data"}
+    limiter = pickle.loads(pickle.dumps(TransferLimiter(3)))
+    assert (limiter.max_in_flight, limiter.in_flight) == (3, 0)
+
+    local_path = tmp_path / "a.txt"
+    local_path.write_text("a")
+    client = pickle.loads(
+        pickle.dumps(
+            S3Uploader(
+                config=config,
+                retry_policy=RetryPolicy(max_attempts=1),
+                sse="AES256",
+                max_requests_per_sec=10,
+                limiter=TransferLimiter(2),
+            )
+        )
+    )
+    results = client.upload_multiple_files("test-bucket", [(str(local_path), "a.txt")])
+    assert results.failed_keys == [str(local_path)]
+
+
+def test_preserve_mtime(tmp_path):
+    """Test that preserve_mtime survives pickling and leaves failed items untouched."""
+
+    local_path = tmp_path / "a.txt"
+    local_path.write_text("a")
+    config = S3Config("us-east-1", endpoint_url="http://127.0.0.1:1")
+    uploader = S3Uploader(
+        config=config, retry_policy=RetryPolicy(max_attempts=1), preserve_mtime=True
+    )
+    downloader = S3Downloader(
+        config=config, retry_policy=RetryPolicy(max_attempts=1), preserve_mtime=True
+    )
+
+    for client in (uploader, pickle.loads(pickle.dumps(uploader))):
+        results = client.upload_multiple_files(
+            "test-bucket",
+            [(str(local_path), "a.txt"), (str(tmp_path / "missing.txt"), "missing.txt")],
+        )
+        errors = {item.key: item.error_message for item in results.failed}
+        assert sorted(errors) == sorted([str(local_path), str(tmp_path / "missing.txt")])
+        assert "Failed to read file" in errors[str(tmp_path / "missing.txt")]
+
+    for client in (downloader, pickle.loads(pickle.dumps(downloader))):
+        results = client.download_multiple_files_with_paths(
+            "test-bucket", [("a.txt", str(tmp_path / "b.txt"))]
+        )
+        assert results.failed_keys == ["a.txt"]
+        assert not (tmp_path / "b.txt").exists()
+
+
+def test_permissions_and_symlinks(tmp_path):
+    """Test that symlink policies are validated, pickled, and skip links when asked."""
+
+    target = tmp_path / "a.txt"
+    target.write_text("a")
+    link = tmp_path / "link.txt"
+    link.symlink_to(target.name)
+    config = S3Config("us-east-1", endpoint_url="http://127.0.0.1:1")
+
+    for cls in (S3Uploader, S3Downloader):
+        with pytest.raises(ValueError):
+            cls(config=config, symlinks="copy")
+
+    uploader = S3Uploader(
+        config=config,
+        retry_policy=RetryPolicy(max_attempts=1),
+        preserve_permissions=True,
+        symlinks="skip",
+    )
+    for client in (uploader, pickle.loads(pickle.dumps(uploader))):
+        results = client.upload_multiple_files(
+            "test-bucket", [(str(target), "a.txt"), (str(link), "link.txt")]
+        )
+        assert results.failed_keys == [str(target)]
+        assert results.skipped == [str(link)]
+
+    downloader = S3Downloader(
+        config=config,
+        retry_policy=RetryPolicy(max_attempts=1),
+        preserve_permissions=True,
+        symlinks="preserve",
+    )
+    restored = pickle.loads(pickle.dumps(downloader))
+    results = restored.download_multiple_files_with_paths(
+        "test-bucket", [("link.txt", str(tmp_path / "b.txt"))]
+    )
+    assert results.failed_keys == ["link.txt"]
+    assert not (tmp_path / "b.txt").exists()
+
+
+def test_preserved_symlinks_are_not_written_through(tmp_path):
+    """Test that a batch restoring links refuses to download through a link below its directory."""
+
+    outside = tmp_path / "outside"
+    outside.mkdir()
+    base_directory = tmp_path / "restored"
+    base_directory.mkdir()
+    (base_directory / "link").symlink_to(outside)
+
+    downloader = S3Downloader(
+        config=S3Config("us-east-1", endpoint_url="http://127.0.0.1:1"),
+        retry_policy=RetryPolicy(max_attempts=1),
+        symlinks="preserve",
+    )
+    results = downloader.download_multiple_files(
+        "test-bucket",
+        ["link/cron.d/x"],
+        str(base_directory),
+        preserve_structure=True,
+    )
+
+    assert results.failed_keys == ["link/cron.d/x"]
+    assert "through the symlink" in results.failed[0].error_message
+    assert list(outside.iterdir()) == []
+
+
+def test_concurrent_calls_from_threads(tmp_path):
+    """Test that one client serves batches from many threads at once, reinitialized midway."""
+
+    local_path = tmp_path / "a.txt"
+    local_path.write_text("a")
+    config = S3Config("us-east-1", endpoint_url="http://127.0.0.1:1")
+    uploader = S3Uploader(config=config, retry_policy=RetryPolicy(max_attempts=1))
+    downloader = S3Downloader(config=config, retry_policy=RetryPolicy(max_attempts=1))
+
+    def upload(i):
+        if i % 8 == 0:
+            uploader.reinitialize()
+        items = [(str(local_path), f"{i}/{n}.txt") for n in range(4)]
+        return uploader.upload_multiple_files("test-bucket", items)
+
+    def download(i):
+        if i % 8 == 0:
+            downloader.reinitialize()
+        items = [(f"{i}/{n}.txt", str(tmp_path / f"{i}-{n}.txt")) for n in range(4)]
+        return downloader.download_multiple_files_with_paths("test-bucket", items)
+
+    with ThreadPoolExecutor(max_workers=16) as pool:
+        uploads = list(pool.map(upload, range(32)))
+        downloads = list(pool.map(download, range(32)))
+
+    assert all(len(results.failed) == 4 for results in uploads + downloads)
+    assert not any((tmp_path / f"{i}-0.txt").exists() for i in range(32))
+
+
+def test_s3_client(tmp_path):
+    """Test that S3Client runs both directions and its object operations on one client."""
+
+    client = S3Client(
+        endpoint_url="http://127.0.0.1:1",
+        region_name="us-east-1",
+        retry_policy=RetryPolicy(max_attempts=1),
+    )
+    assert isinstance(client.downloader, S3Downloader)
+    assert isinstance(client.uploader, S3Uploader)
+
+    local_path = tmp_path / "a.txt"
+    local_path.write_text("a")
+    uploads = client.upload_multiple_files("test-bucket", [(str(local_path), "a.txt")])
+    assert uploads.failed_keys == [str(local_path)]
+    downloads = client.download_multiple_files_with_paths(
+        "test-bucket", [("b.txt", str(tmp_path / "b.txt"))]
+    )
+    assert downloads.failed_keys == ["b.txt"]
+    assert client.retry_failed(uploads).failed_keys == [str(local_path)]
+
+    with pytest.raises(RobinzhonError) as raised:
+        client.delete_object("test-bucket", "a.txt")
+    assert raised.value.request_id is None
+    assert raised.value.extended_request_id is None
+    with pytest.raises(RobinzhonError):
+        client.copy_object("test-bucket", "a b.txt", "other-bucket", "a.txt")
+    assert client.delete_objects("test-bucket", ["a.txt"]) == []
+
+    with pytest.raises(ValueError):
+        client.presign("test-bucket", "a.txt", expires_in=0)
+    with pytest.raises(ValueError):
+        client.presign("test-bucket", "a.txt", expires_in=8 * 24 * 3600)
+    with pytest.raises(ValueError):
+        client.presign("test-bucket", "a.txt", method="delete")
+
+    report = client.preflight("test-bucket")
+    assert not report
+    assert report.bucket_exists is None and report.can_write is None
+    assert len(report.problems) == 1
+    assert "test-bucket" in repr(report)
+
+    with pytest.raises(AttributeError):
+        client.no_such_method
+    client.reinitialize()
+    assert client.exists is not None
+
+
+def test_endpoint_toggles(monkeypatch):
+    """Test that the dual-stack, FIPS and acceleration toggles pick the endpoint."""
+
+    monkeypatch.setenv("AWS_ACCESS_KEY_ID", "test")
+    monkeypatch.setenv("AWS_SECRET_ACCESS_KEY", "test")
+
+    def host(**kwargs):
+        client = S3Client(config=S3Config("us-east-1", **kwargs))
+        return urllib.parse.urlsplit(client.presign("test-bucket", "a.txt")).netloc
+
+    assert host() == "test-bucket.s3.us-east-1.amazonaws.com"
+    assert host(use_dualstack_endpoint=True) == (
+        "test-bucket.s3.dualstack.us-east-1.amazonaws.com"
+    )
+    assert host(use_fips_endpoint=True) == "test-bucket.s3-fips.us-east-1.amazonaws.com"
+    assert host(use_accelerate_endpoint=True) == "test-bucket.s3-accelerate.amazonaws.com"
+
+
+def test_upload_dry_run(tmp_path):
+    """Test planning uploads and deletions without sending any request."""
+
+    local_path = tmp_path / "a.txt"
+    local_path.write_bytes(b"hello")
+    missing_path = tmp_path / "missing.txt"
+    uploader = S3Uploader(
+        "us-east-1",
+        endpoint_url="http://127.0.0.1:1",
+        retry_policy=RetryPolicy(max_attempts=1),
+    )
+
+    plan = uploader.upload_multiple_files(
+        "test-bucket",
+        [(str(local_path), "a.txt"), (str(missing_path), "missing.txt")],
+        dry_run=True,
+    )
+    assert isinstance(plan, TransferPlan)
+    assert plan.kind == "upload"
+    assert plan.transfers == [(str(local_path), "a.txt")]
+    assert plan.total_bytes == 5
+    assert [item.key for item in plan.failed] == [str(missing_path)]
+    assert plan.deletions == [] and plan.skipped == []
+    assert len(plan) == 1
+    assert "transfers=1" in repr(plan)
+
+    plan = uploader.upload_multiple_uris(
+        [(str(local_path), "s3://other-bucket/a.txt")], dry_run=True
+    )
+    assert plan.transfers == [(str(local_path), "s3://other-bucket/a.txt")]
+    manifest = tmp_path / "manifest.ndjson"
+    manifest.write_text('{"key": "a.txt", "local_path": "%s"}\n' % local_path)
+    plan = uploader.upload_from_manifest(
+        str(manifest), bucket_name="test-bucket", dry_run=True
+    )
+    assert plan.transfers == [(str(local_path), "s3://test-bucket/a.txt")]
+    async def plan_async():
+        return await uploader.upload_multiple_files_async(
+            "test-bucket", [(str(local_path), "a.txt")], dry_run=True
+        )
+
+    plan = asyncio.run(plan_async())
+    assert plan.transfers == [(str(local_path), "a.txt")] and plan.total_bytes == 5
+
+    client = S3Client("us-east-1", endpoint_url="http://127.0.0.1:1")
+    plan = client.delete_objects("test-bucket", ["a.txt", "b.txt"], dry_run=True)
+    assert plan.kind == "delete"
+    assert plan.deletions == ["a.txt", "b.txt"]
+    assert plan.transfers == [] and plan.total_bytes == 0
+    assert len(plan) == 2
+
+
+def test_download_dry_run_of_keys(tmp_path):
+    """Test planning key batches, which look objects up but write nothing."""
+
+    base_directory = tmp_path / "downloads"
+    downloader = S3Downloader(
+        "us-east-1",
+        endpoint_url="http://127.0.0.1:1",
+        retry_policy=RetryPolicy(max_attempts=1),
+    )
+
+    plan = downloader.download_multiple_files(
+        "test-bucket",
+        ["a/data.csv", "b/data.csv", "../../etc/cron.d/job"],
+        str(base_directory),
+        dry_run=True,
+    )
+    assert isinstance(plan, TransferPlan)
+    assert plan.kind == "download"
+    assert plan.transfers == [] and plan.total_bytes == 0
+    failed = {item.key: item.error_message for item in plan.failed}
+    assert "would overwrite" in failed["b/data.csv"]
+    assert set(failed) == {"a/data.csv", "b/data.csv", "../../etc/cron.d/job"}
+    assert not base_directory.exists()
+
+    plan = downloader.download_multiple_objects(
+        [("bucket-a", "a.csv", str(tmp_path / "a.csv"))], dry_run=True
+    )
+    assert [item.key for item in plan.failed] == ["s3://bucket-a/a.csv"]
+    async def plan_async():
+        return await downloader.download_multiple_uris_async(
+            ["s3://bucket-a/a.csv", "not-a-uri"], str(base_directory), dry_run=True
+        )
+
+    plan = asyncio.run(plan_async())
+    assert len(plan.failed) == 2
+    assert not base_directory.exists()
+
+
+def test_upload_from_manifest(tmp_path):
+    """Test uploading the files a manifest lists, and rejecting bad manifests."""
+
+    local_path = tmp_path / "a.txt"
+    local_path.write_bytes(b"hello")
+    missing_path = tmp_path / "missing.txt"
+    manifest = tmp_path / "manifest.ndjson"
+    manifest.write_text(
+        '{"bucket": "bucket-a", "key": "a.txt", "local_path": "%s"}\n\n'
+        '{"key": "missing.txt", "local_path": "%s"}\n' % (local_path, missing_path)
+    )
+    uploader = S3Uploader(
+        "us-east-1",
+        endpoint_url="http://127.0.0.1:1",
+        retry_policy=RetryPolicy(max_attempts=1),
+    )
+
+    results = uploader.upload_from_manifest(str(manifest), bucket_name="bucket-b")
+    assert sorted(results.failed_keys) == sorted([str(local_path), str(missing_path)])
+
+    csv_manifest = tmp_path / "manifest.txt"
+    csv_manifest.write_text("key,path\na.txt,%s\n" % local_path)
+    with pytest.raises(ValueError, match="header"):
+        uploader.upload_from_manifest(str(csv_manifest), format="csv")
+    with pytest.raises(ValueError, match="line 1"):
+        uploader.upload_from_manifest(str(manifest), format="csv")
+
+
+def test_audit_log(tmp_path):
+    """Test that file transfers are appended to a hash-chained audit log."""
+
+    audit_path = tmp_path / "audit.jsonl"
+    local_path = tmp_path / "a.txt"
+    local_path.write_bytes(b"hello")
+    client = S3Client(
+        "us-east-1",
+        endpoint_url="http://127.0.0.1:1",
+        retry_policy=RetryPolicy(max_attempts=1),
+        audit_log=str(audit_path),
+    )
+
+    client.upload_multiple_files("test-bucket", [(str(local_path), "a.txt")])
+    client.download_multiple_files_with_paths(
+        "test-bucket", [("b.txt", str(tmp_path / "b.txt"))]
+    )
+    entries = [json.loads(line) for line in audit_path.read_text().splitlines()]
+    assert [(entry["operation"], entry["key"]) for entry in entries] == [
+        ("upload", "a.txt"),
+        ("download", "b.txt"),
+    ]
+    assert all(entry["result"] == "failed" and entry["error"] for entry in entries)
+    assert entries[0]["prev"] == "0" * 64
+    assert entries[1]["prev"] == hashlib.sha256(
+        audit_path.read_text().splitlines()[0].encode()
+    ).hexdigest()
+    assert verify_audit_log(str(audit_path)) == 2
+
+    # Another client of the same file continues its chain.
+    restored = pickle.loads(pickle.dumps(client.downloader))
+    restored.download_multiple_files_with_paths(
+        "test-bucket", [("c.txt", str(tmp_path / "c.txt"))]
+    )
+    assert verify_audit_log(str(audit_path)) == 3
+
+    lines = audit_path.read_text().splitlines()
+    lines[1] = lines[1].replace("b.txt", "x.txt")
+    audit_path.write_text("\n".join(lines) + "\n")
+    with pytest.raises(ValueError, match="line 3"):
+        verify_audit_log(str(audit_path))
+    with pytest.raises(RobinzhonError):
+        verify_audit_log(str(tmp_path / "missing.jsonl"))
+    with pytest.raises(RobinzhonError):
+        S3Uploader("us-east-1", audit_log=str(tmp_path / "missing" / "audit.jsonl"))
+
+
+def test_key_batches_from_generators():
+    """Test that key batches read generators lazily and raise what they raise."""
+
+    client = S3Client(
+        "us-east-1",
+        endpoint_url="http://127.0.0.1:1",
+        retry_policy=RetryPolicy(max_attempts=1),
+    )
+
+    progress = TransferProgress()
+    keys = (f"{name}.txt" for name in "ab")
+    assert client.head_multiple("test-bucket", keys, progress=progress) == {}
+    assert progress.files_total == 2
+    assert client.download_multiple_bytes("test-bucket", iter(["a.txt"])) == {}
+    assert client.delete_objects("test-bucket", iter(["a.txt"])) == []
+
+    plan = client.delete_objects(
+        "test-bucket", (f"{name}.txt" for name in "ab"), dry_run=True
+    )
+    assert plan.deletions == ["a.txt", "b.txt"]
+
+    def broken_keys():
+        yield "a.txt"
+        raise RuntimeError("no more keys")
+
+    for batch in [client.head_multiple, client.delete_objects]:
+        with pytest.raises(RuntimeError, match="no more keys"):
+            batch("test-bucket", broken_keys())
+    with pytest.raises(TypeError, match="iterable"):
+        client.restore_objects("test-bucket", "a.txt")